@@ -25,14 +25,41 @@ pub fn read_bool_env_var(v: &str) -> bool {
     val.parse::<bool>() == Ok(true) || val.parse::<usize>() == Ok(1)
 }
 
-pub static MOVE_HOME: Lazy<String> = Lazy::new(|| {
-    std::env::var("MOVE_HOME").unwrap_or_else(|_| {
-        format!(
-            "{}/.move",
-            dirs_next::home_dir()
-                .expect("user's home directory not found")
-                .to_str()
-                .unwrap()
-        )
-    })
-});
+/// The environment variable setting the directory where Move keeps downloaded dependencies and
+/// credentials. Tests set it so that nothing is written to the home directory of the user.
+pub const MOVE_HOME_ENV_VAR: &str = "MOVE_HOME";
+
+/// The directory set by `MOVE_HOME_ENV_VAR`, or `~/.move` if it is unset
+pub fn move_home() -> anyhow::Result<String> {
+    if let Ok(move_home) = std::env::var(MOVE_HOME_ENV_VAR) {
+        return Ok(move_home);
+    }
+    match dirs_next::home_dir() {
+        Some(home_dir) => Ok(format!("{}/.move", home_dir.to_string_lossy())),
+        None => anyhow::bail!(
+            "User's home directory not found. Set {} to the directory Move should use instead",
+            MOVE_HOME_ENV_VAR
+        ),
+    }
+}
+
+pub static MOVE_HOME: Lazy<String> =
+    Lazy::new(|| move_home().unwrap_or_else(|err| panic!("{}", err)));
+
+/// The error for a failure to `operation` the file or directory at `path` inside `MOVE_HOME`. Since
+/// `MOVE_HOME` may be read-only, e.g. in hermetic build environments, it suggests pointing
+/// `MOVE_HOME` to a writable directory.
+pub fn move_home_write_error(
+    operation: &str,
+    path: &std::path::Path,
+    err: std::io::Error,
+) -> anyhow::Error {
+    anyhow::anyhow!(
+        "Unable to {} '{}': {}. If {} is read-only, set {} to a writable directory",
+        operation,
+        path.display(),
+        err,
+        MOVE_HOME_ENV_VAR,
+        MOVE_HOME_ENV_VAR
+    )
+}
@@ -0,0 +1,158 @@
+// Copyright (c) The Move Contributors
+// SPDX-License-Identifier: Apache-2.0
+
+//! Experiments gate the features still in development, which may change or go away, so that they
+//! can ship without affecting the default behavior. They are enabled by name, with the global
+//! `--experiments` option of the CLI or the `MOVE_EXPERIMENTS` environment variable. Every
+//! experiment is declared in `EXPERIMENTS`, and naming an unknown one is an error.
+
+use anyhow::{bail, Context, Result};
+use once_cell::sync::Lazy;
+use std::{collections::BTreeSet, fmt, sync::Mutex};
+
+/// The environment variable enabling experiments when `--experiments` is not given, as a
+/// comma-separated list of names
+pub const MOVE_EXPERIMENTS_ENV_VAR: &str = "MOVE_EXPERIMENTS";
+
+/// `move experimental read-write-set`, the read/write set analysis of a function
+pub const READ_WRITE_SET: &str = "read-write-set";
+
+/// Every experiment, sorted by name
+pub const EXPERIMENTS: &[Experiment] = &[Experiment {
+    name: READ_WRITE_SET,
+    description: "`move experimental read-write-set`, the read/write set analysis of a function",
+    status: ExperimentStatus::Unstable,
+}];
+
+static ENABLED_EXPERIMENTS: Lazy<Mutex<BTreeSet<&'static str>>> =
+    Lazy::new(|| Mutex::new(BTreeSet::new()));
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ExperimentStatus {
+    /// Off unless enabled, and may change in any release
+    Unstable,
+    /// Part of the default behavior. The name is still accepted, so that scripts enabling the
+    /// experiment keep working.
+    Stabilized,
+}
+
+impl fmt::Display for ExperimentStatus {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Unstable => write!(f, "unstable"),
+            Self::Stabilized => write!(f, "stabilized"),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Experiment {
+    pub name: &'static str,
+    pub description: &'static str,
+    pub status: ExperimentStatus,
+}
+
+/// The experiments named in the comma-separated list `names`. Fails on unknown names.
+pub fn parse_experiments(names: &str) -> Result<Vec<&'static Experiment>> {
+    let mut experiments = vec![];
+    for name in names
+        .split(',')
+        .map(str::trim)
+        .filter(|name| !name.is_empty())
+    {
+        match EXPERIMENTS
+            .iter()
+            .find(|experiment| experiment.name == name)
+        {
+            Some(experiment) => experiments.push(experiment),
+            None => bail!(
+                "Unknown experiment '{}'. The available experiments are: {}",
+                name,
+                EXPERIMENTS
+                    .iter()
+                    .map(|experiment| experiment.name)
+                    .collect::<Vec<_>>()
+                    .join(", ")
+            ),
+        }
+    }
+    Ok(experiments)
+}
+
+/// Enable the experiments named in the comma-separated list `names`, or in `MOVE_EXPERIMENTS` if
+/// `names` is `None`. Fails on unknown names, without enabling any experiment.
+pub fn enable_experiments(names: Option<&str>) -> Result<()> {
+    let experiments = match names {
+        Some(names) => parse_experiments(names)?,
+        None => parse_experiments(&std::env::var(MOVE_EXPERIMENTS_ENV_VAR).unwrap_or_default())
+            .with_context(|| format!("Invalid {}", MOVE_EXPERIMENTS_ENV_VAR))?,
+    };
+    ENABLED_EXPERIMENTS
+        .lock()
+        .unwrap()
+        .extend(experiments.into_iter().map(|experiment| experiment.name));
+    Ok(())
+}
+
+/// The experiments enabled by name, sorted by name
+pub fn enabled_experiments() -> Vec<&'static str> {
+    ENABLED_EXPERIMENTS
+        .lock()
+        .unwrap()
+        .iter()
+        .copied()
+        .collect()
+}
+
+/// Whether the experiment `name` is enabled, or stabilized. Panics if no experiment is declared
+/// with this name, as gated code must only name declared experiments.
+pub fn is_enabled(name: &str) -> bool {
+    let experiment = EXPERIMENTS
+        .iter()
+        .find(|experiment| experiment.name == name)
+        .unwrap_or_else(|| panic!("Experiment '{}' is not declared", name));
+    experiment.status == ExperimentStatus::Stabilized
+        || ENABLED_EXPERIMENTS.lock().unwrap().contains(name)
+}
+
+/// Fail unless the experiment `name` is enabled, explaining how to enable it
+pub fn check_enabled(name: &str) -> Result<()> {
+    if !is_enabled(name) {
+        bail!(
+            "This feature is experimental: enable it with `--experiments {}`, or by setting {}={}",
+            name,
+            MOVE_EXPERIMENTS_ENV_VAR,
+            name
+        )
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn experiments_are_sorted_by_name() {
+        let names: Vec<_> = EXPERIMENTS
+            .iter()
+            .map(|experiment| experiment.name)
+            .collect();
+        let mut sorted = names.clone();
+        sorted.sort_unstable();
+        sorted.dedup();
+        assert_eq!(names, sorted);
+    }
+
+    #[test]
+    fn parse_experiments_rejects_unknown_names() {
+        let experiments = parse_experiments(" read-write-set, ,read-write-set").unwrap();
+        assert_eq!(experiments, vec![&EXPERIMENTS[0], &EXPERIMENTS[0]]);
+        assert!(parse_experiments("").unwrap().is_empty());
+        let err = parse_experiments("read-write-set,time-travel").unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "Unknown experiment 'time-travel'. The available experiments are: read-write-set"
+        );
+    }
+}
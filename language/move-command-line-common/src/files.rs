@@ -5,7 +5,12 @@
 use anyhow::{anyhow, bail, *};
 use serde::{Deserialize, Serialize};
 use sha2::Digest;
-use std::{collections::BTreeMap, convert::TryInto, path::Path};
+use std::{
+    collections::BTreeMap,
+    convert::TryInto,
+    fmt,
+    path::{Path, PathBuf},
+};
 
 /// Result of sha256 hash of a file's contents.
 #[derive(Clone, Copy, Eq, PartialEq, Ord, PartialOrd, Hash, Serialize, Deserialize)]
@@ -118,6 +123,97 @@ pub fn path_to_string(path: &Path) -> anyhow::Result<String> {
     }
 }
 
+/// The byte order mark some editors, notably on Windows, write at the start of UTF-8 files
+pub const UTF8_BOM: char = '\u{feff}';
+
+/// The error for a text file that is not valid UTF-8, e.g. because it is corrupted
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct InvalidUtf8File {
+    pub path: PathBuf,
+    /// The offset in the file of the first byte of the first invalid sequence
+    pub offset: usize,
+}
+
+impl fmt::Display for InvalidUtf8File {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "'{}' is not valid UTF-8: invalid byte sequence at byte offset {}",
+            self.path.display(),
+            self.offset
+        )
+    }
+}
+
+impl std::error::Error for InvalidUtf8File {}
+
+/// Read the text file at `path`, without the byte order mark it may start with. Fails with an
+/// `InvalidUtf8File` error if the file is not valid UTF-8.
+pub fn read_utf8_file(path: &Path) -> anyhow::Result<String> {
+    let bytes =
+        std::fs::read(path).with_context(|| format!("Unable to read '{}'", path.display()))?;
+    Ok(utf8_file_contents(path, bytes)?)
+}
+
+/// The text held by `bytes`, read from the file at `path`, without the byte order mark it may
+/// start with
+pub fn utf8_file_contents(path: &Path, bytes: Vec<u8>) -> Result<String, InvalidUtf8File> {
+    String::from_utf8(bytes)
+        .map(|contents| strip_utf8_bom(&contents).to_string())
+        .map_err(|err| InvalidUtf8File {
+            path: path.to_path_buf(),
+            offset: err.utf8_error().valid_up_to(),
+        })
+}
+
+/// `contents` without the byte order mark it may start with
+pub fn strip_utf8_bom(contents: &str) -> &str {
+    contents.strip_prefix(UTF8_BOM).unwrap_or(contents)
+}
+
+/// A `--remap-path-prefix <from>=<to>` mapping: paths starting with `from` are shown as starting
+/// with `to` instead, e.g. so that artifacts do not record the local directory of a package
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord)]
+pub struct PathPrefixRemap {
+    pub from: PathBuf,
+    pub to: PathBuf,
+}
+
+impl PathPrefixRemap {
+    /// Parse a `<from>=<to>` mapping. The first `=` separates the prefixes.
+    pub fn parse(s: &str) -> anyhow::Result<Self> {
+        match s.split_once('=') {
+            Some((from, to)) if !from.is_empty() => Ok(Self {
+                from: PathBuf::from(from),
+                to: PathBuf::from(to),
+            }),
+            _ => bail!(
+                "Invalid path prefix remapping '{}', expected <from>=<to>",
+                s
+            ),
+        }
+    }
+}
+
+impl fmt::Display for PathPrefixRemap {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}={}", self.from.display(), self.to.display())
+    }
+}
+
+/// `path` with its prefix remapped by the last of `remaps` whose `from` it starts with, or `path`
+/// itself if there is none
+pub fn remap_path_prefix(remaps: &[PathPrefixRemap], path: &Path) -> PathBuf {
+    remaps
+        .iter()
+        .rev()
+        .find_map(|remap| {
+            let rest = path.strip_prefix(&remap.from).ok()?;
+            Some(remap.to.join(rest))
+        })
+        .unwrap_or_else(|| path.to_path_buf())
+}
+
 pub fn extension_equals(path: &Path, target_ext: &str) -> bool {
     match path.extension().and_then(|s| s.to_str()) {
         Some(extension) => extension == target_ext,
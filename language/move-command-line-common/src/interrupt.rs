@@ -0,0 +1,91 @@
+// Copyright (c) The Move Contributors
+// SPDX-License-Identifier: Apache-2.0
+
+//! Bookkeeping for work that has to be undone if the process is interrupted (e.g., by Ctrl-C)
+//! before the work completes, such as a half-downloaded dependency or a partially written
+//! changeset. The interrupt handler itself is installed by the binary, which calls
+//! `run_pending_cleanups` before exiting with `INTERRUPTED_EXIT_CODE`.
+
+use once_cell::sync::Lazy;
+use std::{
+    collections::BTreeMap,
+    sync::{
+        atomic::{AtomicBool, AtomicU64, Ordering},
+        Mutex,
+    },
+};
+
+/// Exit code of a process interrupted by SIGINT, following the shell convention of 128 + signal
+pub const INTERRUPTED_EXIT_CODE: i32 = 130;
+
+type Cleanup = Box<dyn FnOnce() + Send>;
+
+static PENDING_CLEANUPS: Lazy<Mutex<BTreeMap<u64, Cleanup>>> =
+    Lazy::new(|| Mutex::new(BTreeMap::new()));
+static NEXT_CLEANUP_ID: AtomicU64 = AtomicU64::new(0);
+static INTERRUPTED: AtomicBool = AtomicBool::new(false);
+/// Held while the cleanup actions run and while `run_uninterrupted` runs its work, so that the
+/// actions never run in the middle of the work
+static CLEANUP_LOCK: Lazy<Mutex<()>> = Lazy::new(|| Mutex::new(()));
+
+/// Keeps a cleanup action registered with `on_interrupt`. Dropping the guard, once the work it
+/// protects has completed, unregisters the action without running it.
+#[must_use]
+pub struct CleanupGuard(u64);
+
+impl Drop for CleanupGuard {
+    fn drop(&mut self) {
+        PENDING_CLEANUPS.lock().unwrap().remove(&self.0);
+    }
+}
+
+/// Register `cleanup` to be run if the process is interrupted before the returned guard is dropped.
+pub fn on_interrupt(cleanup: impl FnOnce() + Send + 'static) -> CleanupGuard {
+    let id = NEXT_CLEANUP_ID.fetch_add(1, Ordering::SeqCst);
+    PENDING_CLEANUPS
+        .lock()
+        .unwrap()
+        .insert(id, Box::new(cleanup));
+    CleanupGuard(id)
+}
+
+/// Mark the process as interrupted and run the cleanup actions of all work in progress, most
+/// recently registered first.
+pub fn run_pending_cleanups() {
+    INTERRUPTED.store(true, Ordering::SeqCst);
+    let _lock = CLEANUP_LOCK.lock().unwrap();
+    let cleanups = std::mem::take(&mut *PENDING_CLEANUPS.lock().unwrap());
+    for (_, cleanup) in cleanups.into_iter().rev() {
+        cleanup()
+    }
+}
+
+/// Returns `true` once `run_pending_cleanups` has been called
+pub fn is_interrupted() -> bool {
+    INTERRUPTED.load(Ordering::SeqCst)
+}
+
+/// Run `work`, a step of the work protected by a cleanup action, such as writing one file, unless
+/// the process is being interrupted. The cleanup actions do not run while `work` runs, so they
+/// undo each step either entirely or not at all. If the process is being interrupted, the calling
+/// thread blocks until the interrupt handler exits the process instead.
+pub fn run_uninterrupted<R>(work: impl FnOnce() -> R) -> R {
+    loop {
+        {
+            let _lock = CLEANUP_LOCK.lock().unwrap();
+            if !is_interrupted() {
+                return work();
+            }
+        }
+        wait_if_interrupted();
+    }
+}
+
+/// If the process is being interrupted, block the calling thread until the interrupt handler
+/// exits the process. Used by work that fails because a cleanup action cancelled it, so that the
+/// failure is not reported in place of the interruption.
+pub fn wait_if_interrupted() {
+    while is_interrupted() {
+        std::thread::park();
+    }
+}
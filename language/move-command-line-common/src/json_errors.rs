@@ -0,0 +1,82 @@
+// Copyright (c) The Move Contributors
+// SPDX-License-Identifier: Apache-2.0
+
+//! The `--json-errors-file` side channel. Warnings and errors are printed to stderr, so that stdout
+//! only holds the output of the command, e.g. the JSON printed with `--format json`. Once
+//! `set_json_errors_file` is called, they are also appended to a file as JSON lines, for tools that
+//! want to parse both.
+
+use anyhow::{Context, Result};
+use once_cell::sync::Lazy;
+use serde::Serialize;
+use std::{fmt, fs::File, io::Write, path::Path, sync::Mutex};
+
+static JSON_ERRORS_FILE: Lazy<Mutex<Option<File>>> = Lazy::new(|| Mutex::new(None));
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Level {
+    Warning,
+    Error,
+}
+
+/// A line of the JSON errors file
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct JsonDiagnostic {
+    pub level: Level,
+    /// Code identifying the kind of compiler diagnostic, e.g. `W09002`
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub code: Option<String>,
+    pub message: String,
+    /// Source file a compiler diagnostic points into, with its line and column starting from 1
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub file: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub line: Option<usize>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub column: Option<usize>,
+}
+
+impl JsonDiagnostic {
+    /// A diagnostic that does not point into a source file
+    pub fn new(level: Level, message: impl fmt::Display) -> Self {
+        Self {
+            level,
+            code: None,
+            message: message.to_string(),
+            file: None,
+            line: None,
+            column: None,
+        }
+    }
+}
+
+/// Write the diagnostics recorded from now on to a new file at `path`, replacing any file there
+pub fn set_json_errors_file(path: &Path) -> Result<()> {
+    let file = File::create(path)
+        .with_context(|| format!("Unable to create the JSON errors file '{}'", path.display()))?;
+    *JSON_ERRORS_FILE.lock().unwrap() = Some(file);
+    Ok(())
+}
+
+/// Whether diagnostics are written to a JSON errors file
+pub fn is_enabled() -> bool {
+    JSON_ERRORS_FILE.lock().unwrap().is_some()
+}
+
+/// Append `diagnostic` to the JSON errors file, if one is set. Failing to write it is not an error
+/// of the command, so it is only reported on stderr.
+pub fn record(diagnostic: &JsonDiagnostic) {
+    if let Some(file) = JSON_ERRORS_FILE.lock().unwrap().as_mut() {
+        let line = serde_json::to_string(diagnostic).expect("diagnostics serialize to JSON");
+        if let Err(err) = writeln!(file, "{}", line) {
+            eprintln!("Unable to write to the JSON errors file: {}", err);
+        }
+    }
+}
+
+/// Print `message` to stderr as a warning, and record it in the JSON errors file
+pub fn warning(message: impl fmt::Display) {
+    eprintln!("Warning: {}", message);
+    record(&JsonDiagnostic::new(Level::Warning, message));
+}
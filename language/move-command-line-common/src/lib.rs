@@ -7,9 +7,13 @@
 pub mod address;
 pub mod character_sets;
 pub mod env;
+pub mod experiments;
 pub mod files;
+pub mod interrupt;
+pub mod json_errors;
 pub mod movey_constants;
 pub mod parser;
 pub mod testing;
 pub mod types;
+pub mod value_rendering;
 pub mod values;
@@ -0,0 +1,137 @@
+// Copyright (c) The Move Contributors
+// SPDX-License-Identifier: Apache-2.0
+
+//! How decoded Move values are printed in diagnostics, such as the storage state of a failing test
+//! or the resources changed by a transaction. Each value is cut after a budget of bytes, set with
+//! the global `--max-value-render-bytes` option of the CLI, so that a large vector does not flood
+//! the terminal or CI logs. With `--redact-values`, values are replaced with their types, for logs
+//! to be shared without the data they hold.
+
+use once_cell::sync::Lazy;
+use std::{fmt, sync::Mutex};
+
+/// The default budget of a rendered value, in bytes
+pub const DEFAULT_MAX_VALUE_RENDER_BYTES: usize = 4096;
+
+static VALUE_RENDERING: Lazy<Mutex<ValueRendering>> =
+    Lazy::new(|| Mutex::new(ValueRendering::default()));
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ValueRendering {
+    /// The most bytes of a value to print
+    pub max_bytes: usize,
+    /// Print the type of a value instead of the value
+    pub redact: bool,
+}
+
+impl Default for ValueRendering {
+    fn default() -> Self {
+        Self {
+            max_bytes: DEFAULT_MAX_VALUE_RENDER_BYTES,
+            redact: false,
+        }
+    }
+}
+
+/// A value as printed in a diagnostic
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RenderedValue {
+    pub text: String,
+    /// Whether the value was cut to fit the budget
+    pub truncated: bool,
+}
+
+impl fmt::Display for RenderedValue {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.text)
+    }
+}
+
+/// Render the values of the diagnostics printed from now on with `rendering`
+pub fn set_value_rendering(rendering: ValueRendering) {
+    *VALUE_RENDERING.lock().unwrap() = rendering;
+}
+
+/// How the values of diagnostics are rendered
+pub fn value_rendering() -> ValueRendering {
+    *VALUE_RENDERING.lock().unwrap()
+}
+
+/// Render `value`, of type `type_`, for a diagnostic: as its type if values are redacted, or cut
+/// to the budget of bytes with a note of how many bytes were left out
+pub fn render_value(value: impl fmt::Display, type_: impl fmt::Display) -> RenderedValue {
+    value_rendering().render(value, type_)
+}
+
+/// What a value of type `type_` is replaced with when values are redacted
+pub fn redacted(type_: impl fmt::Display) -> String {
+    format!("<redacted {}>", type_)
+}
+
+impl ValueRendering {
+    pub fn render(&self, value: impl fmt::Display, type_: impl fmt::Display) -> RenderedValue {
+        if self.redact {
+            return RenderedValue {
+                text: redacted(type_),
+                truncated: false,
+            };
+        }
+        let mut text = value.to_string();
+        if text.len() <= self.max_bytes {
+            return RenderedValue {
+                text,
+                truncated: false,
+            };
+        }
+        let mut end = self.max_bytes;
+        while !text.is_char_boundary(end) {
+            end -= 1;
+        }
+        let omitted = text.len() - end;
+        text.truncate(end);
+        text.push_str(&format!("…({} more bytes)", omitted));
+        RenderedValue {
+            text,
+            truncated: true,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn values_over_the_budget_are_cut() {
+        let rendering = ValueRendering {
+            max_bytes: 4,
+            redact: false,
+        };
+        assert_eq!(
+            rendering.render("[1, 2]", "vector<u8>"),
+            RenderedValue {
+                text: "[1, …(2 more bytes)".to_string(),
+                truncated: true,
+            }
+        );
+        assert_eq!(rendering.render("1234", "u64").text, "1234");
+        // the cut does not split a character
+        assert_eq!(rendering.render("abcdé", "s").text, "abcd…(2 more bytes)");
+        assert_eq!(rendering.render("abcé", "s").text, "abc…(2 more bytes)");
+    }
+
+    #[test]
+    fn redacted_values_are_replaced_with_their_types() {
+        let rendering = ValueRendering {
+            max_bytes: 4,
+            redact: true,
+        };
+        assert_eq!(
+            rendering.render("0x2::M::S { secret: 42 }", "0x2::M::S"),
+            RenderedValue {
+                text: "<redacted 0x2::M::S>".to_string(),
+                truncated: false,
+            }
+        );
+    }
+}
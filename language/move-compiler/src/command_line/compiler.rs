@@ -29,6 +29,7 @@ use std::{
     path::{Path, PathBuf},
 };
 use tempfile::NamedTempFile;
+use tracing::debug_span;
 
 //**************************************************************************************************
 // Definitions
@@ -151,6 +152,10 @@ impl<'a> Compiler<'a> {
         Self::from_package_paths(targets, deps)
     }
 
+    pub fn flags(&self) -> &Flags {
+        &self.flags
+    }
+
     pub fn set_flags(mut self, flags: Flags) -> Self {
         assert!(self.flags.is_empty());
         self.flags = flags;
@@ -214,8 +219,10 @@ impl<'a> Compiler<'a> {
             &compiled_module_named_address_mapping,
         )?;
         let mut compilation_env = CompilationEnv::new(flags);
-        let (source_text, pprog_and_comments_res) =
-            parse_program(&mut compilation_env, maps, targets, deps)?;
+        let (source_text, pprog_and_comments_res) = {
+            let _span = debug_span!("compiler_pass", pass = "parser").entered();
+            parse_program(&mut compilation_env, maps, targets, deps)?
+        };
         let res: Result<_, Diagnostics> = pprog_and_comments_res.and_then(|(pprog, comments)| {
             SteppedCompiler::new_at_parser(compilation_env, pre_compiled_lib, pprog)
                 .run::<TARGET>()
@@ -230,8 +237,9 @@ impl<'a> Compiler<'a> {
     }
 
     pub fn check_and_report(self) -> anyhow::Result<FilesSourceText> {
+        let remaps = self.flags.path_prefix_remaps().to_vec();
         let (files, res) = self.check()?;
-        unwrap_or_report_diagnostics(&files, res);
+        unwrap_or_report_diagnostics(&remap_file_names(&files, &remaps), res);
         Ok(files)
     }
 
@@ -249,9 +257,11 @@ impl<'a> Compiler<'a> {
     }
 
     pub fn build_and_report(self) -> anyhow::Result<(FilesSourceText, Vec<AnnotatedCompiledUnit>)> {
+        let remaps = self.flags.path_prefix_remaps().to_vec();
         let (files, units_res) = self.build()?;
-        let (units, warnings) = unwrap_or_report_diagnostics(&files, units_res);
-        report_warnings(&files, warnings);
+        let shown_files = remap_file_names(&files, &remaps);
+        let (units, warnings) = unwrap_or_report_diagnostics(&shown_files, units_res);
+        report_warnings(&shown_files, warnings);
         Ok((files, units))
     }
 }
@@ -760,10 +770,13 @@ fn run(
 
     match cur {
         PassResult::Parser(prog) => {
-            let prog = parser::merge_spec_modules::program(compilation_env, prog);
-            let prog = unit_test::filter_test_members::program(compilation_env, prog);
-            let prog = verification::ast_filter::program(compilation_env, prog);
-            let eprog = expansion::translate::program(compilation_env, pre_compiled_lib, prog);
+            let eprog = {
+                let _span = debug_span!("compiler_pass", pass = "expansion").entered();
+                let prog = parser::merge_spec_modules::program(compilation_env, prog);
+                let prog = unit_test::filter_test_members::program(compilation_env, prog);
+                let prog = verification::ast_filter::program(compilation_env, prog);
+                expansion::translate::program(compilation_env, pre_compiled_lib, prog)
+            };
             compilation_env.check_diags_at_or_above_severity(Severity::Bug)?;
             run(
                 compilation_env,
@@ -774,7 +787,10 @@ fn run(
             )
         }
         PassResult::Expansion(eprog) => {
-            let nprog = naming::translate::program(compilation_env, pre_compiled_lib, eprog);
+            let nprog = {
+                let _span = debug_span!("compiler_pass", pass = "naming").entered();
+                naming::translate::program(compilation_env, pre_compiled_lib, eprog)
+            };
             compilation_env.check_diags_at_or_above_severity(Severity::Bug)?;
             run(
                 compilation_env,
@@ -785,7 +801,10 @@ fn run(
             )
         }
         PassResult::Naming(nprog) => {
-            let tprog = typing::translate::program(compilation_env, pre_compiled_lib, nprog);
+            let tprog = {
+                let _span = debug_span!("compiler_pass", pass = "typing").entered();
+                typing::translate::program(compilation_env, pre_compiled_lib, nprog)
+            };
             compilation_env.check_diags_at_or_above_severity(Severity::BlockingError)?;
             run(
                 compilation_env,
@@ -796,7 +815,10 @@ fn run(
             )
         }
         PassResult::Typing(tprog) => {
-            let hprog = hlir::translate::program(compilation_env, pre_compiled_lib, tprog);
+            let hprog = {
+                let _span = debug_span!("compiler_pass", pass = "hlir").entered();
+                hlir::translate::program(compilation_env, pre_compiled_lib, tprog)
+            };
             compilation_env.check_diags_at_or_above_severity(Severity::Bug)?;
             run(
                 compilation_env,
@@ -807,7 +829,10 @@ fn run(
             )
         }
         PassResult::HLIR(hprog) => {
-            let cprog = cfgir::translate::program(compilation_env, pre_compiled_lib, hprog);
+            let cprog = {
+                let _span = debug_span!("compiler_pass", pass = "cfgir").entered();
+                cfgir::translate::program(compilation_env, pre_compiled_lib, hprog)
+            };
             compilation_env.check_diags_at_or_above_severity(Severity::NonblockingError)?;
             run(
                 compilation_env,
@@ -818,8 +843,10 @@ fn run(
             )
         }
         PassResult::CFGIR(cprog) => {
-            let compiled_units =
-                to_bytecode::translate::program(compilation_env, pre_compiled_lib, cprog);
+            let compiled_units = {
+                let _span = debug_span!("compiler_pass", pass = "compilation").entered();
+                to_bytecode::translate::program(compilation_env, pre_compiled_lib, cprog)
+            };
             compilation_env.check_diags_at_or_above_severity(Severity::NonblockingError)?;
             let warnings = compilation_env.take_final_warning_diags();
             assert!(until == PASS_COMPILATION);
@@ -136,6 +136,8 @@ codes!(
         InvalidNonPhantomUse:
             { msg: "invalid non-phantom type parameter usage", severity: Warning },
         InvalidAttribute: { msg: "invalid attribute", severity: NonblockingError },
+        InvalidTestOnlyStub:
+            { msg: "invalid test-only stub of a dependency module", severity: NonblockingError },
     ],
     // errors name resolution, mostly expansion/translate and naming/translate
     NameResolution: [
@@ -226,6 +228,8 @@ codes!(
         InvalidTest: { msg: "unable to generate test", severity: NonblockingError },
         InvalidBytecodeInst:
             { msg: "unknown bytecode instruction function", severity: NonblockingError },
+        DuplicateTestAddress:
+            { msg: "same address assigned to several test parameters", severity: Warning },
     ],
     Tests: [
         TestFailed: { msg: "test failure", severity: BlockingError },
@@ -17,13 +17,18 @@ use codespan_reporting::{
         Config,
     },
 };
-use move_command_line_common::{env::read_env_var, files::FileHash};
+use move_command_line_common::{
+    env::read_env_var,
+    files::{remap_path_prefix, FileHash, PathPrefixRemap},
+    json_errors::{self, JsonDiagnostic, Level},
+};
 use move_ir_types::location::*;
 use move_symbol_pool::Symbol;
 use std::{
     collections::{BTreeMap, HashMap, HashSet},
     iter::FromIterator,
     ops::Range,
+    path::Path,
 };
 
 //**************************************************************************************************
@@ -77,6 +82,7 @@ fn report_diagnostics_impl(files: &FilesSourceText, diags: Diagnostics, should_e
         _ => ColorChoice::Auto,
     };
     let mut writer = StandardStream::stderr(color_choice);
+    record_json_diagnostics(files, &diags);
     output_diagnostics(&mut writer, files, diags);
     if should_exit {
         std::process::exit(1);
@@ -93,6 +99,49 @@ pub fn unwrap_or_report_diagnostics<T>(files: &FilesSourceText, res: Result<T, D
     }
 }
 
+/// Record `diags` in the file set with `--json-errors-file`, if any, with the position of their
+/// primary label
+pub fn record_json_diagnostics(files: &FilesSourceText, diags: &Diagnostics) {
+    if !json_errors::is_enabled() {
+        return;
+    }
+    for diagnostic in json_diagnostics(files, diags) {
+        json_errors::record(&diagnostic);
+    }
+}
+
+/// `diags` as JSON diagnostics at the position of their primary label, sorted by position and
+/// without duplicates
+pub fn json_diagnostics(files: &FilesSourceText, diags: &Diagnostics) -> Vec<JsonDiagnostic> {
+    let mut diagnostics: Vec<&Diagnostic> = diags.diagnostics.iter().collect();
+    diagnostics.sort_by_key(|diag| diag.primary_label.0);
+    let mut seen: HashSet<&Diagnostic> = HashSet::new();
+    let mut json_diagnostics = vec![];
+    for diag in diagnostics {
+        if !seen.insert(diag) {
+            continue;
+        }
+        let level = match diag.info.severity() {
+            Severity::Warning => Level::Warning,
+            Severity::NonblockingError | Severity::BlockingError | Severity::Bug => Level::Error,
+        };
+        let (code, message) = diag.info.clone().render();
+        let (loc, label) = &diag.primary_label;
+        let (file, source) = &files[&loc.file_hash()];
+        let prefix = &source[..loc.start() as usize];
+        let line_start = prefix.rfind('\n').map_or(0, |idx| idx + 1);
+        json_diagnostics.push(JsonDiagnostic {
+            level,
+            code: Some(code),
+            message: format!("{}: {}", message, label),
+            file: Some(file.to_string()),
+            line: Some(prefix.matches('\n').count() + 1),
+            column: Some(prefix[line_start..].chars().count() + 1),
+        });
+    }
+    json_diagnostics
+}
+
 pub fn report_diagnostics_to_buffer(files: &FilesSourceText, diags: Diagnostics) -> Vec<u8> {
     let mut writer = Buffer::no_color();
     output_diagnostics(&mut writer, files, diags);
@@ -105,6 +154,24 @@ pub fn report_diagnostics_to_color_buffer(files: &FilesSourceText, diags: Diagno
     writer.into_inner()
 }
 
+/// `files` under the names they are shown with in diagnostics, with their prefixes remapped by
+/// `remaps`. The files keep their local names everywhere else, so that they can be read.
+pub fn remap_file_names(files: &FilesSourceText, remaps: &[PathPrefixRemap]) -> FilesSourceText {
+    files
+        .iter()
+        .map(|(fhash, (fname, source))| {
+            let fname = remap_path_prefix(remaps, Path::new(fname.as_str()));
+            (
+                *fhash,
+                (
+                    Symbol::from(fname.to_string_lossy().as_ref()),
+                    source.clone(),
+                ),
+            )
+        })
+        .collect()
+}
+
 fn output_diagnostics<W: WriteColor>(
     writer: &mut W,
     sources: &FilesSourceText,
@@ -280,6 +347,10 @@ impl Diagnostic {
         }
     }
 
+    pub fn info(&self) -> &DiagnosticInfo {
+        &self.info
+    }
+
     pub fn set_code(mut self, code: impl DiagnosticCode) -> Self {
         self.info = code.into_info();
         self
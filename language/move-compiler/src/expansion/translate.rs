@@ -172,11 +172,14 @@ pub fn program(
     }
 
     for (mident, module) in lib_module_map {
-        if let Err((mident, old_loc)) = source_module_map.add(mident, module) {
-            if !context.env.flags().sources_shadow_deps() {
-                duplicate_module(&mut context, &source_module_map, mident, old_loc)
-            }
-        }
+        let report_duplicates = !context.env.flags().sources_shadow_deps();
+        add_module(
+            &mut context,
+            &mut source_module_map,
+            mident,
+            module,
+            report_duplicates,
+        )
     }
     let mut module_map = source_module_map;
 
@@ -330,20 +333,282 @@ fn check_module_address(
     }
 }
 
+/// The package a module is declared in, to tell apart the declarations of a duplicate module
+#[derive(Clone, Copy)]
+struct ModuleOrigin {
+    package_name: Option<Symbol>,
+    is_dependency: bool,
+    is_test_only: bool,
+}
+
+impl ModuleOrigin {
+    fn of(flags: &Flags, module: &E::ModuleDefinition) -> Self {
+        let test_only = E::AttributeName_::Known(known_attributes::KnownAttribute::Testing(
+            known_attributes::TestingAttribute::TestOnly,
+        ));
+        let is_dependency = match flags.root_package() {
+            // the package system compiles the dependencies as sources, along with the root package
+            Some(root_package) => module.package_name != Some(root_package),
+            None => !module.is_source_module,
+        };
+        Self {
+            package_name: module.package_name,
+            is_dependency,
+            is_test_only: module.attributes.contains_key_(&test_only),
+        }
+    }
+
+    /// Whether this is a test-only module of the root package, which stands in for the dependency
+    /// module from `original` it shares its name with in test mode
+    fn stands_in_for(&self, original: ModuleOrigin) -> bool {
+        self.is_test_only && !self.is_dependency && original.is_dependency
+    }
+
+    /// e.g. " in package 'A' (root package, test-only)", or nothing if the package is not known
+    fn description(&self) -> String {
+        match self.package_name {
+            None => String::new(),
+            Some(package_name) => format!(
+                " in package '{}' ({}{})",
+                package_name,
+                if self.is_dependency {
+                    "dependency"
+                } else {
+                    "root package"
+                },
+                if self.is_test_only { ", test-only" } else { "" }
+            ),
+        }
+    }
+}
+
+/// Adds `module` to `module_map`. A test-only module of the root package and the dependency module
+/// it stands in for are not duplicates: whichever comes first, the stub is checked against the
+/// original, and only the stub is kept. Other duplicates are reported if `report_duplicates` is set.
+fn add_module(
+    context: &mut Context,
+    module_map: &mut UniqueMap<ModuleIdent, E::ModuleDefinition>,
+    mident: ModuleIdent,
+    module: E::ModuleDefinition,
+    report_duplicates: bool,
+) {
+    let origin = ModuleOrigin::of(context.env.flags(), &module);
+    let existing_origin = module_map
+        .get(&mident)
+        .map(|existing| ModuleOrigin::of(context.env.flags(), existing));
+    match existing_origin {
+        Some(existing_origin) if existing_origin.stands_in_for(origin) => {
+            let stub = module_map.get(&mident).unwrap();
+            check_stub(context, stub, mident.loc, &module, origin)
+        }
+        Some(existing_origin) if origin.stands_in_for(existing_origin) => {
+            let original_loc = *module_map.get_loc(&mident).unwrap();
+            let original = module_map.remove(&mident).unwrap();
+            check_stub(context, &module, original_loc, &original, existing_origin);
+            assert!(module_map.add(mident, module).is_ok());
+        }
+        _ => {
+            if let Err((mident, old_loc)) = module_map.add(mident, module) {
+                if report_duplicates {
+                    duplicate_module(context, module_map, mident, old_loc, origin)
+                }
+            }
+        }
+    }
+}
+
 fn duplicate_module(
     context: &mut Context,
     module_map: &UniqueMap<ModuleIdent, E::ModuleDefinition>,
     mident: ModuleIdent,
     old_loc: Loc,
+    origin: ModuleOrigin,
 ) {
     let old_mident = module_map.get_key(&mident).unwrap();
-    let dup_msg = format!("Duplicate definition for module '{}'", mident);
-    let prev_msg = format!("Module previously defined here, with '{}'", old_mident);
-    context.env.add_diag(diag!(
+    let old_origin = ModuleOrigin::of(context.env.flags(), module_map.get(&mident).unwrap());
+    let dup_msg = format!(
+        "Duplicate definition for module '{}'{}",
+        mident,
+        origin.description()
+    );
+    let prev_msg = format!(
+        "Module previously defined here, with '{}'{}",
+        old_mident,
+        old_origin.description()
+    );
+    let mut diag = diag!(
         Declarations::DuplicateItem,
         (mident.loc, dup_msg),
         (old_loc, prev_msg),
-    ))
+    );
+    match (origin.package_name, old_origin.package_name) {
+        (Some(package_name), Some(old_package_name))
+            if package_name == old_package_name
+                && (origin.is_test_only || old_origin.is_test_only) =>
+        {
+            diag.add_note(
+                "Only the modules of dependencies can be replaced by a test-only module. Rename \
+                 one of the modules",
+            )
+        }
+        (Some(package_name), Some(old_package_name)) if package_name == old_package_name => diag
+            .add_note(format!(
+                "Rename one of the modules, or remove one of the files declaring it from package \
+                 '{}'",
+                package_name
+            )),
+        (Some(package_name), Some(old_package_name)) => {
+            let dependency = if origin.is_dependency {
+                package_name
+            } else {
+                old_package_name
+            };
+            diag.add_note(format!(
+                "Rename one of the modules, or, if they are declared at a named address, give \
+                 that address a different value in dependency '{}' with `addr_subst` in the \
+                 manifest entry of the dependency",
+                dependency
+            ))
+        }
+        _ => (),
+    }
+    context.env.add_diag(diag)
+}
+
+/// Checks that the test-only module `stub` can replace the dependency module `original` in the
+/// modules that use it: every public, friend, or entry function of the stub must be declared by
+/// the original with the same signature, and every struct of the stub with the same abilities and
+/// type parameters, so that the code using the stub in test mode also compiles against the
+/// original
+fn check_stub(
+    context: &mut Context,
+    stub: &E::ModuleDefinition,
+    original_mloc: Loc,
+    original: &E::ModuleDefinition,
+    original_origin: ModuleOrigin,
+) {
+    let original_desc = format!("the original module{}", original_origin.description());
+    for (name, fdef) in stub.functions.key_cloned_iter() {
+        if fdef.visibility == E::Visibility::Internal && fdef.entry.is_none() {
+            continue;
+        }
+        let original_fdef = match original.functions.get(&name) {
+            Some(original_fdef) => original_fdef,
+            None => {
+                let msg = format!(
+                    "Invalid test-only stub. Function '{}' is not declared by {}",
+                    name, original_desc
+                );
+                context.env.add_diag(diag!(
+                    Declarations::InvalidTestOnlyStub,
+                    (name.loc(), msg),
+                    (original_mloc, "Original module declared here")
+                ));
+                continue;
+            }
+        };
+        let original_loc = original.functions.get_loc(&name).unwrap();
+        let mut mismatch = |msg: String| {
+            context.env.add_diag(diag!(
+                Declarations::InvalidTestOnlyStub,
+                (name.loc(), format!("Invalid test-only stub. {}", msg)),
+                (*original_loc, "Original function declared here")
+            ))
+        };
+        // visibilities carry the location of their keyword, so they are compared by name
+        let modifiers = |f: &E::Function| {
+            let visibility = match f.visibility {
+                E::Visibility::Internal => "private",
+                E::Visibility::Public(_) => E::Visibility::PUBLIC,
+                E::Visibility::Friend(_) => E::Visibility::FRIEND,
+            };
+            match f.entry {
+                Some(_) => format!("'{} entry'", visibility),
+                None => format!("'{}'", visibility),
+            }
+        };
+        if modifiers(fdef) != modifiers(original_fdef) {
+            mismatch(format!(
+                "Function '{}' is {} in the stub, but {} in {}",
+                name,
+                modifiers(fdef),
+                modifiers(original_fdef),
+                original_desc
+            ));
+            continue;
+        }
+        let (signature, original_signature) = (&fdef.signature, &original_fdef.signature);
+        if signature.type_parameters != original_signature.type_parameters {
+            mismatch(format!(
+                "The type parameters of function '{}' differ from those in {}",
+                name, original_desc
+            ));
+            continue;
+        }
+        if signature.parameters.len() != original_signature.parameters.len() {
+            mismatch(format!(
+                "Function '{}' takes {} parameter(s) in the stub, but {} in {}",
+                name,
+                signature.parameters.len(),
+                original_signature.parameters.len(),
+                original_desc
+            ));
+            continue;
+        }
+        let param_mismatch = signature
+            .parameters
+            .iter()
+            .zip(&original_signature.parameters)
+            .find(|((_, ty), (_, original_ty))| ty != original_ty);
+        if let Some(((var, ty), (_, original_ty))) = param_mismatch {
+            mismatch(format!(
+                "Parameter '{}' of function '{}' has type '{}' in the stub, but '{}' in {}",
+                var, name, ty.value, original_ty.value, original_desc
+            ));
+            continue;
+        }
+        if signature.return_type != original_signature.return_type {
+            mismatch(format!(
+                "Function '{}' returns '{}' in the stub, but '{}' in {}",
+                name,
+                signature.return_type.value,
+                original_signature.return_type.value,
+                original_desc
+            ));
+        }
+    }
+
+    for (name, sdef) in stub.structs.key_cloned_iter() {
+        let msg = match original.structs.get(&name) {
+            None => format!(
+                "Invalid test-only stub. Struct '{}' is not declared by {}",
+                name, original_desc
+            ),
+            Some(original_sdef) if sdef.abilities != original_sdef.abilities => format!(
+                "Invalid test-only stub. Struct '{}' has different abilities than in {}",
+                name, original_desc
+            ),
+            Some(original_sdef) if sdef.type_parameters != original_sdef.type_parameters => {
+                format!(
+                    "Invalid test-only stub. The type parameters of struct '{}' differ from those \
+                     in {}",
+                    name, original_desc
+                )
+            }
+            Some(_) => continue,
+        };
+        let original_loc = original
+            .structs
+            .get_loc(&name)
+            .map_or((original_mloc, "Original module declared here"), |loc| {
+                (*loc, "Original struct declared here")
+            });
+        context.env.add_diag(diag!(
+            Declarations::InvalidTestOnlyStub,
+            (name.loc(), msg),
+            original_loc
+        ))
+    }
 }
 
 fn module(
@@ -355,9 +620,8 @@ fn module(
 ) {
     assert!(context.address == None);
     let (mident, mod_) = module_(context, package_name, module_address, module_def);
-    if let Err((mident, old_loc)) = module_map.add(mident, mod_) {
-        duplicate_module(context, module_map, mident, old_loc)
-    }
+    let report_duplicates = true;
+    add_module(context, module_map, mident, mod_, report_duplicates);
     context.address = None
 }
 
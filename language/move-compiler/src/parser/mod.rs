@@ -10,10 +10,12 @@ pub mod lexer;
 pub(crate) mod merge_spec_modules;
 pub(crate) mod syntax;
 
+pub use syntax::parse_file_string;
+
 use crate::{
     attr_derivation,
     diagnostics::{codes::Severity, Diagnostics, FilesSourceText},
-    parser::{self, ast::PackageDefinition, syntax::parse_file_string},
+    parser::{self, ast::PackageDefinition},
     shared::{CompilationEnv, IndexedPackagePath, NamedAddressMaps},
 };
 use anyhow::anyhow;
@@ -162,7 +164,7 @@ fn ensure_targets_deps_dont_intersect(
     }
     let all_files = intersection
         .into_iter()
-        .map(|s| format!("    {}", s))
+        .map(|s| format!("    {}", compilation_env.flags().remap_file_name(s)))
         .collect::<Vec<_>>()
         .join("\n");
     Err(anyhow!(
@@ -8,6 +8,7 @@ use crate::{
     naming::ast::ModuleDefinition,
 };
 use clap::*;
+use move_command_line_common::files::{remap_path_prefix, PathPrefixRemap};
 use move_ir_types::location::*;
 use move_symbol_pool::Symbol;
 use petgraph::{algo::astar as petgraph_astar, graphmap::DiGraphMap};
@@ -15,6 +16,7 @@ use std::{
     collections::BTreeMap,
     fmt,
     hash::Hash,
+    path::Path,
     sync::atomic::{AtomicUsize, Ordering as AtomicOrdering},
 };
 
@@ -302,6 +304,16 @@ pub struct Flags {
     /// included only in tests, without creating the unit test code regular tests do.
     #[clap(skip)]
     keep_testing_functions: bool,
+
+    /// Prefixes remapped in the file names reported by the compiler, so that they do not show
+    /// local directories
+    #[clap(skip)]
+    path_prefix_remaps: Vec<PathPrefixRemap>,
+
+    /// The package being built, when its dependencies are compiled as sources along with it, so
+    /// that the modules of the dependencies are still told apart from its own
+    #[clap(skip)]
+    root_package: Option<Symbol>,
 }
 
 impl Flags {
@@ -313,6 +325,8 @@ impl Flags {
             flavor: "".to_string(),
             bytecode_version: None,
             keep_testing_functions: false,
+            path_prefix_remaps: vec![],
+            root_package: None,
         }
     }
 
@@ -324,6 +338,8 @@ impl Flags {
             flavor: "".to_string(),
             bytecode_version: None,
             keep_testing_functions: false,
+            path_prefix_remaps: vec![],
+            root_package: None,
         }
     }
 
@@ -335,6 +351,8 @@ impl Flags {
             flavor: "".to_string(),
             bytecode_version: None,
             keep_testing_functions: false,
+            path_prefix_remaps: vec![],
+            root_package: None,
         }
     }
 
@@ -359,6 +377,20 @@ impl Flags {
         }
     }
 
+    pub fn set_path_prefix_remaps(self, path_prefix_remaps: Vec<PathPrefixRemap>) -> Self {
+        Self {
+            path_prefix_remaps,
+            ..self
+        }
+    }
+
+    pub fn set_root_package(self, root_package: Symbol) -> Self {
+        Self {
+            root_package: Some(root_package),
+            ..self
+        }
+    }
+
     pub fn is_empty(&self) -> bool {
         self == &Self::empty()
     }
@@ -386,6 +418,24 @@ impl Flags {
     pub fn bytecode_version(&self) -> Option<u32> {
         self.bytecode_version
     }
+
+    pub fn root_package(&self) -> Option<Symbol> {
+        self.root_package
+    }
+
+    /// The prefixes remapped in the file names shown in diagnostics
+    pub fn path_prefix_remaps(&self) -> &[PathPrefixRemap] {
+        &self.path_prefix_remaps
+    }
+
+    /// The name under which the compiler reports the file at `path`
+    pub fn remap_file_name(&self, path: &str) -> Symbol {
+        Symbol::from(
+            remap_path_prefix(&self.path_prefix_remaps, Path::new(path))
+                .to_string_lossy()
+                .as_ref(),
+        )
+    }
 }
 
 //**************************************************************************************************
@@ -424,6 +474,8 @@ pub mod known_attributes {
         Test,
         // This test is expected to fail
         ExpectedFailure,
+        // This test measures the gas of the calls it makes, for `move package gas-report`
+        GasBenchmark,
     }
 
     #[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
@@ -462,6 +514,7 @@ pub mod known_attributes {
                 TestingAttribute::EXPECTED_FAILURE => {
                     Self::Testing(TestingAttribute::ExpectedFailure)
                 }
+                TestingAttribute::GAS_BENCHMARK => Self::Testing(TestingAttribute::GasBenchmark),
                 VerificationAttribute::VERIFY_ONLY => {
                     Self::Verification(VerificationAttribute::VerifyOnly)
                 }
@@ -493,6 +546,7 @@ pub mod known_attributes {
         pub const TEST: &'static str = "test";
         pub const EXPECTED_FAILURE: &'static str = "expected_failure";
         pub const TEST_ONLY: &'static str = "test_only";
+        pub const GAS_BENCHMARK: &'static str = "gas_benchmark";
         pub const CODE_ASSIGNMENT_NAME: &'static str = "abort_code";
 
         pub const fn name(&self) -> &str {
@@ -500,6 +554,7 @@ pub mod known_attributes {
                 Self::Test => Self::TEST,
                 Self::TestOnly => Self::TEST_ONLY,
                 Self::ExpectedFailure => Self::EXPECTED_FAILURE,
+                Self::GasBenchmark => Self::GAS_BENCHMARK,
             }
         }
 
@@ -520,10 +575,13 @@ pub mod known_attributes {
                 Lazy::new(|| IntoIterator::into_iter([AttributePosition::Function]).collect());
             static EXPECTED_FAILURE_POSITIONS: Lazy<BTreeSet<AttributePosition>> =
                 Lazy::new(|| IntoIterator::into_iter([AttributePosition::Function]).collect());
+            static GAS_BENCHMARK_POSITIONS: Lazy<BTreeSet<AttributePosition>> =
+                Lazy::new(|| IntoIterator::into_iter([AttributePosition::Function]).collect());
             match self {
                 TestingAttribute::TestOnly => &*TEST_ONLY_POSITIONS,
                 TestingAttribute::Test => &*TEST_POSITIONS,
                 TestingAttribute::ExpectedFailure => &*EXPECTED_FAILURE_POSITIONS,
+                TestingAttribute::GasBenchmark => &*GAS_BENCHMARK_POSITIONS,
             }
         }
     }
@@ -36,6 +36,8 @@ pub struct TestCase {
     pub test_name: TestName,
     pub arguments: Vec<MoveValue>,
     pub expected_failure: Option<ExpectedFailure>,
+    /// Annotated `#[gas_benchmark]`, to measure the gas of the calls it makes
+    pub gas_benchmark: bool,
 }
 
 #[derive(Debug, Clone)]
@@ -6,6 +6,8 @@ use crate::{
     cfgir::ast as G,
     diag,
     expansion::ast::{self as E, Address, ModuleIdent, ModuleIdent_},
+    hlir::ast as H,
+    naming::ast as N,
     shared::{
         known_attributes::{KnownAttribute, TestingAttribute},
         CompilationEnv, Identifier, NumericalAddress,
@@ -103,6 +105,7 @@ fn build_test_info<'func>(
     let test_attribute_opt = get_attrs(TestingAttribute::Test);
     let abort_attribute_opt = get_attrs(TestingAttribute::ExpectedFailure);
     let test_only_attribute_opt = get_attrs(TestingAttribute::TestOnly);
+    let gas_benchmark_attribute_opt = get_attrs(TestingAttribute::GasBenchmark);
 
     let test_attribute = match test_attribute_opt {
         None => {
@@ -117,6 +120,17 @@ fn build_test_info<'func>(
                     (abort_attribute.loc, abort_msg),
                 ))
             }
+            // nor can gas benchmarks
+            if let Some(gas_benchmark_attribute) = gas_benchmark_attribute_opt {
+                let fn_msg = "Only functions defined as a test with #[test] can also have a \
+                              #[gas_benchmark] attribute";
+                let benchmark_msg = "Attributed as #[gas_benchmark] here";
+                context.env.add_diag(diag!(
+                    Attributes::InvalidUsage,
+                    (fn_loc, fn_msg),
+                    (gas_benchmark_attribute.loc, benchmark_msg),
+                ))
+            }
             return None;
         }
         Some(test_attribute) => test_attribute,
@@ -136,9 +150,24 @@ fn build_test_info<'func>(
 
     let test_annotation_params = parse_test_attribute(context, test_attribute, 0);
     let mut arguments = Vec::new();
-    for (var, _) in &function.signature.parameters {
+    for (var, ty) in &function.signature.parameters {
         match test_annotation_params.get(&var.value()) {
-            Some(value) => arguments.push(value.clone()),
+            Some((assign_loc, value)) => {
+                if !is_address_or_signer(ty) {
+                    let msg = format!(
+                        "Invalid test parameter assignment. '{}' is assigned an address, but is \
+                         neither a 'signer' nor an 'address'",
+                        var.value()
+                    );
+                    context.env.add_diag(diag!(
+                        Attributes::InvalidTest,
+                        (*assign_loc, msg),
+                        (ty.loc, "Declared with this type"),
+                        (fn_loc, IN_THIS_TEST_MSG),
+                    ))
+                }
+                arguments.push(value.clone())
+            }
             None => {
                 let missing_param_msg = "Missing test parameter assignment in test. Expected a \
                                          parameter to be assigned in this attribute";
@@ -152,6 +181,46 @@ fn build_test_info<'func>(
         }
     }
 
+    let mut assignments: Vec<_> = test_annotation_params
+        .iter()
+        .map(|(name, (assign_loc, value))| (*assign_loc, *name, value))
+        .collect();
+    assignments.sort_by_key(|(assign_loc, _, _)| assign_loc.start());
+    for (idx, (assign_loc, name, value)) in assignments.iter().enumerate() {
+        // assignments to names that are not parameters of the test
+        if !function
+            .signature
+            .parameters
+            .iter()
+            .any(|(var, _)| var.value() == *name)
+        {
+            let msg = format!(
+                "Unused test parameter assignment. The test has no parameter named '{}'",
+                name
+            );
+            context.env.add_diag(diag!(
+                Attributes::InvalidTest,
+                (*assign_loc, msg),
+                (fn_loc, IN_THIS_TEST_MSG),
+            ))
+        }
+        // the same address assigned to several parameters, which then get the same signer
+        if let Some((previous_loc, previous_name, _)) = assignments[..idx]
+            .iter()
+            .find(|(_, _, previous)| previous == value)
+        {
+            let msg = format!(
+                "'{}' is assigned the same address as '{}'",
+                name, previous_name
+            );
+            context.env.add_diag(diag!(
+                Attributes::DuplicateTestAddress,
+                (*assign_loc, msg),
+                (*previous_loc, "Previously assigned here"),
+            ))
+        }
+    }
+
     let expected_failure = match abort_attribute_opt {
         None => None,
         Some(abort_attribute) => parse_failure_attribute(context, abort_attribute),
@@ -161,6 +230,7 @@ fn build_test_info<'func>(
         test_name: fn_name.to_string(),
         arguments,
         expected_failure,
+        gas_benchmark: gas_benchmark_attribute_opt.is_some(),
     })
 }
 
@@ -172,7 +242,7 @@ fn parse_test_attribute(
     context: &mut Context,
     sp!(aloc, test_attribute): &E::Attribute,
     depth: usize,
-) -> BTreeMap<Symbol, MoveValue> {
+) -> BTreeMap<Symbol, (Loc, MoveValue)> {
     use E::Attribute_ as EA;
 
     match test_attribute {
@@ -212,7 +282,7 @@ fn parse_test_attribute(
             };
 
             let mut args = BTreeMap::new();
-            args.insert(nm.value, value);
+            args.insert(nm.value, (*aloc, value));
             args
         }
         EA::Parameterized(nm, attributes) => {
@@ -228,6 +298,22 @@ fn parse_test_attribute(
     }
 }
 
+/// Whether a test parameter of type `ty` can be assigned an address: if it is a `signer` or an
+/// `address`, or a reference to one
+fn is_address_or_signer(sp!(_, ty): &H::SingleType) -> bool {
+    use H::{BaseType_ as HB, SingleType_ as HS, TypeName_ as TN};
+    use N::BuiltinTypeName_ as BT;
+    let (HS::Base(sp!(_, base)) | HS::Ref(_, sp!(_, base))) = ty;
+    match base {
+        HB::Apply(_, sp!(_, TN::Builtin(sp!(_, builtin))), _) => {
+            matches!(builtin, BT::Address | BT::Signer)
+        }
+        // already reported
+        HB::UnresolvedError => true,
+        _ => false,
+    }
+}
+
 fn parse_failure_attribute(
     context: &mut Context,
     sp!(aloc, expected_attr): &E::Attribute,
@@ -0,0 +1,11 @@
+// gas_benchmark attributes can only be placed on #[test] functions
+module 0x1::A {
+    #[gas_benchmark]
+    fun foo() { }
+
+    #[test_only, gas_benchmark]
+    fun bar() { }
+
+    #[test, gas_benchmark]
+    fun baz() { }
+}
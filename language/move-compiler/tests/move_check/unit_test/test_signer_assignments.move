@@ -0,0 +1,17 @@
+// signers can be assigned any address, numerical or named
+module 0x1::M {
+    #[test(_a = @0x2, _b = @std, _c = @A, _d = @K)]
+    fun four_signers(_a: signer, _b: &signer, _c: signer, _d: address) { }
+
+    // warning: A and B are both 0x42, so the signers are the same
+    #[test(_a = @A, _b = @B, _c = @0x1, _d = @0x1)]
+    fun duplicate_addresses(_a: signer, _b: signer, _c: signer, _d: signer) { }
+
+    // failure: no parameter named _c
+    #[test(_a = @0x1, _c = @0x2)]
+    fun unknown_parameter(_a: signer) { }
+
+    // failure: an address cannot be passed as a u64
+    #[test(_a = @0x1, _n = @0x2)]
+    fun not_a_signer(_a: signer, _n: u64) { }
+}
@@ -666,6 +666,8 @@ pub enum StatusCode {
     VM_MAX_TYPE_DEPTH_REACHED = 4024,
     VM_MAX_VALUE_DEPTH_REACHED = 4025,
     VM_EXTENSION_ERROR = 4026,
+    // The values built by the execution outgrew the memory quota of the gas meter.
+    MEMORY_QUOTA_EXCEEDED = 4027,
 
 
     // A reserved status to represent an unknown vm status.
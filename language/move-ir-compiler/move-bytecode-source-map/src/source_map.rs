@@ -572,4 +572,24 @@ impl SourceMap {
 
         Ok(empty_source_map)
     }
+
+    /// Replace the names of the parameters, locals and type parameters of functions, and of the
+    /// type parameters of structs, with the generated names of `dummy_from_view`. Their locations
+    /// are kept.
+    pub fn strip_names(&mut self) {
+        for function_map in self.function_map.values_mut() {
+            rename_by_position(&mut function_map.type_parameters, "Ty");
+            rename_by_position(&mut function_map.parameters, "Arg");
+            rename_by_position(&mut function_map.locals, "loc");
+        }
+        for struct_map in self.struct_map.values_mut() {
+            rename_by_position(&mut struct_map.type_parameters, "Ty");
+        }
+    }
+}
+
+fn rename_by_position(names: &mut [SourceName], prefix: &str) {
+    for (i, (name, _)) in names.iter_mut().enumerate() {
+        *name = format!("{}{}", prefix, i);
+    }
 }
@@ -110,6 +110,7 @@ pub fn run_model_builder_with_options_and_compilation_flags<
 ) -> anyhow::Result<GlobalEnv> {
     let mut env = GlobalEnv::new();
     env.set_extension(options);
+    env.set_path_prefix_remaps(flags.path_prefix_remaps().to_vec());
 
     // Step 1: parse the program to get comments and a separation of targets and dependencies.
     let (files, comments_and_compiler_res) = Compiler::from_package_paths(move_sources, deps)
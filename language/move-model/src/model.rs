@@ -21,6 +21,8 @@ use std::{
     collections::{BTreeMap, BTreeSet, VecDeque},
     ffi::OsStr,
     fmt::{self, Formatter},
+    ops::Range,
+    path::Path,
     rc::Rc,
 };
 
@@ -52,7 +54,10 @@ use move_binary_format::{
     CompiledModule,
 };
 use move_bytecode_source_map::{mapping::SourceMapping, source_map::SourceMap};
-use move_command_line_common::{address::NumericalAddress, files::FileHash};
+use move_command_line_common::{
+    address::NumericalAddress,
+    files::{remap_path_prefix, FileHash, PathPrefixRemap},
+};
 use move_core_types::{
     account_address::AccountAddress,
     identifier::{IdentStr, Identifier},
@@ -480,6 +485,9 @@ pub struct GlobalEnv {
     file_idx_to_id: BTreeMap<u16, FileId>,
     /// A set indicating whether a file id is a target or a dependency.
     file_id_is_dep: BTreeSet<FileId>,
+    /// Prefixes remapped in the file names shown in diagnostics. The source files keep their
+    /// local names.
+    path_prefix_remaps: Vec<PathPrefixRemap>,
     /// A special constant location representing an unknown location.
     /// This uses a pseudo entry in `source_files` to be safely represented.
     unknown_loc: Loc,
@@ -558,6 +566,7 @@ impl GlobalEnv {
             file_id_to_idx,
             file_idx_to_id,
             file_id_is_dep: BTreeSet::new(),
+            path_prefix_remaps: vec![],
             diags: RefCell::new(vec![]),
             symbol_pool: SymbolPool::new(),
             next_free_node_id: Default::default(),
@@ -929,6 +938,11 @@ impl GlobalEnv {
             .any(|(d, _)| d.severity >= Severity::Warning)
     }
 
+    /// Sets the prefixes remapped in the file names shown in diagnostics.
+    pub fn set_path_prefix_remaps(&mut self, remaps: Vec<PathPrefixRemap>) {
+        self.path_prefix_remaps = remaps;
+    }
+
     /// Writes accumulated diagnostics of given or higher severity.
     pub fn report_diag<W: WriteColor>(&self, writer: &mut W, severity: Severity) {
         self.report_diag_with_filter(writer, |d| d.severity >= severity)
@@ -940,6 +954,10 @@ impl GlobalEnv {
         writer: &mut W,
         filter: F,
     ) {
+        let files = DisplayFiles {
+            files: &self.source_files,
+            remaps: &self.path_prefix_remaps,
+        };
         let mut shown = BTreeSet::new();
         for (diag, reported) in self
             .diags
@@ -951,8 +969,7 @@ impl GlobalEnv {
                 // Avoid showing the same message twice. This can happen e.g. because of
                 // duplication of expressions via schema inclusion.
                 if shown.insert(format!("{:?}", diag)) {
-                    emit(writer, &Config::default(), &self.source_files, diag)
-                        .expect("emit must not fail");
+                    emit(writer, &Config::default(), &files, diag).expect("emit must not fail");
                 }
                 *reported = true;
             }
@@ -1710,6 +1727,43 @@ impl Default for GlobalEnv {
     }
 }
 
+/// The source files of a `GlobalEnv` as shown in diagnostics, named with their prefixes remapped
+struct DisplayFiles<'a> {
+    files: &'a Files<String>,
+    remaps: &'a [PathPrefixRemap],
+}
+
+impl<'a> codespan_reporting::files::Files<'a> for DisplayFiles<'a> {
+    type FileId = FileId;
+    type Name = String;
+    type Source = &'a str;
+
+    fn name(&'a self, id: FileId) -> Result<String, codespan_reporting::files::Error> {
+        let name = Path::new(self.files.name(id));
+        Ok(remap_path_prefix(self.remaps, name).display().to_string())
+    }
+
+    fn source(&'a self, id: FileId) -> Result<&'a str, codespan_reporting::files::Error> {
+        codespan_reporting::files::Files::source(self.files, id)
+    }
+
+    fn line_index(
+        &'a self,
+        id: FileId,
+        byte_index: usize,
+    ) -> Result<usize, codespan_reporting::files::Error> {
+        codespan_reporting::files::Files::line_index(self.files, id, byte_index)
+    }
+
+    fn line_range(
+        &'a self,
+        id: FileId,
+        line_index: usize,
+    ) -> Result<Range<usize>, codespan_reporting::files::Error> {
+        codespan_reporting::files::Files::line_range(self.files, id, line_index)
+    }
+}
+
 // =================================================================================================
 /// # Module Environment
 
@@ -7,6 +7,7 @@ use log::{debug, info, warn};
 
 use codespan::{ByteIndex, Span};
 use itertools::Itertools;
+use move_command_line_common::files::{remap_path_prefix, PathPrefixRemap};
 use move_compiler::parser::keywords::{BUILTINS, CONTEXTUAL_KEYWORDS, KEYWORDS};
 use move_model::{
     ast::{ModuleName, SpecBlockInfo, SpecBlockTarget},
@@ -94,6 +95,10 @@ pub struct DocgenOptions {
     pub include_call_diagrams: bool,
     /// If this is being compiled relative to a different place where it will be stored (output directory).
     pub compile_relative_to_output_dir: bool,
+    /// Path prefixes to remap in the links to the documentation of dependencies, so that the
+    /// generated docs do not show local directories.
+    #[serde(skip)]
+    pub remap_path_prefix: Vec<PathPrefixRemap>,
 }
 
 impl Default for DocgenOptions {
@@ -113,6 +118,7 @@ impl Default for DocgenOptions {
             references_file: None,
             include_dep_diagrams: false,
             include_call_diagrams: false,
+            remap_path_prefix: vec![],
         }
     }
 }
@@ -464,9 +470,12 @@ impl<'env> Docgen<'env> {
                 path.push(&file_name);
                 if path.exists() {
                     Some(
-                        self.path_relative_to(&path, &output_path)
-                            .to_string_lossy()
-                            .to_string(),
+                        remap_path_prefix(
+                            &self.options.remap_path_prefix,
+                            &self.path_relative_to(&path, &output_path),
+                        )
+                        .to_string_lossy()
+                        .to_string(),
                     )
                 } else {
                     None
@@ -0,0 +1,24 @@
+#[test_only]
+/// Module providing the time and the block height of the current transaction.
+/// Only included for tests.
+///
+/// Its natives read the clock of the test harness. In unit tests, the clock
+/// starts at the `--initial-time` and `--initial-height` of `move test` and is
+/// moved with `std::unit_test::set_time` and
+/// `std::unit_test::set_block_height`; in the sandbox, it is set by the same
+/// options of `move sandbox run --test`.
+module std::timestamp {
+    /// Conversion factor between seconds and microseconds
+    const MICRO_CONVERSION_FACTOR: u64 = 1000000;
+
+    /// The current time, in microseconds.
+    native public fun now_microseconds(): u64;
+
+    /// The height of the current block.
+    native public fun block_height(): u64;
+
+    /// The current time, in seconds.
+    public fun now_seconds(): u64 {
+        now_microseconds() / MICRO_CONVERSION_FACTOR
+    }
+}
@@ -0,0 +1,89 @@
+#[test_only]
+module std::emitted_events_tests {
+    use std::event;
+    use std::unit_test;
+    use std::vector;
+
+    struct Deposit has copy, drop, store { amount: u64 }
+    struct Withdraw has copy, drop, store { amount: u64 }
+    struct Wrapped<T> has copy, drop, store { value: T }
+
+    #[test]
+    fun no_events_emitted() {
+        assert!(vector::is_empty(&unit_test::emitted_events<Deposit>()), 0);
+    }
+
+    #[test(s = @0x42)]
+    fun events_in_emission_order(s: signer) {
+        let handle = event::new_event_handle<Deposit>(&s);
+        event::emit_event(&mut handle, Deposit { amount: 3 });
+        event::emit_event(&mut handle, Deposit { amount: 1 });
+        event::emit_event(&mut handle, Deposit { amount: 2 });
+
+        let expected = vector[Deposit { amount: 3 }, Deposit { amount: 1 }, Deposit { amount: 2 }];
+        assert!(unit_test::emitted_events<Deposit>() == expected, 0);
+        event::destroy_handle(handle);
+    }
+
+    #[test(s = @0x42)]
+    fun events_filtered_by_type(s: signer) {
+        let deposits = event::new_event_handle<Deposit>(&s);
+        let withdraws = event::new_event_handle<Withdraw>(&s);
+        event::emit_event(&mut deposits, Deposit { amount: 10 });
+        event::emit_event(&mut withdraws, Withdraw { amount: 4 });
+        event::emit_event(&mut deposits, Deposit { amount: 5 });
+
+        assert!(unit_test::emitted_events<Deposit>() == vector[Deposit { amount: 10 }, Deposit { amount: 5 }], 0);
+        assert!(unit_test::emitted_events<Withdraw>() == vector[Withdraw { amount: 4 }], 1);
+        // the amount of a `Deposit` is not a `u64` event
+        assert!(vector::is_empty(&unit_test::emitted_events<u64>()), 2);
+        event::destroy_handle(deposits);
+        event::destroy_handle(withdraws);
+    }
+
+    #[test(s = @0x42)]
+    fun generic_events_filtered_by_instantiation(s: signer) {
+        let numbers = event::new_event_handle<Wrapped<u64>>(&s);
+        let flags = event::new_event_handle<Wrapped<bool>>(&s);
+        event::emit_event(&mut flags, Wrapped { value: true });
+        event::emit_event(&mut numbers, Wrapped { value: 7 });
+        event::emit_event(&mut flags, Wrapped { value: false });
+
+        assert!(unit_test::emitted_events<Wrapped<u64>>() == vector[Wrapped { value: 7 }], 0);
+        assert!(unit_test::emitted_events<Wrapped<bool>>() == vector[Wrapped { value: true }, Wrapped { value: false }], 1);
+        assert!(vector::is_empty(&unit_test::emitted_events<Wrapped<Deposit>>()), 2);
+        event::destroy_handle(numbers);
+        event::destroy_handle(flags);
+    }
+
+    #[test(s = @0x42)]
+    fun assert_event_emitted(s: signer) {
+        let handle = event::new_event_handle<Wrapped<u64>>(&s);
+        event::emit_event(&mut handle, Wrapped { value: 1 });
+        event::emit_event(&mut handle, Wrapped { value: 2 });
+
+        unit_test::assert_event_emitted(Wrapped { value: 2 });
+        unit_test::assert_event_emitted(Wrapped { value: 1 });
+        event::destroy_handle(handle);
+    }
+
+    #[test(s = @0x42)]
+    #[expected_failure(abort_code = 0)]
+    fun assert_event_emitted_with_other_value(s: signer) {
+        let handle = event::new_event_handle<Deposit>(&s);
+        event::emit_event(&mut handle, Deposit { amount: 1 });
+
+        unit_test::assert_event_emitted(Deposit { amount: 2 });
+        event::destroy_handle(handle);
+    }
+
+    #[test(s = @0x42)]
+    #[expected_failure(abort_code = 0)]
+    fun assert_event_emitted_with_other_type(s: signer) {
+        let handle = event::new_event_handle<Withdraw>(&s);
+        event::emit_event(&mut handle, Withdraw { amount: 1 });
+
+        unit_test::assert_event_emitted(Deposit { amount: 1 });
+        event::destroy_handle(handle);
+    }
+}
@@ -0,0 +1,52 @@
+#[test_only]
+module std::timestamp_tests {
+    use std::signer;
+    use std::timestamp;
+    use std::unit_test;
+
+    /// The coins of an account, which can only be withdrawn once `unlock_time` (in seconds) has
+    /// passed
+    struct TimeLock has key { coins: u64, unlock_time: u64 }
+
+    const ELOCKED: u64 = 1;
+
+    fun lock(account: &signer, coins: u64, unlock_time: u64) {
+        move_to(account, TimeLock { coins, unlock_time })
+    }
+
+    fun withdraw(account: &signer): u64 acquires TimeLock {
+        let TimeLock { coins, unlock_time } = move_from<TimeLock>(signer::address_of(account));
+        assert!(timestamp::now_seconds() >= unlock_time, ELOCKED);
+        coins
+    }
+
+    #[test]
+    fun clock_starts_at_zero() {
+        assert!(timestamp::now_microseconds() == 0, 0);
+        assert!(timestamp::block_height() == 0, 1);
+    }
+
+    #[test]
+    fun set_time_and_block_height() {
+        unit_test::set_time(1500000);
+        unit_test::set_block_height(7);
+        assert!(timestamp::now_microseconds() == 1500000, 0);
+        assert!(timestamp::now_seconds() == 1, 1);
+        assert!(timestamp::block_height() == 7, 2);
+    }
+
+    #[test(account = @0x42)]
+    #[expected_failure(abort_code = 1)]
+    fun withdraw_before_unlock_time(account: signer) acquires TimeLock {
+        lock(&account, 10, 100);
+        unit_test::set_time(99 * 1000000);
+        withdraw(&account);
+    }
+
+    #[test(account = @0x42)]
+    fun withdraw_after_unlock_time(account: signer) acquires TimeLock {
+        lock(&account, 10, 100);
+        unit_test::set_time(100 * 1000000);
+        assert!(withdraw(&account) == 10, 0);
+    }
+}
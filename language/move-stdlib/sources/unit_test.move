@@ -9,4 +9,29 @@ module std::unit_test {
     /// This will cause a linking failure if an attempt is made to publish a
     /// test module in a VM that isn't in unit test mode.
     native public fun create_signers_for_testing(num_signers: u64): vector<signer>;
+
+    /// Return the events of type `T` emitted so far by the current test, in
+    /// the order they were emitted. Events of other types (including other
+    /// instantiations of the same generic type) are not included.
+    native public fun emitted_events<T: drop + store>(): vector<T>;
+
+    /// Abort with code 0 unless an event equal to `expected` has been emitted
+    /// so far by the current test.
+    ///
+    /// Implemented natively since every module compiled in test mode depends on
+    /// this module, so it cannot use `std::vector`.
+    native public fun assert_event_emitted<T: drop + store>(expected: T);
+
+    /// Set the time `std::timestamp` reports for the rest of the current test,
+    /// in microseconds. Tests start at the time given by `--initial-time`.
+    native public fun set_time(microseconds: u64);
+
+    /// Set the block height `std::timestamp` reports for the rest of the
+    /// current test. Tests start at the height given by `--initial-height`.
+    native public fun set_block_height(height: u64);
+
+    /// Return the address `move address derive --seed <seed>` prints for
+    /// `seed`: the first bytes of the SHA3-256 hash of `seed`. The
+    /// derivation is stable, so derived addresses can be written down.
+    native public fun derive_address(seed: vector<u8>): address;
 }
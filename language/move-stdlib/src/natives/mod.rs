@@ -9,6 +9,8 @@ pub mod hash;
 pub mod signer;
 pub mod string;
 #[cfg(feature = "testing")]
+pub mod timestamp;
+#[cfg(feature = "testing")]
 pub mod unit_test;
 pub mod vector;
 
@@ -27,6 +29,8 @@ pub struct GasParameters {
 
     #[cfg(feature = "testing")]
     pub unit_test: unit_test::GasParameters,
+    #[cfg(feature = "testing")]
+    pub timestamp: timestamp::GasParameters,
 }
 
 impl GasParameters {
@@ -89,6 +93,33 @@ impl GasParameters {
                     base_cost: 0.into(),
                     unit_cost: 0.into(),
                 },
+                emitted_events: unit_test::EmittedEventsGasParameters {
+                    base_cost: 0.into(),
+                    unit_cost: 0.into(),
+                },
+                assert_event_emitted: unit_test::AssertEventEmittedGasParameters {
+                    base_cost: 0.into(),
+                    unit_cost: 0.into(),
+                },
+                set_time: unit_test::SetTimeGasParameters {
+                    base_cost: 0.into(),
+                },
+                set_block_height: unit_test::SetBlockHeightGasParameters {
+                    base_cost: 0.into(),
+                },
+                derive_address: unit_test::DeriveAddressGasParameters {
+                    base_cost: 0.into(),
+                    unit_cost: 0.into(),
+                },
+            },
+            #[cfg(feature = "testing")]
+            timestamp: timestamp::GasParameters {
+                now_microseconds: timestamp::NowMicrosecondsGasParameters {
+                    base_cost: 0.into(),
+                },
+                block_height: timestamp::BlockHeightGasParameters {
+                    base_cost: 0.into(),
+                },
             },
         }
     }
@@ -116,6 +147,7 @@ pub fn all_natives(
     #[cfg(feature = "testing")]
     {
         add_natives!("unit_test", unit_test::make_all(gas_params.unit_test));
+        add_natives!("timestamp", timestamp::make_all(gas_params.timestamp));
     }
 
     make_table_from_iter(move_std_addr, natives)
@@ -0,0 +1,127 @@
+// Copyright (c) The Move Contributors
+// SPDX-License-Identifier: Apache-2.0
+
+use crate::natives::helpers::make_module_natives;
+use better_any::{Tid, TidAble};
+use move_binary_format::errors::{PartialVMError, PartialVMResult};
+use move_core_types::{gas_algebra::InternalGas, vm_status::StatusCode};
+use move_vm_runtime::native_functions::{NativeContext, NativeFunction};
+use move_vm_types::{
+    loaded_data::runtime_types::Type, natives::function::NativeResult, values::Value,
+};
+use smallvec::smallvec;
+use std::{collections::VecDeque, sync::Arc};
+
+/// The clock read by the natives of `std::timestamp`. This needs to be attached to the
+/// NativeContextExtensions value which is passed into session functions. The unit test runner
+/// and the sandbox attach one set by their `--initial-time` and `--initial-height` options, and
+/// tests can move it with `std::unit_test::set_time` and `std::unit_test::set_block_height`.
+#[derive(Debug, Clone, Default, Tid)]
+pub struct NativeClockContext {
+    pub now_microseconds: u64,
+    pub block_height: u64,
+}
+
+fn clock<'a>(context: &'a NativeContext) -> PartialVMResult<&'a NativeClockContext> {
+    context
+        .extensions()
+        .try_get::<NativeClockContext>()
+        .ok_or_else(|| {
+            PartialVMError::new(StatusCode::UNKNOWN_INVARIANT_VIOLATION_ERROR)
+                .with_message("No clock is attached to the session".to_string())
+        })
+}
+
+/***************************************************************************************************
+ * native fun now_microseconds
+ *
+ *   gas cost: base_cost
+ *
+ **************************************************************************************************/
+#[derive(Debug, Clone)]
+pub struct NowMicrosecondsGasParameters {
+    pub base_cost: InternalGas,
+}
+
+fn native_now_microseconds(
+    gas_params: &NowMicrosecondsGasParameters,
+    context: &mut NativeContext,
+    ty_args: Vec<Type>,
+    args: VecDeque<Value>,
+) -> PartialVMResult<NativeResult> {
+    debug_assert!(ty_args.is_empty());
+    debug_assert!(args.is_empty());
+
+    let now = clock(context)?.now_microseconds;
+    Ok(NativeResult::ok(
+        gas_params.base_cost,
+        smallvec![Value::u64(now)],
+    ))
+}
+
+pub fn make_native_now_microseconds(gas_params: NowMicrosecondsGasParameters) -> NativeFunction {
+    Arc::new(
+        move |context, ty_args, args| -> PartialVMResult<NativeResult> {
+            native_now_microseconds(&gas_params, context, ty_args, args)
+        },
+    )
+}
+
+/***************************************************************************************************
+ * native fun block_height
+ *
+ *   gas cost: base_cost
+ *
+ **************************************************************************************************/
+#[derive(Debug, Clone)]
+pub struct BlockHeightGasParameters {
+    pub base_cost: InternalGas,
+}
+
+fn native_block_height(
+    gas_params: &BlockHeightGasParameters,
+    context: &mut NativeContext,
+    ty_args: Vec<Type>,
+    args: VecDeque<Value>,
+) -> PartialVMResult<NativeResult> {
+    debug_assert!(ty_args.is_empty());
+    debug_assert!(args.is_empty());
+
+    let height = clock(context)?.block_height;
+    Ok(NativeResult::ok(
+        gas_params.base_cost,
+        smallvec![Value::u64(height)],
+    ))
+}
+
+pub fn make_native_block_height(gas_params: BlockHeightGasParameters) -> NativeFunction {
+    Arc::new(
+        move |context, ty_args, args| -> PartialVMResult<NativeResult> {
+            native_block_height(&gas_params, context, ty_args, args)
+        },
+    )
+}
+
+/***************************************************************************************************
+ * module
+ **************************************************************************************************/
+#[derive(Debug, Clone)]
+pub struct GasParameters {
+    pub now_microseconds: NowMicrosecondsGasParameters,
+    pub block_height: BlockHeightGasParameters,
+}
+
+pub fn make_all(gas_params: GasParameters) -> impl Iterator<Item = (String, NativeFunction)> {
+    let natives = [
+        (
+            "now_microseconds",
+            make_native_now_microseconds(gas_params.now_microseconds),
+        ),
+        (
+            "block_height",
+            make_native_block_height(gas_params.block_height),
+        ),
+    ];
+
+    make_module_natives(natives)
+}
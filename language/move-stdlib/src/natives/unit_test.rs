@@ -2,16 +2,21 @@
 // Copyright (c) The Move Contributors
 // SPDX-License-Identifier: Apache-2.0
 
-use crate::natives::helpers::make_module_natives;
-use move_binary_format::errors::PartialVMResult;
+use crate::natives::{helpers::make_module_natives, timestamp::NativeClockContext};
+use move_binary_format::errors::{PartialVMError, PartialVMResult};
 use move_core_types::{
     account_address::AccountAddress,
-    gas_algebra::{InternalGas, InternalGasPerArg, NumArgs},
+    gas_algebra::{InternalGas, InternalGasPerArg, InternalGasPerByte, NumArgs, NumBytes},
+    vm_status::StatusCode,
 };
 use move_vm_runtime::native_functions::{NativeContext, NativeFunction};
 use move_vm_types::{
-    loaded_data::runtime_types::Type, natives::function::NativeResult, pop_arg, values::Value,
+    loaded_data::runtime_types::Type,
+    natives::function::NativeResult,
+    pop_arg,
+    values::{Value, Vector},
 };
+use sha3::{Digest, Sha3_256};
 use smallvec::smallvec;
 use std::{collections::VecDeque, sync::Arc};
 
@@ -63,19 +68,280 @@ pub fn make_native_create_signers_for_testing(
     )
 }
 
+/***************************************************************************************************
+ * native fun emitted_events
+ *
+ *   gas cost: base_cost + unit_cost * num_of_matching_events
+ *
+ **************************************************************************************************/
+#[derive(Debug, Clone)]
+pub struct EmittedEventsGasParameters {
+    pub base_cost: InternalGas,
+    pub unit_cost: InternalGasPerArg,
+}
+
+// Every test runs in a fresh session, so the events seen here are the ones emitted by the current
+// test, in emission order.
+fn emitted_events_of_type<'a>(
+    context: &'a NativeContext,
+    ty: &'a Type,
+) -> impl Iterator<Item = &'a Value> {
+    context
+        .events()
+        .iter()
+        .filter(move |(_, _, event_ty, _, _)| event_ty == ty)
+        .map(|(_, _, _, _, event)| event)
+}
+
+fn native_emitted_events(
+    gas_params: &EmittedEventsGasParameters,
+    context: &mut NativeContext,
+    mut ty_args: Vec<Type>,
+    args: VecDeque<Value>,
+) -> PartialVMResult<NativeResult> {
+    debug_assert!(ty_args.len() == 1);
+    debug_assert!(args.is_empty());
+
+    let ty = ty_args.pop().unwrap();
+    let events = emitted_events_of_type(context, &ty)
+        .map(|event| event.copy_value())
+        .collect::<PartialVMResult<Vec<_>>>()?;
+
+    let cost = gas_params.base_cost + gas_params.unit_cost * NumArgs::new(events.len() as u64);
+
+    Ok(NativeResult::ok(
+        cost,
+        smallvec![Vector::pack(&ty, events)?],
+    ))
+}
+
+pub fn make_native_emitted_events(gas_params: EmittedEventsGasParameters) -> NativeFunction {
+    Arc::new(
+        move |context, ty_args, args| -> PartialVMResult<NativeResult> {
+            native_emitted_events(&gas_params, context, ty_args, args)
+        },
+    )
+}
+
+/***************************************************************************************************
+ * native fun assert_event_emitted
+ *
+ *   gas cost: base_cost + unit_cost * num_of_compared_events
+ *
+ **************************************************************************************************/
+/// Abort code of `assert_event_emitted` when no matching event was emitted.
+const EEVENT_NOT_EMITTED: u64 = 0;
+
+#[derive(Debug, Clone)]
+pub struct AssertEventEmittedGasParameters {
+    pub base_cost: InternalGas,
+    pub unit_cost: InternalGasPerArg,
+}
+
+fn native_assert_event_emitted(
+    gas_params: &AssertEventEmittedGasParameters,
+    context: &mut NativeContext,
+    mut ty_args: Vec<Type>,
+    mut args: VecDeque<Value>,
+) -> PartialVMResult<NativeResult> {
+    debug_assert!(ty_args.len() == 1);
+    debug_assert!(args.len() == 1);
+
+    let ty = ty_args.pop().unwrap();
+    let expected = args.pop_back().unwrap();
+    let mut num_compared = 0;
+    let mut found = false;
+    for event in emitted_events_of_type(context, &ty) {
+        num_compared += 1;
+        if event.equals(&expected)? {
+            found = true;
+            break;
+        }
+    }
+
+    let cost = gas_params.base_cost + gas_params.unit_cost * NumArgs::new(num_compared);
+
+    if found {
+        Ok(NativeResult::ok(cost, smallvec![]))
+    } else {
+        Ok(NativeResult::err(cost, EEVENT_NOT_EMITTED))
+    }
+}
+
+pub fn make_native_assert_event_emitted(
+    gas_params: AssertEventEmittedGasParameters,
+) -> NativeFunction {
+    Arc::new(
+        move |context, ty_args, args| -> PartialVMResult<NativeResult> {
+            native_assert_event_emitted(&gas_params, context, ty_args, args)
+        },
+    )
+}
+
+// Every test runs in a fresh session, whose clock starts at the initial time and height of the
+// test run
+fn clock_mut<'a>(context: &'a mut NativeContext) -> PartialVMResult<&'a mut NativeClockContext> {
+    context
+        .extensions_mut()
+        .try_get_mut::<NativeClockContext>()
+        .ok_or_else(|| {
+            PartialVMError::new(StatusCode::UNKNOWN_INVARIANT_VIOLATION_ERROR)
+                .with_message("No clock is attached to the session".to_string())
+        })
+}
+
+/***************************************************************************************************
+ * native fun set_time
+ *
+ *   gas cost: base_cost
+ *
+ **************************************************************************************************/
+#[derive(Debug, Clone)]
+pub struct SetTimeGasParameters {
+    pub base_cost: InternalGas,
+}
+
+fn native_set_time(
+    gas_params: &SetTimeGasParameters,
+    context: &mut NativeContext,
+    ty_args: Vec<Type>,
+    mut args: VecDeque<Value>,
+) -> PartialVMResult<NativeResult> {
+    debug_assert!(ty_args.is_empty());
+    debug_assert!(args.len() == 1);
+
+    let microseconds = pop_arg!(args, u64);
+    clock_mut(context)?.now_microseconds = microseconds;
+
+    Ok(NativeResult::ok(gas_params.base_cost, smallvec![]))
+}
+
+pub fn make_native_set_time(gas_params: SetTimeGasParameters) -> NativeFunction {
+    Arc::new(
+        move |context, ty_args, args| -> PartialVMResult<NativeResult> {
+            native_set_time(&gas_params, context, ty_args, args)
+        },
+    )
+}
+
+/***************************************************************************************************
+ * native fun set_block_height
+ *
+ *   gas cost: base_cost
+ *
+ **************************************************************************************************/
+#[derive(Debug, Clone)]
+pub struct SetBlockHeightGasParameters {
+    pub base_cost: InternalGas,
+}
+
+fn native_set_block_height(
+    gas_params: &SetBlockHeightGasParameters,
+    context: &mut NativeContext,
+    ty_args: Vec<Type>,
+    mut args: VecDeque<Value>,
+) -> PartialVMResult<NativeResult> {
+    debug_assert!(ty_args.is_empty());
+    debug_assert!(args.len() == 1);
+
+    let height = pop_arg!(args, u64);
+    clock_mut(context)?.block_height = height;
+
+    Ok(NativeResult::ok(gas_params.base_cost, smallvec![]))
+}
+
+pub fn make_native_set_block_height(gas_params: SetBlockHeightGasParameters) -> NativeFunction {
+    Arc::new(
+        move |context, ty_args, args| -> PartialVMResult<NativeResult> {
+            native_set_block_height(&gas_params, context, ty_args, args)
+        },
+    )
+}
+
+/***************************************************************************************************
+ * native fun derive_address
+ *
+ *   gas cost: base_cost + unit_cost * seed_length_in_bytes
+ *
+ **************************************************************************************************/
+/// The address derived from `seed` by `move address derive --scheme sha3`: the first bytes of the
+/// SHA3-256 hash of the seed. The derivation never changes, so addresses derived from a seed can be
+/// written down in tests and scripts.
+pub fn derive_address(seed: &[u8]) -> AccountAddress {
+    let hash = Sha3_256::digest(seed);
+    AccountAddress::from_bytes(&hash[..AccountAddress::LENGTH])
+        .expect("the hash is longer than an address")
+}
+
+#[derive(Debug, Clone)]
+pub struct DeriveAddressGasParameters {
+    pub base_cost: InternalGas,
+    pub unit_cost: InternalGasPerByte,
+}
+
+fn native_derive_address(
+    gas_params: &DeriveAddressGasParameters,
+    _context: &mut NativeContext,
+    ty_args: Vec<Type>,
+    mut args: VecDeque<Value>,
+) -> PartialVMResult<NativeResult> {
+    debug_assert!(ty_args.is_empty());
+    debug_assert!(args.len() == 1);
+
+    let seed = pop_arg!(args, Vec<u8>);
+    let cost = gas_params.base_cost + gas_params.unit_cost * NumBytes::new(seed.len() as u64);
+
+    Ok(NativeResult::ok(
+        cost,
+        smallvec![Value::address(derive_address(&seed))],
+    ))
+}
+
+pub fn make_native_derive_address(gas_params: DeriveAddressGasParameters) -> NativeFunction {
+    Arc::new(
+        move |context, ty_args, args| -> PartialVMResult<NativeResult> {
+            native_derive_address(&gas_params, context, ty_args, args)
+        },
+    )
+}
+
 /***************************************************************************************************
  * module
  **************************************************************************************************/
 #[derive(Debug, Clone)]
 pub struct GasParameters {
     pub create_signers_for_testing: CreateSignersForTestingGasParameters,
+    pub emitted_events: EmittedEventsGasParameters,
+    pub assert_event_emitted: AssertEventEmittedGasParameters,
+    pub set_time: SetTimeGasParameters,
+    pub set_block_height: SetBlockHeightGasParameters,
+    pub derive_address: DeriveAddressGasParameters,
 }
 
 pub fn make_all(gas_params: GasParameters) -> impl Iterator<Item = (String, NativeFunction)> {
-    let natives = [(
-        "create_signers_for_testing",
-        make_native_create_signers_for_testing(gas_params.create_signers_for_testing),
-    )];
+    let natives = [
+        (
+            "create_signers_for_testing",
+            make_native_create_signers_for_testing(gas_params.create_signers_for_testing),
+        ),
+        (
+            "emitted_events",
+            make_native_emitted_events(gas_params.emitted_events),
+        ),
+        (
+            "assert_event_emitted",
+            make_native_assert_event_emitted(gas_params.assert_event_emitted),
+        ),
+        ("set_time", make_native_set_time(gas_params.set_time)),
+        (
+            "set_block_height",
+            make_native_set_block_height(gas_params.set_block_height),
+        ),
+        (
+            "derive_address",
+            make_native_derive_address(gas_params.derive_address),
+        ),
+    ];
 
     make_module_natives(natives)
 }
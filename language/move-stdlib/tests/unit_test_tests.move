@@ -0,0 +1,26 @@
+#[test_only]
+module std::unit_test_tests {
+    use std::bcs;
+    use std::hash;
+    use std::unit_test;
+    use std::vector;
+
+    #[test]
+    fun derive_address_takes_the_first_bytes_of_the_sha3_256_hash() {
+        let seed = b"alice";
+        let address_bytes = bcs::to_bytes(&unit_test::derive_address(seed));
+        let hash = hash::sha3_256(seed);
+        let i = 0;
+        while (i < vector::length(&address_bytes)) {
+            assert!(*vector::borrow(&address_bytes, i) == *vector::borrow(&hash, i), i);
+            i = i + 1;
+        }
+    }
+
+    #[test]
+    fun derive_address_is_deterministic() {
+        assert!(unit_test::derive_address(b"alice") == unit_test::derive_address(b"alice"), 0);
+        assert!(unit_test::derive_address(b"alice") != unit_test::derive_address(b"bob"), 1);
+        assert!(unit_test::derive_address(b"") != unit_test::derive_address(b"alice"), 2);
+    }
+}
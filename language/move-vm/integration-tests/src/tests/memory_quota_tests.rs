@@ -0,0 +1,127 @@
+// Copyright (c) The Move Contributors
+// SPDX-License-Identifier: Apache-2.0
+
+use crate::compiler::{as_module, as_script, compile_units};
+use move_binary_format::errors::VMResult;
+use move_compiler::compiled_unit::AnnotatedCompiledUnit;
+use move_core_types::{
+    account_address::AccountAddress,
+    identifier::Identifier,
+    language_storage::{ModuleId, CORE_CODE_ADDRESS},
+    value::MoveValue,
+    vm_status::StatusCode,
+};
+use move_vm_runtime::move_vm::MoveVM;
+use move_vm_test_utils::{gas_schedule::GasStatus, InMemoryStorage};
+
+// Only `push_back` of the standard library is needed to grow a vector
+const PUSH_BACK_LOOP: &str = r#"
+    module 0x1::vector {
+        native public fun push_back<Element>(v: &mut vector<Element>, e: Element);
+    }
+
+    script {
+        use 0x1::vector;
+
+        fun main(n: u64) {
+            let v = vector[];
+            let i = 0;
+            while (i < n) {
+                vector::push_back(&mut v, i);
+                i = i + 1;
+            }
+        }
+    }
+"#;
+
+// `std::vector` functions marked as bytecode instructions compile to `VecPushBack` and
+// `VecPopBack`
+const PUSH_POP_LOOP: &str = r#"
+    module std::vector {
+        #[bytecode_instruction]
+        native public fun push_back<Element>(v: &mut vector<Element>, e: Element);
+
+        #[bytecode_instruction]
+        native public fun pop_back<Element>(v: &mut vector<Element>): Element;
+    }
+
+    script {
+        use std::vector;
+
+        fun main(n: u64) {
+            let v = vector[];
+            let i = 0;
+            while (i < n) {
+                vector::push_back(&mut v, i);
+                vector::pop_back(&mut v);
+                i = i + 1;
+            }
+        }
+    }
+"#;
+
+/// Run `source`, a `vector` module and a script, with `n` as the argument of the script
+fn run_loop(source: &str, n: u64, gas_status: &mut GasStatus) -> VMResult<()> {
+    let mut module_blob = vec![];
+    let mut script_blob = vec![];
+    for unit in compile_units(source).unwrap() {
+        match unit {
+            AnnotatedCompiledUnit::Module(_) => {
+                as_module(unit).serialize(&mut module_blob).unwrap()
+            }
+            AnnotatedCompiledUnit::Script(_) => {
+                as_script(unit).serialize(&mut script_blob).unwrap()
+            }
+        }
+    }
+
+    let mut storage = InMemoryStorage::new();
+    storage.publish_or_overwrite_module(
+        ModuleId::new(CORE_CODE_ADDRESS, Identifier::new("vector").unwrap()),
+        module_blob,
+    );
+    let vm = MoveVM::new(move_stdlib::natives::all_natives(
+        AccountAddress::from_hex_literal("0x1").unwrap(),
+        move_stdlib::natives::GasParameters::zeros(),
+    ))
+    .unwrap();
+    let mut sess = vm.new_session(&storage);
+    let args = vec![MoveValue::U64(n).simple_serialize().unwrap()];
+    sess.execute_script(script_blob, vec![], args, gas_status)
+        .map(|_| ())
+}
+
+#[test]
+fn push_back_loop_within_quota() {
+    let mut gas_status = GasStatus::new_unmetered().with_memory_quota(1000.into());
+    run_loop(PUSH_BACK_LOOP, 10, &mut gas_status).unwrap();
+    // each u64 takes 16 units of abstract memory
+    assert_eq!(u64::from(gas_status.peak_memory()), 160);
+}
+
+#[test]
+fn push_back_loop_exceeding_quota() {
+    let mut gas_status = GasStatus::new_unmetered().with_memory_quota(1000.into());
+    let err = run_loop(PUSH_BACK_LOOP, 1000, &mut gas_status).unwrap_err();
+    assert_eq!(err.major_status(), StatusCode::MEMORY_QUOTA_EXCEEDED);
+    assert_eq!(
+        err.message().unwrap(),
+        "memory quota of 1000 bytes exceeded: about 1008 bytes in use"
+    );
+    assert_eq!(u64::from(gas_status.peak_memory()), 1008);
+}
+
+#[test]
+fn push_back_loop_without_quota() {
+    let mut gas_status = GasStatus::new_unmetered();
+    run_loop(PUSH_BACK_LOOP, 1000, &mut gas_status).unwrap();
+    assert_eq!(u64::from(gas_status.peak_memory()), 16000);
+}
+
+#[test]
+fn push_pop_loop_within_quota() {
+    let mut gas_status = GasStatus::new_unmetered().with_memory_quota(1000.into());
+    // the popped elements are no longer held, so the loop never holds more than one of them
+    run_loop(PUSH_POP_LOOP, 1000, &mut gas_status).unwrap();
+    assert_eq!(u64::from(gas_status.peak_memory()), 16);
+}
@@ -7,6 +7,7 @@ mod bad_storage_tests;
 mod exec_func_effects_tests;
 mod function_arg_tests;
 mod loader_tests;
+mod memory_quota_tests;
 mod mutated_accounts_tests;
 mod nested_loop_tests;
 mod return_value_tests;
@@ -611,6 +611,27 @@ impl Interpreter {
         Ok(())
     }
 
+    /// Print `locals` and the operand stack on one line, for the trace of the execution: a tab,
+    /// `locals: ` and the locals, then a tab, `stack: ` and the operand stack from the bottom, with
+    /// the values separated by `; `.
+    #[allow(dead_code)]
+    pub(crate) fn debug_print_values<B: Write>(
+        &self,
+        buf: &mut B,
+        locals: &Locals,
+    ) -> PartialVMResult<()> {
+        debug_write!(buf, "\tlocals: ")?;
+        values::debug::print_locals_inline(buf, locals, "; ")?;
+        debug_write!(buf, "\tstack: ")?;
+        for (idx, val) in self.operand_stack.0.iter().enumerate() {
+            if idx > 0 {
+                debug_write!(buf, "; ")?;
+            }
+            values::debug::print_value(buf, val)?;
+        }
+        Ok(())
+    }
+
     /// Generate a string which is the status of the interpreter: call stack, current bytecode
     /// stream, locals and operand stack.
     ///
@@ -44,6 +44,20 @@ impl<'a> NativeContextExtensions<'a> {
             .unwrap()
     }
 
+    /// The extension of type `T`, or `None` if the session was not given one
+    pub fn try_get<T: TidAble<'a>>(&self) -> Option<&T> {
+        self.map
+            .get(&T::id())
+            .map(|ext| ext.as_ref().downcast_ref::<T>().unwrap())
+    }
+
+    /// The extension of type `T`, or `None` if the session was not given one
+    pub fn try_get_mut<T: TidAble<'a>>(&mut self) -> Option<&mut T> {
+        self.map
+            .get_mut(&T::id())
+            .map(|ext| ext.as_mut().downcast_mut::<T>().unwrap())
+    }
+
     pub fn remove<T: TidAble<'a>>(&mut self) -> T {
         // can't use expect below because it requires `T: Debug`.
         match self
@@ -77,6 +91,7 @@ mod tests {
         *exts.get_mut::<Ext>().a += 1;
         assert_eq!(*exts.get_mut::<Ext>().a, 24);
         *exts.get_mut::<Ext>().a += 1;
+        assert_eq!(*exts.try_get::<Ext>().unwrap().a, 25);
         let e1 = exts.remove::<Ext>();
         assert_eq!(*e1.a, 25)
     }
@@ -32,6 +32,10 @@ const MOVE_VM_TRACING_ENV_VAR_NAME: &str = "MOVE_VM_TRACE";
 #[cfg(any(debug_assertions, feature = "debugging"))]
 const MOVE_VM_STEPPING_ENV_VAR_NAME: &str = "MOVE_VM_STEP";
 
+/// Also record the locals and the operand stack before each instruction in the trace
+#[cfg(any(debug_assertions, feature = "debugging"))]
+const MOVE_VM_TRACE_VALUES_ENV_VAR_NAME: &str = "MOVE_VM_TRACE_VALUES";
+
 #[cfg(any(debug_assertions, feature = "debugging"))]
 static FILE_PATH: Lazy<String> = Lazy::new(|| {
     env::var(MOVE_VM_TRACING_ENV_VAR_NAME).unwrap_or_else(|_| "move_vm_trace.trace".to_string())
@@ -40,6 +44,10 @@ static FILE_PATH: Lazy<String> = Lazy::new(|| {
 #[cfg(any(debug_assertions, feature = "debugging"))]
 static TRACING_ENABLED: Lazy<bool> = Lazy::new(|| env::var(MOVE_VM_TRACING_ENV_VAR_NAME).is_ok());
 
+#[cfg(any(debug_assertions, feature = "debugging"))]
+static TRACING_VALUES_ENABLED: Lazy<bool> =
+    Lazy::new(|| env::var(MOVE_VM_TRACE_VALUES_ENV_VAR_NAME).is_ok());
+
 #[cfg(any(debug_assertions, feature = "debugging"))]
 static DEBUGGING_ENABLED: Lazy<bool> =
     Lazy::new(|| env::var(MOVE_VM_STEPPING_ENV_VAR_NAME).is_ok());
@@ -70,15 +78,21 @@ pub(crate) fn trace(
     interp: &Interpreter,
 ) {
     if *TRACING_ENABLED {
+        // the values are only written if they can all be printed
+        let mut values = String::new();
+        if *TRACING_VALUES_ENABLED && interp.debug_print_values(&mut values, locals).is_err() {
+            values.clear();
+        }
         let f = &mut *LOGGING_FILE.lock().unwrap();
         writeln!(
             f,
-            "{}-{:?},{},{},{:?}",
+            "{}-{:?},{},{},{:?}{}",
             process::id(),
             thread::current().id(),
             function_desc.pretty_string(),
             pc,
             instr,
+            values,
         )
         .unwrap();
     }
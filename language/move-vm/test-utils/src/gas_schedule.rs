@@ -17,20 +17,22 @@ use move_binary_format::{
     file_format_common::{instruction_key, Opcodes},
 };
 use move_core_types::{
+    account_address::AccountAddress,
     gas_algebra::{
         AbstractMemorySize, GasQuantity, InternalGas, InternalGasPerAbstractMemoryUnit,
         InternalGasUnit, NumArgs, NumBytes, ToUnit, ToUnitFractional,
     },
-    language_storage::ModuleId,
+    language_storage::{ModuleId, CORE_CODE_ADDRESS},
     vm_status::StatusCode,
 };
 use move_vm_types::{
     gas::{GasMeter, SimpleInstruction},
-    views::{TypeView, ValueView},
+    views::{TypeView, ValueView, ValueVisitor},
 };
 use once_cell::sync::Lazy;
 use serde::{Deserialize, Serialize};
 use std::{
+    cmp,
     ops::{Add, Mul},
     u64,
 };
@@ -112,6 +114,9 @@ pub struct GasStatus<'a> {
     cost_table: &'a CostTable,
     gas_left: InternalGas,
     charge: bool,
+    memory_quota: Option<AbstractMemorySize>,
+    memory_used: AbstractMemorySize,
+    peak_memory: AbstractMemorySize,
 }
 
 impl<'a> GasStatus<'a> {
@@ -124,6 +129,9 @@ impl<'a> GasStatus<'a> {
             gas_left: gas_left.to_unit(),
             cost_table,
             charge: true,
+            memory_quota: None,
+            memory_used: 0.into(),
+            peak_memory: 0.into(),
         }
     }
 
@@ -136,9 +144,29 @@ impl<'a> GasStatus<'a> {
             gas_left: InternalGas::new(0),
             cost_table: &ZERO_COST_SCHEDULE,
             charge: false,
+            memory_quota: None,
+            memory_used: 0.into(),
+            peak_memory: 0.into(),
         }
     }
 
+    /// Fail with `MEMORY_QUOTA_EXCEEDED` once the vectors built by the execution hold more than
+    /// `quota`, whether metering is enabled or not.
+    ///
+    /// Memory is approximated with the abstract memory size of values: the size of the elements
+    /// added to or removed from vectors, and of the vectors copied. Vectors dropped or overwritten
+    /// as a whole are not seen by the gas meter, so their memory stays counted until the end of
+    /// the execution.
+    pub fn with_memory_quota(mut self, quota: AbstractMemorySize) -> Self {
+        self.memory_quota = Some(quota);
+        self
+    }
+
+    /// Return the most memory held at once by the vectors built by the execution.
+    pub fn peak_memory(&self) -> AbstractMemorySize {
+        self.peak_memory
+    }
+
     /// Return the `CostTable` behind this `GasStatus`.
     pub fn cost_table(&self) -> &CostTable {
         self.cost_table
@@ -149,6 +177,11 @@ impl<'a> GasStatus<'a> {
         self.gas_left.to_unit_round_down()
     }
 
+    /// Return the gas left in internal gas units, which measure the cost of single instructions.
+    pub fn remaining_internal_gas(&self) -> InternalGas {
+        self.gas_left
+    }
+
     /// Charge a given amount of gas and fail if not enough gas units are left.
     pub fn deduct_gas(&mut self, amount: InternalGas) -> PartialVMResult<()> {
         if !self.charge {
@@ -196,6 +229,67 @@ impl<'a> GasStatus<'a> {
     pub fn set_metering(&mut self, enabled: bool) {
         self.charge = enabled
     }
+
+    /// Account for `size` more memory held by vectors and fail if it exceeds the memory quota.
+    fn grow_memory(&mut self, size: AbstractMemorySize) -> PartialVMResult<()> {
+        self.memory_used += size;
+        self.peak_memory = cmp::max(self.peak_memory, self.memory_used);
+        match self.memory_quota {
+            Some(quota) if self.memory_used > quota => Err(PartialVMError::new(
+                StatusCode::MEMORY_QUOTA_EXCEEDED,
+            )
+            .with_message(format!(
+                "memory quota of {} bytes exceeded: about {} bytes in use",
+                quota, self.memory_used
+            ))),
+            _ => Ok(()),
+        }
+    }
+
+    fn shrink_memory(&mut self, size: AbstractMemorySize) {
+        self.memory_used = self
+            .memory_used
+            .checked_sub(size)
+            .unwrap_or_else(|| 0.into());
+    }
+}
+
+/// Whether `module_id::func_name` is the native `vector::push_back`, which grows a vector without
+/// going through the `VecPushBack` instruction
+fn is_native_push_back(module_id: &ModuleId, func_name: &str) -> bool {
+    module_id.address() == &CORE_CODE_ADDRESS
+        && module_id.name().as_str() == "vector"
+        && func_name == "push_back"
+}
+
+/// Whether `val` holds a vector, whose copies take memory proportional to its length
+fn holds_vector(val: &impl ValueView) -> bool {
+    struct HoldsVector(bool);
+
+    impl ValueVisitor for HoldsVector {
+        fn visit_u8(&mut self, _depth: usize, _val: u8) {}
+        fn visit_u64(&mut self, _depth: usize, _val: u64) {}
+        fn visit_u128(&mut self, _depth: usize, _val: u128) {}
+        fn visit_bool(&mut self, _depth: usize, _val: bool) {}
+        fn visit_address(&mut self, _depth: usize, _val: AccountAddress) {}
+
+        fn visit_struct(&mut self, _depth: usize, _len: usize) -> bool {
+            !self.0
+        }
+
+        fn visit_vec(&mut self, _depth: usize, _len: usize) -> bool {
+            self.0 = true;
+            false
+        }
+
+        fn visit_ref(&mut self, _depth: usize, _is_global: bool) -> bool {
+            false
+        }
+    }
+
+    let mut visitor = HoldsVector(false);
+    val.visit(&mut visitor);
+    visitor.0
 }
 
 fn get_simple_instruction_opcode(instr: SimpleInstruction) -> Opcodes {
@@ -275,15 +369,18 @@ impl<'b> GasMeter for GasStatus<'b> {
 
     fn charge_call_generic(
         &mut self,
-        _module_id: &ModuleId,
-        _func_name: &str,
+        module_id: &ModuleId,
+        func_name: &str,
         ty_args: impl ExactSizeIterator<Item = impl TypeView>,
         args: impl ExactSizeIterator<Item = impl ValueView>,
     ) -> PartialVMResult<()> {
-        self.charge_instr_with_size(
-            Opcodes::CALL_GENERIC,
-            ((ty_args.len() + args.len() + 1) as u64).into(),
-        )
+        let size = ((ty_args.len() + args.len() + 1) as u64).into();
+        if is_native_push_back(module_id, func_name) {
+            if let Some(element) = args.last() {
+                self.grow_memory(element.legacy_abstract_memory_size())?;
+            }
+        }
+        self.charge_instr_with_size(Opcodes::CALL_GENERIC, size)
     }
 
     fn charge_ld_const(&mut self, size: NumBytes) -> PartialVMResult<()> {
@@ -291,7 +388,11 @@ impl<'b> GasMeter for GasStatus<'b> {
     }
 
     fn charge_copy_loc(&mut self, val: impl ValueView) -> PartialVMResult<()> {
-        self.charge_instr_with_size(Opcodes::COPY_LOC, val.legacy_abstract_memory_size())
+        let size = val.legacy_abstract_memory_size();
+        if holds_vector(&val) {
+            self.grow_memory(size)?;
+        }
+        self.charge_instr_with_size(Opcodes::COPY_LOC, size)
     }
 
     fn charge_move_loc(&mut self, val: impl ValueView) -> PartialVMResult<()> {
@@ -339,7 +440,11 @@ impl<'b> GasMeter for GasStatus<'b> {
     }
 
     fn charge_read_ref(&mut self, ref_val: impl ValueView) -> PartialVMResult<()> {
-        self.charge_instr_with_size(Opcodes::READ_REF, ref_val.legacy_abstract_memory_size())
+        let size = ref_val.legacy_abstract_memory_size();
+        if holds_vector(&ref_val) {
+            self.grow_memory(size)?;
+        }
+        self.charge_instr_with_size(Opcodes::READ_REF, size)
     }
 
     fn charge_write_ref(&mut self, val: impl ValueView) -> PartialVMResult<()> {
@@ -451,7 +556,12 @@ impl<'b> GasMeter for GasStatus<'b> {
         _ty: impl TypeView + 'a,
         args: impl ExactSizeIterator<Item = impl ValueView>,
     ) -> PartialVMResult<()> {
-        self.charge_instr_with_size(Opcodes::VEC_PACK, (args.len() as u64).into())
+        let num_args = (args.len() as u64).into();
+        let size = args.fold(AbstractMemorySize::new(0), |acc, val| {
+            acc + val.legacy_abstract_memory_size()
+        });
+        self.grow_memory(size)?;
+        self.charge_instr_with_size(Opcodes::VEC_PACK, num_args)
     }
 
     fn charge_vec_len(&mut self, _ty: impl TypeView) -> PartialVMResult<()> {
@@ -478,14 +588,19 @@ impl<'b> GasMeter for GasStatus<'b> {
         _ty: impl TypeView,
         val: impl ValueView,
     ) -> PartialVMResult<()> {
-        self.charge_instr_with_size(Opcodes::VEC_PUSH_BACK, val.legacy_abstract_memory_size())
+        let size = val.legacy_abstract_memory_size();
+        self.grow_memory(size)?;
+        self.charge_instr_with_size(Opcodes::VEC_PUSH_BACK, size)
     }
 
     fn charge_vec_pop_back(
         &mut self,
         _ty: impl TypeView,
-        _val: Option<impl ValueView>,
+        val: Option<impl ValueView>,
     ) -> PartialVMResult<()> {
+        if let Some(val) = val {
+            self.shrink_memory(val.legacy_abstract_memory_size());
+        }
         self.charge_instr(Opcodes::VEC_POP_BACK)
     }
 
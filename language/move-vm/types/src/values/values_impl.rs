@@ -2357,6 +2357,21 @@ pub mod debug {
     pub fn print_value<B: Write>(buf: &mut B, val: &Value) -> PartialVMResult<()> {
         print_value_impl(buf, &val.0)
     }
+
+    /// Print the locals on one line, separated by `separator`
+    pub fn print_locals_inline<B: Write>(
+        buf: &mut B,
+        locals: &Locals,
+        separator: &str,
+    ) -> PartialVMResult<()> {
+        for (idx, val) in locals.0.borrow().iter().enumerate() {
+            if idx > 0 {
+                debug_write!(buf, "{}", separator)?;
+            }
+            print_value_impl(buf, val)?;
+        }
+        Ok(())
+    }
 }
 
 /***************************************************************************************
@@ -0,0 +1,339 @@
+// Copyright (c) The Move Contributors
+// SPDX-License-Identifier: Apache-2.0
+
+use super::package_root;
+use crate::utils::{
+    http_cache::{HttpCache, HttpCacheOptions},
+    movey_credential, prompt,
+};
+use anyhow::{bail, Context, Result};
+use chrono::Utc;
+use clap::*;
+use move_command_line_common::{env::move_home, files::read_utf8_file};
+use move_package::{
+    resolution::registry::{self, LOCAL_REGISTRY_NAME, REGISTRY_INDEX_FILE},
+    source_package::{
+        layout::SourcePackageLayout,
+        manifest_parser::{parse_version_requirement_str, parse_version_str},
+        parsed_manifest::{Version, VersionRequirement},
+    },
+};
+use reqwest::Url;
+use serde::Deserialize;
+use std::{
+    collections::BTreeMap,
+    fs,
+    path::{Path, PathBuf},
+};
+use toml_edit::{Document, InlineTable, Item, Table, Value};
+
+const EXAMPLES: &str = "EXAMPLES:
+    Add a dependency on a package of the registry, picking among the packages matching the query:
+        move package add coin
+
+    Add version 1.4.2 of the package named exactly `coin`, failing instead of asking if the
+    registry lists several such packages:
+        move package add coin --exact --version =1.4.2
+
+    Add a dev dependency, picked among the packages listed in a local index:
+        move package add test_utils --dev-dependency --index ./registry-index.json
+
+    Add a package of the registry kept in a directory, for machines without network access:
+        move package add coin --exact --registry-path /srv/move-registry";
+
+/// Add a dependency on a package of the registry to the manifest. When several packages, or several
+/// major versions of a package, match the query, the one to add is picked interactively, unless
+/// `--exact` or `--version` is given. With `--registry-path`, the package is looked up in the
+/// registry directory and the dependency is taken from it.
+#[derive(Parser)]
+#[clap(name = "add", after_help = EXAMPLES)]
+pub struct Add {
+    /// The name of the package to add, or part of it
+    pub query: String,
+    /// Only consider the package named exactly `query`, and fail instead of asking when several of
+    /// its major versions remain
+    #[clap(long = "exact")]
+    pub exact: bool,
+    /// Only consider the versions matching this requirement, such as `1.2.0` or `>=1.5, <2`, and
+    /// fail instead of asking when several candidates remain
+    #[clap(long = "version")]
+    pub version: Option<String>,
+    /// Add the package to the `[dev-dependencies]` instead of the `[dependencies]`
+    #[clap(long = "dev-dependency")]
+    pub dev_dependency: bool,
+    /// Look the package up in this JSON index, in the format of the responses of the registry,
+    /// instead of querying the registry
+    #[clap(long = "index", parse(from_os_str))]
+    pub index: Option<PathBuf>,
+    #[clap(flatten)]
+    pub cache: HttpCacheOptions,
+}
+
+/// A version of a package, as listed by the registry
+#[derive(Debug, Clone, Deserialize)]
+pub struct RegistryPackage {
+    pub name: String,
+    #[serde(default)]
+    pub owner: String,
+    pub version: String,
+    #[serde(default)]
+    pub description: String,
+    /// The repository holding the package, and the commit of this version. Registry directories
+    /// hold the package itself instead.
+    #[serde(default)]
+    pub git: String,
+    #[serde(default)]
+    pub rev: String,
+    /// The directory of the package in the repository, if not at its root
+    pub subdir: Option<String>,
+}
+
+impl Add {
+    pub fn execute(self, path: Option<PathBuf>) -> Result<()> {
+        let root = package_root(path.as_deref())?;
+        let registry_path = registry::registry_path().filter(|_| self.index.is_none());
+        let (registry, packages) = match (&self.index, &registry_path) {
+            (Some(index), _) => (index.display().to_string(), read_index(index)?),
+            (None, Some(registry_path)) => (
+                registry_path.display().to_string(),
+                read_index(&registry_path.join(REGISTRY_INDEX_FILE))?,
+            ),
+            (None, None) => {
+                let move_home = move_home()?;
+                let registry = movey_credential::get_movey_url(&move_home)?;
+                let cache = HttpCache::new(&move_home, &self.cache)?;
+                let packages = search_registry(&cache, &registry, &self.query)?;
+                (registry, packages)
+            }
+        };
+        let requirement = match &self.version {
+            Some(version) => Some(parse_version_requirement_str(version)?),
+            None => None,
+        };
+
+        let candidates = candidates(&packages, &self.query, self.exact, requirement.as_ref())?;
+        let chosen = match candidates.as_slice() {
+            [] => bail!("No package of {} matches '{}'", registry, self.query),
+            [candidate] => candidate,
+            _ if self.exact || requirement.is_some() => bail!(
+                "'{}' matches {} packages of {}:\n{}\nNarrow the query down with --exact or \
+                 --version",
+                self.query,
+                candidates.len(),
+                registry,
+                candidates
+                    .iter()
+                    .map(|candidate| format!("    {}", describe(candidate)))
+                    .collect::<Vec<_>>()
+                    .join("\n")
+            ),
+            _ => {
+                let options: Vec<_> = candidates.iter().map(describe).collect();
+                let question = format!(
+                    "'{}' matches {} packages of {}. Which one should be added?",
+                    self.query,
+                    candidates.len(),
+                    registry
+                );
+                &candidates[prompt::select(&question, &options)?]
+            }
+        };
+
+        let section = if self.dev_dependency {
+            "dev-dependencies"
+        } else {
+            "dependencies"
+        };
+        add_dependency(&root, section, chosen, &registry, registry_path.is_some())?;
+        println!(
+            "Added {} {} to the [{}] of {}",
+            chosen.name,
+            chosen.version,
+            section,
+            SourcePackageLayout::Manifest.location_str()
+        );
+        Ok(())
+    }
+}
+
+/// The packages listed in the JSON index at `path`
+fn read_index(path: &Path) -> Result<Vec<RegistryPackage>> {
+    serde_json::from_str(&read_utf8_file(path)?)
+        .with_context(|| format!("Error parsing the index '{}'", path.display()))
+}
+
+/// The packages of the registry at `registry` matching `query`
+fn search_registry(cache: &HttpCache, registry: &str, query: &str) -> Result<Vec<RegistryPackage>> {
+    let mut url = Url::parse(registry)
+        .and_then(|url| url.join("api/v1/packages/search"))
+        .with_context(|| format!("Invalid registry URL '{}'", registry))?;
+    url.query_pairs_mut().append_pair("query", query);
+    let body = cache.get(url.as_str())?;
+    serde_json::from_str(&body)
+        .with_context(|| format!("Unexpected response from the registry to {}", url))
+}
+
+/// The latest version of each major version of the packages of `packages` named by `query`, or
+/// named exactly `query` if `exact` is set, and matching `requirement`, sorted by name and version.
+/// Package names are compared regardless of case, as the registry does not tell them apart.
+pub fn candidates(
+    packages: &[RegistryPackage],
+    query: &str,
+    exact: bool,
+    requirement: Option<&VersionRequirement>,
+) -> Result<Vec<RegistryPackage>> {
+    let query = query.to_lowercase();
+    let mut latest: BTreeMap<(String, u64), (Version, &RegistryPackage)> = BTreeMap::new();
+    for package in packages {
+        let name = package.name.to_lowercase();
+        let named = if exact {
+            name == query
+        } else {
+            name.contains(&query)
+        };
+        if !named {
+            continue;
+        }
+        let version = parse_version_str(&package.version).with_context(|| {
+            format!(
+                "The registry lists {} with the invalid version '{}'",
+                package.name, package.version
+            )
+        })?;
+        if !requirement.map_or(true, |requirement| requirement.matches(&version)) {
+            continue;
+        }
+        let entry = latest
+            .entry((name, version.0))
+            .or_insert((version, package));
+        if version > entry.0 {
+            *entry = (version, package);
+        }
+    }
+    Ok(latest
+        .into_values()
+        .map(|(_, package)| package.clone())
+        .collect())
+}
+
+/// The name, owner, version and description of `package`, on one line
+fn describe(package: &RegistryPackage) -> String {
+    let mut description = format!("{} {}", package.name, package.version);
+    if !package.owner.is_empty() {
+        description.push_str(&format!(" (by {})", package.owner));
+    }
+    if !package.description.is_empty() {
+        description.push_str(&format!(" - {}", package.description));
+    }
+    description
+}
+
+/// Add `package` to the `section` table of the manifest of the package at `root`, keeping the rest
+/// of the manifest, comments included, as it is. The dependency is preceded by a comment naming the
+/// registry it comes from and the date it is added. A package of a registry directory, when
+/// `from_registry_path` is set, is depended on by version, to be taken from the registry directory
+/// when the package is built.
+fn add_dependency(
+    root: &Path,
+    section: &str,
+    package: &RegistryPackage,
+    registry: &str,
+    from_registry_path: bool,
+) -> Result<()> {
+    let manifest_path = root.join(SourcePackageLayout::Manifest.path());
+    let mut manifest: Document = read_utf8_file(&manifest_path)?
+        .parse()
+        .with_context(|| format!("Error parsing '{}'", manifest_path.display()))?;
+    let table = manifest
+        .as_table_mut()
+        .entry(section)
+        .or_insert_with(|| Item::Table(Table::new()))
+        .as_table_mut()
+        .with_context(|| {
+            format!(
+                "[{}] of '{}' is not a table",
+                section,
+                manifest_path.display()
+            )
+        })?;
+    if table
+        .iter()
+        .any(|(name, _)| name.eq_ignore_ascii_case(&package.name))
+    {
+        bail!(
+            "The package already has {} among its [{}]",
+            package.name,
+            section
+        )
+    }
+
+    let mut dependency = InlineTable::new();
+    if from_registry_path {
+        dependency.insert("version", package.version.as_str().into());
+        dependency.insert("registry", LOCAL_REGISTRY_NAME.into());
+    } else {
+        dependency.insert("git", package.git.as_str().into());
+        dependency.insert("rev", package.rev.as_str().into());
+        if let Some(subdir) = &package.subdir {
+            dependency.insert("subdir", subdir.as_str().into());
+        }
+    }
+    table.insert(&package.name, Item::Value(Value::InlineTable(dependency)));
+    table
+        .key_decor_mut(&package.name)
+        .expect("the dependency was just inserted")
+        .set_prefix(format!(
+            "# {} {}, added from {} on {}\n",
+            package.name,
+            package.version,
+            registry,
+            Utc::now().format("%Y-%m-%d")
+        ));
+    fs::write(&manifest_path, manifest.to_string())
+        .with_context(|| format!("Unable to write '{}'", manifest_path.display()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn package(name: &str, owner: &str, version: &str) -> RegistryPackage {
+        RegistryPackage {
+            name: name.to_string(),
+            owner: owner.to_string(),
+            version: version.to_string(),
+            description: String::new(),
+            git: format!("https://github.com/{}/{}.git", owner, name),
+            rev: "main".to_string(),
+            subdir: None,
+        }
+    }
+
+    fn names(candidates: &[RegistryPackage]) -> Vec<String> {
+        candidates
+            .iter()
+            .map(|c| format!("{} {}", c.name, c.version))
+            .collect()
+    }
+
+    #[test]
+    fn candidates_are_the_latest_version_of_each_major_version() {
+        let packages = [
+            package("coin", "alice", "1.0.0"),
+            package("coin", "alice", "1.2.0"),
+            package("coin", "alice", "2.0.1"),
+            package("CoinSwap", "bob", "0.3.0"),
+            package("wallet", "bob", "1.0.0"),
+        ];
+        let all = candidates(&packages, "COIN", false, None).unwrap();
+        assert_eq!(
+            names(&all),
+            vec!["coin 1.2.0", "coin 2.0.1", "CoinSwap 0.3.0"]
+        );
+        let exact = candidates(&packages, "coin", true, None).unwrap();
+        assert_eq!(names(&exact), vec!["coin 1.2.0", "coin 2.0.1"]);
+        let requirement = parse_version_requirement_str("<2").unwrap();
+        let pinned = candidates(&packages, "coin", true, Some(&requirement)).unwrap();
+        assert_eq!(names(&pinned), vec!["coin 1.2.0"]);
+    }
+}
@@ -0,0 +1,510 @@
+// Copyright (c) The Move Contributors
+// SPDX-License-Identifier: Apache-2.0
+
+use super::package_root;
+use crate::{
+    sandbox::utils::AccountRegistry,
+    utils::storage_lock::{StorageAccess, StorageLock, StorageLockOptions},
+    DEFAULT_STORAGE_DIR,
+};
+use anyhow::{bail, Context, Result};
+use clap::*;
+use move_command_line_common::files::read_utf8_file;
+use move_core_types::{account_address::AccountAddress, identifier::Identifier};
+use move_package::source_package::layout::SourcePackageLayout;
+use move_stdlib::natives::unit_test::derive_address;
+use serde::{Deserialize, Serialize};
+use std::{
+    collections::BTreeMap,
+    fmt::Write as _,
+    fs,
+    path::{Path, PathBuf},
+    str::FromStr,
+};
+use toml_edit::{Document, Item, Table};
+
+const EXAMPLES: &str = "EXAMPLES:
+    Print the address tests get from `std::unit_test::derive_address(b\"alice\")`:
+        move address derive --seed alice
+
+    Print an address spelling out its seed in hexadecimal:
+        move address derive --seed alice --scheme named";
+
+const EXPORT_EXAMPLES: &str = "EXAMPLES:
+    Write the named addresses of the package and the sandbox accounts to a file:
+        move address export > addresses.toml
+
+    Write them as JSON:
+        move address export --format json > addresses.json";
+
+const IMPORT_EXAMPLES: &str = "EXAMPLES:
+    Assign the addresses of a file, such as the sandbox accounts exported, in Move.toml:
+        move address import addresses.toml --to-manifest
+
+    Create sandbox accounts at the addresses of a file, replacing the accounts of the same names:
+        move address import addresses.json --to-sandbox --force";
+
+/// How `move address derive` derives an address from its seed
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ArgEnum)]
+pub enum DeriveScheme {
+    // The first bytes of the SHA3-256 hash of the seed
+    Sha3,
+    // The bytes of the seed, padded with leading zeros
+    Named,
+}
+
+impl FromStr for DeriveScheme {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s {
+            "sha3" => Ok(DeriveScheme::Sha3),
+            "named" => Ok(DeriveScheme::Named),
+            _ => Err(anyhow::anyhow!("Invalid derivation scheme: {}", s)),
+        }
+    }
+}
+
+impl DeriveScheme {
+    pub(crate) fn variants() -> [&'static str; 2] {
+        ["sha3", "named"]
+    }
+}
+
+/// Print an address derived from a seed, for tests and scripts that need "some address" without
+/// inventing a scheme of their own.
+///
+/// The `sha3` scheme takes the first bytes of the SHA3-256 hash of the seed, the way the test-only
+/// `std::unit_test::derive_address` native does, so that addresses derived on the command line
+/// and in Move tests agree. The `named` scheme writes the bytes of the seed, padded with leading
+/// zeros, e.g. `alice` derives `0x...616c696365`.
+///
+/// Derivations are stable: a seed derives the same address with every release of the CLI, and a
+/// changed derivation would get a scheme name of its own. Addresses are as long as the addresses
+/// of the CLI build, 16 bytes unless it was built with the `address20` or `address32` feature.
+#[derive(Parser)]
+#[clap(name = "derive", after_help = EXAMPLES)]
+pub struct Derive {
+    /// The seed to derive the address from, as UTF-8 text
+    #[clap(long = "seed")]
+    pub seed: String,
+    /// How to derive the address: `sha3` or `named`
+    #[clap(
+        long = "scheme",
+        possible_values = DeriveScheme::variants(),
+        ignore_case = true,
+        default_value = "sha3"
+    )]
+    pub scheme: DeriveScheme,
+}
+
+impl Derive {
+    pub fn execute(self) -> Result<()> {
+        let address = derive(self.seed.as_bytes(), self.scheme)?;
+        println!("0x{}", address.to_hex());
+        Ok(())
+    }
+}
+
+/// The address `scheme` derives from `seed`
+pub fn derive(seed: &[u8], scheme: DeriveScheme) -> Result<AccountAddress> {
+    match scheme {
+        DeriveScheme::Sha3 => Ok(derive_address(seed)),
+        DeriveScheme::Named => {
+            if seed.len() > AccountAddress::LENGTH {
+                bail!(
+                    "The seed is {} bytes long, the named scheme only fits seeds of up to {} bytes \
+                     in an address",
+                    seed.len(),
+                    AccountAddress::LENGTH
+                )
+            }
+            let mut bytes = [0u8; AccountAddress::LENGTH];
+            bytes[AccountAddress::LENGTH - seed.len()..].copy_from_slice(seed);
+            Ok(AccountAddress::new(bytes))
+        }
+    }
+}
+
+/// The format of an address book
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ArgEnum)]
+pub enum AddressBookFormat {
+    Toml,
+    Json,
+}
+
+impl FromStr for AddressBookFormat {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s {
+            "toml" => Ok(AddressBookFormat::Toml),
+            "json" => Ok(AddressBookFormat::Json),
+            _ => Err(anyhow::anyhow!("Invalid address book format: {}", s)),
+        }
+    }
+}
+
+impl AddressBookFormat {
+    pub(crate) fn variants() -> [&'static str; 2] {
+        ["toml", "json"]
+    }
+}
+
+/// The named addresses of a package and the accounts of its sandbox, as written by
+/// `move address export`
+#[derive(Debug, Default, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct AddressBook {
+    /// The named addresses assigned in the `[addresses]` of the manifest
+    #[serde(default)]
+    pub addresses: BTreeMap<String, String>,
+    /// The accounts created by `sandbox account create`, by name
+    #[serde(default)]
+    pub accounts: BTreeMap<String, String>,
+}
+
+impl AddressBook {
+    /// The book in TOML, with a table for the named addresses and one for the accounts
+    pub fn to_toml(&self) -> String {
+        let mut document = Document::new();
+        for (section, addresses) in [("addresses", &self.addresses), ("accounts", &self.accounts)] {
+            let mut table = Table::new();
+            for (name, address) in addresses {
+                table.insert(name, toml_edit::value(address.as_str()));
+            }
+            document.insert(section, Item::Table(table));
+        }
+        document.to_string()
+    }
+}
+
+/// Print the named addresses assigned in the manifest of the package and the accounts of its
+/// sandbox as one address book, to keep them consistent with `move address import`.
+#[derive(Parser)]
+#[clap(name = "export", after_help = EXPORT_EXAMPLES)]
+pub struct Export {
+    /// The format to print the address book in: `toml` or `json`
+    #[clap(
+        long = "format",
+        possible_values = AddressBookFormat::variants(),
+        ignore_case = true,
+        default_value = "toml"
+    )]
+    pub format: AddressBookFormat,
+    /// The storage directory of the sandbox, relative to the package root
+    #[clap(long = "storage-dir", default_value = DEFAULT_STORAGE_DIR, parse(from_os_str))]
+    pub storage_dir: PathBuf,
+}
+
+impl Export {
+    pub fn execute(self, path: Option<PathBuf>) -> Result<()> {
+        let root = package_root(path.as_deref())?;
+        let mut book = AddressBook::default();
+        for (name, address) in manifest_addresses(&read_manifest(&root)?)? {
+            book.addresses.insert(name, address.to_hex_literal());
+        }
+        let storage_dir = root.join(&self.storage_dir);
+        let _lock = StorageLock::acquire(
+            &storage_dir,
+            StorageAccess::Shared,
+            "address export",
+            &StorageLockOptions::default(),
+        )?;
+        for account in AccountRegistry::load(&storage_dir)?.accounts() {
+            book.accounts
+                .insert(account.name.clone(), account.address.clone());
+        }
+        match self.format {
+            AddressBookFormat::Toml => print!("{}", book.to_toml()),
+            AddressBookFormat::Json => println!("{}", serde_json::to_string_pretty(&book)?),
+        }
+        Ok(())
+    }
+}
+
+/// Apply the addresses of an address book, such as one written by `move address export`, to the
+/// `[addresses]` of the manifest of the package or to the accounts of its sandbox. Both the named
+/// addresses and the accounts of the book are applied. A name the target already assigns another
+/// address is a conflict: nothing is changed unless the global `--force` is passed to replace the
+/// address.
+#[derive(Parser)]
+#[clap(
+    name = "import",
+    after_help = IMPORT_EXAMPLES,
+    group(ArgGroup::new("target").required(true).args(&["to-manifest", "to-sandbox"]))
+)]
+pub struct Import {
+    /// The address book to import, in TOML, or in JSON if its name ends with `.json`
+    #[clap(parse(from_os_str))]
+    pub file: PathBuf,
+    /// Assign the addresses in the `[addresses]` of the manifest
+    #[clap(long = "to-manifest")]
+    pub to_manifest: bool,
+    /// Record the addresses as accounts of the sandbox, referred to as `@<name>`
+    #[clap(long = "to-sandbox")]
+    pub to_sandbox: bool,
+    /// The storage directory of the sandbox, relative to the package root
+    #[clap(long = "storage-dir", default_value = DEFAULT_STORAGE_DIR, parse(from_os_str))]
+    pub storage_dir: PathBuf,
+}
+
+/// The effect of importing an address on the target
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Change {
+    Added,
+    Unchanged,
+    /// The target assigned the name this other address
+    Replaced(AccountAddress),
+}
+
+impl Import {
+    /// With `force`, replace the addresses the target assigns differently instead of failing
+    pub fn execute(self, path: Option<PathBuf>, force: bool) -> Result<()> {
+        let root = package_root(path.as_deref())?;
+        let addresses = read_address_book(&self.file)?;
+        if self.to_manifest {
+            let mut manifest = read_manifest(&root)?;
+            let current = manifest_addresses(&manifest)?;
+            let changes = self.plan(
+                &addresses,
+                |name| current.get(name).copied(),
+                "Move.toml",
+                force,
+            )?;
+            let table = manifest
+                .as_table_mut()
+                .entry("addresses")
+                .or_insert_with(|| Item::Table(Table::new()))
+                .as_table_mut()
+                .context("[addresses] of Move.toml is not a table")?;
+            for (name, address, change) in &changes {
+                if *change == Change::Unchanged {
+                    continue;
+                }
+                let mut value = toml_edit::Value::from(address.to_hex_literal());
+                // replaced in place, to keep the comments of the entry
+                match table.get_mut(name).and_then(|item| item.as_value_mut()) {
+                    Some(current) => {
+                        *value.decor_mut() = current.decor().clone();
+                        *current = value;
+                    }
+                    None => {
+                        table.insert(name, Item::Value(value));
+                    }
+                }
+            }
+            let manifest_path = root.join(SourcePackageLayout::Manifest.path());
+            fs::write(&manifest_path, manifest.to_string())
+                .with_context(|| format!("Unable to write '{}'", manifest_path.display()))?;
+            report(&changes, "Move.toml");
+        } else {
+            let storage_dir = root.join(&self.storage_dir);
+            let _lock = StorageLock::acquire(
+                &storage_dir,
+                StorageAccess::Exclusive,
+                "address import",
+                &StorageLockOptions::default(),
+            )?;
+            let mut registry = AccountRegistry::load(&storage_dir)?;
+            let current = registry
+                .accounts()
+                .iter()
+                .map(|account| {
+                    Ok((
+                        account.name.clone(),
+                        AccountAddress::from_hex_literal(&account.address)?,
+                    ))
+                })
+                .collect::<Result<BTreeMap<_, _>>>()?;
+            let changes = self.plan(
+                &addresses,
+                |name| current.get(name).copied(),
+                "the sandbox",
+                force,
+            )?;
+            for (name, address, change) in &changes {
+                if *change != Change::Unchanged {
+                    registry.import(name, *address)?;
+                }
+            }
+            registry.save(&storage_dir)?;
+            report(&changes, "the sandbox");
+        }
+        Ok(())
+    }
+
+    /// How importing `addresses` changes a target assigning `current(name)` to each name. Fails
+    /// listing the conflicts, unless `force` is set.
+    fn plan(
+        &self,
+        addresses: &BTreeMap<String, AccountAddress>,
+        current: impl Fn(&str) -> Option<AccountAddress>,
+        target: &str,
+        force: bool,
+    ) -> Result<Vec<(String, AccountAddress, Change)>> {
+        let mut changes = vec![];
+        let mut conflicts = 0;
+        let mut listing = String::new();
+        for (name, address) in addresses {
+            if self.to_manifest && !Identifier::is_valid(name) {
+                bail!(
+                    "'{}' of '{}' cannot be a named address: names of addresses are identifiers",
+                    name,
+                    self.file.display()
+                )
+            }
+            let change = match current(name) {
+                None => Change::Added,
+                Some(current) if current == *address => Change::Unchanged,
+                Some(current) => {
+                    conflicts += 1;
+                    writeln!(
+                        listing,
+                        "    {}: {} in {}, {} in '{}'",
+                        name,
+                        current.to_hex_literal(),
+                        target,
+                        address.to_hex_literal(),
+                        self.file.display()
+                    )?;
+                    Change::Replaced(current)
+                }
+            };
+            changes.push((name.clone(), *address, change));
+        }
+        if conflicts > 0 && !force {
+            bail!(
+                "'{}' assigns {} name(s) another address than {} does:\n{}Pass --force to \
+                 replace the addresses of {}",
+                self.file.display(),
+                conflicts,
+                target,
+                listing,
+                target
+            )
+        }
+        Ok(changes)
+    }
+}
+
+fn report(changes: &[(String, AccountAddress, Change)], target: &str) {
+    let mut added = 0;
+    let mut replaced = 0;
+    for (name, address, change) in changes {
+        match change {
+            Change::Added => {
+                added += 1;
+                println!("    added {} = {}", name, address.to_hex_literal());
+            }
+            Change::Replaced(previous) => {
+                replaced += 1;
+                println!(
+                    "    replaced {} = {} (was {})",
+                    name,
+                    address.to_hex_literal(),
+                    previous.to_hex_literal()
+                );
+            }
+            Change::Unchanged => (),
+        }
+    }
+    println!(
+        "Imported {} address(es) into {}: {} added, {} replaced, {} unchanged",
+        changes.len(),
+        target,
+        added,
+        replaced,
+        changes.len() - added - replaced
+    );
+}
+
+/// The addresses of the address book at `path`, named addresses and accounts together
+fn read_address_book(path: &Path) -> Result<BTreeMap<String, AccountAddress>> {
+    let contents = read_utf8_file(path)?;
+    let book: AddressBook = if path.extension().map_or(false, |ext| ext == "json") {
+        serde_json::from_str(&contents)
+            .with_context(|| format!("Error parsing '{}'", path.display()))?
+    } else {
+        toml_edit::easy::from_str(&contents)
+            .with_context(|| format!("Error parsing '{}'", path.display()))?
+    };
+    let mut addresses = BTreeMap::new();
+    for (name, address) in book.addresses.iter().chain(book.accounts.iter()) {
+        let address = AccountAddress::from_hex_literal(address).with_context(|| {
+            format!(
+                "Invalid address '{}' for '{}' in '{}'",
+                address,
+                name,
+                path.display()
+            )
+        })?;
+        if let Some(other) = addresses.insert(name.clone(), address) {
+            if other != address {
+                bail!(
+                    "'{}' assigns '{}' two addresses, {} and {}",
+                    path.display(),
+                    name,
+                    other.to_hex_literal(),
+                    address.to_hex_literal()
+                )
+            }
+        }
+    }
+    Ok(addresses)
+}
+
+fn read_manifest(root: &Path) -> Result<Document> {
+    let manifest_path = root.join(SourcePackageLayout::Manifest.path());
+    read_utf8_file(&manifest_path)?
+        .parse()
+        .with_context(|| format!("Error parsing '{}'", manifest_path.display()))
+}
+
+/// The named addresses the `[addresses]` of `manifest` assign, leaving out the ones left to assign
+/// with `_`
+fn manifest_addresses(manifest: &Document) -> Result<BTreeMap<String, AccountAddress>> {
+    let mut addresses = BTreeMap::new();
+    let table = match manifest.get("addresses") {
+        Some(table) => table
+            .as_table_like()
+            .context("[addresses] of Move.toml is not a table")?,
+        None => return Ok(addresses),
+    };
+    for (name, value) in table.iter() {
+        let value = value
+            .as_str()
+            .with_context(|| format!("The address of '{}' in Move.toml is not a string", name))?;
+        if value == "_" {
+            continue;
+        }
+        let address = AccountAddress::from_hex_literal(value)
+            .with_context(|| format!("Invalid address '{}' for '{}' in Move.toml", value, name))?;
+        addresses.insert(name.to_string(), address);
+    }
+    Ok(addresses)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn sha3_addresses_are_stable() {
+        let address = derive(b"alice", DeriveScheme::Sha3).unwrap();
+        let hash = "a7dcef9aef26202fce82a7c7d6672afb3a149db207d90a07e437d5abc7fc99ed";
+        assert_eq!(address.to_hex(), hash[..AccountAddress::LENGTH * 2]);
+    }
+
+    #[test]
+    fn named_addresses_spell_out_their_seed() {
+        let address = derive(b"alice", DeriveScheme::Named).unwrap();
+        assert_eq!(address.short_str_lossless(), "616c696365");
+        assert!(derive(&[b'a'; AccountAddress::LENGTH], DeriveScheme::Named).is_ok());
+        let err = derive(&[b'a'; AccountAddress::LENGTH + 1], DeriveScheme::Named).unwrap_err();
+        assert!(
+            err.to_string().contains("only fits seeds of up to"),
+            "{}",
+            err
+        );
+    }
+}
@@ -0,0 +1,335 @@
+// Copyright (c) The Move Contributors
+// SPDX-License-Identifier: Apache-2.0
+
+use super::{package_root, test::build_test_plan, warn_diff::build_at_revision};
+use crate::NativeFunctionRecord;
+use anyhow::{bail, Result};
+use clap::*;
+use move_package::BuildConfig;
+use move_unit_test::UnitTestingConfig;
+use std::{
+    collections::BTreeMap,
+    path::{Path, PathBuf},
+};
+
+const EXAMPLES: &str = "EXAMPLES:
+    Time the tests annotated #[gas_benchmark]:
+        move package bench
+
+    Compare with the benchmarks of the main branch, failing if one got more than 10% slower:
+        move package bench --compare main --threshold 10
+
+    Time every test of the package, 50 times each:
+        move package bench --all --samples 50";
+
+/// Time the unit tests annotated `#[test, gas_benchmark]`, running each of them several times and
+/// printing the mean time of a run with its standard deviation. With `--compare`, the benchmarks
+/// of the package are also run as of another git revision, checked out in a temporary worktree,
+/// and the times printed side by side with their change and how significant it is.
+#[derive(Parser)]
+#[clap(name = "bench", after_help = EXAMPLES)]
+pub struct Bench {
+    /// Time every test of the package instead of the `#[gas_benchmark]` ones
+    #[clap(long = "all")]
+    pub all: bool,
+    /// How many times to run each benchmark, after a first run which is not counted
+    #[clap(long = "samples", default_value = "30")]
+    pub samples: usize,
+    /// The git revision (e.g. `main` or `HEAD~1`) to compare with, whose benchmarks are built and
+    /// run in a temporary worktree
+    #[clap(long = "compare", value_name = "REV")]
+    pub compare: Option<String>,
+    /// Fail if a benchmark got slower than at the compared revision by more than this percentage,
+    /// and significantly so
+    #[clap(long = "threshold", value_name = "PERCENT", requires = "compare")]
+    pub threshold: Option<f64>,
+    /// Bound the number of instructions that can be executed by any one benchmark
+    #[clap(name = "instructions", short = 'i', long = "instructions")]
+    pub instruction_execution_bound: Option<u64>,
+}
+
+/// The times of the runs of a benchmark, in nanoseconds
+#[derive(Debug, Clone, Default)]
+struct Samples(Vec<f64>);
+
+impl Samples {
+    fn mean(&self) -> f64 {
+        self.0.iter().sum::<f64>() / self.0.len() as f64
+    }
+
+    /// The unbiased estimate of the variance of the times
+    fn variance(&self) -> f64 {
+        let mean = self.mean();
+        self.0.iter().map(|time| (time - mean).powi(2)).sum::<f64>() / (self.0.len() - 1) as f64
+    }
+
+    /// The mean time and its standard deviation, e.g. `1.25ms ± 0.03ms`
+    fn summary(&self) -> String {
+        format!(
+            "{} ± {}",
+            format_time(self.mean()),
+            format_time(self.variance().sqrt())
+        )
+    }
+}
+
+impl Bench {
+    pub fn execute(
+        self,
+        path: Option<PathBuf>,
+        config: BuildConfig,
+        natives: Vec<NativeFunctionRecord>,
+    ) -> Result<()> {
+        if self.samples < 2 {
+            bail!("--samples must be at least 2, to estimate how much the times vary")
+        }
+        let root = package_root(path.as_deref())?;
+        let current = self.run_benchmarks(&root, config.clone(), &natives)?;
+        if current.is_empty() {
+            bail!(
+                "The package has no benchmarks. Annotate the tests to time with \
+                 #[test, gas_benchmark], or pass --all to time every test"
+            )
+        }
+        let rev = match &self.compare {
+            None => {
+                println!(
+                    "\n{:<48} {:>24}",
+                    "Benchmark",
+                    format!("Time ({} samples)", self.samples)
+                );
+                for (benchmark, samples) in &current {
+                    println!("{:<48} {:>24}", benchmark, samples.summary());
+                }
+                return Ok(());
+            }
+            Some(rev) => rev,
+        };
+
+        println!("\nRunning the benchmarks as of '{}'", rev);
+        let baseline = build_at_revision(&root, rev, config, |root, config| {
+            self.run_benchmarks(root, config, &natives)
+        })?;
+        let regressions = self.print_comparison(rev, &baseline, &current);
+        if !regressions.is_empty() {
+            bail!(
+                "{} benchmark(s) got significantly slower than at '{}' by more than {}%: {}",
+                regressions.len(),
+                rev,
+                self.threshold.unwrap(),
+                regressions.join(", ")
+            )
+        }
+        Ok(())
+    }
+
+    /// Time the benchmarks of the package at `root`, by test as `<address>::<module>::<test>`.
+    /// Fails if a benchmark fails.
+    fn run_benchmarks(
+        &self,
+        root: &Path,
+        mut config: BuildConfig,
+        natives: &[NativeFunctionRecord],
+    ) -> Result<BTreeMap<String, Samples>> {
+        config.test_mode = true;
+        config.dev_mode = true;
+        // one test at a time, for the benchmarks not to slow each other down
+        let mut unit_test_config = UnitTestingConfig {
+            num_threads: 1,
+            ..UnitTestingConfig::default_with_bound(self.instruction_execution_bound)
+        };
+        let mut test_plan = build_test_plan(
+            root,
+            config,
+            &mut unit_test_config,
+            natives,
+            &mut std::io::stdout(),
+        )?;
+        if !self.all {
+            for module_tests in test_plan.module_tests.values_mut() {
+                module_tests.tests.retain(|_, test| test.gas_benchmark);
+            }
+        }
+        test_plan
+            .module_tests
+            .retain(|_, module_tests| !module_tests.tests.is_empty());
+
+        let mut times = BTreeMap::new();
+        if test_plan.module_tests.is_empty() {
+            return Ok(times);
+        }
+        // the first run warms up the caches and is not counted
+        for run in 0..=self.samples {
+            let (_, results) = unit_test_config.run_and_collect_unit_tests(
+                test_plan.clone(),
+                Some(natives.to_vec()),
+                std::io::sink(),
+            )?;
+            let results = results.expect("tests are run, not listed");
+            let failed: Vec<_> = results
+                .failed_tests()
+                .iter()
+                .flat_map(|(module_id, tests)| {
+                    tests.iter().map(move |test| {
+                        format!(
+                            "{}::{}",
+                            module_id.short_str_lossless(),
+                            test.test_run_info.function_ident
+                        )
+                    })
+                })
+                .collect();
+            if !failed.is_empty() {
+                bail!(
+                    "{} benchmark(s) failed, run `move test` to see why: {}",
+                    failed.len(),
+                    failed.join(", ")
+                )
+            }
+            if run == 0 {
+                continue;
+            }
+            for (module_id, tests) in results.passed_tests() {
+                for test in tests {
+                    times
+                        .entry(format!(
+                            "{}::{}",
+                            module_id.short_str_lossless(),
+                            test.function_ident
+                        ))
+                        .or_insert_with(Samples::default)
+                        .0
+                        .push(test.elapsed_time.as_nanos() as f64);
+                }
+            }
+        }
+        Ok(times)
+    }
+
+    /// Print the times of the benchmarks at `rev` and now side by side, followed by the benchmarks
+    /// only one of them has. Returns the benchmarks which got slower by more than the threshold,
+    /// and significantly so, each with its change.
+    fn print_comparison(
+        &self,
+        rev: &str,
+        baseline: &BTreeMap<String, Samples>,
+        current: &BTreeMap<String, Samples>,
+    ) -> Vec<String> {
+        let mut regressions = vec![];
+        println!(
+            "\n{:<48} {:>24} {:>24} {:>9}",
+            "Benchmark", rev, "Current", "Change"
+        );
+        for (benchmark, now) in current {
+            let before = match baseline.get(benchmark) {
+                Some(before) => before,
+                None => continue,
+            };
+            let change = (now.mean() - before.mean()) * 100.0 / before.mean();
+            let significance = significance(before, now);
+            if !significance.is_empty() && self.threshold.map_or(false, |max| change > max) {
+                regressions.push(format!("{} ({:+.1}%)", benchmark, change));
+            }
+            println!(
+                "{:<48} {:>24} {:>24} {:>+8.1}% {}",
+                benchmark,
+                before.summary(),
+                now.summary(),
+                change,
+                significance
+            );
+        }
+        println!(
+            "\n(*, ** and ***: the change is significant, with less than a 5%, 1% and 0.1% chance \
+             of being noise)"
+        );
+
+        for (heading, benchmarks, others) in [
+            (format!("Only at '{}':", rev), baseline, current),
+            (
+                "Only in the current package:".to_string(),
+                current,
+                baseline,
+            ),
+        ] {
+            let only: Vec<_> = benchmarks
+                .keys()
+                .filter(|benchmark| !others.contains_key(*benchmark))
+                .collect();
+            if !only.is_empty() {
+                println!("\n{}", heading);
+                for benchmark in only {
+                    println!("    {}", benchmark);
+                }
+            }
+        }
+        regressions
+    }
+}
+
+/// How significant the difference between the mean times of `before` and `now` is, by Welch's
+/// t-test with the statistic judged by the normal distribution: `***`, `**` and `*` when a
+/// difference this large arises by chance less than 0.1%, 1% and 5% of the time, and nothing when
+/// it may well be noise.
+fn significance(before: &Samples, now: &Samples) -> &'static str {
+    let difference = (now.mean() - before.mean()).abs();
+    let standard_error =
+        (before.variance() / before.0.len() as f64 + now.variance() / now.0.len() as f64).sqrt();
+    let t = if standard_error == 0.0 {
+        if difference == 0.0 {
+            0.0
+        } else {
+            f64::INFINITY
+        }
+    } else {
+        difference / standard_error
+    };
+    if t > 3.29 {
+        "***"
+    } else if t > 2.58 {
+        "**"
+    } else if t > 1.96 {
+        "*"
+    } else {
+        ""
+    }
+}
+
+/// A time in nanoseconds, in the largest unit it is at least one of
+fn format_time(nanos: f64) -> String {
+    if nanos >= 1e9 {
+        format!("{:.2}s", nanos / 1e9)
+    } else if nanos >= 1e6 {
+        format!("{:.2}ms", nanos / 1e6)
+    } else if nanos >= 1e3 {
+        format!("{:.2}µs", nanos / 1e3)
+    } else {
+        format!("{:.0}ns", nanos)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn significance_grows_with_the_difference_over_the_noise() {
+        let around = |mean: f64| Samples((0..30).map(|i| mean + (i % 3) as f64).collect());
+        assert_eq!(significance(&around(100.0), &around(100.0)), "");
+        assert_eq!(significance(&around(100.0), &around(100.2)), "");
+        assert_eq!(significance(&around(100.0), &around(100.5)), "*");
+        assert_eq!(significance(&around(100.0), &around(110.0)), "***");
+        assert_eq!(
+            significance(&Samples(vec![5.0, 5.0]), &Samples(vec![6.0, 6.0])),
+            "***"
+        );
+    }
+
+    #[test]
+    fn times_are_printed_in_the_largest_unit() {
+        assert_eq!(format_time(999.0), "999ns");
+        assert_eq!(format_time(1_500.0), "1.50µs");
+        assert_eq!(format_time(2_345_678.0), "2.35ms");
+        assert_eq!(format_time(3e9), "3.00s");
+    }
+}
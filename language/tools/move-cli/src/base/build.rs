@@ -1,32 +1,131 @@
 // Copyright (c) The Move Contributors
 // SPDX-License-Identifier: Apache-2.0
 
-use super::reroot_path;
+use super::{
+    reroot_path,
+    workspace::{run_workspace, MemberOutcome, WorkspaceOptions},
+};
+use anyhow::{bail, Context};
 use clap::*;
-use move_package::{Architecture, BuildConfig};
-use std::path::PathBuf;
+use codespan_reporting::diagnostic::Severity;
+use colored::Colorize;
+use move_command_line_common::json_errors;
+use move_compiler::diagnostics::{self, Diagnostics, FilesSourceText};
+use move_package::{
+    compilation::{
+        analysis::AnalysisHost, compiled_package::CompiledPackage,
+        package_layout::CompiledPackageLayout,
+    },
+    resolution::lockfile::DependencySource,
+    source_package::layout::SourcePackageLayout,
+    Architecture, BuildConfig,
+};
+use std::{
+    io::{Read, Write},
+    path::{Path, PathBuf},
+    time::{Duration, Instant},
+};
 
-/// Build the package at `path`. If no path is provided defaults to current directory.
+const EXAMPLES: &str = "EXAMPLES:
+    Build the package containing the current directory:
+        move build
+
+    Build the package in another directory, with its dev addresses and dependencies:
+        move build --path ../coins --dev
+
+    Only download the dependencies of the package:
+        move build --fetch-deps-only
+
+    Build every member of the workspace, even if some fail to build:
+        move build --workspace --keep-going
+
+    Only build the members of the workspace affected by the changes since the main branch:
+        move build --workspace --changed-since origin/main
+
+    Only check the package for errors, without writing the build directory:
+        move build --check-only";
+
+/// Build the package at `path`. If no path is provided defaults to current directory. With `-v`,
+/// the source and the digest of each dependency compiled into the package are printed.
 #[derive(Parser)]
-#[clap(name = "build")]
-pub struct Build;
+#[clap(name = "build", after_help = EXAMPLES)]
+pub struct Build {
+    #[clap(flatten)]
+    pub workspace: WorkspaceOptions,
+    /// Only check the package for errors and warnings, without writing the build directory
+    #[clap(long = "check-only", conflicts_with = "workspace")]
+    pub check_only: bool,
+    /// Check the source file at this path with the contents read from stdin instead of those on
+    /// disk, along with the files depending on it. For editors checking unsaved buffers.
+    #[clap(
+        long = "stdin-overlay",
+        requires = "check-only",
+        hide = true,
+        parse(from_os_str)
+    )]
+    pub stdin_overlay: Option<PathBuf>,
+}
 
 impl Build {
     pub fn execute(self, path: Option<PathBuf>, config: BuildConfig) -> anyhow::Result<()> {
+        self.execute_with_verbosity(path, config, false)
+    }
+
+    /// Like `execute`, also printing the source and digest of each dependency if `verbose` is set
+    pub fn execute_with_verbosity(
+        self,
+        path: Option<PathBuf>,
+        mut config: BuildConfig,
+        verbose: bool,
+    ) -> anyhow::Result<()> {
+        if self.workspace.workspace {
+            return run_workspace(path.as_deref(), &self.workspace, |member| {
+                build_member(member, config.clone(), verbose)
+            });
+        }
+        // the overlay path is relative to the current directory, which `reroot_path` changes
+        let stdin_overlay = match &self.stdin_overlay {
+            Some(file) => Some(std::env::current_dir()?.join(file)),
+            None => None,
+        };
         let rerooted_path = reroot_path(path)?;
         if config.fetch_deps_only {
-            let mut config = config;
             if config.test_mode {
                 config.dev_mode = true;
             }
             config.download_deps_for_package(&rerooted_path)?;
             return Ok(());
         }
+        config.lock_file = Some(rerooted_path.join(SourcePackageLayout::Lockfile.path()));
+        if self.check_only {
+            return check_package(&rerooted_path, config, stdin_overlay.as_deref());
+        }
         let architecture = config.architecture.unwrap_or(Architecture::Move);
 
         match architecture {
             Architecture::Move | Architecture::AsyncMove => {
-                config.compile_package(&rerooted_path, &mut std::io::stderr())?;
+                let start = Instant::now();
+                let install_dir = config.install_dir.clone();
+                let options = BuildOptions {
+                    package_path: rerooted_path,
+                    build_config: config,
+                };
+                let output = build_package(options, &mut std::io::stderr())?;
+                output.report_diagnostics()?;
+                if let Some(package) = &output.package {
+                    if verbose {
+                        print_dependency_sources(
+                            &package.dependency_sources,
+                            &mut std::io::stderr(),
+                        )?;
+                    }
+                    print_build_summary(
+                        package,
+                        install_dir.as_deref(),
+                        start.elapsed(),
+                        &mut std::io::stderr(),
+                    )?;
+                }
             }
 
             Architecture::Ethereum => {
@@ -40,3 +139,289 @@ impl Build {
         Ok(())
     }
 }
+
+/// Build the workspace member at `member` for `move build --workspace`
+fn build_member(
+    member: &Path,
+    mut config: BuildConfig,
+    verbose: bool,
+) -> anyhow::Result<MemberOutcome> {
+    if config.fetch_deps_only {
+        if config.test_mode {
+            config.dev_mode = true;
+        }
+        config.download_deps_for_package(member)?;
+        return Ok(MemberOutcome::Ok);
+    }
+    config.lock_file = Some(member.join(SourcePackageLayout::Lockfile.path()));
+    let start = Instant::now();
+    let install_dir = config
+        .install_dir
+        .clone()
+        .unwrap_or_else(|| member.to_path_buf());
+    let options = BuildOptions {
+        package_path: member.to_path_buf(),
+        build_config: config,
+    };
+    let output = build_package(options, &mut std::io::stderr())?;
+    output.print_diagnostics();
+    match &output.package {
+        None => Ok(MemberOutcome::FailedToCompile),
+        Some(package) => {
+            if verbose {
+                print_dependency_sources(&package.dependency_sources, &mut std::io::stderr())?;
+            }
+            print_build_summary(
+                package,
+                Some(&install_dir),
+                start.elapsed(),
+                &mut std::io::stderr(),
+            )?;
+            Ok(MemberOutcome::Ok)
+        }
+    }
+}
+
+/// Check the package at `path` for errors and warnings without writing the build directory. With
+/// `stdin_overlay`, the file at that path is checked with the contents read from stdin, along
+/// with the files depending on it.
+fn check_package(
+    path: &Path,
+    config: BuildConfig,
+    stdin_overlay: Option<&Path>,
+) -> anyhow::Result<()> {
+    let start = Instant::now();
+    let resolution_graph = config.resolution_graph_for_package(path)?;
+    let package_name = resolution_graph.root_package.package.name;
+    eprintln!("{} {}", "CHECKING".bold().green(), package_name);
+    let mut host = AnalysisHost::new(resolution_graph)?;
+    let result = match stdin_overlay {
+        Some(file) => {
+            let mut contents = String::new();
+            std::io::stdin()
+                .read_to_string(&mut contents)
+                .context("Unable to read the overlay from stdin")?;
+            host.set_overlay(file, contents)?;
+            host.check_file(file)?
+        }
+        None => host.check_all()?,
+    };
+    if result.has_errors() {
+        diagnostics::report_diagnostics(&result.files, result.diagnostics)
+    }
+    diagnostics::report_warnings(&result.files, result.diagnostics);
+    eprintln!(
+        "Checked package {}: {} modules, took {:.2}s",
+        package_name,
+        host.stats().checked_modules.len(),
+        start.elapsed().as_secs_f64()
+    );
+    Ok(())
+}
+
+/// Options for building a package with `build_package`
+pub struct BuildOptions {
+    /// Path to the root directory of the package
+    pub package_path: PathBuf,
+    pub build_config: BuildConfig,
+}
+
+/// A warning or error reported by the compiler
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CompilerDiagnostic {
+    pub severity: Severity,
+    /// Code identifying the kind of diagnostic, e.g. `W09002`
+    pub code: String,
+    pub message: String,
+    /// Source file the diagnostic points into
+    pub file: String,
+    /// Line of the primary location, starting from 1
+    pub line: usize,
+    /// Column of the primary location, starting from 1
+    pub column: usize,
+    /// Explanation attached to the primary location
+    pub label: String,
+    pub notes: Vec<String>,
+}
+
+/// The result of building a package with `build_package`
+pub struct BuildOutput {
+    /// The compiled package, or `None` if compilation failed with errors
+    pub package: Option<CompiledPackage>,
+    /// The warnings and errors reported by the compiler
+    pub diagnostics: Vec<CompilerDiagnostic>,
+    /// The compiled files, named as they are shown in diagnostics
+    files: FilesSourceText,
+    compiler_diagnostics: Diagnostics,
+}
+
+impl BuildOutput {
+    /// Print the diagnostics to stderr the way the compiler does, and fail if compilation failed
+    pub fn report_diagnostics(&self) -> anyhow::Result<()> {
+        self.print_diagnostics();
+        if self.package.is_none() {
+            bail!("Compilation error")
+        }
+        Ok(())
+    }
+
+    /// Print the diagnostics to stderr the way the compiler does, without exiting the process if
+    /// compilation failed
+    pub fn print_diagnostics(&self) {
+        let diags = self.compiler_diagnostics.clone();
+        diagnostics::record_json_diagnostics(&self.files, &diags);
+        let buffer = if atty::is(atty::Stream::Stderr) {
+            diagnostics::report_diagnostics_to_color_buffer(&self.files, diags)
+        } else {
+            diagnostics::report_diagnostics_to_buffer(&self.files, diags)
+        };
+        let _ = std::io::stderr().write_all(&buffer);
+    }
+}
+
+/// Build the package at `options.package_path`, returning the compiled package together with the
+/// compiler diagnostics instead of printing them. Progress messages are written to `writer`.
+pub fn build_package<W: Write>(
+    options: BuildOptions,
+    writer: &mut W,
+) -> anyhow::Result<BuildOutput> {
+    let BuildOptions {
+        package_path,
+        build_config,
+    } = options;
+    let mut reported = None;
+    let mut compilation_failed = false;
+    let result = build_config.compile_package_with_driver(&package_path, writer, |compiler| {
+        let remaps = compiler.flags().path_prefix_remaps().to_vec();
+        let (files, units_res) = compiler.build()?;
+        // the diagnostics are shown with remapped file names, the package keeps the local ones
+        let shown_files = diagnostics::remap_file_names(&files, &remaps);
+        match units_res {
+            Ok((units, warnings)) => {
+                reported = Some((shown_files, warnings));
+                Ok((files, units))
+            }
+            Err(errors) => {
+                reported = Some((shown_files, errors));
+                compilation_failed = true;
+                bail!("Compilation error")
+            }
+        }
+    });
+
+    let package = match result {
+        Ok(package) => Some(package),
+        Err(_) if compilation_failed => None,
+        Err(err) => return Err(err),
+    };
+    let (files, compiler_diagnostics) = reported.unwrap_or_default();
+    Ok(BuildOutput {
+        package,
+        diagnostics: convert_diagnostics(&files, compiler_diagnostics.clone()),
+        files,
+        compiler_diagnostics,
+    })
+}
+
+/// Print what a successful build produced and where, e.g.
+/// `Built package Foo: 2 modules, 1 scripts in build/Foo, took 0.42s`. The build directory is
+/// under `install_dir`, or under the current directory if it is not set.
+pub fn print_build_summary<W: Write>(
+    package: &CompiledPackage,
+    install_dir: Option<&Path>,
+    elapsed: Duration,
+    writer: &mut W,
+) -> anyhow::Result<()> {
+    let package_name = package.compiled_package_info.package_name;
+    let build_dir = match install_dir {
+        Some(install_dir) => install_dir.join(CompiledPackageLayout::Root.path()),
+        None => CompiledPackageLayout::Root.path().to_path_buf(),
+    }
+    .join(package_name.as_str());
+    writeln!(
+        writer,
+        "Built package {}: {} modules, {} scripts in {}, took {:.2}s",
+        package_name,
+        package.root_modules().count(),
+        package.scripts().count(),
+        build_dir.display(),
+        elapsed.as_secs_f64()
+    )?;
+    if package.root_modules().count() == 0 {
+        json_errors::warning(format!("no modules found in package {}", package_name));
+    }
+    Ok(())
+}
+
+/// Print where each dependency was fetched from, the digest of its contents and the directory it
+/// was compiled from
+pub fn print_dependency_sources<W: Write>(
+    sources: &[DependencySource],
+    writer: &mut W,
+) -> anyhow::Result<()> {
+    for source in sources {
+        let locked = &source.locked;
+        let origin = match (&locked.git, &locked.node, &locked.local) {
+            (Some(git), _, _) => {
+                let mut origin = format!("git {} at {}", git, locked.rev.as_deref().unwrap_or(""));
+                if let Some(subdir) = &locked.subdir {
+                    origin = format!("{} in {}", origin, subdir);
+                }
+                origin
+            }
+            (None, Some(node), _) => format!(
+                "node {} at {}",
+                node,
+                locked.address.as_deref().unwrap_or("")
+            ),
+            (None, None, None) if locked.url.is_some() => {
+                let mut origin = format!(
+                    "archive {} with sha256 {}",
+                    locked.url.as_deref().unwrap_or(""),
+                    locked.sha256.as_deref().unwrap_or("")
+                );
+                if let Some(subdir) = &locked.subdir {
+                    origin = format!("{} in {}", origin, subdir);
+                }
+                origin
+            }
+            (None, None, local) => format!("local {}", local.as_deref().unwrap_or("")),
+        };
+        writeln!(writer, "{} {}", "DEPENDENCY".bold().green(), locked.name)?;
+        writeln!(writer, "    source: {}", origin)?;
+        if let Some(digest) = &locked.digest {
+            writeln!(writer, "    digest: {}", digest)?;
+        }
+        writeln!(writer, "    path: {}", source.path.display())?;
+    }
+    Ok(())
+}
+
+fn convert_diagnostics(files: &FilesSourceText, diags: Diagnostics) -> Vec<CompilerDiagnostic> {
+    let codes: Vec<_> = diags
+        .clone()
+        .into_vec()
+        .iter()
+        .map(|diag| diag.info().clone().render().0)
+        .collect();
+    diags
+        .into_codespan_format()
+        .into_iter()
+        .zip(codes)
+        .map(|((severity, message, (loc, label), _, notes), code)| {
+            let (file, contents) = &files[&loc.file_hash()];
+            let prefix = &contents[..loc.start() as usize];
+            let line_start = prefix.rfind('\n').map_or(0, |idx| idx + 1);
+            CompilerDiagnostic {
+                severity,
+                code,
+                message: message.to_string(),
+                file: file.to_string(),
+                line: prefix.matches('\n').count() + 1,
+                column: prefix[line_start..].chars().count() + 1,
+                label,
+                notes,
+            }
+        })
+        .collect()
+}
@@ -0,0 +1,171 @@
+// Copyright (c) The Move Contributors
+// SPDX-License-Identifier: Apache-2.0
+
+use crate::utils::{
+    move_home_cache::{self, CacheEntry, CacheKind, GcPolicy},
+    streaming_upload::format_bytes,
+};
+use anyhow::{bail, Result};
+use clap::*;
+use move_command_line_common::env::move_home;
+use std::{
+    path::{Path, PathBuf},
+    time::{Duration, SystemTime},
+};
+
+const DAY: Duration = Duration::from_secs(24 * 60 * 60);
+const GIB: f64 = (1u64 << 30) as f64;
+
+/// Print the number of entries, the size and the last access time of each cache under MOVE_HOME:
+/// the downloaded dependencies and the registry responses.
+#[derive(Parser)]
+#[clap(name = "stats")]
+pub struct Stats;
+
+impl Stats {
+    pub fn execute(self, verbose: bool) -> Result<()> {
+        let move_home = move_home()?;
+        let entries = move_home_cache::cache_entries(Path::new(&move_home))?;
+        let now = SystemTime::now();
+        println!("Caches in '{}':", move_home);
+        for kind in CacheKind::ALL {
+            let mut entries: Vec<_> = entries.iter().filter(|entry| entry.kind == kind).collect();
+            entries.sort_by_key(|entry| std::cmp::Reverse(entry.last_access));
+            let size = entries.iter().map(|entry| entry.size).sum();
+            match (entries.first(), entries.last()) {
+                (Some(newest), Some(oldest)) => println!(
+                    "{}: {}, {}, last accessed {} (least recently {})",
+                    kind,
+                    format_entries(entries.len()),
+                    format_bytes(size),
+                    format_age(now, newest),
+                    format_age(now, oldest)
+                ),
+                _ => println!("{}: no entries", kind),
+            }
+            if verbose {
+                for entry in entries {
+                    println!(
+                        "  {}  {}  {}",
+                        entry.path.display(),
+                        format_bytes(entry.size),
+                        format_age(now, entry)
+                    );
+                }
+            }
+        }
+        println!(
+            "Total: {}",
+            format_bytes(entries.iter().map(|entry| entry.size).sum())
+        );
+        Ok(())
+    }
+}
+
+/// Remove the least recently used entries of the caches under MOVE_HOME: downloaded dependencies
+/// and registry responses. Nothing else, such as the credentials, is ever removed. The package
+/// lock is held meanwhile, so builds running concurrently wait for the removal to end.
+#[derive(Parser)]
+#[clap(name = "gc")]
+pub struct Gc {
+    /// Remove the least recently used entries until the caches take at most this many gigabytes
+    /// (GiB). Dependencies kept by `--workspace-roots` count in the size but are not removed.
+    #[clap(
+        long = "max-size",
+        value_name = "GB",
+        required_unless_present = "older-than"
+    )]
+    pub max_size: Option<f64>,
+    /// Remove the entries last used more than this many days ago
+    #[clap(long = "older-than", value_name = "DAYS")]
+    pub older_than: Option<u64>,
+    /// Keep the dependencies recorded in the lockfiles (`Move.lock`) found in these directories
+    /// and their subdirectories
+    #[clap(
+        long = "workspace-roots",
+        value_name = "DIR",
+        parse(from_os_str),
+        takes_value(true),
+        multiple_values(true),
+        multiple_occurrences(true)
+    )]
+    pub workspace_roots: Vec<PathBuf>,
+    /// Print the entries that would be removed, without removing them
+    #[clap(long = "dry-run")]
+    pub dry_run: bool,
+}
+
+impl Gc {
+    pub fn execute(self) -> Result<()> {
+        let max_size = match self.max_size {
+            Some(max_size) if max_size.is_nan() || max_size < 0.0 => {
+                bail!("Invalid --max-size {}: expected a number of GiB", max_size)
+            }
+            max_size => max_size.map(|max_size| (max_size * GIB) as u64),
+        };
+        let policy = GcPolicy {
+            max_size,
+            older_than: self
+                .older_than
+                .map(|days| Duration::from_secs(days.saturating_mul(DAY.as_secs()))),
+        };
+        let output =
+            move_home_cache::gc(&move_home()?, &policy, &self.workspace_roots, self.dry_run)?;
+        let now = SystemTime::now();
+        for entry in &output.evicted {
+            println!(
+                "{} {} ({}, {}, last accessed {})",
+                if self.dry_run {
+                    "Would remove"
+                } else {
+                    "Removed"
+                },
+                entry.path.display(),
+                entry.kind,
+                format_bytes(entry.size),
+                format_age(now, entry)
+            );
+        }
+        let freed = output.evicted.iter().map(|entry| entry.size).sum();
+        println!(
+            "{} {}, {}; {} left",
+            if self.dry_run {
+                "Would remove"
+            } else {
+                "Removed"
+            },
+            format_entries(output.evicted.len()),
+            format_bytes(freed),
+            format_bytes(output.remaining_size)
+        );
+        if let Some(max_size) = max_size {
+            if output.remaining_size > max_size {
+                println!(
+                    "The caches still exceed --max-size: the rest is used by the dependencies of \
+                     the lockfiles under --workspace-roots"
+                );
+            }
+        }
+        Ok(())
+    }
+}
+
+fn format_entries(count: usize) -> String {
+    match count {
+        1 => "1 entry".to_string(),
+        count => format!("{} entries", count),
+    }
+}
+
+fn format_age(now: SystemTime, entry: &CacheEntry) -> String {
+    let days = now
+        .duration_since(entry.last_access)
+        .unwrap_or_default()
+        .as_secs()
+        / DAY.as_secs();
+    match days {
+        0 => "today".to_string(),
+        1 => "1 day ago".to_string(),
+        days => format!("{} days ago", days),
+    }
+}
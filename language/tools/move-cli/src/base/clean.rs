@@ -0,0 +1,134 @@
+// Copyright (c) The Move Contributors
+// SPDX-License-Identifier: Apache-2.0
+
+use super::{docgen::INDEX_FILE, package_root};
+use crate::{sandbox::commands::DEFAULT_TRACE_FILE, utils::streaming_upload::format_bytes};
+use anyhow::{bail, Context, Result};
+use clap::*;
+use move_command_line_common::files::MOVE_COVERAGE_MAP_EXTENSION;
+use move_docgen::DocgenOptions;
+use move_package::{
+    compilation::package_layout::CompiledPackageLayout,
+    source_package::layout::{package_move_files, SourcePackageLayout},
+};
+use std::{
+    fs,
+    io::ErrorKind,
+    path::{Path, PathBuf},
+};
+
+/// Remove the files the commands write into a package. Only the `build` directory is removed by
+/// default. The `storage` directory is left alone: use `move sandbox clean` to remove it.
+#[derive(Parser)]
+#[clap(name = "clean")]
+pub struct Clean {
+    /// Also remove the documentation generated by `move docgen` into `doc`. The other files in
+    /// `doc` are kept.
+    #[clap(long = "docs")]
+    pub docs: bool,
+    /// Also remove the coverage data written outside of `build`: the coverage map of older
+    /// versions of `move test --coverage` and the trace of `move sandbox exp-test --track-cov`
+    #[clap(long = "coverage")]
+    pub coverage: bool,
+    /// Remove everything the other flags remove
+    #[clap(long = "all")]
+    pub all: bool,
+    /// List the paths that would be removed and their size, without removing them
+    #[clap(long = "dry-run")]
+    pub dry_run: bool,
+}
+
+impl Clean {
+    pub fn execute(self, path: Option<PathBuf>) -> Result<()> {
+        let root = package_root(path.as_deref())?;
+        let mut targets = vec![CompiledPackageLayout::Root.path().to_path_buf()];
+        if self.docs || self.all {
+            targets.extend(generated_docs(&root)?);
+        }
+        if self.coverage || self.all {
+            targets
+                .push(PathBuf::from(".coverage_map").with_extension(MOVE_COVERAGE_MAP_EXTENSION));
+            targets.push(PathBuf::from(DEFAULT_TRACE_FILE));
+        }
+
+        // every path is checked before anything is removed
+        let mut found = vec![];
+        for target in targets {
+            let path = root.join(&target);
+            match fs::symlink_metadata(&path) {
+                Ok(_) => (),
+                Err(err) if err.kind() == ErrorKind::NotFound => continue,
+                Err(err) => {
+                    return Err(err)
+                        .with_context(|| format!("Unable to access '{}'", path.display()))
+                }
+            }
+            let resolved = path
+                .canonicalize()
+                .with_context(|| format!("Unable to resolve '{}'", path.display()))?;
+            if !resolved.starts_with(&root) {
+                bail!(
+                    "Refusing to remove '{}': it resolves to '{}', outside of the package root '{}'",
+                    target.display(),
+                    resolved.display(),
+                    root.display()
+                )
+            }
+            let size = disk_size(&path)?;
+            found.push((target, path, size));
+        }
+
+        let mut freed = 0;
+        for (target, path, size) in found {
+            if self.dry_run {
+                println!("Would remove {} ({})", target.display(), format_bytes(size));
+            } else {
+                // symbolic links are removed themselves, never what they point to
+                if fs::symlink_metadata(&path)?.is_dir() {
+                    fs::remove_dir_all(&path)
+                } else {
+                    fs::remove_file(&path)
+                }
+                .with_context(|| format!("Unable to remove '{}'", path.display()))?;
+                println!("Removed {} ({})", target.display(), format_bytes(size));
+            }
+            freed += size;
+        }
+        if self.dry_run {
+            println!("Would free {}", format_bytes(freed));
+        } else {
+            println!("Freed {}", format_bytes(freed));
+        }
+        Ok(())
+    }
+}
+
+/// The pages `move docgen` generates into `doc` for the sources and scripts of the package at
+/// `root`, and its index page, relative to `root`
+fn generated_docs(root: &Path) -> Result<Vec<PathBuf>> {
+    let output_dir = PathBuf::from(DocgenOptions::default().output_directory);
+    let mut docs = vec![output_dir.join(INDEX_FILE)];
+    for file in package_move_files(
+        root,
+        &[SourcePackageLayout::Sources, SourcePackageLayout::Scripts],
+    )? {
+        if let Some(stem) = file.file_stem() {
+            docs.push(output_dir.join(format!("{}.md", stem.to_string_lossy())));
+        }
+    }
+    docs.sort();
+    docs.dedup();
+    Ok(docs)
+}
+
+/// The number of bytes the files under `path` take, without following symbolic links
+fn disk_size(path: &Path) -> Result<u64> {
+    let mut size = 0;
+    for entry in walkdir::WalkDir::new(path) {
+        let entry = entry?;
+        if !entry.file_type().is_dir() {
+            size += entry.metadata()?.len();
+        }
+    }
+    Ok(size)
+}
@@ -2,15 +2,82 @@
 // SPDX-License-Identifier: Apache-2.0
 
 use super::reroot_path;
+use crate::utils::{
+    artifact_version::{check_artifacts_version, ArtifactVersionOptions},
+    coverage_exemptions::CoverageExemptions,
+    stale_build::{check_stale_build, StaleBuildOptions},
+};
+use anyhow::{bail, Result};
 use clap::*;
+use move_binary_format::CompiledModule;
+use move_command_line_common::files::MOVE_COVERAGE_MAP_EXTENSION;
 use move_compiler::compiled_unit::{CompiledUnit, NamedCompiledModule};
 use move_coverage::{
-    coverage_map::CoverageMap, format_csv_summary, format_human_summary,
-    source_coverage::SourceCoverageBuilder, summary::summarize_inst_cov,
+    coverage_map::{CoverageMap, ExecCoverageMap},
+    format_csv_summary, format_human_summary,
+    lcov::write_lcov_record,
+    source_coverage::SourceCoverageBuilder,
+    summary::summarize_inst_cov,
 };
 use move_disassembler::disassembler::Disassembler;
-use move_package::BuildConfig;
-use std::path::PathBuf;
+use move_package::{
+    compilation::{compiled_package::CompiledPackage, package_layout::CompiledPackageLayout},
+    source_package::manifest_parser::parse_move_manifest_from_file,
+    BuildConfig,
+};
+use std::{
+    fs,
+    io::Write,
+    path::{Path, PathBuf},
+    str::FromStr,
+};
+
+/// The reports written by `move test --coverage`, in addition to the coverage map
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ArgEnum)]
+pub enum CoverageFormat {
+    // Only print the coverage summary after the test results
+    Summary,
+    // Also write an LCOV tracefile to `build/coverage/lcov.info`
+    Lcov,
+    // Also write an HTML page per module to `build/coverage/html`
+    Html,
+}
+
+impl FromStr for CoverageFormat {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s {
+            "summary" => Ok(CoverageFormat::Summary),
+            "lcov" => Ok(CoverageFormat::Lcov),
+            "html" => Ok(CoverageFormat::Html),
+            _ => bail!("Invalid coverage format: {}", s),
+        }
+    }
+}
+
+impl CoverageFormat {
+    pub(crate) fn variants() -> [&'static str; 3] {
+        ["summary", "lcov", "html"]
+    }
+}
+
+/// The directory `move test --coverage` writes coverage information to
+pub fn coverage_dir(package_path: &Path, config: &BuildConfig) -> PathBuf {
+    config
+        .install_dir
+        .clone()
+        .unwrap_or_else(|| package_path.to_path_buf())
+        .join(CompiledPackageLayout::Root.path())
+        .join(CompiledPackageLayout::Coverage.path())
+}
+
+/// The coverage map written by `move test --coverage` into `coverage_dir`
+pub fn coverage_map_path(coverage_dir: &Path) -> PathBuf {
+    coverage_dir
+        .join("coverage_map")
+        .with_extension(MOVE_COVERAGE_MAP_EXTENSION)
+}
 
 #[derive(Parser)]
 pub enum CoverageSummaryOptions {
@@ -23,6 +90,11 @@ pub enum CoverageSummaryOptions {
         /// Output CSV data of coverage
         #[clap(long = "csv")]
         output_csv: bool,
+        /// Fail if less than this percentage of the instructions of the package are covered. The
+        /// modules and functions listed in the `coverage-exemptions.toml` of the package are left
+        /// out, and shown as exempted in the summary.
+        #[clap(long = "fail-under", value_name = "PERCENT")]
+        fail_under: Option<f64>,
     },
     /// Display coverage information about the module against source code
     #[clap(name = "source")]
@@ -43,24 +115,61 @@ pub enum CoverageSummaryOptions {
 #[derive(Parser)]
 #[clap(name = "coverage")]
 pub struct Coverage {
+    #[clap(flatten)]
+    pub version_options: ArtifactVersionOptions,
+    #[clap(flatten)]
+    pub stale_options: StaleBuildOptions,
     #[clap(subcommand)]
     pub options: CoverageSummaryOptions,
 }
 
 impl Coverage {
     pub fn execute(self, path: Option<PathBuf>, config: BuildConfig) -> anyhow::Result<()> {
+        self.execute_with_verbosity(path, config, false)
+    }
+
+    /// Like `execute`, listing the files changed since the package was built if `verbose` is set
+    pub fn execute_with_verbosity(
+        self,
+        path: Option<PathBuf>,
+        config: BuildConfig,
+        verbose: bool,
+    ) -> anyhow::Result<()> {
         let path = reroot_path(path)?;
-        let coverage_map = CoverageMap::from_binary_file(path.join(".coverage_map.mvcov"))?;
+        let mut coverage_map_path = coverage_map_path(&coverage_dir(&path, &config));
+        if !coverage_map_path.exists() {
+            // written by older versions of `move test --coverage`
+            coverage_map_path = path
+                .join(".coverage_map")
+                .with_extension(MOVE_COVERAGE_MAP_EXTENSION);
+        }
+        // the coverage map is only valid for the bytecode it was recorded against
+        let package_name = parse_move_manifest_from_file(&path)?.package.name;
+        let package_build_dir = config
+            .install_dir
+            .clone()
+            .unwrap_or_else(|| path.clone())
+            .join(CompiledPackageLayout::Root.path())
+            .join(package_name.as_str());
+        check_artifacts_version(
+            &package_build_dir,
+            &config,
+            self.version_options.ignore_version_mismatch,
+        )?;
+        check_stale_build(
+            &path,
+            &package_build_dir,
+            "move test --coverage",
+            self.stale_options.allow_stale,
+            verbose,
+        )?;
+        let coverage_map = CoverageMap::from_binary_file(coverage_map_path)?;
         let package = config.compile_package(&path, &mut Vec::new())?;
-        let modules: Vec<_> = package
-            .root_modules()
-            .filter_map(|unit| match &unit.unit {
-                CompiledUnit::Module(NamedCompiledModule { module, .. }) => Some(module.clone()),
-                _ => None,
-            })
-            .collect();
+        let modules = root_modules(&package);
+        let exemptions = load_exemptions(&path, &package)?;
         match self.options {
             CoverageSummaryOptions::Source { module_name } => {
+                package.ensure_source_maps("Source coverage")?;
                 let unit = package.get_module_by_name_from_root(&module_name)?;
                 let source_path = &unit.source_path;
                 let (module, source_map) = match &unit.unit {
@@ -78,24 +187,45 @@ impl Coverage {
             CoverageSummaryOptions::Summary {
                 functions,
                 output_csv,
-                ..
+                fail_under,
             } => {
-                let coverage_map = coverage_map.to_unified_exec_map();
+                if let Some(threshold) = fail_under {
+                    if !(0.0..=100.0).contains(&threshold) {
+                        bail!(
+                            "Invalid coverage threshold {} -- expected a percentage between 0 \
+                             and 100",
+                            threshold
+                        )
+                    }
+                }
+                let exec_map = coverage_map.to_unified_exec_map();
                 if output_csv {
                     format_csv_summary(
                         modules.as_slice(),
-                        &coverage_map,
+                        &exec_map,
                         summarize_inst_cov,
                         &mut std::io::stdout(),
                     )
                 } else {
-                    format_human_summary(
-                        modules.as_slice(),
-                        &coverage_map,
-                        summarize_inst_cov,
+                    write_human_summary(
+                        &modules,
+                        &exec_map,
+                        &exemptions,
                         &mut std::io::stdout(),
                         functions,
-                    )
+                    )?
+                }
+                if let Some(threshold) = fail_under {
+                    let (covered, total) =
+                        instruction_coverage(&package, &coverage_map, &exemptions);
+                    let percentage = coverage_percentage(covered, total);
+                    if percentage < threshold {
+                        bail!(
+                            "{:.2}% of the instructions are covered, below the required {:.2}%",
+                            percentage,
+                            threshold
+                        )
+                    }
                 }
             }
             CoverageSummaryOptions::Bytecode { module_name } => {
@@ -108,3 +238,148 @@ impl Coverage {
         Ok(())
     }
 }
+
+fn root_modules(package: &CompiledPackage) -> Vec<CompiledModule> {
+    package
+        .root_modules()
+        .filter_map(|unit| match &unit.unit {
+            CompiledUnit::Module(NamedCompiledModule { module, .. }) => Some(module.clone()),
+            _ => None,
+        })
+        .collect()
+}
+
+/// The coverage exemptions of the package at `package_path`, warning about those naming modules or
+/// functions that the root modules of `package` do not define
+pub(crate) fn load_exemptions(
+    package_path: &Path,
+    package: &CompiledPackage,
+) -> Result<CoverageExemptions> {
+    let exemptions = CoverageExemptions::load(package_path)?;
+    exemptions.warn_stale(&root_modules(package));
+    Ok(exemptions)
+}
+
+/// The number of instructions of the root modules of `package` executed according to
+/// `coverage_map`, and the total number of instructions of these modules, as summed up by the
+/// coverage summary. The modules and functions in `exemptions` are left out.
+pub(crate) fn instruction_coverage(
+    package: &CompiledPackage,
+    coverage_map: &CoverageMap,
+    exemptions: &CoverageExemptions,
+) -> (u64, u64) {
+    let exec_map = coverage_map.to_unified_exec_map();
+    root_modules(package)
+        .iter()
+        .filter(|module| exemptions.module_exemption(module).is_none())
+        .flat_map(|module| {
+            exemptions
+                .exclude_functions(module, summarize_inst_cov(module, &exec_map))
+                .function_summaries
+                .into_values()
+        })
+        .fold((0, 0), |(covered, total), summary| {
+            (covered + summary.covered, total + summary.total)
+        })
+}
+
+/// The percentage of instructions covered, counting no instructions as fully covered
+pub(crate) fn coverage_percentage(covered: u64, total: u64) -> f64 {
+    if total == 0 {
+        100.0
+    } else {
+        covered as f64 / total as f64 * 100.0
+    }
+}
+
+/// Print the coverage summary of `modules` to `writer`, less the modules and functions in
+/// `exemptions`, followed by the coverage of each of these
+fn write_human_summary<W: Write>(
+    modules: &[CompiledModule],
+    exec_map: &ExecCoverageMap,
+    exemptions: &CoverageExemptions,
+    writer: &mut W,
+    summarize_functions: bool,
+) -> Result<()> {
+    let covered_modules: Vec<_> = modules
+        .iter()
+        .filter(|module| exemptions.module_exemption(module).is_none())
+        .cloned()
+        .collect();
+    format_human_summary(
+        &covered_modules,
+        exec_map,
+        |module, exec_map| {
+            exemptions.exclude_functions(module, summarize_inst_cov(module, exec_map))
+        },
+        writer,
+        summarize_functions,
+    );
+    for (exemption, covered, total) in
+        exemptions.exempted_coverage(modules, |module| summarize_inst_cov(module, exec_map))
+    {
+        writeln!(
+            writer,
+            "Exempted {}: {} of {} instructions covered ({})",
+            exemption.target(),
+            covered,
+            total,
+            exemption.reason
+        )?;
+    }
+    Ok(())
+}
+
+/// Print the coverage summary of the root modules of `package` to `writer`, less the modules and
+/// functions in `exemptions`, and write the report selected by `format` into `coverage_dir`.
+pub(crate) fn report_coverage<W: Write>(
+    package: &CompiledPackage,
+    coverage_map: &CoverageMap,
+    exemptions: &CoverageExemptions,
+    coverage_dir: &Path,
+    format: CoverageFormat,
+    writer: &mut W,
+) -> Result<()> {
+    let exec_map = coverage_map.to_unified_exec_map();
+    write_human_summary(&root_modules(package), &exec_map, exemptions, writer, false)?;
+    let units = package.root_modules().filter_map(|unit| match &unit.unit {
+        CompiledUnit::Module(NamedCompiledModule {
+            module, source_map, ..
+        }) => Some((module, source_map, &unit.source_path)),
+        _ => None,
+    });
+    match format {
+        CoverageFormat::Summary => (),
+        CoverageFormat::Lcov => package.ensure_source_maps("The LCOV coverage report")?,
+        CoverageFormat::Html => package.ensure_source_maps("The HTML coverage report")?,
+    }
+    match format {
+        CoverageFormat::Summary => (),
+        CoverageFormat::Lcov => {
+            let lcov_path = coverage_dir.join("lcov.info");
+            let mut lcov = vec![];
+            for (module, source_map, source_path) in units {
+                write_lcov_record(module, source_map, source_path, &exec_map, &mut lcov)?;
+            }
+            fs::write(&lcov_path, lcov)?;
+            writeln!(writer, "Wrote LCOV report to {}", lcov_path.display())?;
+        }
+        CoverageFormat::Html => {
+            let html_dir = coverage_dir.join("html");
+            if html_dir.exists() {
+                fs::remove_dir_all(&html_dir)?;
+            }
+            fs::create_dir_all(&html_dir)?;
+            for (module, source_map, source_path) in units {
+                let name = module.self_id().name().to_string();
+                let mut html = vec![];
+                SourceCoverageBuilder::new(module, coverage_map, source_map)
+                    .compute_source_coverage(source_path)
+                    .output_source_coverage_html(&name, &mut html)?;
+                fs::write(html_dir.join(name).with_extension("html"), html)?;
+            }
+            writeln!(writer, "Wrote HTML report to {}", html_dir.display())?;
+        }
+    }
+    Ok(())
+}
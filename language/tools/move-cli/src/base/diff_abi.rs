@@ -0,0 +1,850 @@
+// Copyright (c) The Move Contributors
+// SPDX-License-Identifier: Apache-2.0
+
+use super::{
+    build::{build_package, BuildOptions},
+    package_root,
+    warn_diff::build_at_revision,
+};
+use crate::sandbox::cli::OutputFormat;
+use anyhow::{bail, Context, Result};
+use clap::*;
+use move_binary_format::{
+    access::ModuleAccess,
+    file_format::{
+        Ability, AbilitySet, SignatureToken, StructFieldInformation, StructHandleIndex, Visibility,
+    },
+    CompiledModule,
+};
+use move_compiler::compiled_unit::{CompiledUnit, NamedCompiledModule};
+use move_package::{compilation::package_layout::CompiledPackageLayout, BuildConfig};
+use serde::{Deserialize, Serialize};
+use std::{
+    collections::{BTreeMap, BTreeSet},
+    fmt, fs,
+    path::{Path, PathBuf},
+};
+
+const EXAMPLES: &str = "EXAMPLES:
+    Save the ABI of the package, to compare with after making changes:
+        move package diff-abi --save
+
+    Compare with the package as of the main branch, and print the changes as JSON:
+        move package diff-abi --baseline-rev main --format json";
+
+/// Compare the ABI of the package (its public and entry functions, and its structs with their
+/// abilities, type parameters and fields) with that of a baseline. Each change is classified as
+/// breaking, e.g. a removed function, a changed signature, a removed field or a removed ability,
+/// or as non-breaking, e.g. an added function. Fails if there are breaking changes, unless
+/// `--allow-breaking` is set.
+#[derive(Parser)]
+#[clap(name = "diff-abi", after_help = EXAMPLES)]
+pub struct DiffAbi {
+    /// The directory of the ABI to compare with, as saved by `--save`. Defaults to `build/abi`.
+    #[clap(long = "baseline", parse(from_os_str), conflicts_with = "baseline-rev")]
+    pub baseline: Option<PathBuf>,
+    /// Build the package at this git revision (e.g. `main` or `HEAD~1`) in a temporary worktree,
+    /// and compare with its ABI
+    #[clap(long = "baseline-rev")]
+    pub baseline_rev: Option<String>,
+    /// Save the ABI of the package to `build/abi`, to compare with in later runs
+    #[clap(long = "save")]
+    pub save: bool,
+    /// Do not fail if there are breaking changes
+    #[clap(long = "allow-breaking")]
+    pub allow_breaking: bool,
+    /// Output format of the changes
+    #[clap(long = "format", arg_enum, ignore_case = true, default_value = "text")]
+    pub format: OutputFormat,
+}
+
+/// The public interface of a module, saved to `build/abi/<address>.<module>.json`
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ModuleAbi {
+    /// The address of the module, e.g. `0x2`
+    pub address: String,
+    pub name: String,
+    pub structs: Vec<StructAbi>,
+    /// The public functions and the entry functions of the module
+    pub functions: Vec<FunctionAbi>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct StructAbi {
+    pub name: String,
+    pub abilities: Vec<String>,
+    pub type_parameters: Vec<TypeParameterAbi>,
+    /// The fields of the struct, empty for a native struct
+    pub fields: Vec<FieldAbi>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct TypeParameterAbi {
+    /// The abilities the type parameter is constrained with
+    pub constraints: Vec<String>,
+    pub is_phantom: bool,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct FieldAbi {
+    pub name: String,
+    #[serde(rename = "type")]
+    pub type_: String,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct FunctionAbi {
+    pub name: String,
+    pub is_public: bool,
+    pub is_entry: bool,
+    /// The constraints of each type parameter
+    pub type_parameters: Vec<Vec<String>>,
+    pub parameters: Vec<String>,
+    pub returns: Vec<String>,
+}
+
+/// The kind of a change to the ABI
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ChangeKind {
+    AddedModule,
+    RemovedModule,
+    AddedFunction,
+    /// A function was removed, or is neither public nor entry anymore
+    RemovedFunction,
+    /// The parameters, return types or number of type parameters of a function changed, or it
+    /// stopped being public or entry
+    ChangedSignature,
+    /// A function became public or entry
+    WidenedVisibility,
+    /// A type parameter requires more abilities
+    TightenedConstraints,
+    /// A type parameter requires fewer abilities
+    LoosenedConstraints,
+    AddedStruct,
+    RemovedStruct,
+    /// The number of type parameters of a struct changed, or one of them stopped or started being
+    /// phantom
+    ChangedTypeParameters,
+    AddedField,
+    RemovedField,
+    /// The type of a field changed
+    ChangedField,
+    AddedAbility,
+    RemovedAbility,
+}
+
+impl ChangeKind {
+    /// Whether code depending on the baseline may not compile or link against the package anymore
+    pub fn is_breaking(self) -> bool {
+        use ChangeKind::*;
+        match self {
+            RemovedModule
+            | RemovedFunction
+            | ChangedSignature
+            | TightenedConstraints
+            | RemovedStruct
+            | ChangedTypeParameters
+            | RemovedField
+            | ChangedField
+            | RemovedAbility => true,
+            AddedModule | AddedFunction | WidenedVisibility | LoosenedConstraints | AddedStruct
+            | AddedField | AddedAbility => false,
+        }
+    }
+}
+
+impl fmt::Display for ChangeKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        use ChangeKind::*;
+        let name = match self {
+            AddedModule => "added module",
+            RemovedModule => "removed module",
+            AddedFunction => "added function",
+            RemovedFunction => "removed function",
+            ChangedSignature => "changed signature",
+            WidenedVisibility => "widened visibility",
+            TightenedConstraints => "tightened constraints",
+            LoosenedConstraints => "loosened constraints",
+            AddedStruct => "added struct",
+            RemovedStruct => "removed struct",
+            ChangedTypeParameters => "changed type parameters",
+            AddedField => "added field",
+            RemovedField => "removed field",
+            ChangedField => "changed field",
+            AddedAbility => "added ability",
+            RemovedAbility => "removed ability",
+        };
+        write!(f, "{}", name)
+    }
+}
+
+/// A change to the ABI of the package
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct AbiChange {
+    pub breaking: bool,
+    pub kind: ChangeKind,
+    /// The module, function, struct or field changed, e.g. `0x2::Coin::mint` or
+    /// `0x2::Coin::Coin.value`
+    pub item: String,
+    /// The declaration of the item in the baseline, if any
+    pub before: Option<String>,
+    /// The declaration of the item in the package, if any
+    pub after: Option<String>,
+}
+
+/// The report printed by `--format json`
+#[derive(Serialize)]
+struct AbiDiffReport<'a> {
+    breaking: usize,
+    non_breaking: usize,
+    changes: &'a [AbiChange],
+}
+
+impl DiffAbi {
+    pub fn execute(self, path: Option<PathBuf>, config: BuildConfig) -> Result<()> {
+        let root = package_root(path.as_deref())?;
+        let saved_dir = root
+            .join(CompiledPackageLayout::Root.path())
+            .join(CompiledPackageLayout::SavedAbi.path());
+        let baseline = match (&self.baseline_rev, &self.baseline) {
+            (Some(rev), _) => Some(build_at_revision(&root, rev, config.clone(), package_abi)?),
+            (None, Some(dir)) => Some(read_abi(dir)?),
+            (None, None) if saved_dir.exists() => Some(read_abi(&saved_dir)?),
+            (None, None) if self.save => None,
+            (None, None) => bail!(
+                "No baseline to compare with: '{}' does not exist. Save the ABI of the baseline \
+                 with --save, or pass --baseline or --baseline-rev",
+                saved_dir.display()
+            ),
+        };
+        let current = package_abi(&root, config)?;
+
+        let mut breaking = 0;
+        if let Some(baseline) = &baseline {
+            let changes = diff_abi(baseline, &current);
+            breaking = changes.iter().filter(|change| change.breaking).count();
+            match self.format {
+                OutputFormat::Text => print_changes(&changes, breaking),
+                OutputFormat::Json => {
+                    let report = AbiDiffReport {
+                        breaking,
+                        non_breaking: changes.len() - breaking,
+                        changes: &changes,
+                    };
+                    println!("{}", serde_json::to_string_pretty(&report)?)
+                }
+            }
+        }
+        if self.save {
+            save_abi(&saved_dir, &current)?;
+            let message = format!(
+                "Saved the ABI of {} modules to {}",
+                current.len(),
+                Path::new(CompiledPackageLayout::Root.path())
+                    .join(CompiledPackageLayout::SavedAbi.path())
+                    .display()
+            );
+            // keep the JSON output parseable
+            match self.format {
+                OutputFormat::Text => println!("{}", message),
+                OutputFormat::Json => eprintln!("{}", message),
+            }
+        }
+        if breaking > 0 && !self.allow_breaking {
+            bail!(
+                "{} breaking changes compared to the baseline; pass --allow-breaking to accept \
+                 them",
+                breaking
+            )
+        }
+        Ok(())
+    }
+}
+
+/// The changes from the ABI of `baseline` to that of `current`, breaking changes first
+pub fn diff_abi(baseline: &[ModuleAbi], current: &[ModuleAbi]) -> Vec<AbiChange> {
+    let mut changes = vec![];
+    let baseline = baseline.iter().map(|m| (m.id(), m)).collect();
+    let current = current.iter().map(|m| (m.id(), m)).collect();
+    for (id, before, after) in pair_by_name(&baseline, &current) {
+        match (before, after) {
+            (Some(_), None) => changes.push(change(ChangeKind::RemovedModule, id, None, None)),
+            (None, Some(_)) => changes.push(change(ChangeKind::AddedModule, id, None, None)),
+            (Some(before), Some(after)) => diff_module(&mut changes, before, after),
+            (None, None) => unreachable!(),
+        }
+    }
+    // stable, so that changes stay in the order of the modules and of their items
+    changes.sort_by_key(|change| !change.breaking);
+    changes
+}
+
+fn diff_module(changes: &mut Vec<AbiChange>, before: &ModuleAbi, after: &ModuleAbi) {
+    let module_id = before.id();
+    let (before_structs, after_structs) = (by_name(&before.structs), by_name(&after.structs));
+    for (name, before, after) in pair_by_name(&before_structs, &after_structs) {
+        let item = format!("{}::{}", module_id, name);
+        match (before, after) {
+            (Some(before), None) => changes.push(change(
+                ChangeKind::RemovedStruct,
+                item,
+                Some(before.declaration()),
+                None,
+            )),
+            (None, Some(after)) => changes.push(change(
+                ChangeKind::AddedStruct,
+                item,
+                None,
+                Some(after.declaration()),
+            )),
+            (Some(before), Some(after)) => diff_struct(changes, item, before, after),
+            (None, None) => unreachable!(),
+        }
+    }
+
+    let (before_functions, after_functions) =
+        (by_name(&before.functions), by_name(&after.functions));
+    for (name, before, after) in pair_by_name(&before_functions, &after_functions) {
+        let item = format!("{}::{}", module_id, name);
+        match (before, after) {
+            (Some(before), None) => changes.push(change(
+                ChangeKind::RemovedFunction,
+                item,
+                Some(before.signature()),
+                None,
+            )),
+            (None, Some(after)) => changes.push(change(
+                ChangeKind::AddedFunction,
+                item,
+                None,
+                Some(after.signature()),
+            )),
+            (Some(before), Some(after)) => diff_function(changes, item, before, after),
+            (None, None) => unreachable!(),
+        }
+    }
+}
+
+fn diff_struct(changes: &mut Vec<AbiChange>, item: String, before: &StructAbi, after: &StructAbi) {
+    let declarations = || (Some(before.declaration()), Some(after.declaration()));
+    let phantoms =
+        |s: &StructAbi| -> Vec<bool> { s.type_parameters.iter().map(|tp| tp.is_phantom).collect() };
+    let constraints = |s: &StructAbi| -> Vec<Vec<String>> {
+        s.type_parameters
+            .iter()
+            .map(|tp| tp.constraints.clone())
+            .collect()
+    };
+    if phantoms(before) != phantoms(after) {
+        let (before, after) = declarations();
+        changes.push(change(
+            ChangeKind::ChangedTypeParameters,
+            item.clone(),
+            before,
+            after,
+        ));
+    } else if let Some(kind) = diff_constraints(&constraints(before), &constraints(after)) {
+        let (before, after) = declarations();
+        changes.push(change(kind, item.clone(), before, after));
+    }
+
+    let (removed, added) = diff_sets(&before.abilities, &after.abilities);
+    for (kind, abilities) in [
+        (ChangeKind::RemovedAbility, removed),
+        (ChangeKind::AddedAbility, added),
+    ] {
+        if !abilities.is_empty() {
+            changes.push(change(
+                kind,
+                item.clone(),
+                Some(abilities_str(&before.abilities)),
+                Some(abilities_str(&after.abilities)),
+            ));
+        }
+    }
+
+    let (before_fields, after_fields) = (by_name(&before.fields), by_name(&after.fields));
+    for (name, before, after) in pair_by_name(&before_fields, &after_fields) {
+        let item = format!("{}.{}", item, name);
+        let field = |f: &FieldAbi| format!("{}: {}", f.name, f.type_);
+        match (before, after) {
+            (Some(before), None) => changes.push(change(
+                ChangeKind::RemovedField,
+                item,
+                Some(field(before)),
+                None,
+            )),
+            (None, Some(after)) => changes.push(change(
+                ChangeKind::AddedField,
+                item,
+                None,
+                Some(field(after)),
+            )),
+            (Some(before), Some(after)) if before.type_ != after.type_ => changes.push(change(
+                ChangeKind::ChangedField,
+                item,
+                Some(field(before)),
+                Some(field(after)),
+            )),
+            _ => (),
+        }
+    }
+}
+
+fn diff_function(
+    changes: &mut Vec<AbiChange>,
+    item: String,
+    before: &FunctionAbi,
+    after: &FunctionAbi,
+) {
+    let (signature_before, signature_after) = (Some(before.signature()), Some(after.signature()));
+    let narrowed = (before.is_public && !after.is_public) || (before.is_entry && !after.is_entry);
+    let widened = (!before.is_public && after.is_public) || (!before.is_entry && after.is_entry);
+    if narrowed || before.parameters != after.parameters || before.returns != after.returns {
+        changes.push(change(
+            ChangeKind::ChangedSignature,
+            item,
+            signature_before,
+            signature_after,
+        ));
+        return;
+    }
+    match diff_constraints(&before.type_parameters, &after.type_parameters) {
+        Some(ChangeKind::ChangedTypeParameters) => {
+            changes.push(change(
+                ChangeKind::ChangedSignature,
+                item,
+                signature_before,
+                signature_after,
+            ));
+            return;
+        }
+        Some(kind) => changes.push(change(
+            kind,
+            item.clone(),
+            signature_before.clone(),
+            signature_after.clone(),
+        )),
+        None => (),
+    }
+    if widened {
+        changes.push(change(
+            ChangeKind::WidenedVisibility,
+            item,
+            signature_before,
+            signature_after,
+        ));
+    }
+}
+
+/// How the constraints of type parameters changed: `ChangedTypeParameters` if their number
+/// changed, `TightenedConstraints` if any requires an ability it did not, and
+/// `LoosenedConstraints` if any does not require an ability it did anymore
+fn diff_constraints(before: &[Vec<String>], after: &[Vec<String>]) -> Option<ChangeKind> {
+    if before.len() != after.len() {
+        return Some(ChangeKind::ChangedTypeParameters);
+    }
+    let mut loosened = false;
+    for (before, after) in before.iter().zip(after) {
+        let (removed, added) = diff_sets(before, after);
+        if !added.is_empty() {
+            return Some(ChangeKind::TightenedConstraints);
+        }
+        loosened |= !removed.is_empty();
+    }
+    loosened.then(|| ChangeKind::LoosenedConstraints)
+}
+
+/// The elements of `before` not in `after`, and those of `after` not in `before`
+fn diff_sets<'a>(before: &'a [String], after: &'a [String]) -> (Vec<&'a str>, Vec<&'a str>) {
+    let missing_from = |set: &'a [String], other: &'a [String]| {
+        set.iter()
+            .filter(|s| !other.contains(s))
+            .map(String::as_str)
+            .collect()
+    };
+    (missing_from(before, after), missing_from(after, before))
+}
+
+/// Structs, functions or fields by their name
+fn by_name<T: Named>(items: &[T]) -> BTreeMap<String, &T> {
+    items
+        .iter()
+        .map(|item| (item.name().to_string(), item))
+        .collect()
+}
+
+trait Named {
+    fn name(&self) -> &str;
+}
+
+impl Named for StructAbi {
+    fn name(&self) -> &str {
+        &self.name
+    }
+}
+
+impl Named for FunctionAbi {
+    fn name(&self) -> &str {
+        &self.name
+    }
+}
+
+impl Named for FieldAbi {
+    fn name(&self) -> &str {
+        &self.name
+    }
+}
+
+/// The items of `before` and `after`, paired by name, in order of their names
+fn pair_by_name<'a, T>(
+    before: &BTreeMap<String, &'a T>,
+    after: &BTreeMap<String, &'a T>,
+) -> Vec<(String, Option<&'a T>, Option<&'a T>)> {
+    let names: BTreeSet<&String> = before.keys().chain(after.keys()).collect();
+    names
+        .into_iter()
+        .map(|name| {
+            (
+                name.clone(),
+                before.get(name).copied(),
+                after.get(name).copied(),
+            )
+        })
+        .collect()
+}
+
+fn change(
+    kind: ChangeKind,
+    item: String,
+    before: Option<String>,
+    after: Option<String>,
+) -> AbiChange {
+    AbiChange {
+        breaking: kind.is_breaking(),
+        kind,
+        item,
+        before,
+        after,
+    }
+}
+
+fn print_changes(changes: &[AbiChange], breaking: usize) {
+    if changes.is_empty() {
+        println!("No changes to the ABI");
+        return;
+    }
+    let rows: Vec<[String; 4]> = changes
+        .iter()
+        .map(|change| {
+            let detail = match (&change.before, &change.after) {
+                (Some(before), Some(after)) => format!("{} -> {}", before, after),
+                (Some(declaration), None) | (None, Some(declaration)) => declaration.clone(),
+                (None, None) => String::new(),
+            };
+            let status = if change.breaking {
+                "breaking"
+            } else {
+                "non-breaking"
+            };
+            [
+                status.to_string(),
+                change.kind.to_string(),
+                change.item.clone(),
+                detail,
+            ]
+        })
+        .collect();
+    let header = ["CHANGE", "KIND", "ITEM", "DETAIL"].map(String::from);
+    let mut widths = [0; 3];
+    for row in std::iter::once(&header).chain(&rows) {
+        for (width, cell) in widths.iter_mut().zip(row) {
+            *width = (*width).max(cell.len());
+        }
+    }
+    for row in std::iter::once(&header).chain(&rows) {
+        println!(
+            "{:<w0$}  {:<w1$}  {:<w2$}  {}",
+            row[0],
+            row[1],
+            row[2],
+            row[3],
+            w0 = widths[0],
+            w1 = widths[1],
+            w2 = widths[2]
+        );
+    }
+    println!(
+        "{} breaking, {} non-breaking changes",
+        breaking,
+        changes.len() - breaking
+    );
+}
+
+impl ModuleAbi {
+    /// The module id, e.g. `0x2::Coin`
+    pub fn id(&self) -> String {
+        format!("{}::{}", self.address, self.name)
+    }
+
+    fn new(module: &CompiledModule) -> Self {
+        let self_id = module.self_id();
+        let structs = module
+            .struct_defs()
+            .iter()
+            .map(|def| {
+                let handle = module.struct_handle_at(def.struct_handle);
+                let fields = match &def.field_information {
+                    StructFieldInformation::Native => vec![],
+                    StructFieldInformation::Declared(fields) => fields
+                        .iter()
+                        .map(|field| FieldAbi {
+                            name: module.identifier_at(field.name).to_string(),
+                            type_: type_str(module, &field.signature.0),
+                        })
+                        .collect(),
+                };
+                StructAbi {
+                    name: module.identifier_at(handle.name).to_string(),
+                    abilities: ability_names(handle.abilities),
+                    type_parameters: handle
+                        .type_parameters
+                        .iter()
+                        .map(|tp| TypeParameterAbi {
+                            constraints: ability_names(tp.constraints),
+                            is_phantom: tp.is_phantom,
+                        })
+                        .collect(),
+                    fields,
+                }
+            })
+            .collect();
+        let functions = module
+            .function_defs()
+            .iter()
+            .filter(|def| def.visibility == Visibility::Public || def.is_entry)
+            .map(|def| {
+                let handle = module.function_handle_at(def.function);
+                let types = |idx| {
+                    module
+                        .signature_at(idx)
+                        .0
+                        .iter()
+                        .map(|token| type_str(module, token))
+                        .collect()
+                };
+                FunctionAbi {
+                    name: module.identifier_at(handle.name).to_string(),
+                    is_public: def.visibility == Visibility::Public,
+                    is_entry: def.is_entry,
+                    type_parameters: handle
+                        .type_parameters
+                        .iter()
+                        .map(|constraints| ability_names(*constraints))
+                        .collect(),
+                    parameters: types(handle.parameters),
+                    returns: types(handle.return_),
+                }
+            })
+            .collect();
+        Self {
+            address: format!("0x{}", self_id.address().short_str_lossless()),
+            name: self_id.name().to_string(),
+            structs,
+            functions,
+        }
+    }
+}
+
+impl StructAbi {
+    /// The declaration of the struct without its fields, e.g. `struct Coin<phantom T0> has store`
+    fn declaration(&self) -> String {
+        let type_parameters = self
+            .type_parameters
+            .iter()
+            .map(|tp| {
+                let name = if tp.is_phantom { "phantom T" } else { "T" };
+                (name, &tp.constraints)
+            })
+            .collect::<Vec<_>>();
+        let mut declaration = format!(
+            "struct {}{}",
+            self.name,
+            type_parameters_str(&type_parameters)
+        );
+        if !self.abilities.is_empty() {
+            declaration = format!("{} has {}", declaration, self.abilities.join(", "));
+        }
+        declaration
+    }
+}
+
+impl FunctionAbi {
+    /// The signature of the function, e.g. `public fun mint<T0: drop>(u64): 0x2::Coin::Coin<T0>`
+    fn signature(&self) -> String {
+        let type_parameters = self
+            .type_parameters
+            .iter()
+            .map(|constraints| ("T", constraints))
+            .collect::<Vec<_>>();
+        let returns = match self.returns.as_slice() {
+            [] => String::new(),
+            [ty] => format!(": {}", ty),
+            tys => format!(": ({})", tys.join(", ")),
+        };
+        format!(
+            "{}{}fun {}{}({}){}",
+            if self.is_public { "public " } else { "" },
+            if self.is_entry { "entry " } else { "" },
+            self.name,
+            type_parameters_str(&type_parameters),
+            self.parameters.join(", "),
+            returns
+        )
+    }
+}
+
+/// Format type parameters given by their name prefix and constraints, e.g. `<T0, T1: copy>`
+fn type_parameters_str(type_parameters: &[(&str, &Vec<String>)]) -> String {
+    if type_parameters.is_empty() {
+        return String::new();
+    }
+    let type_parameters = type_parameters
+        .iter()
+        .enumerate()
+        .map(|(i, (prefix, constraints))| {
+            if constraints.is_empty() {
+                format!("{}{}", prefix, i)
+            } else {
+                format!("{}{}: {}", prefix, i, constraints.join(" + "))
+            }
+        })
+        .collect::<Vec<_>>();
+    format!("<{}>", type_parameters.join(", "))
+}
+
+fn abilities_str(abilities: &[String]) -> String {
+    if abilities.is_empty() {
+        "no abilities".to_string()
+    } else {
+        format!("has {}", abilities.join(", "))
+    }
+}
+
+fn ability_names(abilities: AbilitySet) -> Vec<String> {
+    abilities
+        .into_iter()
+        .map(|ability| {
+            match ability {
+                Ability::Copy => "copy",
+                Ability::Drop => "drop",
+                Ability::Store => "store",
+                Ability::Key => "key",
+            }
+            .to_string()
+        })
+        .collect()
+}
+
+/// The source-level name of `token`, with structs qualified by their module, e.g.
+/// `&mut 0x2::Coin::Coin<T0>`
+fn type_str(module: &CompiledModule, token: &SignatureToken) -> String {
+    let struct_name = |idx: StructHandleIndex| {
+        let handle = module.struct_handle_at(idx);
+        let module_handle = module.module_handle_at(handle.module);
+        format!(
+            "0x{}::{}::{}",
+            module
+                .address_identifier_at(module_handle.address)
+                .short_str_lossless(),
+            module.identifier_at(module_handle.name),
+            module.identifier_at(handle.name)
+        )
+    };
+    match token {
+        SignatureToken::Bool => "bool".to_string(),
+        SignatureToken::U8 => "u8".to_string(),
+        SignatureToken::U64 => "u64".to_string(),
+        SignatureToken::U128 => "u128".to_string(),
+        SignatureToken::Address => "address".to_string(),
+        SignatureToken::Signer => "signer".to_string(),
+        SignatureToken::Vector(ty) => format!("vector<{}>", type_str(module, ty)),
+        SignatureToken::Struct(idx) => struct_name(*idx),
+        SignatureToken::StructInstantiation(idx, tys) => format!(
+            "{}<{}>",
+            struct_name(*idx),
+            tys.iter()
+                .map(|ty| type_str(module, ty))
+                .collect::<Vec<_>>()
+                .join(", ")
+        ),
+        SignatureToken::Reference(ty) => format!("&{}", type_str(module, ty)),
+        SignatureToken::MutableReference(ty) => format!("&mut {}", type_str(module, ty)),
+        SignatureToken::TypeParameter(i) => format!("T{}", i),
+    }
+}
+
+/// The ABI of the modules of the package at `root`, which must compile
+fn package_abi(root: &Path, build_config: BuildConfig) -> Result<Vec<ModuleAbi>> {
+    let options = BuildOptions {
+        package_path: root.to_path_buf(),
+        build_config,
+    };
+    let output = build_package(options, &mut std::io::stderr())?;
+    let package = match &output.package {
+        Some(package) => package,
+        None => {
+            output.print_diagnostics();
+            bail!(
+                "Unable to compute the ABI of '{}': it does not compile",
+                root.display()
+            )
+        }
+    };
+    let mut modules: Vec<_> = package
+        .root_modules()
+        .filter_map(|unit| match &unit.unit {
+            CompiledUnit::Module(NamedCompiledModule { module, .. }) => {
+                Some(ModuleAbi::new(module))
+            }
+            CompiledUnit::Script(_) => None,
+        })
+        .collect();
+    modules.sort_by_key(ModuleAbi::id);
+    Ok(modules)
+}
+
+/// Read the ABI saved by `--save` to `dir`
+fn read_abi(dir: &Path) -> Result<Vec<ModuleAbi>> {
+    let entries =
+        fs::read_dir(dir).with_context(|| format!("Unable to read '{}'", dir.display()))?;
+    let mut modules = vec![];
+    for entry in entries {
+        let path = entry?.path();
+        if path.extension().and_then(|ext| ext.to_str()) != Some("json") {
+            continue;
+        }
+        let contents = fs::read_to_string(&path)
+            .with_context(|| format!("Unable to read '{}'", path.display()))?;
+        let module: ModuleAbi = serde_json::from_str(&contents)
+            .with_context(|| format!("Error parsing '{}'", path.display()))?;
+        modules.push(module);
+    }
+    modules.sort_by_key(ModuleAbi::id);
+    Ok(modules)
+}
+
+/// Save `modules` to `dir`, one file per module, replacing what was saved before
+fn save_abi(dir: &Path, modules: &[ModuleAbi]) -> Result<()> {
+    if dir.exists() {
+        fs::remove_dir_all(dir).with_context(|| format!("Unable to remove '{}'", dir.display()))?;
+    }
+    fs::create_dir_all(dir)?;
+    for module in modules {
+        let path = dir.join(format!("{}.{}.json", module.address, module.name));
+        fs::write(&path, serde_json::to_string_pretty(module)?)
+            .with_context(|| format!("Unable to write '{}'", path.display()))?;
+    }
+    Ok(())
+}
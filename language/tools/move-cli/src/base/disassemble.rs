@@ -50,6 +50,7 @@ impl Disassemble {
                 // viewer, or run the disassembler depending on if we need to run interactively
                 // or not.
                 if interactive {
+                    package.ensure_source_maps("The interactive disassembler")?;
                     match unit {
                         CompiledUnitWithSource {
                             unit:
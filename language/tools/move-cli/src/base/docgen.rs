@@ -3,9 +3,26 @@
 
 use super::reroot_path;
 use clap::*;
+use difference::{Changeset, Difference};
+use move_command_line_common::json_errors;
 use move_docgen::DocgenOptions;
-use move_package::{BuildConfig, ModelConfig};
-use std::{fs, path::PathBuf};
+use move_package::{
+    source_package::{manifest_parser::parse_move_manifest_from_file, parsed_manifest::DocgenInfo},
+    BuildConfig, ModelConfig,
+};
+use regex::{Captures, Regex};
+use std::{
+    collections::BTreeMap,
+    fmt::{self, Write},
+    fs,
+    path::{Component, Path, PathBuf},
+};
+
+/// File name of the generated landing page, placed in the output directory
+pub(crate) const INDEX_FILE: &str = "index.md";
+/// Files, relative to the package root, whose content introduces the landing page. The first one
+/// found is used.
+const OVERVIEW_FILES: &[&str] = &["doc/overview.md", "README.md"];
 
 /// Generate javadoc style documentation for Move packages
 #[derive(Parser)]
@@ -53,13 +70,25 @@ pub struct Docgen {
     /// If this is being compiled relative to a different place where it will be stored (output directory)
     #[clap(long = "compile-relative-to-output-dir")]
     pub compile_relative_to_output_dir: bool,
+    /// Check that the documentation in the output directory is up to date instead of writing it,
+    /// failing if it is not, e.g. in CI. Timestamps and tool versions in footers are ignored.
+    #[clap(long = "check")]
+    pub check: bool,
 }
 
 impl Docgen {
     /// Calling the Docgen
     pub fn execute(self, path: Option<PathBuf>, config: BuildConfig) -> anyhow::Result<()> {
+        let rerooted_path = reroot_path(path).unwrap();
+        let manifest = parse_move_manifest_from_file(&rerooted_path)?;
+        let remaps = config.remap_path_prefix_for_package(&manifest, &rerooted_path);
+        // the package directory is left untouched with `--out-dir`
+        let out_dir = match &config.out_dir {
+            Some(_) => config.install_dir.clone(),
+            None => None,
+        };
         let model = config.move_model_for_package(
-            &reroot_path(path).unwrap(),
+            &rerooted_path,
             ModelConfig {
                 all_files_as_targets: false,
                 target_filter: None,
@@ -94,6 +123,11 @@ impl Docgen {
         }
         if self.output_directory.is_some() {
             options.output_directory = self.output_directory.unwrap();
+        } else if let Some(out_dir) = out_dir {
+            options.output_directory = out_dir
+                .join(&options.output_directory)
+                .to_string_lossy()
+                .to_string();
         }
         if self.references_file.is_some() {
             options.references_file = self.references_file;
@@ -101,16 +135,67 @@ impl Docgen {
         if self.compile_relative_to_output_dir {
             options.compile_relative_to_output_dir = true;
         }
+        options.remap_path_prefix = remaps;
 
         // We are using the full namespace, since we already use `Docgen` here.
         // Docgen is the most suitable name for both: this Docgen subcommand,
         // and the actual move_docgen::Docgen.
         let generator = move_docgen::Docgen::new(&model, &options);
+        let mut pages: Vec<(PathBuf, String)> = generator
+            .gen()
+            .into_iter()
+            .map(|(file, content)| (PathBuf::from(file), content))
+            .collect();
+
+        let modules = model
+            .get_modules()
+            .filter(|m| m.is_target() && !m.is_script_module())
+            .map(|m| ModulePage {
+                address: format!("0x{:x}", m.get_name().addr()),
+                name: m.get_name().display(model.symbol_pool()).to_string(),
+                full_name: m.get_full_name_str(),
+                file: PathBuf::from(m.get_source_path())
+                    .with_extension("md")
+                    .file_name()
+                    .expect("file name")
+                    .to_string_lossy()
+                    .to_string(),
+                source: fs::canonicalize(m.get_source_path()).ok(),
+            })
+            .collect();
+        let index_path = if options.compile_relative_to_output_dir {
+            PathBuf::from(INDEX_FILE)
+        } else {
+            PathBuf::from(&options.output_directory).join(INDEX_FILE)
+        };
+        let index = gen_index(
+            manifest.package.name.as_str(),
+            &manifest.docgen.unwrap_or_default(),
+            modules,
+            Path::new(&options.output_directory),
+        )?;
+        pages.push((index_path, index));
 
-        for (file, content) in generator.gen() {
-            let path = PathBuf::from(&file);
-            fs::create_dir_all(path.parent().unwrap())?;
-            fs::write(path.as_path(), content)?;
+        if self.check {
+            anyhow::ensure!(
+                !model.has_errors(),
+                "Errors encountered while generating documentation!"
+            );
+            // hand-written files may live next to the generated ones
+            let inputs: Vec<_> = OVERVIEW_FILES
+                .iter()
+                .map(PathBuf::from)
+                .chain(options.root_doc_templates.iter().map(PathBuf::from))
+                .chain(options.references_file.iter().map(PathBuf::from))
+                .collect();
+            return check_pages(&pages, Path::new(&options.output_directory), &inputs);
+        }
+
+        for (path, content) in pages {
+            if let Some(parent) = path.parent() {
+                fs::create_dir_all(parent)?;
+            }
+            fs::write(&path, content)?;
             println!("Generated {:?}", path);
         }
 
@@ -123,3 +208,298 @@ impl Docgen {
         Ok(())
     }
 }
+
+/// How many of the pages that differ have their differences shown by `--check`
+const EXCERPT_PAGES: usize = 3;
+/// How many changed lines of a page are shown by `--check`
+const EXCERPT_LINES: usize = 20;
+
+/// A difference between the documentation generated and the one in the output directory
+enum PageChange {
+    Changed(PathBuf),
+    /// A page generated that is missing from the output directory
+    Added(PathBuf),
+    /// A page of the output directory that is not generated anymore
+    Removed(PathBuf),
+}
+
+impl fmt::Display for PageChange {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            PageChange::Changed(path) => write!(f, "changed: {}", path.display()),
+            PageChange::Added(path) => write!(f, "added: {}", path.display()),
+            PageChange::Removed(path) => write!(f, "removed: {}", path.display()),
+        }
+    }
+}
+
+/// Compare the generated `pages` with the pages in `output_dir`, leaving them untouched, and fail
+/// listing the differences if there are any. Markdown files of `output_dir` that are `inputs` of
+/// the generation are not pages.
+fn check_pages(
+    pages: &[(PathBuf, String)],
+    output_dir: &Path,
+    inputs: &[PathBuf],
+) -> anyhow::Result<()> {
+    let mut changes = vec![];
+    let mut excerpts = vec![];
+    for (path, content) in pages {
+        match fs::read_to_string(path) {
+            Ok(committed) => {
+                let (committed, content) = (normalize_page(&committed), normalize_page(content));
+                if committed != content {
+                    if excerpts.len() < EXCERPT_PAGES {
+                        excerpts.push((path, diff_excerpt(&committed, &content)));
+                    }
+                    changes.push(PageChange::Changed(path.clone()));
+                }
+            }
+            Err(_) => changes.push(PageChange::Added(path.clone())),
+        }
+    }
+    let is_input = |path: &Path| {
+        inputs
+            .iter()
+            .any(|input| fs::canonicalize(input).ok() == fs::canonicalize(path).ok())
+    };
+    for entry in walkdir::WalkDir::new(output_dir)
+        .into_iter()
+        .filter_map(|entry| entry.ok())
+    {
+        let path = entry.path();
+        if entry.file_type().is_file()
+            && path.extension().map_or(false, |ext| ext == "md")
+            && !pages.iter().any(|(page, _)| page == path)
+            && !is_input(path)
+        {
+            changes.push(PageChange::Removed(path.to_path_buf()));
+        }
+    }
+
+    if changes.is_empty() {
+        println!(
+            "Documentation in '{}' is up to date ({} pages)",
+            output_dir.display(),
+            pages.len()
+        );
+        return Ok(());
+    }
+    for change in &changes {
+        println!("    {}", change);
+    }
+    for (path, excerpt) in excerpts {
+        println!("\n--- {}\n{}", path.display(), excerpt);
+    }
+    anyhow::bail!(
+        "documentation in '{}' is out of date: {} {} differ (run `move docgen` to regenerate it)",
+        output_dir.display(),
+        changes.len(),
+        if changes.len() == 1 { "file" } else { "files" }
+    )
+}
+
+/// `content` without what changes from one generation to the next although the documentation does
+/// not: line endings, trailing whitespace, timestamps, and footers naming the tool that generated
+/// it, e.g. `<!-- Generated by move-docgen 1.2.0 on 2022-08-01 -->`.
+fn normalize_page(content: &str) -> String {
+    let footer =
+        Regex::new(r"(?i)^\W*(generated (by|with|on|at)\b|this file was generated\b)").unwrap();
+    let timestamp = Regex::new(
+        r"\d{4}-\d{2}-\d{2}([T ]\d{2}:\d{2}(:\d{2}(\.\d+)?)?( ?(Z|UTC|[+-]\d{2}:?\d{2}))?)?",
+    )
+    .unwrap();
+    let mut normalized = content
+        .lines()
+        .map(str::trim_end)
+        .filter(|line| !footer.is_match(line))
+        .map(|line| timestamp.replace_all(line, "<timestamp>").into_owned())
+        .collect::<Vec<_>>()
+        .join("\n");
+    normalized.truncate(normalized.trim_end().len());
+    normalized
+}
+
+/// The lines removed from `committed` and added by `generated`, up to `EXCERPT_LINES` of them
+fn diff_excerpt(committed: &str, generated: &str) -> String {
+    let Changeset { diffs, .. } = Changeset::new(committed, generated, "\n");
+    let lines: Vec<_> = diffs
+        .iter()
+        .flat_map(|diff| match diff {
+            Difference::Same(_) => vec![],
+            Difference::Rem(lines) => lines.split('\n').map(|l| format!("-{}", l)).collect(),
+            Difference::Add(lines) => lines.split('\n').map(|l| format!("+{}", l)).collect(),
+        })
+        .collect();
+    let mut excerpt = lines
+        .iter()
+        .take(EXCERPT_LINES)
+        .cloned()
+        .collect::<Vec<_>>()
+        .join("\n");
+    if lines.len() > EXCERPT_LINES {
+        write!(
+            excerpt,
+            "\n... {} more changed lines",
+            lines.len() - EXCERPT_LINES
+        )
+        .unwrap();
+    }
+    excerpt
+}
+
+/// A module of the package and the page documenting it
+struct ModulePage {
+    address: String,
+    name: String,
+    full_name: String,
+    file: String,
+    source: Option<PathBuf>,
+}
+
+/// Generate the landing page: the package overview (or README) followed by a table of contents
+/// linking every module page, grouped by address.
+fn gen_index(
+    package_name: &str,
+    info: &DocgenInfo,
+    mut modules: Vec<ModulePage>,
+    output_dir: &Path,
+) -> anyhow::Result<String> {
+    let current_dir = std::env::current_dir()?;
+    let mut index = String::new();
+    writeln!(
+        index,
+        "# {}\n",
+        info.title.as_deref().unwrap_or(package_name)
+    )?;
+    if let Some(logo) = &info.logo {
+        if logo.exists() {
+            writeln!(
+                index,
+                "![logo]({})\n",
+                relative_to(&current_dir, output_dir, logo).to_string_lossy()
+            )?;
+        } else {
+            json_errors::warning(format!(
+                "logo '{}' not found, leaving it out of the index page",
+                logo.to_string_lossy()
+            ));
+        }
+    }
+    if let Some(overview) = OVERVIEW_FILES.iter().map(Path::new).find(|f| f.is_file()) {
+        let content = fs::read_to_string(overview)?;
+        writeln!(
+            index,
+            "{}\n",
+            rewrite_links(&content, &modules, &current_dir, output_dir).trim_end()
+        )?;
+    }
+
+    // modules listed in `module_order` come first, in that order, the others by name
+    for name in &info.module_order {
+        if !modules.iter().any(|m| m.name == name.as_str()) {
+            json_errors::warning(format!(
+                "module '{}' listed in the [docgen] module_order is not part of the package",
+                name
+            ));
+        }
+    }
+    let rank = |m: &ModulePage| {
+        info.module_order
+            .iter()
+            .position(|name| name.as_str() == m.name)
+            .unwrap_or(info.module_order.len())
+    };
+    modules.sort_by(|a, b| rank(a).cmp(&rank(b)).then_with(|| a.name.cmp(&b.name)));
+    let mut by_address: BTreeMap<&str, Vec<&ModulePage>> = BTreeMap::new();
+    for module in &modules {
+        by_address.entry(&module.address).or_default().push(module);
+    }
+
+    writeln!(index, "## Modules")?;
+    if by_address.is_empty() {
+        writeln!(index, "\nThis package has no modules.")?;
+    }
+    for (address, modules) in by_address {
+        writeln!(index, "\n### Address `{}`\n", address)?;
+        for module in modules {
+            writeln!(index, "- [`{}`]({})", module.full_name, module.file)?;
+        }
+    }
+    Ok(index)
+}
+
+/// Rewrite the relative links of `content`, which are relative to the package root
+/// `current_dir`, so that they resolve from `output_dir`. Links to module sources point to the
+/// module's page instead; links to missing files are dropped (keeping their text) with a warning.
+fn rewrite_links(
+    content: &str,
+    modules: &[ModulePage],
+    current_dir: &Path,
+    output_dir: &Path,
+) -> String {
+    let link = Regex::new(r"(!?)\[([^\]]*)\]\(([^)\s]+)\)").unwrap();
+    link.replace_all(content, |caps: &Captures| {
+        let (image, text, target) = (&caps[1], &caps[2], &caps[3]);
+        let (path, fragment) = match target.find('#') {
+            Some(i) => target.split_at(i),
+            None => (target, ""),
+        };
+        if path.is_empty() || path.contains("://") || path.starts_with("mailto:") {
+            return caps[0].to_string();
+        }
+        let path = Path::new(path);
+        if path.is_absolute() {
+            return caps[0].to_string();
+        }
+        if !path.exists() {
+            json_errors::warning(format!(
+                "dropping link to '{}' from the index page: file not found",
+                target
+            ));
+            return text.to_string();
+        }
+        if path.extension().map_or(false, |ext| ext == "move") {
+            let source = fs::canonicalize(path).ok();
+            return match modules
+                .iter()
+                .find(|m| m.source.is_some() && m.source == source)
+            {
+                Some(module) => format!("{}[{}]({})", image, text, module.file),
+                None => {
+                    json_errors::warning(format!(
+                        "dropping link to '{}' from the index page: no module documented for it",
+                        target
+                    ));
+                    text.to_string()
+                }
+            };
+        }
+        format!(
+            "{}[{}]({}{})",
+            image,
+            text,
+            relative_to(current_dir, output_dir, path).to_string_lossy(),
+            fragment
+        )
+    })
+    .to_string()
+}
+
+/// The path of `target` relative to the directory `from`. Both are relative to `current_dir` (the
+/// package root) or absolute.
+fn relative_to(current_dir: &Path, from: &Path, target: &Path) -> PathBuf {
+    let absolute = |p: &Path| fs::canonicalize(p).unwrap_or_else(|_| current_dir.join(p));
+    let (from, target) = (absolute(from), absolute(target));
+    let from_components = from.components().collect::<Vec<_>>();
+    let target_components = target.components().collect::<Vec<_>>();
+    let common = from_components
+        .iter()
+        .zip(&target_components)
+        .take_while(|(a, b)| a == b)
+        .count();
+    from_components[common..]
+        .iter()
+        .map(|_| Component::ParentDir)
+        .chain(target_components[common..].iter().copied())
+        .collect()
+}
@@ -6,6 +6,9 @@ use clap::*;
 use move_package::{BuildConfig, ModelConfig};
 use std::path::PathBuf;
 
+/// The file, without its extension, `errmap` serializes the error map of a package to by default
+pub const DEFAULT_ERRMAP_FILE: &str = "error_map";
+
 /// Generate error map for the package and its dependencies at `path` for use by the Move
 /// explanation tool.
 #[derive(Parser)]
@@ -16,7 +19,7 @@ pub struct Errmap {
     #[clap(long)]
     pub error_prefix: Option<String>,
     /// The file to serialize the generated error map to.
-    #[clap(long, default_value = "error_map", parse(from_os_str))]
+    #[clap(long, default_value = DEFAULT_ERRMAP_FILE, parse(from_os_str))]
     pub output_file: PathBuf,
 }
 
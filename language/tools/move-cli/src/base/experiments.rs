@@ -0,0 +1,35 @@
+// Copyright (c) The Move Contributors
+// SPDX-License-Identifier: Apache-2.0
+
+use anyhow::Result;
+use clap::*;
+use move_command_line_common::experiments::{is_enabled, EXPERIMENTS};
+
+/// Print the available experiments, with their status, whether they are enabled, and the feature
+/// they gate.
+#[derive(Parser)]
+#[clap(name = "list")]
+pub struct List;
+
+impl List {
+    pub fn execute(self) -> Result<()> {
+        println!(
+            "{:<20} {:<12} {:<8} DESCRIPTION",
+            "NAME", "STATUS", "ENABLED"
+        );
+        for experiment in EXPERIMENTS {
+            println!(
+                "{:<20} {:<12} {:<8} {}",
+                experiment.name,
+                experiment.status.to_string(),
+                if is_enabled(experiment.name) {
+                    "yes"
+                } else {
+                    "no"
+                },
+                experiment.description
+            );
+        }
+        Ok(())
+    }
+}
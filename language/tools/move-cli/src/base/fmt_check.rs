@@ -0,0 +1,246 @@
+// Copyright (c) The Move Contributors
+// SPDX-License-Identifier: Apache-2.0
+
+use super::package_root;
+use anyhow::{bail, Context, Result};
+use clap::*;
+use move_command_line_common::files::UTF8_BOM;
+use move_package::source_package::layout::{package_move_files, SourcePackageLayout};
+use std::{
+    fs,
+    path::{Path, PathBuf},
+};
+
+/// The number of columns a tab advances to, to convert between tabs and spaces
+const TAB_WIDTH: usize = 4;
+
+const EXAMPLES: &str = "EXAMPLES:
+    List the issues in the sources of the package containing the current directory:
+        move package fmt-check
+
+    Fix them in place:
+        move package fmt-check --fix
+
+    Fail if there is any, e.g. in CI:
+        move package fmt-check --deny";
+
+/// Check the Move source files of the package for whitespace that causes spurious diffs across
+/// platforms and editors: CRLF or lone CR line endings, vertical tabs and form feeds, trailing
+/// whitespace, indentation with tabs in a file indented with spaces or the other way around, and a
+/// missing final newline. This is not a formatter: only whitespace is changed by `--fix`.
+#[derive(Parser)]
+#[clap(name = "fmt-check", after_help = EXAMPLES)]
+pub struct FmtCheck {
+    /// Rewrite the files to fix the issues found
+    #[clap(long = "fix")]
+    pub fix: bool,
+    /// Fail if any issue is found and not fixed
+    #[clap(long = "deny")]
+    pub deny: bool,
+}
+
+/// A whitespace issue in a source file
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FormatIssue {
+    /// The line of the issue, starting from 1
+    pub line: usize,
+    pub message: String,
+}
+
+/// How the lines of a file are indented
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum IndentStyle {
+    Spaces,
+    /// Tabs, followed by fewer than `TAB_WIDTH` spaces for alignment
+    Tabs,
+}
+
+impl FmtCheck {
+    pub fn execute(self, path: Option<PathBuf>) -> Result<()> {
+        let root = package_root(path.as_deref())?;
+        let files = source_files(&root)?;
+        let (mut issues, mut files_with_issues) = (0, 0);
+        for file in &files {
+            let source = fs::read_to_string(file)
+                .with_context(|| format!("Unable to read '{}'", file.display()))?;
+            let file_issues = check_source(&source);
+            if file_issues.is_empty() {
+                continue;
+            }
+            issues += file_issues.len();
+            files_with_issues += 1;
+            let relative = file.strip_prefix(&root).unwrap_or(file);
+            for issue in file_issues {
+                println!("{}:{}: {}", relative.display(), issue.line, issue.message);
+            }
+            if self.fix {
+                fs::write(file, fix_source(&source))
+                    .with_context(|| format!("Unable to write '{}'", file.display()))?;
+            }
+        }
+
+        if issues == 0 {
+            println!("No formatting issues in {} files", files.len());
+        } else if self.fix {
+            println!("Fixed {} issues in {} files", issues, files_with_issues);
+        } else {
+            println!(
+                "{} issues in {} files; run with --fix to fix them",
+                issues, files_with_issues
+            );
+            if self.deny {
+                bail!("{} formatting issues, and --deny is set", issues)
+            }
+        }
+        Ok(())
+    }
+}
+
+/// The Move files of the package at `root`, in order
+fn source_files(root: &Path) -> Result<Vec<PathBuf>> {
+    let mut files = package_move_files(
+        root,
+        &[
+            SourcePackageLayout::Sources,
+            SourcePackageLayout::Scripts,
+            SourcePackageLayout::Examples,
+            SourcePackageLayout::Tests,
+            SourcePackageLayout::Specifications,
+        ],
+    )?;
+    files.sort();
+    Ok(files)
+}
+
+/// The whitespace issues of `source`, in order of their lines
+pub fn check_source(source: &str) -> Vec<FormatIssue> {
+    let mut issues = vec![];
+    let mut issue = |line: usize, message: &str| {
+        issues.push(FormatIssue {
+            line,
+            message: message.to_string(),
+        })
+    };
+    if source.starts_with(UTF8_BOM) {
+        issue(1, "byte order mark");
+    }
+    let lines: Vec<&str> = source
+        .split_inclusive('\n')
+        .map(|line| line.strip_suffix('\n').unwrap_or(line))
+        .collect();
+    let crlf_lines: Vec<usize> = (1..=lines.len())
+        .filter(|number| lines[number - 1].ends_with('\r'))
+        .collect();
+    if let Some(first) = crlf_lines.first() {
+        issue(
+            *first,
+            &format!("CRLF line endings on {} lines", crlf_lines.len()),
+        );
+    }
+
+    let lines: Vec<&str> = lines
+        .iter()
+        .map(|line| line.strip_suffix('\r').unwrap_or(line))
+        .collect();
+    let style = indent_style(&lines);
+    for (i, line) in lines.iter().enumerate() {
+        let number = i + 1;
+        for (c, name) in [
+            ('\r', "carriage return without a line feed"),
+            ('\x0b', "vertical tab"),
+            ('\x0c', "form feed"),
+        ] {
+            if line.contains(c) {
+                issue(number, name);
+            }
+        }
+        if line.ends_with(&[' ', '\t'][..]) {
+            issue(number, "trailing whitespace");
+        }
+        if !line.trim().is_empty() {
+            let indentation = indentation(line);
+            if reindent(indentation, style) != indentation {
+                issue(
+                    number,
+                    match style {
+                        IndentStyle::Spaces => {
+                            "indented with tabs, while the file is indented with spaces"
+                        }
+                        IndentStyle::Tabs => {
+                            "indented with spaces, while the file is indented with tabs"
+                        }
+                    },
+                );
+            }
+        }
+    }
+    if !source.is_empty() && !source.ends_with('\n') {
+        issue(lines.len(), "missing newline at the end of the file");
+    }
+    issues.sort_by_key(|issue| issue.line);
+    issues
+}
+
+/// `source` with the issues reported by `check_source` fixed. Line endings become LF, vertical
+/// tabs and form feeds are removed, and the indentation of each line is converted to the
+/// indentation of most lines.
+pub fn fix_source(source: &str) -> String {
+    let source = source
+        .strip_prefix(UTF8_BOM)
+        .unwrap_or(source)
+        .replace("\r\n", "\n")
+        .replace('\r', "\n")
+        .replace(&['\x0b', '\x0c'][..], "");
+    let lines: Vec<&str> = source
+        .lines()
+        .map(|line| line.trim_end_matches(&[' ', '\t'][..]))
+        .collect();
+    let style = indent_style(&lines);
+    let mut fixed = String::new();
+    for line in lines {
+        let indentation = indentation(line);
+        fixed += &reindent(indentation, style);
+        fixed += &line[indentation.len()..];
+        fixed.push('\n');
+    }
+    fixed
+}
+
+/// The style of most of the indented lines of `lines`, spaces if there are as many lines indented
+/// with tabs as with spaces
+fn indent_style(lines: &[&str]) -> IndentStyle {
+    let (mut tabs, mut spaces) = (0, 0);
+    for line in lines {
+        if line.trim().is_empty() {
+            continue;
+        }
+        match line.chars().next() {
+            Some('\t') => tabs += 1,
+            Some(' ') => spaces += 1,
+            _ => (),
+        }
+    }
+    if tabs > spaces {
+        IndentStyle::Tabs
+    } else {
+        IndentStyle::Spaces
+    }
+}
+
+/// The spaces and tabs at the start of `line`
+fn indentation(line: &str) -> &str {
+    let content = line.trim_start_matches(&[' ', '\t'][..]);
+    &line[..line.len() - content.len()]
+}
+
+/// `indentation`, indenting to the same column in `style`
+fn reindent(indentation: &str, style: IndentStyle) -> String {
+    let width = indentation.chars().fold(0, |width, c| match c {
+        '\t' => width + TAB_WIDTH - width % TAB_WIDTH,
+        _ => width + 1,
+    });
+    match style {
+        IndentStyle::Spaces => " ".repeat(width),
+        IndentStyle::Tabs => "\t".repeat(width / TAB_WIDTH) + &" ".repeat(width % TAB_WIDTH),
+    }
+}
@@ -0,0 +1,625 @@
+// Copyright (c) The Move Contributors
+// SPDX-License-Identifier: Apache-2.0
+
+use super::reroot_path;
+use crate::{sandbox::utils::get_gas_status, NativeFunctionRecord};
+use anyhow::{anyhow, bail, Context, Result};
+use clap::*;
+use move_binary_format::{
+    access::ModuleAccess,
+    errors::{Location, VMError},
+    file_format::{CompiledModule, FunctionDefinitionIndex, SignatureToken},
+};
+use move_command_line_common::env::get_bytecode_version_from_env;
+use move_compiler::compiled_unit::CompiledUnit;
+use move_core_types::{
+    account_address::AccountAddress,
+    identifier::{IdentStr, Identifier},
+    language_storage::ModuleId,
+    value::MoveValue,
+    vm_status::StatusCode,
+};
+use move_package::{
+    compilation::{compiled_package::CompiledPackage, package_layout::CompiledPackageLayout},
+    BuildConfig,
+};
+use move_stdlib::natives::timestamp::NativeClockContext;
+use move_vm_runtime::{move_vm::MoveVM, native_extensions::NativeContextExtensions};
+use move_vm_test_utils::{gas_schedule::CostTable, InMemoryStorage};
+use rand::{rngs::StdRng, Rng, SeedableRng};
+use serde::{Deserialize, Serialize};
+use std::{
+    fs,
+    panic::{self, AssertUnwindSafe},
+    path::{Path, PathBuf},
+};
+
+const EXAMPLES: &str = "EXAMPLES:
+    Call the entry function `withdraw` of the module `Bank` with 1000 random inputs:
+        move package fuzz Bank::withdraw --iterations 1000
+
+    Fuzz every entry function of the package, reproducibly:
+        move package fuzz --all --seed 42
+
+    Call a function again with an input that made it fail:
+        move package fuzz --replay build/fuzz-failures/Bank-withdraw.json";
+
+/// How many simpler inputs are tried when shrinking a failing input
+const MAX_SHRINK_ATTEMPTS: usize = 1000;
+
+/// Call entry functions of the package with random arguments of the types of their parameters,
+/// each time in a fresh in-memory state holding the modules of the package and its dependencies.
+/// An abort with a code declared as a constant of the aborting module is expected; any other error,
+/// or a crash of the VM, is a failure. The first failing input of each function is shrunk to a
+/// simpler one that fails the same way, and written to `build/fuzz-failures` to be replayed.
+#[derive(Parser)]
+#[clap(name = "fuzz", after_help = EXAMPLES)]
+pub struct Fuzz {
+    /// The entry function to fuzz, as `<module>::<function>`
+    #[clap(name = "function", required_unless_present_any = &["all", "replay"])]
+    pub function: Option<String>,
+    /// Fuzz every entry function of the package. Functions with type parameters, or with
+    /// parameters of types other than signers, integers, booleans, addresses and vectors of them,
+    /// are skipped.
+    #[clap(long = "all", conflicts_with_all = &["function", "replay"])]
+    pub all: bool,
+    /// Call each function with this many random inputs
+    #[clap(long = "iterations", default_value = "100")]
+    pub iterations: u64,
+    /// Seed the random inputs are generated from. Random by default, and printed so that a run
+    /// can be reproduced.
+    #[clap(long = "seed")]
+    pub seed: Option<u64>,
+    /// Maximum length of the vectors passed as arguments
+    #[clap(long = "max-vector-length", default_value = "8")]
+    pub max_vector_length: usize,
+    /// Maximum number of gas units a call may consume. Running out of gas is not a failure.
+    #[clap(long = "gas-budget", default_value = "1000000")]
+    pub gas_budget: u64,
+    /// Also count aborts with a code declared by the aborting module as failures
+    #[clap(long = "fail-on-abort")]
+    pub fail_on_abort: bool,
+    /// Call a function again with an input written to `build/fuzz-failures`, and fail if it
+    /// still fails
+    #[clap(
+        long = "replay",
+        value_name = "FILE",
+        parse(from_os_str),
+        conflicts_with = "function"
+    )]
+    pub replay: Option<PathBuf>,
+}
+
+/// A failing input, as written to `build/fuzz-failures`
+#[derive(Debug, Serialize, Deserialize)]
+struct FailingInput {
+    /// The function called, as `<address>::<module>::<function>`
+    function: String,
+    /// The seed of the run that found the input
+    seed: u64,
+    /// How the function failed
+    failure: String,
+    /// The arguments, as displayed
+    values: Vec<String>,
+    /// The arguments, BCS-encoded in hex
+    args: Vec<String>,
+}
+
+/// An entry function of the package
+struct Target {
+    module_id: ModuleId,
+    name: Identifier,
+    is_generic: bool,
+    parameters: Vec<SignatureToken>,
+}
+
+impl Target {
+    fn display(&self) -> String {
+        format!(
+            "0x{}::{}::{}",
+            self.module_id.address().short_str_lossless(),
+            self.module_id.name(),
+            self.name
+        )
+    }
+}
+
+/// Calls the entry functions of a package, each time in a fresh state
+struct Fuzzer<'a> {
+    package: &'a CompiledPackage,
+    storage: InMemoryStorage,
+    natives: Vec<NativeFunctionRecord>,
+    cost_table: &'a CostTable,
+    gas_budget: u64,
+    fail_on_abort: bool,
+}
+
+impl Fuzz {
+    pub fn execute(
+        self,
+        path: Option<PathBuf>,
+        config: BuildConfig,
+        natives: Vec<NativeFunctionRecord>,
+        cost_table: &CostTable,
+    ) -> Result<()> {
+        let path = reroot_path(path)?;
+        let package = config
+            .clone()
+            .compile_package(&path, &mut std::io::stdout())?;
+        let fuzzer = Fuzzer::new(
+            &package,
+            natives,
+            cost_table,
+            self.gas_budget,
+            self.fail_on_abort,
+        )?;
+        if let Some(replay) = &self.replay {
+            return fuzzer.replay(replay);
+        }
+
+        let targets = match &self.function {
+            Some(function) => {
+                let target = fuzzer.find(function)?;
+                if let Err(reason) = fuzzable(&target) {
+                    bail!("{} cannot be fuzzed: {}", target.display(), reason)
+                }
+                vec![target]
+            }
+            None => fuzzer
+                .entry_functions()
+                .into_iter()
+                .filter(|target| match fuzzable(target) {
+                    Ok(()) => true,
+                    Err(reason) => {
+                        println!("[ SKIP    ] {}: {}", target.display(), reason);
+                        false
+                    }
+                })
+                .collect(),
+        };
+        let seed = self.seed.unwrap_or_else(rand::random);
+        println!("Fuzzing with seed {}", seed);
+        let failures_dir = fuzz_failures_dir(&path, &config);
+        let mut failed = 0;
+        for target in &targets {
+            // every function is fuzzed with the inputs it would get if fuzzed alone
+            let mut rng = StdRng::seed_from_u64(seed);
+            let failure = (1..=self.iterations).find_map(|call| {
+                let args: Vec<_> = target
+                    .parameters
+                    .iter()
+                    .map(|ty| random_value(&mut rng, ty, self.max_vector_length))
+                    .collect();
+                fuzzer
+                    .call(target, &args)
+                    .map(|failure| (call, args, failure))
+            });
+            let (call, args, failure) = match failure {
+                Some(failure) => failure,
+                None => {
+                    println!(
+                        "[ PASS    ] {} ({} calls)",
+                        target.display(),
+                        self.iterations
+                    );
+                    continue;
+                }
+            };
+            failed += 1;
+            let args = fuzzer.shrink(target, args, &failure);
+            let input = FailingInput {
+                function: target.display(),
+                seed,
+                failure: failure.clone(),
+                values: args.iter().map(|value| value.to_string()).collect(),
+                args: args
+                    .iter()
+                    .map(|value| hex::encode(serialize(value)))
+                    .collect(),
+            };
+            fs::create_dir_all(&failures_dir)?;
+            let input_path =
+                failures_dir.join(format!("{}-{}.json", target.module_id.name(), target.name));
+            fs::write(&input_path, serde_json::to_string_pretty(&input)?)
+                .with_context(|| format!("Failed to write {}", input_path.display()))?;
+            println!("[ FAIL    ] {}: {}", target.display(), failure);
+            println!("    input: ({})", input.values.join(", "));
+            println!(
+                "    found by call {}, shrunk and written to {}",
+                call,
+                input_path.display()
+            );
+        }
+        if failed > 0 {
+            bail!(
+                "Fuzzing found failing inputs for {} of {} function(s)",
+                failed,
+                targets.len()
+            )
+        }
+        Ok(())
+    }
+}
+
+impl<'a> Fuzzer<'a> {
+    fn new(
+        package: &'a CompiledPackage,
+        natives: Vec<NativeFunctionRecord>,
+        cost_table: &'a CostTable,
+        gas_budget: u64,
+        fail_on_abort: bool,
+    ) -> Result<Self> {
+        let bytecode_version = get_bytecode_version_from_env();
+        let mut storage = InMemoryStorage::new();
+        for unit in package.all_modules() {
+            if let CompiledUnit::Module(module) = &unit.unit {
+                let mut bytes = vec![];
+                module
+                    .module
+                    .serialize_for_version(bytecode_version, &mut bytes)?;
+                storage.publish_or_overwrite_module(module.module.self_id(), bytes);
+            }
+        }
+        Ok(Self {
+            package,
+            storage,
+            natives,
+            cost_table,
+            gas_budget,
+            fail_on_abort,
+        })
+    }
+
+    /// The entry functions of the root modules of the package
+    fn entry_functions(&self) -> Vec<Target> {
+        self.package
+            .root_modules()
+            .filter_map(|unit| match &unit.unit {
+                CompiledUnit::Module(module) => Some(&module.module),
+                CompiledUnit::Script(_) => None,
+            })
+            .flat_map(|module| {
+                module
+                    .function_defs()
+                    .iter()
+                    .filter(|function| function.is_entry)
+                    .map(move |function| {
+                        let handle = module.function_handle_at(function.function);
+                        Target {
+                            module_id: module.self_id(),
+                            name: module.identifier_at(handle.name).to_owned(),
+                            is_generic: !handle.type_parameters.is_empty(),
+                            parameters: module.signature_at(handle.parameters).0.clone(),
+                        }
+                    })
+                    .collect::<Vec<_>>()
+            })
+            .collect()
+    }
+
+    /// Find the entry function `function`, given as `<module>::<function>` or
+    /// `<address>::<module>::<function>`
+    fn find(&self, function: &str) -> Result<Target> {
+        self.entry_functions()
+            .into_iter()
+            .find(|target| {
+                function == target.display()
+                    || function == format!("{}::{}", target.module_id.name(), target.name)
+            })
+            .ok_or_else(|| {
+                anyhow!(
+                    "No entry function {} in the modules of package {}",
+                    function,
+                    self.package.compiled_package_info.package_name
+                )
+            })
+    }
+
+    /// Call `target` with `args`, and describe how it failed, if it did
+    fn call(&self, target: &Target, args: &[MoveValue]) -> Option<String> {
+        self.call_serialized(target, args.iter().map(serialize).collect())
+    }
+
+    fn call_serialized(&self, target: &Target, args: Vec<Vec<u8>>) -> Option<String> {
+        let result = panic::catch_unwind(AssertUnwindSafe(|| -> Result<_> {
+            let vm = MoveVM::new(self.natives.iter().cloned())?;
+            let mut gas_status = get_gas_status(self.cost_table, Some(self.gas_budget))?;
+            let mut extensions = NativeContextExtensions::default();
+            extensions.add(NativeClockContext {
+                now_microseconds: 0,
+                block_height: 0,
+            });
+            // the session is dropped without saving its effects
+            let mut session = vm.new_session_with_extensions(&self.storage, extensions);
+            Ok(session
+                .execute_entry_function(
+                    &target.module_id,
+                    &target.name,
+                    vec![],
+                    args,
+                    &mut gas_status,
+                )
+                .err())
+        }));
+        match result {
+            Ok(Ok(None)) => None,
+            Ok(Ok(Some(err))) => self.explain(err),
+            Ok(Err(err)) => Some(format!("the VM could not be set up: {:#}", err)),
+            Err(panic) => {
+                let message = panic
+                    .downcast_ref::<String>()
+                    .map(String::as_str)
+                    .or_else(|| panic.downcast_ref::<&str>().copied())
+                    .unwrap_or("unknown cause");
+                Some(format!("the VM crashed: {}", message))
+            }
+        }
+    }
+
+    /// Describe `err`, unless it is an expected outcome of a call with random arguments: an abort
+    /// with a declared code, or running out of gas
+    fn explain(&self, err: VMError) -> Option<String> {
+        let location = match err.location() {
+            Location::Module(module_id) => Some(module_id),
+            Location::Undefined | Location::Script => None,
+        };
+        match (err.major_status(), err.sub_status()) {
+            (StatusCode::OUT_OF_GAS, _) => return None,
+            (StatusCode::ABORTED, Some(code)) => {
+                let declared =
+                    location.map_or(false, |module_id| self.declared_abort_code(module_id, code));
+                if declared && !self.fail_on_abort {
+                    return None;
+                }
+            }
+            _ => (),
+        }
+        let mut explanation = match (err.major_status(), err.sub_status()) {
+            (StatusCode::ABORTED, Some(code)) => format!("abort with code {}", code),
+            (status, _) => format!("{:?}", status),
+        };
+        if let Some(module_id) = location {
+            explanation += &format!(
+                " in 0x{}::{}",
+                module_id.address().short_str_lossless(),
+                module_id.name()
+            );
+            if let Some((function, offset)) = err.offsets().first() {
+                if let Some(name) = self.function_name(module_id, *function) {
+                    explanation += &format!("::{} at code offset {}", name, offset);
+                }
+            }
+        }
+        Some(explanation)
+    }
+
+    /// Whether `code`, or its reason when it is built by `std::error`, is the value of a `u64`
+    /// constant of the module `module_id`
+    fn declared_abort_code(&self, module_id: &ModuleId, code: u64) -> bool {
+        let module = match self.module(module_id) {
+            Some(module) => module,
+            None => return false,
+        };
+        module.constant_pool().iter().any(|constant| {
+            constant.type_ == SignatureToken::U64
+                && bcs::from_bytes::<u64>(&constant.data)
+                    .map_or(false, |value| value == code || value == code & 0xffff)
+        })
+    }
+
+    fn function_name(
+        &self,
+        module_id: &ModuleId,
+        function: FunctionDefinitionIndex,
+    ) -> Option<&IdentStr> {
+        let module = self.module(module_id)?;
+        let definition = module.function_defs().get(function.0 as usize)?;
+        Some(module.identifier_at(module.function_handle_at(definition.function).name))
+    }
+
+    fn module(&self, module_id: &ModuleId) -> Option<&CompiledModule> {
+        self.package
+            .all_modules()
+            .find_map(|unit| match &unit.unit {
+                CompiledUnit::Module(module) if &module.module.self_id() == module_id => {
+                    Some(&module.module)
+                }
+                _ => None,
+            })
+    }
+
+    /// Shrink `args`, with which `target` fails with `failure`, to simpler arguments with which it
+    /// fails the same way
+    fn shrink(&self, target: &Target, mut args: Vec<MoveValue>, failure: &str) -> Vec<MoveValue> {
+        let mut attempts = 0;
+        'shrink: loop {
+            for i in 0..args.len() {
+                for candidate in shrink_candidates(&args[i]) {
+                    if attempts == MAX_SHRINK_ATTEMPTS {
+                        break 'shrink;
+                    }
+                    attempts += 1;
+                    let mut shrunk = args.clone();
+                    shrunk[i] = candidate;
+                    if self.call(target, &shrunk).as_deref() == Some(failure) {
+                        args = shrunk;
+                        continue 'shrink;
+                    }
+                }
+            }
+            break;
+        }
+        args
+    }
+
+    /// Call the function of the failing input in `input_path` with its arguments again
+    fn replay(&self, input_path: &Path) -> Result<()> {
+        let input: FailingInput = serde_json::from_str(
+            &fs::read_to_string(input_path)
+                .with_context(|| format!("Failed to read {}", input_path.display()))?,
+        )
+        .with_context(|| format!("Invalid failing input in {}", input_path.display()))?;
+        let target = self.find(&input.function)?;
+        let args = input
+            .args
+            .iter()
+            .map(hex::decode)
+            .collect::<Result<_, _>>()
+            .with_context(|| format!("Invalid arguments in {}", input_path.display()))?;
+        println!("Replaying {}({})", input.function, input.values.join(", "));
+        match self.call_serialized(&target, args) {
+            Some(failure) => bail!("{} fails: {}", input.function, failure),
+            None => {
+                println!("{} no longer fails with this input", input.function);
+                Ok(())
+            }
+        }
+    }
+}
+
+/// The directory `move package fuzz` writes failing inputs to
+pub fn fuzz_failures_dir(package_path: &Path, config: &BuildConfig) -> PathBuf {
+    config
+        .install_dir
+        .clone()
+        .unwrap_or_else(|| package_path.to_path_buf())
+        .join(CompiledPackageLayout::Root.path())
+        .join(CompiledPackageLayout::FuzzFailures.path())
+}
+
+/// Check that random arguments can be generated for `target`
+fn fuzzable(target: &Target) -> Result<(), String> {
+    fn generated(ty: &SignatureToken) -> bool {
+        match ty {
+            SignatureToken::Bool
+            | SignatureToken::U8
+            | SignatureToken::U64
+            | SignatureToken::U128
+            | SignatureToken::Address
+            | SignatureToken::Signer => true,
+            SignatureToken::Reference(inner) => **inner == SignatureToken::Signer,
+            SignatureToken::Vector(inner) => **inner != SignatureToken::Signer && generated(inner),
+            _ => false,
+        }
+    }
+    if target.is_generic {
+        Err("it has type parameters".to_string())
+    } else if target.parameters.iter().all(generated) {
+        Ok(())
+    } else {
+        Err(
+            "only parameters of type signer, integer, bool, address and vectors of them are \
+             supported"
+                .to_string(),
+        )
+    }
+}
+
+/// A random value of type `ty`, which must be `fuzzable`
+fn random_value(rng: &mut StdRng, ty: &SignatureToken, max_vector_length: usize) -> MoveValue {
+    match ty {
+        SignatureToken::Bool => MoveValue::Bool(rng.gen()),
+        SignatureToken::U8 => MoveValue::U8(random_integer(rng, u8::MAX.into()) as u8),
+        SignatureToken::U64 => MoveValue::U64(random_integer(rng, u64::MAX.into()) as u64),
+        SignatureToken::U128 => MoveValue::U128(random_integer(rng, u128::MAX)),
+        SignatureToken::Address => MoveValue::Address(random_address(rng)),
+        SignatureToken::Signer | SignatureToken::Reference(_) => {
+            MoveValue::Signer(random_address(rng))
+        }
+        SignatureToken::Vector(inner) => {
+            let length = rng.gen_range(0..=max_vector_length);
+            MoveValue::Vector(
+                (0..length)
+                    .map(|_| random_value(rng, inner, max_vector_length))
+                    .collect(),
+            )
+        }
+        _ => unreachable!("random values are only generated for fuzzable functions"),
+    }
+}
+
+/// A random integer up to `max`, which is one of the boundary values half of the time
+fn random_integer(rng: &mut StdRng, max: u128) -> u128 {
+    if rng.gen_bool(0.5) {
+        let boundaries = [0, 1, max / 2, max / 2 + 1, max - 1, max];
+        boundaries[rng.gen_range(0..boundaries.len())]
+    } else {
+        rng.gen_range(0..=max)
+    }
+}
+
+/// A random address, which is one of the special addresses 0x0 and 0x1 some of the time
+fn random_address(rng: &mut StdRng) -> AccountAddress {
+    match rng.gen_range(0..4) {
+        0 => AccountAddress::ZERO,
+        1 => AccountAddress::ONE,
+        _ => {
+            let mut bytes = [0; AccountAddress::LENGTH];
+            rng.fill(&mut bytes[..]);
+            AccountAddress::new(bytes)
+        }
+    }
+}
+
+/// Values simpler than `value`, simplest first
+fn shrink_candidates(value: &MoveValue) -> Vec<MoveValue> {
+    match value {
+        MoveValue::Bool(true) => vec![MoveValue::Bool(false)],
+        MoveValue::U8(x) => shrink_integer((*x).into())
+            .into_iter()
+            .map(|x| MoveValue::U8(x as u8))
+            .collect(),
+        MoveValue::U64(x) => shrink_integer((*x).into())
+            .into_iter()
+            .map(|x| MoveValue::U64(x as u64))
+            .collect(),
+        MoveValue::U128(x) => shrink_integer(*x)
+            .into_iter()
+            .map(MoveValue::U128)
+            .collect(),
+        MoveValue::Address(address) if *address != AccountAddress::ZERO => {
+            vec![MoveValue::Address(AccountAddress::ZERO)]
+        }
+        MoveValue::Signer(address) if *address != AccountAddress::ZERO => {
+            vec![MoveValue::Signer(AccountAddress::ZERO)]
+        }
+        MoveValue::Vector(elements) if !elements.is_empty() => {
+            let mut candidates = vec![MoveValue::Vector(vec![])];
+            if elements.len() > 1 {
+                let half = elements.len() / 2;
+                candidates.push(MoveValue::Vector(elements[..half].to_vec()));
+                candidates.push(MoveValue::Vector(elements[half..].to_vec()));
+            }
+            for i in 0..elements.len() {
+                let mut shorter = elements.clone();
+                shorter.remove(i);
+                candidates.push(MoveValue::Vector(shorter));
+            }
+            for (i, element) in elements.iter().enumerate() {
+                for candidate in shrink_candidates(element) {
+                    let mut simpler = elements.clone();
+                    simpler[i] = candidate;
+                    candidates.push(MoveValue::Vector(simpler));
+                }
+            }
+            candidates
+        }
+        _ => vec![],
+    }
+}
+
+/// Integers smaller than `x`, smallest first
+fn shrink_integer(x: u128) -> Vec<u128> {
+    if x == 0 {
+        return vec![];
+    }
+    let mut candidates = vec![0, x / 2, x - 1];
+    candidates.dedup();
+    candidates
+}
+
+fn serialize(value: &MoveValue) -> Vec<u8> {
+    value
+        .simple_serialize()
+        .expect("generated arguments must serialize")
+}
@@ -0,0 +1,236 @@
+// Copyright (c) The Move Contributors
+// SPDX-License-Identifier: Apache-2.0
+
+use super::{reroot_path, test::build_test_plan};
+use crate::NativeFunctionRecord;
+use anyhow::{bail, Context, Result};
+use clap::*;
+use move_package::{compilation::package_layout::CompiledPackageLayout, BuildConfig};
+use move_unit_test::{
+    gas_profile::{CallCost, GasProfile},
+    UnitTestingConfig,
+};
+use move_vm_test_utils::gas_schedule::CostTable;
+use serde::{Deserialize, Serialize};
+use std::{
+    collections::BTreeMap,
+    fs,
+    path::{Path, PathBuf},
+};
+
+const EXAMPLES: &str = "EXAMPLES:
+    Measure the tests annotated #[gas_benchmark] and write build/gas-report.json:
+        move package gas-report
+
+    Measure every test of the package:
+        move package gas-report --all
+
+    Fail if a cost grew by more than 5% over the report of the main branch:
+        move package gas-report --baseline main-gas-report.json --max-regression 5";
+
+/// File name of the report, in the build directory
+pub const GAS_REPORT_FILE: &str = "gas-report.json";
+
+/// Measure the instructions and the gas of the unit tests annotated `#[test, gas_benchmark]`, in
+/// total and for each call they make, e.g. to the entry points of the package. Costs are metered
+/// with the cost table of the sandbox, in internal gas units, and written to
+/// `build/gas-report.json` to be compared with by later runs.
+#[derive(Parser)]
+#[clap(name = "gas-report", after_help = EXAMPLES)]
+pub struct GasReport {
+    /// Measure every test of the package instead of the `#[gas_benchmark]` ones
+    #[clap(long = "all")]
+    pub all: bool,
+    /// A report written by an earlier run, e.g. on the main branch, to print the changes in cost
+    /// since
+    #[clap(long = "baseline", value_name = "FILE", parse(from_os_str))]
+    pub baseline: Option<PathBuf>,
+    /// Fail if the gas of a benchmark, or of a call it makes, grew by more than this percentage
+    /// over the baseline
+    #[clap(long = "max-regression", value_name = "PERCENT", requires = "baseline")]
+    pub max_regression: Option<f64>,
+    /// The gas each benchmark may use before it fails as out of gas, in the gas units of
+    /// `sandbox run --gas-budget`
+    #[clap(long = "gas-budget", short = 'g', default_value = "100000000")]
+    pub gas_budget: u64,
+}
+
+/// The costs of the benchmarks, as written to `build/gas-report.json`
+#[derive(Debug, Default, Serialize, Deserialize)]
+pub struct GasReportFile {
+    /// The cost of each benchmark, by test as `<address>::<module>::<test>`
+    pub benchmarks: BTreeMap<String, GasProfile>,
+}
+
+impl GasReport {
+    pub fn execute(
+        self,
+        path: Option<PathBuf>,
+        mut config: BuildConfig,
+        natives: Vec<NativeFunctionRecord>,
+        cost_table: &CostTable,
+    ) -> Result<()> {
+        let path = reroot_path(path)?;
+        let report_path = config
+            .install_dir
+            .clone()
+            .unwrap_or_else(|| path.clone())
+            .join(CompiledPackageLayout::Root.path())
+            .join(GAS_REPORT_FILE);
+        let baseline = match &self.baseline {
+            Some(baseline) => Some(read_report(baseline)?),
+            None => None,
+        };
+
+        config.test_mode = true;
+        config.dev_mode = true;
+        let mut unit_test_config = UnitTestingConfig {
+            gas_profile_cost_table: Some(cost_table.clone()),
+            ..UnitTestingConfig::default_with_bound(Some(self.gas_budget))
+        };
+        let mut stdout = std::io::stdout();
+        let mut test_plan =
+            build_test_plan(&path, config, &mut unit_test_config, &natives, &mut stdout)?;
+        if !self.all {
+            for module_tests in test_plan.module_tests.values_mut() {
+                module_tests.tests.retain(|_, test| test.gas_benchmark);
+            }
+        }
+        test_plan
+            .module_tests
+            .retain(|_, module_tests| !module_tests.tests.is_empty());
+        if test_plan.module_tests.is_empty() {
+            bail!(
+                "The package has no gas benchmarks. Annotate the tests to measure with \
+                 #[test, gas_benchmark], or pass --all to measure every test"
+            )
+        }
+
+        let (_, results) =
+            unit_test_config.run_and_collect_unit_tests(test_plan, Some(natives), stdout)?;
+        let results = results.expect("tests are run, not listed");
+        let failed: usize = results
+            .failed_tests()
+            .values()
+            .map(|tests| tests.len())
+            .sum();
+        if failed > 0 {
+            bail!(
+                "{} gas benchmark(s) failed, so no report was written",
+                failed
+            )
+        }
+        let mut report = GasReportFile::default();
+        for (module_id, tests) in results.passed_tests() {
+            for test in tests {
+                if let Some(profile) = &test.gas_profile {
+                    report.benchmarks.insert(
+                        format!(
+                            "{}::{}",
+                            module_id.short_str_lossless(),
+                            test.function_ident
+                        ),
+                        profile.clone(),
+                    );
+                }
+            }
+        }
+
+        println!();
+        let regressions = print_report(&report, baseline.as_ref(), self.max_regression);
+        fs::create_dir_all(report_path.parent().unwrap())?;
+        fs::write(&report_path, serde_json::to_string_pretty(&report)?)
+            .with_context(|| format!("Unable to write '{}'", report_path.display()))?;
+        println!("\nWrote the gas report to '{}'", report_path.display());
+
+        if let Some(max_regression) = self.max_regression {
+            if !regressions.is_empty() {
+                bail!(
+                    "gas grew by more than {}% over the baseline for {}",
+                    max_regression,
+                    regressions.join(", ")
+                )
+            }
+        }
+        Ok(())
+    }
+}
+
+fn read_report(path: &Path) -> Result<GasReportFile> {
+    let content = fs::read_to_string(path)
+        .with_context(|| format!("Unable to read the gas report '{}'", path.display()))?;
+    serde_json::from_str(&content)
+        .with_context(|| format!("'{}' is not a gas report", path.display()))
+}
+
+/// Print a table of the costs of `report`, each benchmark followed by the calls it makes, with the
+/// changes in gas since `baseline`. Returns the rows whose gas grew by more than `max_regression`
+/// percent, each with its growth.
+fn print_report(
+    report: &GasReportFile,
+    baseline: Option<&GasReportFile>,
+    max_regression: Option<f64>,
+) -> Vec<String> {
+    let mut regressions = vec![];
+    let mut print_row = |name: &str, label: &str, calls: Option<u64>, cost: (u64, u64), base| {
+        let (instructions, gas) = cost;
+        let change = match (baseline, base) {
+            (None, _) => String::new(),
+            (Some(_), None) => "new".to_string(),
+            (Some(_), Some(0)) => "-".to_string(),
+            (Some(_), Some(base)) => {
+                let percent = (gas as f64 - base as f64) * 100.0 / base as f64;
+                if max_regression.map_or(false, |max| percent > max) {
+                    regressions.push(format!("{} ({:+.1}%)", label, percent));
+                }
+                format!("{:+.1}%", percent)
+            }
+        };
+        println!(
+            "{:<48} {:>6} {:>12} {:>12} {:>9}",
+            name,
+            calls.map_or(String::new(), |calls| calls.to_string()),
+            instructions,
+            gas,
+            change
+        );
+    };
+
+    println!(
+        "{:<48} {:>6} {:>12} {:>12} {:>9}",
+        "Benchmark / call",
+        "Calls",
+        "Instructions",
+        "Gas",
+        if baseline.is_some() { "Change" } else { "" }
+    );
+    for (benchmark, profile) in &report.benchmarks {
+        let base = baseline.and_then(|baseline| baseline.benchmarks.get(benchmark));
+        print_row(
+            benchmark,
+            benchmark,
+            None,
+            (profile.instructions, profile.gas),
+            base.map(|base| base.gas),
+        );
+        for (
+            function,
+            CallCost {
+                calls,
+                instructions,
+                gas,
+            },
+        ) in &profile.calls
+        {
+            print_row(
+                &format!("  {}", function),
+                &format!("{} in {}", function, benchmark),
+                Some(*calls),
+                (*instructions, *gas),
+                base.and_then(|base| base.calls.get(function))
+                    .map(|call| call.gas),
+            );
+        }
+    }
+    regressions
+}
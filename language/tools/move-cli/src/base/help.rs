@@ -0,0 +1,122 @@
+// Copyright (c) The Move Contributors
+// SPDX-License-Identifier: Apache-2.0
+
+use crate::MoveCLI;
+use anyhow::{bail, Result};
+use clap::{CommandFactory, ErrorKind, Parser};
+use std::{
+    io::Write,
+    process::{Command, Stdio},
+};
+
+/// The pager guides are printed through when `PAGER` is not set
+const DEFAULT_PAGER: &str = "less";
+
+/// A guide printed by `move help <topic>`
+pub struct HelpTopic {
+    pub name: &'static str,
+    /// One line describing the guide, shown by `move help topics`
+    pub summary: &'static str,
+    pub text: &'static str,
+}
+
+/// The guides `move help` can print
+pub const HELP_TOPICS: &[HelpTopic] = &[
+    HelpTopic {
+        name: "addresses",
+        summary: "Named addresses, and how packages assign them",
+        text: include_str!("help/addresses.txt"),
+    },
+    HelpTopic {
+        name: "dependencies",
+        summary: "Declaring, fetching and pinning the dependencies of a package",
+        text: include_str!("help/dependencies.txt"),
+    },
+    HelpTopic {
+        name: "sandbox-basics",
+        summary: "Publishing packages and running scripts in the local sandbox",
+        text: include_str!("help/sandbox-basics.txt"),
+    },
+];
+
+/// Print a guide on a topic, or the help of a command. `move help topics` lists the guides.
+#[derive(Parser)]
+#[clap(name = "help")]
+pub struct Help {
+    /// The guide to print, `topics` to list the guides, or a command (e.g. `sandbox run`) to print
+    /// the help of
+    #[clap(name = "topic")]
+    pub topic: Vec<String>,
+    /// Print the guide directly, instead of through the pager set by `PAGER` when the output is a
+    /// terminal
+    #[clap(long = "no-pager")]
+    pub no_pager: bool,
+}
+
+impl Help {
+    pub fn execute(self) -> Result<()> {
+        match self.topic.as_slice() {
+            [] => {
+                MoveCLI::command().print_long_help()?;
+                println!("\nRun `move help topics` to list the guides.");
+                Ok(())
+            }
+            [topic] if topic == "topics" => {
+                println!("Help topics:");
+                for topic in HELP_TOPICS {
+                    println!("    {:<20} {}", topic.name, topic.summary);
+                }
+                println!("\nRun `move help <topic>` to read a guide.");
+                Ok(())
+            }
+            [name] if HELP_TOPICS.iter().any(|topic| topic.name == name) => {
+                let topic = HELP_TOPICS.iter().find(|topic| topic.name == name).unwrap();
+                page(topic.text, self.no_pager)
+            }
+            command => {
+                // let clap render the help of the command, as `move <command> --help` does
+                let args = std::iter::once("move")
+                    .chain(command.iter().map(String::as_str))
+                    .chain(std::iter::once("--help"));
+                match MoveCLI::command().try_get_matches_from(args) {
+                    Err(err) if err.kind() == ErrorKind::DisplayHelp => {
+                        err.print()?;
+                        Ok(())
+                    }
+                    _ => bail!(
+                        "No help topic or command named '{}'. Run `move help topics` to list the \
+                         guides, or `move --help` to list the commands",
+                        command.join(" ")
+                    ),
+                }
+            }
+        }
+    }
+}
+
+/// Print `text` through the pager set by `PAGER` (`less` by default) if stdout is a terminal, or
+/// directly otherwise. An empty `PAGER`, or one that cannot be started, also prints directly.
+fn page(text: &str, no_pager: bool) -> Result<()> {
+    if !no_pager && atty::is(atty::Stream::Stdout) {
+        let pager = std::env::var("PAGER").unwrap_or_else(|_| DEFAULT_PAGER.to_string());
+        let mut words = pager.split_whitespace();
+        if let Some(program) = words.next() {
+            if let Ok(mut child) = Command::new(program)
+                .args(words)
+                .stdin(Stdio::piped())
+                .spawn()
+            {
+                // the pager may be quit before it read the whole guide
+                let _ = child
+                    .stdin
+                    .take()
+                    .expect("stdin is piped")
+                    .write_all(text.as_bytes());
+                child.wait()?;
+                return Ok(());
+            }
+        }
+    }
+    print!("{}", text);
+    Ok(())
+}
@@ -2,20 +2,52 @@
 // SPDX-License-Identifier: Apache-2.0
 
 use super::reroot_path;
+use crate::sandbox::cli::OutputFormat;
 use clap::*;
-use move_package::BuildConfig;
-use std::path::PathBuf;
+use move_package::{resolution::lockfile::DependencySource, BuildConfig};
+use serde::Serialize;
+use std::{collections::BTreeMap, path::PathBuf};
 
-/// Print address information.
+/// Print address information. With `--format json`, the named addresses of the package are printed
+/// along with the source and the digest of each dependency compiled into it.
 #[derive(Parser)]
 #[clap(name = "info")]
-pub struct Info;
+pub struct Info {
+    /// Output format of the information
+    #[clap(long = "format", arg_enum, ignore_case = true, default_value = "text")]
+    pub format: OutputFormat,
+}
+
+/// The information printed by `move package info --format json`
+#[derive(Debug, Serialize)]
+pub struct PackageInfo {
+    pub package: String,
+    /// The named addresses in scope for the package, along with their values
+    pub addresses: BTreeMap<String, String>,
+    /// The dependencies compiled into the package, sorted by name
+    pub dependencies: Vec<DependencySource>,
+}
 
 impl Info {
     pub fn execute(self, path: Option<PathBuf>, config: BuildConfig) -> anyhow::Result<()> {
         let rerooted_path = reroot_path(path)?;
-        config
-            .resolution_graph_for_package(&rerooted_path)?
-            .print_info()
+        let graph = config.resolution_graph_for_package(&rerooted_path)?;
+        match self.format {
+            OutputFormat::Text => graph.print_info(),
+            OutputFormat::Json => {
+                let info = PackageInfo {
+                    package: graph.root_package.package.name.to_string(),
+                    addresses: graph
+                        .extract_named_address_mapping()
+                        .map(|(name, addr)| {
+                            (name.to_string(), format!("0x{}", addr.short_str_lossless()))
+                        })
+                        .collect(),
+                    dependencies: DependencySource::for_root_package(&graph),
+                };
+                println!("{}", serde_json::to_string_pretty(&info)?);
+                Ok(())
+            }
+        }
     }
 }
@@ -0,0 +1,327 @@
+// Copyright (c) The Move Contributors
+// SPDX-License-Identifier: Apache-2.0
+
+use super::package_root;
+use anyhow::{bail, Result};
+use clap::*;
+use move_package::{
+    resolution::{
+        lockfile::{DependencySource, LockedDependency},
+        resolution_graph::ResolvedGraph,
+    },
+    source_package::{
+        layout::SourcePackageLayout, parsed_manifest::format_version, workspace::Workspace,
+    },
+    BuildConfig,
+};
+use serde::{Deserialize, Serialize};
+use std::{
+    collections::BTreeMap,
+    path::{Path, PathBuf},
+};
+
+/// The version of the schema of `move package metadata`. Any change to the schema, including
+/// added fields, requires a new version.
+pub const METADATA_FORMAT_VERSION: u64 = 1;
+
+const EXAMPLES: &str = "EXAMPLES:
+    Print the metadata of the workspace or package containing the current directory:
+        move package metadata --format-version 1
+
+    Print the metadata of another package, failing if a dependency must be downloaded or the
+    lockfile updated:
+        move package metadata --format-version 1 --manifest-path ../coins/Move.toml --offline --locked";
+
+/// Print the workspace members, their resolved dependency graphs, and the manifest, sources,
+/// digest and named addresses of every package, as a single JSON document for external build
+/// systems. Packages are resolved but not compiled.
+#[derive(Parser)]
+#[clap(name = "metadata", after_help = EXAMPLES)]
+pub struct Metadata {
+    /// The version of the schema to print. Only version 1 is supported.
+    #[clap(long = "format-version")]
+    pub format_version: u64,
+}
+
+/// The document printed by `move package metadata --format-version 1`
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct MetadataV1 {
+    pub format_version: u64,
+    /// The directory of the manifest declaring the workspace, or `None` outside of a workspace
+    pub workspace_root: Option<PathBuf>,
+    /// The names of the workspace members in the order they are declared, or the name of the
+    /// package outside of a workspace
+    pub members: Vec<String>,
+    /// Every package in the dependency graph of a member, sorted by name
+    pub packages: Vec<PackageMetadata>,
+    /// The dependency graph of each member, in the order of `members`
+    pub resolve: Vec<ResolveMetadata>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct PackageMetadata {
+    pub name: String,
+    pub version: String,
+    /// The root directory of the package
+    pub root: PathBuf,
+    pub manifest_path: PathBuf,
+    /// Where the package is fetched from, or `None` for members and the package the command is
+    /// run on
+    pub source: Option<PackageSource>,
+    /// The digest of the sources and manifest of the package, as recorded in the lockfile
+    pub digest: String,
+    /// The dependencies declared by the manifest
+    pub dependencies: Vec<DependencyMetadata>,
+    /// The Move files compiled with the package, relative to `root`, sorted
+    pub files: Vec<String>,
+}
+
+/// Where a dependency is fetched from
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "kind", rename_all = "lowercase")]
+pub enum PackageSource {
+    /// A directory, relative to the package declaring the dependency
+    Local { path: String },
+    Git {
+        url: String,
+        rev: String,
+        subdir: Option<String>,
+    },
+    /// A package published on a node, resolved by the package hooks
+    Custom { node: String, address: String },
+    /// A `.tar.gz` archive downloaded over HTTP(S)
+    Archive {
+        url: String,
+        sha256: String,
+        subdir: Option<String>,
+    },
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct DependencyMetadata {
+    pub name: String,
+    pub kind: DependencyKind,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum DependencyKind {
+    Normal,
+    /// Declared in `[dev-dependencies]`, only resolved in dev and test mode
+    Dev,
+}
+
+/// The dependency graph of a workspace member
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ResolveMetadata {
+    pub root: String,
+    /// The packages of the graph, sorted by name
+    pub nodes: Vec<ResolveNode>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ResolveNode {
+    pub name: String,
+    /// The packages this package depends on directly, sorted by name
+    pub dependencies: Vec<String>,
+    /// The named addresses in scope for the package and their values, as resolved for the
+    /// member, e.g. `0x1`
+    pub addresses: BTreeMap<String, String>,
+}
+
+impl Metadata {
+    pub fn execute(self, path: Option<PathBuf>, config: BuildConfig) -> Result<()> {
+        if self.format_version != METADATA_FORMAT_VERSION {
+            bail!(
+                "Unsupported metadata format version {} -- expected {}",
+                self.format_version,
+                METADATA_FORMAT_VERSION
+            )
+        }
+        let metadata = metadata(path.as_deref(), config)?;
+        println!("{}", serde_json::to_string_pretty(&metadata)?);
+        Ok(())
+    }
+}
+
+/// The metadata of the workspace containing `path`, or of the package at `path` if it is not in a
+/// workspace. The lockfile of each member is checked or updated as `move build` does.
+pub fn metadata(path: Option<&Path>, config: BuildConfig) -> Result<MetadataV1> {
+    let root = package_root(path)?;
+    let workspace = Workspace::find_containing(&root)?;
+    let member_roots = match &workspace {
+        Some(workspace) => workspace.members.clone(),
+        None => vec![root],
+    };
+
+    let mut members = vec![];
+    let mut packages: BTreeMap<String, PackageMetadata> = BTreeMap::new();
+    let mut resolve = vec![];
+    for member in member_roots {
+        let mut config = config.clone();
+        config.lock_file = Some(member.join(SourcePackageLayout::Lockfile.path()));
+        let graph = config.resolution_graph_for_package(&member)?;
+        let sources: BTreeMap<String, LockedDependency> =
+            DependencySource::for_root_package(&graph)
+                .into_iter()
+                .map(|source| (source.locked.name.clone(), source.locked))
+                .collect();
+        for (name, package) in &graph.package_table {
+            if packages.contains_key(name.as_str()) {
+                continue;
+            }
+            let root = package
+                .package_path
+                .canonicalize()
+                .unwrap_or_else(|_| package.package_path.clone());
+            let manifest = &package.source_package;
+            let mut files = package
+                .get_sources(&graph.build_options)?
+                .into_iter()
+                .map(|file| {
+                    let file = Path::new(file.as_str());
+                    file.strip_prefix(&package.package_path)
+                        .unwrap_or(file)
+                        .to_string_lossy()
+                        .replace('\\', "/")
+                })
+                .collect::<Vec<_>>();
+            files.sort();
+            let dependencies = manifest
+                .dependencies
+                .keys()
+                .map(|dep| (dep, DependencyKind::Normal))
+                .chain(
+                    manifest
+                        .dev_dependencies
+                        .keys()
+                        .map(|dep| (dep, DependencyKind::Dev)),
+                )
+                .map(|(dep, kind)| DependencyMetadata {
+                    name: dep.to_string(),
+                    kind,
+                })
+                .collect();
+            packages.insert(
+                name.to_string(),
+                PackageMetadata {
+                    name: name.to_string(),
+                    version: format_version(&manifest.package.version),
+                    manifest_path: root.join(SourcePackageLayout::Manifest.path()),
+                    root,
+                    source: sources.get(name.as_str()).map(PackageSource::from),
+                    digest: package.source_digest.to_string(),
+                    dependencies,
+                    files,
+                },
+            );
+        }
+        members.push(graph.root_package.package.name.to_string());
+        resolve.push(resolve_metadata(&graph));
+    }
+    // a member may also be a dependency of an earlier member
+    for member in &members {
+        if let Some(package) = packages.get_mut(member) {
+            package.source = None;
+        }
+    }
+
+    Ok(MetadataV1 {
+        format_version: METADATA_FORMAT_VERSION,
+        workspace_root: workspace.map(|workspace| workspace.root),
+        members,
+        packages: packages.into_values().collect(),
+        resolve,
+    })
+}
+
+fn resolve_metadata(graph: &ResolvedGraph) -> ResolveMetadata {
+    let nodes = graph
+        .package_table
+        .iter()
+        .map(|(name, package)| ResolveNode {
+            name: name.to_string(),
+            dependencies: package
+                .immediate_dependencies(graph)
+                .into_iter()
+                .map(|dep| dep.to_string())
+                .collect(),
+            addresses: package
+                .resolution_table
+                .iter()
+                .map(|(name, addr)| (name.to_string(), format!("0x{}", addr.short_str_lossless())))
+                .collect(),
+        })
+        .collect();
+    ResolveMetadata {
+        root: graph.root_package.package.name.to_string(),
+        nodes,
+    }
+}
+
+impl From<&LockedDependency> for PackageSource {
+    fn from(locked: &LockedDependency) -> Self {
+        match (&locked.git, &locked.node, &locked.url) {
+            (Some(url), _, _) => Self::Git {
+                url: url.clone(),
+                rev: locked.rev.clone().unwrap_or_default(),
+                subdir: locked.subdir.clone(),
+            },
+            (None, Some(node), _) => Self::Custom {
+                node: node.clone(),
+                address: locked.address.clone().unwrap_or_default(),
+            },
+            (None, None, Some(url)) => Self::Archive {
+                url: url.clone(),
+                sha256: locked.sha256.clone().unwrap_or_default(),
+                subdir: locked.subdir.clone(),
+            },
+            (None, None, None) => Self::Local {
+                path: locked.local.clone().unwrap_or_default(),
+            },
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn metadata_round_trips_through_json() {
+        let metadata = MetadataV1 {
+            format_version: METADATA_FORMAT_VERSION,
+            workspace_root: Some(PathBuf::from("/ws")),
+            members: vec!["App".to_string()],
+            packages: vec![PackageMetadata {
+                name: "App".to_string(),
+                version: "0.1.0".to_string(),
+                root: PathBuf::from("/ws/app"),
+                manifest_path: PathBuf::from("/ws/app/Move.toml"),
+                source: Some(PackageSource::Git {
+                    url: "https://example.com/app.git".to_string(),
+                    rev: "main".to_string(),
+                    subdir: None,
+                }),
+                digest: "ABCD".to_string(),
+                dependencies: vec![DependencyMetadata {
+                    name: "Core".to_string(),
+                    kind: DependencyKind::Dev,
+                }],
+                files: vec!["sources/App.move".to_string()],
+            }],
+            resolve: vec![ResolveMetadata {
+                root: "App".to_string(),
+                nodes: vec![ResolveNode {
+                    name: "App".to_string(),
+                    dependencies: vec!["Core".to_string()],
+                    addresses: BTreeMap::from([("app".to_string(), "0x2".to_string())]),
+                }],
+            }],
+        };
+        let json = serde_json::to_string(&metadata).unwrap();
+        assert!(json.contains(r#""source":{"kind":"git","#), "{}", json);
+        assert!(json.contains(r#""kind":"dev""#), "{}", json);
+        assert_eq!(serde_json::from_str::<MetadataV1>(&json).unwrap(), metadata);
+    }
+}
@@ -1,26 +1,113 @@
 // Copyright (c) The Move Contributors
 // SPDX-License-Identifier: Apache-2.0
 
+pub mod add;
+pub mod address;
+pub mod bench;
 pub mod build;
+pub mod cache;
+pub mod clean;
 pub mod coverage;
+pub mod diff_abi;
 pub mod disassemble;
 pub mod docgen;
 pub mod errmap;
+pub mod experiments;
+pub mod fmt_check;
+pub mod fuzz;
+pub mod gas_report;
+pub mod help;
 pub mod info;
+pub mod metadata;
 pub mod movey_login;
+pub mod movey_logout;
 pub mod movey_upload;
 pub mod new;
 pub mod prove;
+pub mod publish_log;
 pub mod test;
+pub mod toolchain;
+pub mod verify;
+pub mod warn_diff;
+pub mod workspace;
 
-use move_package::source_package::layout::SourcePackageLayout;
-use std::path::PathBuf;
+use anyhow::{bail, Context};
+use move_package::{
+    source_package::{layout::SourcePackageLayout, manifest_parser::parse_move_manifest_from_file},
+    BuildConfig,
+};
+use std::{
+    env,
+    path::{Path, PathBuf},
+};
+
+/// The environment variable setting the out dir of the build when `--out-dir` is not given
+pub const MOVE_BUILD_DIR_ENV_VAR: &str = "MOVE_BUILD_DIR";
 
 pub fn reroot_path(path: Option<PathBuf>) -> anyhow::Result<PathBuf> {
-    let path = path.unwrap_or_else(|| PathBuf::from("."));
     // Always root ourselves to the package root, and then compile relative to that.
-    let rooted_path = SourcePackageLayout::try_find_root(&path.canonicalize()?)?;
+    let search_path = package_search_path(path.as_deref().unwrap_or_else(|| Path::new(".")))?;
+    let rooted_path = SourcePackageLayout::try_find_root(&search_path)?;
+    if rooted_path != search_path {
+        eprintln!(
+            "Using the package manifest {}",
+            rooted_path
+                .join(SourcePackageLayout::Manifest.path())
+                .display()
+        );
+    }
     std::env::set_current_dir(&rooted_path).unwrap();
 
     Ok(PathBuf::from("."))
 }
+
+/// The root directory of the package `path` points into, or of the package containing the current
+/// directory if `path` is not set
+pub fn package_root(path: Option<&Path>) -> anyhow::Result<PathBuf> {
+    let path = path.unwrap_or_else(|| Path::new("."));
+    SourcePackageLayout::try_find_root(&package_search_path(path)?)
+}
+
+/// The `--path` option may point either at a directory inside a package or at the package's
+/// `Move.toml` itself. Returns the (canonical) directory from which to search for the package root.
+fn package_search_path(path: &Path) -> anyhow::Result<PathBuf> {
+    let canonical_path = path.canonicalize().with_context(|| {
+        format!(
+            "Unable to find package manifest at '{}'",
+            path.to_string_lossy()
+        )
+    })?;
+    if !canonical_path.is_file() {
+        return Ok(canonical_path);
+    }
+    if !canonical_path.ends_with(SourcePackageLayout::Manifest.path()) {
+        bail!(
+            "'{}' is neither a package directory nor a package manifest ({})",
+            path.to_string_lossy(),
+            SourcePackageLayout::Manifest.location_str()
+        )
+    }
+    Ok(canonical_path.parent().unwrap().to_path_buf())
+}
+
+/// Resolve the `--out-dir` of `config`, or `MOVE_BUILD_DIR` if neither `--out-dir` nor
+/// `--install-dir` is given, against the current directory, and install the build of the package
+/// `path` points into under it, so that every command, e.g. `move coverage` after
+/// `move test --coverage`, finds its artifacts there
+pub fn resolve_out_dir(path: Option<&Path>, config: &mut BuildConfig) -> anyhow::Result<()> {
+    let out_dir = config.out_dir.take().or_else(|| {
+        env::var_os(MOVE_BUILD_DIR_ENV_VAR)
+            .filter(|dir| !dir.is_empty() && config.install_dir.is_none())
+            .map(PathBuf::from)
+    });
+    config.out_dir = match out_dir {
+        Some(out_dir) => Some(env::current_dir()?.join(out_dir)),
+        None => return Ok(()),
+    };
+    // outside of a package, e.g. for `move new`, or in a workspace without a root package, the
+    // installation directory of each package is set when it is built
+    if let Ok(manifest) = package_root(path).and_then(|root| parse_move_manifest_from_file(&root)) {
+        config.install_dir = config.install_dir_for_package(manifest.package.name.as_str());
+    }
+    Ok(())
+}
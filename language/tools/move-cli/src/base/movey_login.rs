@@ -1,145 +1,293 @@
 // Copyright (c) The Move Contributors
 // SPDX-License-Identifier: Apache-2.0
 
-use crate::utils::movey_credential::read_credential_file;
-use anyhow::{bail, Result};
+use crate::utils::{
+    keyring::{Keyring, SystemKeyring},
+    movey_credential::{
+        credential_path, get_movey_url, read_credential_file, reconcile_api_token, uses_keyring,
+        CredentialMigration,
+    },
+    prompt,
+    registry_tls::RegistryTlsOptions,
+};
+use anyhow::{bail, Context, Result};
 use clap::Parser;
 use move_command_line_common::{
-    env::MOVE_HOME,
-    movey_constants::{MOVEY_CREDENTIAL_PATH, MOVEY_URL},
+    env::{move_home, move_home_write_error},
+    files::utf8_file_contents,
+    json_errors,
+    movey_constants::MOVEY_URL,
+};
+use serde_json::json;
+use std::{
+    fs,
+    fs::File,
+    io,
+    path::{Path, PathBuf},
 };
-use std::{fs, fs::File, io, path::PathBuf};
 use toml_edit::easy::{map::Map, Value};
 
+const EXAMPLES: &str = "EXAMPLES:
+    Save the API token pasted at the prompt:
+        move movey-login
+
+    Save the API token read from a file:
+        move movey-login < token.txt
+
+    Save the API token given on the command line, e.g. in CI, printing nothing:
+        move movey-login --token $MOVEY_TOKEN --quiet
+
+    Save the API token under another MOVE_HOME:
+        MOVE_HOME=/tmp/move-home move movey-login
+
+    Save the API token to the system keyring, removing the one left in the credential file:
+        move movey-login --migrate-credentials
+
+    Save the API token, and the certificates to use for a registry requiring mutual TLS:
+        move movey-login --cacert ca.pem --client-cert client.pem --client-key client.key
+
+    Save the API token without checking it with Movey, e.g. when offline:
+        move movey-login --no-verify";
+
 #[derive(Parser)]
-#[clap(name = "movey-login")]
-pub struct MoveyLogin;
+#[clap(name = "movey-login", after_help = EXAMPLES)]
+pub struct MoveyLogin {
+    /// The API token to save, instead of reading it from stdin
+    #[clap(long = "token", value_name = "TOKEN")]
+    pub token: Option<String>,
+    /// Do not print that the token was saved
+    #[clap(long = "quiet", short = 'q')]
+    pub quiet: bool,
+    /// When the token is stored in the system keyring (`token_storage = "keyring"` in the
+    /// `[registry]` table of the credential file), remove the plaintext token left in the
+    /// credential file without asking
+    #[clap(long = "migrate-credentials")]
+    pub migrate_credentials: bool,
+    /// Save the token without checking with Movey that it is valid
+    #[clap(long = "no-verify")]
+    pub no_verify: bool,
+    /// Certificate authority and client certificate to save into the `[registry]` table of the
+    /// credential file, as absolute paths, for the commands that talk to the registry
+    #[clap(flatten)]
+    pub tls: RegistryTlsOptions,
+}
 
 impl MoveyLogin {
     pub fn execute(self) -> Result<()> {
-        println!(
-            "Please paste the API Token found on {}/settings/tokens below",
-            MOVEY_URL
-        );
-        let mut line = String::new();
-        loop {
-            match io::stdin().read_line(&mut line) {
-                Ok(_) => {
-                    line = line.trim().to_string();
-                    if !line.is_empty() {
-                        break;
-                    }
-                    println!("Invalid API Token. Try again!");
-                }
-                Err(err) => {
-                    bail!("Error reading file: {}", err);
+        let move_home = move_home()?;
+        // with --token, stdin is left alone, so that the command can run unattended
+        let interactive = self.token.is_none() && atty::is(atty::Stream::Stdin);
+        let line = match &self.token {
+            Some(token) => {
+                let token = token.trim();
+                if token.is_empty() {
+                    bail!("The API token given with --token is empty")
                 }
+                token.to_string()
             }
+            None => read_token()?,
+        };
+        if !self.no_verify {
+            self.verify_token(&move_home, &line, interactive)?;
         }
-        Self::save_credential(line, MOVE_HOME.clone())?;
-        println!("Token for Movey saved.");
+        if interactive {
+            offer_to_replace_corrupted_credential_file(&credential_path(&move_home))?;
+        }
+        let saved = if uses_keyring(&move_home) {
+            SystemKeyring.set(&get_movey_url(&move_home)?, &line)?;
+            reconcile_api_token(
+                &move_home,
+                &SystemKeyring,
+                CredentialMigration::from_flag(self.migrate_credentials),
+            )?;
+            "Token for Movey saved to the system keyring."
+        } else {
+            Self::save_credential(line, move_home.clone())?;
+            "Token for Movey saved."
+        };
+        if !self.quiet {
+            println!("{}", saved);
+        }
+        self.tls.save(&move_home)?;
         Ok(())
     }
 
+    /// Check with the registry that `token` is a valid API token, failing if it is rejected. If the
+    /// registry cannot be reached, warn and, if `interactive`, ask whether to save the token anyway.
+    fn verify_token(&self, move_home: &str, token: &str, interactive: bool) -> Result<()> {
+        let url = get_movey_url(move_home).unwrap_or_else(|_| MOVEY_URL.to_string());
+        let tls = self.tls.resolve(move_home)?;
+        let response = tls
+            .client()?
+            .post(format!("{}/api/v1/tokens/validate", url))
+            .json(&json!({ "token": token }))
+            .send();
+        let problem = match response {
+            Ok(response) if response.status().is_success() => return Ok(()),
+            Ok(response) if response.status().is_client_error() => bail!(
+                "Movey rejected the API token ({}). Check that it was copied whole from \
+                 {}/settings/tokens",
+                response.status(),
+                url
+            ),
+            Ok(response) => format!("{} answered {}", url, response.status()),
+            Err(err) => format!("{:#}", tls.explain_error(&url, err.into())),
+        };
+        json_errors::warning(format!("unable to verify the API token: {}", problem));
+        if !interactive {
+            bail!("The API token was not saved. Pass --no-verify to save it without verifying it")
+        }
+        prompt::confirm("Save the API token anyway?", false)
+    }
+
+    /// Save `token` into the `[registry]` table of the credential file in `move_home`, creating the
+    /// directory and the file if needed. The other fields of the file are kept as they are.
     pub fn save_credential(token: String, move_home: String) -> Result<()> {
-        fs::create_dir_all(&move_home)?;
-        let credential_path = move_home + MOVEY_CREDENTIAL_PATH;
+        fs::create_dir_all(&move_home).map_err(|err| {
+            move_home_write_error("create the directory", Path::new(&move_home), err)
+        })?;
+        let credential_path = credential_path(&move_home);
         let credential_file = PathBuf::from(&credential_path);
         if !credential_file.exists() {
-            create_credential_file(&credential_path)?;
+            create_credential_file(&credential_file)?;
         }
 
         let mut toml: Value = read_credential_file(&credential_path)?;
         // only update token key, keep the rest of the file intact
-        if let Some(registry) = toml.as_table_mut().unwrap().get_mut("registry") {
-            if let Some(toml_token) = registry.as_table_mut().unwrap().get_mut("token") {
-                *toml_token = Value::String(token);
-            } else {
-                registry
-                    .as_table_mut()
-                    .unwrap()
-                    .insert(String::from("token"), Value::String(token));
-            }
-        } else {
-            let mut value = Map::new();
-            value.insert(String::from("token"), Value::String(token));
-            toml.as_table_mut()
-                .unwrap()
-                .insert(String::from("registry"), Value::Table(value));
-        }
+        let registry = toml
+            .as_table_mut()
+            .with_context(|| format!("'{}' is not a TOML table", credential_path))?
+            .entry("registry")
+            .or_insert_with(|| Value::Table(Map::new()))
+            .as_table_mut()
+            .with_context(|| {
+                format!(
+                    "Unable to save the credential to '{}': its `registry` field is not a table",
+                    credential_path
+                )
+            })?;
+        registry.insert(String::from("token"), Value::String(token));
 
         let new_contents = toml.to_string();
-        fs::write(credential_file, new_contents).expect("Unable to write file");
+        fs::write(&credential_file, new_contents).map_err(|err| {
+            move_home_write_error("save the credential to", &credential_file, err)
+        })?;
         Ok(())
     }
 }
 
+/// Read the API token pasted at the prompt, asking again while the lines read are blank
+fn read_token() -> Result<String> {
+    println!(
+        "Please paste the API Token found on {}/settings/tokens below",
+        MOVEY_URL
+    );
+    let mut line = String::new();
+    loop {
+        let read = io::stdin()
+            .read_line(&mut line)
+            .context("Unable to read the API token from stdin")?;
+        if read == 0 {
+            bail!("No API token was given: stdin was closed before a token was read")
+        }
+        line = line.trim().to_string();
+        if !line.is_empty() {
+            return Ok(line);
+        }
+        println!("Invalid API Token. Try again!");
+    }
+}
+
+/// If the credential file is not valid UTF-8, e.g. because it is corrupted, ask whether to remove it
+/// so that a new one is created. Without asking, saving the token fails with an error telling to
+/// remove it.
+fn offer_to_replace_corrupted_credential_file(credential_path: &str) -> Result<()> {
+    let bytes = match fs::read(credential_path) {
+        Ok(bytes) => bytes,
+        Err(_) => return Ok(()),
+    };
+    if let Err(err) = utf8_file_contents(Path::new(credential_path), bytes) {
+        json_errors::warning(format!("the credential file is corrupted: {}", err));
+        prompt::confirm(
+            "Replace it with a new credential file? The settings it holds are lost.",
+            false,
+        )?;
+        fs::remove_file(credential_path).map_err(|err| {
+            move_home_write_error(
+                "remove the credential file",
+                Path::new(credential_path),
+                err,
+            )
+        })?;
+    }
+    Ok(())
+}
+
+/// Create an empty credential file only readable and writable by its owner
 #[cfg(unix)]
-fn create_credential_file(credential_path: &str) -> Result<()> {
+fn create_credential_file(credential_file: &Path) -> Result<()> {
     use std::os::unix::fs::PermissionsExt;
-    let credential_file = File::create(&credential_path)?;
-
-    let mut perms = credential_file.metadata()?.permissions();
+    let file = File::create(credential_file)
+        .map_err(|err| move_home_write_error("create the credential file", credential_file, err))?;
+    let mut perms = file
+        .metadata()
+        .map_err(|err| move_home_write_error("read the permissions of", credential_file, err))?
+        .permissions();
     perms.set_mode(0o600);
-    credential_file.set_permissions(perms)?;
+    file.set_permissions(perms).map_err(|err| {
+        move_home_write_error("restrict the permissions of", credential_file, err)
+    })?;
     Ok(())
 }
 
 #[cfg(windows)]
 #[allow(unused)]
-fn create_credential_file(credential_path: &str) -> Result<()> {
-    let windows_path = credential_path.replace("/", "\\");
-    File::create(&windows_path)?;
+fn create_credential_file(credential_file: &Path) -> Result<()> {
+    let windows_path = credential_file.to_string_lossy().replace('/', "\\");
+    File::create(&windows_path)
+        .map_err(|err| move_home_write_error("create the credential file", credential_file, err))?;
     Ok(())
 }
 
 #[cfg(not(any(unix, windows)))]
 #[allow(unused)]
-fn create_credential_file(credential_path: &str) -> Result<()> {
-    bail!("OS not supported")
+fn create_credential_file(credential_file: &Path) -> Result<()> {
+    bail!(
+        "Unable to create the credential file '{}': OS not supported",
+        credential_file.display()
+    )
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
-    use std::env;
-
-    fn setup_move_home(test_path: &str) -> (String, String) {
-        let cwd = env::current_dir().unwrap();
-        let mut move_home: String = String::from(cwd.to_string_lossy());
-        if !test_path.is_empty() {
-            move_home.push_str(test_path);
-        } else {
-            move_home.push_str("/test");
-        }
-        let credential_path = move_home.clone() + MOVEY_CREDENTIAL_PATH;
-        (move_home, credential_path)
-    }
-
-    fn clean_up(move_home: &str) {
-        let _ = fs::remove_dir_all(move_home);
+    use tempfile::TempDir;
+
+    /// A `MOVE_HOME` that does not exist yet inside a temporary directory, which is removed when
+    /// the returned `TempDir` is dropped
+    fn setup_move_home() -> (TempDir, String, String) {
+        let dir = tempfile::tempdir().unwrap();
+        let move_home = dir.path().join("move_home").to_string_lossy().to_string();
+        let credential_path = credential_path(&move_home);
+        (dir, move_home, credential_path)
     }
 
     #[test]
     fn save_credential_works_if_no_credential_file_exists() {
-        let (move_home, credential_path) =
-            setup_move_home("/save_credential_works_if_no_credential_file_exists");
+        let (_dir, move_home, credential_path) = setup_move_home();
         let _ = fs::remove_dir_all(&move_home);
-        MoveyLogin::save_credential(String::from("test_token"), move_home.clone()).unwrap();
+        MoveyLogin::save_credential(String::from("test_token"), move_home).unwrap();
 
         let contents = fs::read_to_string(&credential_path).expect("Unable to read file");
         let mut toml: Value = contents.parse().unwrap();
         let registry = toml.as_table_mut().unwrap().get_mut("registry").unwrap();
         let token = registry.as_table_mut().unwrap().get_mut("token").unwrap();
         assert!(token.to_string().contains("test_token"));
-
-        clean_up(&move_home);
     }
 
     #[test]
     fn save_credential_works_if_empty_credential_file_exists() {
-        let (move_home, credential_path) =
-            setup_move_home("/save_credential_works_if_empty_credential_file_exists");
+        let (_dir, move_home, credential_path) = setup_move_home();
 
         let _ = fs::remove_dir_all(&move_home);
         fs::create_dir_all(&move_home).unwrap();
@@ -149,21 +297,18 @@ mod tests {
         let mut toml: Value = contents.parse().unwrap();
         assert!(toml.as_table_mut().unwrap().get_mut("registry").is_none());
 
-        MoveyLogin::save_credential(String::from("test_token"), move_home.clone()).unwrap();
+        MoveyLogin::save_credential(String::from("test_token"), move_home).unwrap();
 
         let contents = fs::read_to_string(&credential_path).expect("Unable to read file");
         let mut toml: Value = contents.parse().unwrap();
         let registry = toml.as_table_mut().unwrap().get_mut("registry").unwrap();
         let token = registry.as_table_mut().unwrap().get_mut("token").unwrap();
         assert!(token.to_string().contains("test_token"));
-
-        clean_up(&move_home);
     }
 
     #[test]
     fn save_credential_works_if_token_field_exists() {
-        let (move_home, credential_path) =
-            setup_move_home("/save_credential_works_if_token_field_exists");
+        let (_dir, move_home, credential_path) = setup_move_home();
 
         let _ = fs::remove_dir_all(&move_home);
         fs::create_dir_all(&move_home).unwrap();
@@ -180,7 +325,7 @@ mod tests {
         assert!(token.to_string().contains("old_test_token"));
         assert!(!token.to_string().contains("new_world"));
 
-        MoveyLogin::save_credential(String::from("new_world"), move_home.clone()).unwrap();
+        MoveyLogin::save_credential(String::from("new_world"), move_home).unwrap();
 
         let contents = fs::read_to_string(&credential_path).expect("Unable to read file");
         let mut toml: Value = contents.parse().unwrap();
@@ -190,14 +335,11 @@ mod tests {
         assert!(!token.to_string().contains("old_test_token"));
         let version = registry.as_table_mut().unwrap().get_mut("version").unwrap();
         assert!(version.to_string().contains("0.0.0"));
-
-        clean_up(&move_home);
     }
 
     #[test]
     fn save_credential_works_if_empty_token_field_exists() {
-        let (move_home, credential_path) =
-            setup_move_home("/save_credential_works_if_empty_token_field_exists");
+        let (_dir, move_home, credential_path) = setup_move_home();
 
         let _ = fs::remove_dir_all(&move_home);
         fs::create_dir_all(&move_home).unwrap();
@@ -212,7 +354,7 @@ mod tests {
         let token = registry.as_table_mut().unwrap().get_mut("token").unwrap();
         assert!(!token.to_string().contains("test_token"));
 
-        MoveyLogin::save_credential(String::from("test_token"), move_home.clone()).unwrap();
+        MoveyLogin::save_credential(String::from("test_token"), move_home).unwrap();
 
         let contents = fs::read_to_string(&credential_path).expect("Unable to read file");
         let mut toml: Value = contents.parse().unwrap();
@@ -221,7 +363,142 @@ mod tests {
         assert!(token.to_string().contains("test_token"));
         let version = registry.as_table_mut().unwrap().get_mut("version").unwrap();
         assert!(version.to_string().contains("0.0.0"));
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn save_credential_fails_if_move_home_cannot_be_created() {
+        use std::os::unix::fs::PermissionsExt;
+        let (dir, move_home, _) = setup_move_home();
+        fs::set_permissions(dir.path(), fs::Permissions::from_mode(0o555)).unwrap();
+
+        let result = MoveyLogin::save_credential(String::from("test_token"), move_home.clone());
+        fs::set_permissions(dir.path(), fs::Permissions::from_mode(0o755)).unwrap();
+
+        let error = format!("{:#}", result.unwrap_err());
+        assert!(
+            error.contains(&format!("Unable to create the directory '{}'", move_home)),
+            "{}",
+            error
+        );
+    }
+
+    #[test]
+    fn save_credential_fails_if_credential_file_is_not_toml() {
+        let (_dir, move_home, credential_path) = setup_move_home();
+        fs::create_dir_all(&move_home).unwrap();
+        fs::write(&credential_path, "[registry\ntoken = ").unwrap();
+
+        let result = MoveyLogin::save_credential(String::from("test_token"), move_home);
+
+        let error = format!("{:#}", result.unwrap_err());
+        assert!(
+            error.contains(&format!(
+                "could not parse input at {} as TOML",
+                credential_path
+            )),
+            "{}",
+            error
+        );
+        // the invalid file is left as it is
+        assert_eq!(
+            fs::read_to_string(&credential_path).unwrap(),
+            "[registry\ntoken = "
+        );
+    }
+
+    #[test]
+    fn save_credential_fails_if_registry_is_not_a_table() {
+        let (_dir, move_home, credential_path) = setup_move_home();
+        fs::create_dir_all(&move_home).unwrap();
+        fs::write(&credential_path, "registry = \"https://movey.net\"\n").unwrap();
+
+        let result = MoveyLogin::save_credential(String::from("test_token"), move_home);
+
+        let error = format!("{:#}", result.unwrap_err());
+        assert!(
+            error.contains(&format!(
+                "Unable to save the credential to '{}': its `registry` field is not a table",
+                credential_path
+            )),
+            "{}",
+            error
+        );
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn save_credential_fails_if_credential_file_is_read_only() {
+        use std::os::unix::fs::PermissionsExt;
+        let (_dir, move_home, credential_path) = setup_move_home();
+        fs::create_dir_all(&move_home).unwrap();
+        fs::write(&credential_path, "[registry]\ntoken = \"old_token\"\n").unwrap();
+        fs::set_permissions(&credential_path, fs::Permissions::from_mode(0o400)).unwrap();
+
+        let result = MoveyLogin::save_credential(String::from("test_token"), move_home);
+        fs::set_permissions(&credential_path, fs::Permissions::from_mode(0o600)).unwrap();
+
+        let error = format!("{:#}", result.unwrap_err());
+        assert!(
+            error.contains(&format!(
+                "Unable to save the credential to '{}'",
+                credential_path
+            )) && error
+                .contains("If MOVE_HOME is read-only, set MOVE_HOME to a writable directory"),
+            "{}",
+            error
+        );
+        assert!(fs::read_to_string(&credential_path)
+            .unwrap()
+            .contains("old_token"));
+    }
+
+    #[test]
+    fn save_credential_works_if_credential_file_starts_with_a_bom() {
+        let (_dir, move_home, credential_path) = setup_move_home();
+        fs::create_dir_all(&move_home).unwrap();
+        fs::write(
+            &credential_path,
+            "\u{feff}[registry]\ntoken = \"old_token\"\nversion = \"0.0.0\"\n",
+        )
+        .unwrap();
 
-        clean_up(&move_home);
+        MoveyLogin::save_credential(String::from("test_token"), move_home).unwrap();
+
+        let contents = fs::read_to_string(&credential_path).unwrap();
+        let mut toml: Value = contents.parse().unwrap();
+        let registry = toml.as_table_mut().unwrap().get_mut("registry").unwrap();
+        assert_eq!(registry.get("token").unwrap().as_str(), Some("test_token"));
+        assert_eq!(registry.get("version").unwrap().as_str(), Some("0.0.0"));
+    }
+
+    #[test]
+    fn save_credential_fails_if_credential_file_is_not_utf8() {
+        let (_dir, move_home, credential_path) = setup_move_home();
+        fs::create_dir_all(&move_home).unwrap();
+        let content = b"[registry]\ntoken = \"\xe2\x82\"\n";
+        fs::write(&credential_path, content).unwrap();
+
+        let result = MoveyLogin::save_credential(String::from("test_token"), move_home);
+
+        let error = format!("{:#}", result.unwrap_err());
+        assert!(
+            error.contains(&format!(
+                "The credential file is corrupted. Delete '{}' and run `move movey-login` to save \
+                 the API token again",
+                credential_path
+            )),
+            "{}",
+            error
+        );
+        assert!(
+            error.contains(&format!(
+                "'{}' is not valid UTF-8: invalid byte sequence at byte offset 20",
+                credential_path
+            )),
+            "{}",
+            error
+        );
+        assert_eq!(fs::read(&credential_path).unwrap(), content);
     }
 }
@@ -0,0 +1,168 @@
+// Copyright (c) The Move Contributors
+// SPDX-License-Identifier: Apache-2.0
+
+use crate::utils::{
+    keyring::{Keyring, SystemKeyring},
+    movey_credential::{credential_path, get_movey_url, read_credential_contents, uses_keyring},
+};
+use anyhow::{Context, Result};
+use clap::Parser;
+use move_command_line_common::env::{move_home, move_home_write_error};
+use std::{fs, path::Path};
+use toml_edit::Document;
+
+const EXAMPLES: &str = "EXAMPLES:
+    Remove the API token saved by `move movey-login`:
+        move movey-logout
+
+    Remove the API token saved under another MOVE_HOME:
+        MOVE_HOME=/tmp/move-home move movey-logout";
+
+/// Remove the Movey API token saved by `move movey-login`, from the credential file and, when the
+/// token is stored there, from the system keyring. The other settings of the credential file are
+/// kept.
+#[derive(Parser)]
+#[clap(name = "movey-logout", after_help = EXAMPLES)]
+pub struct MoveyLogout {}
+
+impl MoveyLogout {
+    pub fn execute(self) -> Result<()> {
+        let move_home = move_home()?;
+        let mut removed = false;
+        if uses_keyring(&move_home) {
+            let account = get_movey_url(&move_home)?;
+            if SystemKeyring.get(&account)?.is_some() {
+                SystemKeyring.remove(&account)?;
+                removed = true;
+            }
+        }
+        removed |= Self::remove_credential(move_home)?;
+        if removed {
+            println!("Token for Movey removed.");
+        } else {
+            println!("You are not logged in to Movey: no API token is saved.");
+        }
+        Ok(())
+    }
+
+    /// Remove the token from the `[registry]` table of the credential file in `move_home`, keeping
+    /// the other fields of the file, comments included, as they are. The table is removed once
+    /// empty, and the file once nothing is left in it. Returns whether there was a token to remove.
+    pub fn remove_credential(move_home: String) -> Result<bool> {
+        let credential_path = credential_path(&move_home);
+        if !Path::new(&credential_path).exists() {
+            return Ok(false);
+        }
+        let contents = read_credential_contents(&credential_path)?;
+        let mut document: Document = contents
+            .parse()
+            .with_context(|| format!("could not parse input at {} as TOML", credential_path))?;
+        let registry = match document
+            .get_mut("registry")
+            .and_then(|registry| registry.as_table_like_mut())
+        {
+            Some(registry) => registry,
+            None => return Ok(false),
+        };
+        if registry.remove("token").is_none() {
+            return Ok(false);
+        }
+        if registry.is_empty() {
+            document.remove("registry");
+        }
+
+        let credential_file = Path::new(&credential_path);
+        if document.as_table().is_empty() {
+            fs::remove_file(credential_file).map_err(|err| {
+                move_home_write_error("remove the credential file", credential_file, err)
+            })?;
+        } else {
+            fs::write(credential_file, document.to_string()).map_err(|err| {
+                move_home_write_error("remove the credential from", credential_file, err)
+            })?;
+        }
+        Ok(true)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    /// A `MOVE_HOME` holding a credential file with `contents`, inside a temporary directory which
+    /// is removed when the returned `TempDir` is dropped
+    fn setup_move_home(contents: &str) -> (TempDir, String, String) {
+        let dir = tempfile::tempdir().unwrap();
+        let move_home = dir.path().join("move_home").to_string_lossy().to_string();
+        fs::create_dir_all(&move_home).unwrap();
+        let credential_path = credential_path(&move_home);
+        fs::write(&credential_path, contents).unwrap();
+        (dir, move_home, credential_path)
+    }
+
+    #[test]
+    fn remove_credential_keeps_the_other_fields() {
+        let (_dir, move_home, credential_path) = setup_move_home(
+            "# saved by move movey-login\n[registry]\ntoken = \"test_token\"\nversion = \"0.0.0\"\n",
+        );
+
+        assert!(MoveyLogout::remove_credential(move_home).unwrap());
+        assert_eq!(
+            fs::read_to_string(&credential_path).unwrap(),
+            "# saved by move movey-login\n[registry]\nversion = \"0.0.0\"\n"
+        );
+    }
+
+    #[test]
+    fn remove_credential_removes_the_file_once_empty() {
+        let (_dir, move_home, credential_path) =
+            setup_move_home("[registry]\ntoken = \"test_token\"\n");
+
+        assert!(MoveyLogout::remove_credential(move_home).unwrap());
+        assert!(!Path::new(&credential_path).exists());
+    }
+
+    #[test]
+    fn remove_credential_without_a_token_changes_nothing() {
+        let (dir, move_home, credential_path) =
+            setup_move_home("[registry]\nversion = \"0.0.0\"\n");
+
+        assert!(!MoveyLogout::remove_credential(move_home.clone()).unwrap());
+        assert_eq!(
+            fs::read_to_string(&credential_path).unwrap(),
+            "[registry]\nversion = \"0.0.0\"\n"
+        );
+
+        fs::remove_file(&credential_path).unwrap();
+        assert!(!MoveyLogout::remove_credential(move_home).unwrap());
+        let no_move_home = dir.path().join("missing").to_string_lossy().to_string();
+        assert!(!MoveyLogout::remove_credential(no_move_home).unwrap());
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn remove_credential_fails_if_credential_file_is_read_only() {
+        use std::os::unix::fs::PermissionsExt;
+        let (_dir, move_home, credential_path) =
+            setup_move_home("[registry]\ntoken = \"test_token\"\nversion = \"0.0.0\"\n");
+        fs::set_permissions(&credential_path, fs::Permissions::from_mode(0o400)).unwrap();
+
+        let result = MoveyLogout::remove_credential(move_home);
+        fs::set_permissions(&credential_path, fs::Permissions::from_mode(0o600)).unwrap();
+
+        let error = format!("{:#}", result.unwrap_err());
+        assert!(
+            error.contains(&format!(
+                "Unable to remove the credential from '{}'",
+                credential_path
+            )) && error
+                .contains("If MOVE_HOME is read-only, set MOVE_HOME to a writable directory"),
+            "{}",
+            error
+        );
+        assert!(fs::read_to_string(&credential_path)
+            .unwrap()
+            .contains("test_token"));
+    }
+}
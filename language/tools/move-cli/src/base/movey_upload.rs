@@ -1,12 +1,33 @@
 // Copyright (c) The Move Contributors
 // SPDX-License-Identifier: Apache-2.0
 
-use crate::utils::movey_credential;
-use anyhow::bail;
+use super::reroot_path;
+use crate::utils::{
+    keyring::SystemKeyring,
+    movey_credential::{self, CredentialMigration},
+    prompt,
+    publish_log::{append_publish_log, PublishLogEntry},
+    registry_tls::RegistryTlsOptions,
+    streaming_upload::{file_digest, MultipartUpload},
+};
+use anyhow::{bail, Context};
+use chrono::{SecondsFormat, Utc};
 use clap::*;
-use move_command_line_common::env::MOVE_HOME;
-use reqwest::blocking::Client;
-use std::{env, fs::File, path::PathBuf, process::Command};
+use move_command_line_common::{env::move_home, files::read_utf8_file, json_errors};
+use move_package::{
+    resolution::{git::git, registry},
+    source_package::{
+        layout::{package_move_files, SourcePackageLayout},
+        manifest_parser::{self, parse_move_manifest_from_file},
+        parsed_manifest::format_version,
+    },
+    BuildConfig,
+};
+use std::{
+    fs::File,
+    path::{Path, PathBuf},
+};
+use tempfile::NamedTempFile;
 
 // Metadata that will be collected by Movey
 #[derive(serde::Serialize, Default)]
@@ -15,120 +36,687 @@ pub struct MoveyUploadRequest {
     total_files: usize,
     token: String,
     subdir: String,
+    name: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    release_notes: Option<String>,
 }
 
-/// Upload the package metadata to Movey.net.
+/// The metadata to upload for a package, along with the commit the package is uploaded from, which
+/// `--dry-run` prints. The commit is not part of the metadata sent to Movey.
+#[derive(serde::Serialize)]
+pub struct UploadPreview {
+    pub request: MoveyUploadRequest,
+    pub rev: String,
+    /// The SHA-256 digest of the archive that would be uploaded along with the metadata
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub archive_digest: Option<String>,
+}
+
+impl MoveyUploadRequest {
+    /// The metadata as form fields, in the order of the JSON body, so that the multipart body does
+    /// not depend on how maps are ordered
+    fn form_fields(&self) -> Vec<(String, String)> {
+        vec![
+            ("github_repo_url".to_string(), self.github_repo_url.clone()),
+            ("total_files".to_string(), self.total_files.to_string()),
+            ("token".to_string(), self.token.clone()),
+            ("subdir".to_string(), self.subdir.clone()),
+            ("name".to_string(), self.name.clone()),
+        ]
+        .into_iter()
+        .chain(
+            self.release_notes
+                .iter()
+                .map(|notes| ("release_notes".to_string(), notes.clone())),
+        )
+        .collect()
+    }
+}
+
+const EXAMPLES: &str = "EXAMPLES:
+    Upload the metadata of the package in the current directory:
+        move movey-upload
+
+    Also upload an archive of the package, without asking for confirmation:
+        move movey-upload --archive --yes
+
+    Upload the package in another directory, without showing the progress:
+        move movey-upload --path ../coins --quiet
+
+    Upload to a self-hosted registry requiring a client certificate from a private CA:
+        move movey-upload --cacert ca.pem --client-cert client.pem --client-key client.key
+
+    Attach the notes of a release kept outside of CHANGELOG.md:
+        move movey-upload --changelog docs/RELEASES.md
+
+    Copy the package into a registry kept in a directory, instead of uploading it:
+        move movey-upload --registry-path /srv/move-registry
+
+    Check the package can be uploaded, printing what would be sent without sending it:
+        move movey-upload --dry-run --yes";
+
+/// What the API token is replaced with in the metadata printed by `--dry-run`
+const REDACTED_TOKEN: &str = "<redacted>";
+
+/// The changelog whose section for the version of the package is uploaded as its release notes,
+/// if the package has one
+const CHANGELOG_FILE: &str = "CHANGELOG.md";
+
+/// Upload the package metadata to Movey.net. With `--registry-path`, an archive of the package is
+/// copied into the registry directory instead.
 #[derive(Parser)]
-#[clap(name = "movey-upload")]
-pub struct MoveyUpload;
+#[clap(name = "movey-upload", after_help = EXAMPLES)]
+pub struct MoveyUpload {
+    /// Also upload a `.tar.gz` archive of the files of the package committed to git
+    #[clap(long = "archive")]
+    pub archive: bool,
+    /// Do not show the progress of the archive upload
+    #[clap(long = "quiet", short = 'q')]
+    pub quiet: bool,
+    /// Go ahead with the upload without asking for confirmation
+    #[clap(long = "yes", short = 'y')]
+    pub yes: bool,
+    /// Do not check that the name of the package matches the name of its directory or repository
+    #[clap(long = "no-name-check")]
+    pub no_name_check: bool,
+    /// When the token is stored in the system keyring, remove the plaintext token left in the
+    /// credential file, or move it to the keyring if the keyring holds none, without asking
+    #[clap(long = "migrate-credentials")]
+    pub migrate_credentials: bool,
+    /// Upload the package even if it has no modules, such as a package holding only scripts
+    #[clap(long = "allow-empty")]
+    pub allow_empty: bool,
+    /// Attach the section of this changelog for the version of the package as its release notes.
+    /// Defaults to the `CHANGELOG.md` of the package, if it has one. Sections are found by their
+    /// `## [<version>]` heading, as in the keep-a-changelog format.
+    #[clap(long = "changelog", parse(from_os_str))]
+    pub changelog: Option<PathBuf>,
+    /// The maximum size of the release notes, in bytes
+    #[clap(long = "max-release-notes-size", default_value = "16384")]
+    pub max_release_notes_size: usize,
+    /// Check the package, its repository and the credential as for an upload, and print the
+    /// metadata that would be sent as JSON instead of sending it, along with the commit it is
+    /// uploaded from. The API token is left out, and the credential file is left as it is. With
+    /// `--archive`, the archive is made and its digest printed.
+    #[clap(long = "dry-run", conflicts_with = "migrate-credentials")]
+    pub dry_run: bool,
+    #[clap(flatten)]
+    pub tls: RegistryTlsOptions,
+}
 
 impl MoveyUpload {
     pub fn execute(self, path: Option<PathBuf>) -> anyhow::Result<()> {
-        if let Some(path) = path {
-            if path.exists() && path.is_dir() {
-                let _ = env::set_current_dir(&path);
-            } else {
-                bail!("invalid directory")
+        self.execute_with_config(path, BuildConfig::default())
+    }
+
+    /// Like `execute`, checking the package name as `config` allows
+    pub fn execute_with_config(
+        self,
+        path: Option<PathBuf>,
+        config: BuildConfig,
+    ) -> anyhow::Result<()> {
+        let package_path = match path {
+            Some(_) => reroot_path(path)?,
+            None => PathBuf::from("."),
+        };
+        let options = UploadOptions {
+            package_path,
+            move_home: move_home()?,
+            archive: self.archive,
+            quiet: self.quiet,
+            assume_yes: self.yes,
+            name_check: !self.no_name_check,
+            migrate_credentials: self.migrate_credentials,
+            allow_empty: self.allow_empty,
+            allow_nonstandard_names: config.allow_nonstandard_names,
+            changelog: self.changelog,
+            max_release_notes_size: self.max_release_notes_size,
+            tls: self.tls,
+        };
+        if self.dry_run {
+            if registry::registry_path().is_some() {
+                bail!("--dry-run only applies to uploads to Movey, not to a registry directory")
             }
+            let mut preview = upload_request(&options, CredentialMigration::Skip)?;
+            preview.request.token = REDACTED_TOKEN.to_string();
+            if options.archive {
+                let archive = archive_package(&options.package_path)?;
+                preview.archive_digest = Some(file_digest(archive.path())?);
+            }
+            println!("{}", serde_json::to_string_pretty(&preview)?);
+            return Ok(());
         }
-        // make sure it's a Move project
-        let move_toml = File::open("Move.toml");
-        if move_toml.is_err() {
-            bail!("Move.toml not found")
-        }
-        let metadata = move_toml.unwrap().metadata()?;
-        if metadata.len() == 0 {
-            bail!("Move.toml not found")
+        if let Some(registry_path) = registry::registry_path() {
+            let output = publish_to_registry_path(&options, &registry_path)?;
+            println!(
+                "Your package has been successfully added to the registry at {} as {}.",
+                registry_path.display(),
+                output.package_url
+            );
+            return Ok(());
         }
+        let output = upload_package(&options)?;
+        println!(
+            "Your package has been successfully uploaded to Movey at {}.",
+            output.package_url
+        );
+        Ok(())
+    }
+}
 
-        // use git command to get the repository url
-        let mut movey_upload_request: MoveyUploadRequest = Default::default();
-        let mut output = Command::new("git")
-            .current_dir(".")
-            .args(&["remote", "-v"])
-            .output()
-            .unwrap();
-        if !output.status.success() || output.stdout.is_empty() {
-            bail!("invalid git repository")
-        }
+/// Options for uploading a package with `upload_package`
+pub struct UploadOptions {
+    /// Path to the root directory of the package
+    pub package_path: PathBuf,
+    /// Directory holding the Movey credential, usually `move_home()`
+    pub move_home: String,
+    /// Also upload an archive of the files of the package committed to git
+    pub archive: bool,
+    /// Do not show the progress of the archive upload
+    pub quiet: bool,
+    /// Go ahead without asking for confirmation
+    pub assume_yes: bool,
+    /// Ask for confirmation if the name of the package matches neither the name of its directory
+    /// nor the name of its repository
+    pub name_check: bool,
+    /// Reconcile the credential file with the system keyring without asking
+    pub migrate_credentials: bool,
+    /// Upload the package even if it has no modules
+    pub allow_empty: bool,
+    /// Only warn if the name of the package is not a Move identifier
+    pub allow_nonstandard_names: bool,
+    /// The changelog to take the release notes from, instead of the `CHANGELOG.md` of the package
+    pub changelog: Option<PathBuf>,
+    /// The maximum size of the release notes, in bytes
+    pub max_release_notes_size: usize,
+    /// Certificate authority and client certificate to use for the registry, on top of the ones
+    /// set in the credential file
+    pub tls: RegistryTlsOptions,
+}
 
-        let lines = String::from_utf8_lossy(output.stdout.as_slice());
-        let lines = lines.split('\n');
-        for line in lines {
-            if line.contains("github.com") {
-                let tokens: Vec<&str> = line.split(&['\t', ' '][..]).collect();
-                if tokens.len() != 3 {
-                    bail!("invalid remote url")
+/// The result of uploading a package with `upload_package`
+#[derive(Debug)]
+pub struct UploadOutput {
+    /// Address of the package's page on Movey, or the path of its archive in a registry directory
+    pub package_url: String,
+}
+
+/// Upload the metadata of the package at `options.package_path` to Movey.
+pub fn upload_package(options: &UploadOptions) -> anyhow::Result<UploadOutput> {
+    let movey_upload_request = upload_request(
+        options,
+        CredentialMigration::from_flag(options.migrate_credentials),
+    )?
+    .request;
+    let tls = options.tls.resolve(&options.move_home)?;
+    let movey_url = movey_credential::get_movey_url(&options.move_home);
+    match movey_url {
+        Ok(url) => {
+            let client = tls.client()?;
+            let upload_url = format!("{}/api/v1/packages/upload", &url);
+            let response = if options.archive {
+                let archive = archive_package(&options.package_path)?;
+                archive_upload(&movey_upload_request, archive.path())?.send(
+                    &client,
+                    &upload_url,
+                    options.quiet,
+                )
+            } else {
+                client
+                    .post(&upload_url)
+                    .json(&movey_upload_request)
+                    .send()
+                    .map_err(anyhow::Error::from)
+            };
+            match response {
+                Ok(response) => {
+                    if response.status().is_success() {
+                        let response_id = response.text()?;
+                        record_upload(options, &url, &response_id);
+                        Ok(UploadOutput {
+                            package_url: format!("{}/packages/{}", url, response_id),
+                        })
+                    } else if response.status().is_client_error() {
+                        bail!("{}", response.text()?)
+                    } else {
+                        bail!("An unexpected error occurred. Please try again later");
+                    }
                 }
-                // convert ssh url to https
-                let https_url = if tokens[1].starts_with("git@github.com") {
-                    tokens[1].replace(':', "/").replace("git@", "https://")
-                } else {
-                    String::from(tokens[1])
-                };
-                movey_upload_request.github_repo_url = if https_url.ends_with(".git") {
-                    https_url[..https_url.len() - 4].to_string()
-                } else {
-                    https_url
-                };
+                Err(err) => Err(tls.explain_error(&url, err)),
             }
         }
+        Err(_) => bail!("An unexpected error occurred. Please try again later"),
+    }
+}
 
-        // use git command to get the subdir if move package is not on the top level
-        output = Command::new("git")
-            .current_dir(".")
-            .args(&["rev-parse", "--show-prefix"])
-            .output()
-            .unwrap();
-        let subdir = String::from_utf8_lossy(output.stdout.as_slice());
-        movey_upload_request.subdir = String::from(subdir);
-
-        // use git command to count total files
-        output = Command::new("git")
-            .current_dir(".")
-            .args(&["ls-files"])
-            .output()
-            .unwrap();
-        let tracked_files = String::from_utf8_lossy(output.stdout.as_slice());
-        let tracked_files: Vec<&str> = tracked_files.split('\n').collect();
-        let mut total_files = tracked_files.len();
-        for file_path in tracked_files {
-            if file_path.is_empty() {
-                total_files -= 1;
-                continue;
+/// The metadata to upload for the package at `options.package_path` and the commit it is uploaded
+/// from, once the package, its git repository and the credential have been checked. Nothing is
+/// sent, and the credential file is only changed as `migration` allows.
+pub fn upload_request(
+    options: &UploadOptions,
+    migration: CredentialMigration,
+) -> anyhow::Result<UploadPreview> {
+    let package_path = &options.package_path;
+    // make sure it's a Move project
+    let move_toml = File::open(package_path.join("Move.toml"));
+    if move_toml.is_err() {
+        bail!("Move.toml not found")
+    }
+    let metadata = move_toml.unwrap().metadata()?;
+    if metadata.len() == 0 {
+        bail!("Move.toml not found")
+    }
+
+    // use git command to get the repository url
+    let mut movey_upload_request: MoveyUploadRequest = Default::default();
+    let mut output = git()?
+        .command()
+        .current_dir(package_path)
+        .args(&["remote", "-v"])
+        .output()?;
+    if !output.status.success() || output.stdout.is_empty() {
+        bail!("invalid git repository")
+    }
+
+    let lines = String::from_utf8_lossy(output.stdout.as_slice());
+    let lines = lines.split('\n');
+    for line in lines {
+        if line.contains("github.com") {
+            let tokens: Vec<&str> = line.split(&['\t', ' '][..]).collect();
+            if tokens.len() != 3 {
+                bail!("invalid remote url")
             }
+            // convert ssh url to https
+            let https_url = if tokens[1].starts_with("git@github.com") {
+                tokens[1].replace(':', "/").replace("git@", "https://")
+            } else {
+                String::from(tokens[1])
+            };
+            movey_upload_request.github_repo_url = if https_url.ends_with(".git") {
+                https_url[..https_url.len() - 4].to_string()
+            } else {
+                https_url
+            };
         }
-        movey_upload_request.total_files = total_files;
-        movey_upload_request.token = movey_credential::get_registry_api_token(&MOVE_HOME)?;
-        let movey_url = movey_credential::get_movey_url(&MOVE_HOME);
-        match movey_url {
-            Ok(url) => {
-                let client = Client::new();
-                let response = client
-                    .post(&format!("{}/api/v1/packages/upload", &url))
-                    .json(&movey_upload_request)
-                    .send();
-                match response {
-                    Ok(response) => {
-                        if response.status().is_success() {
-                            println!(
-                                "Your package has been successfully uploaded to Movey at {}/packages/{}.",
-                                url,
-                                response.text()?
-                            );
-                        } else if response.status().is_client_error() {
-                            bail!("{}", response.text()?)
-                        } else if response.status().is_server_error() {
-                            bail!("An unexpected error occurred. Please try again later");
-                        }
-                    }
-                    Err(_) => {
-                        bail!("An unexpected error occurred. Please try again later");
-                    }
-                }
+    }
+
+    output = git()?
+        .command()
+        .current_dir(package_path)
+        .args(&["rev-parse", "--verify", "HEAD"])
+        .output()?;
+    if !output.status.success() {
+        bail!("The repository has no commits. Commit the package before uploading it")
+    }
+    let rev = String::from_utf8_lossy(&output.stdout).trim().to_string();
+
+    if options.name_check {
+        check_package_name(
+            package_path,
+            &movey_upload_request.github_repo_url,
+            options.assume_yes,
+        )?;
+    }
+    let manifest = parse_move_manifest_from_file(package_path)?;
+    movey_upload_request.name = registry_package_name(
+        manifest.package.name.as_str(),
+        options.allow_nonstandard_names,
+    )?;
+    movey_upload_request.release_notes = release_notes(
+        package_path,
+        options.changelog.as_deref(),
+        &format_version(&manifest.package.version),
+        options.max_release_notes_size,
+    )?;
+    if !options.allow_empty
+        && package_move_files(package_path, &[SourcePackageLayout::Sources])?.is_empty()
+    {
+        bail!(
+            "The package has no modules under '{}'. Pass --allow-empty to upload it anyway",
+            SourcePackageLayout::Sources.location_str()
+        )
+    }
+
+    // use git command to get the subdir if move package is not on the top level
+    output = git()?
+        .command()
+        .current_dir(package_path)
+        .args(&["rev-parse", "--show-prefix"])
+        .output()?;
+    let subdir = String::from_utf8_lossy(output.stdout.as_slice());
+    movey_upload_request.subdir = String::from(subdir);
+
+    // use git command to count total files
+    output = git()?
+        .command()
+        .current_dir(package_path)
+        .args(&["ls-files"])
+        .output()?;
+    let tracked_files = String::from_utf8_lossy(output.stdout.as_slice());
+    let tracked_files: Vec<&str> = tracked_files.split('\n').collect();
+    let mut total_files = tracked_files.len();
+    for file_path in tracked_files {
+        if file_path.is_empty() {
+            total_files -= 1;
+            continue;
+        }
+    }
+    movey_upload_request.total_files = total_files;
+    movey_upload_request.token =
+        movey_credential::reconcile_api_token(&options.move_home, &SystemKeyring, migration)?;
+    Ok(UploadPreview {
+        request: movey_upload_request,
+        rev,
+        archive_digest: None,
+    })
+}
+
+/// Add an archive of the files of the package at `options.package_path` committed to git to the
+/// registry directory at `registry_path`, and list it in its index. The package is checked as for
+/// an upload, but no API token is needed. The package is listed under its own name rather than in
+/// lowercase, so that `package add` depends on it by the name it is built with.
+pub fn publish_to_registry_path(
+    options: &UploadOptions,
+    registry_path: &Path,
+) -> anyhow::Result<UploadOutput> {
+    let package_path = &options.package_path;
+    if !package_path.join("Move.toml").is_file() {
+        bail!("Move.toml not found")
+    }
+    let manifest = parse_move_manifest_from_file(package_path)?;
+    let name = manifest.package.name.to_string();
+    check_uploaded_package_name(&name, options.allow_nonstandard_names)?;
+    if !options.allow_empty
+        && package_move_files(package_path, &[SourcePackageLayout::Sources])?.is_empty()
+    {
+        bail!(
+            "The package has no modules under '{}'. Pass --allow-empty to upload it anyway",
+            SourcePackageLayout::Sources.location_str()
+        )
+    }
+    let archive = archive_package(package_path)?;
+    let archive_path = registry::publish_archive(
+        registry_path,
+        &name,
+        &format_version(&manifest.package.version),
+        archive.path(),
+        serde_json::Map::new(),
+    )?;
+    record_upload(options, &registry_path.display().to_string(), "");
+    Ok(UploadOutput {
+        package_url: archive_path.display().to_string(),
+    })
+}
+
+/// Record the upload of the package at `options.package_path` to the registry at `registry_url` in
+/// the publish log. The upload already succeeded, so failing to record it is only a warning.
+fn record_upload(options: &UploadOptions, registry_url: &str, response_id: &str) {
+    let result = parse_move_manifest_from_file(&options.package_path).and_then(|manifest| {
+        let rev = git()?
+            .command()
+            .current_dir(&options.package_path)
+            .args(&["rev-parse", "HEAD"])
+            .output()?;
+        let entry = PublishLogEntry {
+            timestamp: Utc::now().to_rfc3339_opts(SecondsFormat::Secs, true),
+            package: manifest.package.name.to_string(),
+            version: format_version(&manifest.package.version),
+            rev: rev
+                .status
+                .success()
+                .then(|| String::from_utf8_lossy(&rev.stdout).trim().to_string()),
+            registry: registry_url.to_string(),
+            response_id: (!response_id.is_empty()).then(|| response_id.to_string()),
+        };
+        append_publish_log(&options.move_home, entry)
+    });
+    if let Err(err) = result {
+        json_errors::warning(format!(
+            "the upload was not recorded in the publish log: {:#}",
+            err
+        ));
+    }
+}
+
+/// The name of the package `name` in the registry, which ignores case: `name` in lowercase, with a
+/// warning if that differs from `name`. Fails if `name` is not a Move identifier, unless
+/// `allow_nonstandard_names` is set.
+fn registry_package_name(name: &str, allow_nonstandard_names: bool) -> anyhow::Result<String> {
+    check_uploaded_package_name(name, allow_nonstandard_names)?;
+    let registry_name = name.to_lowercase();
+    if registry_name != name {
+        json_errors::warning(format!(
+            "the package '{}' is listed in the registry as '{}'",
+            name, registry_name
+        ));
+    }
+    Ok(registry_name)
+}
+
+/// The release notes of `version` of the package at `package_path`: the section for `version` of
+/// `changelog`, or of the `CHANGELOG.md` of the package if `changelog` is not set. Without
+/// `changelog`, a package with no `CHANGELOG.md`, or whose `CHANGELOG.md` has no section for
+/// `version`, has no release notes.
+fn release_notes(
+    package_path: &Path,
+    changelog: Option<&Path>,
+    version: &str,
+    max_size: usize,
+) -> anyhow::Result<Option<String>> {
+    let changelog_path = match changelog {
+        Some(changelog) => {
+            if !changelog.is_file() {
+                bail!("The changelog '{}' does not exist", changelog.display())
             }
-            Err(_) => bail!("An unexpected error occurred. Please try again later"),
+            changelog.to_path_buf()
         }
-        Ok(())
+        None => {
+            let changelog_path = package_path.join(CHANGELOG_FILE);
+            if !changelog_path.is_file() {
+                return Ok(None);
+            }
+            changelog_path
+        }
+    };
+    let contents = read_utf8_file(&changelog_path)?;
+    let notes = match changelog_section(&contents, version) {
+        Some(notes) => notes,
+        None if changelog.is_some() => bail!(
+            "The changelog '{}' has no section for version {}",
+            changelog_path.display(),
+            version
+        ),
+        None => {
+            json_errors::warning(format!(
+                "'{}' has no section for version {}, so the upload has no release notes",
+                changelog_path.display(),
+                version
+            ));
+            return Ok(None);
+        }
+    };
+    if notes.len() > max_size {
+        bail!(
+            "The release notes of version {} in '{}' are {} bytes long, over the limit of {} \
+             bytes. Shorten them, or raise the limit with --max-release-notes-size",
+            version,
+            changelog_path.display(),
+            notes.len(),
+            max_size
+        )
+    }
+    Ok(Some(notes))
+}
+
+/// The section of the changelog `contents` for `version`, without its heading: the lines between
+/// its `## [<version>]` (or `## <version>`) heading and the next heading of the same level, or the
+/// link definitions ending the changelog. `None` if there is no such section, or it is empty.
+fn changelog_section(contents: &str, version: &str) -> Option<String> {
+    let is_heading = |line: &str| line.starts_with("## ");
+    let heading_version = |line: &str| {
+        let title = line.trim_start_matches('#').trim();
+        let title = title
+            .strip_prefix('[')
+            .and_then(|title| title.split_once(']'))
+            .map_or_else(
+                || title.split_whitespace().next().unwrap_or_default(),
+                |(version, _)| version,
+            );
+        title.trim_start_matches('v').to_string()
+    };
+    let section: Vec<_> = contents
+        .lines()
+        .skip_while(|line| !(is_heading(line) && heading_version(line) == version))
+        .skip(1)
+        .take_while(|line| !is_heading(line) && !is_link_definition(line))
+        .collect();
+    let section = section.join("\n").trim().to_string();
+    (!section.is_empty()).then(|| section)
+}
+
+/// Whether `line` defines a link, such as the `[1.0.0]: https://...` lines at the end of a
+/// keep-a-changelog changelog
+fn is_link_definition(line: &str) -> bool {
+    line.starts_with('[')
+        && line
+            .split_once("]:")
+            .map_or(false, |(label, _)| !label.contains(']'))
+}
+
+/// Warn and ask for confirmation if the name of the package at `package_path` differs from both the
+/// name of its directory and the name of the repository at `repo_url`, as listings whose names do
+/// not match their sources are confusing. Names are compared ignoring case, `-` and `_`.
+fn check_package_name(package_path: &Path, repo_url: &str, assume_yes: bool) -> anyhow::Result<()> {
+    let package_name = parse_move_manifest_from_file(package_path)?
+        .package
+        .name
+        .to_string();
+    let dir_name = package_path
+        .canonicalize()?
+        .file_name()
+        .map(|name| name.to_string_lossy().to_string())
+        .unwrap_or_default();
+    let repo_name = repo_url.rsplit('/').next().unwrap_or_default();
+    let normalize = |name: &str| name.to_lowercase().replace(&['-', '_'][..], "");
+    if [dir_name.as_str(), repo_name]
+        .iter()
+        .any(|name| normalize(name) == normalize(&package_name))
+    {
+        return Ok(());
+    }
+
+    json_errors::warning(format!(
+        "the package name matches neither its directory nor its repository\n    \
+         package name:    {}\n    \
+         directory name:  {}\n    \
+         repository name: {}",
+        package_name,
+        dir_name,
+        if repo_name.is_empty() { "-" } else { repo_name }
+    ));
+    prompt::confirm(
+        "Upload the package under this name anyway? Pass --no-name-check to skip this check.",
+        assume_yes,
+    )
+}
+
+/// The metadata in `request` as form fields, followed by the archive at `archive_path`. The
+/// SHA-256 digest of the archive is sent along so that the server can check that it was received
+/// intact.
+fn archive_upload(
+    request: &MoveyUploadRequest,
+    archive_path: &Path,
+) -> anyhow::Result<MultipartUpload> {
+    let mut fields = request.form_fields();
+    fields.push(("archive_digest".to_string(), file_digest(archive_path)?));
+    Ok(MultipartUpload {
+        fields,
+        file_field: "archive".to_string(),
+        file_name: "package.tar.gz".to_string(),
+        file_path: archive_path.to_path_buf(),
+    })
+}
+
+/// Fail if `name` is not a Move identifier, or only warn if `allow_nonstandard_names` is set
+fn check_uploaded_package_name(name: &str, allow_nonstandard_names: bool) -> anyhow::Result<()> {
+    if let Err(err) = manifest_parser::check_package_name(name) {
+        if !allow_nonstandard_names {
+            bail!(
+                "{} Pass --allow-nonstandard-names to upload legacy packages anyway",
+                err
+            )
+        }
+        json_errors::warning(err);
+    }
+    Ok(())
+}
+
+/// Write the files of the package at `package_path` committed to git into a temporary `.tar.gz`
+/// archive. The `tests/` directory is left out, as it is not part of the published sources.
+fn archive_package(package_path: &Path) -> anyhow::Result<NamedTempFile> {
+    let archive = tempfile::Builder::new()
+        .prefix("move-package")
+        .suffix(".tar.gz")
+        .tempfile()?;
+    let output = git()?
+        .command()
+        .current_dir(package_path)
+        .args(["archive", "--format=tar.gz", "-o"])
+        .arg(archive.path())
+        .args(["HEAD", "."])
+        .arg(format!(
+            ":(exclude){}",
+            SourcePackageLayout::Tests.location_str()
+        ))
+        .output()
+        .context("Unable to run git to archive the package")?;
+    if !output.status.success() {
+        bail!(
+            "Unable to archive the package: {}",
+            String::from_utf8_lossy(&output.stderr).trim()
+        )
+    }
+    Ok(archive)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const CHANGELOG: &str = "# Changelog
+
+## [Unreleased]
+
+- Nothing yet
+
+## [1.1.0] - 2022-09-01
+
+### Added
+
+- Coin::burn
+
+## [1.0.0] - 2022-06-01
+
+- First release
+
+[1.1.0]: https://github.com/move-language/coins/compare/v1.0.0...v1.1.0
+[1.0.0]: https://github.com/move-language/coins/releases/tag/v1.0.0
+";
+
+    #[test]
+    fn changelog_section_is_found_by_its_heading() {
+        assert_eq!(
+            changelog_section(CHANGELOG, "1.1.0").unwrap(),
+            "### Added\n\n- Coin::burn"
+        );
+        assert_eq!(
+            changelog_section(CHANGELOG, "1.0.0").unwrap(),
+            "- First release"
+        );
+        assert_eq!(changelog_section(CHANGELOG, "1.0"), None);
+        assert_eq!(
+            changelog_section("## v2.0.0 (2022-10-01)\n\nBreaking\n", "2.0.0").unwrap(),
+            "Breaking"
+        );
     }
 }
@@ -0,0 +1,63 @@
+// Copyright (c) The Move Contributors
+// SPDX-License-Identifier: Apache-2.0
+
+use crate::{
+    sandbox::cli::OutputFormat,
+    utils::publish_log::{filter_publish_log, publish_log_path, read_publish_log},
+};
+use anyhow::Result;
+use clap::*;
+use move_command_line_common::env::move_home;
+
+const EXAMPLES: &str = "EXAMPLES:
+    List every upload, oldest first:
+        move package publish-log
+
+    Print the last 5 uploads of the package Coins as JSON:
+        move package publish-log --package Coins --limit 5 --format json";
+
+/// List the packages uploaded with `move movey-upload`, oldest first, from the log kept in
+/// MOVE_HOME: when, which version from which commit, to which registry, and the identifier the
+/// registry answered with.
+#[derive(Parser)]
+#[clap(name = "publish-log", after_help = EXAMPLES)]
+pub struct PublishLog {
+    /// Only list the uploads of this package
+    #[clap(long = "package", value_name = "NAME")]
+    pub package: Option<String>,
+    /// Only list the last this many uploads
+    #[clap(long = "limit", value_name = "N")]
+    pub limit: Option<usize>,
+    /// Output format of the uploads
+    #[clap(long = "format", arg_enum, ignore_case = true, default_value = "text")]
+    pub format: OutputFormat,
+}
+
+impl PublishLog {
+    pub fn execute(self) -> Result<()> {
+        let move_home = move_home()?;
+        let log = read_publish_log(&move_home)?;
+        let entries = filter_publish_log(&log, self.package.as_deref(), self.limit);
+        match self.format {
+            OutputFormat::Json => println!("{}", serde_json::to_string_pretty(&entries)?),
+            OutputFormat::Text if entries.is_empty() => println!(
+                "No uploads recorded in '{}'",
+                publish_log_path(&move_home).display()
+            ),
+            OutputFormat::Text => {
+                for entry in entries {
+                    println!(
+                        "{}  {} {}  rev {}  {}  id {}",
+                        entry.timestamp,
+                        entry.package,
+                        entry.version,
+                        entry.rev.as_deref().unwrap_or("-"),
+                        entry.registry,
+                        entry.response_id.as_deref().unwrap_or("-")
+                    );
+                }
+            }
+        }
+        Ok(())
+    }
+}
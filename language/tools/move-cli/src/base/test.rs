@@ -1,26 +1,38 @@
 // Copyright (c) The Move Contributors
 // SPDX-License-Identifier: Apache-2.0
 
-use super::reroot_path;
-use crate::NativeFunctionRecord;
-use anyhow::Result;
+use super::{
+    build::{build_package, BuildOptions},
+    coverage::{coverage_dir, coverage_map_path, load_exemptions, report_coverage, CoverageFormat},
+    reroot_path,
+    workspace::{run_workspace, MemberOutcome, WorkspaceOptions},
+};
+use crate::{unresolved_natives, NativeFunctionRecord, UNRESOLVED_NATIVES_HINT};
+use anyhow::{bail, Context, Result};
 use clap::*;
-use move_command_line_common::files::{FileHash, MOVE_COVERAGE_MAP_EXTENSION};
+use colored::Colorize;
+use move_command_line_common::files::FileHash;
 use move_compiler::{
     diagnostics::{self, codes::Severity},
     shared::{NumberFormat, NumericalAddress},
     unit_test::{plan_builder::construct_test_plan, TestPlan},
     PASS_CFGIR,
 };
+use move_core_types::language_storage::ModuleId;
 use move_coverage::coverage_map::{output_map_to_file, CoverageMap};
-use move_package::{compilation::build_plan::BuildPlan, BuildConfig};
-use move_unit_test::UnitTestingConfig;
+use move_package::{
+    compilation::{build_plan::BuildPlan, package_layout::CompiledPackageLayout},
+    BuildConfig,
+};
+use move_unit_test::{test_reporter::TestResults, UnitTestingConfig};
+use serde::{Deserialize, Serialize};
 use std::{
     collections::HashMap,
+    ffi::OsString,
     fs,
     io::Write,
     path::{Path, PathBuf},
-    process::ExitStatus,
+    process::{Command, ExitStatus, Stdio},
 };
 // if windows
 #[cfg(target_family = "windows")]
@@ -32,9 +44,25 @@ use std::os::unix::prelude::ExitStatusExt;
 #[cfg(not(any(target_family = "windows", target_family = "unix")))]
 compile_error!("Unsupported OS, currently we only support windows and unix family");
 
+const EXAMPLES: &str = "EXAMPLES:
+    Run every unit test of the package:
+        move test
+
+    Run the tests of the `Coin` module, with statistics:
+        move test --filter Coin --statistics
+
+    Collect coverage information and write an LCOV tracefile:
+        move test --coverage --coverage-format lcov
+
+    Save the state of failing tests to `build/test-failures`:
+        move test --dump-state-on-failure
+
+    Run the tests of each module in its own process:
+        move test --isolate-modules";
+
 /// Run Move unit tests in this package.
 #[derive(Parser)]
-#[clap(name = "test")]
+#[clap(name = "test", after_help = EXAMPLES)]
 pub struct Test {
     /// Bound the number of instructions that can be executed by any one test.
     #[clap(name = "instructions", short = 'i', long = "instructions")]
@@ -60,11 +88,36 @@ pub struct Test {
     /// Show the storage state at the end of execution of a failing test
     #[clap(name = "global_state_on_error", short = 'g', long = "state_on_error")]
     pub report_storage_on_error: bool,
+    /// Write the global storage and the events of each failing test at the point of failure to
+    /// `build/test-failures/<test>/state.json`, with the values of resources and events decoded
+    #[clap(long = "dump-state-on-failure")]
+    pub dump_state_on_failure: bool,
+    /// Bound the size of each state dump, in bytes. Resources and events past the bound are left
+    /// out of the dump, and long vectors are always summarized.
+    #[clap(
+        long = "dump-state-size-limit",
+        default_value = "1048576",
+        requires = "dump-state-on-failure"
+    )]
+    pub dump_state_size_limit: usize,
 
     /// Ignore compiler's warning, and continue run tests
     #[clap(name = "ignore_compile_warnings", long = "ignore_compile_warnings")]
     pub ignore_compile_warnings: bool,
 
+    /// Time `std::timestamp` reports when each test starts, in microseconds. Tests can move it
+    /// with `std::unit_test::set_time`.
+    #[clap(
+        long = "initial-time",
+        value_name = "MICROSECONDS",
+        default_value = "0"
+    )]
+    pub initial_time: u64,
+    /// Block height `std::timestamp` reports when each test starts. Tests can move it with
+    /// `std::unit_test::set_block_height`.
+    #[clap(long = "initial-height", default_value = "0")]
+    pub initial_height: u64,
+
     /// Use the stackless bytecode interpreter to run the tests and cross check its results with
     /// the execution result from Move VM.
     #[clap(long = "stackless")]
@@ -72,9 +125,39 @@ pub struct Test {
     /// Verbose mode
     #[clap(long = "verbose")]
     pub verbose_mode: bool,
-    /// Collect coverage information for later use with the various `package coverage` subcommands
-    #[clap(long = "coverage")]
+    /// Collect coverage information into `build/coverage` and print a coverage summary after the
+    /// test results. The collected information can be inspected further with the various
+    /// `package coverage` subcommands.
+    #[clap(name = "coverage", long = "coverage")]
     pub compute_coverage: bool,
+    /// The coverage report to write in addition to the summary: `summary` (none), `lcov` or
+    /// `html`
+    #[clap(
+        long = "coverage-format",
+        possible_values = CoverageFormat::variants(),
+        ignore_case = true,
+        default_value = "summary",
+        requires = "coverage"
+    )]
+    pub coverage_format: CoverageFormat,
+    /// Run the tests of each module in a process of its own, so that a test crashing the process
+    /// or corrupting its state, e.g. in a native function, cannot affect the tests of the other
+    /// modules. The modules whose process crashed are reported apart from the failing tests.
+    #[clap(
+        long = "isolate-modules",
+        conflicts_with_all = &["list", "coverage", "dump-state-on-failure", "workspace"]
+    )]
+    pub isolate_modules: bool,
+    /// Run the tests of this module only, e.g. `0x2::M`, and print their outcome as JSON. Used by
+    /// `--isolate-modules` to run the tests of each module in a process of its own.
+    #[clap(long = "run-test-module", hide = true)]
+    pub run_test_module: Option<String>,
+    #[clap(flatten)]
+    pub workspace: WorkspaceOptions,
+    /// The arguments the CLI was run with, which the processes started by `--isolate-modules` are
+    /// run with as well. The arguments of the current process if empty.
+    #[clap(skip)]
+    pub argv: Vec<OsString>,
 
     /// Use the EVM-based execution backend.
     /// Does not work with --stackless.
@@ -90,7 +173,6 @@ impl Test {
         config: BuildConfig,
         natives: Vec<NativeFunctionRecord>,
     ) -> anyhow::Result<()> {
-        let rerooted_path = reroot_path(path)?;
         let Self {
             instruction_execution_bound,
             filter,
@@ -98,10 +180,19 @@ impl Test {
             num_threads,
             report_statistics,
             report_storage_on_error,
+            dump_state_on_failure,
+            dump_state_size_limit,
             ignore_compile_warnings,
+            initial_time,
+            initial_height,
             check_stackless_vm,
             verbose_mode,
             compute_coverage,
+            coverage_format,
+            isolate_modules,
+            run_test_module,
+            workspace,
+            argv,
             #[cfg(feature = "evm-backend")]
             evm,
         } = self;
@@ -112,31 +203,366 @@ impl Test {
             num_threads,
             report_statistics,
             report_storage_on_error,
+            dump_state_on_failure,
+            dump_state_size_limit,
             check_stackless_vm,
             verbose: verbose_mode,
             ignore_compile_warnings,
+            initial_time,
+            initial_block_height: initial_height,
             #[cfg(feature = "evm-backend")]
             evm,
 
             ..UnitTestingConfig::default_with_bound(None)
         };
-        let result = run_move_unit_tests(
-            &rerooted_path,
-            config,
+        if workspace.workspace {
+            return run_workspace(path.as_deref(), &workspace, |member| {
+                let options = TestOptions {
+                    package_path: member.to_path_buf(),
+                    build_config: config.clone(),
+                    unit_test_config: unit_test_config.clone(),
+                    compute_coverage,
+                    coverage_format,
+                };
+                test_member(options, natives.clone())
+            });
+        }
+        // the processes started by `--isolate-modules` resolve relative paths from here
+        let working_dir = std::env::current_dir()?;
+        let options = TestOptions {
+            package_path: reroot_path(path)?,
+            build_config: config,
             unit_test_config,
-            natives,
             compute_coverage,
-            &mut std::io::stdout(),
-        )?;
+            coverage_format,
+        };
+        if let Some(module) = run_test_module {
+            let output =
+                test_package_module(options, natives, Some(&module), &mut std::io::sink())?;
+            println!("{}", serde_json::to_string(&output.tests)?);
+            return Ok(());
+        }
+        let output = if isolate_modules {
+            let argv = if argv.is_empty() {
+                std::env::args_os().collect()
+            } else {
+                argv
+            };
+            test_modules_in_processes(
+                options,
+                natives,
+                &argv,
+                &working_dir,
+                &mut std::io::stdout(),
+            )?
+        } else {
+            test_package(options, natives, &mut std::io::stdout())?
+        };
 
         // Return a non-zero exit code if any test failed
-        if let UnitTestResult::Failure = result {
+        if !output.all_passed() {
             std::process::exit(1)
         }
         Ok(())
     }
 }
 
+/// Run the tests of a workspace member for `move test --workspace`
+fn test_member(
+    options: TestOptions,
+    natives: Vec<NativeFunctionRecord>,
+) -> anyhow::Result<MemberOutcome> {
+    // The test runner exits the process on compilation errors, so they are caught by building the
+    // member in test mode first
+    let build_options = BuildOptions {
+        package_path: options.package_path.clone(),
+        build_config: BuildConfig {
+            test_mode: true,
+            dev_mode: true,
+            ..options.build_config.clone()
+        },
+    };
+    let build = build_package(build_options, &mut std::io::stderr())?;
+    if build.package.is_none() {
+        build.print_diagnostics();
+        return Ok(MemberOutcome::FailedToCompile);
+    }
+
+    let output = test_package(options, natives, &mut std::io::stdout())?;
+    let failed = output.failed().count();
+    Ok(if failed == 0 {
+        MemberOutcome::Ok
+    } else {
+        MemberOutcome::TestsFailed {
+            failed,
+            total: output.tests.len(),
+        }
+    })
+}
+
+/// Options for running the unit tests of a package with `test_package`
+pub struct TestOptions {
+    /// Path to the root directory of the package
+    pub package_path: PathBuf,
+    pub build_config: BuildConfig,
+    pub unit_test_config: UnitTestingConfig,
+    /// Collect coverage information, and print a coverage summary after the test results
+    pub compute_coverage: bool,
+    /// The coverage report to write in addition to the summary
+    pub coverage_format: CoverageFormat,
+}
+
+/// The outcome of a single unit test
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct TestOutcome {
+    /// Fully qualified name of the test, e.g. `0x1::M::test_f`
+    pub name: String,
+    pub instructions_executed: u64,
+    /// Why the test failed, as rendered in the test report. `None` if the test passed.
+    pub failure: Option<String>,
+}
+
+/// A module whose tests crashed the process running them with `move test --isolate-modules`
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CrashedModule {
+    /// Name of the module, e.g. `0x1::M`
+    pub name: String,
+    /// How the process ended, e.g. `signal: 6 (SIGABRT)`
+    pub status: String,
+}
+
+/// The results of running the unit tests of a package with `test_package`
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct TestOutput {
+    /// The tests that were run, ordered by module. Empty if the tests were only listed.
+    pub tests: Vec<TestOutcome>,
+    /// The modules whose tests crashed the process running them, in which case their outcome is
+    /// unknown. Only tests run with `--isolate-modules` can crash without ending the whole run.
+    pub crashed_modules: Vec<CrashedModule>,
+}
+
+impl TestOutput {
+    pub fn passed(&self) -> impl Iterator<Item = &TestOutcome> {
+        self.tests.iter().filter(|test| test.failure.is_none())
+    }
+
+    pub fn failed(&self) -> impl Iterator<Item = &TestOutcome> {
+        self.tests.iter().filter(|test| test.failure.is_some())
+    }
+
+    pub fn all_passed(&self) -> bool {
+        self.failed().next().is_none() && self.crashed_modules.is_empty()
+    }
+}
+
+impl From<&TestResults> for TestOutput {
+    fn from(results: &TestResults) -> Self {
+        let name = |module_id: &ModuleId, function: &str| {
+            format!("{}::{}", module_name(module_id), function)
+        };
+        let passed = results
+            .passed_tests()
+            .iter()
+            .flat_map(|(module_id, tests)| {
+                tests.iter().map(move |info| TestOutcome {
+                    name: name(module_id, &info.function_ident),
+                    instructions_executed: info.instructions_executed,
+                    failure: None,
+                })
+            });
+        let failed = results
+            .failed_tests()
+            .iter()
+            .flat_map(|(module_id, failures)| {
+                failures.iter().map(move |failure| TestOutcome {
+                    name: name(module_id, &failure.test_run_info.function_ident),
+                    instructions_executed: failure.test_run_info.instructions_executed,
+                    failure: Some(failure.render_error(results.test_plan())),
+                })
+            });
+        let mut tests: Vec<_> = passed.chain(failed).collect();
+        tests.sort_by(|t1, t2| t1.name.cmp(&t2.name));
+        TestOutput {
+            tests,
+            crashed_modules: vec![],
+        }
+    }
+}
+
+/// The name of a module as used in the names of tests, e.g. `0x1::M`
+fn module_name(module_id: &ModuleId) -> String {
+    format!(
+        "0x{}::{}",
+        module_id.address().short_str_lossless(),
+        module_id.name()
+    )
+}
+
+/// Run the unit tests of the package at `options.package_path` and return the outcome of each
+/// test. The test report is written to `writer`.
+pub fn test_package<W: Write + Send>(
+    options: TestOptions,
+    natives: Vec<NativeFunctionRecord>,
+    writer: &mut W,
+) -> Result<TestOutput> {
+    test_package_module(options, natives, None, writer)
+}
+
+/// Run the unit tests of the package, or only those of `module` if set
+fn test_package_module<W: Write + Send>(
+    options: TestOptions,
+    natives: Vec<NativeFunctionRecord>,
+    module: Option<&str>,
+    writer: &mut W,
+) -> Result<TestOutput> {
+    let TestOptions {
+        package_path,
+        build_config,
+        unit_test_config,
+        compute_coverage,
+        coverage_format,
+    } = options;
+    let results = collect_move_unit_tests(
+        &package_path,
+        build_config,
+        unit_test_config,
+        natives,
+        module,
+        compute_coverage.then(|| coverage_format),
+        writer,
+    )?;
+    Ok(results.as_ref().map(TestOutput::from).unwrap_or_default())
+}
+
+/// Run the tests of each module of the package in a process of its own, which runs the CLI again
+/// with `argv` and `--run-test-module` in `working_dir`, and report their outcome to `writer`
+fn test_modules_in_processes<W: Write>(
+    options: TestOptions,
+    natives: Vec<NativeFunctionRecord>,
+    argv: &[OsString],
+    working_dir: &Path,
+    writer: &mut W,
+) -> Result<TestOutput> {
+    let TestOptions {
+        package_path,
+        mut build_config,
+        mut unit_test_config,
+        ..
+    } = options;
+    build_config.test_mode = true;
+    build_config.dev_mode = true;
+    let test_plan = build_test_plan(
+        &package_path,
+        build_config,
+        &mut unit_test_config,
+        &natives,
+        writer,
+    )?;
+
+    let program = std::env::current_exe()?;
+    let mut output = TestOutput::default();
+    writeln!(
+        writer,
+        "Running Move unit tests, each module in a process of its own"
+    )?;
+    for module_id in test_plan.module_tests.keys() {
+        let module = module_name(module_id);
+        let mut command = Command::new(&program);
+        command
+            .current_dir(working_dir)
+            .args(argv.iter().skip(1))
+            .arg("--run-test-module")
+            .arg(&module);
+        // the warnings were reported when planning the tests
+        if !unit_test_config.ignore_compile_warnings {
+            command.arg("--ignore_compile_warnings");
+        }
+        let child = command
+            .stderr(Stdio::inherit())
+            .output()
+            .with_context(|| format!("Unable to run the tests of module {}", module))?;
+        let tests = if child.status.success() {
+            serde_json::from_slice::<Vec<TestOutcome>>(&child.stdout).ok()
+        } else {
+            None
+        };
+        match tests {
+            Some(tests) => {
+                for test in &tests {
+                    match test.failure {
+                        None => writeln!(
+                            writer,
+                            "[ {}    ] {}",
+                            "PASS".bold().bright_green(),
+                            test.name
+                        )?,
+                        Some(_) => writeln!(
+                            writer,
+                            "[ {}    ] {}",
+                            "FAIL".bold().bright_red(),
+                            test.name
+                        )?,
+                    }
+                }
+                output.tests.extend(tests);
+            }
+            None => {
+                writeln!(
+                    writer,
+                    "[ {}   ] {}: {}",
+                    "CRASH".bold().bright_red(),
+                    module,
+                    child.status
+                )?;
+                output.crashed_modules.push(CrashedModule {
+                    name: module,
+                    status: child.status.to_string(),
+                });
+            }
+        }
+    }
+
+    let failed: Vec<_> = output.failed().collect();
+    if !failed.is_empty() {
+        writeln!(writer, "\nTest failures:\n")?;
+        for test in &failed {
+            writeln!(writer, "┌── {} ──────", test.name.bold())?;
+            writeln!(
+                writer,
+                "│ {}",
+                test.failure
+                    .as_deref()
+                    .unwrap_or_default()
+                    .replace('\n', "\n│ ")
+            )?;
+            writeln!(writer, "└──────────────────\n")?;
+        }
+    }
+    let passed = output.passed().count();
+    write!(
+        writer,
+        "Test result: {}. Total tests: {}; passed: {}; failed: {}",
+        if output.all_passed() {
+            "OK".bold().bright_green()
+        } else {
+            "FAILED".bold().bright_red()
+        },
+        output.tests.len(),
+        passed,
+        failed.len()
+    )?;
+    if output.crashed_modules.is_empty() {
+        writeln!(writer)?;
+    } else {
+        writeln!(
+            writer,
+            "; crashed modules: {}",
+            output.crashed_modules.len()
+        )?;
+    }
+    Ok(output)
+}
+
 /// Encapsulates the possible returned states when running unit tests on a move package.
 #[derive(PartialEq, Eq, Debug)]
 pub enum UnitTestResult {
@@ -146,15 +572,125 @@ pub enum UnitTestResult {
 
 pub fn run_move_unit_tests<W: Write + Send>(
     pkg_path: &Path,
-    mut build_config: move_package::BuildConfig,
-    mut unit_test_config: UnitTestingConfig,
+    build_config: move_package::BuildConfig,
+    unit_test_config: UnitTestingConfig,
     natives: Vec<NativeFunctionRecord>,
     compute_coverage: bool,
     writer: &mut W,
 ) -> Result<UnitTestResult> {
-    let mut test_plan = None;
+    let results = collect_move_unit_tests(
+        pkg_path,
+        build_config,
+        unit_test_config,
+        natives,
+        None,
+        compute_coverage.then(|| CoverageFormat::Summary),
+        writer,
+    )?;
+    if results.map_or(true, |results| results.all_tests_passed()) {
+        Ok(UnitTestResult::Success)
+    } else {
+        Ok(UnitTestResult::Failure)
+    }
+}
+
+/// Run the unit tests of the package at `pkg_path`, or only those of `module` if set, reporting to
+/// `writer`. If `coverage` is set, coverage is collected and reported after the test results.
+/// Returns `None` if the tests were only listed.
+fn collect_move_unit_tests<W: Write + Send>(
+    pkg_path: &Path,
+    mut build_config: move_package::BuildConfig,
+    mut unit_test_config: UnitTestingConfig,
+    natives: Vec<NativeFunctionRecord>,
+    module: Option<&str>,
+    coverage: Option<CoverageFormat>,
+    writer: &mut W,
+) -> Result<Option<TestResults>> {
+    let compute_coverage = coverage.is_some();
     build_config.test_mode = true;
     build_config.dev_mode = true;
+    let coverage_dir = coverage_dir(pkg_path, &build_config);
+    if unit_test_config.dump_state_on_failure && unit_test_config.dump_state_dir.is_none() {
+        unit_test_config.dump_state_dir = Some(test_failures_dir(pkg_path, &build_config));
+    }
+    // Coverage is reported against the package without its test code, as `move coverage` does
+    let coverage_build_config = BuildConfig {
+        test_mode: false,
+        ..build_config.clone()
+    };
+
+    let mut test_plan = build_test_plan(
+        pkg_path,
+        build_config,
+        &mut unit_test_config,
+        &natives,
+        writer,
+    )?;
+    if let Some(module) = module {
+        test_plan
+            .module_tests
+            .retain(|module_id, _| module_name(module_id) == module);
+        if test_plan.module_tests.is_empty() {
+            bail!("The package has no tests in module {}", module);
+        }
+    }
+    let no_tests = test_plan.module_tests.is_empty();
+
+    let trace_path = coverage_dir.join(".trace");
+    let coverage_map_path = coverage_map_path(&coverage_dir);
+    let cleanup_trace = || {
+        if compute_coverage && trace_path.exists() {
+            std::fs::remove_file(&trace_path).unwrap();
+        }
+    };
+
+    cleanup_trace();
+
+    // If we need to compute test coverage set the VM tracking environment variable since we will
+    // need this trace to construct the coverage information.
+    if compute_coverage {
+        fs::create_dir_all(&coverage_dir)?;
+        std::env::set_var("MOVE_VM_TRACE", &trace_path);
+    }
+
+    // Run the tests. If any of the tests fail, then we don't produce a coverage report, so cleanup
+    // the trace files.
+    let (writer, results) = unit_test_config
+        .run_and_collect_unit_tests(test_plan, Some(natives), writer)
+        .unwrap();
+    if !results.as_ref().map_or(true, TestResults::all_tests_passed) {
+        cleanup_trace();
+        return Ok(results);
+    }
+
+    // Compute the coverage map. This will be used by other commands after this.
+    if let (Some(format), false) = (coverage, no_tests) {
+        let coverage_map = CoverageMap::from_trace_file(trace_path);
+        output_map_to_file(&coverage_map_path, &coverage_map).unwrap();
+        let package = coverage_build_config.compile_package(pkg_path, &mut Vec::new())?;
+        let exemptions = load_exemptions(pkg_path, &package)?;
+        report_coverage(
+            &package,
+            &coverage_map,
+            &exemptions,
+            &coverage_dir,
+            format,
+            writer,
+        )?;
+    }
+    Ok(results)
+}
+
+/// Compile the package at `pkg_path` in test mode, reporting to `writer`, and plan its unit tests.
+/// Sets the named addresses of `unit_test_config` to those of the package.
+pub(crate) fn build_test_plan<W: Write>(
+    pkg_path: &Path,
+    build_config: BuildConfig,
+    unit_test_config: &mut UnitTestingConfig,
+    natives: &[NativeFunctionRecord],
+    writer: &mut W,
+) -> Result<TestPlan> {
+    let mut test_plan = None;
 
     // Build the resolution graph
     let resolution_graph = build_config.resolution_graph_for_package(pkg_path)?;
@@ -195,9 +731,12 @@ pub fn run_move_unit_tests<W: Write + Send>(
     // then save it, before resuming the rest of the compilation and returning the results and
     // control back to the Move package system.
     build_plan.compile_with_driver(writer, |compiler| {
+        let remaps = compiler.flags().path_prefix_remaps().to_vec();
         let (files, comments_and_compiler_res) = compiler.run::<PASS_CFGIR>().unwrap();
+        // the diagnostics are shown with remapped file names, the test plan keeps the local ones
+        let shown_files = diagnostics::remap_file_names(&files, &remaps);
         let (_, compiler) =
-            diagnostics::unwrap_or_report_diagnostics(&files, comments_and_compiler_res);
+            diagnostics::unwrap_or_report_diagnostics(&shown_files, comments_and_compiler_res);
         let (mut compiler, cfgir) = compiler.into_ast();
         let compilation_env = compiler.compilation_env();
         let built_test_plan = construct_test_plan(compilation_env, Some(root_package), &cfgir);
@@ -208,57 +747,44 @@ pub fn run_move_unit_tests<W: Write + Send>(
                 Severity::Warning
             },
         ) {
-            diagnostics::report_diagnostics(&files, diags);
+            diagnostics::report_diagnostics(&shown_files, diags);
         }
 
         let compilation_result = compiler.at_cfgir(cfgir).build();
 
-        let (units, _) = diagnostics::unwrap_or_report_diagnostics(&files, compilation_result);
+        let (units, _) =
+            diagnostics::unwrap_or_report_diagnostics(&shown_files, compilation_result);
         test_plan = Some((built_test_plan, files.clone(), units.clone()));
         Ok((files, units))
     })?;
 
     let (test_plan, mut files, units) = test_plan.unwrap();
     files.extend(dep_file_map);
-    let test_plan = test_plan.unwrap();
-    let no_tests = test_plan.is_empty();
-    let test_plan = TestPlan::new(test_plan, files, units);
-
-    let trace_path = pkg_path.join(".trace");
-    let coverage_map_path = pkg_path
-        .join(".coverage_map")
-        .with_extension(MOVE_COVERAGE_MAP_EXTENSION);
-    let cleanup_trace = || {
-        if compute_coverage && trace_path.exists() {
-            std::fs::remove_file(&trace_path).unwrap();
-        }
-    };
-
-    cleanup_trace();
-
-    // If we need to compute test coverage set the VM tracking environment variable since we will
-    // need this trace to construct the coverage information.
-    if compute_coverage {
-        std::env::set_var("MOVE_VM_TRACE", &trace_path);
-    }
+    let test_plan = TestPlan::new(test_plan.unwrap(), files, units);
 
-    // Run the tests. If any of the tests fail, then we don't produce a coverage report, so cleanup
-    // the trace files.
-    if !unit_test_config
-        .run_and_report_unit_tests(test_plan, Some(natives), writer)
-        .unwrap()
-        .1
-    {
-        cleanup_trace();
-        return Ok(UnitTestResult::Failure);
+    // Without this check, the tests of modules declaring unknown natives fail to even load them
+    let unresolved = unresolved_natives(
+        test_plan.module_info.values().map(|info| &info.module),
+        natives,
+    );
+    if !unresolved.is_empty() {
+        bail!(
+            "No implementation is registered for the native function(s) {}. {}",
+            unresolved.join(", "),
+            UNRESOLVED_NATIVES_HINT
+        );
     }
+    Ok(test_plan)
+}
 
-    // Compute the coverage map. This will be used by other commands after this.
-    if compute_coverage && !no_tests {
-        let coverage_map = CoverageMap::from_trace_file(trace_path);
-        output_map_to_file(&coverage_map_path, &coverage_map).unwrap();
-    }
-    Ok(UnitTestResult::Success)
+/// The directory `move test --dump-state-on-failure` writes the state of failing tests to
+pub fn test_failures_dir(package_path: &Path, config: &BuildConfig) -> PathBuf {
+    config
+        .install_dir
+        .clone()
+        .unwrap_or_else(|| package_path.to_path_buf())
+        .join(CompiledPackageLayout::Root.path())
+        .join("test-failures")
 }
 
 impl From<UnitTestResult> for ExitStatus {
@@ -0,0 +1,47 @@
+// Copyright (c) The Move Contributors
+// SPDX-License-Identifier: Apache-2.0
+
+use crate::utils::toolchain::{cli_version, ToolchainRequirement, TOOLCHAIN_FILE};
+use anyhow::Result;
+use clap::*;
+use move_package::source_package::parsed_manifest::format_version;
+use std::path::{Path, PathBuf};
+
+/// Print the version of this CLI, and the version the project requires with the file requiring it.
+#[derive(Parser)]
+#[clap(name = "show")]
+pub struct Show;
+
+impl Show {
+    pub fn execute(self, path: Option<PathBuf>) -> Result<()> {
+        let current = cli_version();
+        println!("Active version: move-cli {}", format_version(&current));
+        let dir = path.unwrap_or_else(|| PathBuf::from(".")).canonicalize()?;
+        let dir = if dir.is_file() {
+            dir.parent().map(Path::to_path_buf).unwrap_or(dir)
+        } else {
+            dir
+        };
+        match ToolchainRequirement::find(&dir)? {
+            Some(requirement) => {
+                println!(
+                    "Required version: {}{}, set in '{}'",
+                    requirement.version,
+                    if requirement.strict { " (strict)" } else { "" },
+                    requirement.source.display()
+                );
+                let status = if requirement.version.matches(&current) {
+                    "satisfied"
+                } else {
+                    "not satisfied"
+                };
+                println!("Status: {}", status);
+            }
+            None => println!(
+                "Required version: none (no {} or [toolchain] section in Move.toml found)",
+                TOOLCHAIN_FILE
+            ),
+        }
+        Ok(())
+    }
+}
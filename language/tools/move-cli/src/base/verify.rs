@@ -0,0 +1,466 @@
+// Copyright (c) The Move Contributors
+// SPDX-License-Identifier: Apache-2.0
+
+use super::{
+    build::{build_package, BuildOptions},
+    coverage::{
+        coverage_dir, coverage_map_path, coverage_percentage, instruction_coverage,
+        load_exemptions, CoverageFormat,
+    },
+    prove::Prove,
+    reroot_path,
+    test::{test_package, TestOptions},
+};
+use crate::{sandbox::cli::OutputFormat, NativeFunctionRecord};
+use anyhow::{bail, Result};
+use clap::*;
+use codespan_reporting::diagnostic::Severity;
+use colored::Colorize;
+use move_binary_format::access::{ModuleAccess, ScriptAccess};
+use move_compiler::compiled_unit::{CompiledUnit, NamedCompiledModule, NamedCompiledScript};
+use move_core_types::language_storage::ModuleId;
+use move_coverage::coverage_map::CoverageMap;
+use move_package::{
+    compilation::compiled_package::CompiledPackage, resolution::resolution_graph::ResolvedGraph,
+    source_package::layout::SourcePackageLayout, BuildConfig,
+};
+use move_unit_test::UnitTestingConfig;
+use serde::Serialize;
+use std::{collections::BTreeSet, fmt, path::PathBuf, time::Instant};
+
+const EXAMPLES: &str = "EXAMPLES:
+    Run the checks of the package containing the current directory:
+        move package verify
+
+    Also prove the package, and require its unit tests to cover 80% of its instructions:
+        move package verify --with-prover --with-coverage 80
+
+    Run every check even if one fails, and print the report as JSON:
+        move package verify --keep-going --format json";
+
+/// Run the checks a package is expected to pass before it is committed, in order: resolve the
+/// manifest and the dependencies, build the package with warnings denied, run the unit tests, run
+/// the lints, and optionally prove the package and check the test coverage. A report of the status
+/// and duration of each stage is printed at the end, and the command fails if any stage failed.
+#[derive(Parser)]
+#[clap(name = "verify", after_help = EXAMPLES)]
+pub struct Verify {
+    /// Also run the Move Prover on the package, after the lints
+    #[clap(long = "with-prover")]
+    pub with_prover: bool,
+    /// Also check that the unit tests execute at least this percentage of the instructions of the
+    /// modules of the package, less those exempted in its `coverage-exemptions.toml`
+    #[clap(long = "with-coverage", value_name = "PERCENT")]
+    pub with_coverage: Option<f64>,
+    /// Keep going after a stage fails, and exit with an error at the end. By default, the first
+    /// stage that fails stops the run.
+    #[clap(long = "keep-going")]
+    pub keep_going: bool,
+    /// Output format of the report
+    #[clap(long = "format", arg_enum, ignore_case = true, default_value = "text")]
+    pub format: OutputFormat,
+}
+
+/// A stage of `move package verify`, in the order the stages run
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Stage {
+    /// Parse the manifest and resolve the dependencies, checking the lockfile
+    Manifest,
+    /// Build the package, failing on warnings
+    Build,
+    /// Run the unit tests
+    Test,
+    /// Check the compiled package for unused dependencies
+    Lint,
+    /// Run the Move Prover, with `--with-prover`
+    Prove,
+    /// Check the coverage of the unit tests, with `--with-coverage`
+    Coverage,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum StageStatus {
+    Passed,
+    Failed,
+    /// The stage did not run because an earlier stage failed
+    Skipped,
+}
+
+/// The outcome of one stage of `move package verify`
+#[derive(Debug, Clone, Serialize)]
+pub struct StageReport {
+    pub stage: Stage,
+    pub status: StageStatus,
+    /// How long the stage took, in milliseconds. 0 for skipped stages.
+    pub duration_ms: u64,
+    /// Why the stage failed or was skipped
+    pub message: Option<String>,
+}
+
+/// The report printed by `move package verify`
+#[derive(Debug, Clone, Serialize)]
+pub struct VerifyReport {
+    pub package: String,
+    pub passed: bool,
+    /// The stages that were requested, in the order they run
+    pub stages: Vec<StageReport>,
+}
+
+impl Verify {
+    pub fn execute(
+        self,
+        path: Option<PathBuf>,
+        mut config: BuildConfig,
+        natives: Vec<NativeFunctionRecord>,
+    ) -> Result<()> {
+        if let Some(threshold) = self.with_coverage {
+            if !(0.0..=100.0).contains(&threshold) {
+                bail!(
+                    "Invalid coverage threshold {} -- expected a percentage between 0 and 100",
+                    threshold
+                )
+            }
+        }
+        let rerooted_path = reroot_path(path)?;
+        config.lock_file = Some(rerooted_path.join(SourcePackageLayout::Lockfile.path()));
+
+        let mut stages = vec![Stage::Manifest, Stage::Build, Stage::Test, Stage::Lint];
+        if self.with_prover {
+            stages.push(Stage::Prove);
+        }
+        if self.with_coverage.is_some() {
+            stages.push(Stage::Coverage);
+        }
+
+        let mut verifier = Verifier {
+            path: rerooted_path,
+            config,
+            natives,
+            coverage_threshold: self.with_coverage,
+            graph: None,
+            package: None,
+            tests_passed: None,
+        };
+        let mut reports: Vec<StageReport> = vec![];
+        for stage in stages {
+            let failed = reports.iter().any(|r| r.status == StageStatus::Failed);
+            let skipped_because = if failed && !self.keep_going {
+                Some("an earlier stage failed".to_string())
+            } else {
+                verifier.missing_requirement(stage)
+            };
+            let report = match skipped_because {
+                Some(reason) => StageReport {
+                    stage,
+                    status: StageStatus::Skipped,
+                    duration_ms: 0,
+                    message: Some(reason),
+                },
+                None => {
+                    eprintln!("{} {}", "VERIFYING".bold().green(), stage);
+                    let start = Instant::now();
+                    let result = verifier.run(stage);
+                    let duration_ms = start.elapsed().as_millis() as u64;
+                    match result {
+                        Ok(()) => StageReport {
+                            stage,
+                            status: StageStatus::Passed,
+                            duration_ms,
+                            message: None,
+                        },
+                        Err(err) => {
+                            eprintln!("Error: {:#}", err);
+                            StageReport {
+                                stage,
+                                status: StageStatus::Failed,
+                                duration_ms,
+                                message: Some(err.to_string()),
+                            }
+                        }
+                    }
+                }
+            };
+            reports.push(report);
+        }
+
+        let report = VerifyReport {
+            package: verifier.package_name(),
+            passed: reports.iter().all(|r| r.status == StageStatus::Passed),
+            stages: reports,
+        };
+        match self.format {
+            OutputFormat::Text => print_report(&report),
+            OutputFormat::Json => println!("{}", serde_json::to_string_pretty(&report)?),
+        }
+
+        let failed: Vec<_> = report
+            .stages
+            .iter()
+            .filter(|r| r.status == StageStatus::Failed)
+            .map(|r| r.stage.to_string())
+            .collect();
+        if !failed.is_empty() {
+            if !self.keep_going
+                && report
+                    .stages
+                    .iter()
+                    .any(|r| r.status == StageStatus::Skipped)
+            {
+                bail!(
+                    "Verification failed at the {} stage. Pass --keep-going to run the remaining \
+                     stages regardless of failures",
+                    failed.join(", ")
+                )
+            }
+            bail!("Verification failed at the {} stage(s)", failed.join(", "))
+        }
+        Ok(())
+    }
+}
+
+/// The state shared by the stages, so that each stage reuses what the earlier ones produced
+struct Verifier {
+    path: PathBuf,
+    config: BuildConfig,
+    natives: Vec<NativeFunctionRecord>,
+    coverage_threshold: Option<f64>,
+    /// Set by the manifest stage
+    graph: Option<ResolvedGraph>,
+    /// Set by the build stage if the package compiled
+    package: Option<CompiledPackage>,
+    /// Set by the test stage to the number of tests that ran, if they all passed
+    tests_passed: Option<usize>,
+}
+
+impl Verifier {
+    /// Why `stage` cannot run, if an earlier stage did not produce what it needs
+    fn missing_requirement(&self, stage: Stage) -> Option<String> {
+        match stage {
+            Stage::Manifest => None,
+            Stage::Build | Stage::Test | Stage::Prove if self.graph.is_none() => {
+                Some("the manifest stage failed".to_string())
+            }
+            Stage::Lint if self.package.is_none() => Some("the package did not build".to_string()),
+            Stage::Coverage if self.tests_passed.is_none() => {
+                Some("the tests did not pass".to_string())
+            }
+            _ => None,
+        }
+    }
+
+    fn run(&mut self, stage: Stage) -> Result<()> {
+        match stage {
+            Stage::Manifest => self.check_manifest(),
+            Stage::Build => self.build(),
+            Stage::Test => self.test(),
+            Stage::Lint => self.lint(),
+            Stage::Prove => self.prove(),
+            Stage::Coverage => self.check_coverage(),
+        }
+    }
+
+    fn package_name(&self) -> String {
+        match &self.graph {
+            Some(graph) => graph.root_package.package.name.to_string(),
+            None => self.path.display().to_string(),
+        }
+    }
+
+    fn check_manifest(&mut self) -> Result<()> {
+        self.graph = Some(
+            self.config
+                .clone()
+                .resolution_graph_for_package(&self.path)?,
+        );
+        Ok(())
+    }
+
+    fn build(&mut self) -> Result<()> {
+        let options = BuildOptions {
+            package_path: self.path.clone(),
+            build_config: self.config.clone(),
+        };
+        let output = build_package(options, &mut std::io::stderr())?;
+        output.print_diagnostics();
+        let warnings = output
+            .diagnostics
+            .iter()
+            .filter(|diag| diag.severity == Severity::Warning)
+            .count();
+        let package = match output.package {
+            Some(package) => package,
+            None => bail!("The package failed to compile"),
+        };
+        if warnings > 0 {
+            bail!("The package compiled with {} warning(s)", warnings)
+        }
+        self.package = Some(package);
+        Ok(())
+    }
+
+    fn test(&mut self) -> Result<()> {
+        // The test runner exits the process on compilation errors and warnings, so they are
+        // caught by building the package in test mode first
+        let build_options = BuildOptions {
+            package_path: self.path.clone(),
+            build_config: BuildConfig {
+                test_mode: true,
+                dev_mode: true,
+                ..self.config.clone()
+            },
+        };
+        let build = build_package(build_options, &mut std::io::stderr())?;
+        if build.package.is_none() || !build.diagnostics.is_empty() {
+            build.print_diagnostics();
+            bail!("The package failed to compile without warnings in test mode")
+        }
+
+        let options = TestOptions {
+            package_path: self.path.clone(),
+            build_config: self.config.clone(),
+            unit_test_config: UnitTestingConfig::default_with_bound(None),
+            compute_coverage: self.coverage_threshold.is_some(),
+            coverage_format: CoverageFormat::Summary,
+        };
+        let output = test_package(options, self.natives.clone(), &mut std::io::stderr())?;
+        let failed = output.failed().count();
+        if failed > 0 {
+            bail!("{} of {} tests failed", failed, output.tests.len())
+        }
+        self.tests_passed = Some(output.tests.len());
+        Ok(())
+    }
+
+    /// Fail if a package declared in `[dependencies]` provides modules but none of them is used by
+    /// the modules and scripts of the package
+    fn lint(&mut self) -> Result<()> {
+        let graph = self.graph.as_ref().unwrap();
+        let package = self.package.as_ref().unwrap();
+        let used: BTreeSet<ModuleId> = package
+            .root_compiled_units
+            .iter()
+            .flat_map(|unit| match &unit.unit {
+                CompiledUnit::Module(NamedCompiledModule { module, .. }) => {
+                    module.immediate_dependencies()
+                }
+                CompiledUnit::Script(NamedCompiledScript { script, .. }) => {
+                    script.immediate_dependencies()
+                }
+            })
+            .collect();
+        let mut lints = vec![];
+        for dep in graph.root_package.dependencies.keys() {
+            let modules: Vec<ModuleId> = package
+                .deps_compiled_units
+                .iter()
+                .filter(|(name, _)| name == dep)
+                .filter_map(|(_, unit)| match &unit.unit {
+                    CompiledUnit::Module(NamedCompiledModule { module, .. }) => {
+                        Some(module.self_id())
+                    }
+                    CompiledUnit::Script(_) => None,
+                })
+                .collect();
+            if !modules.is_empty() && !modules.iter().any(|module| used.contains(module)) {
+                lints.push(format!(
+                    "unused dependency: none of the modules of `{}` is used by the package. \
+                     Remove it from [dependencies], or move it to [dev-dependencies] if only \
+                     tests use it",
+                    dep
+                ));
+            }
+        }
+        for lint in &lints {
+            eprintln!("{} {}", "LINT".bold().red(), lint);
+        }
+        if !lints.is_empty() {
+            bail!("{} lint(s) failed", lints.len())
+        }
+        Ok(())
+    }
+
+    fn prove(&mut self) -> Result<()> {
+        let prove = Prove {
+            target_filter: None,
+            for_test: false,
+            options: None,
+        };
+        prove.execute(Some(self.path.clone()), self.config.clone())
+    }
+
+    fn check_coverage(&mut self) -> Result<()> {
+        let threshold = self.coverage_threshold.unwrap();
+        if self.tests_passed == Some(0) {
+            bail!("The package has no unit tests to collect coverage with")
+        }
+        // Coverage is recorded against the package built as `move test --coverage` builds it
+        let config = BuildConfig {
+            test_mode: false,
+            dev_mode: true,
+            ..self.config.clone()
+        };
+        let coverage_map =
+            CoverageMap::from_binary_file(coverage_map_path(&coverage_dir(&self.path, &config)))?;
+        let package = config.compile_package(&self.path, &mut Vec::new())?;
+        let exemptions = load_exemptions(&self.path, &package)?;
+        let (covered, total) = instruction_coverage(&package, &coverage_map, &exemptions);
+        let percentage = coverage_percentage(covered, total);
+        eprintln!(
+            "{} of {} instructions covered ({:.2}%)",
+            covered, total, percentage
+        );
+        if percentage < threshold {
+            bail!(
+                "The unit tests cover {:.2}% of the instructions, below the required {:.2}%",
+                percentage,
+                threshold
+            )
+        }
+        Ok(())
+    }
+}
+
+/// Print the status and duration of each stage, e.g. `    build     passed  0.42s`
+fn print_report(report: &VerifyReport) {
+    println!("Verification summary for {}:", report.package);
+    let width = report
+        .stages
+        .iter()
+        .map(|r| r.stage.to_string().len())
+        .max()
+        .unwrap_or(0);
+    for r in &report.stages {
+        let status = match r.status {
+            StageStatus::Passed => "passed",
+            StageStatus::Failed => "failed",
+            StageStatus::Skipped => "skipped",
+        };
+        let mut line = format!(
+            "    {:<width$}  {:<7}  {:.2}s",
+            r.stage.to_string(),
+            status,
+            r.duration_ms as f64 / 1000.0,
+            width = width
+        );
+        if let Some(message) = &r.message {
+            line = format!("{}  ({})", line, message);
+        }
+        println!("{}", line);
+    }
+}
+
+impl fmt::Display for Stage {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = match self {
+            Self::Manifest => "manifest",
+            Self::Build => "build",
+            Self::Test => "test",
+            Self::Lint => "lint",
+            Self::Prove => "prove",
+            Self::Coverage => "coverage",
+        };
+        write!(f, "{}", name)
+    }
+}
@@ -0,0 +1,373 @@
+// Copyright (c) The Move Contributors
+// SPDX-License-Identifier: Apache-2.0
+
+use super::{
+    build::{build_package, BuildOptions},
+    package_root,
+};
+use anyhow::{bail, Context, Result};
+use clap::*;
+use codespan_reporting::diagnostic::Severity;
+use move_command_line_common::{files::FileHash, interrupt};
+use move_compiler::{
+    parser::{
+        ast::{Definition, ModuleMember},
+        parse_file_string,
+    },
+    shared::{CompilationEnv, Flags},
+};
+use move_package::{
+    compilation::package_layout::CompiledPackageLayout, resolution::git::git as git_installation,
+    BuildConfig,
+};
+use serde::{Deserialize, Serialize};
+use std::{
+    collections::{BTreeMap, HashMap},
+    fs,
+    path::{Path, PathBuf},
+};
+
+/// The file under `build` the warnings are saved to by `--save`, and read from by default
+pub const WARNINGS_FILE: &str = "warnings.json";
+
+/// Compare the compiler warnings of the package with those of a baseline, and list the warnings
+/// added and removed since. Warnings are matched by their code, message, module and function
+/// rather than by their position, so that moving code around does not show up as a change.
+#[derive(Parser)]
+#[clap(name = "warn-diff")]
+pub struct WarnDiff {
+    /// The warnings to compare with, as saved by `--save`. Defaults to `build/warnings.json`.
+    #[clap(long = "baseline", parse(from_os_str), conflicts_with = "baseline-rev")]
+    pub baseline: Option<PathBuf>,
+    /// Build the package at this git revision (e.g. `main` or `HEAD~1`) in a temporary worktree,
+    /// and compare with its warnings
+    #[clap(long = "baseline-rev")]
+    pub baseline_rev: Option<String>,
+    /// Save the warnings of the package to `build/warnings.json`, to compare with in later runs
+    #[clap(long = "save")]
+    pub save: bool,
+    /// Fail if the package has warnings the baseline does not have
+    #[clap(long = "deny-new")]
+    pub deny_new: bool,
+}
+
+/// A compiler warning, identified by what it is about rather than by where it is
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Warning {
+    /// Code identifying the kind of warning, e.g. `W09002`
+    pub code: String,
+    /// The message of the warning followed by the explanation of its location, with whitespace
+    /// collapsed
+    pub message: String,
+    /// The module the warning points into, e.g. `0x2::Coin`, if any
+    pub module: Option<String>,
+    /// The function or script the warning points into, if any
+    pub function: Option<String>,
+    /// Where the warning points to, relative to the package root. Not used to match warnings.
+    pub file: String,
+    pub line: usize,
+    pub column: usize,
+}
+
+/// The contents of a file saved by `--save`
+#[derive(Serialize, Deserialize)]
+struct SavedWarnings {
+    warnings: Vec<Warning>,
+}
+
+impl WarnDiff {
+    pub fn execute(self, path: Option<PathBuf>, config: BuildConfig) -> Result<()> {
+        let root = package_root(path.as_deref())?;
+        let saved_path = root
+            .join(CompiledPackageLayout::Root.path())
+            .join(WARNINGS_FILE);
+        let baseline = match (&self.baseline_rev, &self.baseline) {
+            (Some(rev), _) => Some(warnings_at_revision(&root, rev, config.clone())?),
+            (None, Some(path)) => Some(read_warnings(path)?),
+            (None, None) if saved_path.exists() => Some(read_warnings(&saved_path)?),
+            (None, None) if self.save => None,
+            (None, None) => bail!(
+                "No baseline to compare with: '{}' does not exist. Save the warnings of the \
+                 baseline with --save, or pass --baseline or --baseline-rev",
+                saved_path.display()
+            ),
+        };
+        let current = package_warnings(&root, config)?;
+
+        let mut new_warnings = 0;
+        if let Some(baseline) = &baseline {
+            let diff = diff_warnings(baseline, &current);
+            new_warnings = diff.added.len();
+            for (title, warnings) in [("New", &diff.added), ("Fixed", &diff.removed)] {
+                if !warnings.is_empty() {
+                    println!("{} warnings:", title);
+                    for warning in warnings {
+                        println!("    {}", describe(warning));
+                    }
+                }
+            }
+            println!(
+                "{} new, {} fixed, {} unchanged",
+                diff.added.len(),
+                diff.removed.len(),
+                diff.unchanged
+            );
+        }
+        if self.save {
+            let saved = SavedWarnings { warnings: current };
+            fs::create_dir_all(saved_path.parent().unwrap())?;
+            fs::write(&saved_path, serde_json::to_string_pretty(&saved)?)
+                .with_context(|| format!("Unable to write '{}'", saved_path.display()))?;
+            println!(
+                "Saved {} warnings to {}",
+                saved.warnings.len(),
+                Path::new(CompiledPackageLayout::Root.path())
+                    .join(WARNINGS_FILE)
+                    .display()
+            );
+        }
+        if self.deny_new && new_warnings > 0 {
+            bail!(
+                "{} new warnings compared to the baseline, and --deny-new is set",
+                new_warnings
+            )
+        }
+        Ok(())
+    }
+}
+
+/// The warnings added, removed and kept between two sets of warnings
+pub struct WarningsDiff<'a> {
+    pub added: Vec<&'a Warning>,
+    pub removed: Vec<&'a Warning>,
+    pub unchanged: usize,
+}
+
+/// Match the warnings of `current` with those of `baseline` that have the same code, message,
+/// module and function. Warnings that are the same except for their position are matched in
+/// order.
+pub fn diff_warnings<'a>(baseline: &'a [Warning], current: &'a [Warning]) -> WarningsDiff<'a> {
+    type Key<'a> = (&'a str, &'a str, Option<&'a str>, Option<&'a str>);
+    let key = |warning: &'a Warning| -> Key<'a> {
+        (
+            warning.code.as_str(),
+            warning.message.as_str(),
+            warning.module.as_deref(),
+            warning.function.as_deref(),
+        )
+    };
+    let mut groups: BTreeMap<Key, (Vec<&Warning>, Vec<&Warning>)> = BTreeMap::new();
+    for warning in baseline {
+        groups.entry(key(warning)).or_default().0.push(warning);
+    }
+    for warning in current {
+        groups.entry(key(warning)).or_default().1.push(warning);
+    }
+    let mut diff = WarningsDiff {
+        added: vec![],
+        removed: vec![],
+        unchanged: 0,
+    };
+    for (before, after) in groups.into_values() {
+        let kept = before.len().min(after.len());
+        diff.unchanged += kept;
+        diff.removed.extend(&before[kept..]);
+        diff.added.extend(&after[kept..]);
+    }
+    diff
+}
+
+fn describe(warning: &Warning) -> String {
+    let scope = match (&warning.module, &warning.function) {
+        (Some(module), Some(function)) => format!("{}::{}", module, function),
+        (Some(module), None) => module.clone(),
+        (None, Some(script)) => format!("script {}", script),
+        (None, None) => warning.file.clone(),
+    };
+    format!(
+        "{} {}: {} ({}:{}:{})",
+        warning.code, scope, warning.message, warning.file, warning.line, warning.column
+    )
+}
+
+fn read_warnings(path: &Path) -> Result<Vec<Warning>> {
+    let contents =
+        fs::read_to_string(path).with_context(|| format!("Unable to read '{}'", path.display()))?;
+    let saved: SavedWarnings = serde_json::from_str(&contents)
+        .with_context(|| format!("Error parsing '{}'", path.display()))?;
+    Ok(saved.warnings)
+}
+
+/// The warnings of the package at `root`, which must compile
+fn package_warnings(root: &Path, build_config: BuildConfig) -> Result<Vec<Warning>> {
+    let options = BuildOptions {
+        package_path: root.to_path_buf(),
+        build_config,
+    };
+    let output = build_package(options, &mut std::io::stderr())?;
+    if output.package.is_none() {
+        output.print_diagnostics();
+        bail!(
+            "Unable to compare the warnings of '{}': it does not compile",
+            root.display()
+        )
+    }
+
+    // the source and definitions of each file, to find the module and function a warning
+    // points into
+    let mut files: HashMap<String, (String, Vec<Definition>)> = HashMap::new();
+    let mut warnings = vec![];
+    for diag in output.diagnostics {
+        if diag.severity != Severity::Warning {
+            continue;
+        }
+        let (source, definitions) = files
+            .entry(diag.file.clone())
+            .or_insert_with(|| parse_file(Path::new(&diag.file)));
+        let offset = offset_of(source, diag.line, diag.column);
+        let (module, function) = enclosing_items(definitions, offset);
+        let file = Path::new(&diag.file);
+        warnings.push(Warning {
+            code: diag.code,
+            message: format!("{}: {}", diag.message, diag.label)
+                .split_whitespace()
+                .collect::<Vec<_>>()
+                .join(" "),
+            module,
+            function,
+            file: file
+                .strip_prefix(root)
+                .unwrap_or(file)
+                .to_string_lossy()
+                .to_string(),
+            line: diag.line,
+            column: diag.column,
+        });
+    }
+    Ok(warnings)
+}
+
+/// The warnings of the package at `root` as of the git revision `rev`
+fn warnings_at_revision(root: &Path, rev: &str, build_config: BuildConfig) -> Result<Vec<Warning>> {
+    build_at_revision(root, rev, build_config, package_warnings)
+}
+
+/// Call `build` with the root of the package at `root` as of the git revision `rev`, checked out
+/// in a temporary worktree of the repository containing the package, and a build config
+/// installing into a temporary directory
+pub(crate) fn build_at_revision<T>(
+    root: &Path,
+    rev: &str,
+    mut build_config: BuildConfig,
+    build: impl FnOnce(&Path, BuildConfig) -> Result<T>,
+) -> Result<T> {
+    let toplevel = PathBuf::from(git(root, &["rev-parse", "--show-toplevel"])?.trim())
+        .canonicalize()
+        .context("Unable to find the root of the git repository containing the package")?;
+    let package_dir = root
+        .strip_prefix(&toplevel)
+        .with_context(|| format!("'{}' is not in '{}'", root.display(), toplevel.display()))?
+        .to_path_buf();
+
+    let temp_dir = tempfile::tempdir()?;
+    let worktree = temp_dir.path().join("baseline");
+    let worktree_arg = worktree.to_string_lossy().to_string();
+    git(
+        &toplevel,
+        &["worktree", "add", "--detach", &worktree_arg, rev],
+    )
+    .with_context(|| format!("Unable to check out '{}'", rev))?;
+    let _remove_on_interrupt = interrupt::on_interrupt({
+        let (toplevel, worktree_arg) = (toplevel.clone(), worktree_arg.clone());
+        move || {
+            let _ = git(&toplevel, &["worktree", "remove", "--force", &worktree_arg]);
+        }
+    });
+
+    build_config.install_dir = Some(temp_dir.path().join("install"));
+    let result = build(&worktree.join(package_dir), build_config)
+        .with_context(|| format!("Unable to build the baseline at '{}'", rev));
+    git(&toplevel, &["worktree", "remove", "--force", &worktree_arg])?;
+    result
+}
+
+/// Run git in `dir`, returning its output
+fn git(dir: &Path, args: &[&str]) -> Result<String> {
+    let output = git_installation()?
+        .command()
+        .current_dir(dir)
+        .args(args)
+        .output()
+        .context("Unable to run git")?;
+    if !output.status.success() {
+        bail!(
+            "`git {}` failed: {}",
+            args.join(" "),
+            String::from_utf8_lossy(&output.stderr).trim()
+        )
+    }
+    Ok(String::from_utf8_lossy(&output.stdout).to_string())
+}
+
+/// The source and the definitions of the Move file at `path`, with no definitions if it cannot be
+/// read or parsed
+fn parse_file(path: &Path) -> (String, Vec<Definition>) {
+    let source = fs::read_to_string(path).unwrap_or_default();
+    let mut env = CompilationEnv::new(Flags::empty());
+    let definitions = parse_file_string(&mut env, FileHash::new(&source), &source)
+        .map(|(definitions, _)| definitions)
+        .unwrap_or_default();
+    (source, definitions)
+}
+
+/// The byte offset of the `column`th character of the `line`th line of `source`, both starting
+/// from 1
+fn offset_of(source: &str, line: usize, column: usize) -> u32 {
+    let line_start: usize = source
+        .split_inclusive('\n')
+        .take(line.saturating_sub(1))
+        .map(str::len)
+        .sum();
+    let column_offset: usize = source[line_start..]
+        .chars()
+        .take(column.saturating_sub(1))
+        .map(char::len_utf8)
+        .sum();
+    (line_start + column_offset) as u32
+}
+
+/// The names of the module and of the function or script containing `offset`
+fn enclosing_items(definitions: &[Definition], offset: u32) -> (Option<String>, Option<String>) {
+    let contains = |start: u32, end: u32| start <= offset && offset < end;
+    for definition in definitions {
+        let (address, modules) = match definition {
+            Definition::Module(module) => (None, std::slice::from_ref(module)),
+            Definition::Address(block) => (Some(&block.addr), &block.modules[..]),
+            Definition::Script(script) => {
+                if contains(script.loc.start(), script.loc.end()) {
+                    return (None, Some(script.function.name.to_string()));
+                }
+                continue;
+            }
+        };
+        for module in modules {
+            if !contains(module.loc.start(), module.loc.end()) {
+                continue;
+            }
+            let name = match module.address.as_ref().or(address) {
+                Some(address) => format!("{}::{}", address, module.name),
+                None => module.name.to_string(),
+            };
+            let function = module.members.iter().find_map(|member| match member {
+                ModuleMember::Function(function)
+                    if contains(function.loc.start(), function.loc.end()) =>
+                {
+                    Some(function.name.to_string())
+                }
+                _ => None,
+            });
+            return (Some(name), function);
+        }
+    }
+    (None, None)
+}
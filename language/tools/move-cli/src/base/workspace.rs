@@ -0,0 +1,334 @@
+// Copyright (c) The Move Contributors
+// SPDX-License-Identifier: Apache-2.0
+
+use anyhow::{bail, Context, Result};
+use clap::*;
+use move_package::{
+    resolution::git::git,
+    source_package::{
+        layout::SourcePackageLayout, manifest_parser::parse_move_manifest_from_file,
+        workspace::Workspace,
+    },
+};
+use std::{
+    collections::{BTreeMap, BTreeSet},
+    fmt,
+    path::{Path, PathBuf},
+};
+
+/// Options of the commands that can run on every member of a workspace
+#[derive(Parser, Default)]
+pub struct WorkspaceOptions {
+    /// Run on every member of the workspace the package belongs to, in the order the members are
+    /// declared in the `[workspace]` section of its manifest
+    #[clap(long = "workspace")]
+    pub workspace: bool,
+    /// Keep going after a member fails, and exit with an error at the end. By default, the first
+    /// member that fails stops the run.
+    #[clap(long = "keep-going", requires = "workspace")]
+    pub keep_going: bool,
+    /// Only run on the members affected by the files changed since this git revision: the members
+    /// holding changed files, and the members depending on them
+    #[clap(long = "changed-since", requires = "workspace")]
+    pub changed_since: Option<String>,
+    /// Like `--changed-since`, with the changed files given as a comma-separated list of paths
+    /// relative to the current directory instead of asked to git
+    #[clap(
+        long = "changed-files",
+        requires = "workspace",
+        conflicts_with = "changed-since",
+        use_value_delimiter = true,
+        parse(from_os_str)
+    )]
+    pub changed_files: Option<Vec<PathBuf>>,
+}
+
+/// The outcome of a command for one member of a workspace
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum MemberOutcome {
+    Ok,
+    FailedToCompile,
+    TestsFailed {
+        failed: usize,
+        total: usize,
+    },
+    /// The member was not run because an earlier member failed
+    Skipped,
+    /// The member was not run because no file it depends on changed
+    Unaffected,
+}
+
+impl MemberOutcome {
+    pub fn is_failure(&self) -> bool {
+        matches!(self, Self::FailedToCompile | Self::TestsFailed { .. })
+    }
+}
+
+impl fmt::Display for MemberOutcome {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Ok => write!(f, "ok"),
+            Self::FailedToCompile => write!(f, "failed to compile"),
+            Self::TestsFailed { failed, total } => {
+                write!(f, "tests failed ({} of {})", failed, total)
+            }
+            Self::Skipped => write!(f, "skipped"),
+            Self::Unaffected => write!(f, "unaffected"),
+        }
+    }
+}
+
+/// Run `run_member` on each member of the workspace `path` belongs to, and print a summary of the
+/// outcome for each member. Unless `options.keep_going` is set, the members after the first one
+/// that fails are skipped. Errors raised for a member, such as failures to resolve its
+/// dependencies, count as compilation failures. Fails if any member failed. With
+/// `options.changed_since` or `options.changed_files`, the members not affected by the changed
+/// files are left out, and why each member is run or not is printed first.
+pub fn run_workspace(
+    path: Option<&Path>,
+    options: &WorkspaceOptions,
+    mut run_member: impl FnMut(&Path) -> Result<MemberOutcome>,
+) -> Result<()> {
+    let workspace = Workspace::find(path.unwrap_or_else(|| Path::new(".")))?;
+    let changed_files = match (&options.changed_since, &options.changed_files) {
+        (Some(rev), _) => Some(files_changed_since(&workspace.root, rev)?),
+        (None, Some(files)) => {
+            let current_dir = std::env::current_dir()?;
+            Some(files.iter().map(|file| current_dir.join(file)).collect())
+        }
+        (None, None) => None,
+    };
+    let affected = match changed_files {
+        Some(changed_files) => {
+            let reasons = affected_members(&workspace, &changed_files)?;
+            let width = workspace
+                .members
+                .iter()
+                .map(|member| workspace.member_name(member).len())
+                .max()
+                .unwrap_or(0);
+            eprintln!("Affected workspace members:");
+            for member in &workspace.members {
+                let reason = match reasons.get(member) {
+                    Some(reason) => format!("build: {}", reason),
+                    None => "skip: no changes to it or to the members it depends on".to_string(),
+                };
+                eprintln!(
+                    "    {:<width$}  {}",
+                    workspace.member_name(member),
+                    reason,
+                    width = width
+                );
+            }
+            eprintln!();
+            Some(reasons)
+        }
+        None => None,
+    };
+
+    let mut outcomes: Vec<(PathBuf, MemberOutcome)> = vec![];
+    for member in &workspace.members {
+        let outcome = if affected
+            .as_ref()
+            .map_or(false, |affected| !affected.contains_key(member))
+        {
+            MemberOutcome::Unaffected
+        } else if outcomes.iter().any(|(_, outcome)| outcome.is_failure()) && !options.keep_going {
+            MemberOutcome::Skipped
+        } else {
+            run_member(member).unwrap_or_else(|err| {
+                eprintln!("Error: {:#}", err);
+                MemberOutcome::FailedToCompile
+            })
+        };
+        outcomes.push((member.clone(), outcome));
+    }
+
+    let names: Vec<_> = outcomes
+        .iter()
+        .map(|(member, _)| workspace.member_name(member))
+        .collect();
+    let width = names.iter().map(String::len).max().unwrap_or(0);
+    eprintln!("\nWorkspace summary:");
+    for (name, (_, outcome)) in names.iter().zip(&outcomes) {
+        eprintln!("    {:<width$}  {}", name, outcome, width = width);
+    }
+
+    let failed = outcomes
+        .iter()
+        .filter(|(_, outcome)| outcome.is_failure())
+        .count();
+    if failed > 0 {
+        let skipped = outcomes
+            .iter()
+            .filter(|(_, outcome)| *outcome == MemberOutcome::Skipped)
+            .count();
+        if skipped > 0 {
+            bail!(
+                "{} of {} workspace members failed, and {} were skipped. Pass --keep-going to run \
+                 every member regardless of failures",
+                failed,
+                outcomes.len(),
+                skipped
+            )
+        }
+        bail!("{} of {} workspace members failed", failed, outcomes.len())
+    }
+    Ok(())
+}
+
+/// Why a workspace member is affected by a change
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum AffectedReason {
+    /// Files of the member changed, of which this is the first
+    Changed { first: PathBuf, count: usize },
+    /// The manifest declaring the workspace changed
+    WorkspaceManifest,
+    /// A member the member depends on, directly or not, is affected
+    Dependency(String),
+}
+
+impl fmt::Display for AffectedReason {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Changed { first, count: 1 } => write!(f, "{} changed", first.display()),
+            Self::Changed { first, count } => write!(
+                f,
+                "{} and {} other file(s) changed",
+                first.display(),
+                count - 1
+            ),
+            Self::WorkspaceManifest => write!(f, "the workspace manifest changed"),
+            Self::Dependency(name) => write!(f, "depends on {}, which is affected", name),
+        }
+    }
+}
+
+/// The members of `workspace` affected by changes to `changed_files` (absolute paths), with the
+/// reason each one is affected: the members holding changed files, and the members depending on
+/// them through local dependencies, directly or not
+pub fn affected_members(
+    workspace: &Workspace,
+    changed_files: &[PathBuf],
+) -> Result<BTreeMap<PathBuf, AffectedReason>> {
+    let members: Vec<_> = workspace
+        .members
+        .iter()
+        .map(|member| canonical(member))
+        .collect();
+    let workspace_manifest = canonical(&workspace.root).join(SourcePackageLayout::Manifest.path());
+    let mut affected = BTreeMap::new();
+    for file in changed_files {
+        let file = canonical(file);
+        if file == workspace_manifest {
+            for member in &workspace.members {
+                affected.insert(member.clone(), AffectedReason::WorkspaceManifest);
+            }
+            break;
+        }
+        // the innermost member holding the file, if members are nested
+        let holder = members
+            .iter()
+            .enumerate()
+            .filter(|(_, member)| file.starts_with(member))
+            .max_by_key(|(_, member)| member.components().count());
+        if let Some((idx, member)) = holder {
+            let relative = file.strip_prefix(member).unwrap_or(&file).to_path_buf();
+            affected
+                .entry(workspace.members[idx].clone())
+                .and_modify(|reason| {
+                    if let AffectedReason::Changed { count, .. } = reason {
+                        *count += 1
+                    }
+                })
+                .or_insert(AffectedReason::Changed {
+                    first: Path::new(&workspace.member_name(&workspace.members[idx]))
+                        .join(relative),
+                    count: 1,
+                });
+        }
+    }
+
+    // the members each member depends on
+    let mut dependencies: BTreeMap<&PathBuf, BTreeSet<usize>> = BTreeMap::new();
+    for member in &workspace.members {
+        let manifest = parse_move_manifest_from_file(member)?;
+        let local_dependencies = manifest
+            .dependencies
+            .values()
+            .chain(manifest.dev_dependencies.values())
+            .filter(|dep| {
+                dep.git_info.is_none() && dep.node_info.is_none() && dep.archive_info.is_none()
+            })
+            .map(|dep| canonical(&member.join(&dep.local)));
+        let member_dependencies = dependencies.entry(member).or_default();
+        for dependency in local_dependencies {
+            if let Some(idx) = members.iter().position(|member| *member == dependency) {
+                member_dependencies.insert(idx);
+            }
+        }
+    }
+    // propagate to the dependents until nothing changes, as members can be declared in any order
+    loop {
+        let mut added = false;
+        for member in &workspace.members {
+            if affected.contains_key(member) {
+                continue;
+            }
+            let affected_dependency = dependencies[member]
+                .iter()
+                .map(|idx| &workspace.members[*idx])
+                .find(|dependency| affected.contains_key(*dependency));
+            if let Some(dependency) = affected_dependency {
+                let reason = AffectedReason::Dependency(workspace.member_name(dependency));
+                affected.insert(member.clone(), reason);
+                added = true;
+            }
+        }
+        if !added {
+            return Ok(affected);
+        }
+    }
+}
+
+/// The files of the git repository holding `root` that differ from revision `rev`, committed or
+/// not, and the untracked files, as absolute paths
+fn files_changed_since(root: &Path, rev: &str) -> Result<Vec<PathBuf>> {
+    let run_git = |args: &[&str]| -> Result<String> {
+        let output = git()?
+            .command()
+            .current_dir(root)
+            .args(args)
+            .output()
+            .context("Unable to run git")?;
+        if !output.status.success() {
+            bail!(
+                "`git {}` failed: {}",
+                args.join(" "),
+                String::from_utf8_lossy(&output.stderr).trim()
+            )
+        }
+        Ok(String::from_utf8_lossy(&output.stdout).to_string())
+    };
+    let top_level = PathBuf::from(run_git(&["rev-parse", "--show-toplevel"])?.trim());
+    let changed = run_git(&["diff", "--name-only", rev, "--"])?;
+    let untracked = run_git(&["ls-files", "--others", "--exclude-standard", "--full-name"])?;
+    Ok(changed
+        .lines()
+        .chain(untracked.lines())
+        .filter(|line| !line.is_empty())
+        .map(|line| top_level.join(line))
+        .collect())
+}
+
+/// `path` with its symbolic links resolved, if it exists. Deleted files keep their path, with the
+/// links of their closest existing parent resolved.
+fn canonical(path: &Path) -> PathBuf {
+    if let Ok(path) = path.canonicalize() {
+        return path;
+    }
+    match (path.parent(), path.file_name()) {
+        (Some(parent), Some(name)) => canonical(parent).join(name),
+        _ => path.to_path_buf(),
+    }
+}
@@ -6,6 +6,7 @@ use std::{path::PathBuf, str::FromStr};
 
 use crate::{experimental, sandbox::utils::PackageContext, Move};
 use anyhow::Result;
+use move_command_line_common::experiments;
 use move_core_types::{
     language_storage::TypeTag, parser, transaction_argument::TransactionArgument,
 };
@@ -99,6 +100,7 @@ impl ExperimentalCommand {
                 type_args,
                 concretize,
             } => {
+                experiments::check_enabled(experiments::READ_WRITE_SET)?;
                 let state = PackageContext::new(&move_args.package_path, &move_args.build_config)?
                     .prepare_state(storage_dir)?;
                 experimental::commands::analyze_read_write_set(
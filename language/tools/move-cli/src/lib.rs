@@ -3,11 +3,36 @@
 // SPDX-License-Identifier: Apache-2.0
 
 use base::{
-    build::Build, coverage::Coverage, disassemble::Disassemble, docgen::Docgen, errmap::Errmap,
-    info::Info, movey_login::MoveyLogin, movey_upload::MoveyUpload, new::New, prove::Prove,
+    add::Add,
+    address::{Derive, Export, Import},
+    bench::Bench,
+    build::Build,
+    cache::{Gc, Stats},
+    clean::Clean,
+    coverage::Coverage,
+    diff_abi::DiffAbi,
+    disassemble::Disassemble,
+    docgen::Docgen,
+    errmap::Errmap,
+    experiments::List,
+    fmt_check::FmtCheck,
+    fuzz::Fuzz,
+    gas_report::GasReport,
+    help::Help,
+    info::Info,
+    metadata::Metadata,
+    movey_login::MoveyLogin,
+    movey_logout::MoveyLogout,
+    movey_upload::MoveyUpload,
+    new::New,
+    prove::Prove,
+    publish_log::PublishLog,
     test::Test,
+    toolchain::Show,
+    verify::Verify,
+    warn_diff::WarnDiff,
 };
-use move_package::BuildConfig;
+use move_package::{resolution::registry::set_registry_path, BuildConfig};
 
 pub mod base;
 pub mod experimental;
@@ -24,21 +49,40 @@ pub const DEFAULT_BUILD_DIR: &str = ".";
 const BCS_EXTENSION: &str = "bcs";
 
 use anyhow::Result;
-use clap::Parser;
+use clap::{AppSettings, Arg, CommandFactory, Parser};
+use move_binary_format::{access::ModuleAccess, CompiledModule};
+use move_command_line_common::{
+    env, experiments, interrupt,
+    json_errors::{self, JsonDiagnostic, Level},
+    value_rendering::{set_value_rendering, ValueRendering},
+};
 use move_core_types::{
     account_address::AccountAddress, errmap::ErrorMapping, identifier::Identifier,
 };
-use move_vm_runtime::native_functions::NativeFunction;
-use move_vm_test_utils::gas_schedule::CostTable;
-use std::path::PathBuf;
+use move_stdlib::natives::{all_natives, nursery_natives, GasParameters, NurseryGasParameters};
+use move_vm_runtime::native_functions::{NativeFunction, NativeFunctionTable};
+use move_vm_test_utils::gas_schedule::{CostTable, INITIAL_COST_SCHEDULE};
+use std::{
+    ffi::OsString,
+    path::{Path, PathBuf},
+};
+use utils::storage_lock::StorageLockOptions;
 
 type NativeFunctionRecord = (AccountAddress, Identifier, Identifier, NativeFunction);
 
 #[derive(Parser)]
 #[clap(author, version, about)]
 pub struct Move {
-    /// Path to a package which the command should be run with respect to.
-    #[clap(long = "path", short = 'p', global = true, parse(from_os_str))]
+    /// Path to a package which the command should be run with respect to. May be either a
+    /// directory inside the package or the package's `Move.toml`. Relative paths used by the
+    /// command (sources, build and storage directories) are resolved against the package root.
+    #[clap(
+        long = "path",
+        short = 'p',
+        alias = "manifest-path",
+        global = true,
+        parse(from_os_str)
+    )]
     pub package_path: Option<PathBuf>,
 
     /// Print additional diagnostics if available.
@@ -54,10 +98,51 @@ pub struct Move {
 /// The `cmd` argument is added here rather than in `Move` to make it
 /// easier for other crates to extend `move-cli`
 #[derive(Parser)]
+#[clap(
+    arg_required_else_help = true,
+    disable_help_subcommand = true,
+    after_help = "Run `move --list` to list the built-in commands, and the plugin commands \
+                  implemented by `move-<name>` executables on the PATH."
+)]
 pub struct MoveCLI {
     #[clap(flatten)]
     pub move_args: Move,
 
+    /// Write detailed logs of the command, such as the dependencies fetched and the compiler passes
+    /// run, to this file, whatever the console prints. Defaults to the `MOVE_LOG_FILE` environment
+    /// variable.
+    #[clap(long = "log-file", global = true, parse(from_os_str))]
+    pub log_file: Option<PathBuf>,
+
+    /// Also write the warnings and errors to this file, as one JSON object per line. They are
+    /// printed to stderr either way, so that stdout only holds the output of the command.
+    #[clap(long = "json-errors-file", global = true, parse(from_os_str))]
+    pub json_errors_file: Option<PathBuf>,
+
+    /// Enable experimental features, which may change or go away in any release, by a
+    /// comma-separated list of names. Defaults to the `MOVE_EXPERIMENTS` environment variable.
+    /// `move experiments list` prints the available experiments.
+    #[clap(long = "experiments", global = true, value_name = "NAME,...")]
+    pub experiments: Option<String>,
+
+    /// The most bytes of each decoded Move value to print in diagnostics, such as the storage
+    /// state of a failing test or the resources changed by a transaction. Longer values are cut
+    /// with a note of how many bytes were left out.
+    #[clap(long = "max-value-render-bytes", global = true, default_value = "4096")]
+    pub max_value_render_bytes: usize,
+
+    /// Print the types of the decoded Move values in diagnostics instead of the values, e.g. to
+    /// share logs without the data they hold.
+    #[clap(long = "redact-values", global = true)]
+    pub redact_values: bool,
+
+    /// Use the registry kept in this directory, with an `index.json` listing its packages, instead
+    /// of a registry server: dependencies given as `{ version = "...", registry = "local" }` are
+    /// taken from it, `package add` looks packages up in it and `movey-upload` copies the package
+    /// archive into it. Defaults to the `path` of the `[registry]` table of the credential file.
+    #[clap(long = "registry-path", global = true, parse(from_os_str))]
+    pub registry_path: Option<PathBuf>,
+
     #[clap(subcommand)]
     pub cmd: Command,
 }
@@ -69,11 +154,18 @@ pub enum Command {
     Disassemble(Disassemble),
     Docgen(Docgen),
     Errmap(Errmap),
+    Help(Help),
     Info(Info),
     MoveyUpload(MoveyUpload),
     New(New),
     Prove(Prove),
     Test(Test),
+    /// Manage the files of a package.
+    #[clap(name = "package")]
+    Package {
+        #[clap(subcommand)]
+        cmd: PackageCommand,
+    },
     /// Execute a sandbox command.
     #[clap(name = "sandbox")]
     Sandbox {
@@ -81,8 +173,14 @@ pub enum Command {
         /// and script execution.
         #[clap(long, default_value = DEFAULT_STORAGE_DIR, parse(from_os_str))]
         storage_dir: PathBuf,
+        #[clap(flatten)]
+        lock_options: StorageLockOptions,
         #[clap(subcommand)]
         cmd: sandbox::cli::SandboxCommand,
+        /// The arguments the CLI was run with, recorded in the transaction log of `storage_dir`.
+        /// The arguments of the current process if empty.
+        #[clap(skip)]
+        argv: Vec<OsString>,
     },
     /// (Experimental) Run static analyses on Move source or bytecode.
     #[clap(name = "experimental")]
@@ -96,6 +194,75 @@ pub enum Command {
     },
     #[clap(name = "movey-login")]
     MoveyLogin(MoveyLogin),
+    #[clap(name = "movey-logout")]
+    MoveyLogout(MoveyLogout),
+    /// Show the version of the CLI a project requires, set in `move-toolchain.toml` or in the
+    /// `[toolchain]` section of `Move.toml`.
+    #[clap(name = "toolchain")]
+    Toolchain {
+        #[clap(subcommand)]
+        cmd: ToolchainCommand,
+    },
+    /// List the experimental features that `--experiments` enables.
+    #[clap(name = "experiments")]
+    Experiments {
+        #[clap(subcommand)]
+        cmd: ExperimentsCommand,
+    },
+    /// Report and trim the caches under MOVE_HOME: downloaded dependencies and registry responses.
+    #[clap(name = "cache")]
+    Cache {
+        #[clap(subcommand)]
+        cmd: CacheCommand,
+    },
+    /// Work with account addresses.
+    #[clap(name = "address")]
+    Address {
+        #[clap(subcommand)]
+        cmd: AddressCommand,
+    },
+    /// Run the `move-<name>` executable on the PATH for any other command `<name>`, with the
+    /// remaining arguments.
+    #[clap(external_subcommand)]
+    External(Vec<String>),
+}
+
+#[derive(Parser)]
+pub enum PackageCommand {
+    Add(Add),
+    Bench(Bench),
+    Clean(Clean),
+    DiffAbi(DiffAbi),
+    FmtCheck(FmtCheck),
+    Fuzz(Fuzz),
+    GasReport(GasReport),
+    Metadata(Metadata),
+    PublishLog(PublishLog),
+    Verify(Verify),
+    WarnDiff(WarnDiff),
+}
+
+#[derive(Parser)]
+pub enum ToolchainCommand {
+    Show(Show),
+}
+
+#[derive(Parser)]
+pub enum ExperimentsCommand {
+    List(List),
+}
+
+#[derive(Parser)]
+pub enum CacheCommand {
+    Stats(Stats),
+    Gc(Gc),
+}
+
+#[derive(Parser)]
+pub enum AddressCommand {
+    Derive(Derive),
+    Export(Export),
+    Import(Import),
 }
 
 pub fn run_cli(
@@ -109,25 +276,101 @@ pub fn run_cli(
     //         1. It's still using the old CostTable.
     //         2. The CostTable only affects sandbox runs, but not unit tests, which use a unit cost table.
     match cmd {
-        Command::Build(c) => c.execute(move_args.package_path, move_args.build_config),
-        Command::Coverage(c) => c.execute(move_args.package_path, move_args.build_config),
+        Command::Build(c) => c.execute_with_verbosity(
+            move_args.package_path,
+            move_args.build_config,
+            move_args.verbose,
+        ),
+        Command::Coverage(c) => c.execute_with_verbosity(
+            move_args.package_path,
+            move_args.build_config,
+            move_args.verbose,
+        ),
         Command::Disassemble(c) => c.execute(move_args.package_path, move_args.build_config),
         Command::Docgen(c) => c.execute(move_args.package_path, move_args.build_config),
         Command::Errmap(c) => c.execute(move_args.package_path, move_args.build_config),
+        Command::Help(c) => c.execute(),
         Command::Info(c) => c.execute(move_args.package_path, move_args.build_config),
-        Command::MoveyUpload(c) => c.execute(move_args.package_path),
+        Command::MoveyUpload(c) => {
+            c.execute_with_config(move_args.package_path, move_args.build_config)
+        }
         Command::New(c) => c.execute_with_defaults(move_args.package_path),
+        Command::Package { cmd } => match cmd {
+            PackageCommand::Add(c) => c.execute(move_args.package_path),
+            PackageCommand::Bench(c) => {
+                c.execute(move_args.package_path, move_args.build_config, natives)
+            }
+            PackageCommand::Clean(c) => c.execute(move_args.package_path),
+            PackageCommand::DiffAbi(c) => c.execute(move_args.package_path, move_args.build_config),
+            PackageCommand::FmtCheck(c) => c.execute(move_args.package_path),
+            PackageCommand::Fuzz(c) => c.execute(
+                move_args.package_path,
+                move_args.build_config,
+                natives,
+                cost_table,
+            ),
+            PackageCommand::GasReport(c) => c.execute(
+                move_args.package_path,
+                move_args.build_config,
+                natives,
+                cost_table,
+            ),
+            PackageCommand::Metadata(c) => {
+                c.execute(move_args.package_path, move_args.build_config)
+            }
+            PackageCommand::PublishLog(c) => c.execute(),
+            PackageCommand::Verify(c) => {
+                c.execute(move_args.package_path, move_args.build_config, natives)
+            }
+            PackageCommand::WarnDiff(c) => {
+                c.execute(move_args.package_path, move_args.build_config)
+            }
+        },
         Command::Prove(c) => c.execute(move_args.package_path, move_args.build_config),
         Command::Test(c) => c.execute(move_args.package_path, move_args.build_config, natives),
-        Command::Sandbox { storage_dir, cmd } => cmd.handle_command(
-            natives,
-            cost_table,
-            error_descriptions,
-            &move_args,
-            &storage_dir,
-        ),
+        Command::Sandbox {
+            storage_dir,
+            lock_options,
+            cmd,
+            argv,
+        } => {
+            let argv = if argv.is_empty() {
+                std::env::args_os().collect()
+            } else {
+                argv
+            };
+            cmd.handle_command(
+                natives,
+                cost_table,
+                error_descriptions,
+                &move_args,
+                &storage_dir,
+                &lock_options,
+                &argv,
+            )
+        }
         Command::Experimental { storage_dir, cmd } => cmd.handle_command(&move_args, &storage_dir),
         Command::MoveyLogin(c) => c.execute(),
+        Command::MoveyLogout(c) => c.execute(),
+        Command::Toolchain { cmd } => match cmd {
+            ToolchainCommand::Show(c) => c.execute(move_args.package_path),
+        },
+        Command::Experiments { cmd } => match cmd {
+            ExperimentsCommand::List(c) => c.execute(),
+        },
+        Command::Cache { cmd } => match cmd {
+            CacheCommand::Stats(c) => c.execute(move_args.verbose),
+            CacheCommand::Gc(c) => c.execute(),
+        },
+        Command::Address { cmd } => match cmd {
+            AddressCommand::Derive(c) => c.execute(),
+            AddressCommand::Export(c) => c.execute(move_args.package_path),
+            AddressCommand::Import(c) => c.execute(
+                move_args.package_path,
+                move_args.build_config.force_recompilation,
+            ),
+        },
+        Command::External(args) => utils::plugins::run_plugin(&args, &move_args),
     }
 }
 
@@ -136,12 +379,315 @@ pub fn move_cli(
     cost_table: &CostTable,
     error_descriptions: &ErrorMapping,
 ) -> Result<()> {
-    let args = MoveCLI::parse();
-    run_cli(
-        natives,
-        cost_table,
-        error_descriptions,
-        args.move_args,
-        args.cmd,
-    )
+    let argv: Vec<_> = std::env::args_os().collect();
+    if is_list_request(&argv) {
+        list_commands();
+        return Ok(());
+    }
+    let args = MoveCLI::parse_from(&argv);
+    install_interrupt_handler()?;
+    run_move_cli(natives, cost_table, error_descriptions, &argv, args)
+}
+
+fn run_move_cli(
+    natives: Vec<NativeFunctionRecord>,
+    cost_table: &CostTable,
+    error_descriptions: &ErrorMapping,
+    argv: &[OsString],
+    args: MoveCLI,
+) -> Result<()> {
+    if let Some(path) = &args.json_errors_file {
+        json_errors::set_json_errors_file(path)?;
+    }
+    let result = run_move_command(natives, cost_table, error_descriptions, argv, args);
+    if let Err(err) = &result {
+        json_errors::record(&JsonDiagnostic::new(Level::Error, format!("{:#}", err)));
+    }
+    result
+}
+
+fn run_move_command(
+    natives: Vec<NativeFunctionRecord>,
+    cost_table: &CostTable,
+    error_descriptions: &ErrorMapping,
+    argv: &[OsString],
+    mut args: MoveCLI,
+) -> Result<()> {
+    if let Some(log_file) = utils::log_file::log_file_path(args.log_file.clone()) {
+        utils::log_file::init(&log_file, argv)?;
+    }
+    experiments::enable_experiments(args.experiments.as_deref())?;
+    set_value_rendering(ValueRendering {
+        max_bytes: args.max_value_render_bytes,
+        redact: args.redact_values,
+    });
+    if args.cmd.works_on_package() {
+        set_registry_path(registry_path(args.registry_path.as_deref())?);
+        utils::toolchain::check_project_toolchain(args.move_args.package_path.as_deref())?;
+        base::resolve_out_dir(
+            args.move_args.package_path.as_deref(),
+            &mut args.move_args.build_config,
+        )?;
+    }
+    match args.cmd {
+        Command::Test(mut test) => {
+            // the processes running the tests of each module with `--isolate-modules` are given
+            // the same arguments
+            test.argv = argv.to_vec();
+            let cmd = Command::Test(test);
+            run_cli(natives, cost_table, error_descriptions, args.move_args, cmd)
+        }
+        Command::Sandbox {
+            storage_dir,
+            lock_options,
+            cmd,
+            argv: _,
+        } => {
+            // recorded in the transaction log as the arguments of the command
+            let cmd = Command::Sandbox {
+                storage_dir,
+                lock_options,
+                cmd,
+                argv: argv.to_vec(),
+            };
+            run_cli(natives, cost_table, error_descriptions, args.move_args, cmd)
+        }
+        cmd => run_cli(natives, cost_table, error_descriptions, args.move_args, cmd),
+    }
+}
+
+impl Command {
+    /// Whether the command works on a package, and so needs the toolchain it requires, the
+    /// directory it is built in and the registry its dependencies are resolved from. `move
+    /// toolchain` reports the requirement rather than being refused by it.
+    fn works_on_package(&self) -> bool {
+        match self {
+            Command::Build(_)
+            | Command::Coverage(_)
+            | Command::Disassemble(_)
+            | Command::Docgen(_)
+            | Command::Errmap(_)
+            | Command::Info(_)
+            | Command::MoveyUpload(_)
+            | Command::Prove(_)
+            | Command::Test(_)
+            | Command::Package { .. }
+            | Command::Sandbox { .. }
+            | Command::Experimental { .. } => true,
+            Command::Address { cmd } => !matches!(cmd, AddressCommand::Derive(_)),
+            Command::Help(_)
+            | Command::New(_)
+            | Command::MoveyLogin(_)
+            | Command::MoveyLogout(_)
+            | Command::Toolchain { .. }
+            | Command::Experiments { .. }
+            | Command::Cache { .. }
+            | Command::External(_) => false,
+        }
+    }
+}
+
+/// Whether `argv` asks for `--list` among the global options and gives no command. It is answered
+/// before parsing the arguments, as a command is required otherwise.
+// the derived parser requires the command through this deprecated setting, so it has to be unset
+#[allow(deprecated)]
+fn is_list_request(argv: &[OsString]) -> bool {
+    MoveCLI::command()
+        .unset_setting(AppSettings::SubcommandRequiredElseHelp)
+        .arg_required_else_help(false)
+        .arg(Arg::new("list").long("list"))
+        .try_get_matches_from(argv)
+        .map_or(false, |matches| {
+            matches.is_present("list") && matches.subcommand().is_none()
+        })
+}
+
+/// The registry directory given with `--registry-path`, relative to the current directory, or else
+/// the one set in the credential file
+fn registry_path(flag: Option<&Path>) -> Result<Option<PathBuf>> {
+    match flag {
+        Some(path) => Ok(Some(std::env::current_dir()?.join(path))),
+        // without a MOVE_HOME, commands needing one report it themselves, and a malformed
+        // credential file is reported by the commands reading it
+        None => Ok(env::move_home()
+            .ok()
+            .and_then(|move_home| utils::movey_credential::get_registry_path(&move_home).ok())
+            .flatten()),
+    }
+}
+
+/// Print the built-in commands with their description, followed by the plugin commands found on
+/// the PATH that are not shadowed by a built-in command
+fn list_commands() {
+    println!("Installed commands:");
+    let cli = MoveCLI::command();
+    let builtins: Vec<_> = cli
+        .get_subcommands()
+        .map(|cmd| {
+            let about = cmd.get_about().unwrap_or_default();
+            (cmd.get_name(), about.lines().next().unwrap_or_default())
+        })
+        .collect();
+    for (name, about) in &builtins {
+        println!("    {:<20} {}", name, about);
+    }
+    for (name, path) in utils::plugins::list_plugins() {
+        if !builtins.iter().any(|(builtin, _)| *builtin == name) {
+            println!("    {:<20} {}", name, path.display());
+        }
+    }
+}
+
+/// Builds a Move CLI for a binary embedding it, e.g. to run code relying on native functions
+/// beyond those of the Move standard library. The extra natives are available to the sandbox and
+/// to unit tests.
+///
+/// ```no_run
+/// # fn double_native() -> move_vm_runtime::native_functions::NativeFunction { unimplemented!() }
+/// use move_core_types::account_address::AccountAddress;
+///
+/// fn main() -> anyhow::Result<()> {
+///     let addr = AccountAddress::from_hex_literal("0x2")?;
+///     move_cli::MoveCliBuilder::new()?
+///         .with_native(addr, "math", "double", double_native())
+///         .run()
+/// }
+/// ```
+pub struct MoveCliBuilder<'a> {
+    natives: NativeFunctionTable,
+    cost_table: &'a CostTable,
+    error_descriptions: ErrorMapping,
+}
+
+impl<'a> MoveCliBuilder<'a> {
+    /// A CLI with the natives and error descriptions of the Move standard library (including
+    /// the nursery) published at `0x1`, as run by the `move` binary
+    pub fn new() -> Result<Self> {
+        let addr = AccountAddress::ONE;
+        Ok(Self {
+            natives: all_natives(addr, GasParameters::zeros())
+                .into_iter()
+                .chain(nursery_natives(addr, NurseryGasParameters::zeros()))
+                .collect(),
+            cost_table: &INITIAL_COST_SCHEDULE,
+            error_descriptions: bcs::from_bytes(move_stdlib::error_descriptions())?,
+        })
+    }
+
+    /// Add `natives` to the native functions of the CLI. A native replaces any native already
+    /// registered under the same name.
+    pub fn with_natives(mut self, natives: impl IntoIterator<Item = NativeFunctionRecord>) -> Self {
+        for native in natives {
+            self.natives.retain(|(addr, module, function, _)| {
+                (addr, module, function) != (&native.0, &native.1, &native.2)
+            });
+            self.natives.push(native);
+        }
+        self
+    }
+
+    /// Add the native function `address::module::function`
+    pub fn with_native(
+        self,
+        address: AccountAddress,
+        module: &str,
+        function: &str,
+        native: NativeFunction,
+    ) -> Self {
+        let native = (
+            address,
+            Identifier::new(module).expect("Invalid module name"),
+            Identifier::new(function).expect("Invalid function name"),
+            native,
+        );
+        self.with_natives([native])
+    }
+
+    pub fn with_cost_table(mut self, cost_table: &'a CostTable) -> Self {
+        self.cost_table = cost_table;
+        self
+    }
+
+    pub fn with_error_descriptions(mut self, error_descriptions: ErrorMapping) -> Self {
+        self.error_descriptions = error_descriptions;
+        self
+    }
+
+    pub fn natives(&self) -> &NativeFunctionTable {
+        &self.natives
+    }
+
+    /// Run the CLI with the arguments of the process
+    pub fn run(self) -> Result<()> {
+        move_cli(self.natives, self.cost_table, &self.error_descriptions)
+    }
+
+    /// Run the CLI with `args`, the first of which is the name of the binary
+    pub fn run_with_args<I, T>(self, args: I) -> Result<()>
+    where
+        I: IntoIterator<Item = T>,
+        T: Into<OsString> + Clone,
+    {
+        let argv: Vec<OsString> = args.into_iter().map(Into::into).collect();
+        if is_list_request(&argv) {
+            list_commands();
+            return Ok(());
+        }
+        let args = MoveCLI::try_parse_from(&argv)?;
+        run_move_cli(
+            self.natives,
+            self.cost_table,
+            &self.error_descriptions,
+            &argv,
+            args,
+        )
+    }
+}
+
+/// The names of the native functions declared by `modules` that have no implementation in
+/// `natives`, e.g. `0x2::M::f`
+pub fn unresolved_natives<'a>(
+    modules: impl IntoIterator<Item = &'a CompiledModule>,
+    natives: &[NativeFunctionRecord],
+) -> Vec<String> {
+    let mut unresolved = vec![];
+    for module in modules {
+        for function_def in module.function_defs().iter().filter(|def| def.is_native()) {
+            let handle = module.function_handle_at(function_def.function);
+            let module_handle = module.module_handle_at(handle.module);
+            let address = module.address_identifier_at(module_handle.address);
+            let module_name = module.identifier_at(module_handle.name);
+            let function_name = module.identifier_at(handle.name);
+            let resolved = natives.iter().any(|(addr, module, function, _)| {
+                addr == address
+                    && module.as_ident_str() == module_name
+                    && function.as_ident_str() == function_name
+            });
+            if !resolved {
+                unresolved.push(format!(
+                    "0x{}::{}::{}",
+                    address.short_str_lossless(),
+                    module_name,
+                    function_name
+                ));
+            }
+        }
+    }
+    unresolved
+}
+
+/// Explains how to provide the native functions listed by `unresolved_natives`
+pub const UNRESOLVED_NATIVES_HINT: &str = "Native functions beyond those of the Move standard \
+    library must be implemented by the program running the Move CLI: embed the CLI with \
+    `move_cli::MoveCliBuilder::with_natives` to register them.";
+
+/// On SIGINT or SIGTERM, undo the work in progress (e.g., half-downloaded dependencies or
+/// partially saved modules) and exit. Cross-process package locks are released by the OS when the
+/// process exits.
+fn install_interrupt_handler() -> Result<()> {
+    ctrlc::set_handler(|| {
+        interrupt::run_pending_cleanups();
+        std::process::exit(interrupt::INTERRUPTED_EXIT_CODE)
+    })?;
+    Ok(())
 }
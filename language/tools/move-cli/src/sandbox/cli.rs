@@ -5,27 +5,113 @@
 use crate::{
     sandbox::{
         self,
-        utils::{on_disk_state_view::OnDiskStateView, PackageContext},
+        commands::{
+            parse_entry_function, CallGraphQuery, ConflictPreference, ImportMode, MigrateOptions,
+            PublishOptions, RunOptions,
+        },
+        utils::{
+            is_bytecode_file, log_args, on_disk_state_view::OnDiskStateView, parse_run_argument,
+            record_transaction, rotate_transaction_log, AccountRegistry, CoverageTracker,
+            PackageContext, RunArgument,
+        },
+    },
+    utils::{
+        artifact_version::check_artifacts_version,
+        project_config::{PROJECT_CONFIG_DIR, PROJECT_CONFIG_FILE},
+        storage_lock::{StorageAccess, StorageLock, StorageLockOptions},
     },
     Move, NativeFunctionRecord, DEFAULT_BUILD_DIR,
 };
 use anyhow::Result;
-use clap::Parser;
+use clap::{ArgEnum, Parser};
+use move_compiler::shared::{self, NumericalAddress};
 use move_core_types::{
-    errmap::ErrorMapping, language_storage::TypeTag, parser,
-    transaction_argument::TransactionArgument,
+    account_address::AccountAddress,
+    errmap::ErrorMapping,
+    identifier::Identifier,
+    language_storage::{ModuleId, StructTag, TypeTag},
+    parser,
 };
 use move_package::compilation::package_layout::CompiledPackageLayout;
 use move_vm_test_utils::gas_schedule::CostTable;
 use std::{
+    ffi::OsString,
     fs,
     path::{Path, PathBuf},
 };
 
+const PUBLISH_EXAMPLES: &str = "EXAMPLES:
+    Publish the modules of the package:
+        move sandbox publish
+
+    Also publish the modules of its dependencies, e.g. the standard library:
+        move sandbox publish --with-deps
+
+    Publish the package as if its named address `Example` was bound to 0x42:
+        move sandbox publish --address-mapping Example=0x42
+
+    Only publish the `Coin` module, as a bundle:
+        move sandbox publish --include-module Coin --bundle";
+
+const RUN_EXAMPLES: &str = "EXAMPLES:
+    Run a script of the package with a signer and a u64 argument:
+        move sandbox run sources/scripts/mint.move --signers 0xA11CE --args 10
+
+    Call the script function `mint` of a published module, passing its argument by name:
+        move sandbox run storage/0x00000000000000000000000000000002/modules/Coin.mv mint --signers 0xA11CE --arg amount=10
+
+    Print the effects of a script as JSON instead of saving them:
+        move sandbox run sources/scripts/transfer.move --signers 0xA11CE --args 0xB0B 5 --dry-run --format json
+
+    Run a generic script with a gas budget:
+        move sandbox run sources/scripts/swap.move --type-args u64 --gas-budget 10000
+
+    Add the instructions a script executes to the coverage map of `move test --coverage`:
+        move sandbox run sources/scripts/mint.move --signers 0xA11CE --args 10 --track-coverage";
+
+const MIGRATE_EXAMPLES: &str = "EXAMPLES:
+    Move the `CoinV1` resource of every address holding one to its new layout:
+        move sandbox migrate --type 0x2::Coin::CoinV1 --script 0x2::Coin::upgrade
+
+    Check which addresses the migration would fail for, without saving anything:
+        move sandbox migrate --type 0x2::Coin::CoinV1 --script 0x2::Coin::upgrade --dry-run
+
+    Pass a u64 argument to the migration function after the address:
+        move sandbox migrate --type 0x2::Coin::CoinV1 --script 0x2::Coin::upgrade --args 100";
+
+const VIEW_EXAMPLES: &str = "EXAMPLES:
+    Show the public functions of a published module:
+        move sandbox view storage/0x00000000000000000000000000000002/modules/Coin.mv
+
+    Show the disassembly of a published module:
+        move sandbox view storage/0x00000000000000000000000000000002/modules/Coin.mv --disassemble
+
+    Show the first ten events of an event stream as JSON:
+        move sandbox view storage/0x000000000000000000000000000a11ce/events/0.bcs --limit 10 --format json
+
+    List the resources stored at an address with their values:
+        move sandbox view --resources 0xa11ce --decode
+
+    List the modules published at an address as JSON:
+        move sandbox view --modules 0x2 --format json
+
+    List the resources stored at an address in a copy of another sandbox's storage:
+        move sandbox view --resources 0xa11ce --decode --state ../shared/storage";
+
+const IMPORT_EXAMPLES: &str = "EXAMPLES:
+    Load a state exported from another sandbox in place of the state in storage:
+        move sandbox import ../shared/state --replace
+
+    Add an exported state to storage, failing if they hold different versions of anything:
+        move sandbox import ../shared/state --merge
+
+    Add an exported state to storage, keeping the stored version of what both hold:
+        move sandbox import ../shared/state --merge --prefer-local";
+
 #[derive(Parser)]
 pub enum SandboxCommand {
     /// Compile the modules in this package and its dependencies and publish the resulting bytecodes in global storage.
-    #[clap(name = "publish")]
+    #[clap(name = "publish", after_help = PUBLISH_EXAMPLES)]
     Publish {
         /// If set, fail when attempting to publish a module that already
         /// exists in global storage.
@@ -40,6 +126,14 @@ pub enum SandboxCommand {
         /// modules in all its dependencies.
         #[clap(long = "with-deps")]
         with_deps: bool,
+        /// If set, first publish the modules of all dependencies that are missing from storage,
+        /// in dependency order. Fails if storage holds a different version of a dependency module.
+        #[clap(long = "bundle-deps", conflicts_with = "with-deps")]
+        bundle_deps: bool,
+        /// Together with --bundle-deps, republish dependency modules for which storage holds a
+        /// different version instead of failing.
+        #[clap(long = "override-deps", requires = "bundle-deps")]
+        override_deps: bool,
         /// If set, all modules at once as a bundle. The default is to publish
         /// modules sequentially.
         #[clap(long = "bundle")]
@@ -52,10 +146,41 @@ pub enum SandboxCommand {
             multiple_occurrences(true)
         )]
         override_ordering: Option<Vec<String>>,
+        /// Only publish the modules whose name matches this glob pattern (e.g., `Coin*`). Can be
+        /// repeated. Fails if a published module depends on a module left out that is not
+        /// already in storage.
+        #[clap(long = "include-module", takes_value(true), multiple_occurrences(true))]
+        include_modules: Vec<String>,
+        /// Do not publish the modules whose name matches this glob pattern (e.g., `*Tests`). Can
+        /// be repeated, and takes precedence over `--include-module`.
+        #[clap(long = "exclude-module", takes_value(true), multiple_occurrences(true))]
+        exclude_modules: Vec<String>,
+        /// Publish the package as if its named address `name` was bound to `addr`, e.g.
+        /// `--address-mapping Example=0x42`. Can be repeated.
+        #[clap(
+            long = "address-mapping",
+            parse(try_from_str = shared::parse_named_address),
+            takes_value(true),
+            multiple_occurrences(true)
+        )]
+        address_mapping: Vec<(String, NumericalAddress)>,
+        /// Publish the modules in dependency order, this many at a time, saving each batch to
+        /// storage before publishing the next, to bound the memory used by very large packages.
+        /// If a batch fails to publish, the batches published before are rolled back.
+        #[clap(
+            long = "batch-size",
+            conflicts_with_all = &["bundle", "ignore-breaking-changes"]
+        )]
+        batch_size: Option<usize>,
+        /// Print what changed in each module that storage already holds: the functions, structs
+        /// and constants added, removed or changed, and the change in size. Always printed for
+        /// the module that fails the compatibility check.
+        #[clap(long = "show-diff")]
+        show_diff: bool,
     },
     /// Run a Move script that reads/writes resources stored on disk in `storage-dir`.
     /// The script must be defined in the package.
-    #[clap(name = "run")]
+    #[clap(name = "run", after_help = RUN_EXAMPLES)]
     Run {
         /// Path to .mv file containing either script or module bytecodes. If the file is a module, the
         /// `script_name` parameter must be set.
@@ -67,6 +192,9 @@ pub enum SandboxCommand {
         script_name: Option<String>,
         /// Possibly-empty list of signers for the current transaction (e.g., `account` in
         /// `main(&account: signer)`). Must match the number of signers expected by `script_file`.
+        /// `@<name>` stands for the account of that name created by `sandbox account create`.
+        /// Defaults to the `default-signers` of the `[sandbox]` section of `.move/config.toml` at
+        /// the root of the package, if any.
         #[clap(
             long = "signers",
             takes_value(true),
@@ -74,22 +202,36 @@ pub enum SandboxCommand {
             multiple_occurrences(true)
         )]
         signers: Vec<String>,
+        /// Do not use the `default-signers` of `.move/config.toml` when `--signers` is not passed.
+        #[clap(long = "no-default-signers", conflicts_with = "signers")]
+        no_default_signers: bool,
         /// Possibly-empty list of arguments passed to the transaction (e.g., `i` in
         /// `main(i: u64)`). Must match the arguments types expected by `script_file`.
         /// Supported argument types are
         /// bool literals (true, false),
         /// u64 literals (e.g., 10, 58),
         /// address literals (e.g., 0x12, 0x0000000000000000000000000000000f),
-        /// hexadecimal strings (e.g., x"0012" will parse as the vector<u8> value [00, 12]), and
-        /// ASCII strings (e.g., 'b"hi" will parse as the vector<u8> value [68, 69]).
+        /// hexadecimal strings (e.g., x"0012" will parse as the vector<u8> value [00, 12]),
+        /// ASCII strings (e.g., 'b"hi" will parse as the vector<u8> value [68, 69]), and
+        /// the addresses of the accounts created by `sandbox account create` (e.g., @alice).
         #[clap(
             long = "args",
-            parse(try_from_str = parser::parse_transaction_argument),
+            parse(try_from_str = parse_run_argument),
             takes_value(true),
             multiple_values(true),
             multiple_occurrences(true)
         )]
-        args: Vec<TransactionArgument>,
+        args: Vec<RunArgument>,
+        /// Argument passed to the parameter of the same name, as `<name>=<value>` (e.g.,
+        /// `--arg amount=10`). Can be repeated to pass every non-signer parameter by name, in any
+        /// order. Cannot be mixed with `--args`.
+        #[clap(
+            long = "arg",
+            parse(try_from_str = parse_named_argument),
+            takes_value(true),
+            multiple_occurrences(true)
+        )]
+        named_args: Vec<(String, RunArgument)>,
         /// Possibly-empty list of type arguments passed to the transaction (e.g., `T` in
         /// `main<T>()`). Must match the type arguments kinds expected by `script_file`.
         #[clap(
@@ -105,12 +247,84 @@ pub enum SandboxCommand {
         /// By default, no `gas-budget` is specified and gas metering is disabled.
         #[clap(long = "gas-budget", short = 'g')]
         gas_budget: Option<u64>,
+        /// Maximum memory, in MB, that the vectors built by execution may hold. When the quota is
+        /// exceeded, execution fails with a MEMORY_QUOTA_EXCEEDED error. If set, the peak memory
+        /// is reported after execution. Defaults to 1024 MB.
+        #[clap(long = "memory-quota", value_name = "MB")]
+        memory_quota: Option<u64>,
+        /// Time the test-only `std::timestamp` reports during execution, in microseconds.
+        #[clap(
+            long = "initial-time",
+            value_name = "MICROSECONDS",
+            default_value = "0"
+        )]
+        initial_time: u64,
+        /// Block height the test-only `std::timestamp` reports during execution.
+        #[clap(long = "initial-height", default_value = "0")]
+        initial_height: u64,
         /// If set, the effects of executing `script_file` (i.e., published, updated, and
-        /// deleted resources) will NOT be committed to disk.
+        /// deleted resources) will NOT be committed to disk. They are printed instead, along with
+        /// the emitted events and the gas used.
         #[clap(long = "dry-run", short = 'n')]
         dry_run: bool,
+        /// Output format of the effects printed by `--dry-run`.
+        #[clap(long = "format", arg_enum, ignore_case = true, default_value = "text")]
+        format: OutputFormat,
+        /// If set, abort codes are not decoded into the names of the error constants they stand
+        /// for. By default, they are looked up in the error maps generated by `move errmap` in the
+        /// root directories of the package and of its dependencies.
+        #[clap(long = "no-errmap-lookup")]
+        no_errmap_lookup: bool,
+        /// Run a bytecode file from the build directory of a package even if the package was
+        /// built by another major version of Move or with another bytecode format version.
+        #[clap(long = "ignore-version-mismatch")]
+        ignore_version_mismatch: bool,
+        /// Add the instructions executed by the transaction to the coverage map of the package,
+        /// which `move coverage` reports on along with the coverage of the unit tests. Only the
+        /// modules stored as the package currently builds them are counted.
+        #[clap(long = "track-coverage")]
+        track_coverage: bool,
     },
-    /// Run expected value tests using the given batch file.
+    /// Run an entry function of a published module once for every address storing a resource of
+    /// a given type, e.g. to reshape the resource after an upgrade of its module. The effects of
+    /// each run that succeeds are saved, and the addresses for which it aborts are listed.
+    #[clap(name = "migrate", after_help = MIGRATE_EXAMPLES)]
+    Migrate {
+        /// Type of the resource to migrate, e.g. `0x2::Coin::CoinV1`.
+        #[clap(long = "type", parse(try_from_str = parse_struct_tag))]
+        resource: StructTag,
+        /// Entry function run for each address storing the resource, e.g. `0x2::Coin::upgrade`.
+        /// Its first parameter is passed the address, as a signer or as an address depending on
+        /// its type.
+        #[clap(long = "script", parse(try_from_str = parse_entry_function))]
+        script: (ModuleId, Identifier),
+        /// Arguments passed to the entry function after the address, as for `sandbox run`.
+        #[clap(
+            long = "args",
+            parse(try_from_str = parse_run_argument),
+            takes_value(true),
+            multiple_values(true),
+            multiple_occurrences(true)
+        )]
+        args: Vec<RunArgument>,
+        /// Maximum number of gas units the entry function may consume for each address.
+        #[clap(long = "gas-budget", short = 'g')]
+        gas_budget: Option<u64>,
+        /// Print the effects the entry function would have for each address instead of saving
+        /// them.
+        #[clap(long = "dry-run", short = 'n')]
+        dry_run: bool,
+        /// If set, abort codes are not decoded into the names of the error constants they stand
+        /// for.
+        #[clap(long = "no-errmap-lookup")]
+        no_errmap_lookup: bool,
+        /// Add the instructions executed for every address to the coverage map of the package,
+        /// as for `sandbox run`.
+        #[clap(long = "track-coverage")]
+        track_coverage: bool,
+    },
+    /// Run expected value tests using the given batch file. The integration tests in the
+    /// `integration-tests` directory of a package are run as well.
     #[clap(name = "exp-test")]
     Test {
         /// Use an ephemeral directory to serve as the testing workspace.
@@ -121,20 +335,185 @@ pub enum SandboxCommand {
         /// By default, coverage will not be tracked nor shown.
         #[clap(long = "track-cov")]
         track_cov: bool,
+        /// Overwrite the `expected.txt` and `expected.json` files of the integration tests with
+        /// their actual outcomes instead of checking them.
+        #[clap(long = "update-expected")]
+        update_expected: bool,
     },
-    /// View Move resources, events files, and modules stored on disk.
-    #[clap(name = "view")]
+    /// View Move resources, events files, and modules stored on disk. Modules are shown with their
+    /// address, name, size, bytecode version, dependencies and public functions.
+    #[clap(name = "view", after_help = VIEW_EXAMPLES)]
     View {
         /// Path to a resource, events file, or module stored on disk.
-        #[clap(name = "file", parse(from_os_str))]
-        file: PathBuf,
+        #[clap(
+            name = "file",
+            parse(from_os_str),
+            required_unless_present_any = &["resources", "modules"]
+        )]
+        file: Option<PathBuf>,
+        /// List every resource stored at `address` with its type and size, sorted by type,
+        /// instead of viewing a file.
+        #[clap(
+            long = "resources",
+            value_name = "ADDRESS",
+            parse(try_from_str = AccountAddress::from_hex_literal),
+            conflicts_with_all = &["file", "modules"]
+        )]
+        resources: Option<AccountAddress>,
+        /// List every module published at `address` with its size and bytecode version, sorted
+        /// by name, instead of viewing a file.
+        #[clap(
+            long = "modules",
+            value_name = "ADDRESS",
+            parse(try_from_str = AccountAddress::from_hex_literal),
+            conflicts_with = "file"
+        )]
+        modules: Option<AccountAddress>,
+        /// When listing resources, also show their decoded values.
+        #[clap(long = "decode", requires = "resources")]
+        decode: bool,
+        /// When viewing a module, show its disassembly instead of its public functions.
+        #[clap(long = "disassemble")]
+        disassemble: bool,
+        /// When viewing events, skip the events with a lower sequence number.
+        #[clap(long = "from-seq", default_value = "0")]
+        from_seq: u64,
+        /// When viewing events, show at most `limit` events.
+        #[clap(long = "limit")]
+        limit: Option<usize>,
+        /// Output format of a module's metadata, of events, or of a listing. `json` is only
+        /// supported for modules, events and listings, and includes the `next_seq` of events to
+        /// pass as `--from-seq` for the next page.
+        #[clap(long = "format", arg_enum, ignore_case = true, default_value = "text")]
+        format: OutputFormat,
+        /// Read from this storage directory, e.g. a copy of the storage of another sandbox,
+        /// instead of `storage-dir`. It is only read: the package is not built and its
+        /// dependencies are not added to it, so values are decoded with the modules it holds.
+        /// Files to view are given by their path under this directory.
+        #[clap(long = "state", value_name = "DIR", parse(from_os_str))]
+        state: Option<PathBuf>,
     },
     /// Delete all resources, events, and modules stored on disk under `storage-dir`.
-    /// Does *not* delete anything in `src`.
-    Clean {},
+    /// Does *not* delete anything in `src`. The transaction log is rotated to `<storage-dir>.logs`.
+    Clean {
+        /// Keep the accounts created by `sandbox account create`
+        #[clap(long = "keep-accounts")]
+        keep_accounts: bool,
+    },
+    /// Copy the modules, resources, events and accounts stored on disk under `storage-dir` to a
+    /// directory, to be loaded into another sandbox with `sandbox import`. The transaction log is
+    /// not copied.
+    #[clap(name = "export")]
+    Export {
+        /// The directory to copy the state to, which must be empty or not exist
+        #[clap(parse(from_os_str))]
+        dir: PathBuf,
+    },
+    /// Load a state written by `sandbox export` into the storage under `storage-dir`. The new
+    /// state is written next to storage and swapped into place once complete, so that an import
+    /// that fails or is interrupted leaves storage as it was. The import is recorded in the
+    /// transaction log.
+    #[clap(name = "import", after_help = IMPORT_EXAMPLES)]
+    Import {
+        /// The directory `sandbox export` wrote the state to
+        #[clap(parse(from_os_str))]
+        dir: PathBuf,
+        /// Replace the state in storage with the imported one
+        #[clap(
+            long = "replace",
+            conflicts_with = "merge",
+            required_unless_present = "merge"
+        )]
+        replace: bool,
+        /// Add the imported state to the state in storage. The modules, resources, event streams
+        /// and accounts both hold with different contents are listed as conflicts and fail the
+        /// import, unless --prefer-import or --prefer-local is given.
+        #[clap(long = "merge")]
+        merge: bool,
+        /// With --merge, keep the imported version of what conflicts
+        #[clap(
+            long = "prefer-import",
+            requires = "merge",
+            conflicts_with = "prefer-local"
+        )]
+        prefer_import: bool,
+        /// With --merge, keep the stored version of what conflicts
+        #[clap(long = "prefer-local", requires = "merge")]
+        prefer_local: bool,
+    },
+    /// Show the publishes and runs that produced the state stored on disk under `storage-dir`.
+    #[clap(name = "log")]
+    Log {
+        /// Only show the `last` most recent entries.
+        #[clap(long = "last")]
+        last: Option<usize>,
+        /// Output format of the log.
+        #[clap(long = "format", arg_enum, ignore_case = true, default_value = "text")]
+        format: OutputFormat,
+    },
     /// Run well-formedness checks on the `storage-dir` and `install-dir` directories.
     #[clap(name = "doctor")]
     Doctor {},
+    /// Run the bytecode verifier and the linker over every module stored on disk under
+    /// `storage-dir`, e.g. after upgrading Move, and report the modules that fail them. Storage is
+    /// only read: the package is not built and its dependencies are not added to it.
+    #[clap(name = "verify-storage")]
+    VerifyStorage {
+        /// Verify the modules of this storage directory, e.g. a copy of the storage of another
+        /// sandbox, instead of `storage-dir`.
+        #[clap(long = "state", parse(from_os_str))]
+        state: Option<PathBuf>,
+        /// Number of modules to verify in parallel.
+        #[clap(long = "jobs", short = 'j', default_value = "1")]
+        jobs: usize,
+        /// Output format of the report.
+        #[clap(long = "format", arg_enum, ignore_case = true, default_value = "text")]
+        format: OutputFormat,
+    },
+    /// Print the static call graph of the modules stored on disk under `storage-dir`, or find the
+    /// functions that can reach a function, e.g. a sensitive one. Move has no dynamic dispatch, so
+    /// the graph holds exactly the calls functions can make. Storage is only read.
+    #[clap(name = "call-graph")]
+    CallGraph {
+        /// List every function that can call this function, directly or not, e.g.
+        /// `0x2::Coin::burn`, with the shortest chain of calls from each.
+        #[clap(
+            long = "callers-of",
+            value_name = "FUNCTION",
+            parse(try_from_str = parse_entry_function),
+            conflicts_with = "callees-of"
+        )]
+        callers_of: Option<(ModuleId, Identifier)>,
+        /// List every function this function can call, directly or not, with the shortest chain
+        /// of calls to each. Native functions are marked as such.
+        #[clap(
+            long = "callees-of",
+            value_name = "FUNCTION",
+            parse(try_from_str = parse_entry_function)
+        )]
+        callees_of: Option<(ModuleId, Identifier)>,
+        /// Print the graph, or the part of it `--callers-of` or `--callees-of` finds, in the DOT
+        /// format of Graphviz.
+        #[clap(long = "dot")]
+        dot: bool,
+        /// Use the modules of the package and of its dependencies, as built, instead of the
+        /// modules in storage.
+        #[clap(long = "package")]
+        package: bool,
+    },
+    /// Work with the traces of executions recorded by setting `MOVE_VM_TRACE` to a file, and
+    /// `MOVE_VM_TRACE_VALUES` to record the values too.
+    #[clap(name = "trace")]
+    Trace {
+        #[clap(subcommand)]
+        cmd: TraceCommand,
+    },
+    /// Create and list named accounts, whose addresses can be passed to `run` as `@<name>`.
+    #[clap(name = "account")]
+    Account {
+        #[clap(subcommand)]
+        cmd: AccountCommand,
+    },
     /// Generate struct layout bindings for the modules stored on disk under `storage-dir`
     // TODO: expand this to generate script bindings, etc.?.
     #[clap(name = "generate")]
@@ -144,18 +523,65 @@ pub enum SandboxCommand {
     },
 }
 
+#[derive(Parser)]
+pub enum AccountCommand {
+    /// Create an account with a fresh address, recorded in `storage-dir/accounts.toml`. Addresses
+    /// are derived from the number of accounts created so far, so the same commands always create
+    /// the same accounts.
+    #[clap(name = "create")]
+    Create {
+        /// Name the account is referred to by, as `@<name>`. Defaults to `account<N>` for the
+        /// `N`th account created.
+        #[clap(long = "name")]
+        name: Option<String>,
+    },
+    /// List the accounts created by `sandbox account create`, with their addresses.
+    #[clap(name = "list")]
+    List {},
+}
+
+#[derive(Parser)]
+pub enum TraceCommand {
+    /// Step through a recorded execution, forward and back, with breakpoints, printing the locals
+    /// and the operand stack where the trace records them. Commands are read from stdin, one per
+    /// line: type `help` to list them. Nothing is executed again.
+    #[clap(name = "debug")]
+    Debug {
+        /// The trace file, e.g. `MOVE_VM_TRACE=run.trace move sandbox run ...` records `run.trace`.
+        #[clap(parse(from_os_str))]
+        trace: PathBuf,
+        /// Debug this execution of the trace, counting from 1, instead of the last one. Every
+        /// process run with `MOVE_VM_TRACE` set appends its executions to the file.
+        #[clap(long = "execution")]
+        execution: Option<usize>,
+    },
+}
+
 #[derive(Parser)]
 pub enum GenerateCommand {
     /// Generate struct layout bindings for the modules stored on disk under `storage-dir`.
     #[clap(name = "struct-layouts")]
     StructLayouts {
         /// Path to a module stored on disk.
-        #[clap(long, parse(from_os_str))]
-        module: PathBuf,
+        #[clap(long, parse(from_os_str), required_unless_present = "all")]
+        module: Option<PathBuf>,
         /// If set, generate bindings for the specified struct and type arguments. If unset,
         /// generate bindings for all closed struct definitions.
         #[clap(flatten)]
         options: StructLayoutOptions,
+        /// Generate bindings for every struct with the `key` or `store` ability of every module in
+        /// storage, each into its own file under `output-dir`, along with an index mapping type
+        /// tags to these files.
+        #[clap(long = "all", conflicts_with_all = &["module", "struct"])]
+        all: bool,
+        /// With `--all`, generate bindings for the modules of the package instead of the modules
+        /// in storage.
+        #[clap(long = "package", requires = "all")]
+        package: bool,
+        /// Directory `--all` generates bindings into. Defaults to `struct_layouts` under the build
+        /// directory.
+        #[clap(long = "output-dir", parse(from_os_str), requires = "all")]
+        output_dir: Option<PathBuf>,
     },
 }
 #[derive(Parser)]
@@ -179,7 +605,61 @@ pub struct StructLayoutOptions {
     shallow: bool,
 }
 
+#[derive(Debug, Clone, Copy, ArgEnum)]
+pub enum OutputFormat {
+    // Human-readable output, e.g. one line per log entry followed by its changes
+    Text,
+    // Machine-readable output, e.g. the raw log entries as a JSON array
+    Json,
+}
+
+impl Default for OutputFormat {
+    fn default() -> Self {
+        Self::Text
+    }
+}
+
 impl SandboxCommand {
+    /// The storage directory the command uses, its name, and how it uses the directory, or `None`
+    /// if it does not use `storage_dir`. `view` shares storage although it adds the modules of the
+    /// dependencies of the package missing from it, as any command adds the same ones.
+    fn storage_access<'a>(
+        &'a self,
+        storage_dir: &'a Path,
+    ) -> Option<(&'a Path, &'static str, StorageAccess)> {
+        use StorageAccess::*;
+        Some(match self {
+            SandboxCommand::Publish { .. } => (storage_dir, "sandbox publish", Exclusive),
+            SandboxCommand::Run { .. } => (storage_dir, "sandbox run", Exclusive),
+            SandboxCommand::Migrate { .. } => (storage_dir, "sandbox migrate", Exclusive),
+            SandboxCommand::Clean { .. } => (storage_dir, "sandbox clean", Exclusive),
+            SandboxCommand::Import { .. } => (storage_dir, "sandbox import", Exclusive),
+            SandboxCommand::Export { .. } => (storage_dir, "sandbox export", Shared),
+            SandboxCommand::Doctor {} => (storage_dir, "sandbox doctor", Exclusive),
+            SandboxCommand::Generate { .. } => (storage_dir, "sandbox generate", Exclusive),
+            SandboxCommand::Account {
+                cmd: AccountCommand::Create { .. },
+            } => (storage_dir, "sandbox account create", Exclusive),
+            SandboxCommand::Account {
+                cmd: AccountCommand::List {},
+            } => (storage_dir, "sandbox account list", Shared),
+            SandboxCommand::View { state, .. } => (
+                state.as_deref().unwrap_or(storage_dir),
+                "sandbox view",
+                Shared,
+            ),
+            SandboxCommand::VerifyStorage { state, .. } => (
+                state.as_deref().unwrap_or(storage_dir),
+                "sandbox verify-storage",
+                Shared,
+            ),
+            SandboxCommand::CallGraph { .. } => (storage_dir, "sandbox call-graph", Shared),
+            SandboxCommand::Log { .. } => (storage_dir, "sandbox log", Shared),
+            // tests use storage of their own, and traces are recorded outside of storage
+            SandboxCommand::Test { .. } | SandboxCommand::Trace { .. } => return None,
+        })
+    }
+
     pub fn handle_command(
         &self,
         natives: Vec<NativeFunctionRecord>,
@@ -187,62 +667,235 @@ impl SandboxCommand {
         error_descriptions: &ErrorMapping,
         move_args: &Move,
         storage_dir: &Path,
+        lock_options: &StorageLockOptions,
+        argv: &[OsString],
     ) -> Result<()> {
+        let _lock = match self.storage_access(storage_dir) {
+            Some((dir, command, access)) => {
+                Some(StorageLock::acquire(dir, access, command, lock_options)?)
+            }
+            None => None,
+        };
+        let log_args = log_args(argv);
         match self {
             SandboxCommand::Publish {
                 no_republish,
                 ignore_breaking_changes,
                 with_deps,
+                bundle_deps,
+                override_deps,
                 bundle,
                 override_ordering,
+                include_modules,
+                exclude_modules,
+                address_mapping,
+                batch_size,
+                show_diff,
             } => {
                 let context =
                     PackageContext::new(&move_args.package_path, &move_args.build_config)?;
-                let state = context.prepare_state(storage_dir)?;
+                // with --bundle-deps, the dependencies are checked and installed by `publish`
+                let state = if *bundle_deps {
+                    context.open_state(storage_dir)?
+                } else {
+                    context.prepare_state(storage_dir)?
+                };
+                let options = PublishOptions {
+                    no_republish: *no_republish,
+                    ignore_breaking_changes: *ignore_breaking_changes,
+                    with_deps: *with_deps,
+                    bundle_deps: *bundle_deps,
+                    override_deps: *override_deps,
+                    bundle: *bundle,
+                    override_ordering: override_ordering.clone(),
+                    include_modules: include_modules.clone(),
+                    exclude_modules: exclude_modules.clone(),
+                    address_mapping: address_mapping.clone(),
+                    package_path: context.package_path().to_path_buf(),
+                    batch_size: *batch_size,
+                    show_diff: *show_diff,
+                    verbose: move_args.verbose,
+                    log_args,
+                };
                 sandbox::commands::publish(
                     natives,
                     cost_table,
                     &state,
                     context.package(),
-                    *no_republish,
-                    *ignore_breaking_changes,
-                    *with_deps,
-                    *bundle,
-                    override_ordering.as_ref().map(|o| o.as_slice()),
-                    move_args.verbose,
-                )
+                    &options,
+                )?;
+                Ok(())
             }
             SandboxCommand::Run {
                 script_file,
                 script_name,
                 signers,
+                no_default_signers,
                 args,
+                named_args,
                 type_args,
                 gas_budget,
+                memory_quota,
+                initial_time,
+                initial_height,
                 dry_run,
+                format,
+                no_errmap_lookup,
+                ignore_version_mismatch,
+                track_coverage,
             } => {
                 let context =
                     PackageContext::new(&move_args.package_path, &move_args.build_config)?;
+                // bytecode files under `build/<package>/bytecode_scripts` may have been built by
+                // another version of Move
+                if is_bytecode_file(script_file) {
+                    if let Some(package_build_dir) = script_file.parent().and_then(Path::parent) {
+                        check_artifacts_version(
+                            package_build_dir,
+                            &move_args.build_config,
+                            *ignore_version_mismatch,
+                        )?;
+                    }
+                }
                 let state = context.prepare_state(storage_dir)?;
-                sandbox::commands::run(
+                let error_map = if *no_errmap_lookup {
+                    ErrorMapping::default()
+                } else {
+                    context.error_map(error_descriptions)
+                };
+                let coverage_tracker = if *track_coverage {
+                    Some(CoverageTracker::start(
+                        context.coverage_map_path(&move_args.build_config),
+                    )?)
+                } else {
+                    None
+                };
+                let accounts = AccountRegistry::load(storage_dir)?;
+                let signers = if signers.is_empty() && !*no_default_signers {
+                    let default_signers: Vec<_> = context
+                        .default_signers()?
+                        .iter()
+                        .map(|signer| format!("0x{}", signer.short_str_lossless()))
+                        .collect();
+                    if !default_signers.is_empty() {
+                        let message = format!(
+                            "Using the default signers from {}: {}",
+                            Path::new(PROJECT_CONFIG_DIR)
+                                .join(PROJECT_CONFIG_FILE)
+                                .display(),
+                            default_signers.join(", ")
+                        );
+                        // stdout only holds the JSON output
+                        match format {
+                            OutputFormat::Text => println!("{}", message),
+                            OutputFormat::Json => eprintln!("{}", message),
+                        }
+                    }
+                    default_signers
+                } else {
+                    signers
+                        .iter()
+                        .map(|signer| accounts.resolve_signer(signer))
+                        .collect::<Result<_>>()?
+                };
+                let options = RunOptions {
+                    script_path: script_file.clone(),
+                    script_name: script_name.clone(),
+                    signers,
+                    args: args
+                        .iter()
+                        .map(|arg| accounts.resolve_argument(arg))
+                        .collect::<Result<_>>()?,
+                    named_args: named_args
+                        .iter()
+                        .map(|(name, arg)| Ok((name.clone(), accounts.resolve_argument(arg)?)))
+                        .collect::<Result<_>>()?,
+                    type_args: type_args.clone(),
+                    gas_budget: *gas_budget,
+                    memory_quota: *memory_quota,
+                    initial_time: *initial_time,
+                    initial_height: *initial_height,
+                    dry_run: *dry_run,
+                    format: *format,
+                    verbose: move_args.verbose,
+                };
+                let output = sandbox::commands::run(
                     natives,
                     cost_table,
-                    error_descriptions,
+                    &error_map,
                     &state,
                     context.package(),
-                    script_file,
-                    script_name,
-                    signers,
-                    args,
-                    type_args.to_vec(),
-                    *gas_budget,
-                    *dry_run,
-                    move_args.verbose,
-                )
+                    &options,
+                )?;
+                if output.error.is_none() && !*dry_run {
+                    record_transaction(
+                        state.storage_dir(),
+                        "run",
+                        &log_args,
+                        output.changes,
+                        output.gas_used,
+                    )?;
+                }
+                if let Some(coverage_tracker) = coverage_tracker {
+                    coverage_tracker.finish(&state, context.package())?;
+                }
+                Ok(())
+            }
+            SandboxCommand::Migrate {
+                resource,
+                script: (module_id, function),
+                args,
+                gas_budget,
+                dry_run,
+                no_errmap_lookup,
+                track_coverage,
+            } => {
+                let context =
+                    PackageContext::new(&move_args.package_path, &move_args.build_config)?;
+                let state = context.prepare_state(storage_dir)?;
+                let error_map = if *no_errmap_lookup {
+                    ErrorMapping::default()
+                } else {
+                    context.error_map(error_descriptions)
+                };
+                let coverage_tracker = if *track_coverage {
+                    Some(CoverageTracker::start(
+                        context.coverage_map_path(&move_args.build_config),
+                    )?)
+                } else {
+                    None
+                };
+                let accounts = AccountRegistry::load(storage_dir)?;
+                let options = MigrateOptions {
+                    resource: resource.clone(),
+                    module_id: module_id.clone(),
+                    function: function.clone(),
+                    args: args
+                        .iter()
+                        .map(|arg| accounts.resolve_argument(arg))
+                        .collect::<Result<_>>()?,
+                    gas_budget: *gas_budget,
+                    dry_run: *dry_run,
+                    verbose: move_args.verbose,
+                    log_args,
+                };
+                sandbox::commands::migrate(
+                    natives,
+                    cost_table,
+                    &error_map,
+                    &state,
+                    context.package(),
+                    &options,
+                )?;
+                if let Some(coverage_tracker) = coverage_tracker {
+                    coverage_tracker.finish(&state, context.package())?;
+                }
+                Ok(())
             }
             SandboxCommand::Test {
                 use_temp_dir,
                 track_cov,
+                update_expected,
             } => sandbox::commands::run_all(
                 move_args
                     .package_path
@@ -251,18 +904,59 @@ impl SandboxCommand {
                 &std::env::current_exe()?,
                 *use_temp_dir,
                 *track_cov,
-            ),
-            SandboxCommand::View { file } => {
-                let state = PackageContext::new(&move_args.package_path, &move_args.build_config)?
-                    .prepare_state(storage_dir)?;
-                sandbox::commands::view(&state, file)
+                *update_expected,
+            )?
+            .check(),
+            SandboxCommand::View {
+                file,
+                resources,
+                modules,
+                decode,
+                disassemble,
+                from_seq,
+                limit,
+                format,
+                state,
+            } => {
+                let state = match state {
+                    Some(state_dir) => OnDiskStateView::open_read_only(state_dir)?,
+                    None => PackageContext::new(&move_args.package_path, &move_args.build_config)?
+                        .prepare_state(storage_dir)?,
+                };
+                match (file, resources, modules) {
+                    (_, Some(address), _) => {
+                        sandbox::commands::view_resources(&state, address, *decode, *format)
+                    }
+                    (_, _, Some(address)) => {
+                        sandbox::commands::view_modules(&state, address, *format)
+                    }
+                    (Some(file), None, None) => {
+                        let events = sandbox::commands::EventRange {
+                            from_seq: *from_seq,
+                            limit: *limit,
+                        };
+                        sandbox::commands::view(&state, file, *disassemble, events, *format)
+                    }
+                    (None, None, None) => unreachable!("clap requires a file or an address"),
+                }
             }
-            SandboxCommand::Clean {} => {
-                // delete storage
+            SandboxCommand::Clean { keep_accounts } => {
+                // delete storage, keeping its transaction log around
                 let storage_dir = Path::new(storage_dir);
+                let accounts = if *keep_accounts {
+                    Some(AccountRegistry::load(storage_dir)?)
+                } else {
+                    None
+                };
+                rotate_transaction_log(storage_dir, "clean", &log_args)?;
                 if storage_dir.exists() {
                     fs::remove_dir_all(&storage_dir)?;
                 }
+                if let Some(accounts) = accounts {
+                    if !accounts.accounts().is_empty() {
+                        accounts.save(storage_dir)?;
+                    }
+                }
 
                 // delete build
                 let build_dir = Path::new(
@@ -278,30 +972,150 @@ impl SandboxCommand {
                 }
                 Ok(())
             }
+            SandboxCommand::Export { dir } => sandbox::commands::export(storage_dir, dir),
+            SandboxCommand::Import {
+                dir,
+                replace,
+                merge: _,
+                prefer_import,
+                prefer_local,
+            } => {
+                let mode = if *replace {
+                    ImportMode::Replace
+                } else if *prefer_import {
+                    ImportMode::Merge(Some(ConflictPreference::Import))
+                } else if *prefer_local {
+                    ImportMode::Merge(Some(ConflictPreference::Local))
+                } else {
+                    ImportMode::Merge(None)
+                };
+                sandbox::commands::import(storage_dir, dir, mode, &log_args)
+            }
+            SandboxCommand::Log { last, format } => {
+                sandbox::commands::log(storage_dir, *last, *format)
+            }
             SandboxCommand::Doctor {} => {
                 let state = PackageContext::new(&move_args.package_path, &move_args.build_config)?
                     .prepare_state(storage_dir)?;
                 sandbox::commands::doctor(&state)
             }
+            SandboxCommand::VerifyStorage {
+                state,
+                jobs,
+                format,
+            } => {
+                let state =
+                    OnDiskStateView::open_read_only(state.as_deref().unwrap_or(storage_dir))?;
+                sandbox::commands::verify_storage(&state, *jobs, *format)
+            }
+            SandboxCommand::CallGraph {
+                callers_of,
+                callees_of,
+                dot,
+                package,
+            } => {
+                let modules = if *package {
+                    PackageContext::new(&move_args.package_path, &move_args.build_config)?
+                        .package()
+                        .all_modules_map()
+                        .iter_modules_owned()
+                } else {
+                    OnDiskStateView::open_read_only(storage_dir)?.get_all_modules()?
+                };
+                let query = match (callers_of, callees_of) {
+                    (Some(function), _) => CallGraphQuery::CallersOf(function.clone()),
+                    (None, Some(function)) => CallGraphQuery::CalleesOf(function.clone()),
+                    (None, None) => CallGraphQuery::All,
+                };
+                sandbox::commands::call_graph(&modules, &query, *dot)
+            }
+            SandboxCommand::Trace { cmd } => match cmd {
+                TraceCommand::Debug { trace, execution } => {
+                    sandbox::commands::debug_trace_file(trace, *execution)
+                }
+            },
+            SandboxCommand::Account { cmd } => handle_account_commands(cmd, storage_dir),
             SandboxCommand::Generate { cmd } => {
-                let state = PackageContext::new(&move_args.package_path, &move_args.build_config)?
-                    .prepare_state(storage_dir)?;
-                handle_generate_commands(cmd, &state)
+                let context =
+                    PackageContext::new(&move_args.package_path, &move_args.build_config)?;
+                let state = context.prepare_state(storage_dir)?;
+                handle_generate_commands(cmd, &context, &state)
+            }
+        }
+    }
+}
+
+fn handle_account_commands(cmd: &AccountCommand, storage_dir: &Path) -> Result<()> {
+    let mut accounts = AccountRegistry::load(storage_dir)?;
+    match cmd {
+        AccountCommand::Create { name } => {
+            let account = accounts.create(name.as_deref())?.clone();
+            accounts.save(storage_dir)?;
+            println!("Created account @{} at {}", account.name, account.address);
+        }
+        AccountCommand::List {} => {
+            if accounts.accounts().is_empty() {
+                println!("No accounts. Create one with `move sandbox account create`");
+            }
+            for account in accounts.accounts() {
+                println!("{:<20} {}", format!("@{}", account.name), account.address);
             }
         }
     }
+    Ok(())
 }
 
-fn handle_generate_commands(cmd: &GenerateCommand, state: &OnDiskStateView) -> Result<()> {
+fn handle_generate_commands(
+    cmd: &GenerateCommand,
+    context: &PackageContext,
+    state: &OnDiskStateView,
+) -> Result<()> {
     match cmd {
-        GenerateCommand::StructLayouts { module, options } => {
-            sandbox::commands::generate::generate_struct_layouts(
-                module,
-                &options.struct_,
-                &options.type_args,
+        GenerateCommand::StructLayouts {
+            module: Some(module),
+            options,
+            ..
+        } => sandbox::commands::generate::generate_struct_layouts(
+            module,
+            &options.struct_,
+            &options.type_args,
+            options.shallow,
+            state,
+        ),
+        GenerateCommand::StructLayouts {
+            module: None,
+            options,
+            package,
+            output_dir,
+            ..
+        } => {
+            let modules = if *package {
+                context.package().root_modules_map().iter_modules_owned()
+            } else {
+                state.get_all_modules()?
+            };
+            sandbox::commands::generate::generate_all_struct_layouts(
+                &modules,
                 options.shallow,
+                &output_dir
+                    .clone()
+                    .unwrap_or_else(|| state.struct_layouts_dir()),
                 state,
             )
         }
     }
 }
+
+fn parse_named_argument(s: &str) -> Result<(String, RunArgument)> {
+    match s.split_once('=') {
+        Some((name, value)) => Ok((name.to_string(), parse_run_argument(value)?)),
+        None => anyhow::bail!("Invalid argument `{}`, expected `<name>=<value>`", s),
+    }
+}
+
+fn parse_struct_tag(s: &str) -> Result<StructTag> {
+    match parser::parse_type_tag(s)? {
+        TypeTag::Struct(tag) => Ok(tag),
+        _ => anyhow::bail!("Invalid type `{}`, expected a struct type", s),
+    }
+}
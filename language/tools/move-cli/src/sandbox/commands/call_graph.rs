@@ -0,0 +1,274 @@
+// Copyright (c) The Move Contributors
+// SPDX-License-Identifier: Apache-2.0
+
+use anyhow::{bail, Result};
+use move_binary_format::{access::ModuleAccess, file_format::Bytecode, CompiledModule};
+use move_core_types::{identifier::Identifier, language_storage::ModuleId};
+use std::{
+    collections::{BTreeMap, BTreeSet, VecDeque},
+    fmt::Write,
+};
+
+/// A function, identified by its module and name
+pub type FunctionId = (ModuleId, Identifier);
+
+/// The kind of a function of a `CallGraph`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FunctionKind {
+    /// Defined with code by one of the modules of the graph
+    Defined,
+    /// Native, implemented by the VM
+    Native,
+    /// Called by one of the modules of the graph, but defined by a module left out of it
+    Missing,
+}
+
+/// The static call graph of a set of modules. Move has no dynamic dispatch, so the graph holds
+/// exactly the calls each function can make. Native functions, and the functions of modules left
+/// out of the set, call no functions.
+#[derive(Debug, Default)]
+pub struct CallGraph {
+    functions: BTreeMap<FunctionId, FunctionKind>,
+    callees: BTreeMap<FunctionId, BTreeSet<FunctionId>>,
+    callers: BTreeMap<FunctionId, BTreeSet<FunctionId>>,
+}
+
+/// Which of the queries of `call_graph` to answer
+#[derive(Debug)]
+pub enum CallGraphQuery {
+    /// The whole graph
+    All,
+    /// The functions that can reach this function
+    CallersOf(FunctionId),
+    /// The functions this function can reach
+    CalleesOf(FunctionId),
+}
+
+impl CallGraph {
+    pub fn new(modules: &[CompiledModule]) -> Self {
+        let mut graph = Self::default();
+        for module in modules {
+            for def in module.function_defs() {
+                let handle = module.function_handle_at(def.function);
+                let kind = if def.is_native() {
+                    FunctionKind::Native
+                } else {
+                    FunctionKind::Defined
+                };
+                let id = (
+                    module.self_id(),
+                    module.identifier_at(handle.name).to_owned(),
+                );
+                graph.functions.insert(id, kind);
+            }
+        }
+        for module in modules {
+            for def in module.function_defs() {
+                let caller = (
+                    module.self_id(),
+                    module
+                        .identifier_at(module.function_handle_at(def.function).name)
+                        .to_owned(),
+                );
+                let code = match &def.code {
+                    Some(code) => &code.code,
+                    None => continue,
+                };
+                for instruction in code {
+                    let handle = match instruction {
+                        Bytecode::Call(idx) => module.function_handle_at(*idx),
+                        Bytecode::CallGeneric(idx) => {
+                            module.function_handle_at(module.function_instantiation_at(*idx).handle)
+                        }
+                        _ => continue,
+                    };
+                    let callee = (
+                        module.module_id_for_handle(module.module_handle_at(handle.module)),
+                        module.identifier_at(handle.name).to_owned(),
+                    );
+                    graph
+                        .functions
+                        .entry(callee.clone())
+                        .or_insert(FunctionKind::Missing);
+                    graph
+                        .callers
+                        .entry(callee.clone())
+                        .or_default()
+                        .insert(caller.clone());
+                    graph
+                        .callees
+                        .entry(caller.clone())
+                        .or_default()
+                        .insert(callee);
+                }
+            }
+        }
+        graph
+    }
+
+    pub fn kind(&self, function: &FunctionId) -> Option<FunctionKind> {
+        self.functions.get(function).copied()
+    }
+
+    /// Every function that can call `function`, directly or not, with the shortest chain of calls
+    /// from it to `function`, closest first
+    pub fn callers_of(&self, function: &FunctionId) -> Vec<Vec<FunctionId>> {
+        Self::reach(function, &self.callers)
+            .into_iter()
+            .map(|mut path| {
+                path.reverse();
+                path
+            })
+            .collect()
+    }
+
+    /// Every function `function` can call, directly or not, with the shortest chain of calls from
+    /// `function` to it, closest first
+    pub fn callees_of(&self, function: &FunctionId) -> Vec<Vec<FunctionId>> {
+        Self::reach(function, &self.callees)
+    }
+
+    /// The shortest path from `start` to every function reached through `edges`, in breadth-first
+    /// order
+    fn reach(
+        start: &FunctionId,
+        edges: &BTreeMap<FunctionId, BTreeSet<FunctionId>>,
+    ) -> Vec<Vec<FunctionId>> {
+        let mut previous: BTreeMap<&FunctionId, &FunctionId> = BTreeMap::new();
+        let mut queue = VecDeque::from([start]);
+        let mut reached = vec![];
+        while let Some(function) = queue.pop_front() {
+            for next in edges.get(function).into_iter().flatten() {
+                if next == start || previous.contains_key(next) {
+                    continue;
+                }
+                previous.insert(next, function);
+                reached.push(next);
+                queue.push_back(next);
+            }
+        }
+        reached
+            .into_iter()
+            .map(|function| {
+                let mut path = vec![function.clone()];
+                let mut current = function;
+                while let Some(prev) = previous.get(current) {
+                    path.push((*prev).clone());
+                    current = prev;
+                }
+                path.reverse();
+                path
+            })
+            .collect()
+    }
+
+    /// The graph in the DOT format of Graphviz, restricted to `functions` if set
+    pub fn to_dot(&self, functions: Option<&BTreeSet<FunctionId>>) -> String {
+        let included = |function: &FunctionId| functions.map_or(true, |f| f.contains(function));
+        let mut dot = "digraph calls {\n".to_string();
+        for (function, kind) in &self.functions {
+            if !included(function) {
+                continue;
+            }
+            let name = format_function(function);
+            let attributes = match kind {
+                FunctionKind::Defined => String::new(),
+                FunctionKind::Native => format!(" [label=\"{} (native)\", style=dashed]", name),
+                FunctionKind::Missing => format!(" [label=\"{} (missing)\", style=dotted]", name),
+            };
+            writeln!(dot, "    \"{}\"{};", name, attributes).unwrap();
+        }
+        for (caller, callees) in &self.callees {
+            for callee in callees
+                .iter()
+                .filter(|callee| included(caller) && included(callee))
+            {
+                writeln!(
+                    dot,
+                    "    \"{}\" -> \"{}\";",
+                    format_function(caller),
+                    format_function(callee)
+                )
+                .unwrap();
+            }
+        }
+        dot.push_str("}\n");
+        dot
+    }
+
+    fn check_known(&self, function: &FunctionId) -> Result<()> {
+        if self.kind(function).is_none() {
+            bail!("No module defines or calls {}", format_function(function))
+        }
+        Ok(())
+    }
+
+    /// `function`, marked when it is native or missing
+    fn describe(&self, function: &FunctionId) -> String {
+        match self.kind(function) {
+            Some(FunctionKind::Native) => format!("{} (native)", format_function(function)),
+            Some(FunctionKind::Missing) => format!("{} (missing)", format_function(function)),
+            _ => format_function(function),
+        }
+    }
+}
+
+/// Print the answer to `query` about the call graph of `modules`, as text or, if `dot` is set,
+/// as the graph of the functions involved in the DOT format
+pub fn call_graph(modules: &[CompiledModule], query: &CallGraphQuery, dot: bool) -> Result<()> {
+    let graph = CallGraph::new(modules);
+    let (function, paths, summary) = match query {
+        CallGraphQuery::All => {
+            if dot {
+                print!("{}", graph.to_dot(None));
+            } else {
+                for (caller, callees) in &graph.callees {
+                    for callee in callees {
+                        println!("{} -> {}", format_function(caller), graph.describe(callee));
+                    }
+                }
+            }
+            return Ok(());
+        }
+        CallGraphQuery::CallersOf(function) => {
+            graph.check_known(function)?;
+            let paths = graph.callers_of(function);
+            let summary = format!(
+                "{} function(s) can call {}",
+                paths.len(),
+                format_function(function)
+            );
+            (function, paths, summary)
+        }
+        CallGraphQuery::CalleesOf(function) => {
+            graph.check_known(function)?;
+            let paths = graph.callees_of(function);
+            let summary = format!(
+                "{} can call {} function(s)",
+                format_function(function),
+                paths.len()
+            );
+            (function, paths, summary)
+        }
+    };
+    if dot {
+        let functions = paths.iter().flatten().cloned().chain([function.clone()]);
+        print!("{}", graph.to_dot(Some(&functions.collect())));
+        return Ok(());
+    }
+    println!("{}", summary);
+    for path in paths {
+        let path: Vec<_> = path.iter().map(|f| graph.describe(f)).collect();
+        println!("    {}", path.join(" -> "));
+    }
+    Ok(())
+}
+
+fn format_function((module, name): &FunctionId) -> String {
+    format!(
+        "0x{}::{}::{}",
+        module.address().short_str_lossless(),
+        module.name(),
+        name
+    )
+}
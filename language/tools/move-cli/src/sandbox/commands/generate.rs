@@ -4,12 +4,25 @@
 
 use crate::sandbox::utils::on_disk_state_view::OnDiskStateView;
 use anyhow::{bail, Result};
-use move_bytecode_utils::layout::SerdeLayoutBuilder;
+use move_binary_format::{access::ModuleAccess, CompiledModule};
+use move_bytecode_utils::{layout::SerdeLayoutBuilder, module_cache::GetModule};
 use move_core_types::{
     identifier::Identifier,
-    language_storage::{StructTag, TypeTag},
+    language_storage::{ModuleId, StructTag, TypeTag},
 };
-use std::path::Path;
+use std::{
+    collections::BTreeMap,
+    fs,
+    path::{Path, PathBuf},
+};
+
+/// File under the output directory of `generate_all_struct_layouts` mapping the type tag of each
+/// struct to its layout file
+pub const STRUCT_LAYOUTS_INDEX_FILE: &str = "index.yaml";
+
+/// Index entry of the generic structs, whose layouts depend on the type arguments they are
+/// instantiated with
+pub const REQUIRES_TYPE_ARGS: &str = "requires type args";
 
 pub fn generate_struct_layouts(
     path: &Path,
@@ -46,3 +59,82 @@ pub fn generate_struct_layouts(
         bail!("Can't resolve module at {:?}", path)
     }
 }
+
+/// Generate the layout of every struct with the `key` or `store` ability declared in `modules`
+/// into `output_dir`/<address>/<module>/<struct>.yaml, and an index mapping the type tag of each
+/// struct to its layout file, relative to `output_dir`. Generic structs are listed in the index as
+/// `REQUIRES_TYPE_ARGS`. Modules other than `modules` are resolved from `state`.
+pub fn generate_all_struct_layouts(
+    modules: &[CompiledModule],
+    shallow: bool,
+    output_dir: &Path,
+    state: &OnDiskStateView,
+) -> Result<()> {
+    let resolver = ModulesOverState { modules, state };
+    let mut index = BTreeMap::new();
+    for module in modules {
+        let module_id = module.self_id();
+        for struct_def in module.struct_defs() {
+            let handle = module.struct_handle_at(struct_def.struct_handle);
+            if !handle.abilities.has_key() && !handle.abilities.has_store() {
+                continue;
+            }
+            let struct_tag = StructTag {
+                address: *module_id.address(),
+                module: module_id.name().to_owned(),
+                name: module.identifier_at(handle.name).to_owned(),
+                type_params: vec![],
+            };
+            if !handle.type_parameters.is_empty() {
+                index.insert(struct_tag.to_string(), REQUIRES_TYPE_ARGS.to_string());
+                continue;
+            }
+
+            let mut layout_builder = if shallow {
+                SerdeLayoutBuilder::new_shallow(resolver)
+            } else {
+                SerdeLayoutBuilder::new(resolver)
+            };
+            layout_builder.build_struct_layout(&struct_tag)?;
+            let layout_path =
+                PathBuf::from(format!("0x{}", module_id.address().short_str_lossless()))
+                    .join(module_id.name().as_str())
+                    .join(format!("{}.yaml", struct_tag.name));
+            let layout_file = output_dir.join(&layout_path);
+            fs::create_dir_all(layout_file.parent().unwrap())?;
+            fs::write(
+                layout_file,
+                serde_yaml::to_string(layout_builder.registry())?,
+            )?;
+            index.insert(
+                struct_tag.to_string(),
+                layout_path.to_string_lossy().replace('\\', "/"),
+            );
+        }
+    }
+
+    let index = serde_yaml::to_string(&index)?;
+    fs::create_dir_all(output_dir)?;
+    fs::write(output_dir.join(STRUCT_LAYOUTS_INDEX_FILE), &index)?;
+    println!("{}", index);
+    Ok(())
+}
+
+/// Resolves `modules`, which may not be published yet, and the modules in `state`
+#[derive(Clone, Copy)]
+struct ModulesOverState<'a> {
+    modules: &'a [CompiledModule],
+    state: &'a OnDiskStateView,
+}
+
+impl GetModule for ModulesOverState<'_> {
+    type Error = anyhow::Error;
+    type Item = CompiledModule;
+
+    fn get_module_by_id(&self, id: &ModuleId) -> Result<Option<CompiledModule>> {
+        match self.modules.iter().find(|module| &module.self_id() == id) {
+            Some(module) => Ok(Some(module.clone())),
+            None => self.state.get_module_by_id(id),
+        }
+    }
+}
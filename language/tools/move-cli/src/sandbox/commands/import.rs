@@ -0,0 +1,438 @@
+// Copyright (c) The Move Contributors
+// SPDX-License-Identifier: Apache-2.0
+
+//! Moving the state of a sandbox to another one: `sandbox export` copies the modules, resources,
+//! events and accounts of storage to a directory, and `sandbox import` loads them into storage,
+//! either in place of its state or merged with it. Imports are written to a directory next to
+//! storage and swapped into place once complete, so that an import that fails or is interrupted
+//! leaves storage as it was, and can be run again.
+
+use crate::{
+    sandbox::utils::{
+        on_disk_state_view::{EVENTS_DIR, MODULES_DIR, RESOURCES_DIR},
+        record_transaction,
+        transaction_log::LOG_DIR,
+        AccountRegistry, ACCOUNTS_FILE,
+    },
+    utils::storage_lock::STORAGE_LOCK_FILE,
+};
+use anyhow::{bail, Context, Result};
+use move_binary_format::{access::ModuleAccess, CompiledModule};
+use move_command_line_common::interrupt;
+use move_core_types::{
+    account_address::AccountAddress,
+    identifier::Identifier,
+    language_storage::{ModuleId, TypeTag},
+    parser,
+};
+use std::{
+    collections::BTreeMap,
+    ffi::OsString,
+    fmt, fs,
+    path::{Path, PathBuf},
+};
+
+/// How `sandbox import` combines the imported state with the state in storage
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ImportMode {
+    /// Replace the state in storage with the imported one
+    Replace,
+    /// Add the imported state to the state in storage. Modules, resources, events and accounts
+    /// both hold with different contents are conflicts, which fail the import unless a side to
+    /// prefer is given.
+    Merge(Option<ConflictPreference>),
+}
+
+/// The side kept when the imported state and storage conflict
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ConflictPreference {
+    Import,
+    Local,
+}
+
+/// A file of the state of a sandbox
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum StateFile {
+    Module(ModuleId),
+    Resource(AccountAddress, String),
+    Events(AccountAddress, String),
+    Accounts,
+}
+
+impl fmt::Display for StateFile {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            StateFile::Module(id) => write!(
+                f,
+                "module 0x{}::{}",
+                id.address().short_str_lossless(),
+                id.name()
+            ),
+            StateFile::Resource(addr, tag) => {
+                write!(f, "resource {} at 0x{}", tag, addr.short_str_lossless())
+            }
+            StateFile::Events(addr, stream) => write!(
+                f,
+                "event stream {} of 0x{}",
+                stream,
+                addr.short_str_lossless()
+            ),
+            StateFile::Accounts => write!(f, "accounts"),
+        }
+    }
+}
+
+impl StateFile {
+    /// The file at `relative_path` in a state directory, which must be the file of a module,
+    /// resource or event stream under the directory of its address, or the accounts
+    fn parse(relative_path: &Path) -> Result<Self> {
+        let components: Vec<_> = relative_path
+            .iter()
+            .map(|component| component.to_string_lossy())
+            .collect();
+        let stem = relative_path
+            .file_stem()
+            .map(|stem| stem.to_string_lossy().to_string())
+            .unwrap_or_default();
+        let not_state = || {
+            anyhow::anyhow!(
+                "'{}' is not a module, resource, event stream or the accounts of a sandbox",
+                relative_path.display()
+            )
+        };
+        match components.as_slice() {
+            [file] if file == ACCOUNTS_FILE => Ok(StateFile::Accounts),
+            [addr, dir, _] => {
+                let addr = AccountAddress::from_hex_literal(addr).map_err(|_| not_state())?;
+                match dir.as_ref() {
+                    MODULES_DIR => {
+                        let name = Identifier::new(stem).map_err(|_| not_state())?;
+                        Ok(StateFile::Module(ModuleId::new(addr, name)))
+                    }
+                    RESOURCES_DIR => match parser::parse_type_tag(&stem) {
+                        Ok(TypeTag::Struct(_)) => Ok(StateFile::Resource(addr, stem)),
+                        _ => Err(not_state()),
+                    },
+                    EVENTS_DIR => Ok(StateFile::Events(addr, stem)),
+                    _ => Err(not_state()),
+                }
+            }
+            _ => Err(not_state()),
+        }
+    }
+
+    /// Check that `bytes`, imported from `path`, can be stored as this file
+    fn check(&self, path: &Path, bytes: &[u8]) -> Result<()> {
+        match self {
+            StateFile::Module(id) => {
+                let module = CompiledModule::deserialize(bytes).map_err(|err| {
+                    anyhow::anyhow!(
+                        "Unable to deserialize the {} at '{}': {}",
+                        self,
+                        path.display(),
+                        err
+                    )
+                })?;
+                if &module.self_id() != id {
+                    bail!(
+                        "'{}' holds module {}, not {}",
+                        path.display(),
+                        module.self_id(),
+                        id
+                    )
+                }
+            }
+            StateFile::Accounts => {
+                toml_edit::easy::from_str::<AccountRegistry>(&String::from_utf8_lossy(bytes))
+                    .with_context(|| format!("Error parsing '{}'", path.display()))?;
+            }
+            StateFile::Resource(..) | StateFile::Events(..) => (),
+        }
+        Ok(())
+    }
+}
+
+/// The files of the state in `dir`, by their path relative to `dir`. The lock and the transaction
+/// log of storage are not part of its state.
+fn state_files(dir: &Path) -> Result<BTreeMap<PathBuf, StateFile>> {
+    let mut files = BTreeMap::new();
+    if !dir.exists() {
+        return Ok(files);
+    }
+    let entries = walkdir::WalkDir::new(dir)
+        .min_depth(1)
+        .into_iter()
+        .filter_entry(|entry| {
+            entry.depth() > 1
+                || (entry.file_name() != STORAGE_LOCK_FILE && entry.file_name() != LOG_DIR)
+        });
+    for entry in entries {
+        let entry = entry.with_context(|| format!("Unable to read '{}'", dir.display()))?;
+        if entry.file_type().is_dir() {
+            continue;
+        }
+        let relative_path = entry.path().strip_prefix(dir)?.to_path_buf();
+        let file = StateFile::parse(&relative_path)
+            .with_context(|| format!("'{}' is not the state of a sandbox", dir.display()))?;
+        files.insert(relative_path, file);
+    }
+    Ok(files)
+}
+
+/// Copy the file at `from` to `to`, creating the directories leading to it
+fn copy_file(from: &Path, to: &Path) -> Result<()> {
+    if let Some(parent) = to.parent() {
+        fs::create_dir_all(parent)
+            .with_context(|| format!("Unable to create '{}'", parent.display()))?;
+    }
+    fs::copy(from, to)
+        .with_context(|| format!("Unable to copy '{}' to '{}'", from.display(), to.display()))?;
+    Ok(())
+}
+
+/// Copy the state stored in `storage_dir` to the directory `dir`, which must be empty or missing
+pub fn export(storage_dir: &Path, dir: &Path) -> Result<()> {
+    if dir.exists() && fs::read_dir(dir)?.next().is_some() {
+        bail!("'{}' is not empty", dir.display())
+    }
+    let files = state_files(storage_dir)?;
+    fs::create_dir_all(dir).with_context(|| format!("Unable to create '{}'", dir.display()))?;
+    for relative_path in files.keys() {
+        copy_file(&storage_dir.join(relative_path), &dir.join(relative_path))?;
+    }
+    println!(
+        "Exported {} files of '{}' to '{}'",
+        files.len(),
+        storage_dir.display(),
+        dir.display()
+    );
+    Ok(())
+}
+
+/// The directory next to `storage_dir` named after it with `suffix`, e.g. `storage.import` for
+/// `storage`, which is on the same file system so that it can be renamed to `storage_dir`
+fn sibling_dir(storage_dir: &Path, suffix: &str) -> PathBuf {
+    let mut name = storage_dir
+        .file_name()
+        .map(OsString::from)
+        .unwrap_or_default();
+    name.push(".");
+    name.push(suffix);
+    storage_dir.with_file_name(name)
+}
+
+/// Load the state exported to `dir` by `sandbox export` into the storage in `storage_dir`, as
+/// `mode` says. The new state is written to a staging directory, storage included for a merge,
+/// and swapped with `storage_dir` once complete. The import is recorded in the transaction log
+/// with `log_args`, the arguments the CLI was run with.
+pub fn import(storage_dir: &Path, dir: &Path, mode: ImportMode, log_args: &[String]) -> Result<()> {
+    if !dir.is_dir() {
+        bail!("'{}' is not a directory", dir.display())
+    }
+    let staging_dir = sibling_dir(storage_dir, "import");
+    let backup_dir = sibling_dir(storage_dir, "import-backup");
+    if backup_dir.exists() {
+        bail!(
+            "An earlier import was stopped while swapping storage, leaving the storage from before \
+             it in '{}'. Move it back to '{}' to undo that import, or remove it to keep the \
+             imported state",
+            backup_dir.display(),
+            storage_dir.display()
+        )
+    }
+    if staging_dir.exists() {
+        // left behind by an import that was killed before it could clean up
+        fs::remove_dir_all(&staging_dir)
+            .with_context(|| format!("Unable to remove '{}'", staging_dir.display()))?;
+    }
+    let imported = state_files(dir)?;
+    let local = state_files(storage_dir)?;
+
+    let _remove_on_interrupt = {
+        let staging_dir = staging_dir.clone();
+        interrupt::on_interrupt(move || {
+            let _ = fs::remove_dir_all(staging_dir);
+        })
+    };
+    let staged = stage(
+        storage_dir,
+        dir,
+        &staging_dir,
+        &local,
+        &imported,
+        mode,
+        log_args,
+    );
+    let changes = match staged {
+        Ok(changes) => changes,
+        Err(err) => {
+            let _ = fs::remove_dir_all(&staging_dir);
+            return Err(err);
+        }
+    };
+    swap(storage_dir, &staging_dir, &backup_dir)?;
+    match mode {
+        ImportMode::Replace => println!(
+            "Replaced the state of '{}' with the {} files of '{}'",
+            storage_dir.display(),
+            imported.len(),
+            dir.display()
+        ),
+        ImportMode::Merge(_) => {
+            println!(
+                "Merged the state of '{}' into '{}'",
+                dir.display(),
+                storage_dir.display()
+            );
+            for change in &changes {
+                println!("    {}", change);
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Write the state resulting from the import to `staging_dir`, with the transaction log of
+/// `storage_dir` and an entry for the import. Returns the changes the import makes to storage.
+fn stage(
+    storage_dir: &Path,
+    dir: &Path,
+    staging_dir: &Path,
+    local: &BTreeMap<PathBuf, StateFile>,
+    imported: &BTreeMap<PathBuf, StateFile>,
+    mode: ImportMode,
+    log_args: &[String],
+) -> Result<Vec<String>> {
+    fs::create_dir_all(staging_dir)
+        .with_context(|| format!("Unable to create '{}'", staging_dir.display()))?;
+    let log_dir = storage_dir.join(LOG_DIR);
+    if log_dir.exists() {
+        for entry in fs::read_dir(&log_dir)? {
+            let entry = entry?;
+            copy_file(
+                &entry.path(),
+                &staging_dir.join(LOG_DIR).join(entry.file_name()),
+            )?;
+        }
+    }
+
+    let mut changes = vec![];
+    let preference = match mode {
+        ImportMode::Replace => {
+            for (relative_path, file) in imported {
+                let path = dir.join(relative_path);
+                file.check(&path, &fs::read(&path)?)?;
+                copy_file(&path, &staging_dir.join(relative_path))?;
+            }
+            changes.push(format!(
+                "replaced the state with the {} files of '{}'",
+                imported.len(),
+                dir.display()
+            ));
+            record_transaction(staging_dir, "import", log_args, changes.clone(), 0)?;
+            return Ok(changes);
+        }
+        ImportMode::Merge(preference) => preference,
+    };
+
+    let local_accounts = AccountRegistry::load(storage_dir)?;
+    let imported_accounts = AccountRegistry::load(dir)?;
+    let mut conflicts: Vec<String> = local_accounts
+        .conflicts_with(&imported_accounts)
+        .into_iter()
+        .map(|name| format!("account @{}", name))
+        .collect();
+    let mut imported_bytes = BTreeMap::new();
+    for (relative_path, file) in imported {
+        if file == &StateFile::Accounts {
+            continue;
+        }
+        let path = dir.join(relative_path);
+        let bytes =
+            fs::read(&path).with_context(|| format!("Unable to read '{}'", path.display()))?;
+        file.check(&path, &bytes)?;
+        if local.contains_key(relative_path) {
+            if fs::read(storage_dir.join(relative_path))? == bytes {
+                continue;
+            }
+            conflicts.push(file.to_string());
+        }
+        imported_bytes.insert(relative_path, bytes);
+    }
+    if !conflicts.is_empty() && preference.is_none() {
+        bail!(
+            "The state of '{}' conflicts with storage:\n{}\nPass --prefer-import or --prefer-local \
+             to resolve the conflicts by keeping the imported or the stored version",
+            dir.display(),
+            conflicts
+                .iter()
+                .map(|conflict| format!("    {}", conflict))
+                .collect::<Vec<_>>()
+                .join("\n")
+        )
+    }
+    let prefer_import = preference == Some(ConflictPreference::Import);
+
+    for relative_path in local.keys() {
+        if local[relative_path] != StateFile::Accounts {
+            copy_file(
+                &storage_dir.join(relative_path),
+                &staging_dir.join(relative_path),
+            )?;
+        }
+    }
+    for (relative_path, bytes) in imported_bytes {
+        let file = &imported[relative_path];
+        if !local.contains_key(relative_path) {
+            changes.push(format!("added {}", file));
+        } else if prefer_import {
+            changes.push(format!("replaced {} with the imported one", file));
+        } else {
+            changes.push(format!("kept the stored {}", file));
+            continue;
+        }
+        let path = staging_dir.join(relative_path);
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        fs::write(&path, bytes).with_context(|| format!("Unable to write '{}'", path.display()))?;
+    }
+    let mut accounts = local_accounts;
+    accounts.merge(&imported_accounts, prefer_import);
+    if !accounts.accounts().is_empty() {
+        accounts.save(staging_dir)?;
+    }
+    record_transaction(staging_dir, "import", log_args, changes.clone(), 0)?;
+    Ok(changes)
+}
+
+/// Move `staging_dir` to `storage_dir`, in place of the storage it holds, which is moved out of
+/// the way first and put back if the swap fails or is interrupted
+fn swap(storage_dir: &Path, staging_dir: &Path, backup_dir: &Path) -> Result<()> {
+    if storage_dir.exists() {
+        fs::rename(storage_dir, backup_dir)
+            .with_context(|| format!("Unable to move '{}'", storage_dir.display()))?;
+    }
+    let _restore_on_interrupt = {
+        let (storage_dir, backup_dir) = (storage_dir.to_path_buf(), backup_dir.to_path_buf());
+        interrupt::on_interrupt(move || {
+            if backup_dir.exists() && !storage_dir.exists() {
+                let _ = fs::rename(backup_dir, storage_dir);
+            }
+        })
+    };
+    if let Err(err) = fs::rename(staging_dir, storage_dir) {
+        if backup_dir.exists() {
+            let _ = fs::rename(backup_dir, storage_dir);
+        }
+        return Err(anyhow::Error::new(err).context(format!(
+            "Unable to move '{}' into place",
+            staging_dir.display()
+        )));
+    }
+    if backup_dir.exists() {
+        fs::remove_dir_all(backup_dir)
+            .with_context(|| format!("Unable to remove '{}'", backup_dir.display()))?;
+    }
+    Ok(())
+}
@@ -0,0 +1,341 @@
+// Copyright (c) The Move Contributors
+// SPDX-License-Identifier: Apache-2.0
+
+//! Integration tests of a package, run by `move sandbox exp-test`. Each directory under the
+//! `integration-tests` directory of the package is a case made of
+//! - an `args.txt` batch file, or a `script.move` transaction script run with the `move sandbox
+//!   run` arguments in an optional `script.args` file,
+//! - an optional `storage` directory holding the state the case starts from,
+//! - an `expected.txt` file holding the output of the case, and/or an `expected.json` file
+//!   describing its outcome: the `abort_code` of the last aborted transaction, the `resources`
+//!   stored under each address and the `events` emitted. Only the sections present in
+//!   `expected.json` are checked.
+//!
+//! Each case runs against a copy of the package in a temporary directory, so that cases do not see
+//! the effects of each other.
+
+use super::test::{
+    copy_package, mask_durations, run_commands, simple_copy_dir, TEST_ARGS_FILENAME,
+};
+use crate::{
+    sandbox::utils::on_disk_state_view::OnDiskStateView, DEFAULT_BUILD_DIR, DEFAULT_STORAGE_DIR,
+};
+use anyhow::{bail, Context, Result};
+use move_command_line_common::{
+    files::{path_to_string, MOVE_EXTENSION},
+    testing::format_diff,
+};
+use move_core_types::account_address::AccountAddress;
+use move_package::{
+    compilation::package_layout::CompiledPackageLayout, source_package::layout::SourcePackageLayout,
+};
+use regex::Regex;
+use serde_json::{json, Map, Value};
+use std::{fs, io, path::Path};
+use tempfile::tempdir;
+
+/// The directory of a package holding its integration tests
+pub const INTEGRATION_TESTS_DIR: &str = "integration-tests";
+
+const SCRIPT_FILENAME: &str = "script.move";
+const SCRIPT_ARGS_FILENAME: &str = "script.args";
+const EXPECTED_OUTPUT_FILENAME: &str = "expected.txt";
+const EXPECTED_OUTCOME_FILENAME: &str = "expected.json";
+
+/// The sections `expected.json` may hold
+const OUTCOME_SECTIONS: [&str; 3] = ["abort_code", "resources", "events"];
+
+/// What running a case produced
+struct Outcome {
+    /// The output of the commands of the case, as in `expected.txt`
+    output: String,
+    /// All the sections `expected.json` may hold
+    sections: Map<String, Value>,
+}
+
+/// Run the integration tests of the package in `package_dir` with `cli_binary`, and report whether
+/// each of them passed. With `update_expected`, their expected outcomes are overwritten with the
+/// actual ones instead.
+pub fn run_all(package_dir: &Path, cli_binary: &Path, update_expected: bool) -> Result<()> {
+    let mut cases = vec![];
+    for entry in fs::read_dir(package_dir.join(INTEGRATION_TESTS_DIR))? {
+        let path = entry?.path();
+        if path.is_dir() {
+            cases.push(path);
+        }
+    }
+    cases.sort();
+
+    let mut passed = 0;
+    for case_dir in &cases {
+        let name = case_dir.file_name().unwrap().to_string_lossy();
+        let outcome = run_case(package_dir, case_dir, cli_binary)
+            .with_context(|| format!("Unable to run integration test {}", name));
+        if update_expected {
+            outcome?.save(case_dir)?;
+            println!("UPDATED {}", name);
+            continue;
+        }
+        match outcome.and_then(|outcome| outcome.mismatches(case_dir)) {
+            Ok(mismatches) if mismatches.is_empty() => {
+                passed += 1;
+                println!("PASS {}", name);
+            }
+            Ok(mismatches) => {
+                println!("FAIL {}", name);
+                for line in mismatches.iter().flat_map(|mismatch| mismatch.lines()) {
+                    println!("    {}", line);
+                }
+            }
+            Err(err) => {
+                println!("FAIL {}", name);
+                println!("    {:#}", err);
+            }
+        }
+    }
+
+    if update_expected {
+        println!(
+            "Updated the expected outcomes of {} integration test(s).",
+            cases.len()
+        );
+        return Ok(());
+    }
+    println!("{} / {} integration test(s) passed.", passed, cases.len());
+    let failed = cases.len() - passed;
+    if failed != 0 {
+        bail!("{} / {} integration test(s) failed.", failed, cases.len())
+    }
+    Ok(())
+}
+
+/// Run the case in `case_dir` against a copy of the package in `package_dir`
+fn run_case(package_dir: &Path, case_dir: &Path, cli_binary: &Path) -> Result<Outcome> {
+    let temp_dir = tempdir()?;
+    let work_dir = copy_package(temp_dir.path(), package_dir)?;
+
+    // the case starts from its own state, not from the one left by running the package before
+    let storage_dir = work_dir.join(DEFAULT_STORAGE_DIR);
+    let build_dir = work_dir.join(DEFAULT_BUILD_DIR);
+    for dir in [
+        &storage_dir,
+        &build_dir.join(CompiledPackageLayout::Root.path()),
+    ] {
+        if dir.exists() {
+            fs::remove_dir_all(dir)?;
+        }
+    }
+    let pre_state = case_dir.join(DEFAULT_STORAGE_DIR);
+    if pre_state.is_dir() {
+        fs::create_dir_all(&storage_dir)?;
+        simple_copy_dir(&storage_dir, &pre_state)?;
+    }
+
+    let commands = case_commands(case_dir, &work_dir)?;
+    let output = mask_durations(&run_commands(
+        commands,
+        &cli_binary.canonicalize()?,
+        &work_dir,
+        None,
+    )?);
+    let sections = outcome_sections(&output, &build_dir, &storage_dir)?;
+    temp_dir.close()?;
+    Ok(Outcome { output, sections })
+}
+
+/// The commands run by the case in `case_dir`. The script of a case is added to the scripts of the
+/// package copied into `work_dir`, since scripts are run from the package they are built with.
+fn case_commands(case_dir: &Path, work_dir: &Path) -> Result<Vec<String>> {
+    let args_path = case_dir.join(TEST_ARGS_FILENAME);
+    if args_path.exists() {
+        return Ok(fs::read_to_string(&args_path)?
+            .lines()
+            .map(String::from)
+            .collect());
+    }
+    let script_path = case_dir.join(SCRIPT_FILENAME);
+    if !script_path.exists() {
+        bail!(
+            "Found neither `{}` nor `{}` in {}",
+            TEST_ARGS_FILENAME,
+            SCRIPT_FILENAME,
+            case_dir.display()
+        )
+    }
+    let name = case_dir.file_name().unwrap().to_string_lossy();
+    let package_script =
+        Path::new(SourcePackageLayout::Scripts.path()).join(format!("{}.{}", name, MOVE_EXTENSION));
+    let dest = work_dir.join(&package_script);
+    if dest.exists() {
+        bail!(
+            "Unable to add the script of the test to the package: `{}` already exists",
+            package_script.display()
+        )
+    }
+    fs::create_dir_all(dest.parent().unwrap())?;
+    fs::copy(&script_path, &dest)?;
+
+    let mut run = format!("sandbox run {}", path_to_string(&package_script)?);
+    match fs::read_to_string(case_dir.join(SCRIPT_ARGS_FILENAME)) {
+        Ok(args) => {
+            for arg in args.split_whitespace() {
+                run.push(' ');
+                run.push_str(arg);
+            }
+        }
+        Err(err) if err.kind() == io::ErrorKind::NotFound => (),
+        Err(err) => return Err(err.into()),
+    }
+    Ok(vec!["sandbox publish".to_string(), run])
+}
+
+/// The sections of `expected.json` describing the outcome of a case that printed `output` and left
+/// `storage_dir` behind
+fn outcome_sections(
+    output: &str,
+    build_dir: &Path,
+    storage_dir: &Path,
+) -> Result<Map<String, Value>> {
+    let abort_code = Regex::new(r"Execution aborted with code (\d+)")
+        .unwrap()
+        .captures_iter(output)
+        .last()
+        .map(|captures| captures[1].parse::<u64>())
+        .transpose()?;
+
+    let state = OnDiskStateView::create(build_dir, storage_dir)?;
+    let mut resources = Map::new();
+    let mut resource_paths = state.resource_paths().collect::<Vec<_>>();
+    resource_paths.sort();
+    for path in resource_paths {
+        if let Some(resource) = state.view_resource(&path)? {
+            let value = serde_json::to_value(&resource)?;
+            resources
+                .entry(holder(&path)?)
+                .or_insert_with(|| Value::Object(Map::new()))
+                .as_object_mut()
+                .unwrap()
+                .insert(resource.type_.to_string(), value);
+        }
+    }
+
+    let mut events = vec![];
+    let mut event_paths = state.event_paths().collect::<Vec<_>>();
+    event_paths.sort();
+    for path in event_paths {
+        let stream = format!(
+            "{}/{}",
+            holder(&path)?,
+            path.file_stem().unwrap_or_default().to_string_lossy()
+        );
+        for (sequence_number, event_type, data) in state.view_events(&path)? {
+            events.push(json!({
+                "stream": stream,
+                "sequence_number": sequence_number,
+                "type": event_type.to_string(),
+                "data": data,
+            }));
+        }
+    }
+
+    let mut sections = Map::new();
+    sections.insert("abort_code".to_string(), json!(abort_code));
+    sections.insert("resources".to_string(), Value::Object(resources));
+    sections.insert("events".to_string(), Value::Array(events));
+    Ok(sections)
+}
+
+/// The address whose storage directory holds the resource or events file at `path`, e.g. `0x2`
+fn holder(path: &Path) -> Result<String> {
+    let dir = path
+        .parent()
+        .and_then(Path::parent)
+        .and_then(Path::file_name)
+        .with_context(|| format!("Unexpected storage path {}", path.display()))?;
+    let addr = AccountAddress::from_hex_literal(&dir.to_string_lossy())?;
+    Ok(format!("0x{}", addr.short_str_lossless()))
+}
+
+/// The sections of the `expected.json` file at `path`, if it exists
+fn read_expected_sections(path: &Path) -> Result<Option<Map<String, Value>>> {
+    if !path.exists() {
+        return Ok(None);
+    }
+    let sections = match serde_json::from_str(&fs::read_to_string(path)?)
+        .with_context(|| format!("Unable to parse {}", path.display()))?
+    {
+        Value::Object(sections) => sections,
+        _ => bail!("{} must hold a JSON object", path.display()),
+    };
+    for section in sections.keys() {
+        if !OUTCOME_SECTIONS.contains(&section.as_str()) {
+            bail!(
+                "Unknown section `{}` in {}; expected one of {}",
+                section,
+                path.display(),
+                OUTCOME_SECTIONS.join(", ")
+            )
+        }
+    }
+    Ok(Some(sections))
+}
+
+impl Outcome {
+    /// How this outcome differs from the one expected by the case in `case_dir`
+    fn mismatches(&self, case_dir: &Path) -> Result<Vec<String>> {
+        let output_path = case_dir.join(EXPECTED_OUTPUT_FILENAME);
+        let sections_path = case_dir.join(EXPECTED_OUTCOME_FILENAME);
+        if !output_path.exists() && !sections_path.exists() {
+            bail!(
+                "Found neither `{}` nor `{}`; run with `--update-expected` to create `{}`",
+                EXPECTED_OUTPUT_FILENAME,
+                EXPECTED_OUTCOME_FILENAME,
+                EXPECTED_OUTPUT_FILENAME
+            )
+        }
+
+        let mut mismatches = vec![];
+        if output_path.exists() {
+            let expected = fs::read_to_string(&output_path)?;
+            if expected != self.output {
+                mismatches.push(format!(
+                    "output differs from `{}`:\n{}",
+                    EXPECTED_OUTPUT_FILENAME,
+                    format_diff(expected, &self.output)
+                ));
+            }
+        }
+        for (section, expected) in read_expected_sections(&sections_path)?.unwrap_or_default() {
+            let actual = &self.sections[&section];
+            if &expected != actual {
+                mismatches.push(format!(
+                    "{}: expected {}, found {}",
+                    section, expected, actual
+                ));
+            }
+        }
+        Ok(mismatches)
+    }
+
+    /// Overwrite the expected outcome of the case in `case_dir` with this one. Only the sections
+    /// already in `expected.json` are kept, and `expected.txt` is created if neither file exists.
+    fn save(&self, case_dir: &Path) -> Result<()> {
+        let output_path = case_dir.join(EXPECTED_OUTPUT_FILENAME);
+        let sections_path = case_dir.join(EXPECTED_OUTCOME_FILENAME);
+        let expected_sections = read_expected_sections(&sections_path)?;
+        if let Some(expected) = &expected_sections {
+            let sections = expected
+                .keys()
+                .map(|section| (section.clone(), self.sections[section].clone()))
+                .collect::<Map<_, _>>();
+            fs::write(
+                &sections_path,
+                serde_json::to_string_pretty(&sections)? + "\n",
+            )?;
+        }
+        if output_path.exists() || expected_sections.is_none() {
+            fs::write(&output_path, &self.output)?;
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,35 @@
+// Copyright (c) The Move Contributors
+// SPDX-License-Identifier: Apache-2.0
+
+use crate::sandbox::{cli::OutputFormat, utils::read_transaction_log};
+use anyhow::Result;
+use std::path::Path;
+
+/// Print the state-mutating commands recorded under `storage_dir`, oldest first. If `last` is set,
+/// only the `last` most recent entries are printed.
+pub fn log(storage_dir: &Path, last: Option<usize>, format: OutputFormat) -> Result<()> {
+    let entries = read_transaction_log(storage_dir)?;
+    let skip = last.map_or(0, |n| entries.len().saturating_sub(n));
+    let entries = &entries[skip..];
+
+    match format {
+        OutputFormat::Json => println!("{}", serde_json::to_string_pretty(entries)?),
+        OutputFormat::Text => {
+            if entries.is_empty() {
+                println!("No transactions recorded.");
+            }
+            for entry in entries {
+                println!(
+                    "[{}] {} (gas used: {})",
+                    entry.sequence_number,
+                    entry.args.join(" "),
+                    entry.gas_used
+                );
+                for change in &entry.changes {
+                    println!("    {}", change);
+                }
+            }
+        }
+    }
+    Ok(())
+}
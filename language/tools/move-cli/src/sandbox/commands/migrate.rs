@@ -0,0 +1,230 @@
+// Copyright (c) The Move Contributors
+// SPDX-License-Identifier: Apache-2.0
+
+use crate::{
+    sandbox::{
+        commands::{run, RunOptions},
+        utils::{on_disk_state_view::OnDiskStateView, record_transaction},
+    },
+    NativeFunctionRecord,
+};
+use anyhow::{anyhow, bail, Result};
+use move_binary_format::{access::ModuleAccess, file_format::SignatureToken};
+use move_bytecode_utils::module_cache::GetModule;
+use move_core_types::{
+    account_address::AccountAddress,
+    errmap::ErrorMapping,
+    identifier::Identifier,
+    language_storage::{ModuleId, StructTag},
+    transaction_argument::TransactionArgument,
+};
+use move_package::compilation::compiled_package::CompiledPackage;
+use move_vm_test_utils::gas_schedule::CostTable;
+
+/// Options for `migrate`. See `move sandbox migrate --help` for their meaning.
+#[derive(Debug)]
+pub struct MigrateOptions {
+    /// Type of the resource to migrate
+    pub resource: StructTag,
+    /// Module of the entry function migrating the resource of an address
+    pub module_id: ModuleId,
+    /// Entry function migrating the resource of the address passed as its first argument
+    pub function: Identifier,
+    /// Arguments passed to the entry function after the address
+    pub args: Vec<TransactionArgument>,
+    pub gas_budget: Option<u64>,
+    pub dry_run: bool,
+    pub verbose: bool,
+    /// The arguments the CLI was run with, recorded in the transaction log
+    pub log_args: Vec<String>,
+}
+
+/// The result of `migrate`
+#[derive(Debug, Default)]
+pub struct MigrateOutput {
+    /// The addresses whose resource was migrated, or would have been in a dry run
+    pub migrated: Vec<AccountAddress>,
+    /// The addresses for which the entry function aborted. The errors are explained on stdout.
+    pub failed: Vec<AccountAddress>,
+}
+
+/// How the entry function of a migration is passed the address it migrates
+enum AddressParameter {
+    Signer,
+    Address,
+}
+
+/// Parse `<address>::<module>::<function>`, e.g. `0x2::Coin::migrate`
+pub fn parse_entry_function(s: &str) -> Result<(ModuleId, Identifier)> {
+    let parts: Vec<_> = s.split("::").collect();
+    match parts[..] {
+        [address, module, function] => Ok((
+            ModuleId::new(
+                AccountAddress::from_hex_literal(address)?,
+                Identifier::new(module)?,
+            ),
+            Identifier::new(function)?,
+        )),
+        _ => bail!(
+            "Invalid function {}: expected <address>::<module>::<function>",
+            s
+        ),
+    }
+}
+
+/// Run the entry function of `options` once for every address storing a resource of the type to
+/// migrate, committing the effects of each run that succeeds and recording it in the transaction
+/// log. The runs that abort are listed in the summary, and rerunning the migration retries them if
+/// the entry function moves the resources it migrates to another type.
+pub fn migrate(
+    natives: Vec<NativeFunctionRecord>,
+    cost_table: &CostTable,
+    error_descriptions: &ErrorMapping,
+    state: &OnDiskStateView,
+    package: &CompiledPackage,
+    options: &MigrateOptions,
+) -> Result<MigrateOutput> {
+    let MigrateOptions {
+        resource,
+        module_id,
+        function,
+        args,
+        gas_budget,
+        dry_run,
+        verbose,
+        log_args,
+    } = options;
+    let address_parameter = address_parameter(state, module_id, function)?;
+    let holders = state.holders_of(resource)?;
+    let entry_function = format!("{}::{}", short_module_id(module_id), function);
+    if holders.is_empty() {
+        println!("No address stores a resource of type {}", resource);
+        return Ok(MigrateOutput::default());
+    }
+    println!(
+        "Migrating the {} address(es) storing a resource of type {} with {}{}",
+        holders.len(),
+        resource,
+        entry_function,
+        if *dry_run { " (dry run)" } else { "" }
+    );
+
+    let mut output = MigrateOutput::default();
+    for addr in holders {
+        let addr_literal = format!("0x{}", addr.short_str_lossless());
+        let (signers, address_arg) = match address_parameter {
+            AddressParameter::Signer => (vec![addr_literal.clone()], None),
+            AddressParameter::Address => (vec![], Some(TransactionArgument::Address(addr))),
+        };
+        let run_options = RunOptions {
+            script_path: state.get_module_path(module_id),
+            script_name: Some(function.to_string()),
+            signers,
+            args: address_arg
+                .into_iter()
+                .chain(args.iter().cloned())
+                .collect(),
+            gas_budget: *gas_budget,
+            dry_run: *dry_run,
+            verbose: *verbose,
+            ..RunOptions::default()
+        };
+        let result = run(
+            natives.clone(),
+            cost_table,
+            error_descriptions,
+            state,
+            package,
+            &run_options,
+        )?;
+        if result.error.is_none() {
+            if !*dry_run {
+                record_transaction(
+                    state.storage_dir(),
+                    "migrate",
+                    log_args,
+                    result.changes,
+                    result.gas_used,
+                )?;
+            }
+            println!("[ OK     ] {}", addr_literal);
+            output.migrated.push(addr);
+        } else {
+            println!("[ FAILED ] {}", addr_literal);
+            output.failed.push(addr);
+        }
+    }
+
+    println!(
+        "{} {} of {} address(es); failed: {}",
+        if *dry_run {
+            "Would migrate"
+        } else {
+            "Migrated"
+        },
+        output.migrated.len(),
+        output.migrated.len() + output.failed.len(),
+        output.failed.len()
+    );
+    if !output.failed.is_empty() {
+        let failed: Vec<_> = output
+            .failed
+            .iter()
+            .map(|addr| format!("0x{}", addr.short_str_lossless()))
+            .collect();
+        if *dry_run {
+            bail!("The migration would fail for {}", failed.join(", "))
+        }
+        bail!(
+            "Failed to migrate {}. The other addresses are migrated: rerun the migration to \
+             retry the failed ones",
+            failed.join(", ")
+        )
+    }
+    Ok(output)
+}
+
+/// How `function` of the published module `module_id` is passed the address it migrates: its
+/// first parameter must be a signer or an address
+fn address_parameter(
+    state: &OnDiskStateView,
+    module_id: &ModuleId,
+    function: &Identifier,
+) -> Result<AddressParameter> {
+    let module = state
+        .get_module_by_id(module_id)?
+        .ok_or_else(|| anyhow!("Module {} is not published", short_module_id(module_id)))?;
+    let handle = module
+        .function_defs()
+        .iter()
+        .map(|def| module.function_handle_at(def.function))
+        .find(|handle| module.identifier_at(handle.name) == function.as_ident_str())
+        .ok_or_else(|| {
+            anyhow!(
+                "Function {}::{} not found",
+                short_module_id(module_id),
+                function
+            )
+        })?;
+    match module.signature_at(handle.parameters).0.first() {
+        Some(SignatureToken::Signer) => Ok(AddressParameter::Signer),
+        Some(SignatureToken::Reference(ty)) if **ty == SignatureToken::Signer => {
+            Ok(AddressParameter::Signer)
+        }
+        Some(SignatureToken::Address) => Ok(AddressParameter::Address),
+        _ => bail!(
+            "The first parameter of {}::{} must be a signer or an address, to be passed the \
+             address whose resource it migrates",
+            short_module_id(module_id),
+            function
+        ),
+    }
+}
+
+fn short_module_id(module_id: &ModuleId) -> String {
+    format!(
+        "0x{}::{}",
+        module_id.address().short_str_lossless(),
+        module_id.name()
+    )
+}
@@ -2,15 +2,28 @@
 // Copyright (c) The Move Contributors
 // SPDX-License-Identifier: Apache-2.0
 
+pub mod call_graph;
 pub mod doctor;
 pub mod generate;
+pub mod import;
+pub mod integration_test;
+pub mod log;
+pub mod migrate;
 pub mod publish;
 pub mod run;
 pub mod test;
+pub mod trace_debug;
+pub mod verify_storage;
 pub mod view;
 
+pub use call_graph::*;
 pub use doctor::*;
+pub use import::*;
+pub use log::*;
+pub use migrate::*;
 pub use publish::*;
 pub use run::*;
 pub use test::*;
+pub use trace_debug::*;
+pub use verify_storage::*;
 pub use view::*;
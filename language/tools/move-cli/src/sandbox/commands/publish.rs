@@ -5,30 +5,111 @@
 use crate::{
     sandbox::utils::{
         explain_publish_changeset, explain_publish_error, get_gas_status, module,
-        on_disk_state_view::OnDiskStateView,
+        on_disk_state_view::OnDiskStateView, print_module_diff, record_transaction,
+        summarize_changes,
     },
     NativeFunctionRecord,
 };
 use anyhow::{bail, Result};
-use move_binary_format::errors::Location;
-use move_command_line_common::env::get_bytecode_version_from_env;
-use move_package::compilation::compiled_package::CompiledPackage;
+use move_binary_format::{
+    access::ModuleAccess, errors::Location, file_format::CompiledModule, normalized,
+};
+use move_bytecode_utils::{module_cache::GetModule, Modules};
+use move_command_line_common::{
+    address::NumericalAddress, env::get_bytecode_version_from_env, interrupt,
+};
+use move_compiler::{
+    compiled_unit::{CompiledUnit, NamedCompiledModule},
+    diagnostics::report_diagnostics_to_buffer,
+};
+use move_core_types::{
+    account_address::AccountAddress, language_storage::ModuleId, resolver::ModuleResolver,
+};
+use move_package::{
+    compilation::{
+        build_plan::BuildPlan,
+        compiled_package::{CompiledPackage, CompiledUnitWithSource},
+    },
+    source_package::parsed_manifest::PackageName,
+    BuildConfig, PackageLock,
+};
 use move_vm_runtime::move_vm::MoveVM;
 use move_vm_test_utils::gas_schedule::CostTable;
-use std::collections::BTreeMap;
+use regex::Regex;
+use std::{
+    collections::{BTreeMap, BTreeSet},
+    fmt::Display,
+    path::{Path, PathBuf},
+};
+use tracing::info_span;
+
+/// Options for `publish`. See `move sandbox publish --help` for their meaning.
+#[derive(Debug, Default)]
+pub struct PublishOptions {
+    pub no_republish: bool,
+    pub ignore_breaking_changes: bool,
+    pub with_deps: bool,
+    pub bundle_deps: bool,
+    pub override_deps: bool,
+    pub bundle: bool,
+    pub override_ordering: Option<Vec<String>>,
+    /// Only publish the modules whose name matches one of these glob patterns. All modules are
+    /// published if empty.
+    pub include_modules: Vec<String>,
+    /// Do not publish the modules whose name matches one of these glob patterns
+    pub exclude_modules: Vec<String>,
+    pub address_mapping: Vec<(String, NumericalAddress)>,
+    /// The root directory of the package, built again to remap its named addresses
+    pub package_path: PathBuf,
+    /// Publish the modules in dependency order, this many at a time, saving each batch before
+    /// publishing the next. All modules are published in a single batch if `None`.
+    pub batch_size: Option<usize>,
+    /// Print the changes to the modules storage already holds before publishing them
+    pub show_diff: bool,
+    pub verbose: bool,
+    /// The arguments the CLI was run with, recorded in the transaction log
+    pub log_args: Vec<String>,
+}
+
+/// The result of `publish`
+#[derive(Debug, Default)]
+pub struct PublishOutput {
+    /// The modules written to storage, including any dependencies installed with `bundle_deps`.
+    /// Empty if publishing was rejected; the reason is explained on stdout.
+    pub published: Vec<ModuleId>,
+}
 
 pub fn publish(
     natives: impl IntoIterator<Item = NativeFunctionRecord>,
     cost_table: &CostTable,
     state: &OnDiskStateView,
     package: &CompiledPackage,
-    no_republish: bool,
-    ignore_breaking_changes: bool,
-    with_deps: bool,
-    bundle: bool,
-    override_ordering: Option<&[String]>,
-    verbose: bool,
-) -> Result<()> {
+    options: &PublishOptions,
+) -> Result<PublishOutput> {
+    let &PublishOptions {
+        no_republish,
+        ignore_breaking_changes,
+        with_deps,
+        bundle_deps,
+        override_deps,
+        bundle,
+        ref override_ordering,
+        ref include_modules,
+        ref exclude_modules,
+        ref address_mapping,
+        ref package_path,
+        batch_size,
+        show_diff,
+        verbose,
+        ref log_args,
+    } = options;
+    let _span = info_span!(
+        "publish",
+        package = %package.compiled_package_info.package_name
+    )
+    .entered();
+    let mut published = vec![];
+
     // collect all modules compiled
     let compiled_modules = if with_deps {
         package.all_modules().collect::<Vec<_>>()
@@ -38,6 +119,12 @@ pub fn publish(
     if verbose {
         println!("Found {} modules", compiled_modules.len());
     }
+    let filtered = !include_modules.is_empty() || !exclude_modules.is_empty();
+    let (compiled_modules, skipped_modules) = if filtered {
+        filter_modules(compiled_modules, include_modules, exclude_modules, state)?
+    } else {
+        (compiled_modules, vec![])
+    };
 
     // order the modules for publishing
     let modules_to_publish = match override_ordering {
@@ -61,6 +148,65 @@ pub fn publish(
         None => compiled_modules,
     };
 
+    // collect the dependency modules, deepest dependencies first
+    let dep_modules = if bundle_deps {
+        ordered_dependency_modules(package)?
+    } else {
+        vec![]
+    };
+    let dep_package_names: Vec<_> = dep_modules.iter().map(|(name, _)| *name).collect();
+    let num_deps = dep_modules.len();
+    let all_modules: Vec<_> = dep_modules
+        .into_iter()
+        .map(|(_, unit)| unit)
+        .chain(modules_to_publish)
+        .collect();
+
+    // re-instantiate named addresses if requested
+    let remapped_modules;
+    let all_modules = if address_mapping.is_empty() {
+        all_modules
+    } else {
+        remapped_modules =
+            remap_named_addresses(package, package_path, all_modules, address_mapping, state)?;
+        remapped_modules.iter().collect()
+    };
+    let (dep_modules, modules_to_publish) = all_modules.split_at(num_deps);
+    let modules_to_publish = modules_to_publish.to_vec();
+
+    let bytecode_version = get_bytecode_version_from_env();
+
+    // only install the dependency modules that storage does not already hold
+    let mut deps_to_install = vec![];
+    let mut deps_present = vec![];
+    let mut conflicts = vec![];
+    for (package_name, unit) in dep_package_names.iter().zip(dep_modules) {
+        let compiled_module = module(&unit.unit)?;
+        match state.get_module(&compiled_module.self_id())? {
+            None => deps_to_install.push((*package_name, *unit)),
+            Some(bytes) if bytes == unit.unit.serialize(bytecode_version) => {
+                deps_present.push((*package_name, *unit))
+            }
+            Some(bytes) => {
+                let stored_module = CompiledModule::deserialize(&bytes)?;
+                conflicts.push(explain_module_conflict(
+                    *package_name,
+                    &stored_module,
+                    compiled_module,
+                ));
+                deps_to_install.push((*package_name, *unit));
+            }
+        }
+    }
+    if !conflicts.is_empty() && !override_deps {
+        bail!(
+            "Storage holds a different version of {} dependency module(s):\n{}\n\
+             Re-run with --override-deps to republish them anyway.",
+            conflicts.len(),
+            conflicts.join("\n")
+        );
+    }
+
     if no_republish {
         let republished = modules_to_publish
             .iter()
@@ -77,14 +223,92 @@ pub fn publish(
         if !republished.is_empty() {
             eprintln!("Failed to republish modules since the --no-republish flag is set. Tried to republish the following modules: {}",
                 republished.join(", "));
-            return Ok(());
+            return Ok(PublishOutput::default());
         }
     }
 
-    let bytecode_version = get_bytecode_version_from_env();
+    if batch_size == Some(0) {
+        bail!("The batch size must be at least 1")
+    }
+
+    if show_diff {
+        for unit in &modules_to_publish {
+            let new_module = module(&unit.unit)?;
+            if let Some(bytes) = state.get_module(&new_module.self_id())? {
+                let stored_module = CompiledModule::deserialize(&bytes)?;
+                let new_size = unit.unit.serialize(bytecode_version).len();
+                print_module_diff(&stored_module, bytes.len(), new_module, new_size);
+            }
+        }
+    }
 
     // use the the publish_module API from the VM if we do not allow breaking changes
     if !ignore_breaking_changes {
+        let natives: Vec<_> = natives.into_iter().collect();
+        if !deps_to_install.is_empty() {
+            // the VM caches the modules it loads, so a dependency that replaces a conflicting module
+            // is only visible to the modules depending on it in a fresh VM. Install the dependencies
+            // one by one, as a sequence of `sandbox publish` commands would.
+            let mut changes = vec![];
+            for (_, unit) in &deps_to_install {
+                let vm = MoveVM::new(natives.clone()).unwrap();
+                let mut gas_status = get_gas_status(cost_table, None)?;
+                let mut session = vm.new_session(state);
+                let module_bytes = unit.unit.serialize(bytecode_version);
+                let sender = *module(&unit.unit)?.self_id().address();
+                if let Err(err) = session.publish_module(module_bytes, sender, &mut gas_status) {
+                    explain_publish_error(err, state, unit, true)?;
+                    return Ok(PublishOutput { published });
+                }
+                let (changeset, events) = session.finish().map_err(|e| e.into_vm_status())?;
+                changes.extend(summarize_changes(&changeset, &events));
+                let modules: Vec<_> = changeset
+                    .into_modules()
+                    .map(|(module_id, blob_opt)| {
+                        (module_id, blob_opt.ok().expect("must be non-deletion"))
+                    })
+                    .collect();
+                state.save_modules(&modules)?;
+                published.extend(modules.into_iter().map(|(module_id, _)| module_id));
+            }
+            record_transaction(state.storage_dir(), "publish", log_args, changes, 0)?;
+        }
+
+        if let Some(batch_size) = batch_size {
+            let batched = match publish_in_batches(
+                natives,
+                cost_table,
+                state,
+                &modules_to_publish,
+                batch_size,
+                show_diff,
+                verbose,
+            )? {
+                Some(batched) => batched,
+                None => return Ok(PublishOutput { published }),
+            };
+            println!(
+                "Published {} modules in {} batch(es) of up to {} modules",
+                batched.published.len(),
+                batched.num_batches,
+                batch_size
+            );
+            published.extend(batched.published);
+            record_transaction(state.storage_dir(), "publish", log_args, batched.changes, 0)?;
+            if bundle_deps {
+                print_install_manifest(
+                    package,
+                    &deps_to_install,
+                    &deps_present,
+                    &modules_to_publish,
+                )?;
+            }
+            if filtered {
+                print_filter_summary(&modules_to_publish, &skipped_modules)?;
+            }
+            return Ok(PublishOutput { published });
+        }
+
         let vm = MoveVM::new(natives).unwrap();
         let mut gas_status = get_gas_status(cost_table, None)?;
         let mut session = vm.new_session(state);
@@ -120,10 +344,10 @@ pub fn publish(
                         if let Location::Module(module_id) = err.location() {
                             // find the module where error occures and explain
                             if let Some(unit) = modules_to_publish
-                                .into_iter()
+                                .iter()
                                 .find(|&x| x.unit.name().as_str() == module_id.name().as_str())
                             {
-                                explain_publish_error(err, state, unit)?
+                                explain_publish_error(err, state, unit, !show_diff)?
                             } else {
                                 println!("Unable to locate the module in the multi-module publishing error");
                             }
@@ -141,7 +365,7 @@ pub fn publish(
 
                 let res = session.publish_module(module_bytes, sender, &mut gas_status);
                 if let Err(err) = res {
-                    explain_publish_error(err, state, unit)?;
+                    explain_publish_error(err, state, unit, !show_diff)?;
                     has_error = true;
                     break;
                 }
@@ -154,6 +378,7 @@ pub fn publish(
             if verbose {
                 explain_publish_changeset(&changeset);
             }
+            let changes = summarize_changes(&changeset, &events);
             let modules: Vec<_> = changeset
                 .into_modules()
                 .map(|(module_id, blob_opt)| {
@@ -161,19 +386,552 @@ pub fn publish(
                 })
                 .collect();
             state.save_modules(&modules)?;
+            published.extend(modules.into_iter().map(|(module_id, _)| module_id));
+            record_transaction(state.storage_dir(), "publish", log_args, changes, 0)?;
+            if bundle_deps {
+                print_install_manifest(
+                    package,
+                    &deps_to_install,
+                    &deps_present,
+                    &modules_to_publish,
+                )?;
+            }
+            if filtered {
+                print_filter_summary(&modules_to_publish, &skipped_modules)?;
+            }
         }
     } else {
         // NOTE: the VM enforces the most strict way of module republishing and does not allow
         // backward incompatible changes, as as result, if this flag is set, we skip the VM process
         // and force the CLI to override the on-disk state directly
         let mut serialized_modules = vec![];
-        for unit in modules_to_publish {
+        let mut changes = vec![];
+        let all_units = deps_to_install
+            .iter()
+            .map(|(_, unit)| *unit)
+            .chain(modules_to_publish.iter().copied());
+        for unit in all_units {
             let id = module(&unit.unit)?.self_id();
             let module_bytes = unit.unit.serialize(bytecode_version);
+            changes.push(if state.has_module(&id) {
+                format!("updated module {}", id.short_str_lossless())
+            } else {
+                format!("published module {}", id.short_str_lossless())
+            });
             serialized_modules.push((id, module_bytes));
         }
         state.save_modules(&serialized_modules)?;
+        published.extend(
+            serialized_modules
+                .into_iter()
+                .map(|(module_id, _)| module_id),
+        );
+        record_transaction(state.storage_dir(), "publish", log_args, changes, 0)?;
+        if bundle_deps {
+            print_install_manifest(
+                package,
+                &deps_to_install,
+                &deps_present,
+                &modules_to_publish,
+            )?;
+        }
+        if filtered {
+            print_filter_summary(&modules_to_publish, &skipped_modules)?;
+        }
     }
 
+    Ok(PublishOutput { published })
+}
+
+/// The result of `publish_in_batches`
+struct BatchedPublish {
+    published: Vec<ModuleId>,
+    /// The changes to record in the transaction log
+    changes: Vec<String>,
+    num_batches: usize,
+}
+
+/// Publish `units` in dependency order, `batch_size` modules at a time. Each batch is published in
+/// its own VM session, whose changes are saved before the next batch is published, so that the
+/// changes of the whole package are never held in memory at once. If a batch fails to publish, the
+/// batches saved before it are rolled back, so that storage is left as it was. Returns `None` if
+/// a batch failed; the reason is explained on stdout.
+fn publish_in_batches(
+    natives: Vec<NativeFunctionRecord>,
+    cost_table: &CostTable,
+    state: &OnDiskStateView,
+    units: &[&CompiledUnitWithSource],
+    batch_size: usize,
+    show_diff: bool,
+    verbose: bool,
+) -> Result<Option<BatchedPublish>> {
+    let bytecode_version = get_bytecode_version_from_env();
+    let units = topological_order(units)?;
+    let ids = units
+        .iter()
+        .map(|unit| Ok(module(&unit.unit)?.self_id()))
+        .collect::<Result<Vec<_>>>()?;
+    let snapshot = state.snapshot_modules(&ids)?;
+    let interrupted_snapshot = snapshot.clone();
+    let _rollback_on_interrupt = interrupt::on_interrupt(move || {
+        let _ = interrupted_snapshot.restore();
+    });
+
+    let num_batches = (units.len() + batch_size - 1) / batch_size;
+    let mut published = vec![];
+    let mut changes = vec![];
+    for (i, batch) in units.chunks(batch_size).enumerate() {
+        // a fresh VM per batch, as the VM caches the modules it loads
+        let vm = MoveVM::new(natives.clone()).unwrap();
+        let mut gas_status = get_gas_status(cost_table, None)?;
+        let mut session = vm.new_session(state);
+        for unit in batch {
+            let module_bytes = unit.unit.serialize(bytecode_version);
+            let sender = *module(&unit.unit)?.self_id().address();
+            if let Err(err) = session.publish_module(module_bytes, sender, &mut gas_status) {
+                snapshot.restore()?;
+                explain_publish_error(err, state, unit, !show_diff)?;
+                println!(
+                    "Publishing batch {} of {} failed. The {} batch(es) published before were \
+                     rolled back.",
+                    i + 1,
+                    num_batches,
+                    i
+                );
+                return Ok(None);
+            }
+        }
+        let (changeset, events) = session.finish().map_err(|e| e.into_vm_status())?;
+        if verbose {
+            println!("Batch {} of {}:", i + 1, num_batches);
+            explain_publish_changeset(&changeset);
+        }
+        changes.extend(summarize_changes(&changeset, &events));
+        let modules: Vec<_> = changeset
+            .into_modules()
+            .map(|(module_id, blob_opt)| (module_id, blob_opt.ok().expect("must be non-deletion")))
+            .collect();
+        if let Err(err) = state.save_modules(&modules) {
+            snapshot.restore()?;
+            return Err(err);
+        }
+        published.extend(modules.into_iter().map(|(module_id, _)| module_id));
+    }
+    Ok(Some(BatchedPublish {
+        published,
+        changes,
+        num_batches,
+    }))
+}
+
+/// Order `units` so that every module comes after the modules of `units` it depends on. Among the
+/// modules whose dependencies are all placed, the one with the smallest id comes first, so that the
+/// order does not depend on the order of `units`.
+fn topological_order<'a>(
+    units: &[&'a CompiledUnitWithSource],
+) -> Result<Vec<&'a CompiledUnitWithSource>> {
+    let mut pending = BTreeMap::new();
+    for unit in units {
+        let compiled_module = module(&unit.unit)?;
+        pending.insert(
+            compiled_module.self_id(),
+            (*unit, compiled_module.immediate_dependencies()),
+        );
+    }
+    let mut ordered = vec![];
+    while !pending.is_empty() {
+        let next = pending
+            .iter()
+            .find(|(_, (_, deps))| deps.iter().all(|dep| !pending.contains_key(dep)))
+            .map(|(id, _)| id.clone());
+        match next {
+            Some(id) => ordered.push(pending.remove(&id).unwrap().0),
+            None => bail!("Circular dependency detected"),
+        }
+    }
+    Ok(ordered)
+}
+
+/// Split `units` into the modules to publish and the modules to skip, according to the glob patterns
+/// of `--include-module` and `--exclude-module`. Fails if a pattern matches no module, or if a module
+/// to publish depends on a skipped module that storage does not hold.
+fn filter_modules<'a>(
+    units: Vec<&'a CompiledUnitWithSource>,
+    include_modules: &[String],
+    exclude_modules: &[String],
+    state: &OnDiskStateView,
+) -> Result<(
+    Vec<&'a CompiledUnitWithSource>,
+    Vec<&'a CompiledUnitWithSource>,
+)> {
+    let include = glob_patterns(include_modules)?;
+    let exclude = glob_patterns(exclude_modules)?;
+    for (flag, patterns) in [
+        ("--include-module", &include),
+        ("--exclude-module", &exclude),
+    ] {
+        for (pattern, regex) in patterns {
+            if !units
+                .iter()
+                .any(|unit| regex.is_match(unit.unit.name().as_str()))
+            {
+                bail!("{} pattern '{}' matches no module", flag, pattern)
+            }
+        }
+    }
+
+    let (included, skipped): (Vec<_>, Vec<_>) = units.into_iter().partition(|unit| {
+        let name = unit.unit.name();
+        let matches = |patterns: &[(&String, Regex)]| {
+            patterns
+                .iter()
+                .any(|(_, regex)| regex.is_match(name.as_str()))
+        };
+        (include.is_empty() || matches(&include)) && !matches(&exclude)
+    });
+
+    let skipped_ids = skipped
+        .iter()
+        .map(|unit| Ok(module(&unit.unit)?.self_id()))
+        .collect::<Result<BTreeSet<_>>>()?;
+    for unit in &included {
+        let compiled_module = module(&unit.unit)?;
+        for dep in compiled_module.immediate_dependencies() {
+            if skipped_ids.contains(&dep) && !state.has_module(&dep) {
+                bail!(
+                    "Module {} depends on module {}, which is skipped by the --include-module \
+                     and --exclude-module patterns and is not in storage",
+                    compiled_module.self_id().short_str_lossless(),
+                    dep.short_str_lossless()
+                )
+            }
+        }
+    }
+    Ok((included, skipped))
+}
+
+/// Compile glob patterns, in which `*` matches any sequence of characters and `?` any character,
+/// into regexes matching whole module names
+fn glob_patterns(patterns: &[String]) -> Result<Vec<(&String, Regex)>> {
+    patterns
+        .iter()
+        .map(|pattern| {
+            let regex = pattern
+                .split('*')
+                .map(|part| {
+                    part.split('?')
+                        .map(regex::escape)
+                        .collect::<Vec<_>>()
+                        .join(".")
+                })
+                .collect::<Vec<_>>()
+                .join(".*");
+            Ok((pattern, Regex::new(&format!("^{}$", regex))?))
+        })
+        .collect()
+}
+
+/// Print which modules `sandbox publish` published and which it skipped because of the
+/// `--include-module` and `--exclude-module` patterns, sorted by module id: the order of the units
+/// of a package differs between a fresh build and one loaded from the build directory
+fn print_filter_summary(
+    included: &[&CompiledUnitWithSource],
+    skipped: &[&CompiledUnitWithSource],
+) -> Result<()> {
+    for (heading, units) in [
+        ("Included modules:", included),
+        ("Skipped modules:", skipped),
+    ] {
+        if units.is_empty() {
+            continue;
+        }
+        println!("{}", heading);
+        let ids = units
+            .iter()
+            .map(|unit| Ok(module(&unit.unit)?.self_id()))
+            .collect::<Result<BTreeSet<_>>>()?;
+        for id in ids {
+            println!("  {}", id.short_str_lossless());
+        }
+    }
+    Ok(())
+}
+
+/// Return the modules of all dependencies of `package` together with the name of the package that
+/// defines them, in an order in which they can be published.
+fn ordered_dependency_modules(
+    package: &CompiledPackage,
+) -> Result<Vec<(PackageName, &CompiledUnitWithSource)>> {
+    let mut units = BTreeMap::new();
+    for (package_name, unit) in &package.deps_compiled_units {
+        if let Ok(compiled_module) = module(&unit.unit) {
+            units.insert(compiled_module.self_id(), (*package_name, unit));
+        }
+    }
+    let modules = units
+        .values()
+        .map(|(_, unit)| module(&unit.unit))
+        .collect::<Result<Vec<_>>>()?;
+    let modules = Modules::new(modules);
+    let graph = modules.compute_dependency_graph();
+    let ordered = graph
+        .compute_topological_order()?
+        .map(|m| units[&m.self_id()])
+        .collect();
+    Ok(ordered)
+}
+
+/// Describe how the dependency module `new_module` from `package_name` differs from the version of
+/// the module already in storage.
+fn explain_module_conflict(
+    package_name: PackageName,
+    stored_module: &CompiledModule,
+    new_module: &CompiledModule,
+) -> String {
+    let old_api = normalized::Module::new(stored_module);
+    let new_api = normalized::Module::new(new_module);
+    let mut differences = vec![];
+    diff_members(
+        "struct",
+        &old_api.structs,
+        &new_api.structs,
+        &mut differences,
+    );
+    diff_members(
+        "function",
+        &old_api.exposed_functions,
+        &new_api.exposed_functions,
+        &mut differences,
+    );
+    if old_api.friends != new_api.friends {
+        differences.push("friend declarations differ".to_string());
+    }
+    if differences.is_empty() {
+        differences.push("module implementation differs".to_string());
+    }
+    format!(
+        "  {} (from {}): {}",
+        new_module.self_id().short_str_lossless(),
+        package_name,
+        differences.join(", ")
+    )
+}
+
+fn diff_members<K: Ord + Display, V: PartialEq>(
+    kind: &str,
+    stored: &BTreeMap<K, V>,
+    new: &BTreeMap<K, V>,
+    differences: &mut Vec<String>,
+) {
+    for (name, value) in new {
+        match stored.get(name) {
+            None => differences.push(format!("{} {} is not in storage", kind, name)),
+            Some(stored_value) if stored_value != value => {
+                differences.push(format!("{} {} has a different signature", kind, name))
+            }
+            Some(_) => (),
+        }
+    }
+    for name in stored.keys().filter(|name| !new.contains_key(name)) {
+        differences.push(format!("{} {} is only in storage", kind, name));
+    }
+}
+
+/// Print what `sandbox publish --bundle-deps` put into storage
+fn print_install_manifest(
+    package: &CompiledPackage,
+    deps_installed: &[(PackageName, &CompiledUnitWithSource)],
+    deps_present: &[(PackageName, &CompiledUnitWithSource)],
+    root_modules: &[&CompiledUnitWithSource],
+) -> Result<()> {
+    let root_name = package.compiled_package_info.package_name;
+    let root_modules = root_modules.iter().map(|unit| (root_name, *unit));
+    println!("Installed modules:");
+    for (package_name, unit) in deps_installed.iter().copied().chain(root_modules) {
+        println!(
+            "  {} (from {})",
+            module(&unit.unit)?.self_id().short_str_lossless(),
+            package_name
+        );
+    }
+    if !deps_present.is_empty() {
+        println!("Already in storage:");
+        for (package_name, unit) in deps_present {
+            println!(
+                "  {} (from {})",
+                module(&unit.unit)?.self_id().short_str_lossless(),
+                package_name
+            );
+        }
+    }
+    Ok(())
+}
+
+/// Rewrite `units` as if `package`, whose root directory is `package_path`, had been built with
+/// the named addresses in `address_mapping` bound to the given values, and check that the
+/// resulting modules still pass the bytecode verifier and link against each other and the modules
+/// in `state`.
+fn remap_named_addresses(
+    package: &CompiledPackage,
+    package_path: &Path,
+    units: Vec<&CompiledUnitWithSource>,
+    address_mapping: &[(String, NumericalAddress)],
+    state: &OnDiskStateView,
+) -> Result<Vec<CompiledUnitWithSource>> {
+    let instantiation = &package.compiled_package_info.address_alias_instantiation;
+    let mut substitution = BTreeMap::new();
+    for (name, new_addr) in address_mapping {
+        let old_addr = match instantiation
+            .iter()
+            .find(|(named_addr, _)| named_addr.as_str() == name)
+        {
+            Some((_, addr)) => *addr,
+            None => bail!(
+                "Unable to remap named address '{}': it is not declared by package {} or its dependencies",
+                name,
+                package.compiled_package_info.package_name
+            ),
+        };
+        // dependencies may know a named address under another name, so the addresses are
+        // substituted by value and all names bound to the same value have to be remapped together
+        for (other_name, addr) in instantiation {
+            if *addr != old_addr {
+                continue;
+            }
+            let other_new_addr = address_mapping
+                .iter()
+                .find(|(n, _)| n == other_name.as_str())
+                .map(|(_, a)| a.into_inner());
+            if other_new_addr != Some(new_addr.into_inner()) {
+                bail!(
+                    "Named addresses '{}' and '{}' are both bound to 0x{} and must be mapped to \
+                     the same address",
+                    name,
+                    other_name,
+                    old_addr.short_str_lossless()
+                )
+            }
+        }
+        substitution.insert(old_addr, new_addr.into_inner());
+    }
+
+    // the compiled code does not tell the named addresses it was written with, such as
+    // `@Example`, from literal addresses, so the package is built again with the new values
+    let rebuilt = rebuild_package(package, package_path, &substitution)?;
+    let remapped = units
+        .into_iter()
+        .map(|unit| {
+            let rebuilt_unit = rebuilt
+                .all_compiled_units_with_source()
+                .find(|rebuilt_unit| {
+                    rebuilt_unit.source_path == unit.source_path
+                        && rebuilt_unit.unit.name() == unit.unit.name()
+                });
+            let unit = match rebuilt_unit {
+                Some(rebuilt_unit) => rebuilt_unit.clone(),
+                None => bail!(
+                    "Module {} is missing from the package built with its addresses remapped",
+                    unit.unit.name()
+                ),
+            };
+            if let CompiledUnit::Module(NamedCompiledModule { module, .. }) = &unit.unit {
+                if let Err(err) = move_bytecode_verifier::verify_module(module) {
+                    bail!(
+                        "Module {} does not verify after remapping its addresses: {}",
+                        module.self_id(),
+                        err
+                    )
+                }
+            }
+            Ok(unit)
+        })
+        .collect::<Result<Vec<_>>>()?;
+    check_remapped_dependencies(&remapped, state)?;
+    Ok(remapped)
+}
+
+/// Build `package` again from its sources at `package_path`, with the named addresses bound to an
+/// address `substitution` maps bound to the address it maps it to. The build goes to a temporary
+/// directory, leaving that of the package alone.
+fn rebuild_package(
+    package: &CompiledPackage,
+    package_path: &Path,
+    substitution: &BTreeMap<AccountAddress, AccountAddress>,
+) -> Result<CompiledPackage> {
+    let install_dir = tempfile::tempdir()?;
+    let build_config = BuildConfig {
+        install_dir: Some(install_dir.path().to_path_buf()),
+        out_dir: None,
+        lock_file: None,
+        ..package.compiled_package_info.build_flags.clone()
+    };
+    let mut graph = build_config.resolution_graph_for_package(package_path)?;
+    for resolved_package in graph.package_table.values_mut() {
+        for addr in resolved_package.resolution_table.values_mut() {
+            if let Some(new_addr) = substitution.get(addr) {
+                *addr = *new_addr;
+            }
+        }
+    }
+    let mutx = PackageLock::lock();
+    let rebuilt = BuildPlan::create(graph)?.compile_with_driver(&mut std::io::sink(), |compiler| {
+        // the warnings were reported when the package was first built
+        let (files, units_res) = compiler.build()?;
+        match units_res {
+            Ok((units, _warnings)) => Ok((files, units)),
+            Err(diags) => bail!(
+                "Package {} does not compile with its addresses remapped:\n{}",
+                package.compiled_package_info.package_name,
+                String::from_utf8_lossy(&report_diagnostics_to_buffer(&files, diags))
+            ),
+        }
+    });
+    mutx.unlock();
+    rebuilt
+}
+
+/// Check that each of the remapped modules in `units` still links: the modules it depends on at
+/// their new address must be among `units` or in `state`, and declare what it uses from them
+fn check_remapped_dependencies(
+    units: &[CompiledUnitWithSource],
+    state: &OnDiskStateView,
+) -> Result<()> {
+    let remapped: BTreeMap<_, _> = units
+        .iter()
+        .filter_map(|unit| match &unit.unit {
+            CompiledUnit::Module(NamedCompiledModule { module, .. }) => {
+                Some((module.self_id(), module))
+            }
+            CompiledUnit::Script(_) => None,
+        })
+        .collect();
+    for module in remapped.values() {
+        let mut published_with = vec![];
+        let mut stored = vec![];
+        for dep_id in module.immediate_dependencies() {
+            if let Some(dep) = remapped.get(&dep_id) {
+                published_with.push(*dep);
+                continue;
+            }
+            match state.get_module_by_id(&dep_id)? {
+                Some(dep) => stored.push(dep),
+                None => bail!(
+                    "Module {} depends on {} once its addresses are remapped, which is neither \
+                     in storage nor published with it",
+                    module.self_id().short_str_lossless(),
+                    dep_id.short_str_lossless()
+                ),
+            }
+        }
+        let deps = published_with.into_iter().chain(&stored);
+        if let Err(err) = move_bytecode_verifier::dependencies::verify_module(module, deps) {
+            bail!(
+                "Module {} does not link against its dependencies after remapping its \
+                 addresses: {}",
+                module.self_id(),
+                err
+            )
+        }
+    }
     Ok(())
 }
@@ -3,43 +3,118 @@
 // SPDX-License-Identifier: Apache-2.0
 
 use crate::{
-    sandbox::utils::{
-        contains_module, explain_execution_effects, explain_execution_error, get_gas_status,
-        is_bytecode_file, maybe_commit_effects, on_disk_state_view::OnDiskStateView,
+    sandbox::{
+        cli::OutputFormat,
+        utils::{
+            contains_module, explain_execution_effects, explain_execution_error, get_gas_status,
+            is_bytecode_file, maybe_commit_effects, on_disk_state_view::OnDiskStateView,
+            preview_effects, print_effects_preview, summarize_changes, EffectsPreview,
+        },
     },
     NativeFunctionRecord,
 };
 use anyhow::{anyhow, bail, Result};
-use move_binary_format::file_format::CompiledModule;
+use move_binary_format::{
+    access::{ModuleAccess, ScriptAccess},
+    binary_views::BinaryIndexedView,
+    file_format::{CompiledModule, FunctionDefinitionIndex, SignatureToken, TableIndex},
+};
+use move_bytecode_source_map::source_map::FunctionSourceMap;
 use move_command_line_common::env::get_bytecode_version_from_env;
+use move_compiler::compiled_unit::CompiledUnit;
 use move_core_types::{
     account_address::AccountAddress,
+    effects::Event,
     errmap::ErrorMapping,
     identifier::IdentStr,
     language_storage::TypeTag,
     transaction_argument::{convert_txn_args, TransactionArgument},
     value::MoveValue,
+    vm_status::{StatusCode, VMStatus},
 };
-use move_package::compilation::compiled_package::CompiledPackage;
-use move_vm_runtime::move_vm::MoveVM;
+use move_package::{compilation::compiled_package::CompiledPackage, DebugInfo};
+use move_stdlib::natives::timestamp::NativeClockContext;
+use move_vm_runtime::{move_vm::MoveVM, native_extensions::NativeContextExtensions};
 use move_vm_test_utils::gas_schedule::CostTable;
-use std::{fs, path::Path};
+use std::{collections::BTreeMap, fmt, fs, path::PathBuf};
+
+/// The memory quota of transactions run without `--memory-quota`, in MB
+pub const DEFAULT_MEMORY_QUOTA_MB: u64 = 1024;
+
+/// Options for `run`. See `move sandbox run --help` for their meaning.
+#[derive(Debug, Default)]
+pub struct RunOptions {
+    /// Script source file, or script or module bytecode file under storage
+    pub script_path: PathBuf,
+    /// Entry function to call if `script_path` is a module
+    pub script_name: Option<String>,
+    pub signers: Vec<String>,
+    pub args: Vec<TransactionArgument>,
+    /// Arguments bound to the parameters of the same name. Cannot be mixed with `args`.
+    pub named_args: Vec<(String, TransactionArgument)>,
+    pub type_args: Vec<TypeTag>,
+    pub gas_budget: Option<u64>,
+    /// Memory the vectors built by the execution may hold, in MB. Defaults to
+    /// `DEFAULT_MEMORY_QUOTA_MB`; the peak memory is only reported when it is set.
+    pub memory_quota: Option<u64>,
+    /// Time `std::timestamp` reports during the transaction, in microseconds
+    pub initial_time: u64,
+    /// Block height `std::timestamp` reports during the transaction
+    pub initial_height: u64,
+    pub dry_run: bool,
+    /// Output format of the effects previewed by a dry run
+    pub format: OutputFormat,
+    pub verbose: bool,
+}
+
+/// The result of `run`
+#[derive(Debug, Default)]
+pub struct RunOutput {
+    /// The status the VM aborted execution with, or `None` if execution succeeded. The error is
+    /// explained on stdout.
+    pub error: Option<VMStatus>,
+    /// Summary of the modules, resources and events changed by the transaction
+    pub changes: Vec<String>,
+    /// The events emitted by the transaction
+    pub events: Vec<Event>,
+    /// Gas consumed by the transaction. Always 0 when no gas budget was given.
+    pub gas_used: u64,
+    /// The most memory held at once by the vectors built by the transaction, in bytes
+    pub peak_memory: u64,
+    /// The effects the transaction would have had, if it was a dry run that succeeded
+    pub preview: Option<EffectsPreview>,
+}
 
+/// Run the script or entry function of `options`, committing its effects unless it is a dry run.
+/// The transaction is recorded in the transaction log by the caller, under the name of the command
+/// that ran it.
 pub fn run(
     natives: impl IntoIterator<Item = NativeFunctionRecord>,
     cost_table: &CostTable,
     error_descriptions: &ErrorMapping,
     state: &OnDiskStateView,
     package: &CompiledPackage,
-    script_path: &Path,
-    script_name_opt: &Option<String>,
-    signers: &[String],
-    txn_args: &[TransactionArgument],
-    vm_type_args: Vec<TypeTag>,
-    gas_budget: Option<u64>,
-    dry_run: bool,
-    verbose: bool,
-) -> Result<()> {
+    options: &RunOptions,
+) -> Result<RunOutput> {
+    let RunOptions {
+        script_path,
+        script_name: script_name_opt,
+        signers,
+        args: txn_args,
+        named_args,
+        type_args: vm_type_args,
+        gas_budget,
+        memory_quota,
+        initial_time,
+        initial_height,
+        dry_run,
+        format,
+        verbose,
+    } = options;
+    let (gas_budget, dry_run, verbose) = (*gas_budget, *dry_run, *verbose);
+    if let (OutputFormat::Json, false) = (format, dry_run) {
+        bail!("`--format json` is only supported with `--dry-run`")
+    }
     if !script_path.exists() {
         bail!("Script file {:?} does not exist", script_path)
     };
@@ -63,8 +138,27 @@ move run` must be applied to a module inside `storage/`",
         // script source file; package is already compiled so load it up
         match script_opt {
             Some(unit) => unit.unit.serialize(bytecode_version),
-            None => bail!("Unable to find script in file {:?}", script_path),
+            None => {
+                // scripts are matched to their source file by their source maps
+                package.ensure_source_maps("Running a script from its source file")?;
+                bail!("Unable to find script in file {:?}", script_path)
+            }
+        }
+    };
+
+    let txn_args = &if named_args.is_empty() {
+        txn_args.clone()
+    } else {
+        if !txn_args.is_empty() {
+            bail!("Arguments can be passed by name with `--arg` or by position with `--args`, but not both")
         }
+        EntryFunction::find(
+            package,
+            &bytecode,
+            script_name_opt.as_deref(),
+            bytecode_version,
+        )?
+        .bind(named_args)?
     };
 
     let signer_addresses = signers
@@ -75,8 +169,22 @@ move run` must be applied to a module inside `storage/`",
     let vm_args: Vec<Vec<u8>> = convert_txn_args(txn_args);
 
     let vm = MoveVM::new(natives).unwrap();
-    let mut gas_status = get_gas_status(cost_table, gas_budget)?;
-    let mut session = vm.new_session(state);
+    let memory_quota_mb = memory_quota.unwrap_or(DEFAULT_MEMORY_QUOTA_MB);
+    let memory_quota_bytes = match memory_quota_mb.checked_mul(1 << 20) {
+        Some(bytes) => bytes,
+        None => bail!(
+            "Memory quota set too high; maximum is {} MB",
+            u64::MAX >> 20
+        ),
+    };
+    let mut gas_status =
+        get_gas_status(cost_table, gas_budget)?.with_memory_quota(memory_quota_bytes.into());
+    let mut extensions = NativeContextExtensions::default();
+    extensions.add(NativeClockContext {
+        now_microseconds: *initial_time,
+        block_height: *initial_height,
+    });
+    let mut session = vm.new_session_with_extensions(state, extensions);
 
     let script_type_parameters = vec![];
     let script_parameters = vec![];
@@ -111,22 +219,324 @@ move run` must be applied to a module inside `storage/`",
         ),
     };
 
+    let peak_memory = u64::from(gas_status.peak_memory());
     if let Err(err) = res {
+        let status = err.clone().into_vm_status();
         explain_execution_error(
             error_descriptions,
             err,
             state,
             &script_type_parameters,
             &script_parameters,
-            &vm_type_args,
+            vm_type_args,
             &signer_addresses,
             txn_args,
-        )
+        )?;
+        if status.status_code() == StatusCode::MEMORY_QUOTA_EXCEEDED {
+            let message = format!(
+                "The memory quota is {} MB; raise it with `--memory-quota <MB>`",
+                memory_quota_mb
+            );
+            match format {
+                OutputFormat::Text => println!("{}", message),
+                OutputFormat::Json => eprintln!("{}", message),
+            }
+        }
+        Ok(RunOutput {
+            error: Some(status),
+            peak_memory,
+            ..RunOutput::default()
+        })
     } else {
         let (changeset, events) = session.finish().map_err(|e| e.into_vm_status())?;
-        if verbose {
-            explain_execution_effects(&changeset, &events, state)?
+        let changes = summarize_changes(&changeset, &events);
+        let gas_used = gas_budget.map_or(0, |budget| {
+            budget.saturating_sub(gas_status.remaining_gas().into())
+        });
+        let preview = if dry_run {
+            // the preview explains the effects in full, so the verbose explanation is not needed
+            let mut preview = preview_effects(&changeset, &events, state, gas_used)?;
+            if memory_quota.is_some() {
+                preview.peak_memory = Some(peak_memory);
+            }
+            print_effects_preview(&preview, *format)?;
+            Some(preview)
+        } else {
+            if verbose {
+                explain_execution_effects(&changeset, &events, state)?
+            }
+            if memory_quota.is_some() {
+                println!(
+                    "Peak memory: {} bytes of the {} MB quota",
+                    peak_memory, memory_quota_mb
+                )
+            }
+            maybe_commit_effects(true, changeset, events.clone(), state)?;
+            None
+        };
+        Ok(RunOutput {
+            error: None,
+            changes,
+            events,
+            gas_used,
+            peak_memory,
+            preview,
+        })
+    }
+}
+
+/// A function a transaction can call, with the parameter names recorded in its source map
+struct EntryFunction {
+    name: String,
+    /// The name and type of each parameter
+    parameters: Vec<(String, SignatureToken)>,
+    /// Formatted types of the parameters, by name
+    parameter_types: BTreeMap<String, String>,
+}
+
+impl EntryFunction {
+    /// Find the function `script_name` of the module in `bytecode`, or the script in `bytecode`
+    /// if `script_name` is not set, among the compiled units of `package`
+    fn find(
+        package: &CompiledPackage,
+        bytecode: &[u8],
+        script_name: Option<&str>,
+        bytecode_version: Option<u32>,
+    ) -> Result<Self> {
+        let debug_info = package.compiled_package_info.build_flags.debug_info();
+        if debug_info != DebugInfo::Full {
+            bail!(
+                "Parameter names are not recorded for packages built with `--debug-info {}`. \
+                 Pass arguments with `--args` instead",
+                debug_info
+            )
+        }
+        match script_name {
+            Some(script_name) => {
+                let module_id = CompiledModule::deserialize(bytecode)
+                    .map_err(|e| anyhow!("Error deserializing module: {:?}", e))?
+                    .self_id();
+                let unit = package.all_modules().find_map(|unit| match &unit.unit {
+                    CompiledUnit::Module(unit) if unit.module.self_id() == module_id => Some(unit),
+                    _ => None,
+                });
+                let unit = match unit {
+                    Some(unit) => unit,
+                    None => bail!(
+                        "Parameter names of module {} are unknown, as it is not part of this \
+                         package or its dependencies. Pass arguments with `--args` instead",
+                        module_id
+                    ),
+                };
+                let module = &unit.module;
+                let (idx, function) = module
+                    .function_defs()
+                    .iter()
+                    .enumerate()
+                    .find(|(_, function)| {
+                        module
+                            .identifier_at(module.function_handle_at(function.function).name)
+                            .as_str()
+                            == script_name
+                    })
+                    .ok_or_else(|| anyhow!("Function {}::{} not found", module_id, script_name))?;
+                let handle = module.function_handle_at(function.function);
+                Self::new(
+                    script_name,
+                    BinaryIndexedView::Module(module),
+                    &module.signature_at(handle.parameters).0,
+                    unit.source_map
+                        .get_function_source_map(FunctionDefinitionIndex(idx as TableIndex))?,
+                )
+            }
+            None => {
+                let unit = package.scripts().find_map(|unit| match &unit.unit {
+                    CompiledUnit::Script(script)
+                        if unit.unit.serialize(bytecode_version) == bytecode =>
+                    {
+                        Some(script)
+                    }
+                    _ => None,
+                });
+                let unit = match unit {
+                    Some(unit) => unit,
+                    None => bail!(
+                        "Parameter names of the script are unknown, as it is not part of this \
+                         package. Pass arguments with `--args` instead"
+                    ),
+                };
+                let script = &unit.script;
+                Self::new(
+                    unit.name.as_str(),
+                    BinaryIndexedView::Script(script),
+                    &script.signature_at(script.parameters).0,
+                    unit.source_map
+                        .get_function_source_map(FunctionDefinitionIndex(0))?,
+                )
+            }
         }
-        maybe_commit_effects(!dry_run, changeset, events, state)
+    }
+
+    fn new(
+        name: &str,
+        view: BinaryIndexedView,
+        parameter_types: &[SignatureToken],
+        source_map: &FunctionSourceMap,
+    ) -> Result<Self> {
+        if source_map.parameters.len() != parameter_types.len() {
+            bail!(
+                "Parameter names of {} are missing from its source map",
+                name
+            )
+        }
+        let parameters: Vec<_> = source_map
+            .parameters
+            .iter()
+            .zip(parameter_types)
+            .map(|((name, _), ty)| {
+                // the compiler suffixes the names of locals with `#` and an index to make them unique
+                let name = name.split('#').next().unwrap_or(name).to_string();
+                (name, ty.clone())
+            })
+            .collect();
+        let parameter_types = parameters
+            .iter()
+            .map(|(name, ty)| (name.clone(), format_type(&view, ty)))
+            .collect();
+        Ok(Self {
+            name: name.to_string(),
+            parameters,
+            parameter_types,
+        })
+    }
+
+    /// Order `named_args` like the parameters they are bound to. Signer parameters are bound by
+    /// `--signers` instead, and every other parameter must be bound exactly once.
+    fn bind(
+        &self,
+        named_args: &[(String, TransactionArgument)],
+    ) -> Result<Vec<TransactionArgument>> {
+        let mut args = BTreeMap::new();
+        for (name, arg) in named_args {
+            match self.parameters.iter().find(|(param, _)| param == name) {
+                None => bail!("Unknown parameter `{}` of `{}`", name, self),
+                Some((_, ty)) if is_signer(ty) => bail!(
+                    "Parameter `{}` of `{}` is a signer, which is passed with `--signers`",
+                    name,
+                    self
+                ),
+                Some((_, ty)) => {
+                    let arg = self.fit_integer(name, ty, arg)?;
+                    if args.insert(name.as_str(), arg).is_some() {
+                        bail!(
+                            "Parameter `{}` of `{}` is passed more than once",
+                            name,
+                            self
+                        )
+                    }
+                }
+            }
+        }
+        let missing: Vec<_> = self
+            .parameters
+            .iter()
+            .filter(|(name, ty)| !is_signer(ty) && !args.contains_key(name.as_str()))
+            .map(|(name, _)| format!("`{}`", name))
+            .collect();
+        if !missing.is_empty() {
+            bail!(
+                "Missing argument(s) for parameter(s) {} of `{}`",
+                missing.join(", "),
+                self
+            )
+        }
+        Ok(self
+            .parameters
+            .iter()
+            .filter_map(|(name, _)| args.remove(name.as_str()))
+            .collect())
+    }
+
+    /// Integers without a suffix parse as `u64`: give them the integer type of the parameter
+    /// they are passed to instead, so that e.g. `--arg level=3` passes a `u8`
+    fn fit_integer(
+        &self,
+        name: &str,
+        ty: &SignatureToken,
+        arg: &TransactionArgument,
+    ) -> Result<TransactionArgument> {
+        Ok(match (ty, arg) {
+            (SignatureToken::U8, TransactionArgument::U64(value)) => match u8::try_from(*value) {
+                Ok(value) => TransactionArgument::U8(value),
+                Err(_) => bail!(
+                    "Argument {} of parameter `{}` of `{}` does not fit in a u8",
+                    value,
+                    name,
+                    self
+                ),
+            },
+            (SignatureToken::U128, TransactionArgument::U64(value)) => {
+                TransactionArgument::U128(*value as u128)
+            }
+            _ => arg.clone(),
+        })
+    }
+}
+
+impl fmt::Display for EntryFunction {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let parameters = self
+            .parameters
+            .iter()
+            .map(|(name, _)| format!("{}: {}", name, self.parameter_types[name]))
+            .collect::<Vec<_>>();
+        write!(f, "fun {}({})", self.name, parameters.join(", "))
+    }
+}
+
+fn is_signer(ty: &SignatureToken) -> bool {
+    match ty {
+        SignatureToken::Signer => true,
+        SignatureToken::Reference(ty) => **ty == SignatureToken::Signer,
+        _ => false,
+    }
+}
+
+fn format_type(view: &BinaryIndexedView, ty: &SignatureToken) -> String {
+    use SignatureToken::*;
+    let format_struct = |idx, type_args: &[SignatureToken]| {
+        let handle = view.struct_handle_at(idx);
+        let module = view.module_handle_at(handle.module);
+        let type_args = if type_args.is_empty() {
+            String::new()
+        } else {
+            let type_args = type_args
+                .iter()
+                .map(|ty| format_type(view, ty))
+                .collect::<Vec<_>>();
+            format!("<{}>", type_args.join(", "))
+        };
+        format!(
+            "0x{}::{}::{}{}",
+            view.address_identifier_at(module.address)
+                .short_str_lossless(),
+            view.identifier_at(module.name),
+            view.identifier_at(handle.name),
+            type_args
+        )
+    };
+    match ty {
+        Bool => "bool".to_string(),
+        U8 => "u8".to_string(),
+        U64 => "u64".to_string(),
+        U128 => "u128".to_string(),
+        Address => "address".to_string(),
+        Signer => "signer".to_string(),
+        Vector(ty) => format!("vector<{}>", format_type(view, ty)),
+        Struct(idx) => format_struct(*idx, &[]),
+        StructInstantiation(idx, type_args) => format_struct(*idx, type_args),
+        Reference(ty) => format!("&{}", format_type(view, ty)),
+        MutableReference(ty) => format!("&mut {}", format_type(view, ty)),
+        TypeParameter(idx) => format!("T{}", idx),
     }
 }
@@ -2,8 +2,13 @@
 // Copyright (c) The Move Contributors
 // SPDX-License-Identifier: Apache-2.0
 
-use crate::{sandbox::utils::module, DEFAULT_BUILD_DIR, DEFAULT_STORAGE_DIR};
+use super::integration_test::{self, INTEGRATION_TESTS_DIR};
+use crate::{
+    sandbox::utils::{module, rotated_logs_dir},
+    DEFAULT_BUILD_DIR, DEFAULT_STORAGE_DIR,
+};
 
+use anyhow::{anyhow, bail, Context};
 use move_command_line_common::{
     env::read_bool_env_var,
     files::{find_filenames, path_to_string},
@@ -17,10 +22,11 @@ use move_package::{
     source_package::{layout::SourcePackageLayout, manifest_parser::parse_move_manifest_from_file},
     BuildConfig,
 };
+use regex::Regex;
 use std::{
     collections::{BTreeMap, HashMap},
     env,
-    fmt::Write as FmtWrite,
+    fmt::{self, Write as FmtWrite},
     fs::{self, File},
     io::{self, BufRead, Write},
     path::{Path, PathBuf},
@@ -51,7 +57,7 @@ const MOVE_VM_TRACING_ENV_VAR_NAME: &str = "MOVE_VM_TRACE";
 /// dump the execution trace to. The trace will be used by the coverage tool
 /// if --track-cov is set. If --track-cov is not set, then no trace file will
 /// be produced.
-const DEFAULT_TRACE_FILE: &str = "trace";
+pub const DEFAULT_TRACE_FILE: &str = "trace";
 
 fn collect_coverage(
     trace_file: &Path,
@@ -149,7 +155,14 @@ fn copy_deps(tmp_dir: &Path, pkg_dir: &Path) -> anyhow::Result<PathBuf> {
     Ok(tmp_dir)
 }
 
-fn simple_copy_dir(dst: &Path, src: &Path) -> io::Result<()> {
+/// Copy `pkg_dir` and its dependencies under `tmp_dir`, and return the path of the copy of `pkg_dir`
+pub(crate) fn copy_package(tmp_dir: &Path, pkg_dir: &Path) -> anyhow::Result<PathBuf> {
+    let padded_dir = copy_deps(tmp_dir, pkg_dir)?;
+    simple_copy_dir(&padded_dir, pkg_dir)?;
+    Ok(padded_dir)
+}
+
+pub(crate) fn simple_copy_dir(dst: &Path, src: &Path) -> io::Result<()> {
     for entry in fs::read_dir(src)? {
         let src_entry = entry?;
         let src_entry_path = src_entry.path();
@@ -164,14 +177,181 @@ fn simple_copy_dir(dst: &Path, src: &Path) -> io::Result<()> {
     Ok(())
 }
 
-/// Run the `args_path` batch file with`cli_binary`
+/// Run the `move` commands in `lines`, formatted as in an `args.txt` batch file, with `cli_binary`
+/// in `work_dir`, and return their output. With a `trace_file`, the VM traces the execution into it.
+pub(crate) fn run_commands(
+    lines: impl IntoIterator<Item = String>,
+    cli_binary: &Path,
+    work_dir: &Path,
+    trace_file: Option<&Path>,
+) -> anyhow::Result<String> {
+    let mut output = "".to_string();
+    let lines: Vec<_> = lines.into_iter().collect();
+    check_lines(&lines)?;
+
+    // Disable colors in error reporting from the Move compiler
+    env::set_var(COLOR_MODE_ENV_VAR, "NONE");
+    for (line_number, args_line) in (1..).zip(lines) {
+        if let Some(external_cmd) = args_line.strip_prefix('>') {
+            let external_cmd = external_cmd.trim_start();
+            let mut cmd_iter = external_cmd.split_ascii_whitespace();
+
+            // checked by `check_lines`
+            let external_program = cmd_iter.next().unwrap();
+
+            let mut command = Command::new(external_program);
+            command.args(cmd_iter);
+            command.current_dir(work_dir);
+            let cmd_output = command.output().with_context(|| {
+                format!(
+                    "line {}: unable to run the external command `{}`",
+                    line_number, external_cmd
+                )
+            })?;
+
+            writeln!(&mut output, "External Command `{}`:", external_cmd)?;
+            output += std::str::from_utf8(&cmd_output.stdout)?;
+            output += std::str::from_utf8(&cmd_output.stderr)?;
+
+            continue;
+        }
+
+        if args_line.starts_with('#') {
+            // allow comments in args.txt
+            continue;
+        }
+        let args_iter: Vec<&str> = args_line.split_whitespace().collect();
+        if args_iter.is_empty() {
+            // allow blank lines in args.txt
+            continue;
+        }
+
+        // enable tracing in the VM by setting the env var.
+        match trace_file {
+            None => {
+                // this check prevents cascading the coverage tracking flag.
+                // in particular, if
+                //   1. we run with move-cli test <path-to-args-A.txt> --track-cov, and
+                //   2. in this <args-A.txt>, there is another command: test <args-B.txt>
+                // then, when running <args-B.txt>, coverage will not be tracked nor printed
+                env::remove_var(MOVE_VM_TRACING_ENV_VAR_NAME);
+            }
+            Some(path) => env::set_var(MOVE_VM_TRACING_ENV_VAR_NAME, path.as_os_str()),
+        }
+
+        let cmd_output = Command::new(cli_binary)
+            .current_dir(work_dir)
+            .args(args_iter)
+            .output()
+            .with_context(|| format!("line {}: unable to run `{}`", line_number, args_line))?;
+        writeln!(&mut output, "Command `{}`:", args_line)?;
+        output += std::str::from_utf8(&cmd_output.stdout)?;
+        output += std::str::from_utf8(&cmd_output.stderr)?;
+    }
+    Ok(output)
+}
+
+/// Check the lines of a batch file before running any of them, so that a malformed file does not
+/// leave the commands before the error run
+fn check_lines(lines: &[String]) -> anyhow::Result<()> {
+    for (line_number, args_line) in (1..).zip(lines) {
+        if let Some(external_cmd) = args_line.strip_prefix('>') {
+            if external_cmd.trim().is_empty() {
+                bail!(
+                    "line {}: `>` must be followed by the external command to run",
+                    line_number
+                )
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Why a batch file did not pass
+#[derive(Debug)]
+pub enum TestError {
+    /// The commands ran, but their output differs from the expected one
+    Failed(anyhow::Error),
+    /// The batch file could not be run, e.g. because it is malformed or its expected output is
+    /// missing
+    Harness(anyhow::Error),
+}
+
+impl fmt::Display for TestError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Failed(err) | Self::Harness(err) => write!(f, "{:#}", err),
+        }
+    }
+}
+
+impl std::error::Error for TestError {}
+
+impl From<anyhow::Error> for TestError {
+    fn from(err: anyhow::Error) -> Self {
+        Self::Harness(err)
+    }
+}
+
+impl From<io::Error> for TestError {
+    fn from(err: io::Error) -> Self {
+        Self::Harness(err.into())
+    }
+}
+
+/// The outcome of the batch files run by `run_all`
+#[derive(Debug, Default)]
+pub struct TestSummary {
+    /// The batch files that passed
+    pub passed: Vec<PathBuf>,
+    /// The batch files whose output differs from the expected one, with the difference
+    pub failed: Vec<(PathBuf, String)>,
+    /// The batch files that could not be run, with why
+    pub harness_errors: Vec<(PathBuf, String)>,
+}
+
+impl TestSummary {
+    pub fn total(&self) -> usize {
+        self.passed.len() + self.failed.len() + self.harness_errors.len()
+    }
+
+    pub fn is_success(&self) -> bool {
+        self.failed.is_empty() && self.harness_errors.is_empty()
+    }
+
+    /// Fail unless every batch file passed
+    pub fn check(&self) -> anyhow::Result<()> {
+        match (self.failed.len(), self.harness_errors.len()) {
+            (0, 0) => Ok(()),
+            (failed, 0) => bail!("{} / {} test(s) failed.", failed, self.total()),
+            (failed, harness_errors) => bail!(
+                "{} / {} test(s) failed, and {} could not be run.",
+                failed,
+                self.total(),
+                harness_errors
+            ),
+        }
+    }
+}
+
+/// Run the `args_path` batch file with`cli_binary`. Errors setting up or running the batch file,
+/// such as a malformed line, are told apart from output that differs from the expected one.
 pub fn run_one(
     args_path: &Path,
     cli_binary: &Path,
     use_temp_dir: bool,
     track_cov: bool,
-) -> anyhow::Result<Option<ExecCoverageMapWithModules>> {
-    let args_file = io::BufReader::new(File::open(args_path)?).lines();
+) -> Result<Option<ExecCoverageMapWithModules>, TestError> {
+    let args_file = io::BufReader::new(File::open(args_path)?)
+        .lines()
+        .collect::<io::Result<Vec<_>>>()?;
+    let update_baseline = read_env_update_baseline();
+    let exp_path = args_path.with_extension(EXP_EXT);
+    if !update_baseline && !exp_path.exists() {
+        return Err(TestError::Harness(anyhow!(add_update_baseline_fix(
+            format!("missing the expected output '{}'", exp_path.display())
+        ))));
+    }
     let cli_binary_path = cli_binary.canonicalize()?;
 
     // path where we will run the binary
@@ -179,8 +359,7 @@ pub fn run_one(
     let temp_dir = if use_temp_dir {
         // symlink everything in the exe_dir into the temp_dir
         let dir = tempdir()?;
-        let padded_dir = copy_deps(dir.path(), exe_dir)?;
-        simple_copy_dir(&padded_dir, exe_dir)?;
+        let padded_dir = copy_package(dir.path(), exe_dir)?;
         Some((dir, padded_dir))
     } else {
         None
@@ -210,8 +389,6 @@ pub fn run_one(
             .arg("clean")
             .output()?;
     }
-    let mut output = "".to_string();
-
     // always use the absolute path for the trace file as we may change dirs in the process
     let trace_file = if track_cov {
         Some(wks_dir.canonicalize()?.join(DEFAULT_TRACE_FILE))
@@ -219,61 +396,7 @@ pub fn run_one(
         None
     };
 
-    // Disable colors in error reporting from the Move compiler
-    env::set_var(COLOR_MODE_ENV_VAR, "NONE");
-    for args_line in args_file {
-        let args_line = args_line?;
-
-        if let Some(external_cmd) = args_line.strip_prefix('>') {
-            let external_cmd = external_cmd.trim_start();
-            let mut cmd_iter = external_cmd.split_ascii_whitespace();
-
-            let external_program = cmd_iter.next().expect("empty external command");
-
-            let mut command = Command::new(external_program);
-            command.args(cmd_iter);
-            if let Some(work_dir) = temp_dir.as_ref() {
-                command.current_dir(&work_dir.1);
-            } else {
-                command.current_dir(exe_dir);
-            }
-            let cmd_output = command.output()?;
-
-            writeln!(&mut output, "External Command `{}`:", external_cmd)?;
-            output += std::str::from_utf8(&cmd_output.stdout)?;
-            output += std::str::from_utf8(&cmd_output.stderr)?;
-
-            continue;
-        }
-
-        if args_line.starts_with('#') {
-            // allow comments in args.txt
-            continue;
-        }
-        let args_iter: Vec<&str> = args_line.split_whitespace().collect();
-        if args_iter.is_empty() {
-            // allow blank lines in args.txt
-            continue;
-        }
-
-        // enable tracing in the VM by setting the env var.
-        match &trace_file {
-            None => {
-                // this check prevents cascading the coverage tracking flag.
-                // in particular, if
-                //   1. we run with move-cli test <path-to-args-A.txt> --track-cov, and
-                //   2. in this <args-A.txt>, there is another command: test <args-B.txt>
-                // then, when running <args-B.txt>, coverage will not be tracked nor printed
-                env::remove_var(MOVE_VM_TRACING_ENV_VAR_NAME);
-            }
-            Some(path) => env::set_var(MOVE_VM_TRACING_ENV_VAR_NAME, path.as_os_str()),
-        }
-
-        let cmd_output = cli_command_template().args(args_iter).output()?;
-        writeln!(&mut output, "Command `{}`:", args_line)?;
-        output += std::str::from_utf8(&cmd_output.stdout)?;
-        output += std::str::from_utf8(&cmd_output.stderr)?;
-    }
+    let output = run_commands(args_file, &cli_binary_path, wks_dir, trace_file.as_deref())?;
 
     // collect coverage information
     let cov_info = match &trace_file {
@@ -303,16 +426,23 @@ pub fn run_one(
             .output()?;
 
         // check that build and storage was deleted
-        assert!(
-            !storage_dir.exists(),
-            "`move clean` failed to eliminate {} directory",
-            DEFAULT_STORAGE_DIR
-        );
-        assert!(
-            !build_output.exists(),
-            "`move clean` failed to eliminate {} directory",
-            DEFAULT_BUILD_DIR
-        );
+        for (dir, name) in [
+            (&storage_dir, DEFAULT_STORAGE_DIR),
+            (&build_output, DEFAULT_BUILD_DIR),
+        ] {
+            if dir.exists() {
+                return Err(TestError::Harness(anyhow!(
+                    "`move clean` failed to eliminate {} directory",
+                    name
+                )));
+            }
+        }
+
+        // `move clean` keeps the transaction log around; discard it too
+        let rotated_logs = rotated_logs_dir(&storage_dir);
+        if rotated_logs.exists() {
+            fs::remove_dir_all(rotated_logs)?;
+        }
 
         // clean the trace file as well if it exists
         if let Some(trace_path) = &trace_file {
@@ -328,56 +458,86 @@ pub fn run_one(
     }
 
     // compare output and exp_file
-    let update_baseline = read_env_update_baseline();
-    let exp_path = args_path.with_extension(EXP_EXT);
+    let output = mask_durations(&output);
     if update_baseline {
         fs::write(exp_path, &output)?;
         return Ok(cov_info);
     }
 
-    let expected_output = fs::read_to_string(exp_path).unwrap_or_else(|_| "".to_string());
+    let expected_output = fs::read_to_string(exp_path)?;
     if expected_output != output {
         let msg = format!(
             "Expected output differs from actual output:\n{}",
             format_diff(expected_output, output)
         );
-        anyhow::bail!(add_update_baseline_fix(msg))
+        Err(TestError::Failed(anyhow!(add_update_baseline_fix(msg))))
     } else {
         Ok(cov_info)
     }
 }
 
+/// Replace the durations commands report, such as the time `build` took, which differ from run to
+/// run, with a placeholder
+pub(crate) fn mask_durations(output: &str) -> String {
+    let duration = Regex::new(r"took \d+(\.\d+)?s").unwrap();
+    duration.replace_all(output, "took [TIME]").into_owned()
+}
+
+/// Run the batch files found under `args_path` and, if `args_path` is a package, its integration
+/// tests. With `update_expected`, the expected outcomes of the integration tests are overwritten
+/// instead of checked. A batch file that cannot be run is reported as such, and the others are run
+/// regardless; the integration tests are only run if every batch file passed.
 pub fn run_all(
     args_path: &Path,
     cli_binary: &Path,
     use_temp_dir: bool,
     track_cov: bool,
-) -> anyhow::Result<()> {
-    let mut test_total: u64 = 0;
-    let mut test_passed: u64 = 0;
+    update_expected: bool,
+) -> anyhow::Result<TestSummary> {
+    let mut summary = TestSummary::default();
     let mut cov_info = ExecCoverageMapWithModules::empty();
+    let integration_tests_dir = args_path.join(INTEGRATION_TESTS_DIR);
+    let has_integration_tests = integration_tests_dir.is_dir();
 
-    // find `args.txt` and iterate over them
+    // find `args.txt` and iterate over them, leaving out the batch files of integration tests
     for entry in find_filenames(&[args_path], |fpath| {
         fpath.file_name().expect("unexpected file entry path") == TEST_ARGS_FILENAME
+            && !fpath
+                .components()
+                .any(|component| component.as_os_str() == INTEGRATION_TESTS_DIR)
     })? {
         match run_one(Path::new(&entry), cli_binary, use_temp_dir, track_cov) {
             Ok(cov_opt) => {
-                test_passed = test_passed.checked_add(1).unwrap();
+                summary.passed.push(entry.into());
                 if let Some(cov) = cov_opt {
                     cov_info.merge(cov);
                 }
             }
-            Err(ex) => eprintln!("Test {} failed with error: {}", entry, ex),
+            Err(TestError::Failed(err)) => {
+                eprintln!("Test {} failed with error: {}", entry, err);
+                summary.failed.push((entry.into(), err.to_string()));
+            }
+            Err(TestError::Harness(err)) => {
+                eprintln!("Test {} could not be run: {:#}", entry, err);
+                summary
+                    .harness_errors
+                    .push((entry.into(), format!("{:#}", err)));
+            }
         }
-        test_total = test_total.checked_add(1).unwrap();
     }
-    println!("{} / {} test(s) passed.", test_passed, test_total);
+    if summary.total() != 0 || !has_integration_tests {
+        println!(
+            "{} / {} test(s) passed.",
+            summary.passed.len(),
+            summary.total()
+        );
+    }
+    if !summary.is_success() {
+        return Ok(summary);
+    }
 
-    // if any test fails, bail
-    let test_failed = test_total.checked_sub(test_passed).unwrap();
-    if test_failed != 0 {
-        anyhow::bail!("{} / {} test(s) failed.", test_failed, test_total)
+    if has_integration_tests {
+        integration_test::run_all(args_path, cli_binary, update_expected)?;
     }
 
     // show coverage information if requested
@@ -388,5 +548,5 @@ pub fn run_all(
         }
     }
 
-    Ok(())
+    Ok(summary)
 }
@@ -0,0 +1,543 @@
+// Copyright (c) The Move Contributors
+// SPDX-License-Identifier: Apache-2.0
+
+use anyhow::{bail, Context, Result};
+use move_command_line_common::files::read_utf8_file;
+use move_core_types::account_address::AccountAddress;
+use std::{
+    io::{self, BufRead, Write},
+    path::Path,
+};
+
+/// The environment variable making the VM also record the locals and the operand stack before each
+/// instruction in the trace written to `MOVE_VM_TRACE`
+pub const MOVE_VM_TRACE_VALUES_ENV_VAR: &str = "MOVE_VM_TRACE_VALUES";
+
+const HELP: &str = "Commands:
+    step [N], s [N]            Go to the next instruction, or N instructions further
+    next, n                    Go to the next instruction of the current function, over calls
+    finish, f                  Go to the instruction following the return of the current function
+    continue, c                Go to the next breakpoint, or to the end of the trace
+    back [N], b [N]            Go back to the previous instruction, or N instructions back
+    abort                      Go to the instruction the execution aborted at
+    break FUNCTION [PC]        Stop at the entry of FUNCTION, e.g. M::f, or at its instruction PC
+    delete N                   Remove breakpoint N
+    breakpoints                List the breakpoints
+    locals                     Print the locals of the current function
+    stack                      Print the operand stack
+    backtrace, bt              Print the calls leading to the current function
+    quit, q                    Stop debugging";
+
+/// An instruction executed by the VM, as recorded in a trace
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TraceStep {
+    /// The function executing the instruction, e.g. `0x2::M::f`, or `Script::main`
+    pub function: String,
+    pub pc: u16,
+    pub instruction: String,
+    /// The locals and the operand stack before the instruction, if the trace records them
+    pub values: Option<TraceValues>,
+}
+
+/// The values recorded before an instruction, printed by the VM
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TraceValues {
+    /// The locals of the function, `-` for the ones holding no value
+    pub locals: Vec<String>,
+    /// The operand stack, from the bottom
+    pub stack: Vec<String>,
+}
+
+/// One execution of a trace, e.g. of a script or of an entry function, with the calls it makes
+#[derive(Debug)]
+pub struct TraceExecution {
+    pub steps: Vec<TraceStep>,
+    /// For each step, the step calling its function, or `None` in the function the execution
+    /// starts with
+    callers: Vec<Option<usize>>,
+}
+
+/// The executions recorded in the trace file at `path`, in the order they started. A trace file is
+/// appended to by every process run with `MOVE_VM_TRACE` set.
+pub fn read_trace(path: &Path) -> Result<Vec<TraceExecution>> {
+    let mut executions: Vec<(String, Vec<TraceStep>)> = vec![];
+    for (line_number, line) in read_utf8_file(path)?.lines().enumerate() {
+        let (id, step) = parse_step(line).with_context(|| {
+            format!(
+                "Error parsing line {} of the trace '{}'",
+                line_number + 1,
+                path.display()
+            )
+        })?;
+        match executions
+            .iter_mut()
+            .find(|(execution, _)| *execution == id)
+        {
+            Some((_, steps)) => steps.push(step),
+            None => executions.push((id, vec![step])),
+        }
+    }
+    Ok(executions
+        .into_iter()
+        .map(|(_, steps)| TraceExecution::new(steps))
+        .collect())
+}
+
+/// The execution id and the step recorded by a line of a trace, such as
+/// `42-ThreadId(1),0x2::M::f,3,Add`, followed by the values if they are recorded
+fn parse_step(line: &str) -> Result<(String, TraceStep)> {
+    let mut fields = line.split('\t');
+    let mut parts = fields.next().unwrap_or_default().splitn(4, ',');
+    let (id, function, pc, instruction) =
+        match (parts.next(), parts.next(), parts.next(), parts.next()) {
+            (Some(id), Some(function), Some(pc), Some(instruction)) => {
+                (id, function, pc, instruction)
+            }
+            _ => bail!("Expected <execution>,<function>,<pc>,<instruction>"),
+        };
+    let values = match (fields.next(), fields.next()) {
+        (Some(locals), Some(stack)) => Some(TraceValues {
+            locals: split_values(
+                locals
+                    .strip_prefix("locals:")
+                    .context("Expected the locals")?,
+            ),
+            stack: split_values(stack.strip_prefix("stack:").context("Expected the stack")?),
+        }),
+        _ => None,
+    };
+    let step = TraceStep {
+        function: short_function_name(function),
+        pc: pc.parse().with_context(|| format!("Invalid pc '{}'", pc))?,
+        instruction: instruction.to_string(),
+        values,
+    };
+    Ok((id.to_string(), step))
+}
+
+fn split_values(values: &str) -> Vec<String> {
+    let values = values.trim();
+    if values.is_empty() {
+        return vec![];
+    }
+    values.split("; ").map(str::to_string).collect()
+}
+
+/// `function` with its address in the short form, e.g. `0x2::M::f`, as addresses are written out
+/// in full in traces
+fn short_function_name(function: &str) -> String {
+    match function.split_once("::") {
+        Some((address, rest)) => match AccountAddress::from_hex_literal(address) {
+            Ok(address) => format!("0x{}::{}", address.short_str_lossless(), rest),
+            Err(_) => function.to_string(),
+        },
+        None => function.to_string(),
+    }
+}
+
+impl TraceExecution {
+    fn new(steps: Vec<TraceStep>) -> Self {
+        // Calls to native functions record no step: the step following them is the next
+        // instruction of the caller
+        let mut callers = Vec::with_capacity(steps.len());
+        let mut frames: Vec<usize> = vec![];
+        for (i, step) in steps.iter().enumerate() {
+            if i > 0 {
+                let previous = &steps[i - 1];
+                let returned_from_native =
+                    step.function == previous.function && step.pc == previous.pc.wrapping_add(1);
+                if previous.instruction.starts_with("Call") && !returned_from_native {
+                    frames.push(i - 1);
+                } else if previous.instruction == "Ret" {
+                    frames.pop();
+                }
+            }
+            callers.push(frames.last().copied());
+        }
+        Self { steps, callers }
+    }
+
+    /// The number of calls the function of `step` is nested in
+    fn depth(&self, step: usize) -> usize {
+        let mut depth = 0;
+        let mut current = step;
+        while let Some(caller) = self.callers[current] {
+            depth += 1;
+            current = caller;
+        }
+        depth
+    }
+
+    /// The step the execution aborted at, or `None` if it returned from the function it started
+    /// with. A trace only records the instructions, so an execution stopped by an error, such as
+    /// an arithmetic error, is also reported as aborted.
+    pub fn abort_step(&self) -> Option<usize> {
+        let last = self.steps.len().checked_sub(1)?;
+        if self.steps[last].instruction == "Ret" && self.callers[last].is_none() {
+            None
+        } else {
+            Some(last)
+        }
+    }
+}
+
+/// A breakpoint at the entry of a function, or at one of its instructions
+#[derive(Debug)]
+struct Breakpoint {
+    function: String,
+    pc: Option<u16>,
+}
+
+impl Breakpoint {
+    fn matches(&self, step: &TraceStep) -> bool {
+        let function = step.function == self.function
+            || step.function.ends_with(&format!("::{}", self.function));
+        function && step.pc == self.pc.unwrap_or(0)
+    }
+}
+
+impl std::fmt::Display for Breakpoint {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self.pc {
+            Some(pc) => write!(f, "pc {} of {}", pc, self.function),
+            None => write!(f, "entry of {}", self.function),
+        }
+    }
+}
+
+/// Step through execution number `execution` of the trace file at `path`, counting from 1, or
+/// through its last execution, with the commands read from stdin
+pub fn debug_trace_file(path: &Path, execution_number: Option<usize>) -> Result<()> {
+    let executions = read_trace(path)?;
+    let count = executions.len();
+    let execution = match execution_number {
+        Some(execution) if (1..=count).contains(&execution) => &executions[execution - 1],
+        Some(execution) => bail!(
+            "The trace '{}' holds {} execution(s), not {}",
+            path.display(),
+            count,
+            execution
+        ),
+        None => match executions.last() {
+            Some(execution) => execution,
+            None => bail!("The trace '{}' is empty", path.display()),
+        },
+    };
+    if count > 1 && execution_number.is_none() {
+        eprintln!(
+            "The trace holds {} executions: debugging the last one. Pick another with --execution",
+            count
+        );
+    }
+    debug_trace(
+        execution,
+        io::stdin().lock(),
+        io::stdout(),
+        atty::is(atty::Stream::Stdin),
+    )
+}
+
+/// Step through `execution` with the commands read from `input`, one per line, printing to
+/// `output`. Everything is read from the trace: nothing is executed again.
+pub fn debug_trace(
+    execution: &TraceExecution,
+    mut input: impl BufRead,
+    mut output: impl Write,
+    prompt: bool,
+) -> Result<()> {
+    if execution.steps.is_empty() {
+        bail!("The trace holds no instructions")
+    }
+    let mut debugger = Debugger {
+        execution,
+        current: 0,
+        breakpoints: vec![],
+    };
+    writeln!(
+        output,
+        "The trace holds {} instructions. Type `help` for the commands.",
+        execution.steps.len()
+    )?;
+    debugger.print_location(&mut output)?;
+    loop {
+        if prompt {
+            write!(output, "(trace) ")?;
+            output.flush()?;
+        }
+        let mut line = String::new();
+        if input.read_line(&mut line)? == 0 {
+            return Ok(());
+        }
+        let words: Vec<_> = line.split_whitespace().collect();
+        match words.as_slice() {
+            [] => continue,
+            ["quit" | "q"] => return Ok(()),
+            ["help" | "h"] => writeln!(output, "{}", HELP)?,
+            [command @ ("step" | "s" | "back" | "b"), count @ ..] => {
+                let count = match count {
+                    [] => 1,
+                    [count] => match count.parse::<usize>() {
+                        Ok(count) => count,
+                        Err(_) => {
+                            writeln!(output, "'{}' is not a number of instructions", count)?;
+                            continue;
+                        }
+                    },
+                    _ => {
+                        writeln!(output, "Usage: {} [N]", command)?;
+                        continue;
+                    }
+                };
+                let last = execution.steps.len() - 1;
+                debugger.current = if command.starts_with('s') {
+                    (debugger.current + count).min(last)
+                } else {
+                    debugger.current.saturating_sub(count)
+                };
+                debugger.print_location(&mut output)?;
+            }
+            ["next" | "n"] => {
+                let depth = execution.depth(debugger.current);
+                debugger.run_until(&mut output, |step| execution.depth(step) <= depth)?;
+            }
+            ["finish" | "f"] => {
+                let depth = execution.depth(debugger.current);
+                debugger.run_until(&mut output, |step| execution.depth(step) < depth)?;
+            }
+            ["continue" | "c"] => debugger.run_until(&mut output, |_| false)?,
+            ["abort"] => match execution.abort_step() {
+                Some(step) => {
+                    debugger.current = step;
+                    writeln!(output, "The execution aborted at:")?;
+                    debugger.print_location(&mut output)?;
+                }
+                None => writeln!(output, "The execution returned without aborting")?,
+            },
+            ["break", function, pc @ ..] => {
+                let pc = match pc {
+                    [] => None,
+                    [pc] => match pc.parse::<u16>() {
+                        Ok(pc) => Some(pc),
+                        Err(_) => {
+                            writeln!(output, "'{}' is not an instruction offset", pc)?;
+                            continue;
+                        }
+                    },
+                    _ => {
+                        writeln!(output, "Usage: break FUNCTION [PC]")?;
+                        continue;
+                    }
+                };
+                let breakpoint = Breakpoint {
+                    function: short_function_name(function),
+                    pc,
+                };
+                writeln!(
+                    output,
+                    "Breakpoint {}: {}",
+                    debugger.breakpoints.len() + 1,
+                    breakpoint
+                )?;
+                debugger.breakpoints.push(Some(breakpoint));
+            }
+            ["delete", number] => match number
+                .parse::<usize>()
+                .ok()
+                .and_then(|n| debugger.breakpoints.get_mut(n.wrapping_sub(1)))
+            {
+                Some(breakpoint @ Some(_)) => {
+                    *breakpoint = None;
+                    writeln!(output, "Deleted breakpoint {}", number)?;
+                }
+                _ => writeln!(output, "There is no breakpoint {}", number)?,
+            },
+            ["breakpoints"] => {
+                let mut any = false;
+                for (i, breakpoint) in debugger.breakpoints.iter().enumerate() {
+                    if let Some(breakpoint) = breakpoint {
+                        writeln!(output, "    [{}] {}", i + 1, breakpoint)?;
+                        any = true;
+                    }
+                }
+                if !any {
+                    writeln!(output, "No breakpoints")?;
+                }
+            }
+            ["locals"] => debugger.print_values(&mut output, true)?,
+            ["stack"] => debugger.print_values(&mut output, false)?,
+            ["backtrace" | "bt"] => {
+                let mut frame = Some(debugger.current);
+                let mut depth = 0;
+                while let Some(step) = frame {
+                    let step_data = &execution.steps[step];
+                    writeln!(
+                        output,
+                        "    [{}] {} pc {}",
+                        depth, step_data.function, step_data.pc
+                    )?;
+                    frame = execution.callers[step];
+                    depth += 1;
+                }
+            }
+            _ => writeln!(
+                output,
+                "Unknown command: {}. Type `help` for the commands.",
+                line.trim()
+            )?,
+        }
+    }
+}
+
+struct Debugger<'a> {
+    execution: &'a TraceExecution,
+    current: usize,
+    /// The breakpoints, numbered from 1, with `None` for the deleted ones
+    breakpoints: Vec<Option<Breakpoint>>,
+}
+
+impl Debugger<'_> {
+    /// Go forward to the first step `stop` holds for, or the first breakpoint, or else the end of
+    /// the trace
+    fn run_until(&mut self, output: &mut impl Write, stop: impl Fn(usize) -> bool) -> Result<()> {
+        let steps = &self.execution.steps;
+        for (step, step_data) in steps.iter().enumerate().skip(self.current + 1) {
+            let breakpoint = self
+                .breakpoints
+                .iter()
+                .enumerate()
+                .find(|(_, bp)| bp.as_ref().map_or(false, |bp| bp.matches(step_data)));
+            if let Some((number, _)) = breakpoint {
+                writeln!(output, "Breakpoint {} hit", number + 1)?;
+            } else if !stop(step) {
+                continue;
+            }
+            self.current = step;
+            return self.print_location(output);
+        }
+        self.current = steps.len() - 1;
+        writeln!(output, "Reached the end of the trace")?;
+        self.print_location(output)
+    }
+
+    fn print_location(&self, output: &mut impl Write) -> Result<()> {
+        let step = &self.execution.steps[self.current];
+        writeln!(
+            output,
+            "[{}/{}] {} pc {}: {}",
+            self.current + 1,
+            self.execution.steps.len(),
+            step.function,
+            step.pc,
+            step.instruction
+        )?;
+        Ok(())
+    }
+
+    fn print_values(&self, output: &mut impl Write, locals: bool) -> Result<()> {
+        let step = &self.execution.steps[self.current];
+        let values = match &step.values {
+            Some(values) => values,
+            None => {
+                writeln!(
+                    output,
+                    "The trace holds no values: record it with {} set along with MOVE_VM_TRACE",
+                    MOVE_VM_TRACE_VALUES_ENV_VAR
+                )?;
+                return Ok(());
+            }
+        };
+        let (title, values) = if locals {
+            (format!("Locals of {}:", step.function), &values.locals)
+        } else {
+            ("Operand stack, from the bottom:".to_string(), &values.stack)
+        };
+        writeln!(output, "{}", title)?;
+        if values.is_empty() {
+            writeln!(output, "    (none)")?;
+        }
+        for (i, value) in values.iter().enumerate() {
+            writeln!(output, "    [{}] {}", i, value)?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const TRACE: &str = "\
+7-ThreadId(1),0x2::M::run,0,LdU64(7)\tlocals: -\tstack:
+7-ThreadId(1),0x2::M::run,1,Call(0)\tlocals: -\tstack: 7
+7-ThreadId(1),0x2::M::double,0,CopyLoc(0)\tlocals: 7\tstack:
+7-ThreadId(1),0x2::M::double,1,CopyLoc(0)\tlocals: 7\tstack: 7
+7-ThreadId(1),0x2::M::double,2,Add\tlocals: 7\tstack: 7; 7
+7-ThreadId(1),0x2::M::double,3,Ret\tlocals: 7\tstack: 14
+7-ThreadId(1),0x2::M::run,2,StLoc(0)\tlocals: -\tstack: 14
+7-ThreadId(1),0x2::M::run,3,LdU64(1)\tlocals: 14\tstack:
+7-ThreadId(1),0x2::M::run,4,Abort\tlocals: 14\tstack: 1
+";
+
+    fn session(commands: &str) -> String {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("trace");
+        // traces hold full addresses
+        let address = AccountAddress::from_hex_literal("0x2").unwrap();
+        let trace = TRACE.replace("0x2::", &format!("0x{}::", address.to_hex()));
+        std::fs::write(&path, trace).unwrap();
+        let executions = read_trace(&path).unwrap();
+        assert_eq!(executions.len(), 1);
+        let mut output = vec![];
+        debug_trace(&executions[0], commands.as_bytes(), &mut output, false).unwrap();
+        String::from_utf8(output).unwrap()
+    }
+
+    #[test]
+    fn steps_over_calls_and_stops_at_breakpoints() {
+        let output =
+            session("next\nnext\nbreak M::double 2\ncontinue\nstack\nbt\nfinish\nlocals\n");
+        assert_eq!(
+            output,
+            "\
+The trace holds 9 instructions. Type `help` for the commands.
+[1/9] 0x2::M::run pc 0: LdU64(7)
+[2/9] 0x2::M::run pc 1: Call(0)
+[7/9] 0x2::M::run pc 2: StLoc(0)
+Breakpoint 1: pc 2 of M::double
+Reached the end of the trace
+[9/9] 0x2::M::run pc 4: Abort
+Operand stack, from the bottom:
+    [0] 1
+    [0] 0x2::M::run pc 4
+Reached the end of the trace
+[9/9] 0x2::M::run pc 4: Abort
+Locals of 0x2::M::run:
+    [0] 14
+"
+        );
+    }
+
+    #[test]
+    fn travels_back_and_jumps_to_the_abort() {
+        let output = session("break double\nc\nlocals\nbt\nabort\nback 3\nstack\n");
+        assert_eq!(
+            output,
+            "\
+The trace holds 9 instructions. Type `help` for the commands.
+[1/9] 0x2::M::run pc 0: LdU64(7)
+Breakpoint 1: entry of double
+Breakpoint 1 hit
+[3/9] 0x2::M::double pc 0: CopyLoc(0)
+Locals of 0x2::M::double:
+    [0] 7
+    [0] 0x2::M::double pc 0
+    [1] 0x2::M::run pc 1
+The execution aborted at:
+[9/9] 0x2::M::run pc 4: Abort
+[6/9] 0x2::M::double pc 3: Ret
+Operand stack, from the bottom:
+    [0] 14
+"
+        );
+    }
+}
@@ -0,0 +1,247 @@
+// Copyright (c) The Move Contributors
+// SPDX-License-Identifier: Apache-2.0
+
+use crate::sandbox::{cli::OutputFormat, utils::on_disk_state_view::OnDiskStateView};
+use anyhow::{bail, Result};
+use move_binary_format::{
+    access::ModuleAccess,
+    errors::{Location, PartialVMError, VMError, VMResult},
+    file_format::CompiledModule,
+};
+use move_core_types::{language_storage::ModuleId, vm_status::StatusCode};
+use serde::Serialize;
+use std::{collections::BTreeMap, fs, thread};
+
+/// The outcome of verifying a module stored on disk
+#[derive(Debug, Serialize)]
+pub struct ModuleVerification {
+    /// The module, as identified by the path it is stored at
+    pub module: String,
+    /// Why the module fails verification, or `None` if it passes
+    pub error: Option<String>,
+}
+
+/// The outcome of `verify_storage`, with the modules sorted by id
+#[derive(Debug, Serialize)]
+pub struct StorageVerification {
+    pub modules: Vec<ModuleVerification>,
+    pub passed: usize,
+    pub failed: usize,
+}
+
+/// Verify every module stored in `state` with `verify_modules`, report the outcome for each in
+/// `format`, and fail if any of them does not pass
+pub fn verify_storage(state: &OnDiskStateView, jobs: usize, format: OutputFormat) -> Result<()> {
+    let verification = verify_modules(state, jobs)?;
+    match format {
+        OutputFormat::Text => {
+            for module in &verification.modules {
+                match &module.error {
+                    None => println!("[ OK     ] {}", module.module),
+                    Some(error) => println!("[ FAILED ] {}: {}", module.module, error),
+                }
+            }
+            println!(
+                "Verified {} module(s); passed: {}, failed: {}",
+                verification.modules.len(),
+                verification.passed,
+                verification.failed
+            );
+        }
+        OutputFormat::Json => println!("{}", serde_json::to_string_pretty(&verification)?),
+    }
+    if verification.failed > 0 {
+        bail!(
+            "{} of the {} module(s) in storage fail verification",
+            verification.failed,
+            verification.modules.len()
+        )
+    }
+    Ok(())
+}
+
+/// Run the bytecode verifier, the linker and the cyclic dependency check over every module
+/// stored in `state`, linking each against the other modules of `state`. Modules are verified
+/// `jobs` at a time, and storage is only read.
+pub fn verify_modules(state: &OnDiskStateView, jobs: usize) -> Result<StorageVerification> {
+    let mut paths = BTreeMap::new();
+    for path in state.module_paths() {
+        if let Some(id) = state.get_module_id(&path) {
+            paths.insert(id, path);
+        }
+    }
+    // the modules failing to deserialize are left out, so that modules depending on them fail to
+    // link
+    let mut modules = BTreeMap::new();
+    let mut errors = BTreeMap::new();
+    for (id, path) in &paths {
+        match CompiledModule::deserialize(&fs::read(path)?) {
+            Ok(module) if module.self_id() != *id => {
+                errors.insert(
+                    id.clone(),
+                    format!(
+                        "Stored as {}, but the module is {}",
+                        format_module_id(id),
+                        format_module_id(&module.self_id())
+                    ),
+                );
+            }
+            Ok(module) => {
+                modules.insert(id.clone(), module);
+            }
+            Err(e) => {
+                let error = describe_error(&e.finish(Location::Undefined));
+                errors.insert(id.clone(), format!("Failed to deserialize: {}", error));
+            }
+        }
+    }
+
+    let to_verify: Vec<_> = modules.values().collect();
+    let chunk_size = (to_verify.len() + jobs.max(1) - 1) / jobs.max(1);
+    let verified: Vec<(ModuleId, Option<String>)> = thread::scope(|s| {
+        let handles: Vec<_> = to_verify
+            .chunks(chunk_size.max(1))
+            .map(|chunk| {
+                let modules = &modules;
+                s.spawn(move || {
+                    chunk
+                        .iter()
+                        .map(|module| (module.self_id(), verify_module(module, modules).err()))
+                        .collect::<Vec<_>>()
+                })
+            })
+            .collect();
+        handles
+            .into_iter()
+            .flat_map(|handle| handle.join().expect("verification does not panic"))
+            .collect()
+    });
+    for (id, error) in verified {
+        if let Some(error) = error {
+            errors.insert(id, error);
+        }
+    }
+
+    let modules: Vec<_> = paths
+        .keys()
+        .map(|id| ModuleVerification {
+            module: format_module_id(id),
+            error: errors.remove(id),
+        })
+        .collect();
+    let failed = modules.iter().filter(|m| m.error.is_some()).count();
+    Ok(StorageVerification {
+        passed: modules.len() - failed,
+        failed,
+        modules,
+    })
+}
+
+/// Verify `module` on its own, then link it against the modules in `modules`
+fn verify_module(
+    module: &CompiledModule,
+    modules: &BTreeMap<ModuleId, CompiledModule>,
+) -> Result<(), String> {
+    move_bytecode_verifier::verify_module(module)
+        .map_err(|e| format!("Failed to verify: {}", describe_error(&e)))?;
+
+    let mut deps = vec![];
+    for dep in module.immediate_dependencies() {
+        match modules.get(&dep) {
+            Some(dep_module) => deps.push(dep_module),
+            None => {
+                return Err(format!(
+                    "Failed to link: the dependency {} is missing from storage or does not \
+                     deserialize",
+                    format_module_id(&dep)
+                ))
+            }
+        }
+    }
+    move_bytecode_verifier::dependencies::verify_module(module, deps)
+        .map_err(|e| format!("Failed to link: {}", describe_error(&e)))?;
+
+    let lookup = |id: &ModuleId| -> Result<&CompiledModule, PartialVMError> {
+        modules
+            .get(id)
+            .ok_or_else(|| PartialVMError::new(StatusCode::MISSING_DEPENDENCY))
+    };
+    let cyclic_check: VMResult<()> = move_bytecode_verifier::cyclic_dependencies::verify_module(
+        module,
+        |id| lookup(id).map(|m| m.immediate_dependencies()),
+        |id| lookup(id).map(|m| m.immediate_friends()),
+    );
+    cyclic_check.map_err(|e| format!("Failed to link: {}", describe_error(&e)))
+}
+
+/// The status of `error`, followed by its message and the first code offset it is reported at
+fn describe_error(error: &VMError) -> String {
+    let mut description = format!("{:?}", error.major_status());
+    if let Some(message) = error.message() {
+        description.push_str(&format!(": {}", message));
+    }
+    if let Some((function, offset)) = error.offsets().first() {
+        description.push_str(&format!(
+            " (function definition {}, code offset {})",
+            function, offset
+        ));
+    }
+    description
+}
+
+fn format_module_id(id: &ModuleId) -> String {
+    format!("0x{}::{}", id.address().short_str_lossless(), id.name())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use move_binary_format::file_format::empty_module;
+    use move_core_types::identifier::Identifier;
+
+    fn save_module(state: &OnDiskStateView, name: &str, identifiers: &[&str]) {
+        let mut module = empty_module();
+        module.identifiers = identifiers
+            .iter()
+            .map(|identifier| Identifier::new(*identifier).unwrap())
+            .collect();
+        let path = state.get_module_path(&ModuleId::new(
+            module.address_identifiers[0],
+            Identifier::new(name).unwrap(),
+        ));
+        let mut bytes = vec![];
+        module.serialize(&mut bytes).unwrap();
+        fs::create_dir_all(path.parent().unwrap()).unwrap();
+        fs::write(path, bytes).unwrap();
+    }
+
+    #[test]
+    fn verify_modules_reports_the_modules_failing_the_verifier() {
+        let storage_dir = tempfile::tempdir().unwrap();
+        let state = OnDiskStateView::open_read_only(storage_dir.path()).unwrap();
+        save_module(&state, "Valid", &["Valid"]);
+        // deserializes, but the verifier rejects the duplicate identifier
+        save_module(&state, "Invalid", &["Invalid", "Invalid"]);
+        // deserializes, but is stored under another name
+        save_module(&state, "Moved", &["Valid"]);
+
+        let verification = verify_modules(&state, 2).unwrap();
+        let outcomes: Vec<_> = verification
+            .modules
+            .iter()
+            .map(|m| (m.module.as_str(), m.error.as_deref()))
+            .collect();
+        assert_eq!(
+            outcomes,
+            vec![
+                ("0x0::Invalid", Some("Failed to verify: DUPLICATE_ELEMENT")),
+                (
+                    "0x0::Moved",
+                    Some("Stored as 0x0::Moved, but the module is 0x0::Valid")
+                ),
+                ("0x0::Valid", None),
+            ]
+        );
+        assert_eq!((verification.passed, verification.failed), (1, 2));
+    }
+}
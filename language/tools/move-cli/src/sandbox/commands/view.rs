@@ -2,41 +2,354 @@
 // Copyright (c) The Move Contributors
 // SPDX-License-Identifier: Apache-2.0
 
-use crate::sandbox::utils::{
-    contains_module, is_bytecode_file, on_disk_state_view::OnDiskStateView,
+use crate::sandbox::{
+    cli::OutputFormat,
+    utils::{
+        contains_module, format_function_signature, is_bytecode_file,
+        on_disk_state_view::{OnDiskStateView, MODULES_DIR},
+    },
 };
 
 use anyhow::{bail, Result};
-use std::path::Path;
-/// Print a module or resource stored in `file`
-pub fn view(state: &OnDiskStateView, path: &Path) -> Result<()> {
-    if state.is_resource_path(path) {
-        match state.view_resource(path)? {
-            Some(resource) => println!("{}", resource),
-            None => println!("Resource not found."),
-        }
+use move_binary_format::{
+    access::ModuleAccess,
+    file_format::{CompiledModule, Visibility},
+    normalized,
+};
+use move_core_types::{
+    account_address::AccountAddress,
+    language_storage::{ModuleId, TypeTag},
+};
+use move_resource_viewer::{AnnotatedMoveStruct, AnnotatedMoveValue};
+use serde::Serialize;
+use std::{
+    fs,
+    io::{self, Write},
+    path::Path,
+};
+
+/// The contents of a file under storage, as returned by `view_file`
+#[derive(Debug)]
+pub enum ViewOutput {
+    /// A resource, or `None` if the file does not exist
+    Resource(Option<AnnotatedMoveStruct>),
+    Events(EventsPage),
+    /// A module, or `None` if no module is published at the path
+    Module(Option<ModuleView>),
+    /// A disassembled script, or `None` if the file does not exist
+    Bytecode(Option<String>),
+}
+
+/// A module stored on disk, as returned by `view_file`
+#[derive(Debug)]
+pub struct ModuleView {
+    pub metadata: ModuleMetadata,
+    /// Signatures of the public functions of the module, e.g. `public fun f(u64): bool`
+    pub public_functions: Vec<String>,
+    pub disassembly: String,
+}
+
+/// The part of a `ModuleView` printed by `move sandbox view --format json`
+#[derive(Debug, Serialize)]
+pub struct ModuleMetadata {
+    pub address: String,
+    pub name: String,
+    /// Size of the serialized module in bytes
+    pub size: usize,
+    pub bytecode_version: u32,
+    /// The modules this module depends on
+    pub dependencies: Vec<String>,
+}
+
+/// A resource stored at an address, as listed by `move sandbox view --resources`
+#[derive(Debug, Serialize)]
+pub struct ResourceEntry {
+    #[serde(rename = "type")]
+    pub type_: String,
+    /// Size of the serialized resource in bytes
+    pub size: u64,
+    /// The decoded resource, only set with `--decode`
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub value: Option<AnnotatedMoveStruct>,
+}
+
+/// The events of a stream to view, by sequence number
+#[derive(Debug, Default, Clone, Copy)]
+pub struct EventRange {
+    pub from_seq: u64,
+    /// The maximum number of events, or `None` for all events from `from_seq` on
+    pub limit: Option<usize>,
+}
+
+/// Some of the events of a stream stored on disk, as returned by `view_file`
+#[derive(Debug, Serialize)]
+pub struct EventsPage {
+    pub events: Vec<SequencedEvent>,
+    /// The `from_seq` of the next page, or `None` if this page ends the stream
+    pub next_seq: Option<u64>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct SequencedEvent {
+    pub sequence_number: u64,
+    #[serde(rename = "type")]
+    pub type_: String,
+    pub data: AnnotatedMoveValue,
+}
+
+/// Decode the module, resource or events stored in `path`. Only the events in `events` are
+/// decoded from an events file.
+pub fn view_file(state: &OnDiskStateView, path: &Path, events: EventRange) -> Result<ViewOutput> {
+    Ok(if state.is_resource_path(path) {
+        ViewOutput::Resource(state.view_resource(path)?)
     } else if state.is_event_path(path) {
-        let events = state.view_events(path)?;
-        if events.is_empty() {
-            println!("Events not found.")
-        } else {
-            for event in events {
-                println!("{}", event)
-            }
-        }
+        ViewOutput::Events(events_page(state.view_events(path)?, events))
     } else if is_bytecode_file(path) {
-        let bytecode_opt = if contains_module(path) {
-            OnDiskStateView::view_module(path)?
+        if contains_module(path) {
+            ViewOutput::Module(Some(summarize_module(path)?))
+        } else if !path.exists() && module_id_from_path(path).is_some() {
+            ViewOutput::Module(None)
         } else {
             // bytecode extension, but not a module--assume it's a script
-            OnDiskStateView::view_script(path)?
-        };
-        match bytecode_opt {
-            Some(bytecode) => println!("{}", bytecode),
-            None => println!("Bytecode not found."),
+            ViewOutput::Bytecode(OnDiskStateView::view_script(path)?)
         }
     } else {
         bail!("`move view <file>` must point to a valid file under storage")
+    })
+}
+
+fn summarize_module(path: &Path) -> Result<ModuleView> {
+    let bytes = fs::read(path)?;
+    let module = CompiledModule::deserialize(&bytes)?;
+    let metadata = module_metadata(&module, bytes.len());
+    let public_functions = normalized::Module::new(&module)
+        .exposed_functions
+        .iter()
+        .filter(|(_, function)| function.visibility == Visibility::Public)
+        .map(|(name, function)| format_function_signature(name.as_str(), function))
+        .collect();
+    Ok(ModuleView {
+        metadata,
+        public_functions,
+        disassembly: OnDiskStateView::view_module(path)?.unwrap_or_default(),
+    })
+}
+
+fn module_metadata(module: &CompiledModule, size: usize) -> ModuleMetadata {
+    ModuleMetadata {
+        address: format_address(module.address()),
+        name: module.name().to_string(),
+        size,
+        bytecode_version: module.version(),
+        dependencies: module
+            .immediate_dependencies()
+            .iter()
+            .map(format_module_id)
+            .collect(),
+    }
+}
+
+/// The module a path of the form `<storage>/<address>/modules/<name>.mv` would hold
+fn module_id_from_path(path: &Path) -> Option<String> {
+    let modules_dir = path.parent()?;
+    if !modules_dir.ends_with(MODULES_DIR) {
+        return None;
+    }
+    let address = modules_dir.parent()?.file_name()?.to_str()?;
+    let address = AccountAddress::from_hex_literal(address).ok()?;
+    let name = path.file_stem()?.to_str()?;
+    Some(format!("{}::{}", format_address(&address), name))
+}
+
+fn format_address(address: &AccountAddress) -> String {
+    format!("0x{}", address.short_str_lossless())
+}
+
+fn format_module_id(module_id: &ModuleId) -> String {
+    format!(
+        "{}::{}",
+        format_address(module_id.address()),
+        module_id.name()
+    )
+}
+
+fn events_page(stream: Vec<(u64, TypeTag, AnnotatedMoveValue)>, range: EventRange) -> EventsPage {
+    let limit = range.limit.unwrap_or(usize::MAX);
+    let mut events = stream
+        .into_iter()
+        .filter(|(sequence_number, _, _)| *sequence_number >= range.from_seq)
+        .map(|(sequence_number, event_type, data)| SequencedEvent {
+            sequence_number,
+            type_: event_type.to_string(),
+            data,
+        })
+        .peekable();
+    let page = events.by_ref().take(limit).collect();
+    let next_seq = events.peek().map(|event| event.sequence_number);
+    EventsPage {
+        events: page,
+        next_seq,
+    }
+}
+
+/// Print a module, resource or events stored in `file`. Modules are summarized by their metadata
+/// and public functions, or by their metadata and disassembly if `disassemble` is set. Only the
+/// events in `events` are printed from an events file.
+pub fn view(
+    state: &OnDiskStateView,
+    path: &Path,
+    disassemble: bool,
+    events: EventRange,
+    format: OutputFormat,
+) -> Result<()> {
+    let output = view_file(state, path, events)?;
+    if let OutputFormat::Json = format {
+        match output {
+            ViewOutput::Module(Some(module)) => {
+                println!("{}", serde_json::to_string_pretty(&module.metadata)?)
+            }
+            ViewOutput::Module(None) => bail!("Module {} is not published", module_name(path)),
+            ViewOutput::Events(page) => println!("{}", serde_json::to_string_pretty(&page)?),
+            _ => bail!("`--format json` is only supported when viewing a module or events"),
+        }
+        return Ok(());
+    }
+    match output {
+        ViewOutput::Resource(Some(resource)) => println!("{}", resource),
+        ViewOutput::Resource(None) => println!("Resource not found."),
+        ViewOutput::Events(page) if page.events.is_empty() => println!("Events not found."),
+        ViewOutput::Events(page) => {
+            for event in page.events {
+                println!("{}", event.data)
+            }
+        }
+        ViewOutput::Module(Some(module)) => {
+            let metadata = &module.metadata;
+            println!("Module {}::{}", metadata.address, metadata.name);
+            println!("Size: {} bytes", metadata.size);
+            println!("Bytecode version: {}", metadata.bytecode_version);
+            if metadata.dependencies.is_empty() {
+                println!("Dependencies: none");
+            } else {
+                println!("Dependencies:");
+                for dependency in &metadata.dependencies {
+                    println!("  {}", dependency);
+                }
+            }
+            if disassemble {
+                println!();
+                println!("{}", module.disassembly);
+            } else if module.public_functions.is_empty() {
+                println!("Public functions: none");
+            } else {
+                println!("Public functions:");
+                for function in &module.public_functions {
+                    println!("  {}", function);
+                }
+            }
+        }
+        ViewOutput::Module(None) => println!("Module {} is not published.", module_name(path)),
+        ViewOutput::Bytecode(Some(bytecode)) => println!("{}", bytecode),
+        ViewOutput::Bytecode(None) => println!("Bytecode not found."),
+    }
+    Ok(())
+}
+
+fn module_name(path: &Path) -> String {
+    module_id_from_path(path).unwrap_or_else(|| path.display().to_string())
+}
+
+/// Print the resources stored at `address`, sorted by type, with their decoded values if `decode`
+/// is set. Resources are read and printed one at a time, so that large accounts are not held in
+/// memory.
+pub fn view_resources(
+    state: &OnDiskStateView,
+    address: &AccountAddress,
+    decode: bool,
+    format: OutputFormat,
+) -> Result<()> {
+    let resources = state.resources_at(address)?;
+    let entries = resources.into_iter().map(|(tag, path)| {
+        let value = if decode {
+            state.view_resource(&path)?
+        } else {
+            None
+        };
+        Ok(ResourceEntry {
+            type_: tag.to_string(),
+            size: fs::metadata(&path)?.len(),
+            value,
+        })
+    });
+    print_listing(
+        entries,
+        format,
+        &format!("No resources at {}.", format_address(address)),
+        |out, entry| {
+            writeln!(out, "{} ({} bytes)", entry.type_, entry.size)?;
+            if let Some(value) = &entry.value {
+                writeln!(out, "{}", value)?;
+            }
+            Ok(())
+        },
+    )
+}
+
+/// Print the modules published at `address`, sorted by name, one at a time
+pub fn view_modules(
+    state: &OnDiskStateView,
+    address: &AccountAddress,
+    format: OutputFormat,
+) -> Result<()> {
+    let modules = state.modules_at(address)?;
+    let entries = modules.into_iter().map(|(_, path)| {
+        let bytes = fs::read(&path)?;
+        let module = CompiledModule::deserialize(&bytes)?;
+        Ok(module_metadata(&module, bytes.len()))
+    });
+    print_listing(
+        entries,
+        format,
+        &format!("No modules at {}.", format_address(address)),
+        |out, module| {
+            writeln!(
+                out,
+                "{}::{} ({} bytes, bytecode version {})",
+                module.address, module.name, module.size, module.bytecode_version
+            )
+        },
+    )
+}
+
+/// Print each of `entries` as soon as it is read: with `print_text`, or as an element of a JSON
+/// array on its own line. `empty` is printed instead of text if there are no entries.
+fn print_listing<T: Serialize>(
+    entries: impl Iterator<Item = Result<T>>,
+    format: OutputFormat,
+    empty: &str,
+    print_text: impl Fn(&mut io::StdoutLock, &T) -> io::Result<()>,
+) -> Result<()> {
+    let stdout = io::stdout();
+    let mut out = stdout.lock();
+    let mut is_empty = true;
+    for entry in entries {
+        let entry = entry?;
+        match format {
+            OutputFormat::Text => print_text(&mut out, &entry)?,
+            OutputFormat::Json => write!(
+                out,
+                "{}  {}",
+                if is_empty { "[\n" } else { ",\n" },
+                serde_json::to_string(&entry)?
+            )?,
+        }
+        is_empty = false;
+    }
+    match format {
+        OutputFormat::Text if is_empty => writeln!(out, "{}", empty)?,
+        OutputFormat::Text => (),
+        OutputFormat::Json if is_empty => writeln!(out, "[]")?,
+        OutputFormat::Json => writeln!(out, "\n]")?,
     }
     Ok(())
 }
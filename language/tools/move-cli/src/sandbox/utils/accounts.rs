@@ -0,0 +1,233 @@
+// Copyright (c) The Move Contributors
+// SPDX-License-Identifier: Apache-2.0
+
+use anyhow::{bail, Context, Result};
+use move_core_types::{
+    account_address::AccountAddress, parser, transaction_argument::TransactionArgument,
+};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::{
+    fs,
+    path::{Path, PathBuf},
+};
+
+/// The file in the storage directory holding the accounts created by `sandbox account create`
+pub const ACCOUNTS_FILE: &str = "accounts.toml";
+
+/// Hashed along with the creation counter to derive the address of an account
+const ACCOUNT_ADDRESS_SEED: &[u8] = b"move-sandbox-account:";
+
+/// An account created by `sandbox account create`
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Account {
+    pub name: String,
+    /// The address of the account, as a hex literal
+    pub address: String,
+    /// The value of the creation counter the address was derived from, or 0 for an account
+    /// imported with its address by `move address import`
+    pub index: u64,
+}
+
+/// The accounts created by `sandbox account create`, stored in `storage/accounts.toml`. Addresses
+/// are derived from a creation counter, so that the same commands always create the same accounts,
+/// and accounts are referred to as `@<name>` by the arguments of `sandbox run`.
+#[derive(Debug, Default, Serialize, Deserialize)]
+pub struct AccountRegistry {
+    /// The number of accounts created so far
+    #[serde(default)]
+    counter: u64,
+    #[serde(default)]
+    accounts: Vec<Account>,
+}
+
+/// An argument of `sandbox run`: either a value, or `@<name>` for the address of an account of
+/// the registry
+#[derive(Debug, Clone)]
+pub enum RunArgument {
+    Value(TransactionArgument),
+    Account(String),
+}
+
+impl AccountRegistry {
+    /// The path of the registry of the storage in `storage_dir`
+    pub fn path(storage_dir: &Path) -> PathBuf {
+        storage_dir.join(ACCOUNTS_FILE)
+    }
+
+    /// The registry of the storage in `storage_dir`, or an empty one if no account was created
+    pub fn load(storage_dir: &Path) -> Result<Self> {
+        let path = Self::path(storage_dir);
+        if !path.exists() {
+            return Ok(Self::default());
+        }
+        let contents = fs::read_to_string(&path)
+            .with_context(|| format!("Unable to read '{}'", path.display()))?;
+        toml_edit::easy::from_str(&contents)
+            .with_context(|| format!("Error parsing '{}'", path.display()))
+    }
+
+    pub fn save(&self, storage_dir: &Path) -> Result<()> {
+        let path = Self::path(storage_dir);
+        fs::create_dir_all(storage_dir)?;
+        fs::write(&path, toml_edit::easy::to_string(self)?)
+            .with_context(|| format!("Unable to write '{}'", path.display()))
+    }
+
+    pub fn accounts(&self) -> &[Account] {
+        &self.accounts
+    }
+
+    /// Create an account named `name`, or `account<counter>` if no name is given
+    pub fn create(&mut self, name: Option<&str>) -> Result<&Account> {
+        if let Some(name) = name {
+            check_account_name(name)?;
+            if let Some(account) = self.find(name) {
+                bail!(
+                    "An account named '{}' already exists, at {}",
+                    name,
+                    account.address
+                )
+            }
+        }
+        // skip the counter values whose address or default name is taken
+        let (index, address, name) = loop {
+            self.counter += 1;
+            let address = format!("0x{}", derive_address(self.counter));
+            let name = match name {
+                Some(name) => name.to_string(),
+                None => format!("account{}", self.counter),
+            };
+            if self
+                .accounts
+                .iter()
+                .all(|account| account.address != address && account.name != name)
+            {
+                break (self.counter, address, name);
+            }
+        };
+        self.accounts.push(Account {
+            name,
+            address,
+            index,
+        });
+        Ok(self.accounts.last().unwrap())
+    }
+
+    /// Record the account `name` at `address`, replacing the address of the account of that name
+    /// if there is one. Unlike created accounts, such accounts are not derived from the counter.
+    pub fn import(&mut self, name: &str, address: AccountAddress) -> Result<()> {
+        check_account_name(name)?;
+        let address = format!("0x{}", address);
+        match self
+            .accounts
+            .iter_mut()
+            .find(|account| account.name == name)
+        {
+            Some(account) => {
+                account.address = address;
+                account.index = 0;
+            }
+            None => self.accounts.push(Account {
+                name: name.to_string(),
+                address,
+                index: 0,
+            }),
+        }
+        Ok(())
+    }
+
+    /// The names of the accounts `other` has at another address than `self`
+    pub fn conflicts_with(&self, other: &Self) -> Vec<String> {
+        other
+            .accounts
+            .iter()
+            .filter(|account| {
+                self.find(&account.name)
+                    .map_or(false, |own| own.address != account.address)
+            })
+            .map(|account| account.name.clone())
+            .collect()
+    }
+
+    /// Add the accounts of `other` that `self` does not have, and take the address `other` has for
+    /// the accounts in conflict if `prefer_other` is set. The creation counter is the larger of the
+    /// two, so that the accounts created from then on are new to both.
+    pub fn merge(&mut self, other: &Self, prefer_other: bool) {
+        for account in &other.accounts {
+            match self
+                .accounts
+                .iter_mut()
+                .find(|own| own.name == account.name)
+            {
+                Some(own) if prefer_other => *own = account.clone(),
+                Some(_) => (),
+                None => self.accounts.push(account.clone()),
+            }
+        }
+        self.counter = self.counter.max(other.counter);
+    }
+
+    /// The address of the account named `name`
+    pub fn resolve(&self, name: &str) -> Result<AccountAddress> {
+        match self.find(name) {
+            Some(account) => Ok(AccountAddress::from_hex_literal(&account.address)?),
+            None => bail!(
+                "Unknown account '@{0}'. Create it with `move sandbox account create --name {0}`, \
+                 or list the accounts with `move sandbox account list`",
+                name
+            ),
+        }
+    }
+
+    /// `signer` with `@<name>` replaced by the address of the account `name`
+    pub fn resolve_signer(&self, signer: &str) -> Result<String> {
+        match signer.strip_prefix('@') {
+            Some(name) => Ok(self.resolve(name)?.to_hex_literal()),
+            None => Ok(signer.to_string()),
+        }
+    }
+
+    pub fn resolve_argument(&self, arg: &RunArgument) -> Result<TransactionArgument> {
+        match arg {
+            RunArgument::Value(value) => Ok(value.clone()),
+            RunArgument::Account(name) => Ok(TransactionArgument::Address(self.resolve(name)?)),
+        }
+    }
+
+    fn find(&self, name: &str) -> Option<&Account> {
+        self.accounts.iter().find(|account| account.name == name)
+    }
+}
+
+fn check_account_name(name: &str) -> Result<()> {
+    if name.is_empty()
+        || !name
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || c == '_' || c == '-')
+    {
+        bail!(
+            "Invalid account name '{}': names are made of letters, digits, '_' and '-'",
+            name
+        )
+    }
+    Ok(())
+}
+
+/// Parse an argument of `sandbox run`, e.g. `10` or `@alice`
+pub fn parse_run_argument(s: &str) -> Result<RunArgument> {
+    match s.strip_prefix('@') {
+        Some(name) => Ok(RunArgument::Account(name.to_string())),
+        None => Ok(RunArgument::Value(parser::parse_transaction_argument(s)?)),
+    }
+}
+
+/// The address of the account created with creation counter `index`
+fn derive_address(index: u64) -> AccountAddress {
+    let digest = Sha256::new()
+        .chain(ACCOUNT_ADDRESS_SEED)
+        .chain(index.to_le_bytes())
+        .finalize();
+    AccountAddress::from_bytes(&digest[..AccountAddress::LENGTH])
+        .expect("digests are longer than addresses")
+}
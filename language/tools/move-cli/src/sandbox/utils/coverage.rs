@@ -0,0 +1,113 @@
+// Copyright (c) The Move Contributors
+// SPDX-License-Identifier: Apache-2.0
+
+use crate::sandbox::utils::OnDiskStateView;
+use anyhow::{Context, Result};
+use move_command_line_common::env::get_bytecode_version_from_env;
+use move_compiler::compiled_unit::CompiledUnit;
+use move_core_types::{
+    account_address::AccountAddress, identifier::Identifier, resolver::ModuleResolver,
+};
+use move_coverage::coverage_map::{output_map_to_file, CoverageMap};
+use move_package::compilation::compiled_package::CompiledPackage;
+use sha2::{Digest, Sha256};
+use std::{
+    collections::BTreeSet,
+    fs,
+    path::{Path, PathBuf},
+};
+use tempfile::NamedTempFile;
+
+/// The environment variable the VM reads the path of its trace file from
+const MOVE_VM_TRACING_ENV_VAR_NAME: &str = "MOVE_VM_TRACE";
+
+/// Records the instructions executed by sandbox transactions into the coverage map `move test
+/// --coverage` writes, so that `move coverage` reports on both
+pub struct CoverageTracker {
+    trace: NamedTempFile,
+    coverage_map_path: PathBuf,
+}
+
+impl CoverageTracker {
+    /// Start tracing the VM. Must be called before the first transaction of the process is
+    /// executed, as the VM only looks up its trace file once.
+    pub fn start(coverage_map_path: PathBuf) -> Result<Self> {
+        let coverage_dir = coverage_map_path.parent().unwrap_or_else(|| Path::new("."));
+        fs::create_dir_all(coverage_dir).with_context(|| {
+            format!(
+                "Failed to create the coverage directory {}",
+                coverage_dir.display()
+            )
+        })?;
+        let trace = NamedTempFile::new_in(coverage_dir)?;
+        std::env::set_var(MOVE_VM_TRACING_ENV_VAR_NAME, trace.path());
+        Ok(Self {
+            trace,
+            coverage_map_path,
+        })
+    }
+
+    /// Add the instructions traced since `start` to the coverage map. Only the modules stored in
+    /// `state` as `package` built them are counted, as the coverage of other modules cannot be
+    /// reported against the package sources.
+    pub fn finish(self, state: &OnDiskStateView, package: &CompiledPackage) -> Result<()> {
+        let mut coverage_map = CoverageMap::from_trace_file(self.trace.path());
+        let traced_modules = coverage_map
+            .exec_maps
+            .values()
+            .flat_map(|exec_map| exec_map.module_maps.keys().cloned())
+            .collect();
+        let current_modules = current_modules(state, package, &traced_modules)?;
+        for exec_map in coverage_map.exec_maps.values_mut() {
+            exec_map
+                .module_maps
+                .retain(|module, _| current_modules.contains(module));
+        }
+        if self.coverage_map_path.exists() {
+            let mut previous = CoverageMap::from_binary_file(&self.coverage_map_path)?;
+            previous.merge(coverage_map);
+            coverage_map = previous;
+        }
+        output_map_to_file(&self.coverage_map_path, &coverage_map)
+    }
+}
+
+/// The modules among `traced_modules` whose bytecode in `state` is the bytecode `package` was built
+/// with, as identified by its hash. A warning is printed for each module `state` stores another
+/// build of.
+fn current_modules(
+    state: &OnDiskStateView,
+    package: &CompiledPackage,
+    traced_modules: &BTreeSet<(AccountAddress, Identifier)>,
+) -> Result<BTreeSet<(AccountAddress, Identifier)>> {
+    let bytecode_version = get_bytecode_version_from_env();
+    let mut current_modules = BTreeSet::new();
+    for unit in package.all_modules() {
+        let module = match &unit.unit {
+            CompiledUnit::Module(module) => &module.module,
+            CompiledUnit::Script(_) => continue,
+        };
+        let module_id = module.self_id();
+        let key = (*module_id.address(), module_id.name().to_owned());
+        if !traced_modules.contains(&key) {
+            continue;
+        }
+        let stored_bytes = match state.get_module(&module_id)? {
+            Some(bytes) => bytes,
+            None => continue,
+        };
+        let mut package_bytes = vec![];
+        module.serialize_for_version(bytecode_version, &mut package_bytes)?;
+        if Sha256::digest(&stored_bytes) == Sha256::digest(&package_bytes) {
+            current_modules.insert(key);
+        } else {
+            eprintln!(
+                "Warning: storage holds another build of module 0x{}::{}, its coverage is not \
+                 recorded. Publish the package again to record it.",
+                module_id.address().short_str_lossless(),
+                module_id.name()
+            );
+        }
+    }
+    Ok(current_modules)
+}
@@ -2,7 +2,10 @@
 // Copyright (c) The Move Contributors
 // SPDX-License-Identifier: Apache-2.0
 
-use crate::sandbox::utils::on_disk_state_view::OnDiskStateView;
+use crate::{
+    sandbox::{cli::OutputFormat, utils::on_disk_state_view::OnDiskStateView},
+    UNRESOLVED_NATIVES_HINT,
+};
 use anyhow::{bail, Result};
 use colored::Colorize;
 use difference::{Changeset, Difference};
@@ -10,11 +13,17 @@ use move_binary_format::{
     access::ModuleAccess,
     compatibility::Compatibility,
     errors::VMError,
-    file_format::{AbilitySet, CompiledModule, FunctionDefinitionIndex, SignatureToken},
+    file_format::{
+        AbilitySet, CompiledModule, FunctionDefinitionIndex, SignatureToken, TableIndex,
+    },
     normalized, IndexKind,
 };
 use move_bytecode_utils::Modules;
-use move_command_line_common::files::{FileHash, MOVE_COMPILED_EXTENSION};
+use move_command_line_common::{
+    env::get_bytecode_version_from_env,
+    files::{FileHash, MOVE_COMPILED_EXTENSION},
+    value_rendering::render_value,
+};
 use move_compiler::{
     compiled_unit::{CompiledUnit, NamedCompiledModule},
     diagnostics::{self, report_diagnostics, Diagnostic, Diagnostics, FileName},
@@ -22,28 +31,38 @@ use move_compiler::{
 use move_core_types::{
     account_address::AccountAddress,
     effects::{ChangeSet, Event, Op},
-    errmap::ErrorMapping,
+    errmap::{ErrorDescription, ErrorMapping},
     language_storage::{ModuleId, TypeTag},
+    resolver::ModuleResolver,
     transaction_argument::TransactionArgument,
     vm_status::{AbortLocation, StatusCode, VMStatus},
 };
 use move_ir_types::location::Loc;
 use move_package::compilation::compiled_package::CompiledUnitWithSource;
-use move_resource_viewer::{AnnotatedMoveStruct, MoveValueAnnotator};
+use move_resource_viewer::MoveValueAnnotator;
 use move_vm_test_utils::gas_schedule::Gas;
+use serde::Serialize;
 use std::{
     collections::{BTreeMap, HashMap},
-    fs,
+    fmt, fs,
     path::Path,
 };
 
+pub mod accounts;
+pub mod coverage;
+pub mod module_diff;
 pub mod on_disk_state_view;
 pub mod package_context;
+pub mod transaction_log;
 
+pub use accounts::*;
+pub use coverage::*;
+pub use module_diff::*;
 use move_bytecode_utils::module_cache::GetModule;
 use move_vm_test_utils::gas_schedule::{CostTable, GasStatus};
 pub use on_disk_state_view::*;
 pub use package_context::*;
+pub use transaction_log::*;
 
 pub fn get_gas_status(cost_table: &CostTable, gas_budget: Option<u64>) -> Result<GasStatus> {
     let gas_status = if let Some(gas_budget) = gas_budget {
@@ -104,7 +123,7 @@ pub(crate) fn explain_publish_changeset(changeset: &ChangeSet) {
 }
 
 // Print a struct with a specified outer indent
-fn print_struct_with_indent(value: &AnnotatedMoveStruct, indent: u64) {
+fn print_struct_with_indent(value: impl fmt::Display, indent: u64) {
     let indent_str: String = (0..indent).map(|_| " ").collect::<String>();
     let value_str = format!("{}", value);
     let lines = value_str.split('\n');
@@ -115,8 +134,8 @@ fn print_struct_with_indent(value: &AnnotatedMoveStruct, indent: u64) {
 
 // Print struct diff with a specified outer indent
 fn print_struct_diff_with_indent(
-    value1: &AnnotatedMoveStruct,
-    value2: &AnnotatedMoveStruct,
+    value1: impl fmt::Display,
+    value2: impl fmt::Display,
     indent: u64,
 ) {
     let indent_str: String = (0..indent).map(|_| " ").collect::<String>();
@@ -159,10 +178,12 @@ pub(crate) fn explain_execution_effects(
     if !events.is_empty() {
         println!("Emitted {:?} events:", events.len());
         // TODO: better event printing
-        for (event_key, event_sequence_number, _event_type, event_data) in events {
+        for (event_key, event_sequence_number, event_type, event_data) in events {
             println!(
-                "Emitted {:?} as the {}th event to stream {:?}",
-                event_data, event_sequence_number, event_key
+                "Emitted {} as the {}th event to stream {:?}",
+                render_value(format!("{:?}", event_data), event_type),
+                event_sequence_number,
+                event_key
             )
         }
     }
@@ -191,19 +212,23 @@ pub(crate) fn explain_execution_effects(
                 Op::New(blob) => {
                     bytes_to_write += blob.len();
                     println!(
-                        "Added type {}: {:?} (wrote {:?} bytes)",
-                        struct_tag, blob, bytes_to_write
+                        "Added type {}: {} (wrote {:?} bytes)",
+                        struct_tag,
+                        render_value(format!("{:?}", blob), struct_tag),
+                        bytes_to_write
                     );
                     // Print new resource
                     let resource =
                         MoveValueAnnotator::new(state).view_resource(struct_tag, blob)?;
-                    print_struct_with_indent(&resource, 6)
+                    print_struct_with_indent(render_value(resource, struct_tag), 6)
                 }
                 Op::Modify(blob) => {
                     bytes_to_write += blob.len();
                     println!(
-                        "Changed type {}: {:?} (wrote {:?} bytes)",
-                        struct_tag, blob, bytes_to_write
+                        "Changed type {}: {} (wrote {:?} bytes)",
+                        struct_tag,
+                        render_value(format!("{:?}", blob), struct_tag),
+                        bytes_to_write
                     );
                     // Print resource diff
                     let resource_data = state
@@ -214,7 +239,11 @@ pub(crate) fn explain_execution_effects(
                     let resource_new =
                         MoveValueAnnotator::new(state).view_resource(struct_tag, blob)?;
 
-                    print_struct_diff_with_indent(&resource_old, &resource_new, 8)
+                    print_struct_diff_with_indent(
+                        render_value(resource_old, struct_tag),
+                        render_value(resource_new, struct_tag),
+                        8,
+                    )
                 }
                 Op::Delete => {
                     println!(
@@ -227,7 +256,7 @@ pub(crate) fn explain_execution_effects(
                         .unwrap();
                     let resource_old =
                         MoveValueAnnotator::new(state).view_resource(struct_tag, &resource_data)?;
-                    print_struct_with_indent(&resource_old, 6);
+                    print_struct_with_indent(render_value(resource_old, struct_tag), 6);
                 }
             };
             total_bytes_written += bytes_to_write;
@@ -264,8 +293,9 @@ pub(crate) fn maybe_commit_effects(
             }
         }
 
-        for (event_key, event_sequence_number, event_type, event_data) in events {
-            state.save_event(&event_key, event_sequence_number, event_type, event_data)?
+        // storage numbers the events of each stream itself
+        for (event_key, _, event_type, event_data) in events {
+            state.save_event(&event_key, event_type, event_data)?
         }
     } else if !(changeset.resources().next().is_none() && events.is_empty()) {
         println!("Discarding changes; re-run without --dry-run if you would like to keep them.")
@@ -274,6 +304,200 @@ pub(crate) fn maybe_commit_effects(
     Ok(())
 }
 
+/// The effects of a transaction that `move sandbox run --dry-run` shows instead of committing them
+#[derive(Debug, Default, Serialize)]
+pub struct EffectsPreview {
+    pub modules: Vec<ModuleChange>,
+    pub resources: Vec<ResourceChange>,
+    pub events: Vec<EventPreview>,
+    /// Gas consumed by the transaction. Always 0 when no gas budget was given.
+    pub gas_used: u64,
+    /// The most memory held at once by the vectors built by the transaction, in bytes. Only
+    /// reported when a memory quota is given.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub peak_memory: Option<u64>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct ModuleChange {
+    pub module: String,
+    /// `published` or `updated`
+    pub op: String,
+}
+
+#[derive(Debug, Serialize)]
+pub struct ResourceChange {
+    pub address: String,
+    #[serde(rename = "type")]
+    pub type_: String,
+    /// `added`, `changed` or `deleted`
+    pub op: String,
+    /// The resource before the transaction, or `None` if it is added
+    pub before: Option<String>,
+    /// The resource after the transaction, or `None` if it is deleted
+    pub after: Option<String>,
+    /// Whether `before` or `after` was cut to fit `--max-value-render-bytes`
+    pub truncated: bool,
+}
+
+#[derive(Debug, Serialize)]
+pub struct EventPreview {
+    /// The event stream, as a hex string
+    pub key: String,
+    pub sequence_number: u64,
+    #[serde(rename = "type")]
+    pub type_: String,
+    pub data: String,
+    /// Whether `data` was cut to fit `--max-value-render-bytes`
+    pub truncated: bool,
+}
+
+impl EffectsPreview {
+    pub fn is_empty(&self) -> bool {
+        self.modules.is_empty() && self.resources.is_empty() && self.events.is_empty()
+    }
+}
+
+/// Render the effects of a transaction against the resources currently stored in `state`
+pub(crate) fn preview_effects(
+    changeset: &ChangeSet,
+    events: &[Event],
+    state: &OnDiskStateView,
+    gas_used: u64,
+) -> Result<EffectsPreview> {
+    let annotator = MoveValueAnnotator::new(state);
+    let mut preview = EffectsPreview {
+        gas_used,
+        ..EffectsPreview::default()
+    };
+    for (addr, name, blob_op) in changeset.modules() {
+        let op = match blob_op {
+            Op::New(_) => "published",
+            Op::Modify(_) => "updated",
+            Op::Delete => panic!("Deleting a module is not supported"),
+        };
+        preview.modules.push(ModuleChange {
+            module: ModuleId::new(addr, name.clone()).short_str_lossless(),
+            op: op.to_string(),
+        });
+    }
+    for (addr, account) in changeset.accounts() {
+        for (struct_tag, blob_op) in account.resources() {
+            let before = match blob_op {
+                Op::New(_) => None,
+                Op::Modify(_) | Op::Delete => state
+                    .get_resource_bytes(*addr, struct_tag.clone())?
+                    .map(|blob| annotator.view_resource(struct_tag, &blob))
+                    .transpose()?
+                    .map(|resource| render_value(resource, struct_tag)),
+            };
+            let (op, after) = match blob_op {
+                Op::New(blob) => ("added", Some(blob)),
+                Op::Modify(blob) => ("changed", Some(blob)),
+                Op::Delete => ("deleted", None),
+            };
+            let after = after
+                .map(|blob| annotator.view_resource(struct_tag, blob))
+                .transpose()?
+                .map(|resource| render_value(resource, struct_tag));
+            let truncated = [&before, &after]
+                .iter()
+                .any(|value| value.as_ref().map_or(false, |value| value.truncated));
+            preview.resources.push(ResourceChange {
+                address: format!("0x{}", addr.short_str_lossless()),
+                type_: struct_tag.to_string(),
+                op: op.to_string(),
+                before: before.map(|value| value.text),
+                after: after.map(|value| value.text),
+                truncated,
+            });
+        }
+    }
+    for (event_key, event_sequence_number, event_type, event_data) in events {
+        let data = render_value(annotator.view_value(event_type, event_data)?, event_type);
+        preview.events.push(EventPreview {
+            key: format!("0x{}", hex::encode(event_key)),
+            sequence_number: *event_sequence_number,
+            type_: event_type.to_string(),
+            data: data.text,
+            truncated: data.truncated,
+        });
+    }
+    Ok(preview)
+}
+
+/// Print `preview` as text or JSON
+pub(crate) fn print_effects_preview(preview: &EffectsPreview, format: OutputFormat) -> Result<()> {
+    if let OutputFormat::Json = format {
+        println!("{}", serde_json::to_string_pretty(preview)?);
+        return Ok(());
+    }
+    let print_indented = |label: &str, value: &str| {
+        println!("    {}:", label);
+        for line in value.lines() {
+            println!("      {}", line);
+        }
+    };
+    for module in &preview.modules {
+        println!("{} module {}", module.op, module.module);
+    }
+    for resource in &preview.resources {
+        println!(
+            "{} resource {} under {}",
+            resource.op, resource.type_, resource.address
+        );
+        if let Some(before) = &resource.before {
+            print_indented("before", before);
+        }
+        if let Some(after) = &resource.after {
+            print_indented("after", after);
+        }
+    }
+    for event in &preview.events {
+        println!(
+            "emitted event {} of type {} to stream {}",
+            event.sequence_number, event.type_, event.key
+        );
+        print_indented("data", &event.data);
+    }
+    println!("Gas used: {}", preview.gas_used);
+    if let Some(peak_memory) = preview.peak_memory {
+        println!("Peak memory: {} bytes", peak_memory);
+    }
+    if !preview.is_empty() {
+        println!("Discarding changes; re-run without --dry-run if you would like to keep them.")
+    }
+    Ok(())
+}
+
+/// One-line descriptions of the modules, resources and events changed by a transaction, as recorded
+/// in the transaction log
+pub(crate) fn summarize_changes(changeset: &ChangeSet, events: &[Event]) -> Vec<String> {
+    let mut changes = vec![];
+    for (addr, name, blob_op) in changeset.modules() {
+        let module_id = ModuleId::new(addr, name.clone()).short_str_lossless();
+        changes.push(match blob_op {
+            Op::New(_) => format!("published module {}", module_id),
+            Op::Modify(_) => format!("updated module {}", module_id),
+            Op::Delete => format!("deleted module {}", module_id),
+        });
+    }
+    for (addr, account) in changeset.accounts() {
+        let addr = addr.short_str_lossless();
+        for (struct_tag, blob_op) in account.resources() {
+            changes.push(match blob_op {
+                Op::New(_) => format!("added resource {} under 0x{}", struct_tag, addr),
+                Op::Modify(_) => format!("changed resource {} under 0x{}", struct_tag, addr),
+                Op::Delete => format!("deleted resource {} under 0x{}", struct_tag, addr),
+            });
+        }
+    }
+    if !events.is_empty() {
+        changes.push(format!("emitted {} event(s)", events.len()));
+    }
+    changes
+}
+
 pub(crate) fn explain_type_error(
     script_params: &[SignatureToken],
     signers: &[AccountAddress],
@@ -314,10 +538,13 @@ pub(crate) fn explain_type_error(
     println!("Execution failed with type error when binding type arguments to type parameters")
 }
 
+/// Explain why publishing `unit` failed. If the module is not compatible with the version in
+/// storage, the changes between them are printed as well if `print_diff` is set.
 pub(crate) fn explain_publish_error(
     error: VMError,
     state: &OnDiskStateView,
     unit: &CompiledUnitWithSource,
+    print_diff: bool,
 ) -> Result<()> {
     use StatusCode::*;
     let mut files = HashMap::new();
@@ -360,6 +587,11 @@ pub(crate) fn explain_publish_error(
                 // but this is not easy to check without walking the global state and looking for everything
                 println!("Linking API for structs/functions of module {} has changed. Need to redeploy all dependent modules.", module_id)
             }
+            if print_diff {
+                let old_size = state.get_module(&module_id)?.map_or(0, |bytes| bytes.len());
+                let new_size = unit.unit.serialize(get_bytecode_version_from_env()).len();
+                print_module_diff(&old_module, old_size, module, new_size);
+            }
         }
         VMStatus::Error(CYCLIC_MODULE_DEPENDENCY) => {
             println!(
@@ -413,16 +645,33 @@ pub(crate) fn explain_publish_error(
             let mut diags = Diagnostics::new();
             for (ind_kind, table_ind) in err_indices {
                 if let IndexKind::FunctionHandle = ind_kind {
-                    let native_function = &(module.function_defs())[*table_ind as usize];
+                    // the index counts the native functions of the module only
+                    let (def_idx, native_function) = match module
+                        .function_defs()
+                        .iter()
+                        .enumerate()
+                        .filter(|(_, def)| def.is_native())
+                        .nth(*table_ind as usize)
+                    {
+                        Some(native_function) => native_function,
+                        None => continue,
+                    };
                     let fh = module.function_handle_at(native_function.function);
                     let mh = module.module_handle_at(fh.module);
                     let function_source_map = unit
                         .unit
                         .source_map()
-                        .get_function_source_map(FunctionDefinitionIndex(*table_ind));
-                    if let Ok(map) = function_source_map {
+                        .get_function_source_map(FunctionDefinitionIndex(def_idx as TableIndex));
+                    // generated source maps of packages built without them point nowhere
+                    if let Some(map) = function_source_map
+                        .ok()
+                        .filter(|map| map.definition_location.file_hash() == file_hash)
+                    {
                         let err_string = format!(
-                            "Missing implementation for the native function {}::{}",
+                            "Missing implementation for the native function 0x{}::{}::{}",
+                            module
+                                .address_identifier_at(mh.address)
+                                .short_str_lossless(),
                             module.identifier_at(mh.name).as_str(),
                             module.identifier_at(fh.name).as_str()
                         );
@@ -430,7 +679,7 @@ pub(crate) fn explain_publish_error(
                             diagnostics::codes::Declarations::InvalidFunction,
                             (map.definition_location, err_string),
                             Vec::<(Loc, String)>::new(),
-                            Vec::<String>::new(),
+                            vec![UNRESOLVED_NATIVES_HINT.to_string()],
                         );
                         diags.add(diag);
                     }
@@ -449,6 +698,22 @@ pub(crate) fn explain_publish_error(
     Ok(())
 }
 
+/// The error constant `abort_code` stands for in module `id`, along with the name of its category
+/// if the code combines a category of `error_map` with the reason of the constant, as in
+/// `category + (reason << 8)`
+fn explain_abort_code(
+    error_map: &ErrorMapping,
+    id: &ModuleId,
+    abort_code: u64,
+) -> Option<(ErrorDescription, Option<String>)> {
+    if let Some(error_desc) = error_map.get_explanation(id, abort_code) {
+        return Some((error_desc, None));
+    }
+    let category = error_map.error_categories.get(&(abort_code & 0xff))?;
+    let error_desc = error_map.get_explanation(id, abort_code >> 8)?;
+    Some((error_desc, Some(category.code_name.clone())))
+}
+
 /// Explain an execution error
 pub(crate) fn explain_execution_error(
     error_descriptions: &ErrorMapping,
@@ -461,22 +726,26 @@ pub(crate) fn explain_execution_error(
     txn_args: &[TransactionArgument],
 ) -> Result<()> {
     use StatusCode::*;
+    let message = error.message().cloned();
     match error.into_vm_status() {
         VMStatus::MoveAbort(AbortLocation::Module(id), abort_code) => {
             // try to use move-explain to explain the abort
-
-            print!(
-                "Execution aborted with code {} in module {}.",
-                abort_code, id
-            );
-
-            if let Some(error_desc) = error_descriptions.get_explanation(&id, abort_code) {
-                println!(
-                    " Abort code details:\nName: {}\nDescription:{}",
-                    error_desc.code_name, error_desc.code_description,
-                )
-            } else {
-                println!()
+            match explain_abort_code(error_descriptions, &id, abort_code) {
+                Some((error_desc, category)) => {
+                    let name = match category {
+                        Some(category) => format!("{}, {}", error_desc.code_name, category),
+                        None => error_desc.code_name.clone(),
+                    };
+                    println!(
+                        "Execution aborted with code {} ({}) in module {}. Abort code \
+                         details:\nName: {}\nDescription:{}",
+                        abort_code, name, id, error_desc.code_name, error_desc.code_description,
+                    )
+                }
+                None => println!(
+                    "Execution aborted with code {} in module {}.",
+                    abort_code, id
+                ),
             }
         }
         VMStatus::MoveAbort(AbortLocation::Script, abort_code) => {
@@ -511,6 +780,10 @@ pub(crate) fn explain_execution_error(
                 EXECUTION_STACK_OVERFLOW => "an execution stack overflow".to_string(),
                 CALL_STACK_OVERFLOW => "a call stack overflow".to_string(),
                 OUT_OF_GAS => "an out of gas error".to_string(),
+                MEMORY_QUOTA_EXCEEDED => format!(
+                    "a MEMORY_QUOTA_EXCEEDED error ({})",
+                    message.as_deref().unwrap_or("memory quota exceeded")
+                ),
                 _ => format!("a {} error", status_code.status_type()),
             };
             // TODO: map to source code location
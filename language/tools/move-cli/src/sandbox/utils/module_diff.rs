@@ -0,0 +1,340 @@
+// Copyright (c) The Move Contributors
+// SPDX-License-Identifier: Apache-2.0
+
+//! The structural differences between two versions of a module, read from their bytecode alone:
+//! the functions, structs, constants and friends added, removed or changed, and the change in
+//! serialized size.
+
+use move_binary_format::{
+    access::ModuleAccess,
+    file_format::{Ability, AbilitySet, CompiledModule, Constant, Visibility},
+    normalized::{self, Type},
+};
+use move_core_types::identifier::Identifier;
+use std::collections::BTreeMap;
+
+/// The differences between the module held by storage and the module replacing it, one per line,
+/// e.g. `~ function f: public fun f(u64) -> public fun f(u64, bool)`. Additions start with `+`,
+/// removals with `-` and changes with `~`; the last line is the change in serialized size.
+pub fn diff_modules(
+    stored: &CompiledModule,
+    stored_size: usize,
+    new: &CompiledModule,
+    new_size: usize,
+) -> Vec<String> {
+    let mut lines = vec![];
+
+    let functions = |module: &CompiledModule| -> BTreeMap<Identifier, String> {
+        module
+            .function_defs()
+            .iter()
+            .map(|def| {
+                let (name, function) = normalized::Function::new(module, def);
+                let signature = format_function_signature(name.as_str(), &function);
+                (name, signature)
+            })
+            .collect()
+    };
+    diff_items(
+        "function",
+        &functions(stored),
+        &functions(new),
+        |name, stored, new| vec![format!("~ function {}: {} -> {}", name, stored, new)],
+        &mut lines,
+    );
+
+    let structs = |module: &CompiledModule| -> BTreeMap<Identifier, normalized::Struct> {
+        module
+            .struct_defs()
+            .iter()
+            .map(|def| normalized::Struct::new(module, def))
+            .collect()
+    };
+    let (stored_structs, new_structs) = (structs(stored), structs(new));
+    let declarations = |structs: &BTreeMap<Identifier, normalized::Struct>| {
+        structs
+            .iter()
+            .map(|(name, s)| (name.clone(), format_struct(name.as_str(), s)))
+            .collect::<BTreeMap<_, _>>()
+    };
+    diff_items(
+        "struct",
+        &declarations(&stored_structs),
+        &declarations(&new_structs),
+        |name, _, _| diff_struct(name, &stored_structs[name], &new_structs[name]),
+        &mut lines,
+    );
+
+    let constants = |module: &CompiledModule| {
+        let mut constants: Vec<String> = module
+            .constant_pool()
+            .iter()
+            .map(|constant| format_constant(module, constant))
+            .collect();
+        constants.sort();
+        constants
+    };
+    let (stored_constants, new_constants) = (constants(stored), constants(new));
+    for constant in &stored_constants {
+        if !new_constants.contains(constant) {
+            lines.push(format!("- constant {}", constant));
+        }
+    }
+    for constant in &new_constants {
+        if !stored_constants.contains(constant) {
+            lines.push(format!("+ constant {}", constant));
+        }
+    }
+
+    let (stored_friends, new_friends) = (stored.immediate_friends(), new.immediate_friends());
+    for friend in &stored_friends {
+        if !new_friends.contains(friend) {
+            lines.push(format!("- friend {}", friend.short_str_lossless()));
+        }
+    }
+    for friend in &new_friends {
+        if !stored_friends.contains(friend) {
+            lines.push(format!("+ friend {}", friend.short_str_lossless()));
+        }
+    }
+
+    if lines.is_empty() {
+        lines.push("no structural changes".to_string());
+    }
+    lines.push(format!(
+        "size: {} -> {} bytes ({:+})",
+        stored_size,
+        new_size,
+        new_size as i64 - stored_size as i64
+    ));
+    lines
+}
+
+/// Print the differences between the module held by storage and the module replacing it
+pub fn print_module_diff(
+    stored: &CompiledModule,
+    stored_size: usize,
+    new: &CompiledModule,
+    new_size: usize,
+) {
+    println!("Changes to module {}:", new.self_id().short_str_lossless());
+    for line in diff_modules(stored, stored_size, new, new_size) {
+        println!("  {}", line);
+    }
+}
+
+/// Report the items of `new` that are not in `stored` and the other way around, by name, along
+/// with the lines `diff_item` returns for the items whose declaration differs
+fn diff_items(
+    kind: &str,
+    stored: &BTreeMap<Identifier, String>,
+    new: &BTreeMap<Identifier, String>,
+    diff_item: impl Fn(&Identifier, &str, &str) -> Vec<String>,
+    lines: &mut Vec<String>,
+) {
+    for (name, declaration) in stored {
+        match new.get(name) {
+            None => lines.push(format!("- {} {}", kind, declaration)),
+            Some(new_declaration) if new_declaration != declaration => {
+                lines.extend(diff_item(name, declaration, new_declaration))
+            }
+            Some(_) => (),
+        }
+    }
+    for (name, declaration) in new {
+        if !stored.contains_key(name) {
+            lines.push(format!("+ {} {}", kind, declaration));
+        }
+    }
+}
+
+fn diff_struct(
+    name: &Identifier,
+    stored: &normalized::Struct,
+    new: &normalized::Struct,
+) -> Vec<String> {
+    let mut lines = vec![];
+    if stored.abilities != new.abilities {
+        lines.push(format!(
+            "~ struct {}: abilities {} -> {}",
+            name,
+            format_struct_abilities(stored.abilities),
+            format_struct_abilities(new.abilities)
+        ));
+    }
+    let (stored_params, new_params) = (
+        format_struct_type_parameters(stored),
+        format_struct_type_parameters(new),
+    );
+    if stored_params != new_params {
+        let or_none = |params: String| {
+            if params.is_empty() {
+                "none".to_string()
+            } else {
+                params
+            }
+        };
+        lines.push(format!(
+            "~ struct {}: type parameters {} -> {}",
+            name,
+            or_none(stored_params),
+            or_none(new_params)
+        ));
+    }
+    for (i, field) in stored.fields.iter().enumerate() {
+        match new.fields.iter().position(|f| f.name == field.name) {
+            None => lines.push(format!(
+                "~ struct {}: - field {}: {}",
+                name, field.name, field.type_
+            )),
+            Some(j) if new.fields[j].type_ != field.type_ => lines.push(format!(
+                "~ struct {}: field {}: {} -> {}",
+                name, field.name, field.type_, new.fields[j].type_
+            )),
+            Some(j) if j != i => lines.push(format!(
+                "~ struct {}: field {} moved from position {} to {}",
+                name, field.name, i, j
+            )),
+            Some(_) => (),
+        }
+    }
+    for field in &new.fields {
+        if !stored.fields.iter().any(|f| f.name == field.name) {
+            lines.push(format!(
+                "~ struct {}: + field {}: {}",
+                name, field.name, field.type_
+            ));
+        }
+    }
+    lines
+}
+
+/// The signature of a function, e.g. `public entry fun f<T0: drop>(u64, T0): bool`
+pub fn format_function_signature(name: &str, function: &normalized::Function) -> String {
+    let type_parameters = if function.type_parameters.is_empty() {
+        String::new()
+    } else {
+        let type_parameters = function
+            .type_parameters
+            .iter()
+            .enumerate()
+            .map(|(idx, abilities)| {
+                let abilities = format_abilities(*abilities);
+                if abilities.is_empty() {
+                    format!("T{}", idx)
+                } else {
+                    format!("T{}: {}", idx, abilities.join(" + "))
+                }
+            })
+            .collect::<Vec<_>>();
+        format!("<{}>", type_parameters.join(", "))
+    };
+    let return_type = match function.return_.as_slice() {
+        [] => String::new(),
+        [ty] => format!(": {}", ty),
+        tys => format!(": ({})", format_types(tys)),
+    };
+    let visibility = match function.visibility {
+        Visibility::Public => "public ",
+        Visibility::Friend => "public(friend) ",
+        Visibility::Private => "",
+    };
+    format!(
+        "{}{}fun {}{}({}){}",
+        visibility,
+        if function.is_entry { "entry " } else { "" },
+        name,
+        type_parameters,
+        format_types(&function.parameters),
+        return_type
+    )
+}
+
+/// The declaration of a struct after the `struct` keyword, e.g.
+/// `Coin<phantom T0> has store { value: u64 }`
+fn format_struct(name: &str, s: &normalized::Struct) -> String {
+    let type_parameters = format_struct_type_parameters(s);
+    let abilities = format_abilities(s.abilities);
+    let fields = s
+        .fields
+        .iter()
+        .map(|field| format!("{}: {}", field.name, field.type_))
+        .collect::<Vec<_>>();
+    format!(
+        "{}{}{} {{ {} }}",
+        name,
+        type_parameters,
+        if abilities.is_empty() {
+            String::new()
+        } else {
+            format!(" has {}", abilities.join(", "))
+        },
+        fields.join(", ")
+    )
+}
+
+fn format_struct_type_parameters(s: &normalized::Struct) -> String {
+    if s.type_parameters.is_empty() {
+        return String::new();
+    }
+    let type_parameters = s
+        .type_parameters
+        .iter()
+        .enumerate()
+        .map(|(idx, param)| {
+            let abilities = format_abilities(param.constraints);
+            format!(
+                "{}T{}{}",
+                if param.is_phantom { "phantom " } else { "" },
+                idx,
+                if abilities.is_empty() {
+                    String::new()
+                } else {
+                    format!(": {}", abilities.join(" + "))
+                }
+            )
+        })
+        .collect::<Vec<_>>();
+    format!("<{}>", type_parameters.join(", "))
+}
+
+fn format_struct_abilities(abilities: AbilitySet) -> String {
+    let abilities = format_abilities(abilities);
+    if abilities.is_empty() {
+        "none".to_string()
+    } else {
+        abilities.join(", ")
+    }
+}
+
+fn format_abilities(abilities: AbilitySet) -> Vec<&'static str> {
+    abilities
+        .into_iter()
+        .map(|ability| match ability {
+            Ability::Copy => "copy",
+            Ability::Drop => "drop",
+            Ability::Store => "store",
+            Ability::Key => "key",
+        })
+        .collect()
+}
+
+fn format_types(tys: &[Type]) -> String {
+    tys.iter()
+        .map(|ty| ty.to_string())
+        .collect::<Vec<_>>()
+        .join(", ")
+}
+
+/// A constant of the constant pool, e.g. `100u64`, as constants have no name in bytecode. The
+/// value is printed with its type, and as the hex of its serialization if it cannot be decoded.
+fn format_constant(module: &CompiledModule, constant: &Constant) -> String {
+    match constant.deserialize_constant() {
+        Some(value) => value.to_string(),
+        None => format!(
+            "{} = 0x{}",
+            Type::new(module, &constant.type_),
+            hex::encode(&constant.data)
+        ),
+    }
+}
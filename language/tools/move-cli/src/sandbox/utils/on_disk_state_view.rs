@@ -10,7 +10,7 @@ use move_binary_format::{
     file_format::{CompiledModule, CompiledScript, FunctionDefinitionIndex},
 };
 use move_bytecode_utils::module_cache::GetModule;
-use move_command_line_common::files::MOVE_COMPILED_EXTENSION;
+use move_command_line_common::{files::MOVE_COMPILED_EXTENSION, interrupt};
 use move_core_types::{
     account_address::AccountAddress,
     identifier::Identifier,
@@ -22,6 +22,7 @@ use move_disassembler::disassembler::Disassembler;
 use move_ir_types::location::Spanned;
 use move_resource_viewer::{AnnotatedMoveStruct, AnnotatedMoveValue, MoveValueAnnotator};
 use std::{
+    collections::BTreeSet,
     convert::{TryFrom, TryInto},
     fs,
     path::{Path, PathBuf},
@@ -38,6 +39,36 @@ pub const EVENTS_DIR: &str = "events";
 
 /// file under `DEFAULT_BUILD_DIR` where a registry of generated struct layouts are stored
 pub const STRUCT_LAYOUTS_FILE: &str = "struct_layouts.yaml";
+/// directory under `DEFAULT_BUILD_DIR` where the layouts of all structs are generated by default
+pub const STRUCT_LAYOUTS_DIR: &str = "struct_layouts";
+
+/// The bytes of some modules of storage at a point in time, taken by
+/// `OnDiskStateView::snapshot_modules`. `None` for the modules storage did not hold.
+#[derive(Debug, Clone)]
+pub struct ModuleSnapshot(Vec<(PathBuf, Option<Vec<u8>>)>);
+
+impl ModuleSnapshot {
+    /// Put the modules back as they were when the snapshot was taken, removing those storage did
+    /// not hold then, along with the directories left empty
+    pub fn restore(&self) -> Result<()> {
+        for (path, bytes_opt) in &self.0 {
+            match bytes_opt {
+                Some(bytes) => fs::write(path, bytes)?,
+                None if path.exists() => {
+                    fs::remove_file(path)?;
+                    // the `modules` directory, then the address directory, if they are empty
+                    for dir in path.ancestors().skip(1).take(2) {
+                        if fs::remove_dir(dir).is_err() {
+                            break;
+                        }
+                    }
+                }
+                None => (),
+            }
+        }
+        Ok(())
+    }
+}
 
 #[derive(Debug)]
 pub struct OnDiskStateView {
@@ -66,19 +97,54 @@ impl OnDiskStateView {
         })
     }
 
+    /// Open the storage directory `storage_dir`, e.g. a copy of the storage of another sandbox,
+    /// to read from it. Unlike `create`, nothing is created, and no build directory is used.
+    pub fn open_read_only<P: Into<PathBuf>>(storage_dir: P) -> Result<Self> {
+        let storage_dir = storage_dir.into();
+        if !storage_dir.is_dir() {
+            bail!(
+                "The state '{}' is not a storage directory",
+                storage_dir.display()
+            )
+        }
+        Ok(Self {
+            build_dir: PathBuf::new(),
+            storage_dir: storage_dir.canonicalize()?,
+        })
+    }
+
     pub fn build_dir(&self) -> &PathBuf {
         &self.build_dir
     }
 
+    pub fn storage_dir(&self) -> &PathBuf {
+        &self.storage_dir
+    }
+
     pub fn struct_layouts_file(&self) -> PathBuf {
         self.build_dir.join(STRUCT_LAYOUTS_FILE)
     }
 
+    pub fn struct_layouts_dir(&self) -> PathBuf {
+        self.build_dir.join(STRUCT_LAYOUTS_DIR)
+    }
+
     fn is_data_path(&self, p: &Path, parent_dir: &str) -> bool {
-        if !p.exists() {
-            return false;
+        // a path that does not exist, e.g. of a resource that is not published (yet), is
+        // canonicalized from its closest existing ancestor
+        let mut existing = p;
+        let mut missing = vec![];
+        while !existing.exists() {
+            match (existing.parent(), existing.file_name()) {
+                (Some(parent), Some(name)) => {
+                    missing.push(name);
+                    existing = parent;
+                }
+                _ => return false,
+            }
         }
-        let p = p.canonicalize().unwrap();
+        let mut p = existing.canonicalize().unwrap();
+        p.extend(missing.into_iter().rev());
         p.starts_with(&self.storage_dir)
             && match p.parent() {
                 Some(parent) => parent.ends_with(parent_dir),
@@ -125,7 +191,7 @@ impl OnDiskStateView {
         path.with_extension(BCS_EXTENSION)
     }
 
-    fn get_module_path(&self, module_id: &ModuleId) -> PathBuf {
+    pub fn get_module_path(&self, module_id: &ModuleId) -> PathBuf {
         let mut path = self.get_addr_path(module_id.address());
         path.push(MODULES_DIR);
         path.push(module_id.name().to_string());
@@ -211,34 +277,100 @@ impl OnDiskStateView {
                     t => bail!("Expected to parse struct tag, but got {}", t),
                 };
                 match Self::get_bytes(resource_path)? {
-                    Some(resource_data) => {
-                        Some(MoveValueAnnotator::new(self).view_resource(&id, &resource_data)?)
-                    }
+                    Some(resource_data) => Some(
+                        MoveValueAnnotator::new(self)
+                            .view_resource(&id, &resource_data)
+                            .map_err(|err| {
+                                self.decoding_error(&TypeTag::Struct(id.clone()), err)
+                            })?,
+                    ),
                     None => None,
                 }
             }),
         }
     }
 
+    /// The events stored in `events_path`. Events are numbered by their position in the stream,
+    /// which also backfills the sequence numbers of streams saved without consistent ones.
     fn get_events(&self, events_path: &Path) -> Result<Vec<Event>> {
-        Ok(if events_path.exists() {
+        let mut events = if events_path.exists() {
             match Self::get_bytes(events_path)? {
                 Some(events_data) => bcs::from_bytes::<Vec<Event>>(&events_data)?,
                 None => vec![],
             }
         } else {
             vec![]
-        })
+        };
+        for (sequence_number, event) in events.iter_mut().enumerate() {
+            event.1 = sequence_number as u64;
+        }
+        Ok(events)
     }
 
-    pub fn view_events(&self, events_path: &Path) -> Result<Vec<AnnotatedMoveValue>> {
+    /// The sequence number, type and value of each event stored in `events_path`
+    pub fn view_events(
+        &self,
+        events_path: &Path,
+    ) -> Result<Vec<(u64, TypeTag, AnnotatedMoveValue)>> {
         let annotator = MoveValueAnnotator::new(self);
         self.get_events(events_path)?
-            .iter()
-            .map(|(_, _, event_type, event_data)| annotator.view_value(event_type, event_data))
+            .into_iter()
+            .map(|(_, sequence_number, event_type, event_data)| {
+                let value = annotator
+                    .view_value(&event_type, &event_data)
+                    .map_err(|err| self.decoding_error(&event_type, err))?;
+                Ok((sequence_number, event_type, value))
+            })
             .collect()
     }
 
+    /// `err`, failing to decode a value of type `tag`, or an error naming the modules storage lacks
+    /// to decode it if it does
+    fn decoding_error(&self, tag: &TypeTag, err: anyhow::Error) -> anyhow::Error {
+        let missing = self.missing_modules(tag);
+        if missing.is_empty() {
+            return err;
+        }
+        anyhow!(
+            "Unable to decode a value of type {}: storage lacks the module(s) {}",
+            tag,
+            missing
+                .iter()
+                .map(|id| format!("0x{}::{}", id.address().short_str_lossless(), id.name()))
+                .collect::<Vec<_>>()
+                .join(", ")
+        )
+    }
+
+    /// The modules of the structs in `tag`, and the modules they depend on, that storage lacks
+    fn missing_modules(&self, tag: &TypeTag) -> Vec<ModuleId> {
+        let mut queue = vec![];
+        let mut tags = vec![tag];
+        while let Some(tag) = tags.pop() {
+            match tag {
+                TypeTag::Vector(inner) => tags.push(inner),
+                TypeTag::Struct(struct_tag) => {
+                    queue.push(struct_tag.module_id());
+                    tags.extend(&struct_tag.type_params);
+                }
+                _ => (),
+            }
+        }
+        let mut seen = BTreeSet::new();
+        let mut missing = vec![];
+        while let Some(id) = queue.pop() {
+            if !seen.insert(id.clone()) {
+                continue;
+            }
+            match self.get_module_by_id(&id) {
+                Ok(Some(module)) => queue.extend(module.immediate_dependencies()),
+                _ => missing.push(id),
+            }
+        }
+        missing.sort();
+        missing
+    }
+
     fn view_bytecode(path: &Path, is_module: bool) -> Result<Option<String>> {
         if path.is_dir() {
             bail!("Bad bytecode path {:?}. Needed file, found directory", path)
@@ -300,10 +432,11 @@ impl OnDiskStateView {
         Ok(fs::write(path, bcs_bytes)?)
     }
 
+    /// Append an event to the stream `event_key`. The event is numbered after the last event of
+    /// the stream, so that sequence numbers strictly increase from 0 without gaps.
     pub fn save_event(
         &self,
         event_key: &[u8],
-        event_sequence_number: u64,
         event_type: TypeTag,
         event_data: Vec<u8>,
     ) -> Result<()> {
@@ -314,6 +447,7 @@ impl OnDiskStateView {
         }
         // grab the old event log (if any) and append this event to it
         let mut event_log = self.get_events(&path)?;
+        let event_sequence_number = event_log.len() as u64;
         event_log.push((
             event_key.to_vec(),
             event_sequence_number,
@@ -342,16 +476,43 @@ impl OnDiskStateView {
     }
 
     /// Save all the modules in the local cache, re-generate mv_interfaces if required.
+    /// If the process is interrupted while the modules are being saved, the modules saved so far
+    /// are restored to their previous state.
     pub fn save_modules<'a>(
         &self,
         modules: impl IntoIterator<Item = &'a (ModuleId, Vec<u8>)>,
     ) -> Result<()> {
+        let modules: Vec<_> = modules.into_iter().collect();
+        let previous_modules =
+            self.snapshot_modules(modules.iter().map(|(module_id, _)| module_id))?;
+        let _rollback_on_interrupt = interrupt::on_interrupt(move || {
+            let _ = previous_modules.restore();
+        });
         for (module_id, module_bytes) in modules {
-            self.save_module(module_id, module_bytes)?;
+            // the rollback runs on the thread of the interrupt handler, so it must not interleave
+            // with the writes
+            interrupt::run_uninterrupted(|| self.save_module(module_id, module_bytes))?;
         }
         Ok(())
     }
 
+    /// Record the modules `ids` as they are stored now, so that they can be put back with
+    /// `ModuleSnapshot::restore` after being overwritten
+    pub fn snapshot_modules<'a>(
+        &self,
+        ids: impl IntoIterator<Item = &'a ModuleId>,
+    ) -> Result<ModuleSnapshot> {
+        let modules = ids
+            .into_iter()
+            .map(|module_id| {
+                let path = self.get_module_path(module_id);
+                let bytes_opt = Self::get_bytes(&path)?;
+                Ok((path, bytes_opt))
+            })
+            .collect::<Result<Vec<_>>>()?;
+        Ok(ModuleSnapshot(modules))
+    }
+
     pub fn delete_module(&self, id: &ModuleId) -> Result<()> {
         let path = self.get_module_path(id);
         fs::remove_file(path)?;
@@ -388,6 +549,67 @@ impl OnDiskStateView {
         self.iter_paths(move |p| self.is_event_path(p))
     }
 
+    /// The resources stored at `addr` with the paths of the files holding them, sorted by type.
+    /// Only the directory is read, leaving the resources themselves on disk.
+    pub fn resources_at(&self, addr: &AccountAddress) -> Result<Vec<(StructTag, PathBuf)>> {
+        let mut resources = vec![];
+        for path in Self::files_in(&self.get_addr_path(addr).join(RESOURCES_DIR))? {
+            let name = path.file_stem().unwrap_or_default().to_string_lossy();
+            match parser::parse_type_tag(&name)? {
+                TypeTag::Struct(tag) => resources.push((tag, path)),
+                t => bail!("Expected to parse struct tag, but got {}", t),
+            }
+        }
+        resources.sort_by_cached_key(|(tag, _)| tag.to_string());
+        Ok(resources)
+    }
+
+    /// The addresses storing a resource of type `tag`, sorted
+    pub fn holders_of(&self, tag: &StructTag) -> Result<Vec<AccountAddress>> {
+        let mut holders = vec![];
+        if !self.storage_dir.is_dir() {
+            return Ok(holders);
+        }
+        for entry in fs::read_dir(&self.storage_dir)? {
+            let entry = entry?;
+            let name = entry.file_name();
+            let addr = match name.to_str().map(AccountAddress::from_hex_literal) {
+                Some(Ok(addr)) if entry.file_type()?.is_dir() => addr,
+                _ => continue,
+            };
+            if self.get_resource_path(addr, tag.clone()).is_file() {
+                holders.push(addr);
+            }
+        }
+        holders.sort();
+        Ok(holders)
+    }
+
+    /// The modules published at `addr` with the paths of the files holding them, sorted by name
+    pub fn modules_at(&self, addr: &AccountAddress) -> Result<Vec<(ModuleId, PathBuf)>> {
+        let mut modules = Self::files_in(&self.get_addr_path(addr).join(MODULES_DIR))?
+            .into_iter()
+            .filter_map(|path| Some((self.get_module_id(&path)?, path)))
+            .collect::<Vec<_>>();
+        modules.sort_by(|(a, _), (b, _)| a.name().cmp(b.name()));
+        Ok(modules)
+    }
+
+    /// The files in `dir`, or none if it does not exist
+    fn files_in(dir: &Path) -> Result<Vec<PathBuf>> {
+        if !dir.is_dir() {
+            return Ok(vec![]);
+        }
+        let mut files = vec![];
+        for entry in fs::read_dir(dir)? {
+            let entry = entry?;
+            if entry.file_type()?.is_file() {
+                files.push(entry.path());
+            }
+        }
+        Ok(files)
+    }
+
     /// Build all modules in the self.storage_dir.
     /// Returns an Err if a module does not deserialize.
     pub fn get_all_modules(&self) -> Result<Vec<CompiledModule>> {
@@ -1,16 +1,36 @@
 // Copyright (c) The Diem Core Contributors
 // Copyright (c) The Move Contributors
 // SPDX-License-Identifier: Apache-2.0
-use crate::{sandbox::utils::OnDiskStateView, DEFAULT_BUILD_DIR};
-use anyhow::Result;
-use move_command_line_common::env::get_bytecode_version_from_env;
-use move_package::{compilation::compiled_package::CompiledPackage, BuildConfig};
-use std::path::{Path, PathBuf};
+use crate::{
+    base::{
+        coverage::{coverage_dir, coverage_map_path},
+        errmap::DEFAULT_ERRMAP_FILE,
+    },
+    sandbox::utils::OnDiskStateView,
+    utils::project_config::ProjectConfig,
+    DEFAULT_BUILD_DIR,
+};
+use anyhow::{anyhow, bail, Result};
+use move_command_line_common::{
+    env::get_bytecode_version_from_env, files::MOVE_ERROR_DESC_EXTENSION,
+};
+use move_core_types::{account_address::AccountAddress, errmap::ErrorMapping};
+use move_package::{
+    compilation::compiled_package::CompiledPackage, source_package::layout::SourcePackageLayout,
+    BuildConfig,
+};
+use move_symbol_pool::Symbol;
+use std::{
+    fs,
+    path::{Path, PathBuf},
+};
 
 /// The PackageContext controls the package that the CLI is executing with respect to, and handles the
 /// creation of the `OnDiskStateView` with the package's dependencies.
 pub struct PackageContext {
     package: CompiledPackage,
+    /// The root directory of the package
+    package_path: PathBuf,
     build_dir: PathBuf,
 }
 
@@ -25,7 +45,12 @@ impl PackageContext {
         let package = build_config
             .clone()
             .compile_package(path, &mut Vec::new())?;
-        Ok(PackageContext { package, build_dir })
+        let package_path = SourcePackageLayout::try_find_root(path)?;
+        Ok(PackageContext {
+            package,
+            package_path,
+            build_dir,
+        })
     }
 
     /// Prepare an OnDiskStateView that is ready to use. Library modules will be preloaded into the
@@ -36,7 +61,7 @@ impl PackageContext {
     /// `view`, and `doctor`.
     pub fn prepare_state(&self, storage_dir: &Path) -> Result<OnDiskStateView> {
         let bytecode_version = get_bytecode_version_from_env();
-        let state = OnDiskStateView::create(self.build_dir.as_path(), storage_dir)?;
+        let state = self.open_state(storage_dir)?;
 
         // preload the storage with library modules (if such modules do not exist yet)
         let package = self.package();
@@ -61,9 +86,96 @@ impl PackageContext {
         Ok(state)
     }
 
+    /// Open the state in `storage_dir` without preloading the modules of the package's dependencies
+    pub fn open_state(&self, storage_dir: &Path) -> Result<OnDiskStateView> {
+        OnDiskStateView::create(self.build_dir.as_path(), storage_dir)
+    }
+
+    /// The root directory of the package
+    pub fn package_path(&self) -> &Path {
+        &self.package_path
+    }
+
     pub fn package(&self) -> &CompiledPackage {
         &self.package
     }
+
+    /// The coverage map `move test --coverage` and `--track-coverage` write for the package
+    pub fn coverage_map_path(&self, build_config: &BuildConfig) -> PathBuf {
+        coverage_map_path(&coverage_dir(&self.package_path, build_config))
+    }
+
+    /// The `default-signers` of the `[sandbox]` section of the project configuration, with the
+    /// named addresses among them resolved to their values in the package
+    pub fn default_signers(&self) -> Result<Vec<AccountAddress>> {
+        let config = ProjectConfig::load(&self.package_path)?;
+        let named_addresses = &self
+            .package
+            .compiled_package_info
+            .address_alias_instantiation;
+        config
+            .sandbox
+            .default_signers
+            .iter()
+            .map(|signer| {
+                if signer.starts_with("0x") {
+                    return AccountAddress::from_hex_literal(signer).map_err(|_| {
+                        anyhow!(
+                            "Invalid address '{}' in the default-signers of '{}'",
+                            signer,
+                            ProjectConfig::path(&self.package_path).display()
+                        )
+                    });
+                }
+                match named_addresses.get(&Symbol::from(signer.as_str())) {
+                    Some(address) => Ok(*address),
+                    None => bail!(
+                        "Named address '{}' in the default-signers of '{}' is not declared by \
+                         package {} or its dependencies",
+                        signer,
+                        ProjectConfig::path(&self.package_path).display(),
+                        self.package.compiled_package_info.package_name
+                    ),
+                }
+            })
+            .collect()
+    }
+
+    /// `error_descriptions` extended with the error maps generated by `move errmap` in the root
+    /// directories of the package and of its dependencies. Packages without an error map, or with
+    /// one that cannot be read, are skipped.
+    pub fn error_map(&self, error_descriptions: &ErrorMapping) -> ErrorMapping {
+        let mut error_map = error_descriptions.clone();
+        let package_paths = std::iter::once(&self.package_path).chain(
+            self.package
+                .dependency_sources
+                .iter()
+                .map(|source| &source.path),
+        );
+        for package_path in package_paths {
+            let errmap_path = package_path
+                .join(DEFAULT_ERRMAP_FILE)
+                .with_extension(MOVE_ERROR_DESC_EXTENSION);
+            let package_error_map = match fs::read(&errmap_path)
+                .ok()
+                .and_then(|bytes| bcs::from_bytes::<ErrorMapping>(&bytes).ok())
+            {
+                Some(package_error_map) => package_error_map,
+                None => continue,
+            };
+            error_map
+                .error_categories
+                .extend(package_error_map.error_categories);
+            for (module_id, errors) in package_error_map.module_error_maps {
+                error_map
+                    .module_error_maps
+                    .entry(module_id)
+                    .or_default()
+                    .extend(errors);
+            }
+        }
+        error_map
+    }
 }
 
 impl Default for PackageContext {
@@ -0,0 +1,137 @@
+// Copyright (c) The Move Contributors
+// SPDX-License-Identifier: Apache-2.0
+
+use anyhow::{Context, Result};
+use serde::{Deserialize, Serialize};
+use std::{
+    ffi::OsString,
+    fs,
+    path::{Path, PathBuf},
+};
+
+/// subdirectory of `DEFAULT_STORAGE_DIR` where the log of state-mutating commands is stored
+pub const LOG_DIR: &str = ".log";
+/// extension appended to the storage directory name to get the directory where logs rotated by
+/// `sandbox clean` are kept
+pub const ROTATED_LOGS_EXTENSION: &str = "logs";
+
+const LOG_ENTRY_EXTENSION: &str = "json";
+
+/// A single state-mutating sandbox command, as recorded in `storage/.log`.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct LogEntry {
+    /// Position of this entry in the log, starting from 0
+    pub sequence_number: u64,
+    /// Name of the sandbox command, e.g. `publish`
+    pub command: String,
+    /// The full argument list the CLI was invoked with (excluding the binary)
+    pub args: Vec<String>,
+    /// Human-readable summary of the modules, resources and events changed by the command
+    pub changes: Vec<String>,
+    /// Gas consumed by the command. Always 0 when gas metering is disabled.
+    pub gas_used: u64,
+}
+
+fn log_dir(storage_dir: &Path) -> PathBuf {
+    storage_dir.join(LOG_DIR)
+}
+
+/// Directory where `rotate_transaction_log` moves the log of `storage_dir`, e.g. `storage.logs` for `storage`
+pub fn rotated_logs_dir(storage_dir: &Path) -> PathBuf {
+    let mut name = storage_dir
+        .file_name()
+        .map(OsString::from)
+        .unwrap_or_default();
+    name.push(".");
+    name.push(ROTATED_LOGS_EXTENSION);
+    storage_dir.with_file_name(name)
+}
+
+fn entry_paths(log_dir: &Path) -> Result<Vec<PathBuf>> {
+    if !log_dir.exists() {
+        return Ok(vec![]);
+    }
+    let mut paths = fs::read_dir(log_dir)?
+        .map(|entry| Ok(entry?.path()))
+        .collect::<Result<Vec<_>>>()?
+        .into_iter()
+        .filter(|path| {
+            path.extension()
+                .map_or(false, |ext| ext == LOG_ENTRY_EXTENSION)
+        })
+        .collect::<Vec<_>>();
+    // entry file names are zero-padded sequence numbers, so lexicographic order is log order
+    paths.sort();
+    Ok(paths)
+}
+
+fn append_entry(
+    log_dir: &Path,
+    command: &str,
+    args: &[String],
+    changes: Vec<String>,
+    gas_used: u64,
+) -> Result<()> {
+    fs::create_dir_all(log_dir)?;
+    let sequence_number = entry_paths(log_dir)?.len() as u64;
+    let entry = LogEntry {
+        sequence_number,
+        command: command.to_string(),
+        args: args.to_vec(),
+        changes,
+        gas_used,
+    };
+    let path = log_dir
+        .join(format!("{:010}", sequence_number))
+        .with_extension(LOG_ENTRY_EXTENSION);
+    fs::write(&path, serde_json::to_string_pretty(&entry)?)
+        .with_context(|| format!("Unable to write transaction log entry {:?}", path))
+}
+
+/// The arguments recorded for a command the CLI was run with `argv`: all of them but the binary
+pub fn log_args(argv: &[OsString]) -> Vec<String> {
+    argv.iter()
+        .skip(1)
+        .map(|arg| arg.to_string_lossy().into_owned())
+        .collect()
+}
+
+/// Append an entry for `command`, run with `args`, to the transaction log of `storage_dir`.
+pub fn record_transaction(
+    storage_dir: &Path,
+    command: &str,
+    args: &[String],
+    changes: Vec<String>,
+    gas_used: u64,
+) -> Result<()> {
+    append_entry(&log_dir(storage_dir), command, args, changes, gas_used)
+}
+
+/// Read all entries of the transaction log of `storage_dir`, in the order they were recorded.
+pub fn read_transaction_log(storage_dir: &Path) -> Result<Vec<LogEntry>> {
+    entry_paths(&log_dir(storage_dir))?
+        .into_iter()
+        .map(|path| {
+            let contents = fs::read_to_string(&path)?;
+            serde_json::from_str(&contents)
+                .with_context(|| format!("Malformed transaction log entry {:?}", path))
+        })
+        .collect()
+}
+
+/// Move the transaction log of `storage_dir` out of the way so that the storage directory can be
+/// deleted. The log is closed with an entry for `command` (typically `clean`), run with `args`, and
+/// kept under `rotated_logs_dir(storage_dir)/<n>`, where `n` counts the previously rotated logs.
+pub fn rotate_transaction_log(storage_dir: &Path, command: &str, args: &[String]) -> Result<()> {
+    let log_dir = log_dir(storage_dir);
+    if !log_dir.exists() {
+        return Ok(());
+    }
+    append_entry(&log_dir, command, args, vec![], 0)?;
+
+    let rotated_dir = rotated_logs_dir(storage_dir);
+    fs::create_dir_all(&rotated_dir)?;
+    let generation = fs::read_dir(&rotated_dir)?.count();
+    fs::rename(&log_dir, rotated_dir.join(generation.to_string()))?;
+    Ok(())
+}
@@ -0,0 +1,64 @@
+// Copyright (c) The Move Contributors
+// SPDX-License-Identifier: Apache-2.0
+
+use anyhow::{bail, Result};
+use clap::*;
+use move_command_line_common::json_errors;
+use move_package::{
+    compilation::compiled_package::{VersionCompatibility, VersionStamp},
+    BuildConfig,
+};
+use std::path::Path;
+
+/// Options of the commands that use the artifacts of an earlier build
+#[derive(Parser, Default)]
+pub struct ArtifactVersionOptions {
+    /// Use build artifacts even if they were built by another major version of Move or with
+    /// another bytecode format version, which this version may not be able to read
+    #[clap(long = "ignore-version-mismatch", global = true)]
+    pub ignore_version_mismatch: bool,
+}
+
+/// Check that the artifacts of the package built into `package_build_dir` (`build/<package>`)
+/// were built by a version of Move this one can read. Artifacts of another minor or patch version
+/// only get a warning. Artifacts of another major version or bytecode format version are refused,
+/// unless `ignore_version_mismatch` is set. Artifacts built before versions were recorded in
+/// `BuildInfo.yaml` are not checked.
+pub fn check_artifacts_version(
+    package_build_dir: &Path,
+    build_config: &BuildConfig,
+    ignore_version_mismatch: bool,
+) -> Result<()> {
+    let stamp = match VersionStamp::read(package_build_dir)? {
+        Some(stamp) => stamp,
+        None => return Ok(()),
+    };
+    let current = VersionStamp::current(build_config);
+    match stamp.compatibility(&current) {
+        VersionCompatibility::Same => (),
+        VersionCompatibility::MinorMismatch => json_errors::warning(format!(
+            "the artifacts in '{}' were built by {}, and are used by {}. Rebuild the \
+             package if they cannot be read.",
+            package_build_dir.display(),
+            stamp,
+            current
+        )),
+        VersionCompatibility::MajorMismatch if ignore_version_mismatch => {
+            json_errors::warning(format!(
+                "using the artifacts in '{}' built by {} with {}, as \
+                 --ignore-version-mismatch is set",
+                package_build_dir.display(),
+                stamp,
+                current
+            ))
+        }
+        VersionCompatibility::MajorMismatch => bail!(
+            "The artifacts in '{}' were built by {}, which {} may not be able to read. Rebuild \
+             the package, or pass --ignore-version-mismatch to use them anyway",
+            package_build_dir.display(),
+            stamp,
+            current
+        ),
+    }
+    Ok(())
+}
@@ -0,0 +1,178 @@
+// Copyright (c) The Move Contributors
+// SPDX-License-Identifier: Apache-2.0
+
+//! The modules and functions of a package left out of its coverage, e.g. for
+//! `move coverage summary --fail-under`, listed in a `coverage-exemptions.toml` file in the package
+//! directory, each with the reason it cannot be covered:
+//!
+//! ```toml
+//! [[exemption]]
+//! module = "Shim"
+//! reason = "Only calls natives the unit tests cannot provide"
+//!
+//! [[exemption]]
+//! module = "Coin"
+//! function = "mint_v1"
+//! reason = "Kept for upgrade compatibility"
+//! ```
+
+use anyhow::{bail, Context, Result};
+use move_binary_format::{access::ModuleAccess, CompiledModule};
+use move_command_line_common::files::read_utf8_file;
+use move_coverage::summary::ModuleSummary;
+use serde::Deserialize;
+use std::path::Path;
+
+/// The file of a package listing its coverage exemptions
+pub const COVERAGE_EXEMPTIONS_FILE: &str = "coverage-exemptions.toml";
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct ExemptionsFile {
+    #[serde(default)]
+    exemption: Vec<CoverageExemption>,
+}
+
+/// A module, or a function of a module, left out of the coverage
+#[derive(Debug, Clone, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct CoverageExemption {
+    /// The name of the module, e.g. `Coin`
+    pub module: String,
+    /// The name of the function, or `None` to exempt the whole module
+    pub function: Option<String>,
+    /// Why the module or function cannot be covered
+    pub reason: String,
+}
+
+impl CoverageExemption {
+    /// The exempted module or function, e.g. `Coin::mint_v1`
+    pub fn target(&self) -> String {
+        match &self.function {
+            Some(function) => format!("{}::{}", self.module, function),
+            None => self.module.clone(),
+        }
+    }
+
+    fn names_module(&self, module: &CompiledModule) -> bool {
+        module.self_id().name().as_str() == self.module
+    }
+
+    fn names_function(&self, module: &CompiledModule, function: &str) -> bool {
+        self.names_module(module) && self.function.as_deref() == Some(function)
+    }
+}
+
+/// The coverage exemptions of a package
+#[derive(Debug, Default)]
+pub struct CoverageExemptions {
+    pub exemptions: Vec<CoverageExemption>,
+}
+
+impl CoverageExemptions {
+    /// The exemptions listed in the `coverage-exemptions.toml` of the package at `package_path`,
+    /// or none if it has no such file
+    pub fn load(package_path: &Path) -> Result<Self> {
+        let path = package_path.join(COVERAGE_EXEMPTIONS_FILE);
+        if !path.is_file() {
+            return Ok(Self::default());
+        }
+        let file: ExemptionsFile = toml_edit::easy::from_str(&read_utf8_file(&path)?)
+            .with_context(|| format!("Error parsing '{}'", path.display()))?;
+        for exemption in &file.exemption {
+            if exemption.reason.trim().is_empty() {
+                bail!(
+                    "The exemption of {} in '{}' needs a reason",
+                    exemption.target(),
+                    path.display()
+                )
+            }
+        }
+        Ok(Self {
+            exemptions: file.exemption,
+        })
+    }
+
+    /// Warn about the exemptions naming modules that are not among `modules`, or functions that
+    /// their module does not define, e.g. after they are removed
+    pub fn warn_stale(&self, modules: &[CompiledModule]) {
+        for exemption in &self.exemptions {
+            let module = modules.iter().find(|module| exemption.names_module(module));
+            let stale = match (module, &exemption.function) {
+                (None, _) => true,
+                (Some(module), Some(function)) => !module.function_defs().iter().any(|def| {
+                    module
+                        .identifier_at(module.function_handle_at(def.function).name)
+                        .as_str()
+                        == function
+                }),
+                (Some(_), None) => false,
+            };
+            if stale {
+                eprintln!(
+                    "Warning: {} exempts {}, which the package does not define",
+                    COVERAGE_EXEMPTIONS_FILE,
+                    exemption.target()
+                );
+            }
+        }
+    }
+
+    /// The exemption of the whole of `module`, if any
+    pub fn module_exemption(&self, module: &CompiledModule) -> Option<&CoverageExemption> {
+        self.exemptions
+            .iter()
+            .find(|exemption| exemption.function.is_none() && exemption.names_module(module))
+    }
+
+    /// `summary` of `module`, less the functions exempted
+    pub fn exclude_functions(
+        &self,
+        module: &CompiledModule,
+        mut summary: ModuleSummary,
+    ) -> ModuleSummary {
+        summary.function_summaries.retain(|name, _| {
+            !self
+                .exemptions
+                .iter()
+                .any(|exemption| exemption.names_function(module, name.as_str()))
+        });
+        summary
+    }
+
+    /// The exemptions naming functions or modules among `modules`, with the number of their
+    /// instructions covered and their total number of instructions according to the summary of
+    /// the module, computed by `summarize`
+    pub fn exempted_coverage(
+        &self,
+        modules: &[CompiledModule],
+        summarize: impl Fn(&CompiledModule) -> ModuleSummary,
+    ) -> Vec<(&CoverageExemption, u64, u64)> {
+        self.exemptions
+            .iter()
+            .filter_map(|exemption| {
+                let module = modules
+                    .iter()
+                    .find(|module| exemption.names_module(module))?;
+                let summary = summarize(module);
+                let functions: Vec<_> = summary
+                    .function_summaries
+                    .iter()
+                    .filter(|(name, _)| match &exemption.function {
+                        Some(function) => name.as_str() == function,
+                        None => true,
+                    })
+                    .map(|(_, function)| function)
+                    .collect();
+                if functions.is_empty() {
+                    return None;
+                }
+                Some((
+                    exemption,
+                    functions.iter().map(|function| function.covered).sum(),
+                    functions.iter().map(|function| function.total).sum(),
+                ))
+            })
+            .collect()
+    }
+}
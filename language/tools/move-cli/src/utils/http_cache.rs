@@ -0,0 +1,358 @@
+// Copyright (c) The Move Contributors
+// SPDX-License-Identifier: Apache-2.0
+
+//! A cache of the responses to the GET requests sent to the registry, kept under `MOVE_HOME`
+
+use crate::utils::{
+    movey_credential,
+    registry_tls::{RegistryTls, RegistryTlsOptions},
+};
+use anyhow::{bail, Context, Result};
+use clap::*;
+use move_command_line_common::json_errors;
+use reqwest::{
+    blocking::{Client, Response},
+    header::{HeaderName, CACHE_CONTROL, ETAG, IF_MODIFIED_SINCE, IF_NONE_MATCH, LAST_MODIFIED},
+    StatusCode,
+};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::{
+    fs,
+    path::{Path, PathBuf},
+    time::{Duration, SystemTime, UNIX_EPOCH},
+};
+
+/// The directory under `MOVE_HOME` holding the cached responses
+pub const HTTP_CACHE_DIR: &str = "http_cache";
+
+/// How long responses that do not set their own freshness lifetime with `Cache-Control: max-age`
+/// are used without revalidation, unless `cache_ttl` is set in the credential file
+pub const DEFAULT_CACHE_TTL: Duration = Duration::from_secs(5 * 60);
+
+/// Options of the commands that query the registry
+#[derive(Parser, Default)]
+pub struct HttpCacheOptions {
+    /// Send every request to the registry, instead of reusing the responses cached under
+    /// MOVE_HOME. The responses are not cached either.
+    #[clap(long = "no-cache")]
+    pub no_cache: bool,
+    #[clap(flatten)]
+    pub tls: RegistryTlsOptions,
+}
+
+/// A response stored in the cache, along with what is needed to revalidate it
+#[derive(Debug, Serialize, Deserialize)]
+struct CacheEntry {
+    url: String,
+    etag: Option<String>,
+    last_modified: Option<String>,
+    /// When the response was received or last revalidated, in seconds since the Unix epoch
+    stored_at: u64,
+    /// How long the response can be used without revalidation, in seconds
+    max_age: u64,
+    body: String,
+}
+
+/// Sends the GET requests of the registry commands, reusing the cached responses that are still
+/// fresh and revalidating the others with `If-None-Match` and `If-Modified-Since`.
+/// `Cache-Control: no-store` responses are never cached, and `no-cache` ones are revalidated on
+/// every use. A cache that cannot be read or written never fails a request: the request is sent
+/// directly instead, with a warning.
+pub struct HttpCache {
+    client: Client,
+    tls: RegistryTls,
+    /// `None` if caching is disabled
+    dir: Option<PathBuf>,
+    ttl: Duration,
+}
+
+impl HttpCache {
+    pub fn new(move_home: &str, options: &HttpCacheOptions) -> Result<Self> {
+        let ttl = movey_credential::get_cache_ttl(move_home)?
+            .map(Duration::from_secs)
+            .unwrap_or(DEFAULT_CACHE_TTL);
+        let tls = options.tls.resolve(move_home)?;
+        Ok(Self {
+            client: tls.client()?,
+            tls,
+            dir: if options.no_cache {
+                None
+            } else {
+                Some(Path::new(move_home).join(HTTP_CACHE_DIR))
+            },
+            ttl,
+        })
+    }
+
+    /// The body of the response to a GET request to `url`. Fails if the response is not a success.
+    pub fn get(&self, url: &str) -> Result<String> {
+        let entry_path = match &self.dir {
+            Some(dir) => dir.join(format!(
+                "{}.json",
+                hex::encode(Sha256::digest(url.as_bytes()))
+            )),
+            None => return Self::body(self.send(url, None)?),
+        };
+        // an entry that cannot be read is replaced by the response to a direct request
+        let entry = read_entry(&entry_path, url).unwrap_or_else(|err| {
+            json_errors::warning(format!(
+                "ignoring the cached response to {}: {:#}",
+                url, err
+            ));
+            None
+        });
+        if let Some(entry) = &entry {
+            if now() < entry.stored_at.saturating_add(entry.max_age) {
+                return Ok(entry.body.clone());
+            }
+        }
+
+        let response = self.send(url, entry.as_ref())?;
+        let mut entry = match (entry, response.status()) {
+            (Some(mut entry), StatusCode::NOT_MODIFIED) => {
+                entry.stored_at = now();
+                entry.max_age = self.max_age(&response).unwrap_or(entry.max_age);
+                entry
+            }
+            _ => {
+                let max_age = match self.max_age(&response) {
+                    Some(max_age) => max_age,
+                    None => return Self::body(response),
+                };
+                let header = |name: HeaderName| {
+                    response
+                        .headers()
+                        .get(name)
+                        .and_then(|value| value.to_str().ok())
+                        .map(str::to_string)
+                };
+                let (etag, last_modified) = (header(ETAG), header(LAST_MODIFIED));
+                CacheEntry {
+                    url: url.to_string(),
+                    etag,
+                    last_modified,
+                    stored_at: now(),
+                    max_age,
+                    body: Self::body(response)?,
+                }
+            }
+        };
+        if let Err(err) = write_entry(&entry_path, &entry) {
+            json_errors::warning(format!(
+                "unable to cache the response to {}: {:#}",
+                url, err
+            ));
+        }
+        Ok(std::mem::take(&mut entry.body))
+    }
+
+    fn send(&self, url: &str, entry: Option<&CacheEntry>) -> Result<Response> {
+        let mut request = self.client.get(url);
+        if let Some(entry) = entry {
+            if let Some(etag) = &entry.etag {
+                request = request.header(IF_NONE_MATCH, etag);
+            }
+            if let Some(last_modified) = &entry.last_modified {
+                request = request.header(IF_MODIFIED_SINCE, last_modified);
+            }
+        }
+        request
+            .send()
+            .map_err(|err| self.tls.explain_error(url, err.into()))
+    }
+
+    fn body(response: Response) -> Result<String> {
+        let status = response.status();
+        if !status.is_success() {
+            bail!(
+                "Request to {} failed with status {}",
+                response.url(),
+                status
+            )
+        }
+        Ok(response.text()?)
+    }
+
+    /// How long the successful or not modified `response` can be cached for, in seconds, or `None`
+    /// if it must not be cached
+    fn max_age(&self, response: &Response) -> Option<u64> {
+        let status = response.status();
+        if !status.is_success() && status != StatusCode::NOT_MODIFIED {
+            return None;
+        }
+        let directives = response
+            .headers()
+            .get_all(CACHE_CONTROL)
+            .iter()
+            .filter_map(|value| value.to_str().ok())
+            .flat_map(|value| value.split(','))
+            .map(|directive| directive.trim().to_lowercase())
+            .collect::<Vec<_>>();
+        if directives.iter().any(|directive| directive == "no-store") {
+            return None;
+        }
+        if directives.iter().any(|directive| directive == "no-cache") {
+            return Some(0);
+        }
+        let max_age = directives
+            .iter()
+            .find_map(|directive| directive.strip_prefix("max-age="))
+            .map(|seconds| seconds.trim_matches('"').parse().unwrap_or(0));
+        Some(max_age.unwrap_or(self.ttl.as_secs()))
+    }
+}
+
+/// The entry cached for `url` in `path`, or `None` if there is none
+fn read_entry(path: &Path, url: &str) -> Result<Option<CacheEntry>> {
+    let contents = match fs::read_to_string(path) {
+        Ok(contents) => contents,
+        Err(err) if err.kind() == std::io::ErrorKind::NotFound => return Ok(None),
+        Err(err) => {
+            return Err(err).with_context(|| format!("Unable to read '{}'", path.display()))
+        }
+    };
+    let entry: CacheEntry = serde_json::from_str(&contents)
+        .with_context(|| format!("'{}' is corrupted", path.display()))?;
+    if entry.url != url {
+        bail!("'{}' holds the response to {}", path.display(), entry.url)
+    }
+    Ok(Some(entry))
+}
+
+fn write_entry(path: &Path, entry: &CacheEntry) -> Result<()> {
+    let dir = path
+        .parent()
+        .expect("cache entries are in the cache directory");
+    fs::create_dir_all(dir).with_context(|| format!("Unable to create '{}'", dir.display()))?;
+    fs::write(path, serde_json::to_string(entry)?)
+        .with_context(|| format!("Unable to write '{}'", path.display()))
+}
+
+fn now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |elapsed| elapsed.as_secs())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use httpmock::prelude::*;
+
+    fn cache(move_home: &Path) -> HttpCache {
+        HttpCache::new(&move_home.to_string_lossy(), &HttpCacheOptions::default()).unwrap()
+    }
+
+    #[test]
+    fn revalidation_returns_not_modified() {
+        let move_home = tempfile::tempdir().unwrap();
+        let server = MockServer::start();
+        // mocks are matched in the order they are defined
+        let revalidate = server.mock(|when, then| {
+            when.method(GET)
+                .path("/api/v1/packages/search")
+                .header("If-None-Match", "\"v1\"");
+            then.status(304);
+        });
+        let fetch = server.mock(|when, then| {
+            when.method(GET).path("/api/v1/packages/search");
+            then.status(200)
+                .header("ETag", "\"v1\"")
+                .header("Cache-Control", "no-cache")
+                .body("[\"move-stdlib\"]");
+        });
+
+        let cache = cache(move_home.path());
+        let url = server.url("/api/v1/packages/search");
+        assert_eq!(cache.get(&url).unwrap(), "[\"move-stdlib\"]");
+        assert_eq!(cache.get(&url).unwrap(), "[\"move-stdlib\"]");
+        fetch.assert_hits(1);
+        revalidate.assert_hits(1);
+    }
+
+    #[test]
+    fn fresh_responses_are_not_requested_again() {
+        let move_home = tempfile::tempdir().unwrap();
+        let server = MockServer::start();
+        let fetch = server.mock(|when, then| {
+            when.method(GET).path("/api/v1/me");
+            then.status(200)
+                .header("Cache-Control", "max-age=3600")
+                .body("{\"name\":\"alice\"}");
+        });
+
+        let cache = cache(move_home.path());
+        let url = server.url("/api/v1/me");
+        assert_eq!(cache.get(&url).unwrap(), "{\"name\":\"alice\"}");
+        assert_eq!(cache.get(&url).unwrap(), "{\"name\":\"alice\"}");
+        fetch.assert_hits(1);
+
+        let no_cache = HttpCache::new(
+            &move_home.path().to_string_lossy(),
+            &HttpCacheOptions {
+                no_cache: true,
+                ..HttpCacheOptions::default()
+            },
+        )
+        .unwrap();
+        assert_eq!(no_cache.get(&url).unwrap(), "{\"name\":\"alice\"}");
+        fetch.assert_hits(2);
+    }
+
+    #[test]
+    fn no_store_responses_are_not_cached() {
+        let move_home = tempfile::tempdir().unwrap();
+        let server = MockServer::start();
+        let fetch = server.mock(|when, then| {
+            when.method(GET).path("/api/v1/packages/versions");
+            then.status(200)
+                .header("ETag", "\"v1\"")
+                .header("Cache-Control", "no-store")
+                .body("[]");
+        });
+
+        let cache = cache(move_home.path());
+        let url = server.url("/api/v1/packages/versions");
+        assert_eq!(cache.get(&url).unwrap(), "[]");
+        assert_eq!(cache.get(&url).unwrap(), "[]");
+        fetch.assert_hits(2);
+        assert!(!move_home.path().join(HTTP_CACHE_DIR).exists());
+    }
+
+    #[test]
+    fn corrupted_entries_fall_back_to_direct_requests() {
+        let move_home = tempfile::tempdir().unwrap();
+        let server = MockServer::start();
+        let fetch = server.mock(|when, then| {
+            when.method(GET).path("/api/v1/me");
+            then.status(200)
+                .header("Cache-Control", "max-age=3600")
+                .body("{\"name\":\"alice\"}");
+        });
+
+        let cache = cache(move_home.path());
+        let url = server.url("/api/v1/me");
+        cache.get(&url).unwrap();
+        for entry in fs::read_dir(move_home.path().join(HTTP_CACHE_DIR)).unwrap() {
+            fs::write(entry.unwrap().path(), "{ not json").unwrap();
+        }
+        assert_eq!(cache.get(&url).unwrap(), "{\"name\":\"alice\"}");
+        fetch.assert_hits(2);
+    }
+
+    #[test]
+    fn failed_responses_are_errors() {
+        let move_home = tempfile::tempdir().unwrap();
+        let server = MockServer::start();
+        server.mock(|when, then| {
+            when.method(GET).path("/api/v1/me");
+            then.status(401);
+        });
+
+        let err = cache(move_home.path())
+            .get(&server.url("/api/v1/me"))
+            .unwrap_err();
+        assert!(err.to_string().contains("401"), "{}", err);
+    }
+}
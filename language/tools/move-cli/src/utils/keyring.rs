@@ -0,0 +1,196 @@
+// Copyright (c) The Move Contributors
+// SPDX-License-Identifier: Apache-2.0
+
+#[cfg(any(target_os = "macos", target_os = "linux"))]
+use anyhow::Context;
+use anyhow::{bail, Result};
+#[cfg(any(target_os = "macos", target_os = "linux"))]
+use std::process::Command;
+
+/// The service the secrets of the Move CLI are stored under in the system keyring
+pub const KEYRING_SERVICE: &str = "move-cli";
+
+/// A store of secrets, each identified by an account name
+pub trait Keyring {
+    /// The secret stored for `account`, or `None` if there is none
+    fn get(&self, account: &str) -> Result<Option<String>>;
+    /// Store `secret` for `account`, replacing the secret stored before if any
+    fn set(&self, account: &str, secret: &str) -> Result<()>;
+    /// Remove the secret stored for `account`, if any
+    fn remove(&self, account: &str) -> Result<()>;
+}
+
+/// The keyring of the operating system, accessed through `security` on macOS and `secret-tool`
+/// (libsecret) on Linux
+pub struct SystemKeyring;
+
+impl Keyring for SystemKeyring {
+    #[cfg(target_os = "macos")]
+    fn get(&self, account: &str) -> Result<Option<String>> {
+        let output = Command::new("security")
+            .args([
+                "find-generic-password",
+                "-s",
+                KEYRING_SERVICE,
+                "-a",
+                account,
+                "-w",
+            ])
+            .output()
+            .context("Unable to run `security` to read the keychain")?;
+        // `security` exits with 44 when the item does not exist
+        match output.status.code() {
+            Some(0) => Ok(Some(
+                String::from_utf8(output.stdout)?.trim_end().to_string(),
+            )),
+            Some(44) => Ok(None),
+            _ => bail!(
+                "Unable to read the keychain: {}",
+                String::from_utf8_lossy(&output.stderr).trim()
+            ),
+        }
+    }
+
+    #[cfg(target_os = "macos")]
+    fn set(&self, account: &str, secret: &str) -> Result<()> {
+        use std::{io::Write, process::Stdio};
+        // the command is passed on stdin to `security -i`, so that the secret does not show in the
+        // process list
+        let mut child = Command::new("security")
+            .arg("-i")
+            .stdin(Stdio::piped())
+            .stdout(Stdio::null())
+            .stderr(Stdio::piped())
+            .spawn()
+            .context("Unable to run `security` to write to the keychain")?;
+        writeln!(
+            child.stdin.take().expect("stdin is piped"),
+            "add-generic-password -U -s {} -a {} -w {}",
+            quote(KEYRING_SERVICE),
+            quote(account),
+            quote(secret)
+        )?;
+        let output = child.wait_with_output()?;
+        // `security -i` reports the failure of a command on stderr, but may still exit with 0
+        if !output.status.success() || !output.stderr.is_empty() {
+            bail!(
+                "Unable to write to the keychain: {}",
+                String::from_utf8_lossy(&output.stderr).trim()
+            )
+        }
+        Ok(())
+    }
+
+    #[cfg(target_os = "macos")]
+    fn remove(&self, account: &str) -> Result<()> {
+        let output = Command::new("security")
+            .args([
+                "delete-generic-password",
+                "-s",
+                KEYRING_SERVICE,
+                "-a",
+                account,
+            ])
+            .output()
+            .context("Unable to run `security` to write to the keychain")?;
+        // `security` exits with 44 when the item does not exist
+        match output.status.code() {
+            Some(0) | Some(44) => Ok(()),
+            _ => bail!(
+                "Unable to remove the secret from the keychain: {}",
+                String::from_utf8_lossy(&output.stderr).trim()
+            ),
+        }
+    }
+
+    #[cfg(target_os = "linux")]
+    fn get(&self, account: &str) -> Result<Option<String>> {
+        let output = Command::new("secret-tool")
+            .args(["lookup", "service", KEYRING_SERVICE, "account", account])
+            .output()
+            .context("Unable to run `secret-tool` to read the keyring")?;
+        // `secret-tool` exits with 1 and prints nothing when the item does not exist
+        if output.status.success() {
+            Ok(Some(
+                String::from_utf8(output.stdout)?.trim_end().to_string(),
+            ))
+        } else if output.stderr.is_empty() {
+            Ok(None)
+        } else {
+            bail!(
+                "Unable to read the keyring: {}",
+                String::from_utf8_lossy(&output.stderr).trim()
+            )
+        }
+    }
+
+    #[cfg(target_os = "linux")]
+    fn set(&self, account: &str, secret: &str) -> Result<()> {
+        use std::{io::Write, process::Stdio};
+        // the secret is passed on stdin, so that it does not show in the process list
+        let mut child = Command::new("secret-tool")
+            .args([
+                "store",
+                "--label",
+                &format!("{} ({})", KEYRING_SERVICE, account),
+                "service",
+                KEYRING_SERVICE,
+                "account",
+                account,
+            ])
+            .stdin(Stdio::piped())
+            .stderr(Stdio::piped())
+            .spawn()
+            .context("Unable to run `secret-tool` to write to the keyring")?;
+        child
+            .stdin
+            .take()
+            .expect("stdin is piped")
+            .write_all(secret.as_bytes())?;
+        let output = child.wait_with_output()?;
+        if !output.status.success() {
+            bail!(
+                "Unable to write to the keyring: {}",
+                String::from_utf8_lossy(&output.stderr).trim()
+            )
+        }
+        Ok(())
+    }
+
+    #[cfg(target_os = "linux")]
+    fn remove(&self, account: &str) -> Result<()> {
+        let output = Command::new("secret-tool")
+            .args(["clear", "service", KEYRING_SERVICE, "account", account])
+            .output()
+            .context("Unable to run `secret-tool` to write to the keyring")?;
+        // `secret-tool` succeeds when the item does not exist
+        if !output.status.success() {
+            bail!(
+                "Unable to remove the secret from the keyring: {}",
+                String::from_utf8_lossy(&output.stderr).trim()
+            )
+        }
+        Ok(())
+    }
+
+    #[cfg(not(any(target_os = "macos", target_os = "linux")))]
+    fn get(&self, _account: &str) -> Result<Option<String>> {
+        bail!("The system keyring is not supported on this platform")
+    }
+
+    #[cfg(not(any(target_os = "macos", target_os = "linux")))]
+    fn set(&self, _account: &str, _secret: &str) -> Result<()> {
+        bail!("The system keyring is not supported on this platform")
+    }
+
+    #[cfg(not(any(target_os = "macos", target_os = "linux")))]
+    fn remove(&self, _account: &str) -> Result<()> {
+        bail!("The system keyring is not supported on this platform")
+    }
+}
+
+/// `arg` quoted as a single argument of a command read by `security -i`
+#[cfg(target_os = "macos")]
+fn quote(arg: &str) -> String {
+    format!("\"{}\"", arg.replace('\\', "\\\\").replace('"', "\\\""))
+}
@@ -1,4 +1,19 @@
 // Copyright (c) The Move Contributors
 // SPDX-License-Identifier: Apache-2.0
 
+pub mod artifact_version;
+pub mod coverage_exemptions;
+pub mod http_cache;
+pub mod keyring;
+pub mod log_file;
+pub mod move_home_cache;
 pub mod movey_credential;
+pub mod plugins;
+pub mod project_config;
+pub mod prompt;
+pub mod publish_log;
+pub mod registry_tls;
+pub mod stale_build;
+pub mod storage_lock;
+pub mod streaming_upload;
+pub mod toolchain;
@@ -0,0 +1,449 @@
+// Copyright (c) The Move Contributors
+// SPDX-License-Identifier: Apache-2.0
+
+//! The caches kept under `MOVE_HOME`: the dependencies downloaded by builds, and the responses of
+//! the registry. Nothing else under `MOVE_HOME`, such as the credential file, belongs to a cache,
+//! so nothing else is ever removed.
+
+use crate::utils::http_cache::HTTP_CACHE_DIR;
+use anyhow::{Context, Result};
+use move_command_line_common::env::move_home_write_error;
+use move_package::{
+    resolution::{
+        archive::ARCHIVE_DIGEST_FILE, lockfile::Lockfile,
+        resolution_graph::PARTIAL_DOWNLOAD_EXTENSION,
+    },
+    source_package::layout::SourcePackageLayout,
+    PackageLock,
+};
+use std::{
+    collections::BTreeSet,
+    fmt, fs,
+    path::{Path, PathBuf},
+    time::{Duration, SystemTime},
+};
+use walkdir::WalkDir;
+
+/// The caches under `MOVE_HOME`
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum CacheKind {
+    /// The git and node dependencies downloaded by builds, one directory each
+    Dependencies,
+    /// The responses of the registry, one file each
+    RegistryResponses,
+}
+
+impl CacheKind {
+    pub const ALL: [Self; 2] = [Self::Dependencies, Self::RegistryResponses];
+}
+
+impl fmt::Display for CacheKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Dependencies => write!(f, "dependencies"),
+            Self::RegistryResponses => write!(f, "registry responses"),
+        }
+    }
+}
+
+/// A downloaded dependency or a cached registry response
+#[derive(Debug, Clone)]
+pub struct CacheEntry {
+    pub kind: CacheKind,
+    pub path: PathBuf,
+    /// The total size of the files of the entry, in bytes
+    pub size: u64,
+    /// The last time a file of the entry was read or written. Directories are left out, as listing
+    /// them, e.g. to compute the size of the entry, counts as an access.
+    pub last_access: SystemTime,
+}
+
+/// What `gc` removes: the entries last accessed longer than `older_than` ago, then the least
+/// recently accessed entries until the caches fit in `max_size` bytes
+#[derive(Debug, Clone, Default)]
+pub struct GcPolicy {
+    pub max_size: Option<u64>,
+    pub older_than: Option<Duration>,
+}
+
+/// The result of `gc`
+#[derive(Debug, Default)]
+pub struct GcOutput {
+    /// The entries removed, or that would have been in a dry run, least recently accessed first
+    pub evicted: Vec<CacheEntry>,
+    /// The size of the caches left
+    pub remaining_size: u64,
+}
+
+/// The entries of the caches under `move_home`, in no particular order. A directory of `move_home`
+/// is a dependency if it holds a git checkout, an extracted archive or a package, or is a download
+/// left unfinished.
+pub fn cache_entries(move_home: &Path) -> Result<Vec<CacheEntry>> {
+    let mut entries = vec![];
+    if !move_home.exists() {
+        return Ok(entries);
+    }
+    for dir_entry in read_dir(move_home)? {
+        let path = dir_entry.path();
+        let file_name = dir_entry.file_name().to_string_lossy().to_string();
+        let is_dependency = path.is_dir()
+            && file_name != HTTP_CACHE_DIR
+            && (file_name.ends_with(PARTIAL_DOWNLOAD_EXTENSION)
+                || path.join(".git").exists()
+                || path.join(ARCHIVE_DIGEST_FILE).exists()
+                || path.join(SourcePackageLayout::Manifest.path()).exists());
+        if is_dependency {
+            entries.push(cache_entry(CacheKind::Dependencies, path)?);
+        }
+    }
+    let http_cache_dir = move_home.join(HTTP_CACHE_DIR);
+    if http_cache_dir.is_dir() {
+        for dir_entry in read_dir(&http_cache_dir)? {
+            let path = dir_entry.path();
+            if path.is_file() && path.extension().map_or(false, |ext| ext == "json") {
+                entries.push(cache_entry(CacheKind::RegistryResponses, path)?);
+            }
+        }
+    }
+    Ok(entries)
+}
+
+/// The directories under `move_home` of the dependencies recorded in the lockfiles found in
+/// `workspace_roots` and their subdirectories, leaving out hidden directories
+pub fn referenced_dependencies(
+    move_home: &str,
+    workspace_roots: &[PathBuf],
+) -> Result<BTreeSet<PathBuf>> {
+    let lockfile_name = SourcePackageLayout::Lockfile.path();
+    let mut referenced = BTreeSet::new();
+    for root in workspace_roots {
+        let walker = WalkDir::new(root).into_iter().filter_entry(|entry| {
+            entry.depth() == 0 || !entry.file_name().to_string_lossy().starts_with('.')
+        });
+        for entry in walker {
+            let entry = entry
+                .with_context(|| format!("Unable to search '{}' for lockfiles", root.display()))?;
+            if entry.file_type().is_dir() || entry.file_name() != lockfile_name {
+                continue;
+            }
+            if let Some(lockfile) = Lockfile::read(entry.path())? {
+                referenced.extend(
+                    lockfile
+                        .dependencies
+                        .iter()
+                        .filter_map(|dep| dep.download_dir(move_home)),
+                );
+            }
+        }
+    }
+    Ok(referenced)
+}
+
+/// The entries `policy` removes, least recently accessed first, given the time `now`. Dependencies
+/// in `referenced` are never removed, but count in the size of the caches.
+pub fn entries_to_evict(
+    entries: &[CacheEntry],
+    referenced: &BTreeSet<PathBuf>,
+    policy: &GcPolicy,
+    now: SystemTime,
+) -> Vec<CacheEntry> {
+    let mut candidates: Vec<_> = entries
+        .iter()
+        .filter(|entry| !referenced.contains(&entry.path))
+        .cloned()
+        .collect();
+    candidates.sort_by_key(|entry| entry.last_access);
+    let mut size: u64 = entries.iter().map(|entry| entry.size).sum();
+    let mut evicted = vec![];
+    for entry in candidates {
+        let too_old = policy.older_than.map_or(false, |older_than| {
+            now.duration_since(entry.last_access).unwrap_or_default() > older_than
+        });
+        let too_big = policy.max_size.map_or(false, |max_size| size > max_size);
+        if too_old || too_big {
+            size -= entry.size;
+            evicted.push(entry);
+        }
+    }
+    evicted
+}
+
+/// Remove the entries of the caches under `move_home` that `policy` selects, keeping the
+/// dependencies recorded in the lockfiles found in `workspace_roots`. The package lock is held
+/// meanwhile, so that no build downloads or reads a dependency while it is removed.
+pub fn gc(
+    move_home: &str,
+    policy: &GcPolicy,
+    workspace_roots: &[PathBuf],
+    dry_run: bool,
+) -> Result<GcOutput> {
+    let referenced = referenced_dependencies(move_home, workspace_roots)?;
+    let lock = PackageLock::lock();
+    let result = cache_entries(Path::new(move_home)).and_then(|entries| {
+        let evicted = entries_to_evict(&entries, &referenced, policy, SystemTime::now());
+        if !dry_run {
+            for entry in &evicted {
+                remove(entry)?;
+            }
+        }
+        let evicted_size: u64 = evicted.iter().map(|entry| entry.size).sum();
+        Ok(GcOutput {
+            remaining_size: entries.iter().map(|entry| entry.size).sum::<u64>() - evicted_size,
+            evicted,
+        })
+    });
+    lock.unlock();
+    result
+}
+
+fn remove(entry: &CacheEntry) -> Result<()> {
+    let result = match entry.kind {
+        CacheKind::Dependencies => fs::remove_dir_all(&entry.path),
+        CacheKind::RegistryResponses => fs::remove_file(&entry.path),
+    };
+    result.map_err(|err| move_home_write_error("remove", &entry.path, err))
+}
+
+fn read_dir(dir: &Path) -> Result<Vec<fs::DirEntry>> {
+    fs::read_dir(dir)
+        .and_then(|entries| entries.collect::<std::io::Result<Vec<_>>>())
+        .with_context(|| format!("Unable to read '{}'", dir.display()))
+}
+
+fn cache_entry(kind: CacheKind, path: PathBuf) -> Result<CacheEntry> {
+    let mut size = 0;
+    let mut last_access = None;
+    for file in WalkDir::new(&path) {
+        let file = file.with_context(|| format!("Unable to read '{}'", path.display()))?;
+        if file.file_type().is_dir() {
+            continue;
+        }
+        let metadata = file
+            .metadata()
+            .with_context(|| format!("Unable to read '{}'", file.path().display()))?;
+        size += metadata.len();
+        let modified = metadata.modified()?;
+        let accessed = metadata.accessed().unwrap_or(modified);
+        last_access = last_access.max(Some(accessed.max(modified)));
+    }
+    let last_access = match last_access {
+        Some(last_access) => last_access,
+        // an empty directory
+        None => fs::metadata(&path)?.modified()?,
+    };
+    Ok(CacheEntry {
+        kind,
+        path,
+        size,
+        last_access,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::process::Command;
+
+    const DAY: Duration = Duration::from_secs(24 * 60 * 60);
+
+    /// Set the access and modification times of the files under `path` to midnight on January 1st
+    /// of `year`
+    fn make_old(path: &Path, year: u32) {
+        for entry in WalkDir::new(path) {
+            let status = Command::new("touch")
+                .args(["-t", &format!("{}01010000", year)])
+                .arg(entry.unwrap().path())
+                .status()
+                .unwrap();
+            assert!(status.success());
+        }
+    }
+
+    fn add_dependency(move_home: &Path, name: &str, size: usize) -> PathBuf {
+        let path = move_home.join(name);
+        fs::create_dir_all(path.join(".git")).unwrap();
+        fs::write(path.join("Move.toml"), vec![b'#'; size]).unwrap();
+        path
+    }
+
+    fn add_response(move_home: &Path, name: &str, size: usize) -> PathBuf {
+        let path = move_home
+            .join(HTTP_CACHE_DIR)
+            .join(format!("{}.json", name));
+        fs::create_dir_all(path.parent().unwrap()).unwrap();
+        fs::write(&path, vec![b' '; size]).unwrap();
+        path
+    }
+
+    /// A `MOVE_HOME` holding an old and a new dependency, an older and a new registry response, and
+    /// an old credential file
+    fn populate(move_home: &Path) -> [PathBuf; 4] {
+        let old_dependency = add_dependency(move_home, "https___example_com_old_git_v1", 1000);
+        let new_dependency = add_dependency(move_home, "https___example_com_new_git_v1", 2000);
+        let old_response = add_response(move_home, "old", 100);
+        let new_response = add_response(move_home, "new", 200);
+        make_old(&old_dependency, 2020);
+        make_old(&old_response, 2019);
+        fs::write(move_home.join("credential.toml"), "[registry]\n").unwrap();
+        make_old(&move_home.join("credential.toml"), 2019);
+        [old_dependency, new_dependency, old_response, new_response]
+    }
+
+    fn evicted_paths(output: &GcOutput) -> Vec<PathBuf> {
+        output
+            .evicted
+            .iter()
+            .map(|entry| entry.path.clone())
+            .collect()
+    }
+
+    #[test]
+    fn cache_entries_leave_out_other_files() {
+        let move_home = tempfile::tempdir().unwrap();
+        let [old_dependency, new_dependency, old_response, new_response] =
+            populate(move_home.path());
+        fs::create_dir_all(move_home.path().join("certs")).unwrap();
+        fs::create_dir_all(
+            move_home
+                .path()
+                .join("https___example_com_git_main.partial"),
+        )
+        .unwrap();
+
+        let mut entries = cache_entries(move_home.path()).unwrap();
+        entries.sort_by_key(|entry| (entry.kind, entry.path.clone()));
+        let summary: Vec<_> = entries
+            .iter()
+            .map(|entry| (entry.kind, entry.path.clone(), entry.size))
+            .collect();
+        assert_eq!(
+            summary,
+            vec![
+                (
+                    CacheKind::Dependencies,
+                    move_home
+                        .path()
+                        .join("https___example_com_git_main.partial"),
+                    0
+                ),
+                (CacheKind::Dependencies, new_dependency, 2000),
+                (CacheKind::Dependencies, old_dependency, 1000),
+                (CacheKind::RegistryResponses, new_response, 200),
+                (CacheKind::RegistryResponses, old_response, 100),
+            ]
+        );
+    }
+
+    #[test]
+    fn gc_older_than_removes_old_entries() {
+        let move_home = tempfile::tempdir().unwrap();
+        let [old_dependency, new_dependency, old_response, new_response] =
+            populate(move_home.path());
+        let policy = GcPolicy {
+            older_than: Some(30 * DAY),
+            ..GcPolicy::default()
+        };
+
+        let output = gc(&move_home.path().to_string_lossy(), &policy, &[], false).unwrap();
+        assert_eq!(
+            evicted_paths(&output),
+            vec![old_response.clone(), old_dependency.clone()]
+        );
+        assert_eq!(output.remaining_size, 2200);
+        assert!(!old_dependency.exists());
+        assert!(!old_response.exists());
+        assert!(new_dependency.exists());
+        assert!(new_response.exists());
+        assert!(move_home.path().join("credential.toml").exists());
+    }
+
+    #[test]
+    fn gc_max_size_removes_least_recently_accessed_entries() {
+        let move_home = tempfile::tempdir().unwrap();
+        let [old_dependency, new_dependency, old_response, new_response] =
+            populate(move_home.path());
+        let policy = GcPolicy {
+            max_size: Some(2500),
+            ..GcPolicy::default()
+        };
+
+        let output = gc(&move_home.path().to_string_lossy(), &policy, &[], false).unwrap();
+        // the older response goes first, and is not enough to fit
+        assert_eq!(evicted_paths(&output), vec![old_response, old_dependency]);
+        assert_eq!(output.remaining_size, 2200);
+        assert!(new_dependency.exists());
+        assert!(new_response.exists());
+
+        let policy = GcPolicy {
+            max_size: Some(2200),
+            ..GcPolicy::default()
+        };
+        let output = gc(&move_home.path().to_string_lossy(), &policy, &[], false).unwrap();
+        assert!(output.evicted.is_empty());
+    }
+
+    #[test]
+    fn gc_dry_run_removes_nothing() {
+        let move_home = tempfile::tempdir().unwrap();
+        let [old_dependency, _, old_response, _] = populate(move_home.path());
+        let policy = GcPolicy {
+            max_size: Some(0),
+            ..GcPolicy::default()
+        };
+
+        let output = gc(&move_home.path().to_string_lossy(), &policy, &[], true).unwrap();
+        assert_eq!(output.evicted.len(), 4);
+        assert_eq!(output.remaining_size, 0);
+        assert!(old_dependency.exists());
+        assert!(old_response.exists());
+    }
+
+    #[test]
+    fn gc_keeps_dependencies_referenced_by_lockfiles() {
+        let move_home = tempfile::tempdir().unwrap();
+        let move_home_str = move_home.path().to_string_lossy().to_string();
+        let [old_dependency, new_dependency, old_response, _] = populate(move_home.path());
+        let workspace = tempfile::tempdir().unwrap();
+        let package = workspace.path().join("packages").join("Old");
+        fs::create_dir_all(&package).unwrap();
+        fs::write(
+            package.join("Move.lock"),
+            r#"
+[[dependency]]
+name = "Old"
+git = "https://example.com/old.git"
+rev = "v1"
+"#,
+        )
+        .unwrap();
+        // lockfiles in hidden directories are left out
+        let hidden = workspace.path().join(".cache").join("New");
+        fs::create_dir_all(&hidden).unwrap();
+        fs::write(
+            hidden.join("Move.lock"),
+            r#"
+[[dependency]]
+name = "New"
+git = "https://example.com/new.git"
+rev = "v1"
+"#,
+        )
+        .unwrap();
+
+        let roots = [workspace.path().to_path_buf()];
+        assert_eq!(
+            referenced_dependencies(&move_home_str, &roots).unwrap(),
+            BTreeSet::from([old_dependency.clone()])
+        );
+        let policy = GcPolicy {
+            max_size: Some(0),
+            ..GcPolicy::default()
+        };
+        let output = gc(&move_home_str, &policy, &roots, false).unwrap();
+        assert_eq!(output.evicted.len(), 3);
+        assert_eq!(output.remaining_size, 1000);
+        assert!(old_dependency.exists());
+        assert!(!new_dependency.exists());
+        assert!(!old_response.exists());
+    }
+}
@@ -1,32 +1,107 @@
 // Copyright (c) The Move Contributors
 // SPDX-License-Identifier: Apache-2.0
 
+use super::{keyring::Keyring, prompt};
 use anyhow::{bail, Context, Result};
-use move_command_line_common::movey_constants::{MOVEY_CREDENTIAL_PATH, MOVEY_URL};
-use std::fs;
-use toml_edit::easy::Value;
+use move_command_line_common::{
+    files::{utf8_file_contents, InvalidUtf8File},
+    json_errors,
+    movey_constants::{MOVEY_CREDENTIAL_PATH, MOVEY_URL},
+};
+use std::{
+    fs,
+    path::{Path, PathBuf},
+};
+use toml_edit::{easy::Value, Document};
 
+/// The environment variable holding the API token, which takes precedence over the credential
+/// file, e.g. to upload from CI without writing the token to disk
+pub const MOVEY_API_TOKEN_ENV_VAR: &str = "MOVEY_API_TOKEN";
+
+/// The value of the `token_storage` field of the `[registry]` table that keeps the API token in
+/// the system keyring instead of the credential file
+pub const KEYRING_TOKEN_STORAGE: &str = "keyring";
+
+/// What to do with a plaintext API token left in the credential file when the token is configured
+/// to be stored in the keyring
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CredentialMigration {
+    /// Move the token to the keyring, or remove it if the keyring already holds one
+    Migrate,
+    /// Ask the user whether to migrate the token
+    Ask,
+    /// Leave the credential file as it is
+    Skip,
+}
+
+impl CredentialMigration {
+    /// `Migrate` if `--migrate-credentials` is set, otherwise `Ask` if stdin is a terminal, or
+    /// `Skip` if there is no one to ask
+    pub fn from_flag(migrate_credentials: bool) -> Self {
+        if migrate_credentials {
+            CredentialMigration::Migrate
+        } else if atty::is(atty::Stream::Stdin) {
+            CredentialMigration::Ask
+        } else {
+            CredentialMigration::Skip
+        }
+    }
+
+    fn confirm(self, question: &str) -> bool {
+        match self {
+            CredentialMigration::Migrate => true,
+            CredentialMigration::Ask => prompt::confirm(question, false).is_ok(),
+            CredentialMigration::Skip => false,
+        }
+    }
+}
+
+/// The API token set in `MOVEY_API_TOKEN`, or else the one of the credential file in `move_home`
 pub fn get_registry_api_token(move_home: &str) -> Result<String> {
+    if let Some(token) = get_env_api_token() {
+        return Ok(token);
+    }
     if let Ok(content) = get_api_token(move_home) {
         Ok(content)
     } else {
         bail!(
-            "There seems to be an error with your Movey API token. \
-            Please run `move movey-login` and follow the instructions."
+            "There seems to be an error with your Movey API token. Set the {} environment \
+             variable to the token, which takes precedence, or run `move movey-login` and follow \
+             the instructions to save it to {}.",
+            MOVEY_API_TOKEN_ENV_VAR,
+            credential_path(move_home)
         )
     }
 }
 
+/// The API token set in `MOVEY_API_TOKEN`, if it is set and not empty
+pub fn get_env_api_token() -> Option<String> {
+    std::env::var(MOVEY_API_TOKEN_ENV_VAR)
+        .ok()
+        .map(|token| token.trim().to_string())
+        .filter(|token| !token.is_empty())
+}
+
+/// The path of the Movey credential file kept in `move_home`
+pub fn credential_path(move_home: &str) -> String {
+    format!("{}{}", move_home, MOVEY_CREDENTIAL_PATH)
+}
+
 pub fn get_api_token(move_home: &str) -> Result<String> {
-    let credential_path = format!("{}{}", move_home, MOVEY_CREDENTIAL_PATH);
+    let credential_path = credential_path(move_home);
     let mut toml: Value = read_credential_file(&credential_path)?;
     let token = get_registry_field(&mut toml, "token")?;
     Ok(token.to_string().replace('\"', ""))
 }
 
+/// The URL of the registry, from the `url` field of the `[registry]` table, or Movey's if it is
+/// not set or there is no credential file
 pub fn get_movey_url(move_home: &str) -> Result<String> {
-    let credential_path = format!("{}{}", move_home, MOVEY_CREDENTIAL_PATH);
-    let contents = fs::read_to_string(&credential_path)?;
+    let credential_path = credential_path(move_home);
+    if !Path::new(&credential_path).exists() {
+        return Ok(MOVEY_URL.to_string());
+    }
+    let contents = read_credential_contents(&credential_path)?;
     let mut toml: Value = contents.parse()?;
 
     let movey_url = get_registry_field(&mut toml, "url");
@@ -37,6 +112,155 @@ pub fn get_movey_url(move_home: &str) -> Result<String> {
     }
 }
 
+/// The number of seconds registry responses are cached for when they do not set their own freshness
+/// lifetime, from the `cache_ttl` field of the `[registry]` table. `None` if it is not set.
+pub fn get_cache_ttl(move_home: &str) -> Result<Option<u64>> {
+    let credential_path = credential_path(move_home);
+    let contents = match read_credential_contents(&credential_path) {
+        Ok(contents) => contents,
+        Err(err) if err.is::<InvalidUtf8File>() => return Err(err),
+        Err(_) => return Ok(None),
+    };
+    let mut toml: Value = contents.parse()?;
+    let cache_ttl = match get_registry_field(&mut toml, "cache_ttl") {
+        Ok(cache_ttl) => cache_ttl,
+        Err(_) => return Ok(None),
+    };
+    match cache_ttl.as_integer() {
+        Some(seconds) if seconds >= 0 => Ok(Some(seconds as u64)),
+        _ => bail!(
+            "Error parsing cache_ttl in {}: expected a number of seconds",
+            MOVEY_CREDENTIAL_PATH
+        ),
+    }
+}
+
+/// The registry directory set by the `path` field of the `[registry]` table, in place of a `url`.
+/// A relative path is relative to `move_home`. `None` if it is not set.
+pub fn get_registry_path(move_home: &str) -> Result<Option<PathBuf>> {
+    let credential_path = credential_path(move_home);
+    let contents = match read_credential_contents(&credential_path) {
+        Ok(contents) => contents,
+        Err(err) if err.is::<InvalidUtf8File>() => return Err(err),
+        Err(_) => return Ok(None),
+    };
+    let mut toml: Value = contents.parse()?;
+    let path = match get_registry_field(&mut toml, "path") {
+        Ok(path) => path,
+        Err(_) => return Ok(None),
+    };
+    match path.as_str() {
+        Some(path) => Ok(Some(Path::new(move_home).join(path))),
+        None => bail!(
+            "Error parsing path in {}: expected the path of a registry directory",
+            MOVEY_CREDENTIAL_PATH
+        ),
+    }
+}
+
+/// Whether the `[registry]` table sets `token_storage = "keyring"`, to keep the API token in the
+/// system keyring instead of the credential file
+pub fn uses_keyring(move_home: &str) -> bool {
+    let contents = match read_credential_contents(&credential_path(move_home)) {
+        Ok(contents) => contents,
+        Err(_) => return false,
+    };
+    // a malformed file is reported by the readers of the token
+    let mut toml: Value = match contents.parse() {
+        Ok(toml) => toml,
+        Err(_) => return false,
+    };
+    match get_registry_field(&mut toml, "token_storage") {
+        Ok(storage) => storage.as_str() == Some(KEYRING_TOKEN_STORAGE),
+        Err(_) => false,
+    }
+}
+
+/// The API token to use: the one set in `MOVEY_API_TOKEN`, as is, or else the one found after
+/// reconciling the credential file with `keyring`. Without keyring
+/// storage, this is the token of the credential file. With keyring storage, the token of the
+/// keyring is preferred: a plaintext token left in the credential file is removed, or moved to
+/// the keyring if the keyring holds none, as `migration` allows. The other keys of the credential
+/// file are kept.
+pub fn reconcile_api_token(
+    move_home: &str,
+    keyring: &dyn Keyring,
+    migration: CredentialMigration,
+) -> Result<String> {
+    if let Some(token) = get_env_api_token() {
+        return Ok(token);
+    }
+    if !uses_keyring(move_home) {
+        return get_registry_api_token(move_home);
+    }
+    let credential_path = credential_path(move_home);
+    let account = get_movey_url(move_home)?;
+    let plaintext = get_api_token(move_home)
+        .ok()
+        .filter(|token| !token.is_empty());
+    match (keyring.get(&account)?, plaintext) {
+        (Some(token), None) => Ok(token),
+        (Some(token), Some(_)) => {
+            json_errors::warning(format!(
+                "{} still holds a plaintext API token, although the token is stored in \
+                 the system keyring",
+                credential_path
+            ));
+            if migration.confirm(&format!(
+                "Remove the plaintext token from {}?",
+                credential_path
+            )) {
+                remove_plaintext_token(&credential_path)?;
+                eprintln!("Removed the plaintext API token from {}", credential_path);
+            } else {
+                eprintln!("Run with --migrate-credentials to remove it");
+            }
+            Ok(token)
+        }
+        (None, Some(token)) => {
+            json_errors::warning(format!(
+                "{} holds a plaintext API token, although the token is configured to be \
+                 stored in the system keyring",
+                credential_path
+            ));
+            if migration.confirm("Move the token to the system keyring?") {
+                keyring.set(&account, &token)?;
+                remove_plaintext_token(&credential_path)?;
+                eprintln!("Moved the API token to the system keyring");
+            } else {
+                eprintln!("Run with --migrate-credentials to move it");
+            }
+            Ok(token)
+        }
+        (None, None) => bail!(
+            "The Movey API token is configured to be stored in the system keyring, but the keyring \
+             holds no token for {}. Run `move movey-login` to save it again, or remove \
+             `token_storage = \"{}\"` from the [registry] table of {} to keep the token in that \
+             file instead",
+            account,
+            KEYRING_TOKEN_STORAGE,
+            credential_path
+        ),
+    }
+}
+
+/// Remove the `token` field of the `[registry]` table of the credential file, keeping the rest of
+/// the file, comments included, as it is
+fn remove_plaintext_token(credential_path: &str) -> Result<()> {
+    let contents = read_credential_contents(credential_path)?;
+    let mut document: Document = contents
+        .parse()
+        .with_context(|| format!("could not parse input at {} as TOML", credential_path))?;
+    if let Some(registry) = document
+        .get_mut("registry")
+        .and_then(|registry| registry.as_table_like_mut())
+    {
+        registry.remove("token");
+    }
+    fs::write(credential_path, document.to_string())
+        .with_context(|| format!("Unable to write {}", credential_path))
+}
+
 fn get_registry_field<'a>(toml: &'a mut Value, field: &'a str) -> Result<&'a mut Value> {
     let registry = toml
         .as_table_mut()
@@ -51,11 +275,22 @@ fn get_registry_field<'a>(toml: &'a mut Value, field: &'a str) -> Result<&'a mut
     Ok(value)
 }
 
+/// The contents of the credential file, without the byte order mark it may start with. A file that
+/// is not valid UTF-8 is reported as corrupted, with how to replace it.
+pub fn read_credential_contents(credential_path: &str) -> Result<String> {
+    let bytes = fs::read(credential_path)
+        .with_context(|| format!("Unable to read the credential file '{}'", credential_path))?;
+    utf8_file_contents(Path::new(credential_path), bytes).map_err(|err| {
+        anyhow::Error::from(err).context(format!(
+            "The credential file is corrupted. Delete '{}' and run `move movey-login` to save \
+             the API token again",
+            credential_path
+        ))
+    })
+}
+
 pub fn read_credential_file(credential_path: &str) -> Result<Value> {
-    let content = match fs::read_to_string(&credential_path) {
-        Ok(content) => content,
-        Err(error) => bail!("Error reading input: {}", error),
-    };
+    let content = read_credential_contents(credential_path)?;
     content.parse().map_err(|e| {
         anyhow::Error::from(e).context(format!(
             "could not parse input at {} as TOML",
@@ -67,25 +302,45 @@ pub fn read_credential_file(credential_path: &str) -> Result<Value> {
 #[cfg(test)]
 mod tests {
     use super::*;
-    use std::{env, fs::File};
+    use std::{cell::RefCell, collections::BTreeMap, fs::File};
+    use tempfile::TempDir;
+
+    /// A keyring kept in memory
+    #[derive(Default)]
+    struct MockKeyring {
+        secrets: RefCell<BTreeMap<String, String>>,
+    }
 
-    fn setup_move_home(test_path: &str) -> (String, String) {
-        let cwd = env::current_dir().unwrap();
-        let mut move_home: String = String::from(cwd.to_string_lossy());
-        move_home.push_str(test_path);
-        let credential_path = move_home.clone() + MOVEY_CREDENTIAL_PATH;
+    impl Keyring for MockKeyring {
+        fn get(&self, account: &str) -> Result<Option<String>> {
+            Ok(self.secrets.borrow().get(account).cloned())
+        }
 
-        (move_home, credential_path)
+        fn set(&self, account: &str, secret: &str) -> Result<()> {
+            self.secrets
+                .borrow_mut()
+                .insert(account.to_string(), secret.to_string());
+            Ok(())
+        }
+
+        fn remove(&self, account: &str) -> Result<()> {
+            self.secrets.borrow_mut().remove(account);
+            Ok(())
+        }
     }
 
-    fn clean_up(move_home: &str) {
-        let _ = fs::remove_dir_all(move_home);
+    /// A `MOVE_HOME` that does not exist yet inside a temporary directory, which is removed when
+    /// the returned `TempDir` is dropped
+    fn setup_move_home() -> (TempDir, String, String) {
+        let dir = tempfile::tempdir().unwrap();
+        let move_home = dir.path().join("move_home").to_string_lossy().to_string();
+        let credential_path = credential_path(&move_home);
+        (dir, move_home, credential_path)
     }
 
     #[test]
     fn get_api_token_works() {
-        let test_path = String::from("/get_api_token_works");
-        let (move_home, credential_path) = setup_move_home(&test_path);
+        let (_dir, move_home, credential_path) = setup_move_home();
         let _ = fs::create_dir_all(&move_home);
         File::create(&credential_path).unwrap();
 
@@ -97,39 +352,60 @@ mod tests {
 
         let token = get_registry_api_token(&move_home).unwrap();
         assert!(token.contains("test-token"));
+    }
+
+    #[test]
+    fn credential_files_starting_with_a_bom_are_read() {
+        let (_dir, move_home, credential_path) = setup_move_home();
+        fs::create_dir_all(&move_home).unwrap();
+        let content = "\u{feff}[registry]\ntoken = \"test-token\"\nurl = \"https://example.com\"\n";
+        fs::write(&credential_path, content).unwrap();
 
-        clean_up(&move_home)
+        assert_eq!(get_api_token(&move_home).unwrap(), "test-token");
+        assert_eq!(get_movey_url(&move_home).unwrap(), "https://example.com");
+    }
+
+    #[test]
+    fn corrupted_credential_files_are_reported_with_how_to_replace_them() {
+        let (_dir, move_home, credential_path) = setup_move_home();
+        fs::create_dir_all(&move_home).unwrap();
+        // the token ends with the first two bytes of a three-byte sequence
+        fs::write(&credential_path, b"[registry]\ntoken = \"\xe2\x82\"\n").unwrap();
+
+        let error = format!("{:#}", get_api_token(&move_home).unwrap_err());
+        assert!(
+            error.contains(&format!(
+                "The credential file is corrupted. Delete '{}' and run `move movey-login`",
+                credential_path
+            )) && error.contains("invalid byte sequence at byte offset 20"),
+            "{}",
+            error
+        );
+        assert!(get_cache_ttl(&move_home).is_err());
     }
 
     #[test]
     fn get_api_token_fails_if_there_is_no_move_home_directory() {
-        let test_path = String::from("/get_api_token_fails_if_there_is_no_move_home_directory");
-        let (move_home, _) = setup_move_home(&test_path);
+        let (_dir, move_home, _) = setup_move_home();
         let _ = fs::remove_dir_all(&move_home);
 
         let token = get_registry_api_token(&move_home);
         assert!(token.is_err());
-
-        clean_up(&move_home)
     }
 
     #[test]
     fn get_api_token_fails_if_there_is_no_credential_file() {
-        let test_path = String::from("/get_api_token_fails_if_there_is_no_credential_file");
-        let (move_home, _) = setup_move_home(&test_path);
+        let (_dir, move_home, _) = setup_move_home();
         let _ = fs::remove_dir_all(&move_home);
         fs::create_dir_all(&move_home).unwrap();
 
         let token = get_registry_api_token(&move_home);
         assert!(token.is_err());
-
-        clean_up(&move_home)
     }
 
     #[test]
     fn get_api_token_fails_if_credential_file_is_in_wrong_format() {
-        let test_path = String::from("/get_api_token_fails_if_credential_file_is_in_wrong_format");
-        let (move_home, credential_path) = setup_move_home(&test_path);
+        let (_dir, move_home, credential_path) = setup_move_home();
         let _ = fs::remove_dir_all(&move_home);
         fs::create_dir_all(&move_home).unwrap();
         File::create(&credential_path).unwrap();
@@ -149,14 +425,11 @@ mod tests {
         fs::write(&credential_path, wrong_token_field).unwrap();
         let token = get_registry_api_token(&move_home);
         assert!(token.is_err());
-
-        clean_up(&move_home)
     }
 
     #[test]
     fn get_movey_url_works() {
-        let test_path = String::from("/get_movey_url_works");
-        let (move_home, credential_path) = setup_move_home(&test_path);
+        let (_dir, move_home, credential_path) = setup_move_home();
         let _ = fs::create_dir_all(&move_home);
         File::create(&credential_path).unwrap();
         let content = r#"
@@ -168,14 +441,11 @@ mod tests {
 
         let url = get_movey_url(&move_home).unwrap();
         assert_eq!(url, "test-url");
-
-        clean_up(&move_home)
     }
 
     #[test]
     fn get_movey_url_returns_default_url_if_url_field_not_existed() {
-        let test_path = String::from("/get_movey_url_returns_default_url_if_url_field_not_existed");
-        let (move_home, credential_path) = setup_move_home(&test_path);
+        let (_dir, move_home, credential_path) = setup_move_home();
         let _ = fs::create_dir_all(&move_home);
         File::create(&credential_path).unwrap();
         let content = r#"
@@ -186,7 +456,167 @@ mod tests {
 
         let url = get_movey_url(&move_home).unwrap();
         assert_eq!(url, MOVEY_URL);
+    }
+
+    #[test]
+    fn get_movey_url_returns_default_url_if_there_is_no_credential_file() {
+        let (_dir, move_home, _) = setup_move_home();
+        assert_eq!(get_movey_url(&move_home).unwrap(), MOVEY_URL);
+    }
+
+    #[test]
+    fn get_registry_path_is_relative_to_move_home() {
+        let (_dir, move_home, credential_path) = setup_move_home();
+        assert_eq!(get_registry_path(&move_home).unwrap(), None);
+        fs::create_dir_all(&move_home).unwrap();
+        fs::write(&credential_path, "[registry]\npath = \"registry\"\n").unwrap();
+        assert_eq!(
+            get_registry_path(&move_home).unwrap(),
+            Some(Path::new(&move_home).join("registry"))
+        );
+        fs::write(&credential_path, "[registry]\npath = \"/srv/registry\"\n").unwrap();
+        assert_eq!(
+            get_registry_path(&move_home).unwrap(),
+            Some(PathBuf::from("/srv/registry"))
+        );
+        fs::write(&credential_path, "[registry]\npath = 1\n").unwrap();
+        assert!(get_registry_path(&move_home).is_err());
+    }
+
+    #[test]
+    fn get_cache_ttl_works() {
+        let (_dir, move_home, credential_path) = setup_move_home();
+        let _ = fs::create_dir_all(&move_home);
+        assert_eq!(get_cache_ttl(&move_home).unwrap(), None);
+
+        let content = r#"
+            [registry]
+            token = "test-token"
+            "#;
+        fs::write(&credential_path, content).unwrap();
+        assert_eq!(get_cache_ttl(&move_home).unwrap(), None);
+
+        let content = r#"
+            [registry]
+            cache_ttl = 60
+            "#;
+        fs::write(&credential_path, content).unwrap();
+        assert_eq!(get_cache_ttl(&move_home).unwrap(), Some(60));
+
+        let content = r#"
+            [registry]
+            cache_ttl = "1 minute"
+            "#;
+        fs::write(&credential_path, content).unwrap();
+        assert!(get_cache_ttl(&move_home).is_err());
+    }
+
+    const KEYRING_CREDENTIAL: &str = r#"# keep the token out of this file
+[registry]
+token = "plaintext-token"
+token_storage = "keyring"
+url = "test-url"
+cache_ttl = 60
+
+[other]
+key = "value"
+"#;
+
+    #[test]
+    fn reconcile_api_token_prefers_the_keyring_and_removes_a_stale_plaintext_token() {
+        let (_dir, move_home, credential_path) = setup_move_home();
+        fs::create_dir_all(&move_home).unwrap();
+        fs::write(&credential_path, KEYRING_CREDENTIAL).unwrap();
+        let keyring = MockKeyring::default();
+        keyring.set("test-url", "keyring-token").unwrap();
+
+        let token = reconcile_api_token(&move_home, &keyring, CredentialMigration::Skip).unwrap();
+        assert_eq!(token, "keyring-token");
+        assert_eq!(
+            fs::read_to_string(&credential_path).unwrap(),
+            KEYRING_CREDENTIAL
+        );
+
+        let token =
+            reconcile_api_token(&move_home, &keyring, CredentialMigration::Migrate).unwrap();
+        assert_eq!(token, "keyring-token");
+        assert_eq!(
+            fs::read_to_string(&credential_path).unwrap(),
+            KEYRING_CREDENTIAL.replace("token = \"plaintext-token\"\n", "")
+        );
+        assert_eq!(get_cache_ttl(&move_home).unwrap(), Some(60));
+    }
+
+    #[test]
+    fn reconcile_api_token_moves_a_plaintext_token_to_the_keyring() {
+        let (_dir, move_home, credential_path) = setup_move_home();
+        fs::create_dir_all(&move_home).unwrap();
+        fs::write(&credential_path, KEYRING_CREDENTIAL).unwrap();
+        let keyring = MockKeyring::default();
+
+        let token = reconcile_api_token(&move_home, &keyring, CredentialMigration::Skip).unwrap();
+        assert_eq!(token, "plaintext-token");
+        assert_eq!(keyring.get("test-url").unwrap(), None);
+        assert_eq!(
+            fs::read_to_string(&credential_path).unwrap(),
+            KEYRING_CREDENTIAL
+        );
+
+        let token =
+            reconcile_api_token(&move_home, &keyring, CredentialMigration::Migrate).unwrap();
+        assert_eq!(token, "plaintext-token");
+        assert_eq!(
+            keyring.get("test-url").unwrap().as_deref(),
+            Some("plaintext-token")
+        );
+        assert_eq!(
+            fs::read_to_string(&credential_path).unwrap(),
+            KEYRING_CREDENTIAL.replace("token = \"plaintext-token\"\n", "")
+        );
+
+        // the token is now read from the keyring
+        let token = reconcile_api_token(&move_home, &keyring, CredentialMigration::Skip).unwrap();
+        assert_eq!(token, "plaintext-token");
+    }
+
+    #[test]
+    fn reconcile_api_token_fails_if_the_keyring_entry_is_missing() {
+        let (_dir, move_home, credential_path) = setup_move_home();
+        fs::create_dir_all(&move_home).unwrap();
+        let content = r#"
+            [registry]
+            token_storage = "keyring"
+            url = "test-url"
+            "#;
+        fs::write(&credential_path, content).unwrap();
+
+        let err = reconcile_api_token(
+            &move_home,
+            &MockKeyring::default(),
+            CredentialMigration::Migrate,
+        )
+        .unwrap_err()
+        .to_string();
+        assert!(err.contains("holds no token for test-url"), "{}", err);
+        assert!(err.contains("move movey-login"), "{}", err);
+        assert_eq!(fs::read_to_string(&credential_path).unwrap(), content);
+    }
+
+    #[test]
+    fn reconcile_api_token_leaves_the_keyring_alone_without_keyring_storage() {
+        let (_dir, move_home, credential_path) = setup_move_home();
+        fs::create_dir_all(&move_home).unwrap();
+        let content = r#"
+            [registry]
+            token = "test-token"
+            "#;
+        fs::write(&credential_path, content).unwrap();
+        let keyring = MockKeyring::default();
+        keyring.set(MOVEY_URL, "keyring-token").unwrap();
 
-        clean_up(&move_home)
+        let token =
+            reconcile_api_token(&move_home, &keyring, CredentialMigration::Migrate).unwrap();
+        assert_eq!(token, "test-token");
+        assert_eq!(fs::read_to_string(&credential_path).unwrap(), content);
     }
 }
@@ -0,0 +1,149 @@
+// Copyright (c) The Move Contributors
+// SPDX-License-Identifier: Apache-2.0
+
+//! Subcommands implemented by `move-<name>` executables on the `PATH`, in the way cargo runs
+//! `cargo-<name>` for `cargo <name>`
+
+use crate::{base::package_root, Move};
+use anyhow::{bail, Context, Result};
+use move_command_line_common::{
+    env::{move_home, MOVE_HOME_ENV_VAR},
+    experiments::{enabled_experiments, MOVE_EXPERIMENTS_ENV_VAR},
+};
+use std::{
+    collections::BTreeMap,
+    env,
+    ffi::OsString,
+    fs,
+    path::{Path, PathBuf},
+    process::Command,
+};
+
+/// Prefix of the names of the executables implementing plugin subcommands
+pub const PLUGIN_PREFIX: &str = "move-";
+
+/// Set for plugins to the root of the package the command runs in, if it runs in a package
+pub const MOVE_PACKAGE_PATH_ENV_VAR: &str = "MOVE_PACKAGE_PATH";
+
+/// Set for plugins to the version of the CLI running them
+pub const MOVE_CLI_VERSION_ENV_VAR: &str = "MOVE_CLI_VERSION";
+
+/// The plugins being run by the CLI processes up the process tree, separated by `:`. A plugin
+/// running `move <name>` for itself would otherwise run forever.
+const MOVE_RUNNING_PLUGINS_ENV_VAR: &str = "MOVE_RUNNING_PLUGINS";
+
+/// Run the plugin subcommand `args[0]` with the rest of `args`, and exit with its exit code
+pub fn run_plugin(args: &[String], move_args: &Move) -> Result<()> {
+    let (name, args) = match args.split_first() {
+        Some(split) => split,
+        None => bail!("No command given"),
+    };
+    let plugin_path = match find_plugin(name) {
+        Some(plugin_path) => plugin_path,
+        None => bail!(
+            "No such command: `{}`. Run `move --list` to see the available commands",
+            name
+        ),
+    };
+    if let Ok(current_exe) = env::current_exe() {
+        if same_file(&current_exe, &plugin_path) {
+            bail!(
+                "Plugin {} is the Move CLI itself",
+                plugin_path.to_string_lossy()
+            )
+        }
+    }
+    let mut running_plugins: Vec<String> = env::var(MOVE_RUNNING_PLUGINS_ENV_VAR)
+        .map(|plugins| plugins.split(':').map(String::from).collect())
+        .unwrap_or_default();
+    if running_plugins.iter().any(|plugin| plugin == name) {
+        bail!(
+            "Plugin `{}{}` runs `move {}` recursively",
+            PLUGIN_PREFIX,
+            name,
+            name
+        )
+    }
+    running_plugins.push(name.clone());
+
+    let mut command = Command::new(&plugin_path);
+    command
+        .args(args)
+        .env(MOVE_RUNNING_PLUGINS_ENV_VAR, running_plugins.join(":"))
+        .env(MOVE_CLI_VERSION_ENV_VAR, env!("CARGO_PKG_VERSION"));
+    // plugins may run outside of a package, or without a home directory
+    if let Ok(package_path) = package_root(move_args.package_path.as_deref()) {
+        command.env(MOVE_PACKAGE_PATH_ENV_VAR, package_path);
+    }
+    if let Ok(move_home) = move_home() {
+        command.env(MOVE_HOME_ENV_VAR, move_home);
+    }
+    // plugins see the experiments enabled by `--experiments` as well
+    command.env(MOVE_EXPERIMENTS_ENV_VAR, enabled_experiments().join(","));
+    let status = command
+        .status()
+        .with_context(|| format!("Failed to run {}", plugin_path.to_string_lossy()))?;
+    // a plugin killed by a signal has no exit code
+    std::process::exit(status.code().unwrap_or(1))
+}
+
+/// The executable implementing the plugin subcommand `name`, found first on the `PATH`
+pub fn find_plugin(name: &str) -> Option<PathBuf> {
+    let file_name = format!("{}{}{}", PLUGIN_PREFIX, name, env::consts::EXE_SUFFIX);
+    search_path()
+        .into_iter()
+        .map(|dir| dir.join(&file_name))
+        .find(|path| is_executable(path))
+}
+
+/// The plugin subcommands on the `PATH` by name, along with the executables implementing them
+pub fn list_plugins() -> BTreeMap<String, PathBuf> {
+    let mut plugins = BTreeMap::new();
+    for dir in search_path() {
+        let entries = match fs::read_dir(&dir) {
+            Ok(entries) => entries,
+            Err(_) => continue,
+        };
+        for entry in entries.flatten() {
+            let path = entry.path();
+            let name = match entry
+                .file_name()
+                .to_str()
+                .and_then(|file_name| file_name.strip_prefix(PLUGIN_PREFIX))
+                .and_then(|name| name.strip_suffix(env::consts::EXE_SUFFIX))
+            {
+                Some(name) if !name.is_empty() && is_executable(&path) => name.to_string(),
+                _ => continue,
+            };
+            // earlier directories on the `PATH` take precedence
+            plugins.entry(name).or_insert(path);
+        }
+    }
+    plugins
+}
+
+fn search_path() -> Vec<PathBuf> {
+    env::var_os("PATH")
+        .map(|path| env::split_paths(&path).collect())
+        .unwrap_or_else(|| env::split_paths(&OsString::new()).collect())
+}
+
+fn same_file(a: &Path, b: &Path) -> bool {
+    match (a.canonicalize(), b.canonicalize()) {
+        (Ok(a), Ok(b)) => a == b,
+        _ => false,
+    }
+}
+
+#[cfg(unix)]
+fn is_executable(path: &Path) -> bool {
+    use std::os::unix::fs::PermissionsExt;
+    fs::metadata(path)
+        .map(|metadata| metadata.is_file() && metadata.permissions().mode() & 0o111 != 0)
+        .unwrap_or(false)
+}
+
+#[cfg(not(unix))]
+fn is_executable(path: &Path) -> bool {
+    path.is_file()
+}
@@ -0,0 +1,56 @@
+// Copyright (c) The Move Contributors
+// SPDX-License-Identifier: Apache-2.0
+
+use anyhow::{Context, Result};
+use serde::Deserialize;
+use std::path::{Path, PathBuf};
+
+/// The directory at the root of a package holding the configuration of the CLI for the package
+pub const PROJECT_CONFIG_DIR: &str = ".move";
+
+/// The file in `PROJECT_CONFIG_DIR` holding the configuration
+pub const PROJECT_CONFIG_FILE: &str = "config.toml";
+
+/// The project-level configuration of the CLI, read from `.move/config.toml` at the root of the
+/// package:
+///
+/// ```toml
+/// [sandbox]
+/// default-signers = ["0xA11CE"]
+/// ```
+#[derive(Debug, Default, Deserialize)]
+pub struct ProjectConfig {
+    #[serde(default)]
+    pub sandbox: SandboxConfig,
+}
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub struct SandboxConfig {
+    /// The signers of `sandbox run` when `--signers` is not passed, as address literals or named
+    /// addresses of the package
+    #[serde(default)]
+    pub default_signers: Vec<String>,
+}
+
+impl ProjectConfig {
+    /// The path of the configuration of the package at `package_root`
+    pub fn path(package_root: &Path) -> PathBuf {
+        package_root
+            .join(PROJECT_CONFIG_DIR)
+            .join(PROJECT_CONFIG_FILE)
+    }
+
+    /// The configuration of the package at `package_root`, or the default one if the package has
+    /// no configuration
+    pub fn load(package_root: &Path) -> Result<Self> {
+        let path = Self::path(package_root);
+        if !path.exists() {
+            return Ok(Self::default());
+        }
+        let contents = std::fs::read_to_string(&path)
+            .with_context(|| format!("Unable to read '{}'", path.display()))?;
+        toml_edit::easy::from_str(&contents)
+            .with_context(|| format!("Error parsing '{}'", path.display()))
+    }
+}
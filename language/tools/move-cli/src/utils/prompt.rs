@@ -0,0 +1,107 @@
+// Copyright (c) The Move Contributors
+// SPDX-License-Identifier: Apache-2.0
+
+use anyhow::{bail, Result};
+use crossterm::{
+    cursor,
+    event::{self, Event, KeyCode, KeyModifiers},
+    queue,
+    terminal::{self, ClearType},
+};
+use std::io::{self, Write};
+
+/// Ask the user to confirm `question` before going ahead with something that is hard to undo,
+/// such as an upload. Confirmed without asking if `assume_yes` is set, which commands expose as
+/// `--yes`. Fails if the user declines, or if stdin is not a terminal, as there is no one to ask.
+pub fn confirm(question: &str, assume_yes: bool) -> Result<()> {
+    if assume_yes {
+        return Ok(());
+    }
+    if !atty::is(atty::Stream::Stdin) {
+        bail!("{} Pass --yes to confirm without being asked", question)
+    }
+    eprint!("{} [y/N] ", question);
+    io::stderr().flush()?;
+    let mut answer = String::new();
+    io::stdin().read_line(&mut answer)?;
+    match answer.trim().to_lowercase().as_str() {
+        "y" | "yes" => Ok(()),
+        _ => bail!("Cancelled"),
+    }
+}
+
+/// Ask the user to pick one of `options`, returning its index. On a terminal, the option is picked
+/// with the arrow keys or its number, then Enter. Otherwise the options are listed with their
+/// numbers and the number is read from a line of stdin, so that the choice can be piped in. Fails
+/// if the user cancels, or if stdin is closed before a choice is made.
+pub fn select(question: &str, options: &[String]) -> Result<usize> {
+    if atty::is(atty::Stream::Stdin) && atty::is(atty::Stream::Stderr) {
+        return select_with_keys(question, options);
+    }
+    eprintln!("{}", question);
+    for (i, option) in options.iter().enumerate() {
+        eprintln!("  {}) {}", i + 1, option);
+    }
+    eprint!("Enter a number from 1 to {}: ", options.len());
+    io::stderr().flush()?;
+    let mut answer = String::new();
+    if io::stdin().read_line(&mut answer)? == 0 {
+        bail!("No choice was made: stdin was closed before a number was read")
+    }
+    match answer.trim().parse::<usize>() {
+        Ok(choice) if (1..=options.len()).contains(&choice) => Ok(choice - 1),
+        _ => bail!(
+            "'{}' is not a number from 1 to {}",
+            answer.trim(),
+            options.len()
+        ),
+    }
+}
+
+fn select_with_keys(question: &str, options: &[String]) -> Result<usize> {
+    eprintln!(
+        "{} (arrow keys or number to move, Enter to pick, Esc to cancel)",
+        question
+    );
+    terminal::enable_raw_mode()?;
+    let result = read_selection(options);
+    terminal::disable_raw_mode()?;
+    result
+}
+
+/// Let the user move the selection among `options`, drawn on stderr, until Enter is pressed.
+/// Expects the terminal to be in raw mode, where lines need an explicit carriage return.
+fn read_selection(options: &[String]) -> Result<usize> {
+    let mut stderr = io::stderr();
+    let mut selected = 0;
+    loop {
+        for (i, option) in options.iter().enumerate() {
+            let marker = if i == selected { '>' } else { ' ' };
+            queue!(stderr, terminal::Clear(ClearType::CurrentLine))?;
+            write!(stderr, "\r{} {}) {}\r\n", marker, i + 1, option)?;
+        }
+        stderr.flush()?;
+        let key = match event::read()? {
+            Event::Key(key) => key,
+            _ => continue,
+        };
+        match key.code {
+            KeyCode::Enter => return Ok(selected),
+            KeyCode::Esc => bail!("Cancelled"),
+            // raw mode keeps Ctrl-C from interrupting the process
+            KeyCode::Char('c') if key.modifiers.contains(KeyModifiers::CONTROL) => {
+                bail!("Cancelled")
+            }
+            KeyCode::Up => selected = selected.checked_sub(1).unwrap_or(options.len() - 1),
+            KeyCode::Down => selected = (selected + 1) % options.len(),
+            KeyCode::Char(digit) => match digit.to_digit(10) {
+                Some(number) if (1..=options.len()).contains(&(number as usize)) => {
+                    selected = number as usize - 1
+                }
+                _ => (),
+            },
+            _ => (),
+        }
+        queue!(stderr, cursor::MoveUp(options.len() as u16))?;
+    }
+}
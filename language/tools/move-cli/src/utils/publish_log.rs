@@ -0,0 +1,159 @@
+// Copyright (c) The Move Contributors
+// SPDX-License-Identifier: Apache-2.0
+
+//! The log of the packages uploaded to the registry, kept in `MOVE_HOME` as an audit trail of what
+//! was uploaded when. Entries are only ever appended, oldest first.
+
+use anyhow::{Context, Result};
+use move_command_line_common::env::move_home_write_error;
+use move_package::PackageLock;
+use serde::{Deserialize, Serialize};
+use std::{
+    fs,
+    io::{ErrorKind, Write},
+    path::{Path, PathBuf},
+};
+use tempfile::NamedTempFile;
+
+/// The file under `MOVE_HOME` holding the log
+pub const PUBLISH_LOG_FILE: &str = "publish-log.toml";
+
+/// A successful upload
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct PublishLogEntry {
+    /// When the upload succeeded, in RFC 3339 format, in UTC
+    pub timestamp: String,
+    pub package: String,
+    pub version: String,
+    /// The git commit the package was uploaded from, if the repository has one
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub rev: Option<String>,
+    /// The URL of the registry the package was uploaded to
+    pub registry: String,
+    /// The identifier the registry answered with, if any
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub response_id: Option<String>,
+}
+
+#[derive(Debug, Default, Serialize, Deserialize)]
+struct PublishLog {
+    #[serde(default, rename = "upload")]
+    uploads: Vec<PublishLogEntry>,
+}
+
+pub fn publish_log_path(move_home: &str) -> PathBuf {
+    Path::new(move_home).join(PUBLISH_LOG_FILE)
+}
+
+/// The entries of the log in `move_home`, oldest first. A missing log holds no entries.
+pub fn read_publish_log(move_home: &str) -> Result<Vec<PublishLogEntry>> {
+    let path = publish_log_path(move_home);
+    let contents = match fs::read_to_string(&path) {
+        Ok(contents) => contents,
+        Err(err) if err.kind() == ErrorKind::NotFound => return Ok(vec![]),
+        Err(err) => {
+            return Err(err).with_context(|| format!("Unable to read '{}'", path.display()))
+        }
+    };
+    let log: PublishLog = toml_edit::easy::from_str(&contents)
+        .with_context(|| format!("Invalid publish log '{}'", path.display()))?;
+    Ok(log.uploads)
+}
+
+/// Append `entry` to the log in `move_home`. The package lock is held meanwhile, so that concurrent
+/// uploads do not lose each other's entries, and the log is replaced atomically, so that it is
+/// never left half written.
+pub fn append_publish_log(move_home: &str, entry: PublishLogEntry) -> Result<()> {
+    let lock = PackageLock::lock();
+    let result = read_publish_log(move_home).and_then(|mut uploads| {
+        uploads.push(entry);
+        write_publish_log(move_home, PublishLog { uploads })
+    });
+    lock.unlock();
+    result
+}
+
+fn write_publish_log(move_home: &str, log: PublishLog) -> Result<()> {
+    let path = publish_log_path(move_home);
+    fs::create_dir_all(move_home)
+        .map_err(|err| move_home_write_error("create the directory", Path::new(move_home), err))?;
+    let contents = toml_edit::easy::to_string(&log)?;
+    let mut file = NamedTempFile::new_in(move_home).map_err(|err| {
+        move_home_write_error("create a temporary file in", Path::new(move_home), err)
+    })?;
+    file.write_all(contents.as_bytes())
+        .map_err(|err| move_home_write_error("write", file.path(), err))?;
+    file.persist(&path)
+        .map_err(|err| move_home_write_error("save the publish log to", &path, err.error))?;
+    Ok(())
+}
+
+/// The last `limit` entries of `entries` for the package `package`, oldest first
+pub fn filter_publish_log<'a>(
+    entries: &'a [PublishLogEntry],
+    package: Option<&str>,
+    limit: Option<usize>,
+) -> Vec<&'a PublishLogEntry> {
+    let entries: Vec<_> = entries
+        .iter()
+        .filter(|entry| package.map_or(true, |package| entry.package == package))
+        .collect();
+    let skipped = limit.map_or(0, |limit| entries.len().saturating_sub(limit));
+    entries.into_iter().skip(skipped).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn entry(package: &str, version: &str) -> PublishLogEntry {
+        PublishLogEntry {
+            timestamp: "2022-10-01T12:00:00Z".to_string(),
+            package: package.to_string(),
+            version: version.to_string(),
+            rev: None,
+            registry: "https://www.movey.net".to_string(),
+            response_id: Some("1".to_string()),
+        }
+    }
+
+    #[test]
+    fn entries_are_appended_in_order() {
+        let dir = tempfile::tempdir().unwrap();
+        let move_home = dir.path().join("move_home").to_string_lossy().to_string();
+        assert!(read_publish_log(&move_home).unwrap().is_empty());
+
+        let first = entry("Coins", "1.0.0");
+        let second = PublishLogEntry {
+            rev: Some("0123abcd".to_string()),
+            response_id: None,
+            ..entry("Tokens", "0.1.0")
+        };
+        append_publish_log(&move_home, first.clone()).unwrap();
+        append_publish_log(&move_home, second.clone()).unwrap();
+        assert_eq!(read_publish_log(&move_home).unwrap(), vec![first, second]);
+        // only the log is left in MOVE_HOME, without temporary files
+        assert_eq!(fs::read_dir(&move_home).unwrap().count(), 1);
+    }
+
+    #[test]
+    fn filter_keeps_the_last_entries_of_a_package() {
+        let entries = vec![
+            entry("Coins", "1.0.0"),
+            entry("Tokens", "0.1.0"),
+            entry("Coins", "1.1.0"),
+            entry("Coins", "1.2.0"),
+        ];
+        let versions = |package, limit| {
+            filter_publish_log(&entries, package, limit)
+                .iter()
+                .map(|entry| entry.version.as_str())
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(versions(None, None).len(), 4);
+        assert_eq!(versions(Some("Coins"), None), ["1.0.0", "1.1.0", "1.2.0"]);
+        assert_eq!(versions(Some("Coins"), Some(2)), ["1.1.0", "1.2.0"]);
+        assert_eq!(versions(None, Some(10)).len(), 4);
+        assert!(versions(Some("Other"), None).is_empty());
+    }
+}
@@ -0,0 +1,328 @@
+// Copyright (c) The Move Contributors
+// SPDX-License-Identifier: Apache-2.0
+
+//! TLS settings of the HTTP client talking to the registry, for self-hosted registries signed by a
+//! private certificate authority or requiring client certificates (mutual TLS)
+
+use crate::utils::movey_credential::{credential_path, read_credential_file};
+use anyhow::{anyhow, bail, Context, Result};
+use clap::*;
+use reqwest::{
+    blocking::{Client, ClientBuilder},
+    Certificate, Identity,
+};
+use std::{
+    fs,
+    path::{Path, PathBuf},
+};
+use toml_edit::easy::{map::Map, Value};
+
+/// The keys of the `[registry]` table of the credential file that set the TLS options
+pub const CACERT_KEY: &str = "cacert";
+pub const CLIENT_CERT_KEY: &str = "client_cert";
+pub const CLIENT_KEY_KEY: &str = "client_key";
+
+/// TLS options of the commands that talk to the registry. Each option defaults to the key of the
+/// `[registry]` table of the credential file named after it, e.g. `client_cert`.
+#[derive(Parser, Debug, Default, Clone)]
+pub struct RegistryTlsOptions {
+    /// PEM file of a certificate authority to trust for the registry, in addition to the ones of
+    /// the system
+    #[clap(long = "cacert", parse(from_os_str))]
+    pub cacert: Option<PathBuf>,
+    /// PEM file of the client certificate to present to a registry that requires mutual TLS
+    #[clap(long = "client-cert", parse(from_os_str))]
+    pub client_cert: Option<PathBuf>,
+    /// PEM file of the PKCS #8 private key of the client certificate
+    #[clap(long = "client-key", parse(from_os_str))]
+    pub client_key: Option<PathBuf>,
+}
+
+/// The TLS settings in effect: the options, completed with the keys of the credential file
+#[derive(Debug)]
+pub struct RegistryTls {
+    cacert: Option<PathBuf>,
+    client_cert: Option<PathBuf>,
+    client_key: Option<PathBuf>,
+    credential_path: String,
+}
+
+impl RegistryTlsOptions {
+    /// Complete the options with the keys of the credential file in `move_home`. Relative paths
+    /// in the credential file are relative to `move_home`.
+    pub fn resolve(&self, move_home: &str) -> Result<RegistryTls> {
+        let credential_path = credential_path(move_home);
+        let registry = if Path::new(&credential_path).exists() {
+            read_credential_file(&credential_path)?
+                .get("registry")
+                .cloned()
+                .unwrap_or_else(|| Value::Table(Map::new()))
+        } else {
+            Value::Table(Map::new())
+        };
+        let from_file = |key: &str| -> Result<Option<PathBuf>> {
+            match registry.get(key) {
+                None => Ok(None),
+                Some(Value::String(path)) => Ok(Some(Path::new(move_home).join(path))),
+                Some(_) => bail!(
+                    "Error parsing {} in {}: expected the path of a PEM file",
+                    key,
+                    credential_path
+                ),
+            }
+        };
+        let tls = RegistryTls {
+            cacert: self
+                .cacert
+                .clone()
+                .map_or_else(|| from_file(CACERT_KEY), |p| Ok(Some(p)))?,
+            client_cert: self
+                .client_cert
+                .clone()
+                .map_or_else(|| from_file(CLIENT_CERT_KEY), |p| Ok(Some(p)))?,
+            client_key: self
+                .client_key
+                .clone()
+                .map_or_else(|| from_file(CLIENT_KEY_KEY), |p| Ok(Some(p)))?,
+            credential_path,
+        };
+        match (&tls.client_cert, &tls.client_key) {
+            (Some(cert), None) => bail!(
+                "A client certificate is set ({}), but no client key. Pass --client-key, or set \
+                 `{}` in the [registry] table of {}",
+                cert.display(),
+                CLIENT_KEY_KEY,
+                tls.credential_path
+            ),
+            (None, Some(key)) => bail!(
+                "A client key is set ({}), but no client certificate. Pass --client-cert, or set \
+                 `{}` in the [registry] table of {}",
+                key.display(),
+                CLIENT_CERT_KEY,
+                tls.credential_path
+            ),
+            _ => Ok(tls),
+        }
+    }
+
+    /// Save the options that are set into the `[registry]` table of the credential file in
+    /// `move_home`, as absolute paths, keeping the rest of the file intact
+    pub fn save(&self, move_home: &str) -> Result<()> {
+        let settings = [
+            (CACERT_KEY, &self.cacert),
+            (CLIENT_CERT_KEY, &self.client_cert),
+            (CLIENT_KEY_KEY, &self.client_key),
+        ];
+        if settings.iter().all(|(_, path)| path.is_none()) {
+            return Ok(());
+        }
+        let credential_path = credential_path(move_home);
+        let mut toml: Value = read_credential_file(&credential_path)?;
+        let table = toml
+            .as_table_mut()
+            .context(format!("Error parsing {}", credential_path))?;
+        let registry = table
+            .entry("registry")
+            .or_insert(Value::Table(Map::new()))
+            .as_table_mut()
+            .context("Error parsing registry table")?;
+        for (key, path) in settings {
+            if let Some(path) = path {
+                let path = path
+                    .canonicalize()
+                    .with_context(|| format!("Unable to find '{}'", path.display()))?;
+                registry.insert(
+                    key.to_string(),
+                    Value::String(path.to_string_lossy().to_string()),
+                );
+            }
+        }
+        fs::write(&credential_path, toml.to_string())
+            .with_context(|| format!("Unable to write {}", credential_path))
+    }
+}
+
+impl RegistryTls {
+    /// An HTTP client trusting the certificate authority and presenting the client certificate
+    /// that are set. The contents of the files are never included in errors or logs.
+    pub fn client(&self) -> Result<Client> {
+        let mut builder = ClientBuilder::new();
+        if let Some(cacert) = &self.cacert {
+            let pem = fs::read(cacert).with_context(|| {
+                format!("Unable to read the CA certificate '{}'", cacert.display())
+            })?;
+            let certificate = Certificate::from_pem(&pem).map_err(|_| {
+                anyhow!(
+                    "The CA certificate '{}' is not a PEM certificate",
+                    cacert.display()
+                )
+            })?;
+            builder = builder.add_root_certificate(certificate);
+        }
+        if let (Some(cert), Some(key)) = (&self.client_cert, &self.client_key) {
+            let cert_pem = fs::read(cert).with_context(|| {
+                format!("Unable to read the client certificate '{}'", cert.display())
+            })?;
+            let key_pem = fs::read(key)
+                .with_context(|| format!("Unable to read the client key '{}'", key.display()))?;
+            let identity = Identity::from_pkcs8_pem(&cert_pem, &key_pem).map_err(|_| {
+                anyhow!(
+                    "Unable to load the client certificate '{}' with the key '{}': expected a PEM \
+                     certificate and a PEM PKCS #8 private key",
+                    cert.display(),
+                    key.display()
+                )
+            })?;
+            builder = builder.identity(identity);
+        }
+        builder.build().context("Unable to set up the HTTP client")
+    }
+
+    /// Explain why a request to the registry at `url` failed with `err`, telling a certificate of
+    /// the registry that cannot be verified from a client certificate that is missing or rejected
+    pub fn explain_error(&self, url: &str, err: anyhow::Error) -> anyhow::Error {
+        let details = err
+            .chain()
+            .map(|cause| cause.to_string())
+            .collect::<Vec<_>>()
+            .join(": ");
+        let lowercase = details.to_lowercase();
+        let is_verification_failure = [
+            "certificate verify failed",
+            "unable to get local issuer certificate",
+            "self signed certificate",
+            "self-signed",
+            "not trusted",
+        ]
+        .iter()
+        .any(|message| lowercase.contains(message));
+        let is_client_certificate_failure = [
+            "certificate required",
+            "handshake failure",
+            "bad certificate",
+            "unknown ca",
+        ]
+        .iter()
+        .any(|message| lowercase.contains(message));
+        if is_verification_failure {
+            anyhow!(
+                "Unable to verify the TLS certificate of the registry at {} ({}). If the registry \
+                 uses a private certificate authority, pass its certificate with --cacert, or set \
+                 `{}` in the [registry] table of {}",
+                url,
+                details,
+                CACERT_KEY,
+                self.credential_path
+            )
+        } else if is_client_certificate_failure {
+            match &self.client_cert {
+                None => anyhow!(
+                    "The registry at {} requires a client certificate ({}). Pass --client-cert \
+                     and --client-key, or set `{}` and `{}` in the [registry] table of {}",
+                    url,
+                    details,
+                    CLIENT_CERT_KEY,
+                    CLIENT_KEY_KEY,
+                    self.credential_path
+                ),
+                Some(cert) => anyhow!(
+                    "The registry at {} rejected the client certificate '{}' ({})",
+                    url,
+                    cert.display(),
+                    details
+                ),
+            }
+        } else {
+            anyhow!("Unable to send a request to {}: {}", url, details)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn write_credential_file(move_home: &Path, contents: &str) {
+        fs::write(credential_path(&move_home.to_string_lossy()), contents).unwrap();
+    }
+
+    #[test]
+    fn options_override_credential_file() {
+        let move_home = tempfile::tempdir().unwrap();
+        write_credential_file(
+            move_home.path(),
+            "[registry]\ntoken = \"t\"\ncacert = \"certs/ca.pem\"\nclient_cert = \"/etc/client.pem\"\n\
+             client_key = \"/etc/client.key\"\n",
+        );
+        let options = RegistryTlsOptions {
+            client_cert: Some(PathBuf::from("other.pem")),
+            client_key: Some(PathBuf::from("other.key")),
+            ..Default::default()
+        };
+        let tls = options
+            .resolve(&move_home.path().to_string_lossy())
+            .unwrap();
+        assert_eq!(tls.cacert, Some(move_home.path().join("certs/ca.pem")));
+        assert_eq!(tls.client_cert, Some(PathBuf::from("other.pem")));
+        assert_eq!(tls.client_key, Some(PathBuf::from("other.key")));
+    }
+
+    #[test]
+    fn client_certificate_requires_key() {
+        let move_home = tempfile::tempdir().unwrap();
+        write_credential_file(
+            move_home.path(),
+            "[registry]\nclient_cert = \"client.pem\"\n",
+        );
+        let err = RegistryTlsOptions::default()
+            .resolve(&move_home.path().to_string_lossy())
+            .unwrap_err();
+        assert!(err.to_string().contains("but no client key"), "{}", err);
+    }
+
+    #[test]
+    fn save_keeps_other_keys() {
+        let move_home = tempfile::tempdir().unwrap();
+        write_credential_file(move_home.path(), "[registry]\ntoken = \"t\"\n");
+        let cacert = move_home.path().join("ca.pem");
+        fs::write(&cacert, "").unwrap();
+        RegistryTlsOptions {
+            cacert: Some(cacert.clone()),
+            ..Default::default()
+        }
+        .save(&move_home.path().to_string_lossy())
+        .unwrap();
+        let toml =
+            read_credential_file(&credential_path(&move_home.path().to_string_lossy())).unwrap();
+        let registry = toml.get("registry").unwrap();
+        assert_eq!(registry.get("token").unwrap().as_str(), Some("t"));
+        assert_eq!(
+            registry
+                .get(CACERT_KEY)
+                .unwrap()
+                .as_str()
+                .map(PathBuf::from),
+            Some(cacert.canonicalize().unwrap())
+        );
+        assert!(registry.get(CLIENT_CERT_KEY).is_none());
+    }
+
+    #[test]
+    fn explain_error_tells_verification_from_client_certificate() {
+        let tls = RegistryTlsOptions::default()
+            .resolve("/nonexistent")
+            .unwrap();
+        let explain = |message: &str| {
+            tls.explain_error("https://registry", anyhow!(message.to_string()))
+                .to_string()
+        };
+        assert!(
+            explain("error:1416F086:SSL routines:certificate verify failed").contains("--cacert")
+        );
+        assert!(
+            explain("error:14094410:SSL routines:sslv3 alert handshake failure")
+                .contains("--client-cert")
+        );
+        assert!(explain("connection refused").starts_with("Unable to send a request"));
+    }
+}
@@ -0,0 +1,53 @@
+// Copyright (c) The Move Contributors
+// SPDX-License-Identifier: Apache-2.0
+
+use anyhow::{bail, Result};
+use clap::*;
+use move_command_line_common::json_errors;
+use move_package::compilation::compiled_package::SourceFileStamp;
+use std::path::Path;
+
+/// Options of the commands that use the artifacts of an earlier build
+#[derive(Parser, Default)]
+pub struct StaleBuildOptions {
+    /// Use build artifacts even if the sources of the package changed since they were built
+    #[clap(long = "allow-stale", global = true)]
+    pub allow_stale: bool,
+}
+
+/// Check that the sources of the package at `package_path` did not change since it was built into
+/// `package_build_dir` (`build/<package>`), refusing its artifacts otherwise, unless `allow_stale`
+/// is set. `rebuild_command` is the command to run to bring the artifacts up to date. The changed
+/// files are listed if `verbose` is set. Artifacts built before source files were recorded in
+/// `BuildInfo.yaml` are not checked.
+pub fn check_stale_build(
+    package_path: &Path,
+    package_build_dir: &Path,
+    rebuild_command: &str,
+    allow_stale: bool,
+    verbose: bool,
+) -> Result<()> {
+    let changes = match SourceFileStamp::changes_since_build(package_path, package_build_dir)? {
+        Some(changes) if !changes.is_empty() => changes,
+        _ => return Ok(()),
+    };
+    if verbose {
+        for change in &changes {
+            eprintln!("    {}", change);
+        }
+    }
+    let summary = format!(
+        "{} {} changed since last build",
+        changes.len(),
+        if changes.len() == 1 { "file" } else { "files" }
+    );
+    if allow_stale {
+        json_errors::warning(format!(
+            "using the stale artifacts in '{}' as --allow-stale is set: {}",
+            package_build_dir.display(),
+            summary
+        ));
+        return Ok(());
+    }
+    bail!("build is stale: {} (run {})", summary, rebuild_command)
+}
@@ -0,0 +1,316 @@
+// Copyright (c) The Move Contributors
+// SPDX-License-Identifier: Apache-2.0
+
+//! An advisory lock on a sandbox storage directory, so that commands run from several terminals do
+//! not interleave their reads and writes. Commands changing storage hold the lock exclusively, and
+//! commands only reading it share it. The lock is an `flock` on the `.lock` file of the directory,
+//! which the system releases when its holder exits, so a lock left by a process that died is
+//! reclaimed as soon as it is tried. The file also lists the processes holding the lock, to tell
+//! the commands waiting for it who they wait for.
+
+use anyhow::{bail, Context, Result};
+use clap::*;
+use std::{
+    fs::{self, File, OpenOptions},
+    path::Path,
+    time::{Duration, Instant, SystemTime, UNIX_EPOCH},
+};
+
+/// The lock file of a storage directory
+pub const STORAGE_LOCK_FILE: &str = ".lock";
+
+/// How often the lock is tried again while waiting for it
+const RETRY_INTERVAL: Duration = Duration::from_millis(100);
+
+/// Options of the sandbox commands that use the storage directory
+#[derive(Parser, Debug, Clone)]
+pub struct StorageLockOptions {
+    /// How long to wait for the other commands using the storage directory to finish, in seconds
+    #[clap(
+        long = "wait",
+        value_name = "SECONDS",
+        default_value = "30",
+        global = true
+    )]
+    pub wait: u64,
+    /// Fail right away if another command is using the storage directory, instead of waiting
+    #[clap(long = "no-wait", global = true)]
+    pub no_wait: bool,
+}
+
+impl Default for StorageLockOptions {
+    fn default() -> Self {
+        Self {
+            wait: 30,
+            no_wait: false,
+        }
+    }
+}
+
+/// How a command uses the storage directory
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum StorageAccess {
+    /// Only reads it, alongside other commands reading it
+    Shared,
+    /// Changes it, alone
+    Exclusive,
+}
+
+/// A process holding the lock, as listed in the lock file
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct LockHolder {
+    pub pid: u32,
+    pub access: StorageAccess,
+    /// When the lock was taken, in seconds since the Unix epoch
+    pub since: u64,
+    pub command: String,
+}
+
+impl LockHolder {
+    fn to_line(&self) -> String {
+        let access = match self.access {
+            StorageAccess::Shared => "shared",
+            StorageAccess::Exclusive => "exclusive",
+        };
+        format!(
+            "{}\t{}\t{}\t{}\n",
+            self.pid, access, self.since, self.command
+        )
+    }
+
+    fn from_line(line: &str) -> Option<Self> {
+        let mut fields = line.splitn(4, '\t');
+        let pid = fields.next()?.parse().ok()?;
+        let access = match fields.next()? {
+            "shared" => StorageAccess::Shared,
+            "exclusive" => StorageAccess::Exclusive,
+            _ => return None,
+        };
+        let since = fields.next()?.parse().ok()?;
+        let command = fields.next()?.to_string();
+        Some(Self {
+            pid,
+            access,
+            since,
+            command,
+        })
+    }
+}
+
+/// The lock on a storage directory, released when dropped
+#[derive(Debug)]
+pub struct StorageLock {
+    file: Option<File>,
+    access: StorageAccess,
+}
+
+impl Drop for StorageLock {
+    fn drop(&mut self) {
+        // the last holder leaves the lock file as it found it, empty, so that reading storage
+        // does not change it
+        if let Some(file) = &self.file {
+            if self.access == StorageAccess::Exclusive || is_last_holder(file) {
+                let _ = file.set_len(0);
+            }
+        }
+    }
+}
+
+impl StorageLock {
+    /// Lock `storage_dir` for `access`, waiting for the commands holding it otherwise as long as
+    /// `options` allow. `command` names the command taking the lock in the messages of the
+    /// commands waiting for it. Storage that does not exist yet is created to be changed, and
+    /// needs no lock to be read.
+    pub fn acquire(
+        storage_dir: &Path,
+        access: StorageAccess,
+        command: &str,
+        options: &StorageLockOptions,
+    ) -> Result<Self> {
+        let lock_path = storage_dir.join(STORAGE_LOCK_FILE);
+        let deadline = Instant::now() + Duration::from_secs(options.wait);
+        let mut announced = false;
+        loop {
+            // checked on every attempt, as `sandbox clean` removes the directory
+            if access == StorageAccess::Shared && !storage_dir.is_dir() {
+                return Ok(Self { file: None, access });
+            }
+            fs::create_dir_all(storage_dir)
+                .with_context(|| format!("Unable to create '{}'", storage_dir.display()))?;
+            match try_lock(&lock_path, access)? {
+                Attempt::Locked(file) => {
+                    record_holder(&file, access, command)?;
+                    return Ok(Self {
+                        file: Some(file),
+                        access,
+                    });
+                }
+                Attempt::Replaced => continue,
+                Attempt::Busy => (),
+            }
+            let holders = live_holders(&lock_path);
+            if options.no_wait || Instant::now() >= deadline {
+                bail!(
+                    "{}. {}",
+                    describe_holders(storage_dir, &holders),
+                    if options.no_wait {
+                        "Wait for it to finish, or drop --no-wait to wait for it".to_string()
+                    } else {
+                        format!(
+                            "Gave up after waiting {}s. Pass --wait <SECONDS> to wait longer",
+                            options.wait
+                        )
+                    }
+                )
+            }
+            if !announced {
+                eprintln!("{}, waiting...", describe_holders(storage_dir, &holders));
+                announced = true;
+            }
+            std::thread::sleep(RETRY_INTERVAL);
+        }
+    }
+}
+
+/// The outcome of an attempt to lock the lock file
+enum Attempt {
+    Locked(File),
+    /// Another process holds a conflicting lock
+    Busy,
+    /// The file was removed or replaced while being locked
+    Replaced,
+}
+
+/// The processes listed in the lock file at `lock_path` that are still running
+pub fn live_holders(lock_path: &Path) -> Vec<LockHolder> {
+    fs::read_to_string(lock_path)
+        .unwrap_or_default()
+        .lines()
+        .filter_map(LockHolder::from_line)
+        .filter(|holder| is_running(holder.pid))
+        .collect()
+}
+
+/// Why `storage_dir` cannot be locked, naming the processes in `holders`, e.g. `storage is locked
+/// by PID 42 (command sandbox publish) for 3s (storage directory 'storage')`
+fn describe_holders(storage_dir: &Path, holders: &[LockHolder]) -> String {
+    let now = unix_time();
+    let holders: Vec<_> = holders
+        .iter()
+        .map(|holder| {
+            format!(
+                "PID {} (command {}) for {}s",
+                holder.pid,
+                holder.command,
+                now.saturating_sub(holder.since)
+            )
+        })
+        .collect();
+    format!(
+        "storage is locked by {} (storage directory '{}')",
+        if holders.is_empty() {
+            "another command".to_string()
+        } else {
+            holders.join(", ")
+        },
+        storage_dir.display()
+    )
+}
+
+/// Add the current process to the holders listed in the locked `file`. An exclusive holder is the
+/// only one, so it clears the holders left by earlier commands first.
+fn record_holder(file: &File, access: StorageAccess, command: &str) -> Result<()> {
+    use std::io::Write;
+
+    if access == StorageAccess::Exclusive {
+        file.set_len(0)?;
+    }
+    let holder = LockHolder {
+        pid: std::process::id(),
+        access,
+        since: unix_time(),
+        command: command.to_string(),
+    };
+    // the file is opened to append, so the lines of processes sharing the lock do not mix
+    let mut file = file;
+    file.write_all(holder.to_line().as_bytes())?;
+    Ok(())
+}
+
+fn unix_time() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |since_epoch| since_epoch.as_secs())
+}
+
+/// Try to lock the lock file at `lock_path` for `access`, without waiting
+#[cfg(unix)]
+fn try_lock(lock_path: &Path, access: StorageAccess) -> Result<Attempt> {
+    use std::os::unix::{fs::MetadataExt, io::AsRawFd};
+
+    let file = OpenOptions::new()
+        .read(true)
+        .append(true)
+        .create(true)
+        .open(lock_path)
+        .with_context(|| format!("Unable to open '{}'", lock_path.display()))?;
+    let operation = match access {
+        StorageAccess::Shared => libc::LOCK_SH,
+        StorageAccess::Exclusive => libc::LOCK_EX,
+    };
+    // SAFETY: the descriptor stays open as long as `file` lives
+    if unsafe { libc::flock(file.as_raw_fd(), operation | libc::LOCK_NB) } != 0 {
+        let err = std::io::Error::last_os_error();
+        if err.raw_os_error() == Some(libc::EWOULDBLOCK) {
+            return Ok(Attempt::Busy);
+        }
+        return Err(err).with_context(|| format!("Unable to lock '{}'", lock_path.display()));
+    }
+    // `sandbox clean` removes the storage directory, lock file included, while holding the lock:
+    // a lock taken on the removed file locks nothing
+    let locked = file.metadata()?;
+    Ok(match fs::metadata(lock_path) {
+        Ok(current) if current.dev() == locked.dev() && current.ino() == locked.ino() => {
+            Attempt::Locked(file)
+        }
+        _ => Attempt::Replaced,
+    })
+}
+
+/// Whether no other process shares the lock on `file`, which then holds it exclusively
+#[cfg(unix)]
+fn is_last_holder(file: &File) -> bool {
+    use std::os::unix::io::AsRawFd;
+
+    // SAFETY: the descriptor stays open as long as `file` lives
+    unsafe { libc::flock(file.as_raw_fd(), libc::LOCK_EX | libc::LOCK_NB) == 0 }
+}
+
+#[cfg(not(unix))]
+fn is_last_holder(_file: &File) -> bool {
+    false
+}
+
+#[cfg(not(unix))]
+fn try_lock(lock_path: &Path, _access: StorageAccess) -> Result<Attempt> {
+    // storage is not locked on other systems
+    OpenOptions::new()
+        .read(true)
+        .append(true)
+        .create(true)
+        .open(lock_path)
+        .map(Attempt::Locked)
+        .with_context(|| format!("Unable to open '{}'", lock_path.display()))
+}
+
+#[cfg(unix)]
+fn is_running(pid: u32) -> bool {
+    // SAFETY: signal 0 only checks that the process exists
+    let signaled = unsafe { libc::kill(pid as libc::pid_t, 0) } == 0;
+    signaled || std::io::Error::last_os_error().raw_os_error() == Some(libc::EPERM)
+}
+
+#[cfg(not(unix))]
+fn is_running(_pid: u32) -> bool {
+    true
+}
@@ -0,0 +1,241 @@
+// Copyright (c) The Move Contributors
+// SPDX-License-Identifier: Apache-2.0
+
+//! Streams a file to a server as part of a `multipart/form-data` request, reading it in chunks so
+//! that memory use does not depend on its size, and showing the progress of the upload on stderr.
+
+use anyhow::Result;
+use reqwest::blocking::{Body, Client, Response};
+use sha2::{Digest, Sha256};
+use std::{
+    fs::File,
+    io::{self, Cursor, Read, Write},
+    path::{Path, PathBuf},
+    thread,
+    time::{Duration, Instant},
+};
+
+/// How often the request is sent before giving up on transient failures
+const MAX_ATTEMPTS: u32 = 3;
+const RETRY_DELAY: Duration = Duration::from_millis(500);
+const PROGRESS_BAR_WIDTH: usize = 30;
+const PROGRESS_REDRAW_INTERVAL: Duration = Duration::from_millis(100);
+
+const MULTIPART_BOUNDARY: &str = "------------------------move-package-upload";
+
+/// A `multipart/form-data` request made of text fields followed by a single file
+pub struct MultipartUpload {
+    pub fields: Vec<(String, String)>,
+    /// Name of the form field holding the file
+    pub file_field: String,
+    /// File name sent to the server
+    pub file_name: String,
+    pub file_path: PathBuf,
+}
+
+impl MultipartUpload {
+    pub fn content_type() -> String {
+        format!("multipart/form-data; boundary={}", MULTIPART_BOUNDARY)
+    }
+
+    /// Everything in the body that comes before the contents of the file
+    fn head(&self) -> Vec<u8> {
+        let fields: String = self
+            .fields
+            .iter()
+            .map(|(name, value)| {
+                format!(
+                    "--{}\r\nContent-Disposition: form-data; name=\"{}\"\r\n\r\n{}\r\n",
+                    MULTIPART_BOUNDARY, name, value
+                )
+            })
+            .collect();
+        let file = format!(
+            "--{}\r\nContent-Disposition: form-data; name=\"{}\"; filename=\"{}\"\r\n\
+             Content-Type: application/gzip\r\n\r\n",
+            MULTIPART_BOUNDARY, self.file_field, self.file_name
+        );
+        (fields + &file).into_bytes()
+    }
+
+    fn tail() -> Vec<u8> {
+        format!("\r\n--{}--\r\n", MULTIPART_BOUNDARY).into_bytes()
+    }
+
+    /// A body streaming the file from disk, which reports its progress if `show_progress` is set
+    fn body(&self, show_progress: bool) -> Result<Body> {
+        let head = self.head();
+        let tail = Self::tail();
+        let file = File::open(&self.file_path)?;
+        let len = head.len() as u64 + file.metadata()?.len() + tail.len() as u64;
+        let reader = Cursor::new(head).chain(file).chain(Cursor::new(tail));
+        let progress = show_progress.then(|| Progress::new(len));
+        Ok(Body::sized(ProgressReader { reader, progress }, len))
+    }
+
+    /// Send the request to `url`. The request is sent again, streaming the file from the start,
+    /// if the connection fails, is dropped or times out, or the server answers with a 5xx status. The
+    /// progress of the upload is shown if stderr is a terminal, unless `quiet` is set.
+    pub fn send(&self, client: &Client, url: &str, quiet: bool) -> Result<Response> {
+        let show_progress = !quiet && atty::is(atty::Stream::Stderr);
+        let mut attempt = 1;
+        loop {
+            let result = client
+                .post(url)
+                .header(reqwest::header::CONTENT_TYPE, Self::content_type())
+                .body(self.body(show_progress)?)
+                .send();
+            let transient = match &result {
+                Ok(response) => response.status().is_server_error(),
+                Err(err) => err.is_connect() || err.is_timeout() || is_connection_dropped(err),
+            };
+            if !transient || attempt == MAX_ATTEMPTS {
+                return Ok(result?);
+            }
+            if !quiet {
+                if show_progress {
+                    // end the line of the progress bar
+                    eprintln!();
+                }
+                eprintln!(
+                    "Upload failed, retrying ({} of {})",
+                    attempt + 1,
+                    MAX_ATTEMPTS
+                );
+            }
+            thread::sleep(RETRY_DELAY * attempt);
+            attempt += 1;
+        }
+    }
+}
+
+/// Whether `err` was caused by the server dropping the connection while the request was sent
+fn is_connection_dropped(err: &reqwest::Error) -> bool {
+    // `send` does not read the body of the response, so a body error means that the body of the
+    // request could not be sent
+    if err.is_body() {
+        return true;
+    }
+    let mut source = std::error::Error::source(err);
+    while let Some(err) = source {
+        if let Some(err) = err.downcast_ref::<io::Error>() {
+            return matches!(
+                err.kind(),
+                io::ErrorKind::ConnectionReset
+                    | io::ErrorKind::ConnectionAborted
+                    | io::ErrorKind::BrokenPipe
+            );
+        }
+        source = err.source();
+    }
+    false
+}
+
+/// The SHA-256 digest of the file at `path`, as a hex string
+pub fn file_digest(path: &Path) -> Result<String> {
+    let mut file = File::open(path)?;
+    let mut hasher = Sha256::new();
+    let mut buf = [0; 64 * 1024];
+    loop {
+        match file.read(&mut buf)? {
+            0 => break,
+            n => hasher.update(&buf[..n]),
+        }
+    }
+    Ok(hex::encode(hasher.finalize()))
+}
+
+struct ProgressReader<R> {
+    reader: R,
+    progress: Option<Progress>,
+}
+
+impl<R: Read> Read for ProgressReader<R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let n = self.reader.read(buf)?;
+        if let Some(progress) = &mut self.progress {
+            progress.advance(n as u64);
+        }
+        Ok(n)
+    }
+}
+
+/// A progress bar showing the bytes sent out of the total, and the estimated time left
+struct Progress {
+    total: u64,
+    sent: u64,
+    started: Instant,
+    last_drawn: Option<Instant>,
+}
+
+impl Progress {
+    fn new(total: u64) -> Self {
+        Self {
+            total,
+            sent: 0,
+            started: Instant::now(),
+            last_drawn: None,
+        }
+    }
+
+    fn advance(&mut self, n: u64) {
+        self.sent += n;
+        let done = self.sent >= self.total;
+        let now = Instant::now();
+        if done
+            || self
+                .last_drawn
+                .map_or(true, |drawn| now - drawn >= PROGRESS_REDRAW_INTERVAL)
+        {
+            self.last_drawn = Some(now);
+            self.draw(done);
+        }
+    }
+
+    fn draw(&self, done: bool) {
+        let ratio = if self.total == 0 {
+            1.0
+        } else {
+            self.sent as f64 / self.total as f64
+        };
+        // the file may have grown since its size was taken
+        let filled = ((ratio * PROGRESS_BAR_WIDTH as f64) as usize).min(PROGRESS_BAR_WIDTH);
+        let elapsed = self.started.elapsed().as_secs_f64();
+        let eta = if self.sent == 0 {
+            "--".to_string()
+        } else {
+            let left = elapsed * (self.total - self.sent.min(self.total)) as f64 / self.sent as f64;
+            format!("{}s", left.ceil() as u64)
+        };
+        let mut stderr = io::stderr();
+        let _ = write!(
+            stderr,
+            "\rUploading [{}{}] {} / {} ETA {}  ",
+            "=".repeat(filled),
+            " ".repeat(PROGRESS_BAR_WIDTH - filled),
+            format_bytes(self.sent),
+            format_bytes(self.total),
+            eta
+        );
+        if done {
+            let _ = writeln!(stderr);
+        }
+        let _ = stderr.flush();
+    }
+}
+
+pub(crate) fn format_bytes(bytes: u64) -> String {
+    const KIB: f64 = 1024.0;
+    const MIB: f64 = KIB * 1024.0;
+    const GIB: f64 = MIB * 1024.0;
+    let bytes_f = bytes as f64;
+    if bytes_f >= GIB {
+        format!("{:.1} GiB", bytes_f / GIB)
+    } else if bytes_f >= MIB {
+        format!("{:.1} MiB", bytes_f / MIB)
+    } else if bytes_f >= KIB {
+        format!("{:.1} KiB", bytes_f / KIB)
+    } else {
+        format!("{} B", bytes)
+    }
+}
@@ -0,0 +1,224 @@
+// Copyright (c) The Move Contributors
+// SPDX-License-Identifier: Apache-2.0
+
+//! The version of the CLI a project requires, set in a `move-toolchain.toml` file or in the
+//! `[toolchain]` section of `Move.toml`, in the package directory or any of its parents:
+//!
+//! ```toml
+//! [toolchain]
+//! version = ">=0.1, <0.2"
+//! # refuse to run, rather than warn, with a version that does not match
+//! strict = true
+//! ```
+
+use anyhow::{bail, Context, Result};
+use move_command_line_common::{files::read_utf8_file, json_errors};
+use move_package::source_package::{
+    layout::SourcePackageLayout,
+    manifest_parser::{parse_version_requirement_str, parse_version_str},
+    parsed_manifest::{format_version, Version, VersionRequirement},
+};
+use serde::Deserialize;
+use std::path::{Path, PathBuf};
+
+/// The file pinning the version of the CLI for the directory holding it and its subdirectories
+pub const TOOLCHAIN_FILE: &str = "move-toolchain.toml";
+
+/// The section of `move-toolchain.toml` and `Move.toml` holding the requirement
+const TOOLCHAIN_SECTION: &str = "toolchain";
+
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct ToolchainSection {
+    version: String,
+    #[serde(default)]
+    strict: bool,
+}
+
+/// The version of the CLI required by a project
+#[derive(Debug)]
+pub struct ToolchainRequirement {
+    pub version: VersionRequirement,
+    /// Refuse to run, rather than warn, with a version that does not match
+    pub strict: bool,
+    /// The file setting the requirement
+    pub source: PathBuf,
+}
+
+/// The version of this CLI
+pub fn cli_version() -> Version {
+    parse_version_str(env!("CARGO_PKG_VERSION"))
+        .expect("Crate version must be of the form <u64>.<u64>.<u64>")
+}
+
+impl ToolchainRequirement {
+    /// The requirement of the closest `move-toolchain.toml`, or `[toolchain]` section of a
+    /// `Move.toml`, in `dir` or its parents. A `move-toolchain.toml` takes precedence over the
+    /// `Move.toml` in the same directory.
+    pub fn find(dir: &Path) -> Result<Option<Self>> {
+        for dir in dir.ancestors() {
+            let toolchain_file = dir.join(TOOLCHAIN_FILE);
+            if toolchain_file.is_file() {
+                return match Self::read(&toolchain_file)? {
+                    Some(requirement) => Ok(Some(requirement)),
+                    None => bail!(
+                        "'{}' has no [{}] section",
+                        toolchain_file.display(),
+                        TOOLCHAIN_SECTION
+                    ),
+                };
+            }
+            let manifest = dir.join(SourcePackageLayout::Manifest.path());
+            if manifest.is_file() {
+                if let Some(requirement) = Self::read(&manifest)? {
+                    return Ok(Some(requirement));
+                }
+            }
+        }
+        Ok(None)
+    }
+
+    /// The requirement in the `[toolchain]` section of the TOML file at `path`, if any. A
+    /// `Move.toml` that does not parse is left for the command to report.
+    fn read(path: &Path) -> Result<Option<Self>> {
+        let contents = read_utf8_file(path)?;
+        let mut toml = match toml_edit::easy::from_str::<toml_edit::easy::Value>(&contents) {
+            Ok(toml) => toml,
+            Err(_) if path.ends_with(SourcePackageLayout::Manifest.path()) => return Ok(None),
+            Err(err) => {
+                return Err(err).with_context(|| format!("Error parsing '{}'", path.display()))
+            }
+        };
+        let section = match toml
+            .as_table_mut()
+            .and_then(|table| table.remove(TOOLCHAIN_SECTION))
+        {
+            Some(section) => section,
+            None => return Ok(None),
+        };
+        let section: ToolchainSection = section.try_into().with_context(|| {
+            format!(
+                "Error parsing the [{}] section of '{}'",
+                TOOLCHAIN_SECTION,
+                path.display()
+            )
+        })?;
+        let version = parse_version_requirement_str(&section.version).with_context(|| {
+            format!("Error parsing the version required in '{}'", path.display())
+        })?;
+        Ok(Some(Self {
+            version,
+            strict: section.strict,
+            source: path.to_path_buf(),
+        }))
+    }
+
+    /// Check `current` against the requirement: `Ok(None)` if it matches, or else the warning to
+    /// print, or an error if the requirement is strict
+    pub fn check(&self, current: &Version) -> Result<Option<String>> {
+        if self.version.matches(current) {
+            return Ok(None);
+        }
+        let mismatch = format!(
+            "This project requires Move CLI {} (set in '{}'), but this is Move CLI {}",
+            self.version,
+            self.source.display(),
+            format_version(current)
+        );
+        if self.strict {
+            bail!(
+                "{}. Install a matching version of the CLI, or set `strict = false` to only warn",
+                mismatch
+            )
+        }
+        Ok(Some(mismatch))
+    }
+}
+
+/// Check this CLI against the requirement of the project at `package_path`, or the current
+/// directory, printing a warning or failing if the version does not match
+pub fn check_project_toolchain(package_path: Option<&Path>) -> Result<()> {
+    let dir = match package_path
+        .unwrap_or_else(|| Path::new("."))
+        .canonicalize()
+    {
+        Ok(path) if path.is_file() => path.parent().map(Path::to_path_buf).unwrap_or(path),
+        Ok(path) => path,
+        // left for the command to report
+        Err(_) => return Ok(()),
+    };
+    if let Some(requirement) = ToolchainRequirement::find(&dir)? {
+        if let Some(warning) = requirement.check(&cli_version())? {
+            json_errors::warning(warning);
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+
+    fn requirement(version: &str, strict: bool) -> ToolchainRequirement {
+        ToolchainRequirement {
+            version: parse_version_requirement_str(version).unwrap(),
+            strict,
+            source: PathBuf::from(TOOLCHAIN_FILE),
+        }
+    }
+
+    #[test]
+    fn check_against_injected_version() {
+        assert!(requirement(">=1.2, <2", true)
+            .check(&(1, 4, 0))
+            .unwrap()
+            .is_none());
+        let warning = requirement(">=1.2, <2", false)
+            .check(&(2, 0, 0))
+            .unwrap()
+            .unwrap();
+        assert_eq!(
+            warning,
+            "This project requires Move CLI >=1.2.0, <2.0.0 (set in 'move-toolchain.toml'), but \
+             this is Move CLI 2.0.0"
+        );
+        let err = requirement(">=1.2, <2", true)
+            .check(&(1, 1, 9))
+            .unwrap_err();
+        assert!(err.to_string().contains("set `strict = false`"), "{}", err);
+    }
+
+    #[test]
+    fn find_closest_requirement() {
+        let dir = tempfile::tempdir().unwrap();
+        let package = dir.path().join("package");
+        fs::create_dir_all(package.join("sources")).unwrap();
+        fs::write(
+            dir.path().join(TOOLCHAIN_FILE),
+            "[toolchain]\nversion = \"1.0\"\n",
+        )
+        .unwrap();
+        fs::write(
+            package.join("Move.toml"),
+            "[package]\nname = \"P\"\nversion = \"0.0.0\"\n",
+        )
+        .unwrap();
+        let found = ToolchainRequirement::find(&package.join("sources"))
+            .unwrap()
+            .unwrap();
+        assert_eq!(found.source, dir.path().join(TOOLCHAIN_FILE));
+        assert!(!found.strict);
+
+        fs::write(
+            package.join("Move.toml"),
+            "[package]\nname = \"P\"\nversion = \"0.0.0\"\n\n[toolchain]\nversion = \">=2\"\n\
+             strict = true\n",
+        )
+        .unwrap();
+        let found = ToolchainRequirement::find(&package).unwrap().unwrap();
+        assert_eq!(found.source, package.join("Move.toml"));
+        assert_eq!(found.version.to_string(), ">=2.0.0");
+        assert!(found.strict);
+    }
+}
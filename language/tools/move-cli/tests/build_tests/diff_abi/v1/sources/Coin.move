@@ -0,0 +1,28 @@
+module 0x2::Coin {
+    struct Coin has copy, drop, store {
+        value: u64,
+        owner: address,
+    }
+
+    struct Vault<phantom T> has key {
+        coins: vector<Coin>,
+    }
+
+    entry fun init(_account: &signer) {}
+
+    public fun mint(value: u64): Coin {
+        Coin { value, owner: @0x2 }
+    }
+
+    public fun value(coin: &Coin): u64 {
+        coin.value
+    }
+
+    public fun burn(coin: Coin) {
+        let Coin { value: _, owner: _ } = coin;
+    }
+
+    public fun wrap<T: drop>(x: T): vector<T> {
+        vector[x]
+    }
+}
@@ -0,0 +1,31 @@
+module 0x2::Coin {
+    // no longer `copy`, and without the `owner` field
+    struct Coin has drop, store {
+        value: u64,
+    }
+
+    struct Vault<phantom T> has key {
+        coins: vector<Coin>,
+    }
+
+    struct Receipt has drop {}
+
+    public entry fun init(_account: &signer) {}
+
+    public fun mint(value: u64, _owner: address): Coin {
+        Coin { value }
+    }
+
+    public fun value(coin: &Coin): u64 {
+        coin.value
+    }
+
+    public fun split(coin: &mut Coin, amount: u64): Coin {
+        coin.value = coin.value - amount;
+        Coin { value: amount }
+    }
+
+    public fun wrap<T: copy + drop>(x: T): vector<T> {
+        vector[x]
+    }
+}
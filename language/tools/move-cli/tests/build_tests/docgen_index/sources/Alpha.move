@@ -0,0 +1,8 @@
+/// Modules at two addresses in one file.
+module Alpha::Alpha {
+    public fun alpha(): u64 { 1 }
+}
+
+module Beta::Beta {
+    public fun beta(): u64 { 2 }
+}
@@ -0,0 +1,4 @@
+/// The module listed first on the index page.
+module Alpha::Zeta {
+    public fun zeta(): u64 { 0 }
+}
@@ -0,0 +1 @@
+module 0x2::M {}
@@ -0,0 +1,3 @@
+module 0x2::M {
+    fun duplicate() {}
+}
@@ -0,0 +1,5 @@
+module 0x2::Clean {
+    public fun zero(): u64 {
+        0
+    }
+}
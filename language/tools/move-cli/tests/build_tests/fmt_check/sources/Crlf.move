@@ -0,0 +1,5 @@
+module 0x2::Crlf {
+    public fun one(): u64 {
+        1 
+    }
+}
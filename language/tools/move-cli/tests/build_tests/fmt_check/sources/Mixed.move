@@ -0,0 +1,9 @@
+module 0x2::Mixed {
+    public fun two(): u64 {
+	    2
+    }
+
+    public fun three(): u64 {
+		3
+    }
+}
\ No newline at end of file
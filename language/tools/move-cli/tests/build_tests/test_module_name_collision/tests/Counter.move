@@ -0,0 +1,4 @@
+module 0x2::Counter {
+    #[test]
+    fun collides() {}
+}
@@ -0,0 +1,7 @@
+module 0x2::Pricing {
+    use 0x2::Oracle;
+
+    public fun quote(feed: u64, amount: u64): u64 {
+        Oracle::latest_price(feed) * amount
+    }
+}
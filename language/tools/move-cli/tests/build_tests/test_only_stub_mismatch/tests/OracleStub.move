@@ -0,0 +1,18 @@
+#[test_only]
+module 0x2::Oracle {
+    struct Price has copy, drop, store {
+        value: u64,
+    }
+
+    public fun latest_price(_feed: u64): u64 {
+        100
+    }
+
+    public fun decimals(): u64 {
+        8
+    }
+
+    public fun refresh(_feed: u64) {}
+
+    public fun set_price(_feed: u64, _price: u64) {}
+}
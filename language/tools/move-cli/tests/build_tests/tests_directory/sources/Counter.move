@@ -0,0 +1,5 @@
+module 0x2::Counter {
+    public fun increment(n: u64): u64 {
+        n + 1
+    }
+}
@@ -0,0 +1,9 @@
+// compiled only in test mode, without needing to be annotated with #[test_only]
+module 0x2::CounterTests {
+    use 0x2::Counter;
+
+    #[test]
+    fun increment_adds_one() {
+        assert!(Counter::increment(1) == 2, 0);
+    }
+}
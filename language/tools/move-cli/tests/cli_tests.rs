@@ -2,27 +2,48 @@
 // Copyright (c) The Move Contributors
 // SPDX-License-Identifier: Apache-2.0
 
+use clap::CommandFactory;
 use httpmock::{prelude::*, Mock};
-use move_cli::sandbox::commands::test;
+use move_cli::{
+    base::{help::HELP_TOPICS, MOVE_BUILD_DIR_ENV_VAR},
+    sandbox::commands::test,
+    utils::{
+        movey_credential::credential_path,
+        storage_lock::{StorageAccess, StorageLock, StorageLockOptions, STORAGE_LOCK_FILE},
+    },
+    MoveCLI,
+};
 use move_command_line_common::{
     files,
-    movey_constants::{MOVEY_CREDENTIAL_PATH, MOVEY_URL},
+    movey_constants::MOVEY_URL,
+    testing::{add_update_baseline_fix, format_diff, read_env_update_baseline},
 };
+use move_core_types::{account_address::AccountAddress, language_storage::TypeTag};
+use move_package::compilation::compiled_package::ArtifactManifest;
 use serde_json::json;
+use sha2::{Digest, Sha256};
 #[cfg(unix)]
 use std::fs::File;
 #[cfg(unix)]
 use std::os::unix::fs::PermissionsExt;
 use std::{
-    env, fs,
-    io::Write,
-    path::PathBuf,
+    collections::BTreeMap,
+    fs,
+    io::{Read, Write},
+    net::TcpListener,
+    path::{Path, PathBuf},
     process::{Command, Stdio},
+    thread,
+    time::Duration,
 };
+use tempfile::TempDir;
 use toml_edit::easy::Value;
 
 pub const CLI_METATEST_PATH: [&str; 3] = ["tests", "metatests", "args.txt"];
 
+/// Batch files, some of them malformed, to check that `run_all` runs them all regardless
+const MALFORMED_METATEST_PATH: [&str; 2] = ["tests", "malformed_metatests"];
+
 fn get_cli_binary_path() -> PathBuf {
     let cli_exe = env!("CARGO_BIN_EXE_move");
     PathBuf::from(cli_exe)
@@ -36,18 +57,83 @@ fn get_metatest_path() -> PathBuf {
 fn run_metatest() {
     let path_cli_binary = get_cli_binary_path();
     let path_metatest = get_metatest_path();
+    let run_all = |use_temp_dir, track_cov| {
+        let summary = test::run_all(
+            &path_metatest,
+            &path_cli_binary,
+            use_temp_dir,
+            track_cov,
+            false,
+        )
+        .unwrap();
+        assert!(summary.failed.is_empty(), "{:?}", summary.failed);
+        assert!(
+            summary.harness_errors.is_empty(),
+            "{:?}",
+            summary.harness_errors
+        );
+        assert!(summary.is_success() && !summary.passed.is_empty());
+    };
 
     // local workspace + with coverage
-    assert!(test::run_all(&path_metatest, path_cli_binary.as_path(), false, true).is_ok());
+    run_all(false, true);
 
     // temp workspace + with coverage
-    assert!(test::run_all(&path_metatest, &path_cli_binary, true, true).is_ok());
+    run_all(true, true);
 
     // local workspace + without coverage
-    assert!(test::run_all(&path_metatest, &path_cli_binary, false, false).is_ok());
+    run_all(false, false);
 
     // temp workspace + without coverage
-    assert!(test::run_all(&path_metatest, &path_cli_binary, true, false).is_ok());
+    run_all(true, false);
+}
+
+#[test]
+fn run_all_reports_malformed_batch_files_and_runs_the_others() {
+    // regenerating the expected outputs would hide what the fixtures are for
+    if read_env_update_baseline() {
+        return;
+    }
+    let path: PathBuf = MALFORMED_METATEST_PATH.iter().collect();
+    let summary = test::run_all(&path, &get_cli_binary_path(), true, false, false).unwrap();
+    let dir_name = |path: &PathBuf| {
+        let dir = path.parent().unwrap().file_name().unwrap();
+        dir.to_string_lossy().into_owned()
+    };
+
+    let mut passed: Vec<_> = summary.passed.iter().map(dir_name).collect();
+    passed.sort();
+    assert_eq!(passed, ["valid_1", "valid_2"]);
+
+    assert_eq!(summary.failed.len(), 1, "{:?}", summary.failed);
+    let (path, diff) = &summary.failed[0];
+    assert_eq!(dir_name(path), "wrong_output");
+    assert!(diff.contains("Expected output differs"), "{}", diff);
+
+    let mut harness_errors: Vec<_> = summary
+        .harness_errors
+        .iter()
+        .map(|(path, error)| (dir_name(path), error.as_str()))
+        .collect();
+    harness_errors.sort();
+    assert_eq!(harness_errors.len(), 2, "{:?}", harness_errors);
+    assert_eq!(harness_errors[0].0, "bad_directive");
+    assert!(
+        harness_errors[0]
+            .1
+            .contains("line 2: `>` must be followed by the external command to run"),
+        "{}",
+        harness_errors[0].1
+    );
+    assert_eq!(harness_errors[1].0, "missing_expected");
+    assert!(
+        harness_errors[1].1.contains("missing the expected output"),
+        "{}",
+        harness_errors[1].1
+    );
+
+    let err = summary.check().unwrap_err().to_string();
+    assert_eq!(err, "1 / 5 test(s) failed, and 2 could not be run.");
 }
 
 #[test]
@@ -69,225 +155,5082 @@ fn cross_process_locking_git_deps() {
     handle.join().unwrap();
 }
 
-const UPLOAD_PACKAGE_PATH: &str = "./tests/upload_tests";
+const MANIFEST_PATH_TESTS_PATH: &str = "./tests/manifest_path_tests";
 #[test]
-fn upload_package_to_movey_works() {
-    let package_path = format!("{}/valid_package1", UPLOAD_PACKAGE_PATH);
-    init_git(&package_path, true);
-    let server = MockServer::start();
-    let server_mock = mock_movey_upload_with_response_body_and_status_code(&server, 200, None);
-    init_stub_registry_file(&package_path, &server.base_url());
-    let relative_package_path = PathBuf::from(&package_path);
-    let absolute_package_path =
-        files::path_to_string(&relative_package_path.canonicalize().unwrap()).unwrap();
+fn build_package_from_sibling_directory_with_manifest_path() {
+    let package_path = PathBuf::from(MANIFEST_PATH_TESTS_PATH).join("Package");
+    let sibling_path = PathBuf::from(MANIFEST_PATH_TESTS_PATH).join("Sibling");
+    let _ = fs::remove_dir_all(package_path.join("build"));
 
     let cli_exe = env!("CARGO_BIN_EXE_move");
     let output = Command::new(cli_exe)
-        .env("MOVE_HOME", &absolute_package_path)
-        .current_dir(&absolute_package_path)
-        .args(["movey-upload"])
+        .current_dir(&sibling_path)
+        .args(["build", "--manifest-path", "../Package/Move.toml"])
         .output()
         .unwrap();
 
-    server_mock.assert();
-    assert!(output.status.success());
-    let output = String::from_utf8_lossy(output.stdout.as_slice()).to_string();
     assert!(
-        output.contains("Your package has been successfully uploaded to Movey"),
+        output.status.success(),
         "{}",
-        output
+        String::from_utf8_lossy(&output.stderr)
     );
+    // artifacts are placed relative to the manifest's directory rather than the cwd
+    assert!(package_path.join("build").join("Package").exists());
+    assert!(!sibling_path.join("build").exists());
+    let _ = fs::remove_dir_all(package_path.join("build"));
 
-    clean_up(&absolute_package_path);
+    let output = Command::new(cli_exe)
+        .current_dir(&sibling_path)
+        .args(["build", "--manifest-path", "../Missing/Move.toml"])
+        .output()
+        .unwrap();
+    assert!(!output.status.success());
+    let error = String::from_utf8_lossy(&output.stderr).to_string();
+    assert!(
+        error.contains("Unable to find package manifest at '../Missing/Move.toml'"),
+        "{}",
+        error
+    );
 }
 
 #[test]
-fn upload_package_to_movey_prints_error_message_if_server_respond_4xx() {
-    let package_path = format!("{}/valid_package2", UPLOAD_PACKAGE_PATH);
-    init_git(&package_path, true);
-    let server = MockServer::start();
-    let server_mock = mock_movey_upload_with_response_body_and_status_code(
-        &server,
-        400,
-        Some("Invalid Api token"),
-    );
-    init_stub_registry_file(&package_path, &server.base_url());
-    let relative_package_path = PathBuf::from(&package_path);
-    let absolute_package_path =
-        files::path_to_string(&relative_package_path.canonicalize().unwrap()).unwrap();
+fn build_from_a_subdirectory_uses_the_enclosing_package() {
+    let workspace = tempfile::tempdir().unwrap();
+    let package_path = workspace.path().join("Package");
+    write_package(&package_path, "Package", "", &[("M", "module 0x2::M {}")]);
 
-    let cli_exe = env!("CARGO_BIN_EXE_move");
-    let output = Command::new(cli_exe)
-        .env("MOVE_HOME", &absolute_package_path)
-        .current_dir(&absolute_package_path)
-        .args(["movey-upload"])
+    let output = Command::new(env!("CARGO_BIN_EXE_move"))
+        .current_dir(package_path.join("sources"))
+        .arg("build")
         .output()
         .unwrap();
+    let stderr = String::from_utf8_lossy(&output.stderr).to_string();
+    assert!(output.status.success(), "{}", stderr);
+    let manifest = package_path.canonicalize().unwrap().join("Move.toml");
+    assert!(
+        stderr.contains(&format!(
+            "Using the package manifest {}",
+            manifest.display()
+        )),
+        "{}",
+        stderr
+    );
+    assert!(package_path.join("build").join("Package").exists());
+    assert!(!package_path.join("sources").join("build").exists());
+}
 
-    server_mock.assert();
-    assert!(!output.status.success());
-    let output = String::from_utf8_lossy(output.stderr.as_slice()).to_string();
-    assert!(output.contains("Error: Invalid Api token"), "{}", output);
+#[test]
+fn build_outside_of_a_package_suggests_creating_one() {
+    let workspace = tempfile::tempdir().unwrap();
+    // the search for a package root stops at the marker, whatever lies above the temp dir
+    fs::write(workspace.path().join(".move-root"), "").unwrap();
+    for name in ["B", "A"] {
+        write_package(&workspace.path().join(name), name, "", &[]);
+    }
+    let nested_path = workspace.path().join("A").join("nested");
+    fs::create_dir(&nested_path).unwrap();
+    fs::write(nested_path.join(".move-root"), "").unwrap();
+    let build = |dir: &Path| {
+        let output = Command::new(env!("CARGO_BIN_EXE_move"))
+            .current_dir(dir)
+            .arg("build")
+            .output()
+            .unwrap();
+        assert!(!output.status.success());
+        String::from_utf8_lossy(&output.stderr).to_string()
+    };
+
+    let error = build(workspace.path());
+    assert!(
+        error.contains("Unable to find package manifest"),
+        "{}",
+        error
+    );
+    assert!(error.contains("`move new <name>`"), "{}", error);
+    assert!(
+        error.contains(&format!(
+            "found in subdirectories with `--path`: {}, {}",
+            Path::new("A").join("Move.toml").display(),
+            Path::new("B").join("Move.toml").display()
+        )),
+        "{}",
+        error
+    );
 
-    clean_up(&absolute_package_path);
+    // the package enclosing a marker is not searched
+    let error = build(&nested_path);
+    assert!(
+        error.contains("Unable to find package manifest"),
+        "{}",
+        error
+    );
+    assert!(!error.contains("subdirectories"), "{}", error);
 }
 
 #[test]
-fn upload_package_to_movey_prints_hardcoded_error_message_if_server_respond_5xx() {
-    let package_path = format!("{}/valid_package3", UPLOAD_PACKAGE_PATH);
-    init_git(&package_path, true);
-    let server = MockServer::start();
-    let server_mock = mock_movey_upload_with_response_body_and_status_code(
-        &server,
-        500,
-        Some("Invalid Api token"),
+fn build_writes_artifact_manifest() {
+    let package = tempfile::tempdir().unwrap();
+    write_package(
+        package.path(),
+        "Artifacts",
+        "",
+        &[
+            ("Coin", "module 0x2::Coin { public fun value(): u64 { 1 } }"),
+            (
+                "Wallet",
+                "module 0x2::Wallet { use 0x2::Coin; fun f(): u64 { Coin::value() } }",
+            ),
+        ],
     );
-    init_stub_registry_file(&package_path, &server.base_url());
-    let relative_package_path = PathBuf::from(&package_path);
-    let absolute_package_path =
-        files::path_to_string(&relative_package_path.canonicalize().unwrap()).unwrap();
+    let build = || {
+        let output = Command::new(env!("CARGO_BIN_EXE_move"))
+            .arg("-p")
+            .arg(package.path())
+            .arg("build")
+            .output()
+            .unwrap();
+        let stderr = String::from_utf8_lossy(&output.stderr).to_string();
+        assert!(output.status.success(), "{}", stderr);
+        assert!(
+            stderr
+                .contains("Built package Artifacts: 2 modules, 0 scripts in build/Artifacts, took"),
+            "{}",
+            stderr
+        );
+        fs::read_to_string(
+            package
+                .path()
+                .join("build")
+                .join("Artifacts")
+                .join("artifact-manifest.json"),
+        )
+        .unwrap()
+    };
 
-    let cli_exe = env!("CARGO_BIN_EXE_move");
-    let output = Command::new(cli_exe)
-        .env("MOVE_HOME", &absolute_package_path)
-        .current_dir(&absolute_package_path)
-        .args(["movey-upload"])
-        .output()
-        .unwrap();
+    let contents = build();
+    let manifest: ArtifactManifest = serde_json::from_str(&contents).unwrap();
+    assert_eq!(manifest.package.as_str(), "Artifacts");
+    let build_dir = package.path().join("build").join("Artifacts");
+    for artifact in &manifest.files {
+        let bytes = fs::read(build_dir.join(&artifact.path)).unwrap();
+        assert_eq!(bytes.len() as u64, artifact.size, "{}", artifact.path);
+        assert_eq!(
+            format!("{:x}", Sha256::digest(&bytes)),
+            artifact.sha256,
+            "{}",
+            artifact.path
+        );
+    }
+    let listed = |kind: &str| {
+        manifest
+            .files
+            .iter()
+            .filter(|artifact| artifact.kind == kind)
+            .map(|artifact| artifact.path.as_str())
+            .collect::<Vec<_>>()
+    };
+    assert_eq!(
+        listed("bytecode"),
+        ["bytecode_modules/Coin.mv", "bytecode_modules/Wallet.mv"]
+    );
+    assert_eq!(
+        listed("source"),
+        ["sources/Coin.move", "sources/Wallet.move"]
+    );
+    assert_eq!(listed("build_info"), ["BuildInfo.yaml"]);
 
-    server_mock.assert();
-    assert!(!output.status.success());
-    let output = String::from_utf8_lossy(output.stderr.as_slice()).to_string();
+    // rebuilding the same sources yields the same manifest
+    assert_eq!(build(), contents);
+}
+
+// Interrupting a build while a git dependency is being cloned leaves no partial download behind,
+// so the next build clones it again from scratch.
+#[cfg(unix)]
+const COVERAGE_PACKAGE_PATH: &str = "./tests/sandbox_tests/package_basics";
+#[test]
+fn test_with_coverage_prints_summary_after_test_results() {
+    let install_dir = tempfile::tempdir().unwrap();
+    let run = |args: &[&str]| {
+        Command::new(env!("CARGO_BIN_EXE_move"))
+            .args(args)
+            .args(["-p", COVERAGE_PACKAGE_PATH, "--install-dir"])
+            .arg(install_dir.path())
+            .output()
+            .unwrap()
+    };
+
+    let output = run(&["test", "--coverage", "--coverage-format", "lcov"]);
     assert!(
-        output.contains("Error: An unexpected error occurred. Please try again later"),
+        output.status.success(),
         "{}",
-        output
+        String::from_utf8_lossy(&output.stderr)
+    );
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    let results = stdout.find("Test result: OK").expect(&stdout);
+    let summary = stdout.find("Move Coverage Summary").expect(&stdout);
+    assert!(results < summary, "{}", stdout);
+    assert!(stdout.contains("% Move Coverage: 100.00"), "{}", stdout);
+
+    let coverage_dir = install_dir.path().join("build").join("coverage");
+    let lcov = fs::read_to_string(coverage_dir.join("lcov.info")).unwrap();
+    assert!(lcov.contains("FNDA:6,double_except_three"), "{}", lcov);
+
+    // the standalone coverage commands read the map written by the test run
+    let output = run(&["coverage", "summary"]);
+    assert!(
+        output.status.success(),
+        "{}",
+        String::from_utf8_lossy(&output.stderr)
     );
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    assert!(stdout.contains("% Move Coverage: 100.00"), "{}", stdout);
 
-    clean_up(&absolute_package_path);
+    let output = run(&["test", "--coverage-format", "html"]);
+    assert!(!output.status.success());
 }
 
 #[test]
-fn upload_package_to_movey_with_no_remote_should_panic() {
-    let package_path = format!("{}/no_git_remote_package", UPLOAD_PACKAGE_PATH);
-    init_git(&package_path, false);
-
-    let cli_exe = env!("CARGO_BIN_EXE_move");
-    let output = Command::new(cli_exe)
-        .current_dir(&package_path)
-        .args(["movey-upload"])
-        .output()
-        .unwrap();
+fn coverage_reports_need_source_maps() {
+    let install_dir = tempfile::tempdir().unwrap();
+    let run = |args: &[&str]| {
+        Command::new(env!("CARGO_BIN_EXE_move"))
+            .args(args)
+            .args([
+                "-p",
+                COVERAGE_PACKAGE_PATH,
+                "--debug-info",
+                "none",
+                "--install-dir",
+            ])
+            .arg(install_dir.path())
+            .output()
+            .unwrap()
+    };
 
+    let output = run(&["test", "--coverage", "--coverage-format", "lcov"]);
     assert!(!output.status.success());
-    let error = String::from_utf8_lossy(output.stderr.as_slice()).to_string();
-    assert!(error.contains("invalid git repository"));
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(
+        stderr.contains("The LCOV coverage report needs source maps")
+            && stderr.contains("`--debug-info none`"),
+        "{}",
+        stderr
+    );
 
-    clean_up(&package_path);
+    // the summary only needs the bytecode
+    let output = run(&["coverage", "summary"]);
+    assert!(
+        output.status.success(),
+        "{}",
+        String::from_utf8_lossy(&output.stderr)
+    );
+    let output = run(&["coverage", "source", "--module", "AModule"]);
+    assert!(!output.status.success());
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(
+        stderr.contains("Source coverage needs source maps"),
+        "{}",
+        stderr
+    );
 }
 
-// is_valid == true: all git commands are run
-// is_valid == false: missing git remote add command
-fn init_git(package_path: &str, is_valid: bool) {
-    Command::new("git")
-        .current_dir(package_path)
-        .args(&["init"])
-        .output()
-        .unwrap();
-    Command::new("git")
-        .current_dir(package_path)
-        .args(&["add", "."])
-        .output()
-        .unwrap();
-    if is_valid {
-        Command::new("git")
-            .current_dir(package_path)
-            .args(&[
-                "remote",
-                "add",
-                "test-origin",
-                "git@github.com:move-language/move.git",
-            ])
-            .output()
-            .unwrap();
-        Command::new("git")
-            .current_dir(package_path)
-            .args(&["config", "user.email", "\"you@example.com\""])
-            .output()
-            .unwrap();
-        Command::new("git")
-            .current_dir(package_path)
-            .args(&["config", "user.name", "\"Your Name\""])
+#[test]
+fn coverage_checks_the_version_of_the_build_artifacts() {
+    let install_dir = tempfile::tempdir().unwrap();
+    let run = |args: &[&str]| {
+        let output = Command::new(env!("CARGO_BIN_EXE_move"))
+            .args(args)
+            .args(["-p", COVERAGE_PACKAGE_PATH, "--install-dir"])
+            .arg(install_dir.path())
             .output()
             .unwrap();
-        Command::new("git")
-            .current_dir(package_path)
-            .args(&["commit", "--allow-empty", "-m", "initial commit"])
+        let stderr = String::from_utf8_lossy(&output.stderr).to_string();
+        (output.status.success(), stderr)
+    };
+    let (success, stderr) = run(&["test", "--coverage"]);
+    assert!(success, "{}", stderr);
+
+    // pretend the artifacts were built by another version of Move
+    let build_info_path = install_dir
+        .path()
+        .join("build")
+        .join("PackageBasics")
+        .join("BuildInfo.yaml");
+    let restamp = |version: &str| {
+        let mut build_info: serde_yaml::Value =
+            serde_yaml::from_str(&fs::read_to_string(&build_info_path).unwrap()).unwrap();
+        build_info["compiled_package_info"]["version_stamp"]["move_tooling_version"] =
+            version.into();
+        fs::write(
+            &build_info_path,
+            serde_yaml::to_string(&build_info).unwrap(),
+        )
+        .unwrap();
+    };
+    let current = format!("Move {} (bytecode version", env!("CARGO_PKG_VERSION"));
+
+    restamp("0.999.0");
+    let (success, stderr) = run(&["coverage", "summary"]);
+    assert!(success, "{}", stderr);
+    assert!(
+        stderr.contains("Warning: the artifacts in")
+            && stderr.contains("were built by Move 0.999.0 (bytecode version")
+            && stderr.contains(&current),
+        "{}",
+        stderr
+    );
+
+    restamp("999.0.0");
+    let (success, stderr) = run(&["coverage", "summary"]);
+    assert!(!success);
+    assert!(
+        stderr.contains("were built by Move 999.0.0 (bytecode version")
+            && stderr.contains(&current)
+            && stderr.contains("pass --ignore-version-mismatch to use them anyway"),
+        "{}",
+        stderr
+    );
+
+    let (success, stderr) = run(&["coverage", "summary", "--ignore-version-mismatch"]);
+    assert!(success, "{}", stderr);
+    assert!(
+        stderr.contains("Warning: using the artifacts in")
+            && stderr.contains("built by Move 999.0.0"),
+        "{}",
+        stderr
+    );
+}
+
+#[test]
+fn coverage_summary_fail_under_leaves_out_exemptions() {
+    let package = tempfile::tempdir().unwrap();
+    let math = "module 0x2::Math {\n    public fun double(x: u64): u64 { x * 2 }\n\n    \
+                public fun triple_v1(x: u64): u64 { x * 3 }\n\n    \
+                #[test]\n    fun test_double() { assert!(double(2) == 4, 0) }\n}";
+    let shim = "module 0x2::Shim {\n    public fun call(): u64 { 7 }\n}";
+    write_package(
+        package.path(),
+        "Pkg",
+        &stdlib_dependency(),
+        &[("Math", math), ("Shim", shim)],
+    );
+    let run = |args: &[&str]| {
+        let output = Command::new(env!("CARGO_BIN_EXE_move"))
+            .current_dir(package.path())
+            .args(args)
             .output()
             .unwrap();
-    }
+        (
+            output.status.success(),
+            String::from_utf8_lossy(&output.stdout).to_string(),
+            String::from_utf8_lossy(&output.stderr).to_string(),
+        )
+    };
+    let (success, stdout, stderr) = run(&["test", "--coverage"]);
+    assert!(success, "{}\n{}", stdout, stderr);
+
+    let (success, _, stderr) = run(&["coverage", "summary", "--fail-under", "90"]);
+    assert!(!success);
+    assert!(stderr.contains("below the required 90.00%"), "{}", stderr);
+
+    fs::write(
+        package.path().join("coverage-exemptions.toml"),
+        "[[exemption]]\nmodule = \"Shim\"\nreason = \"Only calls into the host\"\n\n\
+         [[exemption]]\nmodule = \"Math\"\nfunction = \"triple_v1\"\n\
+         reason = \"Kept for upgrade compatibility\"\n\n\
+         [[exemption]]\nmodule = \"Math\"\nfunction = \"triple_v0\"\nreason = \"Removed\"\n",
+    )
+    .unwrap();
+    let (success, stdout, stderr) = run(&["coverage", "summary", "--fail-under", "90"]);
+    assert!(success, "{}", stderr);
+    assert!(stdout.contains("% Move Coverage: 100.00"), "{}", stdout);
+    // exempted modules and functions are shown, with their coverage, rather than left out
+    assert!(
+        stdout.contains("Exempted Shim: 0 of ")
+            && stdout.contains("instructions covered (Only calls into the host)"),
+        "{}",
+        stdout
+    );
+    assert!(
+        stdout.contains("Exempted Math::triple_v1: 0 of "),
+        "{}",
+        stdout
+    );
+    assert!(!stdout.contains("Exempted Math::triple_v0"), "{}", stdout);
+    assert!(
+        stderr.contains(
+            "Warning: coverage-exemptions.toml exempts Math::triple_v0, which the package does \
+             not define"
+        ),
+        "{}",
+        stderr
+    );
+
+    fs::write(
+        package.path().join("coverage-exemptions.toml"),
+        "[[exemption]]\nmodule = \"Shim\"\nreason = \" \"\n",
+    )
+    .unwrap();
+    let (success, _, stderr) = run(&["coverage", "summary"]);
+    assert!(!success);
+    assert!(stderr.contains("The exemption of Shim in"), "{}", stderr);
+    assert!(stderr.contains("needs a reason"), "{}", stderr);
 }
+
 #[test]
-fn save_credential_works() {
-    let cli_exe = env!("CARGO_BIN_EXE_move");
-    let (move_home, credential_path) = setup_move_home("/save_credential_works");
-    assert!(fs::read_to_string(&credential_path).is_err());
+fn coverage_refuses_a_stale_build() {
+    let package = tempfile::tempdir().unwrap();
+    let math = "module 0x2::Math {\n    public fun double(x: u64): u64 { x * 2 }\n\n    \
+                #[test]\n    fun test_double() { assert!(double(2) == 4, 0) }\n}";
+    write_package(
+        package.path(),
+        "Pkg",
+        &stdlib_dependency(),
+        &[("Math", math)],
+    );
+    let run = |args: &[&str]| {
+        let output = Command::new(env!("CARGO_BIN_EXE_move"))
+            .current_dir(package.path())
+            .args(args)
+            .output()
+            .unwrap();
+        let stderr = String::from_utf8_lossy(&output.stderr).to_string();
+        (output.status.success(), stderr)
+    };
+    let (success, stderr) = run(&["test", "--coverage"]);
+    assert!(success, "{}", stderr);
 
-    match Command::new(cli_exe)
-        .env("MOVE_HOME", &move_home)
-        .current_dir(".")
-        .args(["movey-login"])
-        .stdin(Stdio::piped())
-        .stdout(Stdio::piped())
-        .spawn()
-    {
-        Ok(child) => {
-            let token = "test_token";
-            child
-                .stdin
-                .as_ref()
-                .unwrap()
-                .write_all(token.as_bytes())
-                .unwrap();
-            match child.wait_with_output() {
-                Ok(output) => {
-                    assert!(String::from_utf8_lossy(&output.stdout).contains(&format!(
-                        "Please paste the API Token found on {}/settings/tokens below",
-                        MOVEY_URL
-                    )));
-                    Ok(())
-                }
-                Err(error) => Err(error),
-            }
-        }
-        Err(error) => Err(error),
-    }
+    // rewriting a file without changing it does not make the build stale
+    let math_path = package.path().join("sources").join("Math.move");
+    fs::write(&math_path, format!("{}\n", math)).unwrap();
+    let (success, stderr) = run(&["coverage", "summary"]);
+    assert!(success, "{}", stderr);
+
+    fs::write(&math_path, math.replace("x * 2", "x + x")).unwrap();
+    fs::write(
+        package.path().join("sources").join("Extra.move"),
+        "module 0x2::Extra {}",
+    )
     .unwrap();
+    let (success, stderr) = run(&["coverage", "summary", "-v"]);
+    assert!(!success);
+    assert!(
+        stderr.contains(
+            "build is stale: 2 files changed since last build (run move test --coverage)"
+        ),
+        "{}",
+        stderr
+    );
+    assert!(
+        stderr.contains("added: sources/Extra.move")
+            && stderr.contains("modified: sources/Math.move"),
+        "{}",
+        stderr
+    );
 
-    let contents = fs::read_to_string(&credential_path).expect("Unable to read file");
-    let mut toml: Value = contents.parse().unwrap();
-    let registry = toml.as_table_mut().unwrap().get_mut("registry").unwrap();
-    let token = registry.as_table_mut().unwrap().get_mut("token").unwrap();
-    assert!(token.to_string().contains("test_token"));
+    let (success, stderr) = run(&["coverage", "summary", "--allow-stale"]);
+    assert!(success, "{}", stderr);
+    assert!(
+        stderr.contains("Warning: using the stale artifacts in")
+            && stderr.contains("2 files changed since last build"),
+        "{}",
+        stderr
+    );
+    assert!(
+        !stderr.contains("modified: sources/Math.move"),
+        "{}",
+        stderr
+    );
+}
 
-    let _ = fs::remove_dir_all(move_home);
+fn package_clean(package: &Path, args: &[&str]) -> (bool, String, String) {
+    let output = Command::new(env!("CARGO_BIN_EXE_move"))
+        .arg("-p")
+        .arg(package)
+        .args(["package", "clean"])
+        .args(args)
+        .output()
+        .unwrap();
+    (
+        output.status.success(),
+        String::from_utf8_lossy(&output.stdout).to_string(),
+        String::from_utf8_lossy(&output.stderr).to_string(),
+    )
 }
 
-#[cfg(unix)]
 #[test]
-fn save_credential_fails_if_undeletable_credential_file_exists() {
-    let cli_exe = env!("CARGO_BIN_EXE_move");
-    let (move_home, credential_path) =
-        setup_move_home("/save_credential_fails_if_undeletable_credential_file_exists");
-    let file = File::create(&credential_path).unwrap();
-    let mut perms = file.metadata().unwrap().permissions();
-    perms.set_mode(0o000);
-    file.set_permissions(perms).unwrap();
+fn package_clean_removes_the_requested_outputs() {
+    let package = tempfile::tempdir().unwrap();
+    let root = package.path();
+    write_package(root, "Clean", "", &[("Coin", "module 0x2::Coin {}")]);
+    fs::create_dir_all(root.join("build").join("Clean")).unwrap();
+    fs::write(
+        root.join("build").join("Clean").join("BuildInfo.yaml"),
+        "0123456789",
+    )
+    .unwrap();
+    fs::create_dir_all(root.join("doc")).unwrap();
+    fs::write(root.join("doc").join("Coin.md"), "# Coin").unwrap();
+    fs::write(root.join("doc").join("index.md"), "# Clean").unwrap();
+    fs::write(root.join("doc").join("overview.md"), "Hand written").unwrap();
+    fs::write(root.join(".coverage_map.mvcov"), "map").unwrap();
+    fs::create_dir_all(root.join("storage")).unwrap();
+    fs::write(root.join("storage").join("state"), "state").unwrap();
+
+    // a dry run lists everything in scope and removes nothing
+    let (success, stdout, stderr) = package_clean(root, &["--all", "--dry-run"]);
+    assert!(success, "{}", stderr);
+    assert_eq!(
+        stdout,
+        "Would remove build (10 B)\n\
+         Would remove doc/Coin.md (6 B)\n\
+         Would remove doc/index.md (7 B)\n\
+         Would remove .coverage_map.mvcov (3 B)\n\
+         Would free 26 B\n"
+            .replace('/', std::path::MAIN_SEPARATOR.to_string().as_str())
+    );
+    assert!(root.join("build").exists() && root.join("doc").join("Coin.md").exists());
+
+    // only `build` by default
+    let (success, stdout, stderr) = package_clean(root, &[]);
+    assert!(success, "{}", stderr);
+    assert_eq!(stdout, "Removed build (10 B)\nFreed 10 B\n");
+    assert!(!root.join("build").exists());
+    assert!(root.join("doc").join("Coin.md").exists());
+    assert!(root.join(".coverage_map.mvcov").exists());
+
+    let (success, _, stderr) = package_clean(root, &["--docs"]);
+    assert!(success, "{}", stderr);
+    assert!(!root.join("doc").join("Coin.md").exists());
+    assert!(!root.join("doc").join("index.md").exists());
+    assert!(root.join("doc").join("overview.md").exists());
+    assert!(root.join(".coverage_map.mvcov").exists());
+
+    let (success, _, stderr) = package_clean(root, &["--coverage"]);
+    assert!(success, "{}", stderr);
+    assert!(!root.join(".coverage_map.mvcov").exists());
+
+    // the sandbox state is left to `move sandbox clean`
+    assert!(root.join("storage").join("state").exists());
+}
+
+#[test]
+fn toolchain_requirement_is_checked_on_every_command() {
+    let workspace = tempfile::tempdir().unwrap();
+    let package = workspace.path().join("Pinned");
+    write_package(&package, "Pinned", "", &[("M", "module 0x2::M {}")]);
+    let toolchain_file = workspace.path().join("move-toolchain.toml");
+    let show = || {
+        let output = Command::new(env!("CARGO_BIN_EXE_move"))
+            .arg("-p")
+            .arg(&package)
+            .args(["toolchain", "show"])
+            .output()
+            .unwrap();
+        assert!(output.status.success());
+        String::from_utf8_lossy(&output.stdout).to_string()
+    };
+
+    let stdout = show();
+    assert!(stdout.contains("Required version: none"), "{}", stdout);
+
+    // satisfied by any version of the CLI
+    fs::write(&toolchain_file, "[toolchain]\nversion = \">=0.0.1\"\n").unwrap();
+    let (success, _, stderr) = package_clean(&package, &["--dry-run"]);
+    assert!(success, "{}", stderr);
+    assert!(!stderr.contains("Warning"), "{}", stderr);
+    let stdout = show();
+    assert!(
+        stdout.contains(&format!(
+            "Required version: >=0.0.1, set in '{}'",
+            toolchain_file.canonicalize().unwrap().display()
+        )),
+        "{}",
+        stdout
+    );
+    assert!(stdout.contains("Status: satisfied"), "{}", stdout);
+
+    // satisfied by no version of the CLI: a warning, unless strict
+    fs::write(&toolchain_file, "[toolchain]\nversion = \"<0.0.1\"\n").unwrap();
+    let (success, _, stderr) = package_clean(&package, &["--dry-run"]);
+    assert!(success, "{}", stderr);
+    assert!(
+        stderr.contains("Warning: This project requires Move CLI <0.0.1"),
+        "{}",
+        stderr
+    );
+
+    // the `[toolchain]` section of the manifest takes precedence over the file of a parent
+    let manifest = fs::read_to_string(package.join("Move.toml")).unwrap();
+    fs::write(
+        package.join("Move.toml"),
+        format!(
+            "{}\n[toolchain]\nversion = \"<0.0.1\"\nstrict = true\n",
+            manifest
+        ),
+    )
+    .unwrap();
+    let (success, _, stderr) = package_clean(&package, &["--dry-run"]);
+    assert!(!success);
+    assert!(
+        stderr.contains("This project requires Move CLI <0.0.1")
+            && stderr.contains("Move.toml")
+            && stderr.contains("set `strict = false` to only warn"),
+        "{}",
+        stderr
+    );
+    let stdout = show();
+    assert!(stdout.contains("(strict)"), "{}", stdout);
+    assert!(stdout.contains("Status: not satisfied"), "{}", stdout);
+}
+
+#[cfg(unix)]
+#[test]
+fn package_clean_refuses_to_remove_files_outside_of_the_package() {
+    let package = tempfile::tempdir().unwrap();
+    let outside = tempfile::tempdir().unwrap();
+    let root = package.path();
+    write_package(root, "Clean", "", &[("Coin", "module 0x2::Coin {}")]);
+    fs::write(outside.path().join("precious"), "keep me").unwrap();
+    std::os::unix::fs::symlink(outside.path(), root.join("build")).unwrap();
+
+    let (success, _, stderr) = package_clean(root, &[]);
+    assert!(!success);
+    assert!(
+        stderr.contains("Refusing to remove 'build'")
+            && stderr.contains("outside of the package root"),
+        "{}",
+        stderr
+    );
+    assert!(outside.path().join("precious").exists());
+    assert!(root.join("build").exists());
+
+    // links inside `build` are removed without touching what they point to
+    fs::remove_file(root.join("build")).unwrap();
+    fs::create_dir_all(root.join("build")).unwrap();
+    std::os::unix::fs::symlink(outside.path(), root.join("build").join("link")).unwrap();
+    let (success, _, stderr) = package_clean(root, &[]);
+    assert!(success, "{}", stderr);
+    assert!(!root.join("build").exists());
+    assert!(outside.path().join("precious").exists());
+}
+
+#[test]
+fn warn_diff_compares_with_a_git_revision() {
+    let repo = tempfile::tempdir().unwrap();
+    let root = repo.path().join("counter");
+    write_package(
+        &root,
+        "Counter",
+        "",
+        &[(
+            "Counter",
+            "module 0x2::Counter {\n    \
+             public fun reset(x: &mut u64, hard: bool) { *x = 0 }\n    \
+             public fun add(x: &mut u64, n: u64, verbose: bool) { *x = *x + n }\n}",
+        )],
+    );
+    run_git(repo.path(), &["init"]);
+    run_git(repo.path(), &["add", "."]);
+    run_git(repo.path(), &["commit", "-m", "baseline"]);
+    // `reset` moves below `add`, which loses a warning and gains another one
+    write_package(
+        &root,
+        "Counter",
+        "",
+        &[(
+            "Counter",
+            "module 0x2::Counter {\n    \
+             public fun add(x: &mut u64, n: u64, step: u64) { *x = *x + n }\n    \
+             public fun reset(x: &mut u64, hard: bool) { *x = 0 }\n}",
+        )],
+    );
+
+    let output = Command::new(env!("CARGO_BIN_EXE_move"))
+        .arg("-p")
+        .arg(&root)
+        .args([
+            "package",
+            "warn-diff",
+            "--baseline-rev",
+            "HEAD",
+            "--deny-new",
+        ])
+        .output()
+        .unwrap();
+    assert!(!output.status.success());
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    assert!(
+        stdout.contains(
+            "New warnings:\n    W09002 0x2::Counter::add: unused variable: Unused parameter 'step'"
+        ),
+        "{}",
+        stdout
+    );
+    assert!(
+        stdout.contains("Fixed warnings:\n    W09002 0x2::Counter::add: unused variable: Unused parameter 'verbose'"),
+        "{}",
+        stdout
+    );
+    assert!(
+        stdout.ends_with("1 new, 1 fixed, 1 unchanged\n"),
+        "{}",
+        stdout
+    );
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(stderr.contains("--deny-new is set"), "{}", stderr);
+
+    // the temporary worktree of the baseline is removed
+    let worktrees = Command::new("git")
+        .current_dir(repo.path())
+        .args(["worktree", "list"])
+        .output()
+        .unwrap();
+    assert_eq!(
+        String::from_utf8_lossy(&worktrees.stdout).lines().count(),
+        1
+    );
+}
+
+#[test]
+fn diff_abi_compares_with_a_git_revision() {
+    let repo = tempfile::tempdir().unwrap();
+    let root = repo.path().join("counter");
+    write_package(
+        &root,
+        "Counter",
+        "",
+        &[(
+            "Counter",
+            "module 0x2::Counter {\n    \
+             struct Counter has store { value: u64 }\n    \
+             public fun reset(c: &mut Counter) { c.value = 0 }\n}",
+        )],
+    );
+    commit_git_repo(repo.path());
+    // `Counter` gains an ability, and `reset` a parameter
+    write_package(
+        &root,
+        "Counter",
+        "",
+        &[(
+            "Counter",
+            "module 0x2::Counter {\n    \
+             struct Counter has drop, store { value: u64 }\n    \
+             public fun reset(c: &mut Counter, to: u64) { c.value = to }\n}",
+        )],
+    );
+
+    let diff_abi = |args: &[&str]| {
+        Command::new(env!("CARGO_BIN_EXE_move"))
+            .arg("-p")
+            .arg(&root)
+            .args(["package", "diff-abi", "--baseline-rev", "HEAD"])
+            .args(args)
+            .output()
+            .unwrap()
+    };
+    let output = diff_abi(&[]);
+    assert!(!output.status.success());
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    assert!(
+        stdout.ends_with("1 breaking, 1 non-breaking changes\n"),
+        "{}",
+        stdout
+    );
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(stderr.contains("pass --allow-breaking"), "{}", stderr);
+
+    let output = diff_abi(&["--format", "json", "--allow-breaking"]);
+    assert!(output.status.success());
+    let report: serde_json::Value = serde_json::from_slice(&output.stdout).unwrap();
+    let kinds: Vec<_> = report["changes"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|change| change["kind"].as_str().unwrap())
+        .collect();
+    assert_eq!(kinds, ["changed_signature", "added_ability"]);
+}
+
+#[test]
+fn interrupted_git_download_is_cleaned_up() {
+    let workspace = tempfile::tempdir().unwrap();
+    let dep_path = workspace.path().join("Dep");
+    write_package(
+        &dep_path,
+        "Dep",
+        "",
+        &[("Dep", "module 0x2::Dep { public fun f() {} }")],
+    );
+    commit_git_repo(&dep_path);
+
+    let write_root_package = |git_url: &str| {
+        let root_path = workspace.path().join("Root");
+        let dependencies = format!("Dep = {{ git = \"{}\", rev = \"main\" }}\n", git_url);
+        write_package(&root_path, "Root", &dependencies, &[]);
+        root_path
+    };
+    let move_home = workspace.path().join("move_home");
+    let cli_exe = env!("CARGO_BIN_EXE_move");
+
+    // serve the dependency through a transport that stalls before sending anything, so that the
+    // clone is still in progress when the build is interrupted
+    let slow_url = format!("ext::sh -c sleep% 30;% %S% {}", dep_path.display());
+    let root_path = write_root_package(&slow_url);
+    let mut child = Command::new(cli_exe)
+        .args(["build", "-p"])
+        .arg(&root_path)
+        .env("MOVE_HOME", &move_home)
+        .env("GIT_ALLOW_PROTOCOL", "ext")
+        .stdout(Stdio::null())
+        .stderr(Stdio::null())
+        .spawn()
+        .unwrap();
+    let has_partial_download = || {
+        fs::read_dir(&move_home).map_or(false, |mut entries| {
+            entries.any(|entry| entry.unwrap().path().extension() == Some("partial".as_ref()))
+        })
+    };
+    let start = std::time::Instant::now();
+    while !has_partial_download() {
+        assert!(
+            start.elapsed() < std::time::Duration::from_secs(20),
+            "clone never started"
+        );
+        std::thread::sleep(std::time::Duration::from_millis(50));
+    }
+    assert!(Command::new("kill")
+        .args(["-INT", &child.id().to_string()])
+        .status()
+        .unwrap()
+        .success());
+    let status = child.wait().unwrap();
+    assert_eq!(status.code(), Some(130));
+    assert!(!has_partial_download());
+    assert_eq!(fs::read_dir(&move_home).unwrap().count(), 0);
+
+    let root_path = write_root_package(&format!("file://{}", dep_path.display()));
+    let output = Command::new(cli_exe)
+        .args(["build", "-p"])
+        .arg(&root_path)
+        .env("MOVE_HOME", &move_home)
+        .output()
+        .unwrap();
+    assert!(
+        output.status.success(),
+        "{}",
+        String::from_utf8_lossy(&output.stderr)
+    );
+}
+
+// A git dependency whose sources live in a (nested) submodule is built with the submodule checked
+// out, unless submodules are opted out of.
+#[test]
+fn git_dependency_with_submodules() {
+    let workspace = tempfile::tempdir().unwrap();
+    let inner_path = workspace.path().join("Inner");
+    write_package(
+        &inner_path,
+        "Inner",
+        "",
+        &[("Inner", "module 0x2::Inner { public fun g() {} }")],
+    );
+    commit_git_repo(&inner_path);
+    let sub_path = workspace.path().join("Sub");
+    write_package(
+        &sub_path,
+        "Sub",
+        "",
+        &[(
+            "Sub",
+            "module 0x2::Sub { public fun f() { 0x2::Inner::g() } }",
+        )],
+    );
+    commit_git_repo(&sub_path);
+    add_submodule(&sub_path, &inner_path, "sources/inner");
+    let super_path = workspace.path().join("Super");
+    write_package(
+        &super_path,
+        "Super",
+        "",
+        &[(
+            "Super",
+            "module 0x2::Super { public fun h() { 0x2::Sub::f() } }",
+        )],
+    );
+    commit_git_repo(&super_path);
+    add_submodule(&super_path, &sub_path, "sources/sub");
+
+    let root_path = workspace.path().join("Root");
+    write_package(
+        &root_path,
+        "Root",
+        &format!(
+            "Super = {{ git = \"file://{}\", rev = \"main\" }}\n",
+            super_path.display()
+        ),
+        &[],
+    );
+
+    let build = |move_home: &str, extra_args: &[&str]| {
+        Command::new(env!("CARGO_BIN_EXE_move"))
+            .args(["build", "-p"])
+            .arg(&root_path)
+            .args(extra_args)
+            .env("MOVE_HOME", workspace.path().join(move_home))
+            .envs(ALLOW_FILE_PROTOCOL)
+            .output()
+            .unwrap()
+    };
+    let output = build("move_home", &[]);
+    assert!(
+        output.status.success(),
+        "{}",
+        String::from_utf8_lossy(&output.stderr)
+    );
+
+    let output = build("move_home_without_submodules", &["--no-submodules"]);
+    assert!(!output.status.success());
+    let error = String::from_utf8_lossy(&output.stderr).to_string();
+    assert!(error.contains("Unbound module '0x2::Sub'"), "{}", error);
+}
+
+// Local submodules are only cloned by git when the file protocol is explicitly allowed
+const ALLOW_FILE_PROTOCOL: [(&str, &str); 3] = [
+    ("GIT_CONFIG_COUNT", "1"),
+    ("GIT_CONFIG_KEY_0", "protocol.file.allow"),
+    ("GIT_CONFIG_VALUE_0", "always"),
+];
+
+// A missing or outdated git is reported before anything is fetched or uploaded
+#[cfg(unix)]
+#[test]
+fn git_must_be_installed_and_recent_enough() {
+    let workspace = tempfile::tempdir().unwrap();
+    let root_path = workspace.path().join("Root");
+    write_package(
+        &root_path,
+        "Root",
+        "Dep = { git = \"file:///nonexistent\", rev = \"main\" }\n",
+        &[],
+    );
+    let run = |path: &Path, args: &[&str]| {
+        let output = Command::new(env!("CARGO_BIN_EXE_move"))
+            .current_dir(&root_path)
+            .args(args)
+            .env("MOVE_HOME", workspace.path().join("move_home"))
+            .env("PATH", path)
+            .output()
+            .unwrap();
+        assert!(!output.status.success());
+        String::from_utf8_lossy(&output.stderr).to_string()
+    };
+
+    let no_git = workspace.path().join("empty");
+    fs::create_dir_all(&no_git).unwrap();
+    for args in [&["build"][..], &["movey-upload"]] {
+        let error = run(&no_git, args);
+        assert!(
+            error.contains(
+                "git >= 2.0 is required to download dependencies and publish packages, but it \
+                 was not found on PATH"
+            ),
+            "{}",
+            error
+        );
+    }
+
+    let old_git = fake_git(&workspace.path().join("old"), "1.7.1");
+    let error = run(&old_git, &["build"]);
+    assert!(
+        error.contains(&format!(
+            "git >= 2.0 required to download dependencies and publish packages; found 1.7.1 at {}",
+            old_git.join("git").display()
+        )),
+        "{}",
+        error
+    );
+}
+
+// Only the subdirectory of a git dependency is checked out, unless git is too old to support
+// sparse checkout
+#[cfg(unix)]
+#[test]
+fn git_dependency_in_subdirectory_is_checked_out_sparsely() {
+    let workspace = tempfile::tempdir().unwrap();
+    let repo_path = workspace.path().join("Repo");
+    write_package(
+        &repo_path.join("packages").join("Dep"),
+        "Dep",
+        "",
+        &[("Dep", "module 0x2::Dep { public fun f() {} }")],
+    );
+    write_package(&repo_path.join("packages").join("Other"), "Other", "", &[]);
+    commit_git_repo(&repo_path);
+    let root_path = workspace.path().join("Root");
+    write_package(
+        &root_path,
+        "Root",
+        &format!(
+            "Dep = {{ git = \"file://{}\", rev = \"main\", subdir = \"packages/Dep\" }}\n",
+            repo_path.display()
+        ),
+        &[],
+    );
+
+    let build = |move_home: &str, git_dir: Option<&Path>| {
+        let path = git_dir.map_or_else(|| std::env::var_os("PATH").unwrap_or_default(), path_with);
+        let move_home = workspace.path().join(move_home);
+        let output = Command::new(env!("CARGO_BIN_EXE_move"))
+            .args(["build", "-p"])
+            .arg(&root_path)
+            .env("MOVE_HOME", &move_home)
+            .env("PATH", path)
+            .envs(ALLOW_FILE_PROTOCOL)
+            .output()
+            .unwrap();
+        assert!(
+            output.status.success(),
+            "{}",
+            String::from_utf8_lossy(&output.stderr)
+        );
+        fs::read_dir(&move_home)
+            .unwrap()
+            .map(|entry| entry.unwrap().path())
+            .find(|path| path.join("packages").is_dir())
+            .unwrap()
+    };
+
+    let checkout = build("move_home", None);
+    assert!(checkout.join("packages/Dep/sources/Dep.move").exists());
+    assert!(!checkout.join("packages/Other").exists());
+
+    let checkout = build(
+        "move_home_old_git",
+        Some(&fake_git(&workspace.path().join("old"), "2.20.1")),
+    );
+    assert!(checkout.join("packages/Dep/sources/Dep.move").exists());
+    assert!(checkout.join("packages/Other/Move.toml").exists());
+}
+
+// A dependency pinned to a commit that a force push dropped from its repository fails to download
+// with an explanation, rather than with the error of `git checkout`.
+#[test]
+fn git_dependency_on_a_commit_removed_by_a_force_push() {
+    let workspace = tempfile::tempdir().unwrap();
+    let dep_path = workspace.path().join("Dep");
+    write_package(
+        &dep_path,
+        "Dep",
+        "",
+        &[("Dep", "module 0x2::Dep { public fun f() {} }")],
+    );
+    commit_git_repo(&dep_path);
+    let head = |repo_path: &Path| {
+        let output = Command::new("git")
+            .current_dir(repo_path)
+            .args(["rev-parse", "HEAD"])
+            .output()
+            .unwrap();
+        String::from_utf8(output.stdout).unwrap().trim().to_string()
+    };
+    let pinned = head(&dep_path);
+    let bare_path = workspace.path().join("Dep.git");
+    run_git(
+        workspace.path(),
+        &["clone", "-q", "--bare", "Dep", "Dep.git"],
+    );
+    let root_path = workspace.path().join("Root");
+    write_package(
+        &root_path,
+        "Root",
+        &format!(
+            "Dep = {{ git = \"file://{}\", rev = \"{}\" }}\n",
+            bare_path.display(),
+            pinned
+        ),
+        &[],
+    );
+    let build = |move_home: &str| {
+        Command::new(env!("CARGO_BIN_EXE_move"))
+            .args(["build", "-p"])
+            .arg(&root_path)
+            .env("MOVE_HOME", workspace.path().join(move_home))
+            .envs(ALLOW_FILE_PROTOCOL)
+            .output()
+            .unwrap()
+    };
+    let output = build("move_home");
+    assert!(
+        output.status.success(),
+        "{}",
+        String::from_utf8_lossy(&output.stderr)
+    );
+    let lockfile = fs::read_to_string(root_path.join("Move.lock")).unwrap();
+    assert!(lockfile.contains(&pinned), "{}", lockfile);
+
+    // rewrite the history of the repository, and drop the pinned commit from it
+    fs::write(dep_path.join("README.md"), "rewritten\n").unwrap();
+    run_git(&dep_path, &["add", "."]);
+    run_git(
+        &dep_path,
+        &["commit", "-q", "--amend", "-m", "rewritten commit"],
+    );
+    run_git(&dep_path, &["push", "-q", "--force", "../Dep.git", "main"]);
+    run_git(&bare_path, &["reflog", "expire", "--expire=now", "--all"]);
+    run_git(&bare_path, &["gc", "-q", "--prune=now"]);
+    assert_ne!(head(&bare_path), pinned);
+
+    // the download in the first MOVE_HOME is still used, but a new one cannot be made
+    let output = build("move_home");
+    assert!(
+        output.status.success(),
+        "{}",
+        String::from_utf8_lossy(&output.stderr)
+    );
+    let output = build("other_move_home");
+    assert!(!output.status.success());
+    let error = String::from_utf8_lossy(&output.stderr);
+    assert!(
+        error.contains(&format!(
+            "Git revision '{}' of package 'Dep' does not exist in file://{}. The commit may have \
+             been removed from the repository by a force push.",
+            pinned,
+            bare_path.display()
+        )),
+        "{}",
+        error
+    );
+    assert!(
+        error.contains("Set the `rev` of 'Dep' in Move.toml"),
+        "{}",
+        error
+    );
+}
+
+// A dependency on a branch is checked out at the commit recorded in the lockfile. Once a force push
+// drops that commit, it is only locked to the new tip of the branch with --repin-missing, or if
+// the user agrees, and never with --locked.
+#[test]
+fn git_dependency_repinned_after_a_force_push() {
+    let workspace = tempfile::tempdir().unwrap();
+    let dep_path = workspace.path().join("Dep");
+    write_package(
+        &dep_path,
+        "Dep",
+        "",
+        &[("Dep", "module 0x2::Dep { public fun f() {} }")],
+    );
+    commit_git_repo(&dep_path);
+    let head = |repo_path: &Path| {
+        let output = Command::new("git")
+            .current_dir(repo_path)
+            .args(["rev-parse", "HEAD"])
+            .output()
+            .unwrap();
+        String::from_utf8(output.stdout).unwrap().trim().to_string()
+    };
+    let locked = head(&dep_path);
+    let bare_path = workspace.path().join("Dep.git");
+    run_git(
+        workspace.path(),
+        &["clone", "-q", "--bare", "Dep", "Dep.git"],
+    );
+    let root_path = workspace.path().join("Root");
+    write_package(
+        &root_path,
+        "Root",
+        &format!(
+            "Dep = {{ git = \"file://{}\", rev = \"main\" }}\n",
+            bare_path.display()
+        ),
+        &[],
+    );
+    let build = |move_home: &str, args: &[&str]| {
+        Command::new(env!("CARGO_BIN_EXE_move"))
+            .args(["build", "-p"])
+            .arg(&root_path)
+            .args(args)
+            .env("MOVE_HOME", workspace.path().join(move_home))
+            .envs(ALLOW_FILE_PROTOCOL)
+            .output()
+            .unwrap()
+    };
+    let output = build("move_home", &[]);
+    assert!(
+        output.status.success(),
+        "{}",
+        String::from_utf8_lossy(&output.stderr)
+    );
+    let lockfile_path = root_path.join("Move.lock");
+    let lockfile = fs::read_to_string(&lockfile_path).unwrap();
+    assert!(lockfile.contains("rev = \"main\""), "{}", lockfile);
+    assert!(
+        lockfile.contains(&format!("commit = \"{}\"", locked)),
+        "{}",
+        lockfile
+    );
+
+    // a new download checks out the locked commit rather than the tip of the branch
+    fs::write(dep_path.join("README.md"), "second commit\n").unwrap();
+    run_git(&dep_path, &["add", "."]);
+    run_git(&dep_path, &["commit", "-q", "-m", "second commit"]);
+    run_git(&dep_path, &["push", "-q", "../Dep.git", "main"]);
+    let output = build("second_move_home", &[]);
+    assert!(
+        output.status.success(),
+        "{}",
+        String::from_utf8_lossy(&output.stderr)
+    );
+    assert_eq!(fs::read_to_string(&lockfile_path).unwrap(), lockfile);
+
+    // rewrite the history of the repository, and drop the locked commit from it
+    run_git(&dep_path, &["reset", "-q", "--hard", "HEAD~"]);
+    fs::write(dep_path.join("README.md"), "rewritten\n").unwrap();
+    run_git(&dep_path, &["add", "."]);
+    run_git(
+        &dep_path,
+        &["commit", "-q", "--amend", "-m", "rewritten commit"],
+    );
+    run_git(&dep_path, &["push", "-q", "--force", "../Dep.git", "main"]);
+    run_git(&bare_path, &["reflog", "expire", "--expire=now", "--all"]);
+    run_git(&bare_path, &["gc", "-q", "--prune=now"]);
+    let tip = head(&bare_path);
+    assert_ne!(tip, locked);
+
+    let missing = format!(
+        "The locked commit {} of package 'Dep' no longer exists in file://{}, most likely because \
+         'main' was force pushed.",
+        locked,
+        bare_path.display()
+    );
+    let output = build("other_move_home", &["--locked"]);
+    assert!(!output.status.success());
+    let error = String::from_utf8_lossy(&output.stderr);
+    assert!(error.contains(&missing), "{}", error);
+    assert!(
+        error.contains("The lockfile cannot be updated with --locked"),
+        "{}",
+        error
+    );
+
+    // there is no one to ask when stdin is not a terminal
+    let output = build("other_move_home", &[]);
+    assert!(!output.status.success());
+    let error = String::from_utf8_lossy(&output.stderr);
+    assert!(error.contains(&missing), "{}", error);
+    assert!(error.contains("Pass --repin-missing"), "{}", error);
+    assert_eq!(fs::read_to_string(&lockfile_path).unwrap(), lockfile);
+
+    let output = build("other_move_home", &["--repin-missing"]);
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(output.status.success(), "{}", stderr);
+    assert!(stderr.contains("REPINNING"), "{}", stderr);
+    assert!(
+        stderr.contains(&format!("old rev: {}", locked)),
+        "{}",
+        stderr
+    );
+    assert!(stderr.contains(&format!("new rev: {}", tip)), "{}", stderr);
+    let lockfile = fs::read_to_string(&lockfile_path).unwrap();
+    assert!(
+        lockfile.contains(&format!("commit = \"{}\"", tip)),
+        "{}",
+        lockfile
+    );
+}
+
+/// A directory holding a `git` script that prints `version` for `git --version`, and passes any
+/// other command to the git installed on the system
+#[cfg(unix)]
+fn fake_git(dir: &Path, version: &str) -> PathBuf {
+    let real_git = Command::new("sh")
+        .args(["-c", "command -v git"])
+        .output()
+        .unwrap();
+    fs::create_dir_all(dir).unwrap();
+    let script = dir.join("git");
+    fs::write(
+        &script,
+        format!(
+            "#!/bin/sh\nif [ \"$1\" = \"--version\" ]; then\n    echo \"git version {}\"\nelse\n    \
+             exec {} \"$@\"\nfi\n",
+            version,
+            String::from_utf8_lossy(&real_git.stdout).trim()
+        ),
+    )
+    .unwrap();
+    fs::set_permissions(&script, fs::Permissions::from_mode(0o755)).unwrap();
+    dir.to_path_buf()
+}
+
+const MOVE_NURSERY_PATH: &str = "../../move-stdlib/nursery";
+const EVENTS_PATH: &str = "storage/0x00000000000000000000000000000002/events/0.bcs";
+#[test]
+fn sandbox_view_pages_through_events_by_sequence_number() {
+    let workspace = tempfile::tempdir().unwrap();
+    let package_path = workspace.path().join("Emitter");
+    let nursery_path = fs::canonicalize(MOVE_NURSERY_PATH).unwrap();
+    write_package(
+        &package_path,
+        "Emitter",
+        &format!(
+            "MoveNursery = {{ local = \"{}\" }}\n\n[addresses]\nstd = \"0x1\"\n",
+            path_to_string(&nursery_path)
+        ),
+        &[
+            (
+                "Emitter",
+                "module 0x2::Emitter {
+    use std::event::{Self, EventHandle};
+    struct Ping has drop, store { i: u64 }
+    struct Pings has key { handle: EventHandle<Ping> }
+    public fun init(account: &signer) {
+        move_to(account, Pings { handle: event::new_event_handle<Ping>(account) })
+    }
+    public fun emit(count: u64) acquires Pings {
+        let pings = borrow_global_mut<Pings>(@0x2);
+        let i = 0;
+        while (i < count) {
+            event::emit_event(&mut pings.handle, Ping { i });
+            i = i + 1;
+        }
+    }
+}",
+            ),
+            (
+                "init",
+                "script { fun init(account: signer) { 0x2::Emitter::init(&account) } }",
+            ),
+            (
+                "emit",
+                "script { fun emit(count: u64) { 0x2::Emitter::emit(count) } }",
+            ),
+        ],
+    );
+    let move_cli = |args: &[&str]| {
+        let output = Command::new(get_cli_binary_path())
+            .current_dir(&package_path)
+            .args(args)
+            .output()
+            .unwrap();
+        assert!(
+            output.status.success(),
+            "{}",
+            String::from_utf8_lossy(&output.stderr)
+        );
+        String::from_utf8(output.stdout).unwrap()
+    };
+    let sequence_numbers = |limit: usize| {
+        let mut sequence_numbers = vec![];
+        let mut from_seq = Some(0);
+        while let Some(seq) = from_seq {
+            let page: serde_json::Value = serde_json::from_str(&move_cli(&[
+                "sandbox",
+                "view",
+                EVENTS_PATH,
+                "--from-seq",
+                &seq.to_string(),
+                "--limit",
+                &limit.to_string(),
+                "--format",
+                "json",
+            ]))
+            .unwrap();
+            let events = page["events"].as_array().unwrap();
+            assert!(events.len() <= limit);
+            sequence_numbers.extend(
+                events
+                    .iter()
+                    .map(|event| event["sequence_number"].as_u64().unwrap()),
+            );
+            from_seq = page["next_seq"].as_u64();
+        }
+        sequence_numbers
+    };
+
+    move_cli(&["sandbox", "publish"]);
+    move_cli(&["sandbox", "run", "sources/init.move", "--signers", "0x2"]);
+    move_cli(&["sandbox", "run", "sources/emit.move", "--args", "30"]);
+    move_cli(&["sandbox", "run", "sources/emit.move", "--args", "20"]);
+    assert_eq!(sequence_numbers(7), (0..50).collect::<Vec<u64>>());
+    assert_eq!(sequence_numbers(50), (0..50).collect::<Vec<u64>>());
+
+    // streams saved without consistent sequence numbers are numbered by position when read
+    let events_path = package_path.join(EVENTS_PATH);
+    let mut events: Vec<(Vec<u8>, u64, TypeTag, Vec<u8>)> =
+        bcs::from_bytes(&fs::read(&events_path).unwrap()).unwrap();
+    for event in events.iter_mut() {
+        event.1 = 0;
+    }
+    fs::write(&events_path, bcs::to_bytes(&events).unwrap()).unwrap();
+    assert_eq!(sequence_numbers(16), (0..50).collect::<Vec<u64>>());
+    move_cli(&["sandbox", "run", "sources/emit.move", "--args", "1"]);
+    assert_eq!(sequence_numbers(16), (0..51).collect::<Vec<u64>>());
+}
+
+/// The value of the `Counter` resource stored at `addr` in the sandbox of `package`, if any
+fn counter_value(package: &Path, addr: &str) -> Option<u64> {
+    let addr = AccountAddress::from_hex_literal(addr).unwrap();
+    let resources = package
+        .join("storage")
+        .join(format!("0x{}", addr))
+        .join("resources");
+    let resource = fs::read_dir(resources).ok()?.next()?.unwrap().path();
+    Some(bcs::from_bytes(&fs::read(resource).unwrap()).unwrap())
+}
+
+/// The contents of every file under `dir`, by path
+fn dir_contents(dir: &Path) -> BTreeMap<PathBuf, Vec<u8>> {
+    walkdir::WalkDir::new(dir)
+        .into_iter()
+        .map(|entry| entry.unwrap())
+        .filter(|entry| entry.file_type().is_file())
+        .map(|entry| (entry.path().to_path_buf(), fs::read(entry.path()).unwrap()))
+        .collect()
+}
+
+#[test]
+fn sandbox_import_replaces_or_merges_the_exported_state() {
+    let workspace = tempfile::tempdir().unwrap();
+    // sandboxes of the same package, which publish the same module
+    let sandbox = |name: &str, counters: &[(&str, &str)]| {
+        let package_path = workspace.path().join(name);
+        write_package(
+            &package_path,
+            "Counters",
+            "",
+            &[
+                (
+                    "Counter",
+                    "module 0x2::Counter {
+    struct Counter has key { value: u64 }
+    public fun init(account: &signer, value: u64) { move_to(account, Counter { value }) }
+}",
+                ),
+                (
+                    "init",
+                    "script { fun init(account: signer, value: u64) { 0x2::Counter::init(&account, value) } }",
+                ),
+            ],
+        );
+        let mut commands = vec![vec!["sandbox", "publish"]];
+        for (addr, value) in counters {
+            commands.push(vec![
+                "sandbox",
+                "run",
+                "sources/init.move",
+                "--signers",
+                addr,
+                "--args",
+                value,
+            ]);
+        }
+        for args in commands {
+            let output = Command::new(get_cli_binary_path())
+                .current_dir(&package_path)
+                .args(args)
+                .output()
+                .unwrap();
+            assert!(
+                output.status.success(),
+                "{}",
+                String::from_utf8_lossy(&output.stderr)
+            );
+        }
+        package_path
+    };
+    let move_cli = |package_path: &Path, args: &[&str]| {
+        let output = Command::new(get_cli_binary_path())
+            .current_dir(package_path)
+            .args(args)
+            .output()
+            .unwrap();
+        (
+            output.status.success(),
+            String::from_utf8_lossy(&output.stdout).to_string(),
+            String::from_utf8_lossy(&output.stderr).to_string(),
+        )
+    };
+
+    let exporter = sandbox("Exporter", &[("0xa", "1"), ("0xb", "2")]);
+    let state = workspace.path().join("state");
+    let state_arg = path_to_string(&state);
+    let (success, _, stderr) = move_cli(&exporter, &["sandbox", "export", &state_arg]);
+    assert!(success, "{}", stderr);
+    let (success, _, stderr) = move_cli(&exporter, &["sandbox", "export", &state_arg]);
+    assert!(!success);
+    assert!(stderr.contains("is not empty"), "{}", stderr);
+
+    // a merge without conflicts
+    let other = sandbox("Other", &[("0xc", "3")]);
+    let (success, stdout, stderr) = move_cli(&other, &["sandbox", "import", &state_arg, "--merge"]);
+    assert!(success, "{}", stderr);
+    assert!(stdout.contains("added resource"), "{}", stdout);
+    assert_eq!(counter_value(&other, "0xa"), Some(1));
+    assert_eq!(counter_value(&other, "0xb"), Some(2));
+    assert_eq!(counter_value(&other, "0xc"), Some(3));
+    let (_, log, _) = move_cli(&other, &["sandbox", "log", "--last", "1"]);
+    assert!(log.contains("import"), "{}", log);
+
+    // conflicts fail the merge, listing them, unless a side is preferred
+    let local = sandbox("Local", &[("0xa", "10"), ("0xc", "3")]);
+    let storage = local.join("storage");
+    let before = dir_contents(&storage);
+    let (success, _, stderr) = move_cli(&local, &["sandbox", "import", &state_arg, "--merge"]);
+    assert!(!success);
+    assert!(
+        stderr.contains("conflicts with storage")
+            && stderr.contains("resource 0x")
+            && stderr.contains("::Counter::Counter at 0xa")
+            && stderr.contains("--prefer-import or --prefer-local"),
+        "{}",
+        stderr
+    );
+    assert_eq!(dir_contents(&storage), before);
+
+    // a replace that fails halfway through leaves storage as it was
+    let broken = workspace.path().join("broken");
+    let (success, _, stderr) =
+        move_cli(&exporter, &["sandbox", "export", &path_to_string(&broken)]);
+    assert!(success, "{}", stderr);
+    let broken_module = broken
+        .join(format!(
+            "0x{}",
+            AccountAddress::from_hex_literal("0xb").unwrap()
+        ))
+        .join("modules")
+        .join("Broken.mv");
+    fs::create_dir_all(broken_module.parent().unwrap()).unwrap();
+    fs::write(&broken_module, b"not bytecode").unwrap();
+    let (success, _, stderr) = move_cli(
+        &local,
+        &["sandbox", "import", &path_to_string(&broken), "--replace"],
+    );
+    assert!(!success);
+    assert!(
+        stderr.contains("Unable to deserialize the module 0xb::Broken"),
+        "{}",
+        stderr
+    );
+    assert_eq!(dir_contents(&storage), before);
+    assert!(!local.join("storage.import").exists());
+
+    let (success, stdout, stderr) = move_cli(
+        &local,
+        &["sandbox", "import", &state_arg, "--merge", "--prefer-local"],
+    );
+    assert!(success, "{}", stderr);
+    assert!(stdout.contains("kept the stored resource"), "{}", stdout);
+    assert_eq!(counter_value(&local, "0xa"), Some(10));
+    assert_eq!(counter_value(&local, "0xb"), Some(2));
+    assert_eq!(counter_value(&local, "0xc"), Some(3));
+
+    // importing again is a no-op but for the conflict resolved the other way
+    let (success, stdout, stderr) = move_cli(
+        &local,
+        &[
+            "sandbox",
+            "import",
+            &state_arg,
+            "--merge",
+            "--prefer-import",
+        ],
+    );
+    assert!(success, "{}", stderr);
+    assert!(
+        stdout.contains("replaced resource") && !stdout.contains("added"),
+        "{}",
+        stdout
+    );
+    assert_eq!(counter_value(&local, "0xa"), Some(1));
+    assert_eq!(counter_value(&local, "0xc"), Some(3));
+
+    let (success, _, stderr) = move_cli(&local, &["sandbox", "import", &state_arg, "--replace"]);
+    assert!(success, "{}", stderr);
+    assert_eq!(counter_value(&local, "0xa"), Some(1));
+    assert_eq!(counter_value(&local, "0xb"), Some(2));
+    assert_eq!(counter_value(&local, "0xc"), None);
+    let (success, _, stderr) = move_cli(&local, &["sandbox", "import", &state_arg]);
+    assert!(!success);
+    assert!(stderr.contains("--replace"), "{}", stderr);
+}
+
+#[test]
+fn sandbox_run_with_memory_quota() {
+    let workspace = tempfile::tempdir().unwrap();
+    let package_path = workspace.path().join("Filler");
+    write_package(
+        &package_path,
+        "Filler",
+        &stdlib_dependency(),
+        &[
+            (
+                "fill",
+                "script {
+    use std::vector;
+    fun fill(n: u64) {
+        let v = vector::empty<u64>();
+        let i = 0;
+        while (i < n) {
+            vector::push_back(&mut v, i);
+            i = i + 1;
+        }
+    }
+}",
+            ),
+            (
+                "churn",
+                "script {
+    use std::vector;
+    fun churn(n: u64) {
+        let v = vector::empty<u64>();
+        let i = 0;
+        while (i < n) {
+            vector::push_back(&mut v, i);
+            vector::pop_back(&mut v);
+            i = i + 1;
+        }
+    }
+}",
+            ),
+        ],
+    );
+    let move_cli = |args: &[&str]| {
+        let output = Command::new(get_cli_binary_path())
+            .current_dir(&package_path)
+            .args(args)
+            .output()
+            .unwrap();
+        assert!(
+            output.status.success(),
+            "{}",
+            String::from_utf8_lossy(&output.stderr)
+        );
+        String::from_utf8(output.stdout).unwrap()
+    };
+
+    move_cli(&["sandbox", "publish"]);
+    let fill = |n: &str, extra_args: &[&str]| {
+        let mut args = vec!["sandbox", "run", "sources/fill.move", "--args", n];
+        args.extend(extra_args);
+        move_cli(&args)
+    };
+    // each u64 takes 16 bytes
+    let output = fill("10", &["--memory-quota", "1", "--dry-run"]);
+    assert!(output.contains("Peak memory: 160 bytes"), "{}", output);
+    let output = fill("10", &[]);
+    assert!(!output.contains("Peak memory"), "{}", output);
+
+    let output = fill("100000", &["--memory-quota", "1"]);
+    assert!(
+        output.contains(
+            "a MEMORY_QUOTA_EXCEEDED error (memory quota of 1048576 bytes exceeded: about \
+             1048592 bytes in use)"
+        ) && output.contains("The memory quota is 1 MB"),
+        "{}",
+        output
+    );
+    // the default quota is large enough
+    let output = fill("100000", &[]);
+    assert!(!output.contains("MEMORY_QUOTA_EXCEEDED"), "{}", output);
+
+    // the elements popped are no longer held
+    let output = move_cli(&[
+        "sandbox",
+        "run",
+        "sources/churn.move",
+        "--args",
+        "100000",
+        "--memory-quota",
+        "1",
+    ]);
+    assert!(
+        output.contains("Peak memory: 16 bytes of the 1 MB quota"),
+        "{}",
+        output
+    );
+}
+
+#[test]
+fn sandbox_trace_debug_steps_through_a_recorded_run() {
+    let workspace = tempfile::tempdir().unwrap();
+    let package_path = workspace.path().join("Bank");
+    write_package(
+        &package_path,
+        "Bank",
+        "",
+        &[
+            (
+                "Bank",
+                "module 0x2::Bank {
+    public fun withdraw(balance: u64, amount: u64): u64 {
+        assert!(amount <= balance, 7);
+        balance - amount
+    }
+}",
+            ),
+            (
+                "main",
+                "script {
+    fun main(amount: u64) {
+        let _ = 0x2::Bank::withdraw(10, amount);
+    }
+}",
+            ),
+        ],
+    );
+    let trace = workspace.path().join("run.trace");
+    let cli = get_cli_binary_path();
+    let output = Command::new(&cli)
+        .current_dir(&package_path)
+        .args(["sandbox", "publish"])
+        .output()
+        .unwrap();
+    assert!(
+        output.status.success(),
+        "{}",
+        String::from_utf8_lossy(&output.stderr)
+    );
+    let output = Command::new(&cli)
+        .current_dir(&package_path)
+        .env("MOVE_VM_TRACE", &trace)
+        .env("MOVE_VM_TRACE_VALUES", "1")
+        .args(["sandbox", "run", "sources/main.move", "--args", "25"])
+        .output()
+        .unwrap();
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    assert!(stdout.contains("aborted with code 7"), "{}", stdout);
+
+    let mut debug = Command::new(&cli)
+        .args(["sandbox", "trace", "debug"])
+        .arg(&trace)
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .unwrap();
+    debug
+        .stdin
+        .take()
+        .unwrap()
+        .write_all(b"break Bank::withdraw\ncontinue\nlocals\nabort\nstack\nquit\n")
+        .unwrap();
+    let output = debug.wait_with_output().unwrap();
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    assert!(
+        output.status.success(),
+        "{}",
+        String::from_utf8_lossy(&output.stderr)
+    );
+    for expected in [
+        "Breakpoint 1: entry of Bank::withdraw\nBreakpoint 1 hit\n[4/9] 0x2::Bank::withdraw pc 0: ",
+        "Locals of 0x2::Bank::withdraw:\n    [0] 10\n    [1] 25\n",
+        "The execution aborted at:\n[9/9] 0x2::Bank::withdraw pc ",
+    ] {
+        assert!(stdout.contains(expected), "{}", stdout);
+    }
+}
+
+#[test]
+fn fuzz_finds_and_shrinks_failing_inputs() {
+    let workspace = tempfile::tempdir().unwrap();
+    let package_path = workspace.path().join("Vault");
+    write_package(
+        &package_path,
+        "Vault",
+        &stdlib_dependency(),
+        &[(
+            "Vault",
+            "module 0x2::Vault {
+    use std::vector;
+
+    const EZERO_AMOUNT: u64 = 1;
+
+    public entry fun withdraw(_account: &signer, amount: u64, memo: vector<u8>) {
+        if (amount == 18446744073709551615 && vector::length(&memo) < 100) {
+            // overflows
+            amount = amount + 1;
+        }
+    }
+
+    public entry fun check(amount: u64) {
+        assert!(amount != 0, EZERO_AMOUNT);
+    }
+}",
+        )],
+    );
+    let fuzz = |args: &[&str]| {
+        Command::new(get_cli_binary_path())
+            .current_dir(&package_path)
+            .args(["package", "fuzz"])
+            .args(args)
+            .output()
+            .unwrap()
+    };
+
+    let output = fuzz(&["--all", "--seed", "42"]);
+    assert!(!output.status.success());
+    let stdout = String::from_utf8(output.stdout).unwrap();
+    assert!(
+        stdout.contains("[ PASS    ] 0x2::Vault::check"),
+        "{}",
+        stdout
+    );
+    assert!(
+        stdout
+            .contains("[ FAIL    ] 0x2::Vault::withdraw: ARITHMETIC_ERROR in 0x2::Vault::withdraw"),
+        "{}",
+        stdout
+    );
+    // the signer and the vector are shrunk, and the amount is kept as the only failing value
+    assert!(
+        stdout.contains("input: (signer(0x0), 18446744073709551615u64, vector[])"),
+        "{}",
+        stdout
+    );
+    let failure_path = package_path.join("build/fuzz-failures/Vault-withdraw.json");
+    let failure: serde_json::Value =
+        serde_json::from_str(&fs::read_to_string(&failure_path).unwrap()).unwrap();
+    assert_eq!(failure["function"], "0x2::Vault::withdraw");
+    assert_eq!(failure["seed"], 42);
+    assert_eq!(
+        failure["args"],
+        json!(["00000000000000000000000000000000", "ffffffffffffffff", "00"])
+    );
+    // the same seed finds the same input
+    let output = fuzz(&["Vault::withdraw", "--seed", "42"]);
+    assert!(String::from_utf8(output.stdout)
+        .unwrap()
+        .contains("input: (signer(0x0), 18446744073709551615u64, vector[])"));
+
+    let failure_path = failure_path.to_str().unwrap();
+    let output = fuzz(&["--replay", failure_path]);
+    assert!(!output.status.success());
+    let stderr = String::from_utf8(output.stderr).unwrap();
+    assert!(
+        stderr.contains("0x2::Vault::withdraw fails: ARITHMETIC_ERROR"),
+        "{}",
+        stderr
+    );
+    let source_path = package_path.join("sources/Vault.move");
+    let source = fs::read_to_string(&source_path).unwrap();
+    fs::write(&source_path, source.replace("amount + 1", "amount - 1")).unwrap();
+    let output = fuzz(&["--replay", failure_path]);
+    assert!(output.status.success());
+    assert!(String::from_utf8(output.stdout)
+        .unwrap()
+        .contains("0x2::Vault::withdraw no longer fails with this input"));
+
+    // aborts with a declared code are only failures with --fail-on-abort
+    let output = fuzz(&["Vault::check", "--seed", "42", "--fail-on-abort"]);
+    assert!(!output.status.success());
+    let stdout = String::from_utf8(output.stdout).unwrap();
+    assert!(
+        stdout.contains("[ FAIL    ] 0x2::Vault::check: abort with code 1 in 0x2::Vault::check"),
+        "{}",
+        stdout
+    );
+    assert!(stdout.contains("input: (0u64)"), "{}", stdout);
+}
+
+#[test]
+fn build_with_archive_dependencies() {
+    let workspace = tempfile::tempdir().unwrap();
+    let move_home = workspace.path().join("move_home");
+    // a release archive holding the package in a single top-level directory, and an archive
+    // holding it in a subdirectory next to other files
+    let archive = |name: &str, contents: &Path| {
+        let archive_path = workspace.path().join(format!("{}.tar.gz", name));
+        let status = Command::new("tar")
+            .arg("-czf")
+            .arg(&archive_path)
+            .arg("-C")
+            .arg(contents)
+            .arg(".")
+            .status()
+            .unwrap();
+        assert!(status.success());
+        let bytes = fs::read(archive_path).unwrap();
+        let sha256 = format!("{:x}", Sha256::digest(&bytes));
+        (bytes, sha256)
+    };
+    let release = workspace.path().join("release");
+    write_package(
+        &release.join("Dep-1.0.0"),
+        "Dep",
+        "",
+        &[("Dep", "module 0x2::Dep { public fun f(): u64 { 1 } }")],
+    );
+    let (release_archive, release_sha256) = archive("release", &release);
+    let monorepo = workspace.path().join("monorepo");
+    write_package(
+        &monorepo.join("packages").join("OtherDep"),
+        "OtherDep",
+        "",
+        &[("OtherDep", "module 0x2::OtherDep { public fun g() {} }")],
+    );
+    fs::write(monorepo.join("README.md"), "Packages\n").unwrap();
+    let (monorepo_archive, monorepo_sha256) = archive("monorepo", &monorepo);
+
+    let server = MockServer::start();
+    let redirect = server.mock(|when, then| {
+        when.method(GET).path("/latest.tar.gz");
+        then.status(302).header("Location", "/dep-1.0.0.tar.gz");
+    });
+    let release_mock = server.mock(|when, then| {
+        when.method(GET).path("/dep-1.0.0.tar.gz");
+        then.status(200).body(&release_archive);
+    });
+    let monorepo_mock = server.mock(|when, then| {
+        when.method(GET).path("/monorepo.tar.gz");
+        then.status(200).body(&monorepo_archive);
+    });
+    server.mock(|when, then| {
+        when.method(GET).path("/loop.tar.gz");
+        then.status(302).header("Location", "/loop.tar.gz");
+    });
+
+    let root_path = workspace.path().join("Root");
+    let build = |dependencies: &str, extra_args: &[&str]| {
+        write_package(
+            &root_path,
+            "Root",
+            dependencies,
+            &[(
+                "Root",
+                "module 0x2::Root { public fun h(): u64 { 0x2::OtherDep::g(); 0x2::Dep::f() } }",
+            )],
+        );
+        Command::new(env!("CARGO_BIN_EXE_move"))
+            .args(["build", "-p"])
+            .arg(&root_path)
+            .args(extra_args)
+            .env("MOVE_HOME", &move_home)
+            .output()
+            .unwrap()
+    };
+    let dependencies = |sha256: &str| {
+        format!(
+            "Dep = {{ url = \"{}\", sha256 = \"{}\" }}\n\
+             OtherDep = {{ url = \"{}\", sha256 = \"{}\", subdir = \"packages/OtherDep\" }}\n",
+            server.url("/latest.tar.gz"),
+            sha256,
+            server.url("/monorepo.tar.gz"),
+            monorepo_sha256
+        )
+    };
+
+    // archives are not downloaded with --offline
+    let output = build(&dependencies(&release_sha256), &["--offline"]);
+    assert!(!output.status.success());
+    let error = String::from_utf8_lossy(&output.stderr);
+    assert!(
+        error.contains("has not been downloaded from") && error.contains("--offline"),
+        "{}",
+        error
+    );
+
+    let output = build(&dependencies(&release_sha256), &[]);
+    assert!(
+        output.status.success(),
+        "{}",
+        String::from_utf8_lossy(&output.stderr)
+    );
+    redirect.assert_hits(1);
+    release_mock.assert_hits(1);
+    monorepo_mock.assert_hits(1);
+    let lockfile = fs::read_to_string(root_path.join("Move.lock")).unwrap();
+    assert!(
+        lockfile.contains(&format!("sha256 = \"{}\"", release_sha256))
+            && lockfile.contains(&format!("url = \"{}\"", server.url("/monorepo.tar.gz")))
+            && lockfile.contains("subdir = \"packages/OtherDep\""),
+        "{}",
+        lockfile
+    );
+
+    // the extracted archives are reused, also offline
+    let output = build(&dependencies(&release_sha256), &["--offline", "--locked"]);
+    assert!(
+        output.status.success(),
+        "{}",
+        String::from_utf8_lossy(&output.stderr)
+    );
+    release_mock.assert_hits(1);
+    monorepo_mock.assert_hits(1);
+
+    let wrong_sha256 = "ab".repeat(32);
+    let output = build(&dependencies(&wrong_sha256), &[]);
+    assert!(!output.status.success());
+    let error = String::from_utf8_lossy(&output.stderr);
+    assert!(
+        error.contains(&format!(
+            "has the SHA-256 digest {}, but the manifest requires {}",
+            release_sha256, wrong_sha256
+        )),
+        "{}",
+        error
+    );
+
+    let output = build(
+        &format!(
+            "Dep = {{ url = \"{}\", sha256 = \"{}\" }}\n",
+            server.url("/loop.tar.gz"),
+            release_sha256
+        ),
+        &[],
+    );
+    assert!(!output.status.success());
+    let error = String::from_utf8_lossy(&output.stderr);
+    assert!(error.contains("more than 10 redirects"), "{}", error);
+}
+
+#[test]
+fn build_with_dependencies_from_a_registry_directory() {
+    let workspace = tempfile::tempdir().unwrap();
+    let move_home = workspace.path().join("move_home");
+    // a registry holding a version of `coin` as an archive, and a version of `wallet` as sources
+    let registry = workspace.path().join("registry");
+    let coin = workspace.path().join("Coin");
+    write_package(
+        &coin,
+        "Coin",
+        "",
+        &[("Coin", "module 0x2::Coin { public fun value(): u64 { 1 } }")],
+    );
+    let archive_path = registry.join("coin/1.2.0/coin-1.2.0.tar.gz");
+    fs::create_dir_all(archive_path.parent().unwrap()).unwrap();
+    let status = Command::new("tar")
+        .arg("-czf")
+        .arg(&archive_path)
+        .arg("-C")
+        .arg(&coin)
+        .arg(".")
+        .status()
+        .unwrap();
+    assert!(status.success());
+    let sha256 = format!("{:x}", Sha256::digest(&fs::read(&archive_path).unwrap()));
+    write_package(
+        &registry.join("wallet/0.1.0"),
+        "Wallet",
+        "",
+        &[("Wallet", "module 0x2::Wallet { public fun hold() {} }")],
+    );
+    let index = json!([
+        {"name": "coin", "owner": "alice", "version": "1.2.0",
+         "archive": "coin/1.2.0/coin-1.2.0.tar.gz", "sha256": sha256},
+        {"name": "wallet", "version": "0.1.0"},
+    ]);
+    fs::write(registry.join("index.json"), index.to_string()).unwrap();
+
+    let root_path = workspace.path().join("Root");
+    let build = |dependencies: &str, extra_args: &[&str]| {
+        write_package(
+            &root_path,
+            "Root",
+            dependencies,
+            &[(
+                "Root",
+                "module 0x2::Root { public fun f(): u64 { 0x2::Wallet::hold(); 0x2::Coin::value() } }",
+            )],
+        );
+        Command::new(env!("CARGO_BIN_EXE_move"))
+            .args(["build", "-p"])
+            .arg(&root_path)
+            .args(extra_args)
+            .env("MOVE_HOME", &move_home)
+            .output()
+            .unwrap()
+    };
+    let dependencies = "Coin = { version = \"1.2.0\", registry = \"local\" }\n\
+                        Wallet = { name = \"wallet\", version = \"0.1.0\", registry = \"local\" }\n";
+    let registry_path = path_to_string(&registry);
+
+    let output = build(dependencies, &["--offline"]);
+    assert!(!output.status.success());
+    let error = String::from_utf8_lossy(&output.stderr);
+    assert!(error.contains("no registry directory is set"), "{}", error);
+
+    // the archive is read from the registry, even with --offline
+    let output = build(
+        dependencies,
+        &["--offline", "--registry-path", &registry_path],
+    );
+    assert!(
+        output.status.success(),
+        "{}",
+        String::from_utf8_lossy(&output.stderr)
+    );
+    let lockfile = fs::read_to_string(root_path.join("Move.lock")).unwrap();
+    assert!(
+        lockfile.contains(&format!("url = \"file://{}\"", archive_path.display()))
+            && lockfile.contains(&format!("sha256 = \"{}\"", sha256)),
+        "{}",
+        lockfile
+    );
+
+    // the registry directory set in the credential file
+    fs::write(
+        credential_path(&path_to_string(&move_home)),
+        format!("[registry]\npath = \"{}\"\n", registry_path),
+    )
+    .unwrap();
+    let output = build(dependencies, &["--offline", "--locked"]);
+    assert!(
+        output.status.success(),
+        "{}",
+        String::from_utf8_lossy(&output.stderr)
+    );
+
+    let output = build(
+        "Coin = { version = \"2.0.0\", registry = \"local\" }\n",
+        &[],
+    );
+    assert!(!output.status.success());
+    let error = String::from_utf8_lossy(&output.stderr);
+    assert!(
+        error.contains("has no version 2.0.0 of package 'Coin', only 1.2.0"),
+        "{}",
+        error
+    );
+}
+
+#[test]
+fn upload_to_and_add_from_a_registry_directory() {
+    let workspace = tempfile::tempdir().unwrap();
+    let move_home = workspace.path().join("move_home");
+    let registry = workspace.path().join("registry");
+    let token = workspace.path().join("Token");
+    write_package(
+        &token,
+        "Token",
+        "",
+        &[(
+            "Token",
+            "module 0x2::Token { public fun supply(): u64 { 100 } }",
+        )],
+    );
+    let manifest = fs::read_to_string(token.join("Move.toml")).unwrap();
+    fs::write(
+        token.join("Move.toml"),
+        manifest.replace("version = \"0.0.0\"", "version = \"0.3.1\""),
+    )
+    .unwrap();
+    commit_git_repo(&token);
+    let move_command = |package: &Path, args: &[&str]| {
+        Command::new(env!("CARGO_BIN_EXE_move"))
+            .args(args)
+            .arg("-p")
+            .arg(package)
+            .arg("--registry-path")
+            .arg(&registry)
+            .env("MOVE_HOME", &move_home)
+            .output()
+            .unwrap()
+    };
+
+    let output = move_command(&token, &["movey-upload"]);
+    assert!(
+        output.status.success(),
+        "{}",
+        String::from_utf8_lossy(&output.stderr)
+    );
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    assert!(
+        stdout.contains("Token/0.3.1/Token-0.3.1.tar.gz"),
+        "{}",
+        stdout
+    );
+    let index: serde_json::Value =
+        serde_json::from_str(&fs::read_to_string(registry.join("index.json")).unwrap()).unwrap();
+    assert_eq!(index[0]["name"], "Token");
+    assert_eq!(index[0]["version"], "0.3.1");
+    assert_eq!(index[0]["archive"], "Token/0.3.1/Token-0.3.1.tar.gz");
+
+    let output = move_command(&token, &["movey-upload"]);
+    assert!(!output.status.success());
+    let error = String::from_utf8_lossy(&output.stderr);
+    assert!(
+        error.contains("already has version 0.3.1 of package 'Token'"),
+        "{}",
+        error
+    );
+
+    // a package depending on the uploaded one, added from the registry directory
+    let app = workspace.path().join("App");
+    write_package(
+        &app,
+        "App",
+        "",
+        &[(
+            "App",
+            "module 0x2::App { public fun f(): u64 { 0x2::Token::supply() } }",
+        )],
+    );
+    let output = move_command(&app, &["package", "add", "token", "--exact"]);
+    assert!(
+        output.status.success(),
+        "{}",
+        String::from_utf8_lossy(&output.stderr)
+    );
+    let manifest = fs::read_to_string(app.join("Move.toml")).unwrap();
+    assert!(
+        manifest.contains("Token = { version = \"0.3.1\", registry = \"local\" }"),
+        "{}",
+        manifest
+    );
+    let output = move_command(&app, &["build", "--offline"]);
+    assert!(
+        output.status.success(),
+        "{}",
+        String::from_utf8_lossy(&output.stderr)
+    );
+}
+
+// Commands that do not need to write to MOVE_HOME work when it is read-only, and those that do
+// name the path they failed to write.
+#[cfg(unix)]
+#[test]
+fn build_with_read_only_move_home() {
+    let workspace = tempfile::tempdir().unwrap();
+    let move_home = workspace.path().join("move_home");
+    let mut git_deps = vec![];
+    for name in ["Dep", "OtherDep"] {
+        let dep_path = workspace.path().join(name);
+        let source = format!("module 0x2::{} {{ public fun f() {{}} }}", name);
+        write_package(&dep_path, name, "", &[(name, &source)]);
+        commit_git_repo(&dep_path);
+        git_deps.push(format!(
+            "{} = {{ git = \"file://{}\", rev = \"main\" }}\n",
+            name,
+            dep_path.display()
+        ));
+    }
+    let root_path = workspace.path().join("Root");
+    let build = |dependencies: &str, extra_args: &[&str]| {
+        write_package(&root_path, "Root", dependencies, &[]);
+        Command::new(env!("CARGO_BIN_EXE_move"))
+            .args(["build", "-p"])
+            .arg(&root_path)
+            .args(extra_args)
+            .env("MOVE_HOME", &move_home)
+            .envs(ALLOW_FILE_PROTOCOL)
+            .output()
+            .unwrap()
+    };
+    let set_mode = |mode: u32| {
+        let mut perms = fs::metadata(&move_home).unwrap().permissions();
+        perms.set_mode(mode);
+        fs::set_permissions(&move_home, perms).unwrap();
+    };
+
+    let output = build(&git_deps[0], &[]);
+    assert!(
+        output.status.success(),
+        "{}",
+        String::from_utf8_lossy(&output.stderr)
+    );
+    set_mode(0o555);
+    // the dependency is already downloaded, so nothing is written to MOVE_HOME
+    let offline_output = build(&git_deps[0], &["--locked"]);
+    let download_output = build(&git_deps.concat(), &[]);
+    set_mode(0o755);
+
+    assert!(
+        offline_output.status.success(),
+        "{}",
+        String::from_utf8_lossy(&offline_output.stderr)
+    );
+    assert!(!download_output.status.success());
+    let error = String::from_utf8_lossy(&download_output.stderr);
+    assert!(
+        error.contains("Unable to create the download directory")
+            && error.contains(&move_home.display().to_string())
+            && error.contains("set MOVE_HOME to a writable directory"),
+        "{}",
+        error
+    );
+}
+
+#[cfg(unix)]
+#[test]
+fn sandbox_view_with_read_only_move_home() {
+    let workspace = tempfile::tempdir().unwrap();
+    let move_home = workspace.path().join("move_home");
+    fs::create_dir_all(&move_home).unwrap();
+    let mut perms = fs::metadata(&move_home).unwrap().permissions();
+    perms.set_mode(0o555);
+    fs::set_permissions(&move_home, perms.clone()).unwrap();
+    let package_path = workspace.path().join("Viewed");
+    write_package(
+        &package_path,
+        "Viewed",
+        "",
+        &[("M", "module 0x2::M { public fun f() {} }")],
+    );
+
+    let move_cli = |args: &[&str]| {
+        Command::new(get_cli_binary_path())
+            .current_dir(&package_path)
+            .args(args)
+            .env("MOVE_HOME", &move_home)
+            .output()
+            .unwrap()
+    };
+    let publish_output = move_cli(&["sandbox", "publish"]);
+    let view_output = move_cli(&[
+        "sandbox",
+        "view",
+        "storage/0x00000000000000000000000000000002/modules/M.mv",
+    ]);
+    perms.set_mode(0o755);
+    fs::set_permissions(&move_home, perms).unwrap();
+
+    for output in [publish_output, view_output] {
+        assert!(
+            output.status.success(),
+            "{}",
+            String::from_utf8_lossy(&output.stderr)
+        );
+    }
+    assert_eq!(fs::read_dir(&move_home).unwrap().count(), 0);
+}
+
+// A failing build writes the dependencies it fetched and the compiler passes it ran to the log
+// file, although the console only shows the error.
+#[test]
+fn build_with_log_file() {
+    let workspace = tempfile::tempdir().unwrap();
+    let dep_path = workspace.path().join("Dep");
+    write_package(
+        &dep_path,
+        "Dep",
+        "",
+        &[("Dep", "module 0x2::Dep { public fun f(): u64 { 0 } }")],
+    );
+    commit_git_repo(&dep_path);
+    let dep_url = format!("file://{}", dep_path.display());
+    let root_path = workspace.path().join("Root");
+    write_package(
+        &root_path,
+        "Root",
+        &format!("Dep = {{ git = \"{}\", rev = \"main\" }}\n", dep_url),
+        &[(
+            "M",
+            "module 0x2::M { public fun g(): bool { 0x2::Dep::f() } }",
+        )],
+    );
+    let build = |log_file_arg: Option<&Path>, log_file_env: Option<&Path>| {
+        let mut command = Command::new(get_cli_binary_path());
+        command
+            .args(["build", "-p"])
+            .arg(&root_path)
+            .env("MOVE_HOME", workspace.path().join("move_home"))
+            .env_remove("MOVE_LOG_FILE")
+            .envs(ALLOW_FILE_PROTOCOL);
+        if let Some(path) = log_file_arg {
+            command.arg("--log-file").arg(path);
+        }
+        if let Some(path) = log_file_env {
+            command.env("MOVE_LOG_FILE", path);
+        }
+        let output = command.output().unwrap();
+        assert!(!output.status.success());
+    };
+
+    let log_path = workspace.path().join("build.log");
+    build(Some(log_path.as_path()), None);
+    let log = fs::read_to_string(&log_path).unwrap();
+    assert!(log.contains("command line: "), "{}", log);
+    assert!(log.contains(&dep_url), "{}", log);
+    for pass in ["parser", "expansion", "naming", "typing"] {
+        assert!(
+            log.contains(&format!("compiler_pass{{pass=\"{}\"}}", pass)),
+            "{}",
+            log
+        );
+    }
+
+    // the dependency is already downloaded, and `MOVE_LOG_FILE` names the log file
+    let env_log_path = workspace.path().join("env.log");
+    build(None, Some(env_log_path.as_path()));
+    let log = fs::read_to_string(&env_log_path).unwrap();
+    assert!(log.contains("git dependency already downloaded"), "{}", log);
+    assert!(log.contains("compiler_pass{pass=\"typing\"}"), "{}", log);
+}
+
+// A package of 200 modules is published in batches, and a failure in the third batch leaves
+// storage as it was before the publish.
+#[test]
+fn sandbox_publish_in_batches() {
+    const NUM_MODULES: usize = 200;
+    let workspace = tempfile::tempdir().unwrap();
+    let package_path = workspace.path().join("Large");
+    // every module depends on one before it, so modules are published in the order of their names
+    let write_modules = |extra_field: Option<usize>, extra_function_below: usize| {
+        let sources: Vec<_> = (0..NUM_MODULES)
+            .map(|i| {
+                let dep_call = if i == 0 {
+                    "0".to_string()
+                } else {
+                    format!("0x2::M{:03}::f() + 1", (i - 1) / 2)
+                };
+                let field = if extra_field == Some(i) {
+                    ", w: u64"
+                } else {
+                    ""
+                };
+                let extra_function = if i < extra_function_below {
+                    "    public fun g(): u64 { 1 }\n"
+                } else {
+                    ""
+                };
+                (
+                    format!("M{:03}", i),
+                    format!(
+                        "module 0x2::M{:03} {{\n    struct S has key {{ v: u64{} }}\n    \
+                         public fun f(): u64 {{ {} }}\n{}}}",
+                        i, field, dep_call, extra_function
+                    ),
+                )
+            })
+            .collect();
+        let modules: Vec<_> = sources
+            .iter()
+            .map(|(name, source)| (name.as_str(), source.as_str()))
+            .collect();
+        write_package(&package_path, "Large", "", &modules);
+    };
+    let storage_contents = || {
+        let storage_path = package_path.join("storage");
+        walkdir::WalkDir::new(&storage_path)
+            .into_iter()
+            .map(|entry| entry.unwrap())
+            .filter(|entry| entry.file_type().is_file())
+            .map(|entry| {
+                let path = entry
+                    .path()
+                    .strip_prefix(&storage_path)
+                    .unwrap()
+                    .to_path_buf();
+                (path, fs::read(entry.path()).unwrap())
+            })
+            .collect::<BTreeMap<_, _>>()
+    };
+    let publish = || {
+        let output = Command::new(get_cli_binary_path())
+            .current_dir(&package_path)
+            .args(["sandbox", "publish", "--batch-size", "10"])
+            .output()
+            .unwrap();
+        assert!(
+            output.status.success(),
+            "{}",
+            String::from_utf8_lossy(&output.stderr)
+        );
+        String::from_utf8(output.stdout).unwrap()
+    };
+
+    write_modules(None, 0);
+    let output = publish();
+    assert!(
+        output.contains("Published 200 modules in 20 batch(es) of up to 10 modules"),
+        "{}",
+        output
+    );
+    let published = storage_contents();
+    let num_modules = published
+        .keys()
+        .filter(|path| path.extension().map_or(false, |ext| ext == "mv"))
+        .count();
+    assert_eq!(num_modules, NUM_MODULES);
+
+    // the first two batches update their modules compatibly, and the third breaks a struct layout
+    write_modules(Some(25), 20);
+    let output = publish();
+    assert!(
+        output.contains("Publishing batch 3 of 20 failed"),
+        "{}",
+        output
+    );
+    assert!(output.contains("Breaking change detected"), "{}", output);
+    assert!(storage_contents() == published, "storage changed");
+}
+
+#[test]
+fn concurrent_sandbox_publishes_are_serialized() {
+    let workspace = tempfile::tempdir().unwrap();
+    let package_path = workspace.path().join("Shared");
+    let modules: Vec<_> = (0..20)
+        .map(|i| {
+            (
+                format!("M{:02}", i),
+                format!(
+                    "module 0x2::M{:02} {{ struct S has key {{ v: u64 }} public fun f() {{}} }}",
+                    i
+                ),
+            )
+        })
+        .collect();
+    let modules: Vec<_> = modules
+        .iter()
+        .map(|(name, source)| (name.as_str(), source.as_str()))
+        .collect();
+    write_package(&package_path, "Shared", "", &modules);
+    // build first, so that the publishes only race on storage
+    let build = Command::new(get_cli_binary_path())
+        .current_dir(&package_path)
+        .args(["build"])
+        .output()
+        .unwrap();
+    assert!(build.status.success());
+
+    let publishes: Vec<_> = (0..2)
+        .map(|_| {
+            Command::new(get_cli_binary_path())
+                .current_dir(&package_path)
+                .args(["sandbox", "publish"])
+                .stdout(Stdio::piped())
+                .stderr(Stdio::piped())
+                .spawn()
+                .unwrap()
+        })
+        .collect();
+    for publish in publishes {
+        let output = publish.wait_with_output().unwrap();
+        assert!(
+            output.status.success(),
+            "{}",
+            String::from_utf8_lossy(&output.stderr)
+        );
+    }
+
+    let output = Command::new(get_cli_binary_path())
+        .current_dir(&package_path)
+        .args(["sandbox", "verify-storage"])
+        .output()
+        .unwrap();
+    assert!(
+        output.status.success(),
+        "{}",
+        String::from_utf8_lossy(&output.stdout)
+    );
+    let output = Command::new(get_cli_binary_path())
+        .current_dir(&package_path)
+        .args(["sandbox", "view", "--modules", "0x2"])
+        .output()
+        .unwrap();
+    let listing = String::from_utf8(output.stdout).unwrap();
+    assert!(output.status.success());
+    assert_eq!(listing.matches("0x2::M").count(), 20, "{}", listing);
+}
+
+#[cfg(unix)]
+#[test]
+fn sandbox_commands_wait_for_the_storage_lock() {
+    let workspace = tempfile::tempdir().unwrap();
+    let package_path = workspace.path().join("Locked");
+    write_package(
+        &package_path,
+        "Locked",
+        "",
+        &[("M", "module 0x2::M { public fun f() {} }")],
+    );
+    let storage_path = package_path.join("storage");
+    let move_cli = |args: &[&str]| {
+        let output = Command::new(get_cli_binary_path())
+            .current_dir(&package_path)
+            .args(args)
+            .output()
+            .unwrap();
+        let stderr = String::from_utf8(output.stderr).unwrap();
+        (output.status.success(), stderr)
+    };
+    assert!(move_cli(&["sandbox", "publish"]).0);
+    let lock = |access| {
+        StorageLock::acquire(
+            &storage_path,
+            access,
+            "test holder",
+            &StorageLockOptions::default(),
+        )
+        .unwrap()
+    };
+
+    let holder = lock(StorageAccess::Exclusive);
+    let (success, stderr) = move_cli(&["sandbox", "publish", "--no-wait"]);
+    assert!(!success);
+    assert!(
+        stderr.contains(&format!(
+            "storage is locked by PID {} (command test holder) for ",
+            std::process::id()
+        )),
+        "{}",
+        stderr
+    );
+    let (success, stderr) = move_cli(&["sandbox", "view", "--modules", "0x2", "--wait", "1"]);
+    assert!(!success);
+    assert!(stderr.contains("Gave up after waiting 1s"), "{}", stderr);
+    drop(holder);
+
+    // readers share the lock, and keep writers out
+    let holder = lock(StorageAccess::Shared);
+    let (success, stderr) = move_cli(&["sandbox", "view", "--modules", "0x2", "--no-wait"]);
+    assert!(success, "{}", stderr);
+    let (success, stderr) = move_cli(&["sandbox", "clean", "--no-wait"]);
+    assert!(!success);
+    assert!(stderr.contains("storage is locked by PID"), "{}", stderr);
+    drop(holder);
+
+    // the lock of a process that died is reclaimed
+    let mut dead = Command::new("true").spawn().unwrap();
+    let dead_pid = dead.id();
+    dead.wait().unwrap();
+    fs::write(
+        storage_path.join(STORAGE_LOCK_FILE),
+        format!("{}\texclusive\t0\tsandbox publish\n", dead_pid),
+    )
+    .unwrap();
+    let (success, stderr) = move_cli(&["sandbox", "publish", "--no-wait"]);
+    assert!(success, "{}", stderr);
+    assert!(move_cli(&["sandbox", "clean", "--no-wait"]).0);
+    assert!(!storage_path.exists());
+}
+
+const METADATA_TESTS_PATH: &str = "./tests/metadata_tests";
+
+#[test]
+fn package_metadata_of_workspace() {
+    let dir = tempfile::tempdir().unwrap();
+    let root = dir.path().join("workspace");
+    copy_dir(&Path::new(METADATA_TESTS_PATH).join("workspace"), &root);
+    let root = root.canonicalize().unwrap();
+    let metadata = |args: &[&str]| {
+        Command::new(env!("CARGO_BIN_EXE_move"))
+            .current_dir(root.join("app"))
+            .args(["package", "metadata"])
+            .args(args)
+            .output()
+            .unwrap()
+    };
+
+    let output = metadata(&["--format-version", "1"]);
+    assert!(
+        output.status.success(),
+        "{}",
+        String::from_utf8_lossy(&output.stderr)
+    );
+    let actual = String::from_utf8_lossy(&output.stdout).replace(&*root.to_string_lossy(), "$ROOT");
+    let exp_path = Path::new(METADATA_TESTS_PATH).join("workspace.exp");
+    if read_env_update_baseline() {
+        fs::write(&exp_path, &actual).unwrap();
+    }
+    let expected = fs::read_to_string(&exp_path).unwrap();
+    assert!(
+        expected == actual,
+        "{}",
+        add_update_baseline_fix(format_diff(&expected, &actual))
+    );
+    // nothing is compiled
+    assert!(!root.join("app").join("build").exists());
+
+    // the lockfiles written by the first run are up to date, and no dependency is fetched
+    let output = metadata(&["--format-version", "1", "--offline", "--locked"]);
+    assert!(
+        output.status.success(),
+        "{}",
+        String::from_utf8_lossy(&output.stderr)
+    );
+    assert_eq!(
+        String::from_utf8_lossy(&output.stdout).replace(&*root.to_string_lossy(), "$ROOT"),
+        actual
+    );
+
+    let output = metadata(&["--format-version", "2"]);
+    assert!(!output.status.success());
+    let error = String::from_utf8_lossy(&output.stderr);
+    assert!(
+        error.contains("Unsupported metadata format version 2"),
+        "{}",
+        error
+    );
+}
+
+#[test]
+fn json_output_is_not_mixed_with_diagnostics() {
+    let dir = tempfile::tempdir().unwrap();
+    let package_path = dir.path().join("Noisy");
+    // the unknown manifest section and the unused assignment each cause a warning
+    write_package(
+        &package_path,
+        "Noisy",
+        "\n[unknown_section]\nkey = 1\n",
+        &[("M", "module 0x2::M {\n    public fun f() { let x = 1; }\n}")],
+    );
+    let errors_path = dir.path().join("errors.jsonl");
+    let move_cli = |args: &[&str]| {
+        Command::new(get_cli_binary_path())
+            .current_dir(&package_path)
+            .args(args)
+            .arg("--json-errors-file")
+            .arg(&errors_path)
+            .output()
+            .unwrap()
+    };
+    let json_errors = || -> Vec<serde_json::Value> {
+        fs::read_to_string(&errors_path)
+            .unwrap()
+            .lines()
+            .map(|line| serde_json::from_str(line).unwrap())
+            .collect()
+    };
+
+    for args in [
+        &["info", "--format", "json"][..],
+        &["package", "metadata", "--format-version", "1"],
+        &["sandbox", "view", "--modules", "0x2", "--format", "json"],
+    ] {
+        let output = move_cli(args);
+        let stderr = String::from_utf8_lossy(&output.stderr);
+        assert!(output.status.success(), "{:?}: {}", args, stderr);
+        assert!(
+            serde_json::from_slice::<serde_json::Value>(&output.stdout).is_ok(),
+            "{:?}: {}",
+            args,
+            String::from_utf8_lossy(&output.stdout)
+        );
+        assert!(
+            stderr.contains("Warning: unknown field name found"),
+            "{:?}: {}",
+            args,
+            stderr
+        );
+        let errors = json_errors();
+        assert!(
+            errors.iter().any(|error| error["level"] == "warning"
+                && error["message"]
+                    .as_str()
+                    .unwrap()
+                    .starts_with("unknown field name found")),
+            "{:?}: {:?}",
+            args,
+            errors
+        );
+    }
+    // compiler diagnostics are recorded with their code and position
+    let errors = json_errors();
+    assert!(
+        errors.iter().any(|error| error["code"] == "W09003"
+            && error["level"] == "warning"
+            && error["file"].as_str().unwrap().ends_with("M.move")
+            && error["line"] == 2),
+        "{:?}",
+        errors
+    );
+
+    // the file is replaced by each command, and records the error it fails with
+    let output = move_cli(&["package", "metadata", "--format-version", "2"]);
+    assert!(!output.status.success());
+    assert!(output.stdout.is_empty());
+    let errors = json_errors();
+    assert!(
+        errors.iter().any(|error| error["level"] == "error"
+            && error["message"]
+                .as_str()
+                .unwrap()
+                .contains("Unsupported metadata format version 2")),
+        "{:?}",
+        errors
+    );
+    assert!(
+        errors.iter().all(|error| error["code"].is_null()),
+        "{:?}",
+        errors
+    );
+}
+
+#[test]
+fn build_check_only_with_stdin_overlay() {
+    let dir = tempfile::tempdir().unwrap();
+    let package_path = dir.path().join("Checked");
+    write_package(
+        &package_path,
+        "Checked",
+        "",
+        &[
+            ("A", "module 0x2::A {\n    public fun two(): u64 { 2 }\n}"),
+            (
+                "B",
+                "module 0x2::B {\n    use 0x2::A;\n\n    public fun four(): u64 { A::two() * 2 }\n}",
+            ),
+            ("C", "module 0x2::C {\n    public fun three(): u64 { 3 }\n}"),
+        ],
+    );
+    let check = |args: &[&str], stdin: &str| {
+        let mut child = Command::new(get_cli_binary_path())
+            .current_dir(&package_path)
+            .args(["build", "--check-only"])
+            .args(args)
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::piped())
+            .spawn()
+            .unwrap();
+        child
+            .stdin
+            .take()
+            .unwrap()
+            .write_all(stdin.as_bytes())
+            .unwrap();
+        child.wait_with_output().unwrap()
+    };
+
+    let output = check(&[], "");
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(output.status.success(), "{}", stderr);
+    assert!(
+        stderr.contains("Checked package Checked: 3 modules"),
+        "{}",
+        stderr
+    );
+    assert!(!package_path.join("build").exists());
+
+    // only the overlaid file and the file using it are checked, and the error is reported in the
+    // overlaid file although it is not on disk
+    let output = check(
+        &["--stdin-overlay", "sources/A.move"],
+        "module 0x2::A {\n    public fun two(): u64 { true }\n}\n",
+    );
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(!output.status.success(), "{}", stderr);
+    assert!(stderr.contains("sources/A.move:2:"), "{}", stderr);
+    assert!(
+        fs::read_to_string(package_path.join("sources").join("A.move"))
+            .unwrap()
+            .contains("{ 2 }")
+    );
+
+    let output = check(
+        &["--stdin-overlay", "sources/C.move"],
+        "module 0x2::C {\n    public fun three(): u64 { 1 + 2 }\n}\n",
+    );
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(output.status.success(), "{}", stderr);
+    assert!(
+        stderr.contains("Checked package Checked: 1 modules"),
+        "{}",
+        stderr
+    );
+
+    // the overlay is only read by checks
+    let output = Command::new(get_cli_binary_path())
+        .current_dir(&package_path)
+        .args(["build", "--stdin-overlay", "sources/A.move"])
+        .output()
+        .unwrap();
+    assert!(!output.status.success());
+}
+
+#[test]
+fn build_rejects_package_names_that_are_not_identifiers() {
+    let dir = tempfile::tempdir().unwrap();
+    write_package(dir.path(), "my-package", "", &[("M", "module 0x2::M {}")]);
+    let build = |args: &[&str]| {
+        Command::new(env!("CARGO_BIN_EXE_move"))
+            .current_dir(dir.path())
+            .arg("build")
+            .args(args)
+            .output()
+            .unwrap()
+    };
+
+    let output = build(&[]);
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(!output.status.success());
+    assert!(
+        stderr.contains(
+            "Invalid package name 'my-package'. Package names must be Move identifiers: an ASCII \
+             letter or underscore followed by ASCII letters, digits and underscores. Did you mean \
+             'my_package'? Pass --allow-nonstandard-names to build legacy packages anyway"
+        ),
+        "{}",
+        stderr
+    );
+
+    let output = build(&["--allow-nonstandard-names"]);
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(output.status.success(), "{}", stderr);
+    assert!(
+        stderr.contains("Warning: Invalid package name 'my-package'"),
+        "{}",
+        stderr
+    );
+}
+
+#[test]
+fn build_warns_about_unconventional_package_names() {
+    let dir = tempfile::tempdir().unwrap();
+    write_package(dir.path(), "myPackage", "", &[("M", "module 0x2::M {}")]);
+    let output = Command::new(env!("CARGO_BIN_EXE_move"))
+        .current_dir(dir.path())
+        .arg("build")
+        .output()
+        .unwrap();
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(output.status.success(), "{}", stderr);
+    assert!(
+        stderr.contains(
+            "Warning: the package name 'myPackage' follows neither the snake_case nor the \
+             UpperCamelCase convention"
+        ),
+        "{}",
+        stderr
+    );
+}
+
+/// The files and directories under `dir`, relative to it
+#[cfg(unix)]
+fn list_files(dir: &Path) -> Vec<PathBuf> {
+    walkdir::WalkDir::new(dir)
+        .sort_by_file_name()
+        .into_iter()
+        .map(|entry| {
+            entry
+                .unwrap()
+                .path()
+                .strip_prefix(dir)
+                .unwrap()
+                .to_path_buf()
+        })
+        .collect()
+}
+
+#[cfg(unix)]
+#[test]
+fn build_read_only_packages_into_an_out_dir() {
+    let workspace = tempfile::tempdir().unwrap();
+    let out_dir = workspace.path().join("out");
+    let storage_dir = workspace.path().join("storage");
+    let source = "module 0x2::M {\n    public fun one(): u64 { 1 }\n\n    \
+                  #[test]\n    fun test_one() { assert!(one() == 1, 0) }\n}";
+    let package_path = workspace.path().join("Pkg");
+    write_package(&package_path, "Pkg", &stdlib_dependency(), &[("M", source)]);
+    let other_path = workspace.path().join("Other");
+    write_package(&other_path, "Other", "", &[("N", "module 0x2::N {}")]);
+    let files = list_files(&package_path);
+    let set_mode = |dir: &Path, mode: u32| {
+        for entry in walkdir::WalkDir::new(dir) {
+            let path = entry.unwrap().into_path();
+            let mut perms = fs::metadata(&path).unwrap().permissions();
+            perms.set_mode(if path.is_dir() { mode } else { mode & 0o666 });
+            fs::set_permissions(&path, perms).unwrap();
+        }
+    };
+    let move_cmd = |package_path: &Path, args: &[&str], out_dir_var: Option<&Path>| {
+        let mut command = Command::new(env!("CARGO_BIN_EXE_move"));
+        command.current_dir(package_path).args(args);
+        match out_dir_var {
+            Some(out_dir) => command.env(MOVE_BUILD_DIR_ENV_VAR, out_dir),
+            None => command.env_remove(MOVE_BUILD_DIR_ENV_VAR),
+        };
+        let output = command.output().unwrap();
+        (
+            output.status.success(),
+            String::from_utf8_lossy(&output.stdout).to_string(),
+            String::from_utf8_lossy(&output.stderr).to_string(),
+        )
+    };
+
+    set_mode(&package_path, 0o555);
+    let out = out_dir.to_str().unwrap();
+    let storage = storage_dir.to_str().unwrap();
+    let outputs = [
+        move_cmd(&package_path, &["build", "--out-dir", out], None),
+        move_cmd(
+            &package_path,
+            &["test", "--coverage", "--out-dir", out],
+            None,
+        ),
+        // later commands find the artifacts of the earlier ones in the out dir
+        move_cmd(&package_path, &["coverage", "summary"], Some(&out_dir)),
+        move_cmd(
+            &package_path,
+            &["disassemble", "--name", "M"],
+            Some(&out_dir),
+        ),
+        move_cmd(&package_path, &["docgen"], Some(&out_dir)),
+        move_cmd(
+            &package_path,
+            &["sandbox", "--storage-dir", storage, "publish"],
+            Some(&out_dir),
+        ),
+        // packages share the out dir
+        move_cmd(&other_path, &["build", "--out-dir", out], None),
+    ];
+    set_mode(&package_path, 0o755);
+    for (success, stdout, stderr) in &outputs {
+        assert!(success, "{}\n{}", stdout, stderr);
+    }
+    assert!(outputs[2].1.contains("02::M"), "{}", outputs[2].1);
+    assert!(
+        outputs[3].1.contains("public one(): u64"),
+        "{}",
+        outputs[3].1
+    );
+
+    assert_eq!(list_files(&package_path), files);
+    let built = out_dir.join("Pkg").join("build").join("Pkg");
+    for artifact in [
+        "BuildInfo.yaml",
+        "bytecode_modules/M.mv",
+        "source_maps/M.mvsm",
+    ] {
+        assert!(built.join(artifact).is_file(), "{}", artifact);
+    }
+    assert!(out_dir.join("Pkg").join("doc").join("M.md").is_file());
+    assert!(out_dir.join("Pkg").join("Move.lock").is_file());
+    assert!(out_dir
+        .join("Other")
+        .join("build")
+        .join("Other")
+        .join("bytecode_modules")
+        .join("N.mv")
+        .is_file());
+    assert!(storage_dir
+        .join("0x00000000000000000000000000000002")
+        .join("modules")
+        .join("M.mv")
+        .is_file());
+}
+
+#[test]
+fn docgen_check_fails_when_docs_are_out_of_date() {
+    let workspace = tempfile::tempdir().unwrap();
+    let package_path = workspace.path().join("Documented");
+    let write_module = |doc: &str| {
+        write_package(
+            &package_path,
+            "Documented",
+            "",
+            &[(
+                "M",
+                &format!(
+                    "module 0x2::M {{\n    /// {}\n    public fun f() {{}}\n}}",
+                    doc
+                ),
+            )],
+        )
+    };
+    let docgen = |args: &[&str]| {
+        let output = Command::new(get_cli_binary_path())
+            .current_dir(&package_path)
+            .arg("docgen")
+            .args(args)
+            .output()
+            .unwrap();
+        (
+            output.status.success(),
+            String::from_utf8(output.stdout).unwrap(),
+            String::from_utf8(output.stderr).unwrap(),
+        )
+    };
+    let page_path = package_path.join("doc").join("M.md");
+
+    write_module("Does nothing.");
+    assert!(docgen(&[]).0);
+    let (success, stdout, _) = docgen(&["--check"]);
+    assert!(success, "{}", stdout);
+    assert!(
+        stdout.contains("Documentation in 'doc' is up to date"),
+        "{}",
+        stdout
+    );
+
+    // footers with timestamps or tool versions are not differences
+    let page = fs::read_to_string(&page_path).unwrap();
+    fs::write(
+        &page_path,
+        format!(
+            "{}\n<!-- Generated by move-docgen 1.0.0 on 2022-08-01T12:00:00Z -->\n",
+            page
+        ),
+    )
+    .unwrap();
+    assert!(docgen(&["--check"]).0);
+
+    write_module("Does nothing at all.");
+    fs::write(package_path.join("doc").join("Old.md"), "# Old\n").unwrap();
+    let committed = fs::read_to_string(&page_path).unwrap();
+    let (success, stdout, stderr) = docgen(&["--check"]);
+    assert!(!success);
+    assert!(stdout.contains("changed: doc/M.md"), "{}", stdout);
+    assert!(stdout.contains("removed: doc/Old.md"), "{}", stdout);
+    assert!(stdout.contains("-Does nothing."), "{}", stdout);
+    assert!(stdout.contains("+Does nothing at all."), "{}", stdout);
+    assert!(
+        stderr.contains("documentation in 'doc' is out of date: 2 files differ"),
+        "{}",
+        stderr
+    );
+    assert_eq!(fs::read_to_string(&page_path).unwrap(), committed);
+
+    fs::remove_file(package_path.join("doc").join("Old.md")).unwrap();
+    assert!(docgen(&[]).0);
+    let (success, stdout, _) = docgen(&["--check"]);
+    assert!(success, "{}", stdout);
+}
+
+#[test]
+fn gas_report_fails_on_regressions_over_the_baseline() {
+    let workspace = tempfile::tempdir().unwrap();
+    let package_path = workspace.path().join("Benchmarked");
+    let write_module = |rounds: u64| {
+        write_package(
+            &package_path,
+            "Benchmarked",
+            &stdlib_dependency(),
+            &[(
+                "M",
+                &format!(
+                    "module 0x2::M {{\n    const ROUNDS: u64 = {};\n\n    \
+                     public fun spin(): u64 {{\n        let i = 0;\n        \
+                     while (i < ROUNDS) i = i + 1;\n        i\n    }}\n\n    \
+                     #[test, gas_benchmark]\n    fun bench_spin() {{\n        \
+                     assert!(spin() == ROUNDS, 0);\n    }}\n\n    \
+                     #[test]\n    fun not_measured() {{}}\n}}",
+                    rounds
+                ),
+            )],
+        )
+    };
+    let gas_report = |args: &[&str]| {
+        let output = Command::new(get_cli_binary_path())
+            .current_dir(&package_path)
+            .args(["package", "gas-report"])
+            .args(args)
+            .output()
+            .unwrap();
+        (
+            output.status.success(),
+            String::from_utf8(output.stdout).unwrap(),
+            String::from_utf8(output.stderr).unwrap(),
+        )
+    };
+    let report_path = package_path.join("build").join("gas-report.json");
+    let baseline_path = workspace.path().join("baseline.json");
+
+    write_module(10);
+    let (success, stdout, stderr) = gas_report(&[]);
+    assert!(success, "{}\n{}", stdout, stderr);
+    assert!(stdout.contains("0x2::M::bench_spin"), "{}", stdout);
+    assert!(stdout.contains("  0x2::M::spin"), "{}", stdout);
+    assert!(!stdout.contains("not_measured"), "{}", stdout);
+    let report: serde_json::Value =
+        serde_json::from_str(&fs::read_to_string(&report_path).unwrap()).unwrap();
+    let benchmark = &report["benchmarks"]["0x2::M::bench_spin"];
+    assert!(benchmark["gas"].as_u64().unwrap() > 0);
+    assert_eq!(benchmark["calls"]["0x2::M::spin"]["calls"], 1);
+    fs::copy(&report_path, &baseline_path).unwrap();
+
+    let baseline = baseline_path.to_str().unwrap();
+    let (success, stdout, _) = gas_report(&["--baseline", baseline, "--max-regression", "5"]);
+    assert!(success, "{}", stdout);
+    assert!(stdout.contains("+0.0%"), "{}", stdout);
+
+    write_module(100);
+    let (success, stdout, stderr) = gas_report(&["--baseline", baseline, "--max-regression", "5"]);
+    assert!(!success, "{}", stdout);
+    assert!(
+        stderr.contains("gas grew by more than 5% over the baseline for 0x2::M::bench_spin"),
+        "{}",
+        stderr
+    );
+    // the report is written regardless, to become the next baseline
+    assert_ne!(
+        fs::read_to_string(&report_path).unwrap(),
+        fs::read_to_string(&baseline_path).unwrap()
+    );
+
+    let (success, stdout, stderr) = gas_report(&["--gas-budget", "10"]);
+    assert!(!success, "{}", stdout);
+    assert!(stderr.contains("1 gas benchmark(s) failed"), "{}", stderr);
+
+    let (success, stdout, _) = gas_report(&["--all"]);
+    assert!(success, "{}", stdout);
+    assert!(stdout.contains("0x2::M::not_measured"), "{}", stdout);
+}
+
+#[test]
+fn package_bench_compares_with_another_revision() {
+    let repo = tempfile::tempdir().unwrap();
+    let package_path = repo.path().join("Benchmarked");
+    let write_module = |rounds: u64, extra_benchmark: &str| {
+        write_package(
+            &package_path,
+            "Benchmarked",
+            &stdlib_dependency(),
+            &[(
+                "M",
+                &format!(
+                    "module 0x2::M {{\n    const ROUNDS: u64 = {};\n\n    \
+                     public fun spin(): u64 {{\n        let i = 0;\n        \
+                     while (i < ROUNDS) i = i + 1;\n        i\n    }}\n\n    \
+                     #[test, gas_benchmark]\n    fun bench_spin() {{\n        \
+                     assert!(spin() == ROUNDS, 0);\n    }}\n{}}}",
+                    rounds, extra_benchmark
+                ),
+            )],
+        )
+    };
+    let bench = |args: &[&str]| {
+        let output = Command::new(get_cli_binary_path())
+            .current_dir(&package_path)
+            .args(["package", "bench", "--samples", "10"])
+            .args(args)
+            .output()
+            .unwrap();
+        (
+            output.status.success(),
+            String::from_utf8(output.stdout).unwrap(),
+            String::from_utf8(output.stderr).unwrap(),
+        )
+    };
+
+    write_module(
+        10,
+        "\n    #[test, gas_benchmark]\n    fun bench_removed() {}\n",
+    );
+    commit_git_repo(repo.path());
+    write_module(
+        5_000,
+        "\n    #[test, gas_benchmark]\n    fun bench_added() {}\n",
+    );
+    run_git(repo.path(), &["commit", "-q", "-a", "-m", "spin longer"]);
+
+    let (success, stdout, stderr) = bench(&[]);
+    assert!(success, "{}\n{}", stdout, stderr);
+    assert!(stdout.contains("Time (10 samples)"), "{}", stdout);
+    assert!(stdout.contains("0x2::M::bench_spin"), "{}", stdout);
+
+    let (success, stdout, stderr) = bench(&["--compare", "HEAD~1", "--threshold", "50"]);
+    assert!(!success, "{}", stdout);
+    let row = stdout
+        .lines()
+        .find(|line| line.starts_with("0x2::M::bench_spin"))
+        .unwrap_or_else(|| panic!("{}", stdout));
+    assert!(row.ends_with("***"), "{}", row);
+    assert!(
+        stdout.contains("Only at 'HEAD~1':\n    0x2::M::bench_removed\n"),
+        "{}",
+        stdout
+    );
+    assert!(
+        stdout.contains("Only in the current package:\n    0x2::M::bench_added\n"),
+        "{}",
+        stdout
+    );
+    assert!(
+        stderr.contains(
+            "got significantly slower than at 'HEAD~1' by more than 50%: 0x2::M::bench_spin"
+        ),
+        "{}",
+        stderr
+    );
+    // the worktree of the compared revision is removed
+    let output = Command::new("git")
+        .current_dir(repo.path())
+        .args(["worktree", "list"])
+        .output()
+        .unwrap();
+    assert_eq!(String::from_utf8_lossy(&output.stdout).lines().count(), 1);
+
+    // faster than the compared revision is no regression
+    run_git(repo.path(), &["checkout", "-q", "HEAD~1"]);
+    let (success, stdout, stderr) = bench(&["--compare", "main", "--threshold", "50"]);
+    assert!(success, "{}\n{}", stdout, stderr);
+    assert!(stdout.contains("0x2::M::bench_spin"), "{}", stdout);
+}
+
+#[test]
+fn values_in_test_failures_are_cut_to_the_render_budget() {
+    let package = tempfile::tempdir().unwrap();
+    write_package(
+        package.path(),
+        "Blobs",
+        &stdlib_dependency(),
+        &[(
+            "M",
+            "module 0x2::M {\n    use std::vector;\n\n    \
+             struct Blob has key { chunks: vector<vector<u8>> }\n\n    \
+             #[test(account = @0x2)]\n    fun aborts_holding_a_megabyte(account: signer) {\n        \
+             let chunk = vector::empty<u8>();\n        let i = 0;\n        \
+             while (i < 1024) { vector::push_back(&mut chunk, 171); i = i + 1 };\n        \
+             let chunks = vector::empty();\n        let j = 0;\n        \
+             while (j < 1024) { vector::push_back(&mut chunks, copy chunk); j = j + 1 };\n        \
+             move_to(&account, Blob { chunks });\n        abort 7\n    }\n}",
+        )],
+    );
+    let test = |args: &[&str]| {
+        let output = Command::new(get_cli_binary_path())
+            .current_dir(package.path())
+            .args(["test", "--state_on_error"])
+            .args(args)
+            .output()
+            .unwrap();
+        assert!(!output.status.success());
+        String::from_utf8(output.stdout).unwrap()
+    };
+
+    let stdout = test(&[]);
+    assert!(
+        stdout.contains("Storage state at point of failure"),
+        "{}",
+        stdout
+    );
+    assert!(stdout.contains("abababab"), "{}", stdout);
+    assert!(stdout.contains(" more bytes)"), "{}", stdout);
+    // the megabyte is cut to the default budget of 4 KB
+    assert!(stdout.len() < 8 * 1024, "{} bytes", stdout.len());
+
+    let stdout = test(&["--max-value-render-bytes", "64"]);
+    let line = stdout
+        .lines()
+        .find(|line| line.contains("more bytes)"))
+        .unwrap_or_else(|| panic!("{}", stdout));
+    assert!(line.len() < 128, "{}", line);
+
+    let stdout = test(&["--redact-values"]);
+    assert!(stdout.contains("<redacted 0x2::M::Blob>"), "{}", stdout);
+    assert!(!stdout.contains("abababab"), "{}", stdout);
+}
+
+#[test]
+fn address_derive_agrees_with_the_unit_test_native() {
+    let derive = |args: &[&str]| {
+        let output = Command::new(get_cli_binary_path())
+            .args(["address", "derive"])
+            .args(args)
+            .output()
+            .unwrap();
+        assert!(
+            output.status.success(),
+            "{}",
+            String::from_utf8_lossy(&output.stderr)
+        );
+        String::from_utf8(output.stdout).unwrap().trim().to_string()
+    };
+    let alice = derive(&["--seed", "alice"]);
+    assert_eq!(alice, derive(&["--seed", "alice", "--scheme", "sha3"]));
+    assert_ne!(alice, derive(&["--seed", "bob"]));
+    assert!(derive(&["--seed", "alice", "--scheme", "named"]).ends_with("616c696365"));
+
+    let dir = tempfile::tempdir().unwrap();
+    let package_path = dir.path().join("Derived");
+    write_package(
+        &package_path,
+        "Derived",
+        &stdlib_dependency(),
+        &[(
+            "DerivedTests",
+            &format!(
+                "#[test_only]\nmodule 0x2::DerivedTests {{\n    use std::unit_test;\n\n    \
+                 #[test]\n    fun derived_address_matches_the_cli() {{\n        \
+                 assert!(unit_test::derive_address(b\"alice\") == @{}, 0);\n    }}\n}}",
+                alice
+            ),
+        )],
+    );
+    let output = Command::new(get_cli_binary_path())
+        .current_dir(&package_path)
+        .arg("test")
+        .output()
+        .unwrap();
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    assert!(
+        output.status.success(),
+        "{}{}",
+        stdout,
+        String::from_utf8_lossy(&output.stderr)
+    );
+    assert!(stdout.contains("[ PASS    ] 0x2::DerivedTests::derived_address_matches_the_cli"));
+}
+
+#[test]
+fn address_book_round_trips_through_the_manifest_and_the_sandbox() {
+    let dir = tempfile::tempdir().unwrap();
+    let package_path = dir.path().join("Book");
+    write_package(
+        &package_path,
+        "Book",
+        "\n[addresses]\n# the publisher\nbook = \"0x2\"\n",
+        &[],
+    );
+    let move_cli = |args: &[&str]| {
+        let output = Command::new(get_cli_binary_path())
+            .current_dir(&package_path)
+            .args(args)
+            .output()
+            .unwrap();
+        (
+            output.status.success(),
+            String::from_utf8(output.stdout).unwrap(),
+            String::from_utf8(output.stderr).unwrap(),
+        )
+    };
+    let manifest = || fs::read_to_string(package_path.join("Move.toml")).unwrap();
+
+    let (success, _, stderr) = move_cli(&["sandbox", "account", "create", "--name", "alice"]);
+    assert!(success, "{}", stderr);
+    let (success, book, stderr) = move_cli(&["address", "export"]);
+    assert!(success, "{}", stderr);
+    assert!(book.contains("[addresses]\nbook = \"0x2\"\n"), "{}", book);
+    assert!(book.contains("[accounts]\nalice = "), "{}", book);
+    let (success, json, stderr) = move_cli(&["address", "export", "--format", "json"]);
+    assert!(success, "{}", stderr);
+    let json: serde_json::Value = serde_json::from_str(&json).unwrap();
+    assert_eq!(json["addresses"]["book"], "0x2");
+    let alice = json["accounts"]["alice"].as_str().unwrap().to_string();
+
+    let toml_path = dir.path().join("addresses.toml");
+    fs::write(&toml_path, &book).unwrap();
+    let toml_file = toml_path.to_str().unwrap();
+    let (success, stdout, stderr) = move_cli(&["address", "import", toml_file, "--to-manifest"]);
+    assert!(success, "{}", stderr);
+    assert!(
+        stdout.contains("into Move.toml: 1 added, 0 replaced, 1 unchanged"),
+        "{}",
+        stdout
+    );
+    assert!(manifest().contains("# the publisher\nbook = \"0x2\"\n"));
+    assert!(manifest().contains(&format!("alice = \"{}\"", alice)));
+
+    // a book assigning alice elsewhere is refused by both targets, unless forced
+    let json_path = dir.path().join("addresses.json");
+    fs::write(&json_path, "{ \"accounts\": { \"alice\": \"0x42\" } }").unwrap();
+    let json_file = json_path.to_str().unwrap();
+    let before = manifest();
+    for target in ["--to-manifest", "--to-sandbox"] {
+        let (success, _, stderr) = move_cli(&["address", "import", json_file, target]);
+        assert!(!success);
+        assert!(
+            stderr.contains("assigns 1 name(s) another address than")
+                && stderr.contains("Pass --force"),
+            "{}",
+            stderr
+        );
+    }
+    assert_eq!(before, manifest());
+    let (_, accounts, _) = move_cli(&["sandbox", "account", "list"]);
+    assert!(accounts.contains(&alice), "{}", accounts);
+
+    for target in ["--to-manifest", "--to-sandbox"] {
+        let (success, stdout, stderr) =
+            move_cli(&["address", "import", json_file, target, "--force"]);
+        assert!(success, "{}", stderr);
+        assert!(stdout.contains("replaced alice = 0x42"), "{}", stdout);
+    }
+    assert!(manifest().contains("alice = \"0x42\""));
+    assert!(manifest().contains("# the publisher\n"));
+    let (_, accounts, _) = move_cli(&["sandbox", "account", "list"]);
+    assert!(accounts.contains("@alice"), "{}", accounts);
+    assert!(!accounts.contains(&alice), "{}", accounts);
+}
+
+#[test]
+fn package_verify_runs_stages_in_order() {
+    let dir = tempfile::tempdir().unwrap();
+    // the test stage needs the unit test module of the standard library
+    write_package(
+        &dir.path().join("util"),
+        "Util",
+        &stdlib_dependency(),
+        &[(
+            "Math",
+            "module 0x2::Math {\n    public fun double(x: u64): u64 { x * 2 }\n}",
+        )],
+    );
+    let util = "Util = { local = \"../util\" }\n";
+    let clean = dir.path().join("clean");
+    write_package(
+        &clean,
+        "Clean",
+        util,
+        &[(
+            "Calc",
+            "module 0x3::Calc {\n    use 0x2::Math;\n\n    \
+             public fun quadruple(x: u64): u64 { Math::double(Math::double(x)) }\n\n    \
+             #[test]\n    fun test_quadruple() { assert!(quadruple(2) == 8, 0) }\n}",
+        )],
+    );
+    // depends on Util without using any of its modules
+    let unused_dep = dir.path().join("unused_dep");
+    write_package(
+        &unused_dep,
+        "UnusedDep",
+        util,
+        &[(
+            "One",
+            "module 0x3::One {\n    public fun one(): u64 { 1 }\n\n    \
+             #[test]\n    fun test_one() { assert!(one() == 1, 0) }\n}",
+        )],
+    );
+    let verify = |package: &Path, args: &[&str]| {
+        Command::new(env!("CARGO_BIN_EXE_move"))
+            .current_dir(package)
+            .args(["package", "verify", "--format", "json"])
+            .args(args)
+            .output()
+            .unwrap()
+    };
+    let stages = |output: &std::process::Output| -> Vec<(String, String)> {
+        let report: serde_json::Value = serde_json::from_slice(&output.stdout).unwrap();
+        report["stages"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .map(|stage| {
+                (
+                    stage["stage"].as_str().unwrap().to_string(),
+                    stage["status"].as_str().unwrap().to_string(),
+                )
+            })
+            .collect()
+    };
+    let expected = |statuses: &[(&str, &str)]| -> Vec<(String, String)> {
+        statuses
+            .iter()
+            .map(|(stage, status)| (stage.to_string(), status.to_string()))
+            .collect()
+    };
+
+    let output = verify(&clean, &["--with-coverage", "100"]);
+    assert!(
+        output.status.success(),
+        "{}",
+        String::from_utf8_lossy(&output.stderr)
+    );
+    assert_eq!(
+        stages(&output),
+        expected(&[
+            ("manifest", "passed"),
+            ("build", "passed"),
+            ("test", "passed"),
+            ("lint", "passed"),
+            ("coverage", "passed"),
+        ])
+    );
+
+    // the lint stage fails, and the stages after it are skipped
+    let output = verify(&unused_dep, &["--with-coverage", "100"]);
+    assert_eq!(output.status.code(), Some(1));
+    assert_eq!(
+        stages(&output),
+        expected(&[
+            ("manifest", "passed"),
+            ("build", "passed"),
+            ("test", "passed"),
+            ("lint", "failed"),
+            ("coverage", "skipped"),
+        ])
+    );
+    let error = String::from_utf8_lossy(&output.stderr);
+    assert!(
+        error.contains("unused dependency: none of the modules of `Util` is used by the package"),
+        "{}",
+        error
+    );
+    assert!(error.contains("Pass --keep-going"), "{}", error);
+
+    // unless asked to keep going
+    let output = verify(&unused_dep, &["--with-coverage", "100", "--keep-going"]);
+    assert_eq!(output.status.code(), Some(1));
+    assert_eq!(
+        stages(&output),
+        expected(&[
+            ("manifest", "passed"),
+            ("build", "passed"),
+            ("test", "passed"),
+            ("lint", "failed"),
+            ("coverage", "passed"),
+        ])
+    );
+
+    // the text report lists the stages in the same order
+    let output = Command::new(env!("CARGO_BIN_EXE_move"))
+        .current_dir(&unused_dep)
+        .args(["package", "verify"])
+        .output()
+        .unwrap();
+    assert_eq!(output.status.code(), Some(1));
+    let report = String::from_utf8_lossy(&output.stdout);
+    let lines: Vec<_> = report
+        .lines()
+        .map(|line| line.split_whitespace().collect::<Vec<_>>())
+        .collect();
+    assert_eq!(lines[0], ["Verification", "summary", "for", "UnusedDep:"]);
+    assert_eq!(lines[1][..2], ["manifest", "passed"]);
+    assert_eq!(lines[2][..2], ["build", "passed"]);
+    assert_eq!(lines[3][..2], ["test", "passed"]);
+    assert_eq!(lines[4][..2], ["lint", "failed"]);
+}
+
+/// A registry index listing two major versions of `coin`, and `CoinSwap`
+const REGISTRY_INDEX: &str = r#"[
+    {"name": "coin", "owner": "alice", "version": "1.0.0", "description": "A fungible coin",
+     "git": "https://github.com/alice/coin.git", "rev": "c0ffee1"},
+    {"name": "coin", "owner": "alice", "version": "1.4.2", "description": "A fungible coin",
+     "git": "https://github.com/alice/coin.git", "rev": "c0ffee2"},
+    {"name": "coin", "owner": "alice", "version": "2.0.0", "description": "A fungible coin",
+     "git": "https://github.com/alice/coin.git", "rev": "c0ffee3"},
+    {"name": "CoinSwap", "owner": "bob", "version": "0.3.0", "description": "Swaps coins",
+     "git": "https://github.com/bob/dex.git", "rev": "beef", "subdir": "swap"}
+]"#;
+
+/// Run `move package add` in `package` with the `REGISTRY_INDEX` and `args`, writing `stdin` to
+/// its piped stdin
+fn package_add(package: &Path, args: &[&str], stdin: &str) -> (bool, String) {
+    let index = package.join("index.json");
+    fs::write(&index, REGISTRY_INDEX).unwrap();
+    let mut child = Command::new(env!("CARGO_BIN_EXE_move"))
+        .current_dir(package)
+        .args(["package", "add", "--index", "index.json"])
+        .args(args)
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .unwrap();
+    child
+        .stdin
+        .take()
+        .unwrap()
+        .write_all(stdin.as_bytes())
+        .unwrap();
+    let output = child.wait_with_output().unwrap();
+    (
+        output.status.success(),
+        String::from_utf8_lossy(&output.stderr).to_string(),
+    )
+}
+
+#[test]
+fn package_add_asks_which_matching_package_to_add() {
+    let dir = tempfile::tempdir().unwrap();
+    let package = dir.path().join("Pkg");
+    write_package(&package, "Pkg", "", &[]);
+
+    let (success, stderr) = package_add(&package, &["coin"], "2\n");
+    assert!(success, "{}", stderr);
+    for candidate in [
+        "1) coin 1.4.2 (by alice) - A fungible coin",
+        "2) coin 2.0.0 (by alice) - A fungible coin",
+        "3) CoinSwap 0.3.0 (by bob) - Swaps coins",
+    ] {
+        assert!(stderr.contains(candidate), "{}", stderr);
+    }
+    let manifest = fs::read_to_string(package.join("Move.toml")).unwrap();
+    let today = chrono::Utc::now().format("%Y-%m-%d").to_string();
+    assert!(
+        manifest.contains(&format!(
+            "[dependencies]\n# coin 2.0.0, added from index.json on {}\n\
+             coin = {{ git = \"https://github.com/alice/coin.git\", rev = \"c0ffee3\" }}\n",
+            today
+        )),
+        "{}",
+        manifest
+    );
+
+    // the dependency is only added once, whatever the case of its name
+    let (success, stderr) = package_add(&package, &["COIN", "--version", "2"], "");
+    assert!(!success);
+    assert!(
+        stderr.contains("The package already has coin among its [dependencies]"),
+        "{}",
+        stderr
+    );
+
+    for (stdin, error) in [
+        ("4\n", "'4' is not a number from 1 to 3"),
+        ("", "stdin was closed before a number was read"),
+    ] {
+        let (success, stderr) = package_add(&package, &["coin", "--dev-dependency"], stdin);
+        assert!(!success);
+        assert!(stderr.contains(error), "{}", stderr);
+    }
+}
+
+#[test]
+fn package_add_exact_fails_listing_the_candidates_when_ambiguous() {
+    let dir = tempfile::tempdir().unwrap();
+    let package = dir.path().join("Pkg");
+    write_package(&package, "Pkg", "", &[]);
+    let manifest_path = package.join("Move.toml");
+    let manifest = fs::read_to_string(&manifest_path).unwrap();
+
+    // nothing is read from stdin: the choice is not asked for
+    let (success, stderr) = package_add(&package, &["coin", "--exact"], "1\n");
+    assert!(!success);
+    assert!(
+        stderr.contains(
+            "'coin' matches 2 packages of index.json:\n    \
+             coin 1.4.2 (by alice) - A fungible coin\n    \
+             coin 2.0.0 (by alice) - A fungible coin\n"
+        ),
+        "{}",
+        stderr
+    );
+    assert_eq!(fs::read_to_string(&manifest_path).unwrap(), manifest);
+
+    let (success, stderr) = package_add(&package, &["Coin", "--exact", "--version", "<2"], "");
+    assert!(success, "{}", stderr);
+    let (success, stderr) = package_add(&package, &["swap", "--dev-dependency"], "");
+    assert!(success, "{}", stderr);
+    let manifest = fs::read_to_string(&manifest_path).unwrap();
+    assert!(manifest.contains("rev = \"c0ffee2\""), "{}", manifest);
+    assert!(
+        manifest.contains("[dev-dependencies]\n# CoinSwap 0.3.0, added from index.json on ")
+            && manifest.contains(
+                "CoinSwap = { git = \"https://github.com/bob/dex.git\", rev = \"beef\", subdir = \
+             \"swap\" }"
+            ),
+        "{}",
+        manifest
+    );
+}
+
+#[test]
+fn package_add_searches_the_registry() {
+    let (dir, move_home, _) = setup_move_home();
+    let package = dir.path().join("Pkg");
+    write_package(&package, "Pkg", "", &[]);
+    let server = MockServer::start();
+    let search = server.mock(|when, then| {
+        when.method(GET)
+            .path("/api/v1/packages/search")
+            .query_param("query", "coin");
+        then.status(200).body(REGISTRY_INDEX);
+    });
+    init_stub_registry_file(&move_home, &server.base_url());
+
+    let output = Command::new(env!("CARGO_BIN_EXE_move"))
+        .env("MOVE_HOME", &move_home)
+        .current_dir(&package)
+        .args(["package", "add", "coin", "--version", "1.0.0, <1.1"])
+        .output()
+        .unwrap();
+    assert!(
+        output.status.success(),
+        "{}",
+        String::from_utf8_lossy(&output.stderr)
+    );
+    search.assert();
+    let manifest = fs::read_to_string(package.join("Move.toml")).unwrap();
+    assert!(
+        manifest.contains(&format!(
+            "# coin 1.0.0, added from {} on ",
+            server.base_url()
+        )),
+        "{}",
+        manifest
+    );
+}
+
+fn write_package(path: &Path, name: &str, dependencies: &str, modules: &[(&str, &str)]) {
+    fs::create_dir_all(path.join("sources")).unwrap();
+    fs::write(
+        path.join("Move.toml"),
+        format!(
+            "[package]\nname = \"{}\"\nversion = \"0.0.0\"\n\n[dependencies]\n{}",
+            name, dependencies
+        ),
+    )
+    .unwrap();
+    for (module_name, source) in modules {
+        fs::write(
+            path.join("sources").join(format!("{}.move", module_name)),
+            format!("{}\n", source),
+        )
+        .unwrap();
+    }
+}
+
+const MOVE_STDLIB_PATH: &str = "../../move-stdlib";
+
+/// The dependency on the standard library, with `std` assigned, to pass to `write_package`
+fn stdlib_dependency() -> String {
+    let stdlib_path = fs::canonicalize(MOVE_STDLIB_PATH).unwrap();
+    format!(
+        "MoveStdlib = {{ local = \"{}\" }}\n\n[addresses]\nstd = \"0x1\"\n",
+        path_to_string(&stdlib_path)
+    )
+}
+
+fn run_git(repo_path: &Path, args: &[&str]) {
+    let status = Command::new("git")
+        .current_dir(repo_path)
+        .args(["-c", "user.name=Test", "-c", "user.email=test@example.com"])
+        .args(args)
+        .envs(ALLOW_FILE_PROTOCOL)
+        .stdout(Stdio::null())
+        .status()
+        .unwrap();
+    assert!(status.success(), "git {} failed", args.join(" "));
+}
+
+fn commit_git_repo(repo_path: &Path) {
+    run_git(repo_path, &["init", "-q", "-b", "main"]);
+    run_git(repo_path, &["add", "."]);
+    run_git(repo_path, &["commit", "-q", "-m", "initial commit"]);
+}
+
+fn add_submodule(repo_path: &Path, submodule_repo_path: &Path, path: &str) {
+    run_git(
+        repo_path,
+        &[
+            "submodule",
+            "add",
+            "-q",
+            &submodule_repo_path.to_string_lossy(),
+            path,
+        ],
+    );
+    run_git(repo_path, &["commit", "-q", "-m", "add submodule"]);
+}
+
+const UPLOAD_PACKAGE_PATH: &str = "./tests/upload_tests";
+#[test]
+fn upload_package_to_movey_works() {
+    let (_dir, package_path, move_home) = setup_upload_package("valid_package1");
+    init_git(&package_path, true);
+    let server = MockServer::start();
+    let server_mock =
+        mock_movey_upload_with_response_body_and_status_code(&server, "valid_package1", 200, None);
+    init_stub_registry_file(&move_home, &server.base_url());
+
+    let cli_exe = env!("CARGO_BIN_EXE_move");
+    let output = Command::new(cli_exe)
+        .env("MOVE_HOME", &move_home)
+        .current_dir(&package_path)
+        .args(["movey-upload"])
+        .output()
+        .unwrap();
+
+    server_mock.assert();
+    assert!(output.status.success());
+    let output = String::from_utf8_lossy(output.stdout.as_slice()).to_string();
+    assert!(
+        output.contains("Your package has been successfully uploaded to Movey"),
+        "{}",
+        output
+    );
+}
+
+#[test]
+fn upload_package_to_movey_prefers_the_token_of_movey_api_token() {
+    let (_dir, package_path, move_home) = setup_upload_package("valid_package1");
+    init_git(&package_path, true);
+    let upload = |env_token: Option<&str>| {
+        let mut command = Command::new(get_cli_binary_path());
+        command
+            .env("MOVE_HOME", &move_home)
+            .env_remove("MOVEY_API_TOKEN")
+            .current_dir(&package_path)
+            .args(["movey-upload"]);
+        if let Some(token) = env_token {
+            command.env("MOVEY_API_TOKEN", token);
+        }
+        let output = command.output().unwrap();
+        (
+            output.status.success(),
+            String::from_utf8(output.stderr).unwrap(),
+        )
+    };
+    let server = MockServer::start();
+    let with_env_token = server.mock(|when, then| {
+        when.method(POST)
+            .path("/api/v1/packages/upload")
+            .json_body_partial(r#"{ "token": "env-token" }"#);
+        then.status(200).body("");
+    });
+
+    // only the environment variable holds a token
+    fs::write(
+        credential_path(&move_home),
+        format!("[registry]\nurl = \"{}\"\n", server.base_url()),
+    )
+    .unwrap();
+    let (success, stderr) = upload(Some("env-token"));
+    assert!(success, "{}", stderr);
+    with_env_token.assert_hits(1);
+
+    // the environment variable wins over the credential file
+    init_stub_registry_file(&move_home, &server.base_url());
+    let (success, stderr) = upload(Some("env-token"));
+    assert!(success, "{}", stderr);
+    with_env_token.assert_hits(2);
+
+    // neither holds a token
+    fs::remove_file(credential_path(&move_home)).unwrap();
+    let (success, stderr) = upload(None);
+    assert!(!success);
+    assert!(
+        stderr.contains("Set the MOVEY_API_TOKEN environment variable to the token")
+            && stderr.contains("or run `move movey-login`"),
+        "{}",
+        stderr
+    );
+    with_env_token.assert_hits(2);
+}
+
+#[test]
+fn upload_package_to_movey_prints_error_message_if_server_respond_4xx() {
+    let (_dir, package_path, move_home) = setup_upload_package("valid_package2");
+    init_git(&package_path, true);
+    let server = MockServer::start();
+    let server_mock = mock_movey_upload_with_response_body_and_status_code(
+        &server,
+        "valid_package2",
+        400,
+        Some("Invalid Api token"),
+    );
+    init_stub_registry_file(&move_home, &server.base_url());
+
+    let cli_exe = env!("CARGO_BIN_EXE_move");
+    let output = Command::new(cli_exe)
+        .env("MOVE_HOME", &move_home)
+        .current_dir(&package_path)
+        .args(["movey-upload"])
+        .output()
+        .unwrap();
+
+    server_mock.assert();
+    assert!(!output.status.success());
+    let output = String::from_utf8_lossy(output.stderr.as_slice()).to_string();
+    assert!(output.contains("Error: Invalid Api token"), "{}", output);
+}
+
+#[test]
+fn upload_package_to_movey_prints_hardcoded_error_message_if_server_respond_5xx() {
+    let (_dir, package_path, move_home) = setup_upload_package("valid_package3");
+    init_git(&package_path, true);
+    let server = MockServer::start();
+    let server_mock = mock_movey_upload_with_response_body_and_status_code(
+        &server,
+        "valid_package3",
+        500,
+        Some("Invalid Api token"),
+    );
+    init_stub_registry_file(&move_home, &server.base_url());
+
+    let cli_exe = env!("CARGO_BIN_EXE_move");
+    let output = Command::new(cli_exe)
+        .env("MOVE_HOME", &move_home)
+        .current_dir(&package_path)
+        .args(["movey-upload"])
+        .output()
+        .unwrap();
+
+    server_mock.assert();
+    assert!(!output.status.success());
+    let output = String::from_utf8_lossy(output.stderr.as_slice()).to_string();
+    assert!(
+        output.contains("Error: An unexpected error occurred. Please try again later"),
+        "{}",
+        output
+    );
+}
+
+#[test]
+fn upload_package_to_movey_with_no_remote_should_panic() {
+    let (_dir, package_path, move_home) = setup_upload_package("no_git_remote_package");
+    init_git(&package_path, false);
+
+    let cli_exe = env!("CARGO_BIN_EXE_move");
+    let output = Command::new(cli_exe)
+        .env("MOVE_HOME", &move_home)
+        .current_dir(&package_path)
+        .args(["movey-upload"])
+        .output()
+        .unwrap();
+
+    assert!(!output.status.success());
+    let error = String::from_utf8_lossy(output.stderr.as_slice()).to_string();
+    assert!(error.contains("invalid git repository"));
+}
+
+#[test]
+fn upload_dry_run_prints_the_request_without_sending_it() {
+    let (_dir, package_path, move_home) = setup_upload_package("valid_package1");
+    let server = MockServer::start();
+    let server_mock =
+        mock_movey_upload_with_response_body_and_status_code(&server, "valid_package1", 200, None);
+    init_stub_registry_file(&move_home, &server.base_url());
+    let dry_run = |args: &[&str]| {
+        Command::new(get_cli_binary_path())
+            .env("MOVE_HOME", &move_home)
+            .env_remove("MOVEY_API_TOKEN")
+            .current_dir(&package_path)
+            .args(["movey-upload", "--dry-run"])
+            .args(args)
+            .output()
+            .unwrap()
+    };
+
+    // the checks of an upload still fail it
+    init_git(&package_path, false);
+    let output = dry_run(&[]);
+    assert!(!output.status.success());
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(stderr.contains("invalid git repository"), "{}", stderr);
+    Command::new("git")
+        .current_dir(&package_path)
+        .args([
+            "remote",
+            "add",
+            "origin",
+            "https://github.com/move-language/move",
+        ])
+        .output()
+        .unwrap();
+    let output = dry_run(&[]);
+    assert!(!output.status.success());
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(
+        stderr.contains("The repository has no commits"),
+        "{}",
+        stderr
+    );
+
+    init_git(&package_path, true);
+    let credential = fs::read_to_string(credential_path(&move_home)).unwrap();
+    let output = dry_run(&[]);
+    assert!(
+        output.status.success(),
+        "{}",
+        String::from_utf8_lossy(&output.stderr)
+    );
+    let preview: serde_json::Value = serde_json::from_slice(&output.stdout).unwrap();
+    let head = Command::new("git")
+        .current_dir(&package_path)
+        .args(["rev-parse", "HEAD"])
+        .output()
+        .unwrap();
+    assert_eq!(preview["rev"], String::from_utf8_lossy(&head.stdout).trim());
+    let request = &preview["request"];
+    assert_eq!(
+        request["github_repo_url"],
+        "https://github.com/move-language/move"
+    );
+    assert_eq!(request["name"], "valid_package1");
+    assert_eq!(request["token"], "<redacted>");
+    assert!(request.get("rev").is_none(), "{}", request);
+    assert!(preview.get("archive_digest").is_none(), "{}", preview);
+    assert_eq!(
+        fs::read_to_string(credential_path(&move_home)).unwrap(),
+        credential
+    );
+
+    // the archive is made, but not sent
+    let output = dry_run(&["--archive"]);
+    assert!(
+        output.status.success(),
+        "{}",
+        String::from_utf8_lossy(&output.stderr)
+    );
+    let preview: serde_json::Value = serde_json::from_slice(&output.stdout).unwrap();
+    assert_eq!(preview["archive_digest"].as_str().unwrap().len(), 64);
+
+    fs::remove_file(credential_path(&move_home)).unwrap();
+    let output = dry_run(&[]);
+    assert!(!output.status.success());
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(stderr.contains("or run `move movey-login`"), "{}", stderr);
+    server_mock.assert_hits(0);
+}
+
+#[test]
+fn upload_package_with_mismatched_name_asks_for_confirmation() {
+    let (_dir, package_path, move_home) = setup_upload_package("mismatched_name_package");
+    init_git(&package_path, true);
+    let server = MockServer::start();
+    let server_mock =
+        mock_movey_upload_with_response_body_and_status_code(&server, "my_lib", 200, None);
+    init_stub_registry_file(&move_home, &server.base_url());
+
+    let cli_exe = env!("CARGO_BIN_EXE_move");
+    let upload = |args: &[&str]| {
+        Command::new(cli_exe)
+            .env("MOVE_HOME", &move_home)
+            .current_dir(&package_path)
+            .arg("movey-upload")
+            .args(args)
+            .output()
+            .unwrap()
+    };
+
+    // stdin is not a terminal, so the upload cannot be confirmed interactively
+    let output = upload(&[]);
+    assert!(!output.status.success());
+    let error = String::from_utf8_lossy(&output.stderr);
+    assert!(
+        error.contains("package name:    my_lib")
+            && error.contains("directory name:  mismatched_name_package")
+            && error.contains("repository name: move")
+            && error.contains("Pass --yes to confirm without being asked"),
+        "{}",
+        error
+    );
+    server_mock.assert_hits(0);
+
+    for args in [&["--yes"], &["--no-name-check"]] {
+        let output = upload(args);
+        assert!(
+            output.status.success(),
+            "{}",
+            String::from_utf8_lossy(&output.stderr)
+        );
+    }
+    server_mock.assert_hits(2);
+}
+
+#[test]
+fn upload_lists_packages_under_their_lowercase_name() {
+    let (dir, move_home, _) = setup_move_home();
+    let package_path = dir.path().join("MyLib");
+    write_package(&package_path, "MyLib", "", &[("M", "module 0x2::M {}")]);
+    commit_git_repo(&package_path);
+    run_git(
+        &package_path,
+        &[
+            "remote",
+            "add",
+            "origin",
+            "https://github.com/move-language/move.git",
+        ],
+    );
+    let server = MockServer::start();
+    let server_mock =
+        mock_movey_upload_with_response_body_and_status_code(&server, "mylib", 200, None);
+    init_stub_registry_file(&move_home, &server.base_url());
+
+    let output = Command::new(env!("CARGO_BIN_EXE_move"))
+        .env("MOVE_HOME", &move_home)
+        .current_dir(&package_path)
+        .args(["movey-upload"])
+        .output()
+        .unwrap();
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(output.status.success(), "{}", stderr);
+    server_mock.assert();
+    assert!(
+        stderr.contains("Warning: the package 'MyLib' is listed in the registry as 'mylib'"),
+        "{}",
+        stderr
+    );
+}
+
+#[test]
+fn upload_rejects_package_names_that_are_not_identifiers() {
+    let (dir, move_home, _) = setup_move_home();
+    let package_path = dir.path().join("my-lib");
+    write_package(&package_path, "my-lib", "", &[("M", "module 0x2::M {}")]);
+    commit_git_repo(&package_path);
+    run_git(
+        &package_path,
+        &[
+            "remote",
+            "add",
+            "origin",
+            "https://github.com/move-language/move.git",
+        ],
+    );
+    let server = MockServer::start();
+    let server_mock =
+        mock_movey_upload_with_response_body_and_status_code(&server, "my-lib", 200, None);
+    init_stub_registry_file(&move_home, &server.base_url());
+    let upload = |args: &[&str]| {
+        Command::new(env!("CARGO_BIN_EXE_move"))
+            .env("MOVE_HOME", &move_home)
+            .current_dir(&package_path)
+            .arg("movey-upload")
+            .args(args)
+            .output()
+            .unwrap()
+    };
+
+    let output = upload(&[]);
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(!output.status.success());
+    assert!(
+        stderr.contains(
+            "Error: Invalid package name 'my-lib'. Package names must be Move identifiers: an \
+             ASCII letter or underscore followed by ASCII letters, digits and underscores. Did \
+             you mean 'my_lib'? Pass --allow-nonstandard-names to upload legacy packages anyway"
+        ),
+        "{}",
+        stderr
+    );
+    server_mock.assert_hits(0);
+
+    let output = upload(&["--allow-nonstandard-names"]);
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(output.status.success(), "{}", stderr);
+    assert!(
+        stderr.contains("Warning: Invalid package name 'my-lib'"),
+        "{}",
+        stderr
+    );
+    server_mock.assert();
+}
+
+#[test]
+fn upload_package_without_modules_requires_allow_empty() {
+    for name in ["empty_package", "scripts_only_package"] {
+        let (dir, move_home, _) = setup_move_home();
+        let package_path = dir.path().join(name);
+        copy_dir(&Path::new("./tests/build_tests").join(name), &package_path);
+        let package_path = path_to_string(&package_path);
+        init_git(&package_path, true);
+        let server = MockServer::start();
+        let server_mock = server.mock(|when, then| {
+            when.method(POST).path("/api/v1/packages/upload");
+            then.status(200);
+        });
+        init_stub_registry_file(&move_home, &server.base_url());
+
+        let cli_exe = env!("CARGO_BIN_EXE_move");
+        let upload = |args: &[&str]| {
+            Command::new(cli_exe)
+                .env("MOVE_HOME", &move_home)
+                .current_dir(&package_path)
+                .args(["movey-upload", "--no-name-check"])
+                .args(args)
+                .output()
+                .unwrap()
+        };
+
+        let output = upload(&[]);
+        assert!(!output.status.success());
+        let error = String::from_utf8_lossy(&output.stderr);
+        assert!(
+            error.contains(
+                "The package has no modules under 'sources'. Pass --allow-empty to upload it anyway"
+            ),
+            "{}",
+            error
+        );
+        server_mock.assert_hits(0);
+
+        let output = upload(&["--allow-empty"]);
+        assert!(
+            output.status.success(),
+            "{}",
+            String::from_utf8_lossy(&output.stderr)
+        );
+        server_mock.assert_hits(1);
+    }
+}
+
+#[test]
+fn upload_attaches_the_changelog_section_of_the_version_as_release_notes() {
+    let (dir, move_home, _) = setup_move_home();
+    let package_path = dir.path().join("Coins");
+    write_package(
+        &package_path,
+        "Coins",
+        "",
+        &[("Coin", "module 0x2::Coin {}")],
+    );
+    fs::write(
+        package_path.join("CHANGELOG.md"),
+        "# Changelog\n\n## [Unreleased]\n\n- Coin::split\n\n\
+         ## [0.0.0] - 2022-10-01\n\n### Added\n\n- Coin::burn\n\n\
+         ## [0.0.0-rc1] - 2022-09-01\n\n- First release candidate\n\n\
+         [0.0.0]: https://github.com/move-language/move/releases/tag/v0.0.0\n",
+    )
+    .unwrap();
+    fs::write(package_path.join("RELEASES.md"), "## 1.0.0\n\nNothing\n").unwrap();
+    commit_git_repo(&package_path);
+    run_git(
+        &package_path,
+        &[
+            "remote",
+            "add",
+            "origin",
+            "https://github.com/move-language/move.git",
+        ],
+    );
+    let server = MockServer::start();
+    let with_notes = server.mock(|when, then| {
+        when.method(POST)
+            .path("/api/v1/packages/upload")
+            .json_body_partial(json!({ "release_notes": "### Added\n\n- Coin::burn" }).to_string());
+        then.status(200);
+    });
+    init_stub_registry_file(&move_home, &server.base_url());
+    let upload = |args: &[&str]| {
+        Command::new(env!("CARGO_BIN_EXE_move"))
+            .env("MOVE_HOME", &move_home)
+            .current_dir(&package_path)
+            .args(["movey-upload", "--no-name-check"])
+            .args(args)
+            .output()
+            .unwrap()
+    };
+
+    let output = upload(&[]);
+    assert!(
+        output.status.success(),
+        "{}",
+        String::from_utf8_lossy(&output.stderr)
+    );
+    with_notes.assert_hits(1);
+
+    let output = upload(&["--max-release-notes-size", "10"]);
+    assert!(!output.status.success());
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(
+        stderr.contains("The release notes of version 0.0.0 in")
+            && stderr.contains("are 23 bytes long, over the limit of 10 bytes"),
+        "{}",
+        stderr
+    );
+
+    for (changelog, error) in [
+        ("RELEASES.md", "has no section for version 0.0.0"),
+        ("NEWS.md", "The changelog 'NEWS.md' does not exist"),
+    ] {
+        let output = upload(&["--changelog", changelog]);
+        assert!(!output.status.success());
+        let stderr = String::from_utf8_lossy(&output.stderr);
+        assert!(stderr.contains(error), "{}", stderr);
+    }
+    with_notes.assert_hits(1);
+}
+
+#[test]
+fn uploads_are_recorded_in_the_publish_log() {
+    let (_dir, package_path, move_home) = setup_upload_package("valid_package1");
+    init_git(&package_path, true);
+    let server = MockServer::start();
+    init_stub_registry_file(&move_home, &server.base_url());
+
+    let cli_exe = env!("CARGO_BIN_EXE_move");
+    let move_cmd = |args: &[&str]| {
+        let output = Command::new(cli_exe)
+            .env("MOVE_HOME", &move_home)
+            .current_dir(&package_path)
+            .args(args)
+            .output()
+            .unwrap();
+        assert!(
+            output.status.success(),
+            "{}",
+            String::from_utf8_lossy(&output.stderr)
+        );
+        String::from_utf8(output.stdout).unwrap()
+    };
+    let head = || {
+        let output = Command::new("git")
+            .current_dir(&package_path)
+            .args(["rev-parse", "HEAD"])
+            .output()
+            .unwrap();
+        String::from_utf8(output.stdout).unwrap().trim().to_string()
+    };
+
+    let mut revs = vec![];
+    for response_id in ["1", "2"] {
+        let mut server_mock = mock_movey_upload_with_response_body_and_status_code(
+            &server,
+            "valid_package1",
+            200,
+            Some(response_id),
+        );
+        move_cmd(&["movey-upload"]);
+        server_mock.assert();
+        server_mock.delete();
+        revs.push(head());
+        run_git(
+            Path::new(&package_path),
+            &["commit", "-q", "--allow-empty", "-m", "release"],
+        );
+    }
+    assert_ne!(revs[0], revs[1]);
+
+    let log: serde_json::Value =
+        serde_json::from_str(&move_cmd(&["package", "publish-log", "--format", "json"])).unwrap();
+    let entries = log.as_array().unwrap();
+    assert_eq!(entries.len(), 2, "{}", log);
+    for (entry, (rev, response_id)) in entries.iter().zip(revs.iter().zip(["1", "2"])) {
+        assert_eq!(entry["package"], "valid_package1");
+        assert_eq!(entry["version"], "0.0.0");
+        assert_eq!(entry["rev"], rev.as_str());
+        assert_eq!(entry["registry"], server.base_url().as_str());
+        assert_eq!(entry["response_id"], response_id);
+    }
+    let timestamps: Vec<_> = entries
+        .iter()
+        .map(|entry| entry["timestamp"].as_str().unwrap())
+        .collect();
+    assert!(timestamps[0] <= timestamps[1], "{:?}", timestamps);
+
+    let output = move_cmd(&["package", "publish-log", "--limit", "1"]);
+    assert_eq!(output.lines().count(), 1, "{}", output);
+    assert!(
+        output.contains(&format!("valid_package1 0.0.0  rev {}", revs[1])),
+        "{}",
+        output
+    );
+    let output = move_cmd(&["package", "publish-log", "--package", "other"]);
+    assert!(output.starts_with("No uploads recorded in"), "{}", output);
+}
+
+#[test]
+fn upload_package_archive_to_throttled_server() {
+    let workspace = tempfile::tempdir().unwrap();
+    let package_path = workspace.path().join("Package");
+    write_package(&package_path, "Package", "", &[("M", "module 0x2::M {}")]);
+    // a large file that does not compress well, so that the archive is streamed in many reads
+    let mut state = 0x2545_f491_u32;
+    let data: Vec<u8> = (0..4 * 1024 * 1024)
+        .map(|_| {
+            state ^= state << 13;
+            state ^= state >> 17;
+            state ^= state << 5;
+            state as u8
+        })
+        .collect();
+    fs::write(package_path.join("data.bin"), data).unwrap();
+    commit_git_repo(&package_path);
+    run_git(
+        &package_path,
+        &[
+            "remote",
+            "add",
+            "origin",
+            "https://github.com/move-language/move.git",
+        ],
+    );
+
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let base_url = format!("http://{}", listener.local_addr().unwrap());
+    let server = thread::spawn(move || {
+        // drop the first request halfway through, so that it has to be sent again
+        let (mut stream, _) = listener.accept().unwrap();
+        read_throttled_request(&mut stream, Some(256 * 1024));
+        drop(stream);
+        let (mut stream, _) = listener.accept().unwrap();
+        let request = read_throttled_request(&mut stream, None);
+        stream
+            .write_all(b"HTTP/1.1 200 OK\r\nContent-Length: 7\r\nConnection: close\r\n\r\nPackage")
+            .unwrap();
+        request
+    });
+
+    let move_home = workspace.path().join("move_home");
+    fs::create_dir_all(&move_home).unwrap();
+    init_stub_registry_file(&move_home.to_string_lossy(), &base_url);
+    let output = Command::new(env!("CARGO_BIN_EXE_move"))
+        .env("MOVE_HOME", &move_home)
+        .current_dir(&package_path)
+        .args(["movey-upload", "--archive", "--quiet"])
+        .output()
+        .unwrap();
+    assert!(
+        output.status.success(),
+        "{}",
+        String::from_utf8_lossy(&output.stderr)
+    );
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    assert!(stdout.contains("/packages/Package"), "{}", stdout);
+
+    let (head, body) = server.join().unwrap();
+    assert!(
+        head.starts_with("POST /api/v1/packages/upload HTTP/1.1\r\n"),
+        "{}",
+        head
+    );
+    let boundary = head
+        .lines()
+        .find_map(|line| line.split("boundary=").nth(1))
+        .expect(&head)
+        .trim()
+        .to_string();
+    let mut fields = std::collections::BTreeMap::new();
+    let mut field_names = vec![];
+    let delimiter = format!("--{}", boundary).into_bytes();
+    for part in split_bytes(&body, &delimiter) {
+        let header_end = match find_bytes(part, b"\r\n\r\n") {
+            Some(header_end) => header_end,
+            None => continue,
+        };
+        let headers = String::from_utf8_lossy(&part[..header_end]).to_string();
+        let name = headers
+            .split("name=\"")
+            .nth(1)
+            .and_then(|rest| rest.split('"').next())
+            .unwrap()
+            .to_string();
+        let content = &part[header_end + 4..part.len() - 2];
+        field_names.push(name.clone());
+        fields.insert(name, content.to_vec());
+    }
+    // the fields are always sent in the same order
+    assert_eq!(
+        field_names,
+        [
+            "github_repo_url",
+            "total_files",
+            "token",
+            "subdir",
+            "name",
+            "archive_digest",
+            "archive"
+        ]
+    );
+    let archive = &fields["archive"];
+    // a gzip stream holding the large file
+    assert_eq!(&archive[..2], &[0x1f, 0x8b]);
+    assert!(archive.len() > 1024 * 1024);
+    assert_eq!(
+        String::from_utf8_lossy(&fields["archive_digest"]),
+        hex::encode(Sha256::digest(archive))
+    );
+    assert_eq!(fields["token"], b"test-token");
+    assert_eq!(fields["name"], b"package");
+}
+
+#[cfg(target_os = "linux")]
+#[test]
+fn upload_package_to_registry_with_mutual_tls() {
+    use openssl::ssl::{SslAcceptor, SslMethod, SslVerifyMode, SslVersion};
+
+    let workspace = tempfile::tempdir().unwrap();
+    let package_path = workspace.path().join("Package");
+    write_package(&package_path, "Package", "", &[("M", "module 0x2::M {}")]);
+    commit_git_repo(&package_path);
+    run_git(
+        &package_path,
+        &[
+            "remote",
+            "add",
+            "origin",
+            "https://github.com/move-language/move.git",
+        ],
+    );
+
+    let ca = test_certificate("Test CA", None);
+    let server = test_certificate("localhost", Some(&ca));
+    let client = test_certificate("client", Some(&ca));
+    let move_home = workspace.path().join("move_home");
+    let certs = move_home.join("certs");
+    fs::create_dir_all(&certs).unwrap();
+    fs::write(certs.join("ca.pem"), ca.0.to_pem().unwrap()).unwrap();
+    fs::write(certs.join("client.pem"), client.0.to_pem().unwrap()).unwrap();
+    fs::write(
+        certs.join("client.key"),
+        client.1.private_key_to_pem_pkcs8().unwrap(),
+    )
+    .unwrap();
+
+    let mut acceptor = SslAcceptor::mozilla_intermediate_v5(SslMethod::tls()).unwrap();
+    acceptor.set_certificate(&server.0).unwrap();
+    acceptor.set_private_key(&server.1).unwrap();
+    acceptor.cert_store_mut().add_cert(ca.0.clone()).unwrap();
+    acceptor.add_client_ca(&ca.0).unwrap();
+    acceptor.set_verify(SslVerifyMode::PEER | SslVerifyMode::FAIL_IF_NO_PEER_CERT);
+    // so that a missing client certificate fails the handshake, rather than the first read after
+    // a TLS 1.3 handshake
+    acceptor
+        .set_max_proto_version(Some(SslVersion::TLS1_2))
+        .unwrap();
+    let acceptor = acceptor.build();
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let base_url = format!("https://{}", listener.local_addr().unwrap());
+    let server = thread::spawn(move || {
+        let mut uploads = 0;
+        for stream in listener.incoming() {
+            let mut stream = match acceptor.accept(stream.unwrap()) {
+                Ok(stream) => stream,
+                Err(_) => continue,
+            };
+            read_throttled_request(&mut stream, None);
+            stream
+                .write_all(
+                    b"HTTP/1.1 200 OK\r\nContent-Length: 7\r\nConnection: close\r\n\r\nPackage",
+                )
+                .unwrap();
+            uploads += 1;
+            if uploads == 2 {
+                return;
+            }
+        }
+    });
+
+    init_stub_registry_file(&move_home.to_string_lossy(), &base_url);
+    let upload = |args: &[&Path]| {
+        let mut command = Command::new(env!("CARGO_BIN_EXE_move"));
+        command
+            .env("MOVE_HOME", &move_home)
+            .current_dir(&package_path)
+            .arg("movey-upload");
+        for (flag, path) in ["--cacert", "--client-cert", "--client-key"]
+            .iter()
+            .zip(args)
+        {
+            command.arg(flag).arg(path);
+        }
+        command.output().unwrap()
+    };
+    let stderr =
+        |output: &std::process::Output| String::from_utf8_lossy(&output.stderr).to_string();
+
+    // the certificate of the registry cannot be verified with the system certificate authorities
+    let output = upload(&[]);
+    assert!(!output.status.success());
+    assert!(
+        stderr(&output).contains("Unable to verify the TLS certificate of the registry"),
+        "{}",
+        stderr(&output)
+    );
+    assert!(stderr(&output).contains("--cacert"), "{}", stderr(&output));
+
+    // the registry requires a client certificate
+    let output = upload(&[&certs.join("ca.pem")]);
+    assert!(!output.status.success());
+    assert!(
+        stderr(&output).contains("requires a client certificate"),
+        "{}",
+        stderr(&output)
+    );
+
+    let output = upload(&[
+        &certs.join("ca.pem"),
+        &certs.join("client.pem"),
+        &certs.join("client.key"),
+    ]);
+    assert!(output.status.success(), "{}", stderr(&output));
+    assert!(String::from_utf8_lossy(&output.stdout).contains("/packages/Package"));
+
+    // the same, set in the credential file with paths relative to MOVE_HOME
+    let credential_path = credential_path(&move_home.to_string_lossy());
+    let mut credential = fs::read_to_string(&credential_path).unwrap();
+    credential.push_str(
+        "cacert = \"certs/ca.pem\"\nclient_cert = \"certs/client.pem\"\nclient_key = \"certs/client.key\"\n",
+    );
+    fs::write(&credential_path, credential).unwrap();
+    let output = upload(&[]);
+    assert!(output.status.success(), "{}", stderr(&output));
+    server.join().unwrap();
+}
+
+#[cfg(target_os = "linux")]
+type TestCertificate = (
+    openssl::x509::X509,
+    openssl::pkey::PKey<openssl::pkey::Private>,
+);
+
+/// A key pair and a certificate for `common_name`, signed by `issuer`, or a self-signed
+/// certificate authority if `issuer` is `None`
+#[cfg(target_os = "linux")]
+fn test_certificate(common_name: &str, issuer: Option<&TestCertificate>) -> TestCertificate {
+    use openssl::{
+        asn1::Asn1Time,
+        bn::{BigNum, MsbOption},
+        ec::{EcGroup, EcKey},
+        hash::MessageDigest,
+        nid::Nid,
+        pkey::PKey,
+        x509::{
+            extension::{BasicConstraints, SubjectAlternativeName},
+            X509NameBuilder, X509,
+        },
+    };
+
+    let group = EcGroup::from_curve_name(Nid::X9_62_PRIME256V1).unwrap();
+    let key = PKey::from_ec_key(EcKey::generate(&group).unwrap()).unwrap();
+    let mut name = X509NameBuilder::new().unwrap();
+    name.append_entry_by_text("CN", common_name).unwrap();
+    let name = name.build();
+    let mut serial = BigNum::new().unwrap();
+    serial.rand(64, MsbOption::MAYBE_ZERO, false).unwrap();
+
+    let mut builder = X509::builder().unwrap();
+    builder.set_version(2).unwrap();
+    builder
+        .set_serial_number(&serial.to_asn1_integer().unwrap())
+        .unwrap();
+    builder.set_subject_name(&name).unwrap();
+    builder.set_pubkey(&key).unwrap();
+    builder
+        .set_not_before(&Asn1Time::days_from_now(0).unwrap())
+        .unwrap();
+    builder
+        .set_not_after(&Asn1Time::days_from_now(1).unwrap())
+        .unwrap();
+    match issuer {
+        None => {
+            builder.set_issuer_name(&name).unwrap();
+            builder
+                .append_extension(BasicConstraints::new().critical().ca().build().unwrap())
+                .unwrap();
+            builder.sign(&key, MessageDigest::sha256()).unwrap();
+        }
+        Some((issuer_certificate, issuer_key)) => {
+            builder
+                .set_issuer_name(issuer_certificate.subject_name())
+                .unwrap();
+            let alt_names = SubjectAlternativeName::new()
+                .dns("localhost")
+                .ip("127.0.0.1")
+                .build(&builder.x509v3_context(Some(issuer_certificate), None))
+                .unwrap();
+            builder.append_extension(alt_names).unwrap();
+            builder.sign(issuer_key, MessageDigest::sha256()).unwrap();
+        }
+    }
+    (builder.build(), key)
+}
+
+/// Read an HTTP request slowly, returning its head and body. Stop after `limit` bytes of the body
+/// if it is set.
+fn read_throttled_request(stream: &mut impl Read, limit: Option<usize>) -> (String, Vec<u8>) {
+    let mut request = vec![];
+    let mut buf = [0; 8 * 1024];
+    let head_end = loop {
+        let n = stream.read(&mut buf).unwrap();
+        assert!(
+            n > 0,
+            "connection closed before the end of the request head"
+        );
+        request.extend_from_slice(&buf[..n]);
+        if let Some(head_end) = find_bytes(&request, b"\r\n\r\n") {
+            break head_end + 4;
+        }
+    };
+    let head = String::from_utf8_lossy(&request[..head_end]).to_string();
+    let content_length: usize = head
+        .lines()
+        .find_map(|line| {
+            let (name, value) = line.split_once(':')?;
+            name.eq_ignore_ascii_case("content-length")
+                .then(|| value.trim().parse().unwrap())
+        })
+        .expect(&head);
+    let mut body = request[head_end..].to_vec();
+    let wanted = limit.unwrap_or(content_length).min(content_length);
+    while body.len() < wanted {
+        thread::sleep(Duration::from_millis(1));
+        let n = stream.read(&mut buf).unwrap();
+        assert!(
+            n > 0,
+            "connection closed before the end of the request body"
+        );
+        body.extend_from_slice(&buf[..n]);
+    }
+    (head, body)
+}
+
+fn find_bytes(haystack: &[u8], needle: &[u8]) -> Option<usize> {
+    haystack
+        .windows(needle.len())
+        .position(|window| window == needle)
+}
+
+fn split_bytes<'a>(mut bytes: &'a [u8], delimiter: &[u8]) -> Vec<&'a [u8]> {
+    let mut parts = vec![];
+    while let Some(pos) = find_bytes(bytes, delimiter) {
+        parts.push(&bytes[..pos]);
+        bytes = &bytes[pos + delimiter.len()..];
+    }
+    parts.push(bytes);
+    parts
+}
+
+// is_valid == true: all git commands are run
+// is_valid == false: missing git remote add command
+fn init_git(package_path: &str, is_valid: bool) {
+    Command::new("git")
+        .current_dir(package_path)
+        .args(&["init"])
+        .output()
+        .unwrap();
+    Command::new("git")
+        .current_dir(package_path)
+        .args(&["add", "."])
+        .output()
+        .unwrap();
+    if is_valid {
+        Command::new("git")
+            .current_dir(package_path)
+            .args(&[
+                "remote",
+                "add",
+                "test-origin",
+                "git@github.com:move-language/move.git",
+            ])
+            .output()
+            .unwrap();
+        Command::new("git")
+            .current_dir(package_path)
+            .args(&["config", "user.email", "\"you@example.com\""])
+            .output()
+            .unwrap();
+        Command::new("git")
+            .current_dir(package_path)
+            .args(&["config", "user.name", "\"Your Name\""])
+            .output()
+            .unwrap();
+        Command::new("git")
+            .current_dir(package_path)
+            .args(&["commit", "--allow-empty", "-m", "initial commit"])
+            .output()
+            .unwrap();
+    }
+}
+#[test]
+fn save_credential_works() {
+    let cli_exe = env!("CARGO_BIN_EXE_move");
+    let (_dir, move_home, credential_path) = setup_move_home();
+    assert!(fs::read_to_string(&credential_path).is_err());
+
+    match Command::new(cli_exe)
+        .env("MOVE_HOME", &move_home)
+        .current_dir(".")
+        .args(["movey-login", "--no-verify"])
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .spawn()
+    {
+        Ok(child) => {
+            let token = "test_token";
+            child
+                .stdin
+                .as_ref()
+                .unwrap()
+                .write_all(token.as_bytes())
+                .unwrap();
+            match child.wait_with_output() {
+                Ok(output) => {
+                    assert!(String::from_utf8_lossy(&output.stdout).contains(&format!(
+                        "Please paste the API Token found on {}/settings/tokens below",
+                        MOVEY_URL
+                    )));
+                    Ok(())
+                }
+                Err(error) => Err(error),
+            }
+        }
+        Err(error) => Err(error),
+    }
+    .unwrap();
+
+    let contents = fs::read_to_string(&credential_path).expect("Unable to read file");
+    let mut toml: Value = contents.parse().unwrap();
+    let registry = toml.as_table_mut().unwrap().get_mut("registry").unwrap();
+    let token = registry.as_table_mut().unwrap().get_mut("token").unwrap();
+    assert!(token.to_string().contains("test_token"));
+}
+
+#[test]
+fn movey_logout_removes_the_saved_token() {
+    let (_dir, move_home, credential_path) = setup_move_home();
+    let move_cli = |args: &[&str], stdin: &[u8]| {
+        let mut child = Command::new(get_cli_binary_path())
+            .env("MOVE_HOME", &move_home)
+            .args(args)
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::piped())
+            .spawn()
+            .unwrap();
+        child.stdin.take().unwrap().write_all(stdin).unwrap();
+        let output = child.wait_with_output().unwrap();
+        assert!(
+            output.status.success(),
+            "{}",
+            String::from_utf8_lossy(&output.stderr)
+        );
+        String::from_utf8(output.stdout).unwrap()
+    };
+
+    let stdout = move_cli(&["movey-logout"], b"");
+    assert!(
+        stdout.contains("You are not logged in to Movey"),
+        "{}",
+        stdout
+    );
+
+    move_cli(&["movey-login", "--no-verify"], b"test_token");
+    assert!(fs::read_to_string(&credential_path)
+        .unwrap()
+        .contains("test_token"));
+    let stdout = move_cli(&["movey-logout"], b"");
+    assert!(stdout.contains("Token for Movey removed."), "{}", stdout);
+    assert!(!Path::new(&credential_path).exists());
+
+    fs::write(
+        &credential_path,
+        "[registry]\ntoken = \"test_token\"\nversion = \"0.0.0\"\n",
+    )
+    .unwrap();
+    move_cli(&["movey-logout"], b"");
+    assert_eq!(
+        fs::read_to_string(&credential_path).unwrap(),
+        "[registry]\nversion = \"0.0.0\"\n"
+    );
+    let stdout = move_cli(&["movey-logout"], b"");
+    assert!(
+        stdout.contains("You are not logged in to Movey"),
+        "{}",
+        stdout
+    );
+}
+
+#[test]
+fn movey_login_verifies_the_token_with_movey() {
+    let (_dir, move_home, credential_path) = setup_move_home();
+    let login = |args: &[&str], url: &str| {
+        fs::write(&credential_path, format!("[registry]\nurl = \"{}\"\n", url)).unwrap();
+        let mut child = Command::new(get_cli_binary_path())
+            .env("MOVE_HOME", &move_home)
+            .arg("movey-login")
+            .args(args)
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::piped())
+            .spawn()
+            .unwrap();
+        child
+            .stdin
+            .take()
+            .unwrap()
+            .write_all(b"test_token")
+            .unwrap();
+        let output = child.wait_with_output().unwrap();
+        (
+            output.status.success(),
+            String::from_utf8(output.stderr).unwrap(),
+            fs::read_to_string(&credential_path)
+                .unwrap()
+                .contains("test_token"),
+        )
+    };
+
+    let server = MockServer::start();
+    let valid = server.mock(|when, then| {
+        when.method(POST)
+            .path("/api/v1/tokens/validate")
+            .json_body(json!({ "token": "test_token" }));
+        then.status(200);
+    });
+    let (success, stderr, saved) = login(&[], &server.base_url());
+    valid.assert();
+    assert!(success, "{}", stderr);
+    assert!(saved);
+
+    let server = MockServer::start();
+    let rejected = server.mock(|when, then| {
+        when.method(POST).path("/api/v1/tokens/validate");
+        then.status(401);
+    });
+    let (success, stderr, saved) = login(&[], &server.base_url());
+    rejected.assert();
+    assert!(!success);
+    assert!(
+        stderr.contains("Movey rejected the API token (401 Unauthorized)"),
+        "{}",
+        stderr
+    );
+    assert!(!saved);
+
+    // nothing listens on the port once the listener is dropped
+    let offline_url = format!(
+        "http://{}",
+        TcpListener::bind("127.0.0.1:0")
+            .unwrap()
+            .local_addr()
+            .unwrap()
+    );
+    let (success, stderr, saved) = login(&[], &offline_url);
+    assert!(!success);
+    assert!(
+        stderr.contains("unable to verify the API token")
+            && stderr.contains("Pass --no-verify to save it without verifying it"),
+        "{}",
+        stderr
+    );
+    assert!(!saved);
+    let (success, stderr, saved) = login(&["--no-verify"], &offline_url);
+    assert!(success, "{}", stderr);
+    assert!(saved);
+}
+
+#[test]
+fn movey_login_saves_the_token_given_with_token_flag() {
+    let (_dir, move_home, credential_path) = setup_move_home();
+    let login = |args: &[&str]| {
+        Command::new(get_cli_binary_path())
+            .env("MOVE_HOME", &move_home)
+            .args(["movey-login", "--no-verify"])
+            .args(args)
+            .stdin(Stdio::null())
+            .output()
+            .unwrap()
+    };
+
+    let output = login(&["--token", "test_token"]);
+    assert!(
+        output.status.success(),
+        "{}",
+        String::from_utf8_lossy(&output.stderr)
+    );
+    assert_eq!(
+        String::from_utf8(output.stdout).unwrap(),
+        "Token for Movey saved.\n"
+    );
+    assert_eq!(
+        fs::read_to_string(&credential_path).unwrap(),
+        "[registry]\ntoken = \"test_token\"\n"
+    );
+
+    let output = login(&["--token", "other_token", "--quiet"]);
+    assert!(output.status.success());
+    assert!(output.stdout.is_empty());
+    assert_eq!(
+        fs::read_to_string(&credential_path).unwrap(),
+        "[registry]\ntoken = \"other_token\"\n"
+    );
+
+    let output = login(&["--token", ""]);
+    assert!(!output.status.success());
+    let stderr = String::from_utf8(output.stderr).unwrap();
+    assert!(
+        stderr.contains("The API token given with --token is empty"),
+        "{}",
+        stderr
+    );
+    assert!(fs::read_to_string(&credential_path)
+        .unwrap()
+        .contains("other_token"));
+}
+
+#[cfg(unix)]
+#[test]
+fn save_credential_fails_if_undeletable_credential_file_exists() {
+    let cli_exe = env!("CARGO_BIN_EXE_move");
+    let (_dir, move_home, credential_path) = setup_move_home();
+    let file = File::create(&credential_path).unwrap();
+    let mut perms = file.metadata().unwrap().permissions();
+    perms.set_mode(0o000);
+    file.set_permissions(perms).unwrap();
 
     match std::process::Command::new(cli_exe)
         .env("MOVE_HOME", &move_home)
         .current_dir(".")
-        .args(["movey-login"])
+        .args(["movey-login", "--no-verify"])
         .stdin(Stdio::piped())
         .stdout(Stdio::piped())
         .stderr(Stdio::piped())
@@ -307,8 +5250,12 @@ fn save_credential_fails_if_undeletable_credential_file_exists() {
                         "Please paste the API Token found on {}/settings/tokens below",
                         MOVEY_URL
                     )));
-                    assert!(String::from_utf8_lossy(&output.stderr)
-                        .contains("Error: Error reading input: Permission denied (os error 13)"));
+                    let stderr = String::from_utf8_lossy(&output.stderr);
+                    assert!(stderr.contains(&format!(
+                        "Error: Unable to read the credential file '{}'",
+                        credential_path
+                    )));
+                    assert!(stderr.contains("Permission denied (os error 13)"));
                     Ok(())
                 }
                 Err(error) => Err(error),
@@ -321,30 +5268,694 @@ fn save_credential_fails_if_undeletable_credential_file_exists() {
     let mut perms = file.metadata().unwrap().permissions();
     perms.set_mode(0o600);
     file.set_permissions(perms).unwrap();
-    let _ = fs::remove_file(&credential_path);
+}
+
+#[cfg(unix)]
+#[test]
+fn save_credential_names_read_only_move_home() {
+    let cli_exe = env!("CARGO_BIN_EXE_move");
+    let (_dir, move_home, credential_path) = setup_move_home();
+    let mut perms = fs::metadata(&move_home).unwrap().permissions();
+    perms.set_mode(0o555);
+    fs::set_permissions(&move_home, perms.clone()).unwrap();
+
+    let mut child = Command::new(cli_exe)
+        .env("MOVE_HOME", &move_home)
+        .args(["movey-login", "--no-verify"])
+        .stdin(Stdio::piped())
+        .stdout(Stdio::null())
+        .stderr(Stdio::piped())
+        .spawn()
+        .unwrap();
+    child
+        .stdin
+        .take()
+        .unwrap()
+        .write_all(b"test-token")
+        .unwrap();
+    let output = child.wait_with_output().unwrap();
+    perms.set_mode(0o755);
+    fs::set_permissions(&move_home, perms).unwrap();
+
+    assert!(!output.status.success());
+    let error = String::from_utf8_lossy(&output.stderr);
+    assert!(
+        error.contains(&format!(
+            "Unable to create the credential file '{}'",
+            credential_path
+        )) && error.contains("If MOVE_HOME is read-only, set MOVE_HOME to a writable directory"),
+        "{}",
+        error
+    );
+}
+
+#[cfg(unix)]
+#[test]
+fn movey_commands_do_not_touch_home_directory() {
+    let cli_exe = env!("CARGO_BIN_EXE_move");
+    let (_dir, package_path, move_home) = setup_upload_package("valid_package1");
+    init_git(&package_path, true);
+    let home = tempfile::tempdir().unwrap();
+    let mut perms = fs::metadata(home.path()).unwrap().permissions();
+    perms.set_mode(0o500);
+    fs::set_permissions(home.path(), perms).unwrap();
+
+    let mut child = Command::new(cli_exe)
+        .env("HOME", home.path())
+        .env("MOVE_HOME", &move_home)
+        .current_dir(&package_path)
+        .args(["movey-login", "--no-verify"])
+        .stdin(Stdio::piped())
+        .stdout(Stdio::null())
+        .spawn()
+        .unwrap();
+    child
+        .stdin
+        .take()
+        .unwrap()
+        .write_all(b"test-token")
+        .unwrap();
+    assert!(child.wait().unwrap().success());
+
+    let server = MockServer::start();
+    let server_mock =
+        mock_movey_upload_with_response_body_and_status_code(&server, "valid_package1", 200, None);
+    init_stub_registry_file(&move_home, &server.base_url());
+    let output = Command::new(cli_exe)
+        .env("HOME", home.path())
+        .env("MOVE_HOME", &move_home)
+        .current_dir(&package_path)
+        .args(["movey-upload"])
+        .output()
+        .unwrap();
+    server_mock.assert();
+    assert!(
+        output.status.success(),
+        "{}",
+        String::from_utf8_lossy(&output.stderr)
+    );
+
+    let mut perms = fs::metadata(home.path()).unwrap().permissions();
+    perms.set_mode(0o700);
+    fs::set_permissions(home.path(), perms).unwrap();
+    assert_eq!(fs::read_dir(home.path()).unwrap().count(), 0);
+}
+
+#[cfg(unix)]
+#[test]
+fn unknown_commands_run_plugins_on_the_path() {
+    let cli_exe = env!("CARGO_BIN_EXE_move");
+    let bin_dir = tempfile::tempdir().unwrap();
+    install_plugin(
+        bin_dir.path(),
+        "echoargs",
+        "echo \"args: $*\"
+echo \"package: $MOVE_PACKAGE_PATH\"
+echo \"home: $MOVE_HOME\"
+echo \"version: $MOVE_CLI_VERSION\"
+exit 3",
+    );
+    let move_home = tempfile::tempdir().unwrap();
+    let package_path = fs::canonicalize(COVERAGE_PACKAGE_PATH).unwrap();
+
+    let output = Command::new(cli_exe)
+        .env("PATH", path_with(bin_dir.path()))
+        .env("MOVE_HOME", move_home.path())
+        .arg("-p")
+        .arg(&package_path)
+        .args(["echoargs", "--flag", "value", "-x"])
+        .output()
+        .unwrap();
+    assert_eq!(
+        output.status.code(),
+        Some(3),
+        "{}",
+        String::from_utf8_lossy(&output.stderr)
+    );
+    assert_eq!(
+        String::from_utf8(output.stdout).unwrap(),
+        format!(
+            "args: --flag value -x\npackage: {}\nhome: {}\nversion: {}\n",
+            package_path.display(),
+            move_home.path().display(),
+            env!("CARGO_PKG_VERSION")
+        )
+    );
+
+    let output = Command::new(cli_exe)
+        .env("PATH", path_with(bin_dir.path()))
+        .arg("nosuchplugin")
+        .output()
+        .unwrap();
+    assert!(!output.status.success());
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(
+        stderr.contains("No such command: `nosuchplugin`"),
+        "{}",
+        stderr
+    );
+}
+
+#[cfg(unix)]
+#[test]
+fn list_shows_builtin_and_plugin_commands() {
+    let cli_exe = env!("CARGO_BIN_EXE_move");
+    let bin_dir = tempfile::tempdir().unwrap();
+    install_plugin(bin_dir.path(), "echoargs", "echo \"$*\"");
+    // built-in commands take precedence over plugins of the same name
+    install_plugin(bin_dir.path(), "build", "exit 1");
+
+    let output = Command::new(cli_exe)
+        .env("PATH", path_with(bin_dir.path()))
+        .arg("--list")
+        .output()
+        .unwrap();
+    assert!(
+        output.status.success(),
+        "{}",
+        String::from_utf8_lossy(&output.stderr)
+    );
+    let stdout = String::from_utf8(output.stdout).unwrap();
+    assert!(stdout.starts_with("Installed commands:\n"), "{}", stdout);
+    assert!(stdout.contains("\n    build "), "{}", stdout);
+    assert!(stdout.contains("\n    sandbox "), "{}", stdout);
+    let echoargs = bin_dir.path().join("move-echoargs");
+    assert!(
+        stdout.contains(&format!(
+            "\n    echoargs             {}\n",
+            echoargs.display()
+        )),
+        "{}",
+        stdout
+    );
+    assert!(!stdout.contains("move-build"), "{}", stdout);
+
+    // `--list` is a global option, so it can be given along with the others
+    let output = Command::new(cli_exe)
+        .env("PATH", path_with(bin_dir.path()))
+        .args(["-v", "--list", "--path", "."])
+        .output()
+        .unwrap();
+    assert!(
+        output.status.success(),
+        "{}",
+        String::from_utf8_lossy(&output.stderr)
+    );
+    assert_eq!(String::from_utf8(output.stdout).unwrap(), stdout);
+}
+
+#[cfg(unix)]
+#[test]
+fn plugins_cannot_run_themselves_recursively() {
+    let cli_exe = env!("CARGO_BIN_EXE_move");
+    let bin_dir = tempfile::tempdir().unwrap();
+    install_plugin(bin_dir.path(), "loop", "exec \"$MOVE_BIN\" loop");
+
+    let output = Command::new(cli_exe)
+        .env("PATH", path_with(bin_dir.path()))
+        .env("MOVE_BIN", cli_exe)
+        .arg("loop")
+        .output()
+        .unwrap();
+    assert!(!output.status.success());
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(
+        stderr.contains("Plugin `move-loop` runs `move loop` recursively"),
+        "{}",
+        stderr
+    );
+}
+
+#[cfg(unix)]
+#[test]
+fn experiments_are_enabled_by_option_or_environment() {
+    let cli_exe = env!("CARGO_BIN_EXE_move");
+    let run = |args: &[&str], env: Option<&str>| {
+        let mut command = Command::new(cli_exe);
+        command.env_remove("MOVE_EXPERIMENTS").args(args);
+        if let Some(env) = env {
+            command.env("MOVE_EXPERIMENTS", env);
+        }
+        let output = command.output().unwrap();
+        (
+            output.status.success(),
+            String::from_utf8_lossy(&output.stdout).to_string(),
+            String::from_utf8_lossy(&output.stderr).to_string(),
+        )
+    };
+    let listed = |stdout: &str, enabled: &str| {
+        stdout.contains(&format!(
+            "\nread-write-set       unstable     {:<8} ",
+            enabled
+        ))
+    };
+
+    let (success, stdout, stderr) = run(&["experiments", "list"], None);
+    assert!(success, "{}", stderr);
+    assert!(stdout.starts_with("NAME "), "{}", stdout);
+    assert!(listed(&stdout, "no"), "{}", stdout);
+    let (success, stdout, stderr) = run(
+        &["experiments", "list", "--experiments", "read-write-set"],
+        None,
+    );
+    assert!(success, "{}", stderr);
+    assert!(listed(&stdout, "yes"), "{}", stdout);
+    let (success, stdout, stderr) = run(&["experiments", "list"], Some("read-write-set"));
+    assert!(success, "{}", stderr);
+    assert!(listed(&stdout, "yes"), "{}", stdout);
+
+    // unknown names are errors, rather than silently ignored
+    let (success, _, stderr) = run(
+        &[
+            "--experiments",
+            "read-write-set,time-travel",
+            "experiments",
+            "list",
+        ],
+        None,
+    );
+    assert!(!success);
+    assert!(
+        stderr.contains(
+            "Unknown experiment 'time-travel'. The available experiments are: read-write-set"
+        ),
+        "{}",
+        stderr
+    );
+    let (success, _, stderr) = run(&["experiments", "list"], Some("time-travel"));
+    assert!(!success);
+    assert!(stderr.contains("Invalid MOVE_EXPERIMENTS"), "{}", stderr);
+
+    // plugins see the experiments enabled by the option
+    let bin_dir = tempfile::tempdir().unwrap();
+    install_plugin(
+        bin_dir.path(),
+        "experiments-env",
+        "echo \"experiments: $MOVE_EXPERIMENTS\"",
+    );
+    let output = Command::new(cli_exe)
+        .env("PATH", path_with(bin_dir.path()))
+        .env_remove("MOVE_EXPERIMENTS")
+        .args(["--experiments", "read-write-set", "experiments-env"])
+        .output()
+        .unwrap();
+    assert_eq!(
+        String::from_utf8_lossy(&output.stdout),
+        "experiments: read-write-set\n"
+    );
+}
+
+#[test]
+fn help_renders_every_topic() {
+    let cli_exe = env!("CARGO_BIN_EXE_move");
+    let output = Command::new(cli_exe)
+        .args(["help", "topics"])
+        .output()
+        .unwrap();
+    assert!(output.status.success());
+    let stdout = String::from_utf8(output.stdout).unwrap();
+    for topic in HELP_TOPICS {
+        assert!(
+            stdout.contains(&format!("\n    {:<20} {}\n", topic.name, topic.summary)),
+            "{}",
+            stdout
+        );
+
+        let output = Command::new(cli_exe)
+            .args(["help", topic.name, "--no-pager"])
+            .output()
+            .unwrap();
+        assert!(output.status.success(), "{}", topic.name);
+        let stdout = String::from_utf8(output.stdout).unwrap();
+        assert!(!stdout.trim().is_empty(), "{}", topic.name);
+        assert_eq!(stdout, topic.text);
+    }
+
+    let output = Command::new(cli_exe)
+        .args(["help", "sandbox", "run"])
+        .output()
+        .unwrap();
+    assert!(output.status.success());
+    let stdout = String::from_utf8(output.stdout).unwrap();
+    assert!(stdout.contains("--signers"), "{}", stdout);
+
+    let output = Command::new(cli_exe)
+        .args(["help", "no-such-topic"])
+        .output()
+        .unwrap();
+    assert!(!output.status.success());
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(
+        stderr.contains("No help topic or command named 'no-such-topic'"),
+        "{}",
+        stderr
+    );
+}
+
+/// The example command lines of the `EXAMPLES` sections of `cmd` and its subcommands, along with
+/// the names of the subcommands they belong to
+fn help_examples(
+    cmd: &clap::Command,
+    path: &mut Vec<String>,
+    examples: &mut Vec<(String, String)>,
+) {
+    for sub in cmd.get_subcommands() {
+        path.push(sub.get_name().to_string());
+        for line in sub.get_after_help().unwrap_or_default().lines() {
+            // descriptions are indented by four spaces, and the commands they describe by eight
+            if line.starts_with("        ") {
+                examples.push((path.join(" "), line.trim().to_string()));
+            }
+        }
+        help_examples(sub, path, examples);
+        path.pop();
+    }
+}
+
+#[test]
+fn help_examples_are_valid_commands() {
+    let mut examples = vec![];
+    help_examples(&MoveCLI::command(), &mut vec![], &mut examples);
+    for command in [
+        "build",
+        "test",
+        "sandbox publish",
+        "sandbox run",
+        "sandbox view",
+        "movey-upload",
+        "movey-login",
+    ] {
+        let count = examples.iter().filter(|(path, _)| path == command).count();
+        assert!(count >= 3, "`move {}` has {} examples", command, count);
+    }
+
+    for (path, example) in examples {
+        // skip environment variables, and stop at redirections and pipes
+        let args: Vec<_> = example
+            .split_whitespace()
+            .skip_while(|word| *word != "move")
+            .take_while(|word| !["<", ">", "|"].contains(word))
+            .collect();
+        assert!(!args.is_empty(), "`{}` does not run `move`", example);
+        let mut matches = match MoveCLI::command().try_get_matches_from(&args) {
+            Ok(matches) => matches,
+            Err(err) => panic!("`{}` is not a valid command: {}", example, err),
+        };
+        // the example must run the command it is an example of, rather than e.g. a plugin
+        let mut names = vec![];
+        while let Some((name, sub_matches)) = matches.subcommand() {
+            names.push(name.to_string());
+            matches = sub_matches.clone();
+        }
+        assert_eq!(names.join(" "), path, "`{}`", example);
+    }
+}
+
+/// A workspace of three members, `first`, `second` and `third`, where `second` fails: it does not
+/// compile if `broken_source` is set, and its test fails otherwise
+fn write_workspace(path: &Path, broken_source: bool) {
+    let stdlib = stdlib_dependency();
+    fs::write(
+        path.join("Move.toml"),
+        "[workspace]\nmembers = [\"first\", \"second\", \"third\"]\n",
+    )
+    .unwrap();
+    let test_module = |name: &str, passes: bool| {
+        format!(
+            "module 0x2::{} {{ #[test] fun t() {{ assert!({}, 1) }} }}",
+            name, passes
+        )
+    };
+    write_package(
+        &path.join("first"),
+        "First",
+        &stdlib,
+        &[("First", &test_module("First", true))],
+    );
+    let second = if broken_source {
+        "module 0x2::Second { fun f(): u64 { true } }".to_string()
+    } else {
+        test_module("Second", false)
+    };
+    write_package(
+        &path.join("second"),
+        "Second",
+        &stdlib,
+        &[("Second", &second)],
+    );
+    write_package(
+        &path.join("third"),
+        "Third",
+        &stdlib,
+        &[("Third", &test_module("Third", true))],
+    );
+}
+
+fn run_in_workspace(path: &Path, args: &[&str]) -> (bool, String) {
+    let output = Command::new(env!("CARGO_BIN_EXE_move"))
+        .arg("-p")
+        .arg(path)
+        .args(args)
+        .output()
+        .unwrap();
+    let mut out = String::from_utf8(output.stdout).unwrap();
+    out.push_str(&String::from_utf8(output.stderr).unwrap());
+    (output.status.success(), out)
+}
+
+#[test]
+fn workspace_build_stops_at_the_first_failing_member() {
+    let workspace = tempfile::tempdir().unwrap();
+    write_workspace(workspace.path(), true);
+
+    let (success, output) = run_in_workspace(workspace.path(), &["build", "--workspace"]);
+    assert!(!success);
+    assert!(output.contains("BUILDING First"), "{}", output);
+    assert!(output.contains("BUILDING Second"), "{}", output);
+    assert!(!output.contains("BUILDING Third"), "{}", output);
+    assert!(
+        output.contains(
+            "Workspace summary:\n    first   ok\n    second  failed to compile\n    third   skipped\n"
+        ),
+        "{}",
+        output
+    );
+    assert!(
+        output.contains("1 of 3 workspace members failed, and 1 were skipped"),
+        "{}",
+        output
+    );
+}
+
+#[test]
+fn workspace_build_keeps_going_past_failing_members() {
+    let workspace = tempfile::tempdir().unwrap();
+    write_workspace(workspace.path(), true);
+
+    let (success, output) =
+        run_in_workspace(workspace.path(), &["build", "--workspace", "--keep-going"]);
+    assert!(!success);
+    assert!(output.contains("BUILDING Third"), "{}", output);
+    assert!(
+        output.contains(
+            "Workspace summary:\n    first   ok\n    second  failed to compile\n    third   ok\n"
+        ),
+        "{}",
+        output
+    );
+    assert!(
+        output.contains("1 of 3 workspace members failed\n"),
+        "{}",
+        output
+    );
+}
+
+#[test]
+fn workspace_build_skips_the_members_unaffected_by_changes() {
+    let workspace = tempfile::tempdir().unwrap();
+    fs::write(
+        workspace.path().join("Move.toml"),
+        "[workspace]\nmembers = [\"app\", \"leaf\"]\n",
+    )
+    .unwrap();
+    let leaf = "module 0x2::Leaf {\n    public fun value(): u64 { 1 }\n}";
+    write_package(
+        &workspace.path().join("leaf"),
+        "Leaf",
+        "",
+        &[("Leaf", leaf)],
+    );
+    write_package(
+        &workspace.path().join("app"),
+        "App",
+        "Leaf = { local = \"../leaf\" }\n",
+        &[(
+            "App",
+            "module 0x3::App {\n    public fun value(): u64 { 0x2::Leaf::value() }\n}",
+        )],
+    );
+    commit_git_repo(workspace.path());
+
+    // editing the leaf affects the member depending on it
+    fs::write(
+        workspace
+            .path()
+            .join("leaf")
+            .join("sources")
+            .join("Leaf.move"),
+        leaf.replace("{ 1 }", "{ 2 }"),
+    )
+    .unwrap();
+    let (success, output) = run_in_workspace(
+        workspace.path(),
+        &["build", "--workspace", "--changed-since", "main"],
+    );
+    assert!(success, "{}", output);
+    assert!(
+        output.contains(
+            "Affected workspace members:\n    \
+             app   build: depends on leaf, which is affected\n    \
+             leaf  build: leaf/sources/Leaf.move changed\n"
+        ),
+        "{}",
+        output
+    );
+    assert!(
+        output.contains("BUILDING App") && output.contains("BUILDING Leaf"),
+        "{}",
+        output
+    );
+
+    // editing the app only affects the app
+    let app_source = workspace
+        .path()
+        .join("app")
+        .join("sources")
+        .join("App.move");
+    let output = Command::new(env!("CARGO_BIN_EXE_move"))
+        .current_dir(workspace.path())
+        .args(["build", "--workspace", "--changed-files"])
+        .arg(format!("app/sources/App.move,{}", app_source.display()))
+        .output()
+        .unwrap();
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    assert!(output.status.success(), "{}", stderr);
+    assert!(
+        stderr.contains(
+            "Affected workspace members:\n    \
+             app   build: app/sources/App.move and 1 other file(s) changed\n    \
+             leaf  skip: no changes to it or to the members it depends on\n"
+        ),
+        "{}",
+        stderr
+    );
+    let output = format!("{}{}", stdout, stderr);
+    assert!(
+        output.contains("BUILDING App") && !output.contains("BUILDING Leaf"),
+        "{}",
+        output
+    );
+    assert!(
+        stderr.contains("Workspace summary:\n    app   ok\n    leaf  unaffected\n"),
+        "{}",
+        stderr
+    );
+}
+
+#[test]
+fn workspace_tests_separate_failing_tests_from_failing_compilation() {
+    let workspace = tempfile::tempdir().unwrap();
+    write_workspace(workspace.path(), false);
+
+    let (success, output) = run_in_workspace(workspace.path(), &["test", "--workspace"]);
+    assert!(!success);
+    assert!(!output.contains("0x2::Third::t"), "{}", output);
+    assert!(
+        output.contains("    first   ok\n    second  tests failed (1 of 1)\n    third   skipped\n"),
+        "{}",
+        output
+    );
+
+    let (success, output) =
+        run_in_workspace(workspace.path(), &["test", "--workspace", "--keep-going"]);
+    assert!(!success);
+    assert!(output.contains("0x2::Third::t"), "{}", output);
+    assert!(
+        output.contains("    first   ok\n    second  tests failed (1 of 1)\n    third   ok\n"),
+        "{}",
+        output
+    );
 
-    let _ = fs::remove_dir_all(move_home);
+    write_workspace(workspace.path(), true);
+    let (success, output) =
+        run_in_workspace(workspace.path(), &["test", "--workspace", "--keep-going"]);
+    assert!(!success);
+    assert!(
+        output.contains("    first   ok\n    second  failed to compile\n    third   ok\n"),
+        "{}",
+        output
+    );
+}
+
+/// Install a shell script running `script` as the plugin `name` in `bin_dir`
+#[cfg(unix)]
+fn install_plugin(bin_dir: &Path, name: &str, script: &str) {
+    let path = bin_dir.join(format!("move-{}", name));
+    fs::write(&path, format!("#!/bin/sh\n{}\n", script)).unwrap();
+    let mut perms = fs::metadata(&path).unwrap().permissions();
+    perms.set_mode(0o755);
+    fs::set_permissions(&path, perms).unwrap();
+}
+
+/// The `PATH` of the tests, with `bin_dir` first
+#[cfg(unix)]
+fn path_with(bin_dir: &Path) -> std::ffi::OsString {
+    let path = std::env::var_os("PATH").unwrap_or_default();
+    std::env::join_paths(std::iter::once(bin_dir.to_path_buf()).chain(std::env::split_paths(&path)))
+        .unwrap()
 }
 
-fn setup_move_home(test_path: &str) -> (String, String) {
-    let cwd = env::current_dir().unwrap();
-    let mut move_home: String = String::from(cwd.to_string_lossy());
-    move_home.push_str(test_path);
-    let _ = fs::remove_dir_all(&move_home);
+/// A `MOVE_HOME` inside a temporary directory, which is removed when the returned `TempDir` is
+/// dropped
+fn setup_move_home() -> (TempDir, String, String) {
+    let dir = tempfile::tempdir().unwrap();
+    let move_home = path_to_string(&dir.path().join("move_home"));
     fs::create_dir_all(&move_home).unwrap();
-    let credential_path = move_home.clone() + MOVEY_CREDENTIAL_PATH;
-    (move_home, credential_path)
+    let credential_path = credential_path(&move_home);
+    (dir, move_home, credential_path)
+}
+
+/// A copy of the upload test package `name` and a `MOVE_HOME` next to it in a temporary
+/// directory, so that the git repository and the credential the test creates stay out of the
+/// source tree
+fn setup_upload_package(name: &str) -> (TempDir, String, String) {
+    let (dir, move_home, _) = setup_move_home();
+    let package_path = dir.path().join(name);
+    copy_dir(&Path::new(UPLOAD_PACKAGE_PATH).join(name), &package_path);
+    (dir, path_to_string(&package_path), move_home)
+}
+
+fn copy_dir(from: &Path, to: &Path) {
+    fs::create_dir_all(to).unwrap();
+    for entry in fs::read_dir(from).unwrap() {
+        let entry = entry.unwrap();
+        let target = to.join(entry.file_name());
+        if entry.file_type().unwrap().is_dir() {
+            copy_dir(&entry.path(), &target);
+        } else {
+            fs::copy(entry.path(), target).unwrap();
+        }
+    }
 }
 
-fn clean_up(package_path: &str) {
-    fs::remove_dir_all(format!("{}/.git", package_path)).unwrap();
-    let credential_path = format!("{}{}", package_path, MOVEY_CREDENTIAL_PATH);
-    let _ = fs::remove_file(&credential_path);
+fn path_to_string(path: &Path) -> String {
+    files::path_to_string(path).unwrap()
 }
 
 // create a dummy move_credential.toml file for testing
-fn init_stub_registry_file(package_path: &str, base_url: &str) {
-    let credential_path = format!("{}{}", package_path, MOVEY_CREDENTIAL_PATH);
+fn init_stub_registry_file(move_home: &str, base_url: &str) {
+    let credential_path = credential_path(move_home);
     let content = format!(
         r#"
         [registry]
@@ -359,6 +5970,7 @@ fn init_stub_registry_file(package_path: &str, base_url: &str) {
 // create a mock server to check if the request is sent or not, also returns a stub response for testing
 fn mock_movey_upload_with_response_body_and_status_code<'a>(
     server: &'a MockServer,
+    package_name: &str,
     status_code: u16,
     response_body: Option<&str>,
 ) -> Mock<'a> {
@@ -370,7 +5982,8 @@ fn mock_movey_upload_with_response_body_and_status_code<'a>(
             "github_repo_url": "https://github.com/move-language/move",
             "total_files": 2,
             "token": "test-token",
-            "subdir": '\n'
+            "subdir": '\n',
+            "name": package_name,
             }));
         then.status(status_code).body(response_body.unwrap_or(""));
     })
@@ -0,0 +1,75 @@
+// Copyright (c) The Move Contributors
+// SPDX-License-Identifier: Apache-2.0
+
+//! Runs the CLI the way a binary embedding it would, registering a native function that the
+//! `NativeMath` package declares.
+
+use move_cli::MoveCliBuilder;
+use move_core_types::{account_address::AccountAddress, gas_algebra::InternalGas};
+use move_vm_runtime::native_functions::NativeContext;
+use move_vm_types::{
+    loaded_data::runtime_types::Type,
+    natives::function::{NativeResult, PartialVMResult},
+    pop_arg,
+    values::Value,
+};
+use std::{collections::VecDeque, path::PathBuf, process::Command, sync::Arc};
+
+fn native_math_package() -> PathBuf {
+    PathBuf::from(env!("CARGO_MANIFEST_DIR")).join("tests/embedding_tests/NativeMath")
+}
+
+fn native_double(
+    _context: &mut NativeContext,
+    _ty_args: Vec<Type>,
+    mut args: VecDeque<Value>,
+) -> PartialVMResult<NativeResult> {
+    let x = pop_arg!(args, u64);
+    Ok(NativeResult::ok(
+        InternalGas::new(0),
+        vec![Value::u64(x * 2)].into(),
+    ))
+}
+
+#[test]
+fn unit_tests_call_natives_registered_by_the_embedding_binary() {
+    let install_dir = tempfile::tempdir().unwrap();
+    MoveCliBuilder::new()
+        .unwrap()
+        .with_native(
+            AccountAddress::from_hex_literal("0x2").unwrap(),
+            "math",
+            "double",
+            Arc::new(native_double),
+        )
+        .run_with_args([
+            "move".as_ref(),
+            "test".as_ref(),
+            "-p".as_ref(),
+            native_math_package().as_os_str(),
+            "--install-dir".as_ref(),
+            install_dir.path().as_os_str(),
+        ])
+        .unwrap();
+}
+
+#[test]
+fn unit_tests_fail_on_unregistered_natives() {
+    let install_dir = tempfile::tempdir().unwrap();
+    let output = Command::new(env!("CARGO_BIN_EXE_move"))
+        .arg("test")
+        .arg("-p")
+        .arg(native_math_package())
+        .arg("--install-dir")
+        .arg(install_dir.path())
+        .output()
+        .unwrap();
+    assert!(!output.status.success());
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(stderr.contains("0x2::math::double"), "{}", stderr);
+    assert!(
+        stderr.contains("MoveCliBuilder::with_natives"),
+        "{}",
+        stderr
+    );
+}
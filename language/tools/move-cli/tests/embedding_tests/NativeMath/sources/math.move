@@ -0,0 +1,9 @@
+module NativeMath::math {
+    /// Implemented by the binary running the tests, see `tests/embedding_tests.rs`
+    native public fun double(x: u64): u64;
+
+    #[test]
+    fun double_works() {
+        assert!(double(21) == 42, 0);
+    }
+}
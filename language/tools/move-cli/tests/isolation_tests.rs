@@ -0,0 +1,88 @@
+// Copyright (c) The Move Contributors
+// SPDX-License-Identifier: Apache-2.0
+
+//! Runs the unit tests of the `Crashing` package with `move test --isolate-modules`, one of its
+//! tests calling a native function that aborts the process running it.
+//!
+//! This binary runs without the test harness since it doubles as the CLI: `--isolate-modules`
+//! runs the tests of each module by running the current executable again.
+
+use move_cli::MoveCliBuilder;
+use move_core_types::account_address::AccountAddress;
+use move_vm_runtime::native_functions::NativeContext;
+use move_vm_types::{
+    loaded_data::runtime_types::Type,
+    natives::function::{NativeResult, PartialVMResult},
+    values::Value,
+};
+use std::{collections::VecDeque, path::PathBuf, process::Command, sync::Arc};
+
+fn crashing_package() -> PathBuf {
+    PathBuf::from(env!("CARGO_MANIFEST_DIR")).join("tests/isolation_tests/Crashing")
+}
+
+fn native_crash(
+    _context: &mut NativeContext,
+    _ty_args: Vec<Type>,
+    _args: VecDeque<Value>,
+) -> PartialVMResult<NativeResult> {
+    std::process::abort()
+}
+
+fn crashing_tests_do_not_stop_the_other_modules() {
+    let install_dir = tempfile::tempdir().unwrap();
+    let output = Command::new(std::env::current_exe().unwrap())
+        .arg("test")
+        .arg("-p")
+        .arg(crashing_package())
+        .arg("--install-dir")
+        .arg(install_dir.path())
+        .arg("--isolate-modules")
+        .env("NO_COLOR", "1")
+        .output()
+        .unwrap();
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    assert!(!output.status.success(), "{}", stdout);
+    assert!(stdout.contains("[ CRASH   ] 0x2::process: "), "{}", stdout);
+    assert!(
+        stdout.contains("[ PASS    ] 0x2::counter::increments"),
+        "{}",
+        stdout
+    );
+    assert!(
+        stdout.contains("[ FAIL    ] 0x2::counter::fails"),
+        "{}",
+        stdout
+    );
+    assert!(
+        stdout.contains("[ PASS    ] 0x2::math::doubles"),
+        "{}",
+        stdout
+    );
+    assert!(
+        stdout.contains(
+            "Test result: FAILED. Total tests: 3; passed: 2; failed: 1; crashed modules: 1"
+        ),
+        "{}",
+        stdout
+    );
+}
+
+fn main() {
+    // Run as the CLI, by the test below or by `--isolate-modules`
+    if std::env::args().nth(1).as_deref() == Some("test") {
+        MoveCliBuilder::new()
+            .unwrap()
+            .with_native(
+                AccountAddress::from_hex_literal("0x2").unwrap(),
+                "process",
+                "crash",
+                Arc::new(native_crash),
+            )
+            .run()
+            .unwrap();
+        return;
+    }
+    crashing_tests_do_not_stop_the_other_modules();
+    println!("test crashing_tests_do_not_stop_the_other_modules ... ok");
+}
@@ -0,0 +1,15 @@
+module Crashing::counter {
+    public fun increment(x: u64): u64 {
+        x + 1
+    }
+
+    #[test]
+    fun increments() {
+        assert!(increment(1) == 2, 0);
+    }
+
+    #[test]
+    fun fails() {
+        assert!(increment(1) == 3, 1);
+    }
+}
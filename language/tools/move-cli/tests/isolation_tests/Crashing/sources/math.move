@@ -0,0 +1,10 @@
+module Crashing::math {
+    public fun double(x: u64): u64 {
+        x * 2
+    }
+
+    #[test]
+    fun doubles() {
+        assert!(double(21) == 42, 0);
+    }
+}
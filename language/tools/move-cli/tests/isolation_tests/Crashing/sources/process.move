@@ -0,0 +1,9 @@
+module Crashing::process {
+    /// Implemented by the binary running the tests, see `tests/isolation_tests.rs`
+    native public fun crash();
+
+    #[test]
+    fun crashes_the_process() {
+        crash()
+    }
+}
@@ -0,0 +1,157 @@
+// Copyright (c) The Move Contributors
+// SPDX-License-Identifier: Apache-2.0
+
+use codespan_reporting::diagnostic::Severity;
+use move_cli::base::{
+    build::{build_package, BuildOptions},
+    coverage::CoverageFormat,
+    test::{test_package, TestOptions},
+};
+use move_core_types::account_address::AccountAddress;
+use move_package::BuildConfig;
+use move_stdlib::natives::{all_natives, nursery_natives, GasParameters, NurseryGasParameters};
+use move_unit_test::UnitTestingConfig;
+use std::path::PathBuf;
+use tempfile::TempDir;
+
+fn fixture(name: &str) -> PathBuf {
+    PathBuf::from("tests/library_tests").join(name)
+}
+
+/// Build into a temporary directory so that the fixtures stay clean and tests can run in parallel
+fn build_config(install_dir: &TempDir) -> BuildConfig {
+    BuildConfig {
+        install_dir: Some(install_dir.path().to_path_buf()),
+        ..BuildConfig::default()
+    }
+}
+
+#[test]
+fn build_returns_package_and_warnings() {
+    let install_dir = tempfile::tempdir().unwrap();
+    let options = BuildOptions {
+        package_path: fixture("Example"),
+        build_config: build_config(&install_dir),
+    };
+    let output = build_package(options, &mut Vec::new()).unwrap();
+
+    let package = output.package.expect("package should compile");
+    assert_eq!(
+        package.compiled_package_info.package_name.as_str(),
+        "Example"
+    );
+    assert_eq!(package.root_modules().count(), 1);
+
+    assert_eq!(output.diagnostics.len(), 1);
+    let warning = &output.diagnostics[0];
+    assert_eq!(warning.severity, Severity::Warning);
+    assert!(warning.file.ends_with("Example.move"));
+    assert_eq!((warning.line, warning.column), (3, 13));
+}
+
+#[test]
+fn build_returns_errors_instead_of_exiting() {
+    let install_dir = tempfile::tempdir().unwrap();
+    let options = BuildOptions {
+        package_path: fixture("Broken"),
+        build_config: build_config(&install_dir),
+    };
+    let output = build_package(options, &mut Vec::new()).unwrap();
+
+    assert!(output.package.is_none());
+    assert!(output
+        .diagnostics
+        .iter()
+        .any(|diag| diag.severity == Severity::Error && diag.line == 3));
+}
+
+#[test]
+fn test_returns_outcome_of_each_test() {
+    let install_dir = tempfile::tempdir().unwrap();
+    let options = TestOptions {
+        package_path: fixture("Example"),
+        build_config: build_config(&install_dir),
+        unit_test_config: UnitTestingConfig {
+            ignore_compile_warnings: true,
+            ..UnitTestingConfig::default_with_bound(None)
+        },
+        compute_coverage: false,
+        coverage_format: CoverageFormat::Summary,
+    };
+    let mut report = Vec::new();
+    let natives = all_natives(AccountAddress::ONE, GasParameters::zeros());
+    let output = test_package(options, natives, &mut report).unwrap();
+
+    assert!(!output.all_passed());
+    let passed: Vec<_> = output.passed().map(|test| test.name.as_str()).collect();
+    assert_eq!(passed, vec!["0x2::Example::double_passes"]);
+    let failed: Vec<_> = output.failed().collect();
+    assert_eq!(failed.len(), 1);
+    assert_eq!(failed[0].name, "0x2::Example::double_fails");
+    assert!(failed[0].failure.as_ref().unwrap().contains("42"));
+
+    // the test report is still written out
+    let report = String::from_utf8(report).unwrap();
+    assert!(report.contains("Running Move unit tests"));
+}
+
+#[test]
+fn test_dumps_state_of_failing_tests() {
+    let install_dir = tempfile::tempdir().unwrap();
+    let options = TestOptions {
+        package_path: fixture("Ledger"),
+        build_config: build_config(&install_dir),
+        unit_test_config: UnitTestingConfig {
+            dump_state_on_failure: true,
+            ..UnitTestingConfig::default_with_bound(None)
+        },
+        compute_coverage: false,
+        coverage_format: CoverageFormat::Summary,
+    };
+    let mut report = Vec::new();
+    let natives = all_natives(AccountAddress::ONE, GasParameters::zeros())
+        .into_iter()
+        .chain(nursery_natives(
+            AccountAddress::ONE,
+            NurseryGasParameters::zeros(),
+        ))
+        .collect();
+    let output = test_package(options, natives, &mut report).unwrap();
+    assert!(!output.all_passed());
+
+    let dump_path = install_dir
+        .path()
+        .join("build/test-failures/0x2.Ledger.records_too_much/state.json");
+    let report = String::from_utf8(report).unwrap();
+    assert!(
+        report.contains(&dump_path.display().to_string()),
+        "{}",
+        report
+    );
+    // the dump is written instead of being included in the report
+    assert!(!report.contains("State dump at point of failure"));
+
+    let dump: serde_json::Value =
+        serde_json::from_str(&std::fs::read_to_string(&dump_path).unwrap()).unwrap();
+    let ledger = dump["resources"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .find(|resource| resource["type"] == "0x2::Ledger::Ledger")
+        .unwrap();
+    assert_eq!(ledger["address"], "0x2");
+    // long vectors are summarized
+    let history = ledger["value"]["history"].as_array().unwrap();
+    assert_eq!(history.len(), 33);
+    assert_eq!(history[31], 31);
+    assert_eq!(history[32], "... 68 more elements");
+    assert_eq!(ledger["truncated"], true);
+
+    let events = dump["events"].as_array().unwrap();
+    assert_eq!(events.len(), 100);
+    assert_eq!(events[99]["type"], "0x2::Ledger::Entry");
+    assert_eq!(events[99]["sequence_number"], 99);
+    assert_eq!(events[99]["data"]["amount"], 99);
+    assert_eq!(events[99]["truncated"], false);
+    assert_eq!(dump["omitted"], 0);
+}
@@ -0,0 +1,5 @@
+module 0x2::Broken {
+    public fun f(): u64 {
+        true
+    }
+}
@@ -0,0 +1,16 @@
+module 0x2::Example {
+    public fun double(x: u64): u64 {
+        let unused = 0;
+        x * 2
+    }
+
+    #[test]
+    fun double_passes() {
+        assert!(double(2) == 4, 0);
+    }
+
+    #[test]
+    fun double_fails() {
+        assert!(double(2) == 5, 42);
+    }
+}
@@ -0,0 +1,38 @@
+module 0x2::Ledger {
+    use std::event::{Self, EventHandle};
+    use std::signer;
+    use std::vector;
+
+    struct Entry has drop, store {
+        amount: u64,
+    }
+
+    struct Ledger has key {
+        history: vector<u64>,
+        entries: EventHandle<Entry>,
+    }
+
+    public fun open(account: &signer) {
+        move_to(account, Ledger {
+            history: vector::empty(),
+            entries: event::new_event_handle<Entry>(account),
+        })
+    }
+
+    public fun record(account: &signer, amount: u64) acquires Ledger {
+        let ledger = borrow_global_mut<Ledger>(signer::address_of(account));
+        vector::push_back(&mut ledger.history, amount);
+        event::emit_event(&mut ledger.entries, Entry { amount });
+    }
+
+    #[test(account = @0x2)]
+    fun records_too_much(account: signer) acquires Ledger {
+        open(&account);
+        let i = 0;
+        while (i < 100) {
+            record(&account, i);
+            i = i + 1;
+        };
+        assert!(vector::length(&borrow_global<Ledger>(@0x2).history) < 100, 7);
+    }
+}
@@ -0,0 +1,3 @@
+module Package::M {
+    public fun f(): u64 { 0 }
+}
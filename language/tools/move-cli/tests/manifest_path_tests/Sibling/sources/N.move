@@ -0,0 +1,3 @@
+module Sibling::N {
+    public fun g(): u64 { 1 }
+}
@@ -0,0 +1,3 @@
+module app::wallet {
+    public fun balance(): u64 { util::math::double(core::coin::value()) }
+}
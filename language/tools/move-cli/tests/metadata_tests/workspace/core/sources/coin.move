@@ -0,0 +1,3 @@
+module core::coin {
+    public fun value(): u64 { 1 }
+}
@@ -0,0 +1,10 @@
+module 0x2::Registry {
+    struct Entry has key {
+        peer: address,
+        value: u64,
+    }
+
+    public fun register(account: &signer, peer: address, value: u64) {
+        move_to(account, Entry { peer, value })
+    }
+}
@@ -0,0 +1,7 @@
+script {
+    use Example::Counter;
+
+    fun increment() {
+        Counter::increment()
+    }
+}
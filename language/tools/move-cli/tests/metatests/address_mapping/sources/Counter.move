@@ -0,0 +1,17 @@
+module Example::Counter {
+    struct Counter has key { value: u64 }
+
+    public entry fun init(account: signer) {
+        move_to(&account, Counter { value: 0 })
+    }
+
+    // `@Example` is remapped together with the module address
+    public entry fun increment() acquires Counter {
+        let counter = borrow_global_mut<Counter>(@Example);
+        counter.value = counter.value + 1;
+    }
+
+    public fun value(): u64 acquires Counter {
+        borrow_global<Counter>(@Example).value
+    }
+}
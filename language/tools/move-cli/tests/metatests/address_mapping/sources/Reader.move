@@ -0,0 +1,7 @@
+module Other::Reader {
+    use Example::Counter;
+
+    public fun read(): u64 {
+        Counter::value()
+    }
+}
@@ -0,0 +1,9 @@
+module 0x2::A {
+    public fun one(): u64 {
+        1
+    }
+
+    public fun add(x: u64, y: u64): u64 {
+        x + y
+    }
+}
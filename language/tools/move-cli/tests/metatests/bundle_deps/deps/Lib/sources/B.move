@@ -0,0 +1,7 @@
+module 0x2::B {
+    use 0x2::A;
+
+    public fun double(x: u64): u64 {
+        A::add(x, x)
+    }
+}
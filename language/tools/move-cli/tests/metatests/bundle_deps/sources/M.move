@@ -0,0 +1,7 @@
+module 0x3::M {
+    use 0x2::B;
+
+    public fun quadruple(x: u64): u64 {
+        B::double(B::double(x))
+    }
+}
@@ -0,0 +1,7 @@
+script {
+    use 0x2::Registry;
+
+    fun main(account: signer, value: u64) {
+        Registry::register(&account, value)
+    }
+}
@@ -0,0 +1,9 @@
+module 0x2::Registry {
+    struct Entry has key {
+        value: u64,
+    }
+
+    public fun register(account: &signer, value: u64) {
+        move_to(account, Entry { value })
+    }
+}
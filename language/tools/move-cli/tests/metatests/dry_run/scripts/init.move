@@ -0,0 +1,7 @@
+script {
+    use 0x42::Counter;
+
+    fun init(account: signer) {
+        Counter::init(&account)
+    }
+}
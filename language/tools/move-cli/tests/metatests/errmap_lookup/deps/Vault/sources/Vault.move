@@ -0,0 +1,11 @@
+module 0x2::Vault {
+    /// The amount exceeds the balance of the vault
+    const EINSUFFICIENT_BALANCE: u64 = 5;
+
+    const BALANCE: u64 = 10;
+
+    public fun withdraw(amount: u64): u64 {
+        assert!(amount <= BALANCE, EINSUFFICIENT_BALANCE);
+        BALANCE - amount
+    }
+}
@@ -0,0 +1,7 @@
+script {
+    use 0x3::Teller;
+
+    fun main(amount: u64) {
+        Teller::withdraw(amount);
+    }
+}
@@ -0,0 +1,7 @@
+module 0x3::Teller {
+    use 0x2::Vault;
+
+    public fun withdraw(amount: u64) {
+        Vault::withdraw(amount);
+    }
+}
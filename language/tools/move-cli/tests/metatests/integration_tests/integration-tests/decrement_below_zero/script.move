@@ -0,0 +1,8 @@
+script {
+    use 0x42::Counter;
+
+    fun decrement_below_zero(account: signer) {
+        Counter::init(&account);
+        Counter::decrement(@0x2)
+    }
+}
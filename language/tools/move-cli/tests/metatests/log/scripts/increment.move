@@ -0,0 +1,7 @@
+script {
+    use 0x42::Counter;
+
+    fun increment() {
+        Counter::increment(@0x2)
+    }
+}
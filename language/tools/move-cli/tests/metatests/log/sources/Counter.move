@@ -0,0 +1,12 @@
+module 0x42::Counter {
+    struct Counter has key { value: u64 }
+
+    public fun init(account: &signer) {
+        move_to(account, Counter { value: 0 })
+    }
+
+    public fun increment(addr: address) acquires Counter {
+        let counter = borrow_global_mut<Counter>(addr);
+        counter.value = counter.value + 1;
+    }
+}
@@ -0,0 +1,16 @@
+module 0x2::Coin {
+    struct Coin has key { value: u64 }
+
+    const EEMPTY_COIN: u64 = 1;
+
+    public entry fun mint(account: signer, value: u64) {
+        move_to(&account, Coin { value })
+    }
+
+    /// Migrate the coin of `addr` to a denomination `factor` times smaller
+    public entry fun redenominate(addr: address, factor: u64) acquires Coin {
+        let coin = borrow_global_mut<Coin>(addr);
+        assert!(coin.value > 0, EEMPTY_COIN);
+        coin.value = coin.value * factor;
+    }
+}
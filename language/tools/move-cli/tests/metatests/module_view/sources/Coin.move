@@ -0,0 +1,13 @@
+module Example::Coin {
+    struct Coin has store, drop {
+        value: u64,
+    }
+
+    public fun mint(value: u64): Coin {
+        Coin { value }
+    }
+
+    public fun value(coin: &Coin): u64 {
+        coin.value
+    }
+}
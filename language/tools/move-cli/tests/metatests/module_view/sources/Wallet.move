@@ -0,0 +1,20 @@
+module Example::Wallet {
+    use Example::Coin::{Self, Coin};
+
+    struct Wallet<T: store> has key {
+        items: vector<T>,
+    }
+
+    public fun split(coin: Coin, amount: u64): (Coin, Coin) {
+        let value = Coin::value(&coin);
+        (Coin::mint(value - amount), Coin::mint(amount))
+    }
+
+    public fun borrow_items<T: store>(wallet: &Wallet<T>): &vector<T> {
+        &wallet.items
+    }
+
+    public entry fun create(_account: signer) {}
+
+    fun helper() {}
+}
@@ -0,0 +1,14 @@
+script {
+    use 0x42::Config;
+
+    fun configure(
+        account: signer,
+        amount: u64,
+        owner: address,
+        enabled: bool,
+        memo: vector<u8>,
+        level: u8
+    ) {
+        Config::configure(account, amount, owner, enabled, memo, level)
+    }
+}
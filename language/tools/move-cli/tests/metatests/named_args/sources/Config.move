@@ -0,0 +1,20 @@
+module 0x42::Config {
+    struct Config has key {
+        amount: u64,
+        owner: address,
+        enabled: bool,
+        memo: vector<u8>,
+        level: u8,
+    }
+
+    public entry fun configure(
+        account: signer,
+        amount: u64,
+        owner: address,
+        enabled: bool,
+        memo: vector<u8>,
+        level: u8
+    ) {
+        move_to(&account, Config { amount, owner, enabled, memo, level })
+    }
+}
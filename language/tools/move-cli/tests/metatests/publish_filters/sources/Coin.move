@@ -0,0 +1,9 @@
+module 0x2::Coin {
+    struct Coin has store {
+        value: u64,
+    }
+
+    public fun value(coin: &Coin): u64 {
+        coin.value
+    }
+}
@@ -0,0 +1,5 @@
+module 0x2::TestHelpers {
+    public fun amount(): u64 {
+        42
+    }
+}
@@ -0,0 +1,7 @@
+module 0x2::TestScaffold {
+    use 0x2::TestHelpers;
+
+    public fun double_amount(): u64 {
+        TestHelpers::amount() * 2
+    }
+}
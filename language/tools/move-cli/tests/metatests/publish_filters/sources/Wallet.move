@@ -0,0 +1,11 @@
+module 0x2::Wallet {
+    use 0x2::Coin::{Self, Coin};
+
+    struct Wallet has key {
+        coin: Coin,
+    }
+
+    public fun balance(wallet: &Wallet): u64 {
+        Coin::value(&wallet.coin)
+    }
+}
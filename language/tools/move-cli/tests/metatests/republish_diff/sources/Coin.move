@@ -0,0 +1,20 @@
+module 0x2::Coin {
+    const MAX_SUPPLY: u64 = 1000;
+
+    struct Coin has store {
+        value: u64,
+    }
+
+    public fun mint(value: u64): Coin {
+        assert!(value <= MAX_SUPPLY, 0);
+        Coin { value }
+    }
+
+    public fun value(coin: &Coin): u64 {
+        coin.value
+    }
+
+    fun check(value: u64): bool {
+        value > 0
+    }
+}
@@ -0,0 +1,28 @@
+module 0x2::Coin {
+    const MAX_SUPPLY: u64 = 2000;
+
+    struct Coin has store {
+        value: u64,
+    }
+
+    struct Receipt has drop {
+        amount: u64,
+    }
+
+    public fun mint(value: u64): Coin {
+        assert!(check(value, MAX_SUPPLY), 0);
+        Coin { value }
+    }
+
+    public fun value(coin: &Coin): u64 {
+        coin.value
+    }
+
+    public fun receipt(coin: &Coin): Receipt {
+        Receipt { amount: coin.value }
+    }
+
+    fun check(value: u64, max: u64): bool {
+        value > 0 && value <= max
+    }
+}
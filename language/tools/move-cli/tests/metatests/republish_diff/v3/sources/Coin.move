@@ -0,0 +1,25 @@
+module 0x2::Coin {
+    const MAX_SUPPLY: u64 = 2000;
+
+    struct Coin has store {
+        value: u64,
+        frozen: bool,
+    }
+
+    struct Receipt has drop {
+        amount: u64,
+    }
+
+    public fun mint(value: u64): Coin {
+        assert!(check(value, MAX_SUPPLY), 0);
+        Coin { value, frozen: false }
+    }
+
+    public fun receipt(coin: &Coin): Receipt {
+        Receipt { amount: coin.value }
+    }
+
+    fun check(value: u64, max: u64): bool {
+        value > 0 && value <= max
+    }
+}
@@ -0,0 +1,17 @@
+module admin::Roles {
+    use std::signer;
+
+    #[test(admin = @admin, treasury = @treasury, alice = @0xA11CE, bob = @0xB0B)]
+    fun four_signers(admin: signer, treasury: signer, alice: signer, bob: signer) {
+        assert!(signer::address_of(&admin) == @0xA, 0);
+        assert!(signer::address_of(&treasury) == @treasury, 1);
+        assert!(signer::address_of(&alice) == @0xA11CE, 2);
+        assert!(signer::address_of(&bob) == @0xB0B, 3);
+    }
+
+    #[test(std = @std, owner = @admin)]
+    fun named_addresses(std: signer, owner: address) {
+        assert!(signer::address_of(&std) == @0x1, 0);
+        assert!(owner == @admin, 1);
+    }
+}
@@ -0,0 +1,9 @@
+module 0x2::Entry {
+    use 0x2::Router;
+
+    public entry fun run(account: signer) {
+        Router::route(&account, 10);
+    }
+
+    public entry fun idle() {}
+}
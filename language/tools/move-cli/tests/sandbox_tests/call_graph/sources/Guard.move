@@ -0,0 +1,8 @@
+module 0x2::Guard {
+    use 0x2::Vault;
+
+    public fun checked_withdraw(account: &signer, amount: u64): u64 {
+        assert!(amount < 100, 0);
+        Vault::withdraw(account, amount)
+    }
+}
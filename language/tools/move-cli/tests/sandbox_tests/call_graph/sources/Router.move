@@ -0,0 +1,12 @@
+module 0x2::Router {
+    use 0x2::Guard;
+    use 0x2::Vault;
+
+    public fun route(account: &signer, amount: u64): u64 {
+        Guard::checked_withdraw(account, amount)
+    }
+
+    public fun quote(addr: address): u64 {
+        Vault::balance(addr)
+    }
+}
@@ -0,0 +1,15 @@
+module 0x2::Vault {
+    use std::signer;
+
+    struct Vault has key { amount: u64 }
+
+    public fun withdraw(account: &signer, amount: u64): u64 acquires Vault {
+        let vault = borrow_global_mut<Vault>(signer::address_of(account));
+        vault.amount = vault.amount - amount;
+        amount
+    }
+
+    public fun balance(addr: address): u64 acquires Vault {
+        borrow_global<Vault>(addr).amount
+    }
+}
@@ -0,0 +1,8 @@
+module 0x2::Coins {
+    struct Coin has store { value: u64 }
+
+    struct Wallet has key { owner: address, coins: vector<Coin> }
+
+    // not storable, so no layout is generated for it
+    struct Receipt has drop { amount: u64 }
+}
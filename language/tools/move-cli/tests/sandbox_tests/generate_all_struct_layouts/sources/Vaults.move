@@ -0,0 +1,5 @@
+module 0x2::Vaults {
+    use 0x2::Coins::Coin;
+
+    struct Vault<T: store> has key { items: vector<T>, fee: Coin }
+}
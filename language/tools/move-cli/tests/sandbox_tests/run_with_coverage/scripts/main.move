@@ -0,0 +1,7 @@
+script {
+    use Example::Counter;
+
+    fun main() {
+        Counter::touched(1);
+    }
+}
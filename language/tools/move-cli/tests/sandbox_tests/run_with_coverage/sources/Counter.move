@@ -0,0 +1,13 @@
+module Example::Counter {
+    public fun touched(x: u64): u64 {
+        if (x > 10) {
+            x - 10
+        } else {
+            x + 1
+        }
+    }
+
+    public fun untouched(x: u64): u64 {
+        x * 2
+    }
+}
@@ -0,0 +1,9 @@
+#[test_only]
+module Example::CounterTests {
+    use Example::Counter;
+
+    #[test]
+    fun test_untouched() {
+        assert!(Counter::untouched(2) == 6, 0);
+    }
+}
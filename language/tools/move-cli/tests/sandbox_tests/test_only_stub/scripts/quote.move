@@ -0,0 +1,7 @@
+script {
+    use 0x2::Pricing;
+
+    fun quote() {
+        Pricing::quote(7, 3);
+    }
+}
@@ -0,0 +1,7 @@
+// Answers with a canned price instead of reading the price feed
+#[test_only]
+module 0x2::Oracle {
+    public fun latest_price(_feed: u64): u64 {
+        100
+    }
+}
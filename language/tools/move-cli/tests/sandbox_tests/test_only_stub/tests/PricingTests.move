@@ -0,0 +1,9 @@
+#[test_only]
+module 0x2::PricingTests {
+    use 0x2::Pricing;
+
+    #[test]
+    fun quote_uses_the_latest_price() {
+        assert!(Pricing::quote(7, 3) == 300, 0)
+    }
+}
@@ -0,0 +1,50 @@
+module 0x2::TimeLock {
+    use std::signer;
+    use std::timestamp;
+    #[test_only]
+    use std::unit_test;
+
+    /// Coins that can only be withdrawn once `unlock_time` (in seconds) has passed
+    struct TimeLock has key { coins: u64, unlock_time: u64 }
+
+    const ELOCKED: u64 = 1;
+
+    public entry fun lock(account: signer, coins: u64, unlock_time: u64) {
+        lock_coins(&account, coins, unlock_time)
+    }
+
+    public entry fun withdraw(account: signer) acquires TimeLock {
+        withdraw_coins(&account);
+    }
+
+    fun lock_coins(account: &signer, coins: u64, unlock_time: u64) {
+        move_to(account, TimeLock { coins, unlock_time })
+    }
+
+    fun withdraw_coins(account: &signer): u64 acquires TimeLock {
+        let TimeLock { coins, unlock_time } = move_from<TimeLock>(signer::address_of(account));
+        assert!(timestamp::now_seconds() >= unlock_time, ELOCKED);
+        coins
+    }
+
+    #[test(account = @0x42)]
+    #[expected_failure(abort_code = 1)]
+    fun withdraw_before_unlock_time(account: signer) acquires TimeLock {
+        lock_coins(&account, 10, 100);
+        unit_test::set_time(99 * 1000000);
+        withdraw_coins(&account);
+    }
+
+    #[test(account = @0x42)]
+    fun withdraw_after_advancing_the_clock(account: signer) acquires TimeLock {
+        lock_coins(&account, 10, 100);
+        unit_test::set_time(100 * 1000000);
+        assert!(withdraw_coins(&account) == 10, 0);
+    }
+
+    #[test]
+    fun starts_at_the_initial_time_and_height() {
+        assert!(timestamp::now_seconds() == 100, 0);
+        assert!(timestamp::block_height() == 5, 1);
+    }
+}
@@ -0,0 +1,11 @@
+module 0x2::Coin {
+    struct Coin has key, store { value: u64 }
+
+    public fun mint(value: u64): Coin {
+        Coin { value }
+    }
+
+    public fun value(coin: &Coin): u64 {
+        coin.value
+    }
+}
@@ -0,0 +1,5 @@
+module 0x2::Math {
+    public fun max(a: u64, b: u64): u64 {
+        if (a > b) a else b
+    }
+}
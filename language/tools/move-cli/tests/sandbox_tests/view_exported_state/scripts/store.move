@@ -0,0 +1,5 @@
+script {
+    fun store(account: signer) {
+        0x2::Vault::store(&account, 0x2::Token::mint(7))
+    }
+}
@@ -0,0 +1,7 @@
+module 0x2::Token {
+    struct Coin has store { value: u64 }
+
+    public fun mint(value: u64): Coin {
+        Coin { value }
+    }
+}
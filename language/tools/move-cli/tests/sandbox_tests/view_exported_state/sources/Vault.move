@@ -0,0 +1,7 @@
+module 0x2::Vault {
+    struct Box<T: store> has key { item: T }
+
+    public fun store<T: store>(account: &signer, item: T) {
+        move_to(account, Box { item })
+    }
+}
@@ -0,0 +1,5 @@
+script {
+    fun publish(account: signer) {
+        0x2::Listing::publish(&account)
+    }
+}
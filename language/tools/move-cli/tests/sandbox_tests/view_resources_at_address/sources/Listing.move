@@ -0,0 +1,9 @@
+module 0x2::Listing {
+    struct Counter has key { value: u64 }
+    struct Flags has key { enabled: bool, level: u8 }
+
+    public fun publish(account: &signer) {
+        move_to(account, Flags { enabled: true, level: 3 });
+        move_to(account, Counter { value: 42 });
+    }
+}
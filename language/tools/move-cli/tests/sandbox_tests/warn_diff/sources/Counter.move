@@ -0,0 +1,15 @@
+module 0x2::Counter {
+    struct Counter has key { value: u64 }
+
+    public fun reset(c: &mut Counter, hard: bool) {
+        c.value = 0
+    }
+
+    public fun increment(c: &mut Counter) {
+        c.value = c.value + 1
+    }
+
+    public fun add(c: &mut Counter, n: u64, step: u64) {
+        c.value = c.value + n
+    }
+}
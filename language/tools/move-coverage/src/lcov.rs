@@ -0,0 +1,109 @@
+// Copyright (c) The Move Contributors
+// SPDX-License-Identifier: Apache-2.0
+
+#![forbid(unsafe_code)]
+
+use crate::coverage_map::ExecCoverageMap;
+use codespan::Files;
+use move_binary_format::{
+    access::ModuleAccess,
+    file_format::{CodeOffset, FunctionDefinitionIndex},
+    CompiledModule,
+};
+use move_bytecode_source_map::source_map::SourceMap;
+use std::{
+    collections::BTreeMap,
+    fs,
+    io::{self, Write},
+    path::Path,
+};
+
+/// Write the coverage of `module`, compiled from the source file at `source_path`, as an LCOV
+/// record. A line is hit as many times as the most executed instruction on it.
+pub fn write_lcov_record<W: Write>(
+    module: &CompiledModule,
+    source_map: &SourceMap,
+    source_path: &Path,
+    coverage_map: &ExecCoverageMap,
+    writer: &mut W,
+) -> io::Result<()> {
+    let file_contents = fs::read_to_string(source_path)?;
+    let mut files = Files::new();
+    let file_id = files.add(source_path.as_os_str().to_os_string(), file_contents);
+    // lcov lines are 1-based, codespan lines are 0-based
+    let line_of = |offset: u32| {
+        files
+            .location(file_id, offset)
+            .map(|loc| loc.line.0 + 1)
+            .ok()
+    };
+
+    let module_id = module.self_id();
+    let module_map = coverage_map
+        .module_maps
+        .get(&(*module_id.address(), module_id.name().to_owned()));
+
+    let mut functions = vec![];
+    let mut lines: BTreeMap<u32, u64> = BTreeMap::new();
+    for (idx, function_def) in module.function_defs().iter().enumerate() {
+        let code_unit = match &function_def.code {
+            Some(code_unit) => code_unit,
+            None => continue,
+        };
+        let fn_name = module
+            .identifier_at(module.function_handle_at(function_def.function).name)
+            .to_owned();
+        let function_def_idx = FunctionDefinitionIndex(idx as u16);
+        let function_map = match source_map.get_function_source_map(function_def_idx) {
+            Ok(function_map) => function_map,
+            Err(_) => continue,
+        };
+        let function_coverage =
+            module_map.and_then(|module_map| module_map.get_function_coverage(&fn_name));
+        let hits = |offset: usize| {
+            function_coverage
+                .and_then(|coverage| coverage.get(&(offset as u64)))
+                .copied()
+                .unwrap_or(0)
+        };
+
+        if let Some(line) = line_of(function_map.definition_location.start()) {
+            functions.push((line, fn_name.to_string(), hits(0)));
+        }
+        for offset in 0..code_unit.code.len() {
+            let loc = match function_map.get_code_location(offset as CodeOffset) {
+                Some(loc) => loc,
+                None => continue,
+            };
+            if let Some(line) = line_of(loc.start()) {
+                let count = lines.entry(line).or_insert(0);
+                *count = (*count).max(hits(offset));
+            }
+        }
+    }
+
+    writeln!(writer, "TN:")?;
+    writeln!(writer, "SF:{}", source_path.display())?;
+    for (line, name, _) in &functions {
+        writeln!(writer, "FN:{},{}", line, name)?;
+    }
+    for (_, name, count) in &functions {
+        writeln!(writer, "FNDA:{},{}", count, name)?;
+    }
+    writeln!(writer, "FNF:{}", functions.len())?;
+    writeln!(
+        writer,
+        "FNH:{}",
+        functions.iter().filter(|(_, _, count)| *count > 0).count()
+    )?;
+    for (line, count) in &lines {
+        writeln!(writer, "DA:{},{}", line, count)?;
+    }
+    writeln!(writer, "LF:{}", lines.len())?;
+    writeln!(
+        writer,
+        "LH:{}",
+        lines.values().filter(|count| **count > 0).count()
+    )?;
+    writeln!(writer, "end_of_record")
+}
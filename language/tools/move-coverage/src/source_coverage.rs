@@ -238,6 +238,55 @@ impl SourceCoverage {
         }
         Ok(())
     }
+
+    /// Write the source as a standalone HTML page, with covered code in green and uncovered code
+    /// in red.
+    pub fn output_source_coverage_html<W: Write>(
+        &self,
+        title: &str,
+        output_writer: &mut W,
+    ) -> io::Result<()> {
+        writeln!(output_writer, "<!DOCTYPE html>")?;
+        writeln!(output_writer, "<html>")?;
+        writeln!(output_writer, "<head>")?;
+        writeln!(output_writer, "<meta charset=\"utf-8\">")?;
+        writeln!(output_writer, "<title>{}</title>", escape_html(title))?;
+        writeln!(
+            output_writer,
+            "<style>.covered {{ color: green; }} .uncovered {{ color: red; font-weight: bold; }}</style>"
+        )?;
+        writeln!(output_writer, "</head>")?;
+        writeln!(output_writer, "<body>")?;
+        writeln!(output_writer, "<h1>{}</h1>", escape_html(title))?;
+        writeln!(output_writer, "<pre>")?;
+        for line in self.annotated_lines.iter() {
+            for string_segment in line.iter() {
+                let (class, s) = match string_segment {
+                    StringSegment::Covered(s) => ("covered", s),
+                    StringSegment::Uncovered(s) => ("uncovered", s),
+                };
+                if !s.is_empty() {
+                    write!(
+                        output_writer,
+                        "<span class=\"{}\">{}</span>",
+                        class,
+                        escape_html(s)
+                    )?;
+                }
+            }
+            writeln!(output_writer)?;
+        }
+        writeln!(output_writer, "</pre>")?;
+        writeln!(output_writer, "</body>")?;
+        writeln!(output_writer, "</html>")
+    }
+}
+
+fn escape_html(s: &str) -> String {
+    s.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
 }
 
 fn merge_spans(cov: FunctionSourceCoverage) -> Vec<Span> {
@@ -0,0 +1,381 @@
+// Copyright (c) The Move Contributors
+// SPDX-License-Identifier: Apache-2.0
+
+//! A long-lived analysis of a resolved package for editors and language servers. Unsaved buffers
+//! are given to the `AnalysisHost` as overlays, and only the files affected by a change are
+//! checked again: the dependencies and the other files of the package are compiled once into a
+//! library that is reused until one of them changes.
+
+use crate::{
+    compilation::compiled_package::{declared_module_names, make_source_and_deps_for_compiler},
+    resolution::resolution_graph::ResolvedGraph,
+};
+use anyhow::{anyhow, Context, Result};
+use move_command_line_common::{
+    files::{remap_path_prefix, FileHash, PathPrefixRemap},
+    json_errors::JsonDiagnostic,
+};
+use move_compiler::{
+    construct_pre_compiled_lib,
+    diagnostics::{self, codes::Severity, Diagnostics, FilesSourceText},
+    parser::lexer::{Lexer, Tok},
+    shared::{Flags, PackagePaths},
+    Compiler, FullyCompiledProgram,
+};
+use move_symbol_pool::Symbol;
+use std::{
+    collections::{BTreeMap, BTreeSet},
+    fs,
+    path::{Path, PathBuf},
+    time::{Duration, Instant},
+};
+use tempfile::TempDir;
+
+/// Checks the package of a resolved graph again and again as its files are edited
+pub struct AnalysisHost {
+    resolution_graph: ResolvedGraph,
+    /// The unsaved contents of files, by their canonical path
+    overlays: BTreeMap<PathBuf, Overlay>,
+    /// Where the overlays are written for the compiler to read them
+    overlay_dir: TempDir,
+    library: Option<Library>,
+    stats: AnalysisStats,
+}
+
+struct Overlay {
+    contents: String,
+    /// The directory under `overlay_dir` holding the contents under the name of the file
+    dir: PathBuf,
+}
+
+/// The dependencies and the files of the package outside of the checked files, compiled together
+struct Library {
+    /// The files compiled into the library, with the contents they were compiled from
+    sources: BTreeMap<Symbol, String>,
+    /// `None` if the files failed to compile
+    program: Option<FullyCompiledProgram>,
+}
+
+/// What the last check of an `AnalysisHost` did, for editors to report and tests to observe
+#[derive(Debug, Clone, Default)]
+pub struct AnalysisStats {
+    /// How many times the library of dependencies and unaffected files was compiled
+    pub library_builds: usize,
+    /// The files checked by the last check
+    pub checked_files: Vec<PathBuf>,
+    /// The modules declared in those files, as `<address>::<name>`
+    pub checked_modules: Vec<String>,
+    /// Time the last check spent compiling the library, zero if it was reused
+    pub library_time: Duration,
+    /// Time the last check spent checking its files
+    pub check_time: Duration,
+}
+
+/// The diagnostics of a check, reported under the paths of the files on disk
+#[derive(Debug)]
+pub struct CheckResult {
+    pub files: FilesSourceText,
+    pub diagnostics: Diagnostics,
+}
+
+impl CheckResult {
+    pub fn has_errors(&self) -> bool {
+        matches!(self.diagnostics.max_severity(), Some(severity) if severity > Severity::Warning)
+    }
+
+    /// The diagnostics at the position of their primary label, sorted by position
+    pub fn json_diagnostics(&self) -> Vec<JsonDiagnostic> {
+        diagnostics::json_diagnostics(&self.files, &self.diagnostics)
+    }
+}
+
+impl AnalysisHost {
+    pub fn new(resolution_graph: ResolvedGraph) -> Result<Self> {
+        Ok(Self {
+            resolution_graph,
+            overlays: BTreeMap::new(),
+            overlay_dir: tempfile::tempdir()
+                .context("Unable to create a directory for the file overlays")?,
+            library: None,
+            stats: AnalysisStats::default(),
+        })
+    }
+
+    pub fn resolution_graph(&self) -> &ResolvedGraph {
+        &self.resolution_graph
+    }
+
+    pub fn stats(&self) -> &AnalysisStats {
+        &self.stats
+    }
+
+    /// Check the file at `path` with `contents` instead of what is on disk, until the overlay is
+    /// removed
+    pub fn set_overlay(&mut self, path: &Path, contents: String) -> Result<()> {
+        let path = canonical_path(path);
+        let file_name = path
+            .file_name()
+            .ok_or_else(|| anyhow!("'{}' is not a file", path.display()))?;
+        let dir = match self.overlays.get(&path) {
+            Some(overlay) => overlay.dir.clone(),
+            None => self
+                .overlay_dir
+                .path()
+                .join(self.overlays.len().to_string()),
+        };
+        fs::create_dir_all(&dir)?;
+        fs::write(dir.join(file_name), &contents)
+            .with_context(|| format!("Unable to write the overlay of '{}'", path.display()))?;
+        self.overlays.insert(path, Overlay { contents, dir });
+        Ok(())
+    }
+
+    /// Check the file at `path` with what is on disk again
+    pub fn remove_overlay(&mut self, path: &Path) {
+        if let Some(overlay) = self.overlays.remove(&canonical_path(path)) {
+            let _ = fs::remove_dir_all(overlay.dir);
+        }
+    }
+
+    /// Check the source file of the root package at `path` and the files depending on the modules
+    /// it declares, directly or not. The other files are compiled into a library that is reused by
+    /// the next checks as long as none of them change. If they fail to compile, the whole package
+    /// is checked instead.
+    pub fn check_file(&mut self, path: &Path) -> Result<CheckResult> {
+        self.check(Some(path))
+    }
+
+    /// Check all the source files of the root package against its dependencies
+    pub fn check_all(&mut self) -> Result<CheckResult> {
+        self.check(None)
+    }
+
+    fn check(&mut self, changed: Option<&Path>) -> Result<CheckResult> {
+        let graph = &self.resolution_graph;
+        let root_package = graph.get_package(&graph.root_package.package.name);
+        let dependencies = root_package
+            .transitive_dependencies(graph)
+            .into_iter()
+            .map(|name| {
+                let package = graph.get_package(&name);
+                Ok((
+                    name,
+                    package.get_sources(&graph.build_options)?,
+                    &package.resolution_table,
+                ))
+            })
+            .collect::<Result<Vec<_>>>()?;
+        let (root_paths, deps_paths) =
+            make_source_and_deps_for_compiler(graph, root_package, dependencies)?;
+        let flags = self.flags(std::iter::once(&root_paths).chain(&deps_paths));
+        let remaps = flags.path_prefix_remaps().to_vec();
+        let root_sources = root_paths
+            .paths
+            .iter()
+            .map(|path| Ok((*path, self.read(*path)?)))
+            .collect::<Result<BTreeMap<_, _>>>()?;
+
+        let checked_files = match changed {
+            None => root_paths.paths.iter().copied().collect(),
+            Some(path) => {
+                let path = canonical_path(path);
+                let changed = root_paths
+                    .paths
+                    .iter()
+                    .find(|file| canonical_path(Path::new(file.as_str())) == path)
+                    .ok_or_else(|| {
+                        anyhow!(
+                            "'{}' is not a source file of package '{}'",
+                            path.display(),
+                            graph.root_package.package.name
+                        )
+                    })?;
+                affected_files(&root_sources, *changed)
+            }
+        };
+        let mut library_packages = deps_paths.clone();
+        let unaffected_files: Vec<_> = root_paths
+            .paths
+            .iter()
+            .filter(|path| !checked_files.contains(path))
+            .copied()
+            .collect();
+        if !unaffected_files.is_empty() {
+            library_packages.push(PackagePaths {
+                name: root_paths.name,
+                paths: unaffected_files,
+                named_address_map: root_paths.named_address_map.clone(),
+            });
+        }
+
+        let start = Instant::now();
+        let mut library_sources = BTreeMap::new();
+        for path in library_packages.iter().flat_map(|package| &package.paths) {
+            library_sources.insert(*path, self.read(*path)?);
+        }
+        let library_time = match &self.library {
+            Some(library) if library.sources == library_sources => Duration::ZERO,
+            _ => {
+                let program = match construct_pre_compiled_lib(
+                    self.compiler_paths(library_packages),
+                    None,
+                    flags.clone(),
+                )? {
+                    Ok(program) => Some(program),
+                    Err(_) => None,
+                };
+                self.library = Some(Library {
+                    sources: library_sources,
+                    program,
+                });
+                self.stats.library_builds += 1;
+                start.elapsed()
+            }
+        };
+
+        let start = Instant::now();
+        let library_program = self.library.as_ref().and_then(|lib| lib.program.as_ref());
+        let (checked_files, (mut files, result)) = match library_program {
+            Some(program) => {
+                let targets = PackagePaths {
+                    paths: checked_files.iter().copied().collect(),
+                    ..root_paths
+                };
+                let compiler =
+                    Compiler::from_package_paths(self.compiler_paths(vec![targets]), vec![])
+                        .set_flags(flags)
+                        .set_pre_compiled_lib(program);
+                (checked_files, compiler.build()?)
+            }
+            None => {
+                let all_files = root_paths.paths.iter().copied().collect();
+                let compiler = Compiler::from_package_paths(
+                    self.compiler_paths(vec![root_paths]),
+                    self.compiler_paths(deps_paths),
+                )
+                .set_flags(flags);
+                (all_files, compiler.build()?)
+            }
+        };
+        if let Some(program) = library_program {
+            // diagnostics of the checked files can point into the library
+            files.extend(program.files.clone());
+        }
+
+        self.stats.checked_modules = checked_files
+            .iter()
+            .flat_map(|path| declared_module_names(&root_sources[path]))
+            .collect();
+        self.stats.checked_files = checked_files
+            .into_iter()
+            .map(|path| PathBuf::from(path.as_str()))
+            .collect();
+        self.stats.library_time = library_time;
+        self.stats.check_time = start.elapsed();
+        Ok(CheckResult {
+            files: diagnostics::remap_file_names(&files, &remaps),
+            diagnostics: match result {
+                Ok((_, warnings)) => warnings,
+                Err(errors) => errors,
+            },
+        })
+    }
+
+    /// The contents of the file at `path`, from its overlay if it has one
+    fn read(&self, path: Symbol) -> Result<String> {
+        match self.overlays.get(&canonical_path(Path::new(path.as_str()))) {
+            Some(overlay) => Ok(overlay.contents.clone()),
+            None => fs::read_to_string(path.as_str())
+                .with_context(|| format!("Unable to read '{}'", path)),
+        }
+    }
+
+    /// `packages` with the files that have an overlay replaced by the file of their overlay
+    fn compiler_paths(&self, packages: Vec<PackagePaths>) -> Vec<PackagePaths> {
+        packages
+            .into_iter()
+            .map(|package| PackagePaths {
+                paths: package
+                    .paths
+                    .into_iter()
+                    .map(|path| {
+                        let path_buf = canonical_path(Path::new(path.as_str()));
+                        match (self.overlays.get(&path_buf), path_buf.file_name()) {
+                            (Some(overlay), Some(file_name)) => {
+                                Symbol::from(overlay.dir.join(file_name).to_string_lossy().as_ref())
+                            }
+                            _ => path,
+                        }
+                    })
+                    .collect(),
+                ..package
+            })
+            .collect()
+    }
+
+    /// The compiler flags of the package, reporting the files of the overlays under the paths of
+    /// the files of `packages` they replace
+    fn flags<'a>(&self, packages: impl IntoIterator<Item = &'a PackagePaths>) -> Flags {
+        let options = &self.resolution_graph.build_options;
+        let mut remaps = options.remap_path_prefix.clone();
+        for path in packages.into_iter().flat_map(|package| &package.paths) {
+            let path = Path::new(path.as_str());
+            if let Some(overlay) = self.overlays.get(&canonical_path(path)) {
+                let dir = path.parent().unwrap_or_else(|| Path::new(""));
+                remaps.push(PathPrefixRemap {
+                    from: overlay.dir.clone(),
+                    to: remap_path_prefix(&options.remap_path_prefix, dir),
+                });
+            }
+        }
+        if options.test_mode {
+            Flags::testing()
+        } else {
+            Flags::empty()
+        }
+        .set_path_prefix_remaps(remaps)
+        .set_root_package(self.resolution_graph.root_package.package.name)
+    }
+}
+
+/// The path of `path` with its symbolic links resolved, if it exists
+fn canonical_path(path: &Path) -> PathBuf {
+    path.canonicalize().unwrap_or_else(|_| path.to_path_buf())
+}
+
+/// `changed` and the files of `sources` that mention the name of a module declared in a file of
+/// the result. This is an over-approximation of the files depending on `changed`, as the names are
+/// not resolved.
+fn affected_files(sources: &BTreeMap<Symbol, String>, changed: Symbol) -> BTreeSet<Symbol> {
+    let identifiers: BTreeMap<_, _> = sources
+        .iter()
+        .map(|(path, contents)| (*path, identifiers(contents)))
+        .collect();
+    let mut affected = BTreeSet::from([changed]);
+    let mut queue = vec![changed];
+    while let Some(path) = queue.pop() {
+        let modules: BTreeSet<_> = declared_module_names(&sources[&path])
+            .into_iter()
+            .filter_map(|module| Some(module.rsplit_once("::")?.1.to_string()))
+            .collect();
+        for (other, other_identifiers) in &identifiers {
+            if !affected.contains(other) && !modules.is_disjoint(other_identifiers) {
+                affected.insert(*other);
+                queue.push(*other);
+            }
+        }
+    }
+    affected
+}
+
+/// The identifiers in the Move source `contents`, up to its first lexing error
+fn identifiers(contents: &str) -> BTreeSet<String> {
+    let mut lexer = Lexer::new(contents, FileHash::new(contents));
+    let mut identifiers = BTreeSet::new();
+    while lexer.advance().is_ok() && lexer.peek() != Tok::EOF {
+        if lexer.peek() == Tok::Identifier {
+            identifiers.insert(lexer.content().to_string());
+        }
+    }
+    identifiers
+}
@@ -9,7 +9,9 @@ use crate::{
 use anyhow::Result;
 use move_compiler::{
     compiled_unit::AnnotatedCompiledUnit,
-    diagnostics::{report_diagnostics_to_color_buffer, report_warnings, FilesSourceText},
+    diagnostics::{
+        remap_file_names, report_diagnostics_to_color_buffer, report_warnings, FilesSourceText,
+    },
     Compiler,
 };
 use petgraph::algo::toposort;
@@ -110,16 +112,18 @@ impl BuildPlan {
     /// Compilation process does not exit even if warnings/failures are encountered
     pub fn compile_no_exit<W: Write>(&self, writer: &mut W) -> Result<CompiledPackage> {
         self.compile_with_driver(writer, |compiler| {
+            let remaps = compiler.flags().path_prefix_remaps().to_vec();
             let (files, units_res) = compiler.build()?;
+            let shown_files = remap_file_names(&files, &remaps);
             match units_res {
                 Ok((units, warning_diags)) => {
-                    report_warnings(&files, warning_diags);
+                    report_warnings(&shown_files, warning_diags);
                     Ok((files, units))
                 }
                 Err(error_diags) => {
                     assert!(!error_diags.is_empty());
-                    let diags_buf = report_diagnostics_to_color_buffer(&files, error_diags);
-                    if let Err(err) = std::io::stdout().write_all(&diags_buf) {
+                    let diags_buf = report_diagnostics_to_color_buffer(&shown_files, error_diags);
+                    if let Err(err) = std::io::stderr().write_all(&diags_buf) {
                         anyhow::bail!("Cannot output compiler diagnostics: {}", err);
                     }
                     anyhow::bail!("Compilation error");
@@ -412,6 +416,17 @@ impl BuildPlan {
     fn clean(build_root: &Path, keep_paths: BTreeSet<PackageName>) -> Result<()> {
         for dir in std::fs::read_dir(build_root)? {
             let path = dir?.path();
+            // only the directories of packages are cleaned: files, like the warnings saved by
+            // `move package warn-diff --save`, the coverage collected by `move test --coverage`,
+            // the ABI saved by `move package diff-abi --save` and the failing inputs found by
+            // `move package fuzz` are kept across builds
+            if !path.is_dir()
+                || path.ends_with(CompiledPackageLayout::Coverage.path())
+                || path.ends_with(CompiledPackageLayout::SavedAbi.path())
+                || path.ends_with(CompiledPackageLayout::FuzzFailures.path())
+            {
+                continue;
+            }
             if !keep_paths.iter().any(|name| path.ends_with(name.as_str())) {
                 std::fs::remove_dir_all(&path)?;
             }
@@ -4,23 +4,34 @@
 
 use crate::{
     compilation::package_layout::CompiledPackageLayout,
-    resolution::resolution_graph::{Renaming, ResolvedGraph, ResolvedPackage, ResolvedTable},
+    move_tooling_version,
+    resolution::{
+        lockfile::DependencySource,
+        resolution_graph::{
+            Renaming, ResolvedGraph, ResolvedPackage, ResolvedTable, ResolvingPackage,
+        },
+    },
     source_package::{
-        layout::{SourcePackageLayout, REFERENCE_TEMPLATE_FILENAME},
+        layout::{package_move_files, SourcePackageLayout, REFERENCE_TEMPLATE_FILENAME},
+        manifest_parser::parse_version_str,
         parsed_manifest::{FileName, PackageDigest, PackageName},
     },
-    BuildConfig,
+    BuildConfig, DebugInfo,
 };
-use anyhow::{ensure, Result};
+use anyhow::{bail, ensure, Context, Result};
 use colored::Colorize;
 use move_abigen::{Abigen, AbigenOptions};
-use move_binary_format::file_format::{CompiledModule, CompiledScript};
-use move_bytecode_source_map::utils::source_map_from_file;
+use move_binary_format::{
+    binary_views::BinaryIndexedView,
+    file_format::{CompiledModule, CompiledScript},
+    file_format_common::VERSION_MAX,
+};
+use move_bytecode_source_map::{source_map::SourceMap, utils::source_map_from_file};
 use move_bytecode_utils::Modules;
 use move_command_line_common::{
     env::get_bytecode_version_from_env,
     files::{
-        extension_equals, find_filenames, MOVE_COMPILED_EXTENSION, MOVE_EXTENSION,
+        extension_equals, find_filenames, FileHash, MOVE_COMPILED_EXTENSION, MOVE_EXTENSION,
         SOURCE_MAP_EXTENSION,
     },
 };
@@ -29,18 +40,26 @@ use move_compiler::{
         self, AnnotatedCompiledUnit, CompiledUnit, NamedCompiledModule, NamedCompiledScript,
     },
     diagnostics::FilesSourceText,
+    parser::lexer::{Lexer, Tok},
     shared::{Flags, NamedAddressMap, NumericalAddress, PackagePaths},
     Compiler,
 };
 use move_docgen::{Docgen, DocgenOptions};
-use move_model::{model::GlobalEnv, options::ModelBuilderOptions, run_model_builder_with_options};
+use move_ir_types::location::Spanned;
+use move_model::{
+    model::GlobalEnv, options::ModelBuilderOptions,
+    run_model_builder_with_options_and_compilation_flags,
+};
 use move_symbol_pool::Symbol;
 use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
 use std::{
     collections::{BTreeMap, BTreeSet},
+    fmt,
     io::Write,
     path::{Path, PathBuf},
 };
+use tracing::info_span;
 
 #[derive(Debug, Clone)]
 pub enum CompilationCachingStatus {
@@ -69,6 +88,268 @@ pub struct CompiledPackageInfo {
     pub source_digest: Option<PackageDigest>,
     /// The build flags that were used when compiling this package.
     pub build_flags: BuildConfig,
+    /// The versions of the tooling and of the bytecode format this package was compiled with.
+    /// `None` for packages compiled before these were recorded.
+    #[serde(default)]
+    pub version_stamp: Option<VersionStamp>,
+    /// The source files of the package at the time of compilation. `None` for packages compiled
+    /// before these were recorded.
+    #[serde(default)]
+    pub source_files: Option<Vec<SourceFileStamp>>,
+}
+
+impl CompiledPackageInfo {
+    /// This info as recorded in `BuildInfo.yaml`, with the local paths it holds remapped by
+    /// `--remap-path-prefix`
+    fn remapped(&self) -> Self {
+        let mut info = self.clone();
+        let flags = &self.build_flags;
+        info.build_flags.install_dir = flags.install_dir.as_deref().map(|p| flags.remap_path(p));
+        info.build_flags.out_dir = flags.out_dir.as_deref().map(|p| flags.remap_path(p));
+        info.build_flags.lock_file = flags.lock_file.as_deref().map(|p| flags.remap_path(p));
+        info
+    }
+}
+
+/// The versions a package was compiled with, recorded in its `BuildInfo.yaml` so that the tools
+/// reusing its artifacts can tell whether they are able to read them
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct VersionStamp {
+    /// The version of the Move tooling, e.g. `0.1.0`
+    pub move_tooling_version: String,
+    /// The bytecode format version the compiled units were serialized with
+    pub bytecode_version: u32,
+}
+
+/// How artifacts compiled with one `VersionStamp` can be used by the tooling of another
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum VersionCompatibility {
+    /// Compiled with the same versions
+    Same,
+    /// Compiled by another minor or patch version of the tooling, with the same bytecode format
+    MinorMismatch,
+    /// Compiled by another major version of the tooling, or with another bytecode format
+    MajorMismatch,
+}
+
+impl VersionStamp {
+    /// The versions packages are compiled with under `build_config`
+    pub fn current(build_config: &BuildConfig) -> Self {
+        let (major, minor, patch) = build_config
+            .move_tooling_version
+            .unwrap_or_else(move_tooling_version);
+        Self {
+            move_tooling_version: format!("{}.{}.{}", major, minor, patch),
+            bytecode_version: get_bytecode_version_from_env().unwrap_or(VERSION_MAX),
+        }
+    }
+
+    /// The stamp of the package compiled into `package_build_dir` (`build/<package>`), or `None`
+    /// if there is no package there or it was compiled before stamps were recorded. Only the stamp
+    /// is deserialized, so this is cheap enough to run before every use of the artifacts.
+    pub fn read(package_build_dir: &Path) -> Result<Option<Self>> {
+        #[derive(Deserialize)]
+        struct StampedBuildInfo {
+            compiled_package_info: StampedPackageInfo,
+        }
+        #[derive(Deserialize)]
+        struct StampedPackageInfo {
+            #[serde(default)]
+            version_stamp: Option<VersionStamp>,
+        }
+
+        let build_info_path = package_build_dir.join(CompiledPackageLayout::BuildInfo.path());
+        let contents = match std::fs::read(&build_info_path) {
+            Ok(contents) => contents,
+            Err(err) if err.kind() == std::io::ErrorKind::NotFound => return Ok(None),
+            Err(err) => {
+                return Err(err)
+                    .with_context(|| format!("Unable to read '{}'", build_info_path.display()))
+            }
+        };
+        let info: StampedBuildInfo = serde_yaml::from_slice(&contents)
+            .with_context(|| format!("Error parsing '{}'", build_info_path.display()))?;
+        Ok(info.compiled_package_info.version_stamp)
+    }
+
+    /// How the artifacts compiled with this stamp can be used by the tooling stamping `current`.
+    /// A tooling version that cannot be parsed counts as another major version.
+    pub fn compatibility(&self, current: &VersionStamp) -> VersionCompatibility {
+        if self == current {
+            return VersionCompatibility::Same;
+        }
+        let major_version = |stamp: &VersionStamp| {
+            parse_version_str(&stamp.move_tooling_version)
+                .ok()
+                .map(|(major, _, _)| major)
+        };
+        match (major_version(self), major_version(current)) {
+            (Some(major), Some(current_major))
+                if major == current_major && self.bytecode_version == current.bytecode_version =>
+            {
+                VersionCompatibility::MinorMismatch
+            }
+            _ => VersionCompatibility::MajorMismatch,
+        }
+    }
+}
+
+impl fmt::Display for VersionStamp {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "Move {} (bytecode version {})",
+            self.move_tooling_version, self.bytecode_version
+        )
+    }
+}
+
+/// A source file of a package as it was when the package was compiled, recorded in its
+/// `BuildInfo.yaml` so that the tools reusing its artifacts can tell whether they are stale
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SourceFileStamp {
+    /// Path of the file relative to the package root
+    pub path: PathBuf,
+    /// Size of the file in bytes
+    pub size: u64,
+    /// Modification time of the file, in nanoseconds since the Unix epoch
+    pub modified: u64,
+    /// SHA-256 digest of the contents of the file, in hex
+    pub sha256: String,
+}
+
+/// How a source file of a package changed since the package was compiled
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum SourceFileChange {
+    Modified(PathBuf),
+    Added(PathBuf),
+    Removed(PathBuf),
+}
+
+impl fmt::Display for SourceFileChange {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Modified(path) => write!(f, "modified: {}", path.display()),
+            Self::Added(path) => write!(f, "added: {}", path.display()),
+            Self::Removed(path) => write!(f, "removed: {}", path.display()),
+        }
+    }
+}
+
+impl SourceFileStamp {
+    /// The stamps of the source files of the package at `package_path` compiled with `config`: its
+    /// manifest and its Move files, sorted by path
+    pub fn record(package_path: &Path, config: &BuildConfig) -> Result<Vec<Self>> {
+        package_source_files(package_path, config)?
+            .into_iter()
+            .map(|path| {
+                let full_path = package_path.join(&path);
+                let (size, modified) = size_and_modified(&full_path)?;
+                let contents = std::fs::read(&full_path)
+                    .with_context(|| format!("Unable to read '{}'", full_path.display()))?;
+                Ok(Self {
+                    path,
+                    size,
+                    modified,
+                    sha256: format!("{:x}", Sha256::digest(&contents)),
+                })
+            })
+            .collect()
+    }
+
+    /// The changes to the source files of the package at `package_path` since it was compiled into
+    /// `package_build_dir` (`build/<package>`), or `None` if there is no package there or it was
+    /// compiled before source files were recorded. Only the files whose size or modification time
+    /// changed are hashed, to tell the files that were touched from the files that were edited.
+    pub fn changes_since_build(
+        package_path: &Path,
+        package_build_dir: &Path,
+    ) -> Result<Option<Vec<SourceFileChange>>> {
+        #[derive(Deserialize)]
+        struct StampedBuildInfo {
+            compiled_package_info: StampedPackageInfo,
+        }
+        #[derive(Deserialize)]
+        struct StampedPackageInfo {
+            build_flags: BuildConfig,
+            #[serde(default)]
+            source_files: Option<Vec<SourceFileStamp>>,
+        }
+
+        let build_info_path = package_build_dir.join(CompiledPackageLayout::BuildInfo.path());
+        let contents = match std::fs::read(&build_info_path) {
+            Ok(contents) => contents,
+            Err(err) if err.kind() == std::io::ErrorKind::NotFound => return Ok(None),
+            Err(err) => {
+                return Err(err)
+                    .with_context(|| format!("Unable to read '{}'", build_info_path.display()))
+            }
+        };
+        let info: StampedBuildInfo = serde_yaml::from_slice(&contents)
+            .with_context(|| format!("Error parsing '{}'", build_info_path.display()))?;
+        let (recorded, build_flags) = match info.compiled_package_info {
+            StampedPackageInfo {
+                source_files: Some(recorded),
+                build_flags,
+            } => (recorded, build_flags),
+            _ => return Ok(None),
+        };
+
+        let mut recorded: BTreeMap<_, _> = recorded
+            .into_iter()
+            .map(|stamp| (stamp.path.clone(), stamp))
+            .collect();
+        let mut changes = vec![];
+        for path in package_source_files(package_path, &build_flags)? {
+            let stamp = match recorded.remove(&path) {
+                Some(stamp) => stamp,
+                None => {
+                    changes.push(SourceFileChange::Added(path));
+                    continue;
+                }
+            };
+            let full_path = package_path.join(&path);
+            if size_and_modified(&full_path)? == (stamp.size, stamp.modified) {
+                continue;
+            }
+            let contents = std::fs::read(&full_path)
+                .with_context(|| format!("Unable to read '{}'", full_path.display()))?;
+            if format!("{:x}", Sha256::digest(&contents)) != stamp.sha256 {
+                changes.push(SourceFileChange::Modified(path));
+            }
+        }
+        changes.extend(recorded.into_keys().map(SourceFileChange::Removed));
+        Ok(Some(changes))
+    }
+}
+
+/// The source files of the package at `package_path` compiled with `config`, relative to
+/// `package_path` and sorted
+fn package_source_files(package_path: &Path, config: &BuildConfig) -> Result<Vec<PathBuf>> {
+    let layouts = ResolvingPackage::source_layouts_for_config(config);
+    let mut files: Vec<_> = package_move_files(package_path, &layouts)?
+        .into_iter()
+        .map(|path| {
+            path.strip_prefix(package_path)
+                .map(Path::to_path_buf)
+                .unwrap_or(path)
+        })
+        .collect();
+    files.push(SourcePackageLayout::Manifest.path().to_path_buf());
+    files.sort();
+    Ok(files)
+}
+
+/// The size of the file at `path`, and its modification time in nanoseconds since the Unix epoch
+fn size_and_modified(path: &Path) -> Result<(u64, u64)> {
+    let metadata =
+        std::fs::metadata(path).with_context(|| format!("Unable to read '{}'", path.display()))?;
+    let modified = metadata
+        .modified()
+        .ok()
+        .and_then(|time| time.duration_since(std::time::UNIX_EPOCH).ok())
+        .map_or(0, |since_epoch| since_epoch.as_nanos() as u64);
+    Ok((metadata.len(), modified))
 }
 
 /// Represents a compiled package in memory.
@@ -81,6 +362,8 @@ pub struct CompiledPackage {
     pub root_compiled_units: Vec<CompiledUnitWithSource>,
     /// The output compiled bytecode for dependencies
     pub deps_compiled_units: Vec<(PackageName, CompiledUnitWithSource)>,
+    /// Where the dependencies were fetched from and the digests of their contents, sorted by name
+    pub dependency_sources: Vec<DependencySource>,
 
     // Optional artifacts from compilation
     //
@@ -100,6 +383,31 @@ pub struct OnDiskPackage {
     pub compiled_package_info: CompiledPackageInfo,
     /// Dependency names for this package.
     pub dependencies: Vec<PackageName>,
+    /// Where the dependencies were fetched from and the digests of their contents. Empty for
+    /// packages built before these were recorded.
+    #[serde(default)]
+    pub dependency_sources: Vec<DependencySource>,
+}
+
+/// The files a build wrote under `build/<package>`, saved to `artifact-manifest.json` there so that
+/// tools packaging the build do not have to search for them
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ArtifactManifest {
+    pub package: PackageName,
+    /// Sorted by path
+    pub files: Vec<Artifact>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Artifact {
+    /// Path of the file relative to `build/<package>`, with `/` separators
+    pub path: String,
+    /// What the file holds: `bytecode`, `source_map`, `source`, `doc`, `abi` or `build_info`
+    pub kind: String,
+    /// Size of the file in bytes
+    pub size: u64,
+    /// SHA-256 digest of the contents of the file, in hex
+    pub sha256: String,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -198,6 +506,7 @@ impl OnDiskCompiledPackage {
             compiled_package_info: self.package.compiled_package_info.clone(),
             root_compiled_units,
             deps_compiled_units,
+            dependency_sources: self.package.dependency_sources.clone(),
             compiled_docs,
             compiled_abis,
         })
@@ -212,13 +521,19 @@ impl OnDiskCompiledPackage {
         let bytecode_path = Path::new(bytecode_path_str);
         let path_to_file = CompiledPackageLayout::path_to_file_after_category(bytecode_path);
         let bytecode_bytes = std::fs::read(&bytecode_path)?;
-        let source_map = source_map_from_file(
-            &self
-                .root_path
-                .join(CompiledPackageLayout::SourceMaps.path())
-                .join(&path_to_file)
-                .with_extension(SOURCE_MAP_EXTENSION),
-        )?;
+        let source_map_path = self
+            .root_path
+            .join(CompiledPackageLayout::SourceMaps.path())
+            .join(&path_to_file)
+            .with_extension(SOURCE_MAP_EXTENSION);
+        // packages built with `--debug-info none` have no source maps on disk
+        let load_source_map = |view| {
+            if source_map_path.exists() {
+                source_map_from_file(&source_map_path)
+            } else {
+                SourceMap::dummy_from_view(&view, Spanned::unsafe_no_loc(()).loc)
+            }
+        };
         let source_path = self
             .root_path
             .join(CompiledPackageLayout::Sources.path())
@@ -234,6 +549,7 @@ impl OnDiskCompiledPackage {
         );
         match CompiledScript::deserialize(&bytecode_bytes) {
             Ok(script) => {
+                let source_map = load_source_map(BinaryIndexedView::Script(&script))?;
                 let name = FileName::from(
                     bytecode_path
                         .file_stem()
@@ -251,6 +567,7 @@ impl OnDiskCompiledPackage {
             }
             Err(_) => {
                 let module = CompiledModule::deserialize(&bytecode_bytes)?;
+                let source_map = load_source_map(BinaryIndexedView::Module(&module))?;
                 let (address_bytes, module_name) = {
                     let id = module.self_id();
                     let parsed_addr = NumericalAddress::new(
@@ -280,6 +597,66 @@ impl OnDiskCompiledPackage {
         std::fs::write(path_to_save, bytes).map_err(|err| err.into())
     }
 
+    /// List the files of the package on disk in its artifact manifest. The manifest is written to
+    /// a temporary file first and then renamed, so readers never see a partial manifest.
+    fn save_artifact_manifest(&self) -> Result<()> {
+        let manifest_path = self
+            .root_path
+            .join(CompiledPackageLayout::ArtifactManifest.path());
+        let tmp_path = manifest_path.with_extension("json.tmp");
+        let mut files = vec![];
+        for entry in walkdir::WalkDir::new(&self.root_path) {
+            let entry = entry?;
+            if !entry.file_type().is_file()
+                || entry.path() == manifest_path
+                || entry.path() == tmp_path
+            {
+                continue;
+            }
+            let relative_path = entry.path().strip_prefix(&self.root_path)?;
+            let contents = std::fs::read(entry.path())?;
+            files.push(Artifact {
+                path: relative_path
+                    .components()
+                    .map(|component| component.as_os_str().to_string_lossy())
+                    .collect::<Vec<_>>()
+                    .join("/"),
+                kind: Self::artifact_kind(relative_path).to_string(),
+                size: contents.len() as u64,
+                sha256: format!("{:x}", Sha256::digest(&contents)),
+            });
+        }
+        files.sort_by(|a, b| a.path.cmp(&b.path));
+        let manifest = ArtifactManifest {
+            package: self.package.compiled_package_info.package_name,
+            files,
+        };
+        std::fs::write(&tmp_path, serde_json::to_string_pretty(&manifest)?)?;
+        std::fs::rename(&tmp_path, &manifest_path)?;
+        Ok(())
+    }
+
+    fn artifact_kind(relative_path: &Path) -> &'static str {
+        let category = relative_path.components().next().map(|c| c.as_os_str());
+        let is = |layout: CompiledPackageLayout| category == Some(layout.path().as_os_str());
+        if is(CompiledPackageLayout::CompiledModules) || is(CompiledPackageLayout::CompiledScripts)
+        {
+            "bytecode"
+        } else if is(CompiledPackageLayout::SourceMaps) {
+            "source_map"
+        } else if is(CompiledPackageLayout::Sources) {
+            "source"
+        } else if is(CompiledPackageLayout::CompiledDocs) {
+            "doc"
+        } else if is(CompiledPackageLayout::CompiledABIs) {
+            "abi"
+        } else if is(CompiledPackageLayout::BuildInfo) {
+            "build_info"
+        } else {
+            "other"
+        }
+    }
+
     #[allow(unused)]
     pub(crate) fn has_source_changed_since_last_compile(
         &self,
@@ -351,13 +728,15 @@ impl OnDiskCompiledPackage {
                 .serialize(get_bytecode_version_from_env())
                 .as_slice(),
         )?;
-        self.save_under(
-            CompiledPackageLayout::SourceMaps
-                .path()
-                .join(&file_path)
-                .with_extension(SOURCE_MAP_EXTENSION),
-            compiled_unit.unit.serialize_source_map().as_slice(),
-        )?;
+        if self.package.compiled_package_info.build_flags.debug_info() != DebugInfo::None {
+            self.save_under(
+                CompiledPackageLayout::SourceMaps
+                    .path()
+                    .join(&file_path)
+                    .with_extension(SOURCE_MAP_EXTENSION),
+                compiled_unit.unit.serialize_source_map().as_slice(),
+            )?;
+        }
         self.save_under(
             CompiledPackageLayout::Sources
                 .path()
@@ -457,6 +836,18 @@ impl CompiledPackage {
             })
     }
 
+    /// Fail with an explanation if the package was built without source maps, which `what` needs
+    pub fn ensure_source_maps(&self, what: &str) -> Result<()> {
+        ensure!(
+            self.compiled_package_info.build_flags.debug_info() != DebugInfo::None,
+            "{} needs source maps, which are not emitted for package {} as it is built with \
+             `--debug-info none`. Rebuild it with `--debug-info line-only` or `--debug-info full`",
+            what,
+            self.compiled_package_info.package_name
+        );
+        Ok(())
+    }
+
     pub fn scripts(&self) -> impl Iterator<Item = &CompiledUnitWithSource> {
         self.root_compiled_units
             .iter()
@@ -520,7 +911,11 @@ impl CompiledPackage {
             )?;
         }
         let root_package_name = resolved_package.source_package.package.name;
+        let _span = info_span!("build", package = %root_package_name).entered();
         writeln!(w, "{} {}", "BUILDING".bold().green(), root_package_name)?;
+        if resolution_graph.build_options.test_mode {
+            check_test_module_names(&resolved_package.package_path)?;
+        }
 
         // gather source/dep files with their address mappings
         let (sources_package_paths, deps_package_paths) = make_source_and_deps_for_compiler(
@@ -528,17 +923,21 @@ impl CompiledPackage {
             &resolved_package,
             transitive_dependencies,
         )?;
+        let remaps = &resolution_graph.build_options.remap_path_prefix;
         let flags = if resolution_graph.build_options.test_mode {
             Flags::testing()
         } else {
             Flags::empty()
-        };
+        }
+        .set_path_prefix_remaps(remaps.clone())
+        .set_root_package(root_package_name);
         // invoke the compiler
         let mut paths = deps_package_paths.clone();
         paths.push(sources_package_paths.clone());
 
         let compiler = Compiler::from_package_paths(paths, vec![]).set_flags(flags);
         let (file_map, all_compiled_units) = compiler_driver(compiler)?;
+        let debug_info = resolution_graph.build_options.debug_info();
         let mut root_compiled_units = vec![];
         let mut deps_compiled_units = vec![];
         for annot_unit in all_compiled_units {
@@ -548,7 +947,7 @@ impl CompiledPackage {
                 compiled_unit::CompiledUnitEnum::Script(s) => s.named_script.package_name.unwrap(),
             };
             let unit = CompiledUnitWithSource {
-                unit: annot_unit.into_compiled_unit(),
+                unit: strip_debug_info(annot_unit.into_compiled_unit(), debug_info)?,
                 source_path,
             };
             if package_name == root_package_name {
@@ -563,10 +962,11 @@ impl CompiledPackage {
         if resolution_graph.build_options.generate_docs
             || resolution_graph.build_options.generate_abis
         {
-            let model = run_model_builder_with_options(
+            let model = run_model_builder_with_options_and_compilation_flags(
                 vec![sources_package_paths],
                 deps_package_paths,
                 ModelBuilderOptions::default(),
+                Flags::verification().set_path_prefix_remaps(remaps.clone()),
             )?;
 
             if resolution_graph.build_options.generate_docs {
@@ -575,7 +975,7 @@ impl CompiledPackage {
                     &model,
                     &resolved_package.package_path,
                     &immediate_dependencies,
-                    &resolution_graph.build_options.install_dir,
+                    &resolution_graph.build_options,
                 ));
             }
 
@@ -594,9 +994,15 @@ impl CompiledPackage {
                 address_alias_instantiation: resolved_package.resolution_table,
                 source_digest: Some(resolved_package.source_digest),
                 build_flags: resolution_graph.build_options.clone(),
+                version_stamp: Some(VersionStamp::current(&resolution_graph.build_options)),
+                source_files: Some(SourceFileStamp::record(
+                    &resolved_package.package_path,
+                    &resolution_graph.build_options,
+                )?),
             },
             root_compiled_units,
             deps_compiled_units,
+            dependency_sources: DependencySource::for_root_package(resolution_graph),
             compiled_docs,
             compiled_abis,
         };
@@ -665,10 +1071,11 @@ impl CompiledPackage {
         self.check_filepaths_ok()?;
         assert!(under_path.ends_with(CompiledPackageLayout::Root.path()));
         let root_package = self.compiled_package_info.package_name;
+        let build_flags = &self.compiled_package_info.build_flags;
         let on_disk_package = OnDiskCompiledPackage {
             root_path: under_path.join(root_package.as_str()),
             package: OnDiskPackage {
-                compiled_package_info: self.compiled_package_info.clone(),
+                compiled_package_info: self.compiled_package_info.remapped(),
                 dependencies: self
                     .deps_compiled_units
                     .iter()
@@ -676,6 +1083,14 @@ impl CompiledPackage {
                     .collect::<BTreeSet<_>>()
                     .into_iter()
                     .collect(),
+                dependency_sources: self
+                    .dependency_sources
+                    .iter()
+                    .map(|source| DependencySource {
+                        path: build_flags.remap_path(&source.path),
+                        ..source.clone()
+                    })
+                    .collect(),
             },
         };
 
@@ -722,6 +1137,7 @@ impl CompiledPackage {
             CompiledPackageLayout::BuildInfo.path(),
             serde_yaml::to_string(&on_disk_package.package)?.as_bytes(),
         )?;
+        on_disk_package.save_artifact_manifest()?;
 
         Ok(on_disk_package)
     }
@@ -759,7 +1175,7 @@ impl CompiledPackage {
         model: &GlobalEnv,
         package_root: &Path,
         deps: &[PackageName],
-        install_dir: &Option<PathBuf>,
+        build_options: &BuildConfig,
     ) -> Vec<(String, String)> {
         let root_doc_templates = find_filenames(
             &[package_root
@@ -769,7 +1185,7 @@ impl CompiledPackage {
             |path| extension_equals(path, "md"),
         )
         .unwrap_or_else(|_| vec![]);
-        let root_for_docs = if let Some(install_dir) = install_dir {
+        let root_for_docs = if let Some(install_dir) = &build_options.install_dir {
             install_dir.join(CompiledPackageLayout::Root.path())
         } else {
             CompiledPackageLayout::Root.path().to_path_buf()
@@ -801,6 +1217,7 @@ impl CompiledPackage {
             root_doc_templates,
             compile_relative_to_output_dir: true,
             references_file,
+            remap_path_prefix: build_options.remap_path_prefix.clone(),
             ..DocgenOptions::default()
         };
         let docgen = Docgen::new(model, &doc_options);
@@ -887,3 +1304,101 @@ pub(crate) fn make_source_and_deps_for_compiler(
     };
     Ok((source_package_paths, deps_package_paths))
 }
+
+/// Fail if a module under `tests/` of the package at `package_path` has the name of a module under
+/// `sources/`, as the modules under `tests/` are compiled along with the sources in test mode
+fn check_test_module_names(package_path: &Path) -> Result<()> {
+    let declared_modules = |layout: SourcePackageLayout| -> Result<BTreeMap<String, String>> {
+        let mut modules = BTreeMap::new();
+        for file in package_move_files(package_path, &[layout])? {
+            let relative_path = file
+                .strip_prefix(package_path)
+                .unwrap_or(&file)
+                .to_string_lossy()
+                .to_string();
+            for module in declared_module_names(&std::fs::read_to_string(&file)?) {
+                modules.insert(module, relative_path.clone());
+            }
+        }
+        Ok(modules)
+    };
+    let source_modules = declared_modules(SourcePackageLayout::Sources)?;
+    for (module, test_file) in declared_modules(SourcePackageLayout::Tests)? {
+        if let Some(source_file) = source_modules.get(&module) {
+            bail!(
+                "Module '{}' in '{}' has the same name as the module in '{}'. The modules under \
+                 '{}' are compiled along with those under '{}' in test mode, so their names must \
+                 differ",
+                module,
+                test_file,
+                source_file,
+                SourcePackageLayout::Tests.location_str(),
+                SourcePackageLayout::Sources.location_str(),
+            )
+        }
+    }
+    Ok(())
+}
+
+/// The modules declared in the Move source `contents`, as `<address>::<name>` with the address as
+/// it is written in the source. Lexing stops at the first error, which the compiler reports.
+pub(crate) fn declared_module_names(contents: &str) -> Vec<String> {
+    let mut lexer = Lexer::new(contents, FileHash::new(contents));
+    let mut tokens = vec![];
+    while lexer.advance().is_ok() && lexer.peek() != Tok::EOF {
+        tokens.push((lexer.peek(), lexer.content()));
+    }
+
+    let mut modules = vec![];
+    // the address of the enclosing `address <address> { ... }` block
+    let mut block_address = None;
+    for (i, (tok, content)) in tokens.iter().enumerate() {
+        let next = |offset: usize| tokens.get(i + offset).copied();
+        match (tok, next(1), next(2), next(3)) {
+            (
+                Tok::Identifier,
+                Some((Tok::NumValue | Tok::Identifier, address)),
+                Some((Tok::LBrace, _)),
+                _,
+            ) if *content == "address" => block_address = Some(address),
+            (
+                Tok::Module,
+                Some((Tok::NumValue | Tok::Identifier, address)),
+                Some((Tok::ColonColon, _)),
+                Some((Tok::Identifier, name)),
+            ) => modules.push(format!("{}::{}", address, name)),
+            (Tok::Module, Some((Tok::Identifier, name)), _, _) => {
+                if let Some(address) = block_address {
+                    modules.push(format!("{}::{}", address, name))
+                }
+            }
+            _ => (),
+        }
+    }
+    modules
+}
+
+/// Remove the debug information `debug_info` leaves out from the source map of `unit`. Without
+/// source maps, the unit keeps a generated one so that it can still be disassembled.
+fn strip_debug_info(mut unit: CompiledUnit, debug_info: DebugInfo) -> Result<CompiledUnit> {
+    let source_map = match debug_info {
+        DebugInfo::Full => return Ok(unit),
+        DebugInfo::LineOnly => {
+            let mut source_map = unit.source_map().clone();
+            source_map.strip_names();
+            source_map
+        }
+        DebugInfo::None => {
+            let view = match &unit {
+                CompiledUnit::Module(named) => BinaryIndexedView::Module(&named.module),
+                CompiledUnit::Script(named) => BinaryIndexedView::Script(&named.script),
+            };
+            SourceMap::dummy_from_view(&view, Spanned::unsafe_no_loc(()).loc)?
+        }
+    };
+    match &mut unit {
+        CompiledUnit::Module(named) => named.source_map = source_map,
+        CompiledUnit::Script(named) => named.source_map = source_map,
+    }
+    Ok(unit)
+}
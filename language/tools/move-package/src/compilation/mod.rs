@@ -2,6 +2,7 @@
 // Copyright (c) The Move Contributors
 // SPDX-License-Identifier: Apache-2.0
 
+pub mod analysis;
 pub mod build_plan;
 pub mod compiled_package;
 pub mod model_builder;
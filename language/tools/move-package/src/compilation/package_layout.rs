@@ -7,6 +7,7 @@ use std::path::{Path, PathBuf};
 #[derive(Debug, Clone)]
 pub enum CompiledPackageLayout {
     BuildInfo,
+    ArtifactManifest,
     Root,
     Dependencies,
     Sources,
@@ -15,12 +16,18 @@ pub enum CompiledPackageLayout {
     CompiledScripts,
     CompiledDocs,
     CompiledABIs,
+    Coverage,
+    /// The ABI saved by `move package diff-abi --save`, kept across builds like `Coverage`
+    SavedAbi,
+    /// The failing inputs found by `move package fuzz`, kept across builds like `Coverage`
+    FuzzFailures,
 }
 
 impl CompiledPackageLayout {
     pub fn path(&self) -> &Path {
         let path = match self {
             Self::BuildInfo => "BuildInfo.yaml",
+            Self::ArtifactManifest => "artifact-manifest.json",
             Self::Root => "build",
             Self::Dependencies => "dependencies",
             Self::Sources => "sources",
@@ -29,6 +36,9 @@ impl CompiledPackageLayout {
             Self::CompiledScripts => "bytecode_scripts",
             Self::CompiledDocs => "docs",
             Self::CompiledABIs => "abis",
+            Self::Coverage => "coverage",
+            Self::SavedAbi => "abi",
+            Self::FuzzFailures => "fuzz-failures",
         };
         Path::new(path)
     }
@@ -9,8 +9,12 @@ pub mod package_hooks;
 pub mod resolution;
 pub mod source_package;
 
+pub use package_lock::PackageLock;
+
 use anyhow::{bail, Result};
 use clap::*;
+use move_command_line_common::files::{read_utf8_file, remap_path_prefix, PathPrefixRemap};
+use move_compiler::{compiled_unit::AnnotatedCompiledUnit, diagnostics::FilesSourceText, Compiler};
 use move_core_types::account_address::AccountAddress;
 use move_model::model::GlobalEnv;
 use serde::{Deserialize, Serialize};
@@ -26,9 +30,14 @@ use crate::{
     compilation::{
         build_plan::BuildPlan, compiled_package::CompiledPackage, model_builder::ModelBuilder,
     },
-    package_lock::PackageLock,
-    resolution::resolution_graph::{ResolutionGraph, ResolvedGraph},
-    source_package::manifest_parser,
+    resolution::{
+        lockfile::{update_lockfile, LockfileVersion},
+        resolution_graph::{ResolutionGraph, ResolvedGraph},
+    },
+    source_package::{
+        manifest_parser,
+        parsed_manifest::{SourceManifest, Version},
+    },
 };
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
@@ -90,6 +99,48 @@ impl Architecture {
     }
 }
 
+/// How much debug information is emitted with the build artifacts of a package
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
+pub enum DebugInfo {
+    /// Source maps with the locations and names of functions, parameters and locals
+    Full,
+
+    /// Source maps with locations only. Parameters, locals and type parameters get generated names.
+    LineOnly,
+
+    /// No source maps
+    None,
+}
+
+impl fmt::Display for DebugInfo {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Full => write!(f, "full"),
+
+            Self::LineOnly => write!(f, "line-only"),
+
+            Self::None => write!(f, "none"),
+        }
+    }
+}
+
+impl DebugInfo {
+    fn try_parse_from_str(s: &str) -> Result<Self> {
+        Ok(match s {
+            "full" => Self::Full,
+
+            "line-only" => Self::LineOnly,
+
+            "none" => Self::None,
+
+            _ => bail!(
+                "Unrecognized debug info {} -- only \"full\", \"line-only\" and \"none\" are supported",
+                s
+            ),
+        })
+    }
+}
+
 #[derive(Debug, Parser, Clone, Serialize, Deserialize, Eq, PartialEq, PartialOrd, Default)]
 #[clap(author, version, about)]
 pub struct BuildConfig {
@@ -116,6 +167,17 @@ pub struct BuildConfig {
     #[clap(long = "install-dir", parse(from_os_str), global = true)]
     pub install_dir: Option<PathBuf>,
 
+    /// Install the build of each package into `<out-dir>/<package name>` instead, leaving the
+    /// package directory untouched, e.g. to build read-only checkouts. Packages can share an out
+    /// dir.
+    #[clap(
+        long = "out-dir",
+        parse(from_os_str),
+        global = true,
+        conflicts_with = "install-dir"
+    )]
+    pub out_dir: Option<PathBuf>,
+
     /// Force recompilation of all packages
     #[clap(name = "force-recompilation", long = "force", global = true)]
     pub force_recompilation: bool,
@@ -130,6 +192,74 @@ pub struct BuildConfig {
     /// Only fetch dependency repos to MOVE_HOME
     #[clap(long = "fetch-deps-only", global = true)]
     pub fetch_deps_only: bool,
+
+    /// Do not initialize the git submodules of dependency repos
+    #[clap(long = "no-submodules", global = true)]
+    pub no_submodules: bool,
+
+    /// The lockfile to record resolved dependencies in. No lockfile is used if unset. The lockfile
+    /// is only updated outside of dev mode, as it records the regular dependencies.
+    #[clap(skip)]
+    pub lock_file: Option<PathBuf>,
+
+    /// Format version of the lockfile to write. Defaults to the latest version.
+    #[clap(long = "lockfile-version", global = true, parse(try_from_str = LockfileVersion::try_parse_from_str))]
+    pub lockfile_version: Option<LockfileVersion>,
+
+    /// Fail instead of updating the lockfile if it is out of date
+    #[clap(long = "locked", global = true)]
+    pub locked: bool,
+
+    /// Lock a git dependency whose locked commit is gone from its repository, e.g. after a force
+    /// push, to the current commit of its `rev` without asking first
+    #[clap(long = "repin-missing", global = true, conflicts_with = "locked")]
+    pub repin_missing: bool,
+
+    /// Do not fetch dependencies over the network: fail if a git dependency has not been
+    /// downloaded to MOVE_HOME yet
+    #[clap(long = "offline", global = true)]
+    pub offline: bool,
+
+    /// Build artifacts to ship rather than for local development. Defaults `--debug-info` to
+    /// `line-only`.
+    #[clap(long = "release", global = true)]
+    pub release: bool,
+
+    /// The debug information emitted with the build artifacts: `full` source maps, `line-only`
+    /// source maps without the names of parameters and locals, or `none`. Defaults to `full`, or
+    /// to `line-only` with `--release`.
+    #[clap(long = "debug-info", global = true, parse(try_from_str = DebugInfo::try_parse_from_str))]
+    pub debug_info: Option<DebugInfo>,
+
+    /// The version of the Move tooling checked against the `move-version` requirements of
+    /// packages. Defaults to `move_tooling_version()`.
+    #[clap(skip)]
+    pub move_tooling_version: Option<Version>,
+
+    /// Show paths starting with <from> as starting with <to> in diagnostics, documentation and
+    /// build information, e.g. `--remap-path-prefix /Users/me/work/pkg=/pkg`. May be repeated;
+    /// the last matching mapping applies. The mappings are not recorded in the build information,
+    /// as they hold the local paths they hide.
+    #[clap(
+        long = "remap-path-prefix",
+        value_name = "FROM=TO",
+        global = true,
+        parse(try_from_str = PathPrefixRemap::parse)
+    )]
+    #[serde(skip)]
+    pub remap_path_prefix: Vec<PathPrefixRemap>,
+
+    /// Only warn about package names that are not Move identifiers, or that differ from the name
+    /// of another package in the build only in case, for legacy packages
+    #[clap(long = "allow-nonstandard-names", global = true)]
+    pub allow_nonstandard_names: bool,
+}
+
+/// The version of this Move tooling, which the `move-version` requirements of packages are checked
+/// against
+pub fn move_tooling_version() -> Version {
+    manifest_parser::parse_version_str(env!("CARGO_PKG_VERSION"))
+        .expect("Crate version must be of the form <u64>.<u64>.<u64>")
 }
 
 #[derive(Debug, Clone, Eq, PartialEq, PartialOrd)]
@@ -142,6 +272,52 @@ pub struct ModelConfig {
 }
 
 impl BuildConfig {
+    /// The debug information to emit with the build artifacts
+    pub fn debug_info(&self) -> DebugInfo {
+        self.debug_info.unwrap_or(if self.release {
+            DebugInfo::LineOnly
+        } else {
+            DebugInfo::Full
+        })
+    }
+
+    /// `path` as shown in diagnostics and artifacts, with its prefix remapped by
+    /// `--remap-path-prefix`
+    pub fn remap_path(&self, path: &Path) -> PathBuf {
+        remap_path_prefix(&self.remap_path_prefix, path)
+    }
+
+    /// The installation directory of the build of the package named `package_name`: under the
+    /// `--out-dir`, if it is set, or the `--install-dir`
+    pub fn install_dir_for_package(&self, package_name: &str) -> Option<PathBuf> {
+        match &self.out_dir {
+            Some(out_dir) => Some(out_dir.join(package_name)),
+            None => self.install_dir.clone(),
+        }
+    }
+
+    /// The mappings of `--remap-path-prefix`, preceded by those of the `[build]` section of the
+    /// manifest of the package at `root_path`. Relative `from` prefixes in the manifest are
+    /// relative to `root_path`, like the paths of the sources of the package.
+    pub fn remap_path_prefix_for_package(
+        &self,
+        root_manifest: &SourceManifest,
+        root_path: &Path,
+    ) -> Vec<PathPrefixRemap> {
+        let manifest_remaps = root_manifest
+            .build
+            .iter()
+            .flat_map(|info| &info.remap_path_prefix)
+            .map(|remap| PathPrefixRemap {
+                from: root_path.join(&remap.from),
+                to: remap.to.clone(),
+            });
+        // the last matching mapping applies, so those of the command line take precedence
+        manifest_remaps
+            .chain(self.remap_path_prefix.iter().cloned())
+            .collect()
+    }
+
     /// Compile the package at `path` or the containing Move package. Exit process on warning or
     /// failure.
     pub fn compile_package<W: Write>(self, path: &Path, writer: &mut W) -> Result<CompiledPackage> {
@@ -166,6 +342,24 @@ impl BuildConfig {
         ret
     }
 
+    /// Compile the package at `path` or the containing Move package, letting `compiler_driver`
+    /// run the compiler. The driver decides how warnings and errors are reported.
+    pub fn compile_package_with_driver<W: Write>(
+        self,
+        path: &Path,
+        writer: &mut W,
+        compiler_driver: impl FnMut(
+            Compiler,
+        )
+            -> anyhow::Result<(FilesSourceText, Vec<AnnotatedCompiledUnit>)>,
+    ) -> Result<CompiledPackage> {
+        let resolved_graph = self.resolution_graph_for_package(path)?;
+        let mutx = PackageLock::lock();
+        let ret = BuildPlan::create(resolved_graph)?.compile_with_driver(writer, compiler_driver);
+        mutx.unlock();
+        ret
+    }
+
     #[cfg(feature = "evm-backend")]
     pub fn compile_package_evm<W: Write>(self, path: &Path, writer: &mut W) -> Result<()> {
         let resolved_graph = self.resolution_graph_for_package(path)?;
@@ -217,13 +411,19 @@ impl BuildConfig {
         // possibly be set by a different process in parallel.
         let manifest = manifest_parser::parse_source_manifest(toml_manifest)?;
         let resolution_graph = ResolutionGraph::new(manifest, path, self)?;
-        let ret = resolution_graph.resolve();
+        let ret = resolution_graph.resolve().and_then(|graph| {
+            let options = &graph.build_options;
+            if let (Some(lock_file), false) = (&options.lock_file, options.dev_mode) {
+                update_lockfile(&graph, lock_file, options.lockfile_version, options.locked)?;
+            }
+            Ok(graph)
+        });
         mutx.unlock();
         ret
     }
 
     fn parse_toml_manifest(&self, path: PathBuf) -> Result<toml::Value> {
-        let manifest_string = std::fs::read_to_string(path)?;
+        let manifest_string = read_utf8_file(&path)?;
         manifest_parser::parse_move_manifest_string(manifest_string)
     }
 }
@@ -0,0 +1,159 @@
+// Copyright (c) The Move Contributors
+// SPDX-License-Identifier: Apache-2.0
+
+//! Dependencies distributed as `.tar.gz` archives over HTTP(S), or read from a registry directory
+//! through `file://` URLs. The archive is downloaded once into `MOVE_HOME`, checked against the
+//! SHA-256 digest the manifest requires, and extracted next to its final location, so that an
+//! interrupted or rejected download is never mistaken for a valid one by a later build.
+
+use crate::{
+    resolution::resolution_graph::partial_download_path,
+    source_package::parsed_manifest::{ArchiveInfo, PackageName},
+};
+use anyhow::{bail, Context, Result};
+use flate2::read::GzDecoder;
+use move_command_line_common::{env::move_home_write_error, interrupt};
+use reqwest::{blocking::Client, redirect::Policy};
+use sha2::{Digest, Sha256};
+use std::{fs, path::Path};
+use tracing::{info, info_span};
+
+/// The file recording the digest of the archive a dependency was extracted from, which also marks
+/// the directory as a downloaded dependency
+pub const ARCHIVE_DIGEST_FILE: &str = ".move-archive-sha256";
+
+/// How many redirects are followed when downloading an archive
+pub const MAX_ARCHIVE_REDIRECTS: usize = 10;
+
+/// Download the archive in `archive_info`, check its digest and extract it to `download_to`. If
+/// the archive holds a single top-level directory, as archives made from a release usually do,
+/// its contents are extracted in its place.
+pub fn download_archive_dependency(
+    dep_name: PackageName,
+    archive_info: &ArchiveInfo,
+) -> Result<()> {
+    let _span = info_span!("fetch", package = %dep_name, url = %archive_info.url).entered();
+    let bytes = match local_archive_path(archive_info.url.as_str()) {
+        Some(path) => {
+            info!("copying archive dependency");
+            fs::read(path).with_context(|| {
+                format!(
+                    "Failed to read the archive of package '{}' at {}",
+                    dep_name,
+                    path.display()
+                )
+            })?
+        }
+        None => fetch_archive(dep_name, archive_info)?,
+    };
+    let sha256 = format!("{:x}", Sha256::digest(&bytes));
+    if sha256 != archive_info.sha256.as_str() {
+        bail!(
+            "The archive of package '{}' downloaded from {} has the SHA-256 digest {}, but the \
+             manifest requires {}",
+            dep_name,
+            archive_info.url,
+            sha256,
+            archive_info.sha256
+        )
+    }
+
+    let partial_path = partial_download_path(&archive_info.download_to);
+    if partial_path.exists() {
+        // left behind by a process that was killed before it could clean up
+        fs::remove_dir_all(&partial_path)
+            .map_err(|err| move_home_write_error("remove", &partial_path, err))?;
+    }
+    let _remove_on_interrupt = {
+        let partial_path = partial_path.clone();
+        interrupt::on_interrupt(move || {
+            let _ = fs::remove_dir_all(partial_path);
+        })
+    };
+    fs::create_dir_all(&partial_path).map_err(|err| {
+        move_home_write_error("create the download directory", &partial_path, err)
+    })?;
+    tar::Archive::new(GzDecoder::new(&bytes[..]))
+        .unpack(&partial_path)
+        .with_context(|| {
+            format!(
+                "Failed to extract the archive of package '{}' downloaded from {}",
+                dep_name, archive_info.url
+            )
+        })?;
+    hoist_single_directory(&partial_path)?;
+    let digest_file = partial_path.join(ARCHIVE_DIGEST_FILE);
+    fs::write(&digest_file, &sha256)
+        .map_err(|err| move_home_write_error("write", &digest_file, err))?;
+    fs::rename(&partial_path, &archive_info.download_to)
+        .map_err(|err| move_home_write_error("move into place", &archive_info.download_to, err))?;
+    Ok(())
+}
+
+/// The path of the archive at `url` if it is a `file://` URL, as for the archives of a registry
+/// directory, which are read from disk rather than downloaded
+pub fn local_archive_path(url: &str) -> Option<&Path> {
+    url.strip_prefix("file://").map(Path::new)
+}
+
+/// Download the archive in `archive_info`
+fn fetch_archive(dep_name: PackageName, archive_info: &ArchiveInfo) -> Result<Vec<u8>> {
+    info!("downloading archive dependency");
+    let client = Client::builder()
+        .redirect(Policy::limited(MAX_ARCHIVE_REDIRECTS))
+        .build()?;
+    let response = client
+        .get(archive_info.url.as_str())
+        .send()
+        .map_err(|err| {
+            if err.is_redirect() {
+                anyhow::anyhow!(
+                    "Failed to download the archive of package '{}' from {}: more than {} \
+                     redirects",
+                    dep_name,
+                    archive_info.url,
+                    MAX_ARCHIVE_REDIRECTS
+                )
+            } else {
+                anyhow::Error::new(err).context(format!(
+                    "Failed to download the archive of package '{}' from {}",
+                    dep_name, archive_info.url
+                ))
+            }
+        })?;
+    if !response.status().is_success() {
+        bail!(
+            "Failed to download the archive of package '{}' from {}: the server answered {}",
+            dep_name,
+            archive_info.url,
+            response.status()
+        )
+    }
+    let bytes = response.bytes().with_context(|| {
+        format!(
+            "Failed to download the archive of package '{}' from {}",
+            dep_name, archive_info.url
+        )
+    })?;
+    Ok(bytes.to_vec())
+}
+
+/// Move the contents of the only entry of `dir` into `dir` if that entry is a directory
+fn hoist_single_directory(dir: &Path) -> Result<()> {
+    let entries = fs::read_dir(dir)?.collect::<std::io::Result<Vec<_>>>()?;
+    let top = match entries.as_slice() {
+        [entry] if entry.file_type()?.is_dir() => entry.path(),
+        _ => return Ok(()),
+    };
+    // renamed first, in case it holds an entry with its own name
+    let hoisted = dir.join(".move-archive-root");
+    fs::rename(&top, &hoisted).map_err(|err| move_home_write_error("move", &top, err))?;
+    for entry in fs::read_dir(&hoisted)? {
+        let entry = entry?;
+        let target = dir.join(entry.file_name());
+        fs::rename(entry.path(), &target)
+            .map_err(|err| move_home_write_error("move", &target, err))?;
+    }
+    fs::remove_dir(&hoisted).map_err(|err| move_home_write_error("remove", &hoisted, err))?;
+    Ok(())
+}
@@ -2,14 +2,19 @@
 // Copyright (c) The Move Contributors
 // SPDX-License-Identifier: Apache-2.0
 
-use anyhow::Result;
+use anyhow::{bail, Result};
 use move_command_line_common::files::MOVE_EXTENSION;
 use sha2::{Digest, Sha256};
 use std::path::{Path, PathBuf};
 
-use crate::source_package::{layout::SourcePackageLayout, parsed_manifest::PackageDigest};
+use crate::{
+    resolution::git::git,
+    source_package::{layout::SourcePackageLayout, parsed_manifest::PackageDigest},
+};
 
-pub fn compute_digest(paths: &[PathBuf]) -> Result<PackageDigest> {
+/// Compute the digest of the Move sources and manifests found under `paths`, together with the
+/// `submodule_revisions` the package was checked out at (see `submodule_revisions`).
+pub fn compute_digest(paths: &[PathBuf], submodule_revisions: &[String]) -> Result<PackageDigest> {
     let mut hashed_files = Vec::new();
     let mut hash = |path: &Path| {
         let contents = std::fs::read(path)?;
@@ -47,6 +52,48 @@ pub fn compute_digest(paths: &[PathBuf]) -> Result<PackageDigest> {
     for file_hash in hashed_files.into_iter() {
         hasher.update(file_hash.as_bytes());
     }
+    for revision in submodule_revisions {
+        hasher.update(revision.as_bytes());
+    }
 
     Ok(PackageDigest::from(format!("{:X}", hasher.finalize())))
 }
+
+/// The revisions of the git submodules that are checked out inside `package_path`, as
+/// `<path>@<commit>` entries sorted by path. Empty if the package is not part of a git repository
+/// that has submodules.
+pub fn submodule_revisions(package_path: &Path) -> Result<Vec<String>> {
+    let package_path = package_path.canonicalize()?;
+    let repo_root = match package_path
+        .ancestors()
+        .find(|dir| dir.join(".git").exists())
+    {
+        Some(repo_root) if repo_root.join(".gitmodules").is_file() => repo_root,
+        _ => return Ok(vec![]),
+    };
+    let output = git()?
+        .command()
+        .args(["-C", &repo_root.to_string_lossy()])
+        .args(["submodule", "status", "--recursive"])
+        .output()?;
+    if !output.status.success() {
+        bail!(
+            "Unable to determine the submodule revisions of '{}': {}",
+            repo_root.display(),
+            String::from_utf8_lossy(&output.stderr).trim()
+        )
+    }
+
+    // each line is a status character followed by `<commit> <path>` and an optional description
+    let mut revisions = vec![];
+    for line in String::from_utf8_lossy(&output.stdout).lines() {
+        let mut fields = line[1..].split(' ');
+        if let (Some(commit), Some(path)) = (fields.next(), fields.next()) {
+            if repo_root.join(path).starts_with(&package_path) {
+                revisions.push(format!("{}@{}", path, commit));
+            }
+        }
+    }
+    revisions.sort();
+    Ok(revisions)
+}
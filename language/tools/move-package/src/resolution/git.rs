@@ -0,0 +1,150 @@
+// Copyright (c) The Move Contributors
+// SPDX-License-Identifier: Apache-2.0
+
+//! The git installation used to download dependencies and publish packages. It is looked up on
+//! `PATH` and its version checked once per process, so that a missing or outdated git is reported
+//! with an actionable error rather than the error of the process that failed to spawn.
+
+use anyhow::{anyhow, bail, Context, Result};
+use once_cell::sync::OnceCell;
+use std::{
+    env,
+    ffi::OsStr,
+    fmt,
+    path::{Path, PathBuf},
+    process::Command,
+};
+
+pub type GitVersion = (u64, u64, u64);
+
+/// The oldest git that can download dependencies and publish packages
+pub const MIN_GIT_VERSION: GitVersion = (2, 0, 0);
+
+/// The oldest git providing `git sparse-checkout`, used to only check out the subdirectory of a
+/// dependency. Older versions check out the whole repository.
+pub const SPARSE_CHECKOUT_GIT_VERSION: GitVersion = (2, 25, 0);
+
+const INSTALL_GIT_URL: &str = "https://git-scm.com/downloads";
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Git {
+    pub path: PathBuf,
+    pub version: GitVersion,
+}
+
+static GIT: OnceCell<Result<Git, String>> = OnceCell::new();
+
+/// The git found on `PATH`, detected on the first call. Fails if git is missing or older than
+/// `MIN_GIT_VERSION`.
+pub fn git() -> Result<&'static Git> {
+    GIT.get_or_init(|| {
+        Git::detect(env::var_os("PATH").as_deref()).map_err(|err| format!("{:#}", err))
+    })
+    .as_ref()
+    .map_err(|err| anyhow!("{}", err))
+}
+
+impl Git {
+    /// Find git in the directories of `path_var`, formatted as the `PATH` environment variable, and
+    /// check its version
+    pub fn detect(path_var: Option<&OsStr>) -> Result<Self> {
+        let path = match find_on_path(path_var) {
+            Some(path) => path,
+            None => bail!(
+                "git >= {} is required to download dependencies and publish packages, but it was \
+                 not found on PATH. Install it from {}",
+                DisplayVersion(MIN_GIT_VERSION),
+                INSTALL_GIT_URL
+            ),
+        };
+        let output = Command::new(&path)
+            .arg("--version")
+            .output()
+            .with_context(|| format!("Unable to run {}", path.display()))?;
+        let stdout = String::from_utf8_lossy(&output.stdout);
+        let version = match parse_version(&stdout) {
+            Some(version) if output.status.success() => version,
+            _ => bail!(
+                "Unable to read the version of git at {}: `git --version` printed '{}'",
+                path.display(),
+                stdout.trim()
+            ),
+        };
+        let git = Self { path, version };
+        git.require(
+            MIN_GIT_VERSION,
+            "to download dependencies and publish packages",
+        )?;
+        Ok(git)
+    }
+
+    /// Fail unless this git is at least `version`, which is needed for `purpose`
+    pub fn require(&self, version: GitVersion, purpose: &str) -> Result<()> {
+        if self.version < version {
+            bail!(
+                "git >= {} required {}; found {} at {}. Upgrade it from {}",
+                DisplayVersion(version),
+                purpose,
+                DisplayVersion(self.version),
+                self.path.display(),
+                INSTALL_GIT_URL
+            )
+        }
+        Ok(())
+    }
+
+    pub fn supports_sparse_checkout(&self) -> bool {
+        self.version >= SPARSE_CHECKOUT_GIT_VERSION
+    }
+
+    /// A command running this git
+    pub fn command(&self) -> Command {
+        Command::new(&self.path)
+    }
+}
+
+/// The first `git` executable in the directories of `path_var`
+fn find_on_path(path_var: Option<&OsStr>) -> Option<PathBuf> {
+    let executable = format!("git{}", env::consts::EXE_SUFFIX);
+    env::split_paths(path_var?)
+        .map(|dir| dir.join(&executable))
+        .find(|path| is_executable(path))
+}
+
+#[cfg(unix)]
+fn is_executable(path: &Path) -> bool {
+    use std::os::unix::fs::PermissionsExt;
+    path.metadata().map_or(false, |metadata| {
+        metadata.is_file() && metadata.permissions().mode() & 0o111 != 0
+    })
+}
+
+#[cfg(not(unix))]
+fn is_executable(path: &Path) -> bool {
+    path.is_file()
+}
+
+/// The version printed by `git --version`, e.g. `git version 2.37.1 (Apple Git-137.1)` or
+/// `git version 2.41.0.windows.1`. A missing patch version is read as 0.
+pub fn parse_version(output: &str) -> Option<GitVersion> {
+    let version = output.trim().strip_prefix("git version ")?;
+    let mut numbers = version
+        .split(|c: char| !c.is_ascii_digit())
+        .take_while(|number| !number.is_empty())
+        .map(|number| number.parse::<u64>());
+    let major = numbers.next()?.ok()?;
+    let minor = numbers.next()?.ok()?;
+    let patch = numbers.next().unwrap_or(Ok(0)).ok()?;
+    Some((major, minor, patch))
+}
+
+struct DisplayVersion(GitVersion);
+
+impl fmt::Display for DisplayVersion {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.0 {
+            (major, minor, 0) => write!(f, "{}.{}", major, minor),
+            (major, minor, patch) => write!(f, "{}.{}.{}", major, minor, patch),
+        }
+    }
+}
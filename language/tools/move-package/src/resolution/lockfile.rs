@@ -0,0 +1,357 @@
+// Copyright (c) The Move Contributors
+// SPDX-License-Identifier: Apache-2.0
+
+//! The lockfile (`Move.lock`) records the dependencies a package was resolved to.
+//!
+//! Version 1 lists each dependency with the source it was fetched from. Version 2 adds a header
+//! holding the format version, and records for each dependency the digest of its contents and the
+//! package that depends on it (its resolution parent), as well as the commit a git dependency was
+//! checked out at. Both versions are read, but only the
+//! version requested by the build config is written.
+
+use crate::{
+    resolution::resolution_graph::{head_commit, ResolvedGraph},
+    source_package::{
+        manifest_parser::{archive_download_dir, git_download_dir, node_download_dir},
+        parsed_manifest::Dependency,
+    },
+};
+use anyhow::{bail, Context, Result};
+use move_symbol_pool::Symbol;
+use petgraph::Direction;
+use serde::{Deserialize, Serialize};
+use std::{
+    fmt, fs,
+    path::{Path, PathBuf},
+};
+
+const LOCKFILE_HEADER: &str = "# This file is generated by Move. Do not edit it by hand.\n\n";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
+pub enum LockfileVersion {
+    V1,
+    V2,
+}
+
+impl LockfileVersion {
+    /// The version written unless another one is requested
+    pub const LATEST: Self = Self::V2;
+
+    pub fn try_parse_from_str(s: &str) -> Result<Self> {
+        Ok(match s {
+            "1" => Self::V1,
+            "2" => Self::V2,
+            _ => bail!("Unsupported lockfile version {} -- expected 1 or 2", s),
+        })
+    }
+}
+
+impl fmt::Display for LockfileVersion {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::V1 => write!(f, "1"),
+            Self::V2 => write!(f, "2"),
+        }
+    }
+}
+
+/// The contents of a lockfile
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Lockfile {
+    pub version: LockfileVersion,
+    /// The resolved dependencies, sorted by name
+    pub dependencies: Vec<LockedDependency>,
+}
+
+/// A dependency recorded in a lockfile. Exactly one of `local`, `git`, `node` and `url` is set.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct LockedDependency {
+    pub name: String,
+    /// The package that declares this dependency (version 2 only)
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub parent: Option<String>,
+    /// Path to the dependency, relative to its parent
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub local: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub git: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub rev: Option<String>,
+    /// The commit `rev` resolved to when a git dependency was downloaded, which it is checked out
+    /// at when downloaded again, even if a branch moved on since (version 2 only)
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub commit: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub subdir: Option<String>,
+    /// The node a custom dependency is downloaded from, and the address it is published at
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub node: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub address: Option<String>,
+    /// The URL an archive dependency is downloaded from, and the SHA-256 digest of the archive
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub url: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub sha256: Option<String>,
+    /// The digest of the dependency's sources and manifest (version 2 only)
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub digest: Option<String>,
+}
+
+/// A dependency compiled into a package: where it was fetched from and the digest of its contents,
+/// as recorded in the lockfile, along with the directory its sources were read from
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct DependencySource {
+    #[serde(flatten)]
+    pub locked: LockedDependency,
+    /// The local directory of the dependency, which is inside `MOVE_HOME` for downloaded
+    /// dependencies
+    pub path: PathBuf,
+}
+
+impl LockedDependency {
+    /// The directory under `move_home` this dependency is downloaded to, or `None` for a local
+    /// dependency
+    pub fn download_dir(&self, move_home: &str) -> Option<PathBuf> {
+        match (&self.git, &self.rev, &self.node, &self.address) {
+            (Some(git), Some(rev), _, _) => Some(git_download_dir(move_home, git, rev)),
+            (_, _, Some(node), Some(address)) => {
+                Some(node_download_dir(move_home, node, address, &self.name))
+            }
+            _ => match (&self.url, &self.sha256) {
+                (Some(url), Some(sha256)) => Some(archive_download_dir(move_home, url, sha256)),
+                _ => None,
+            },
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize)]
+struct LockfileV1 {
+    #[serde(default)]
+    dependency: Vec<LockedDependency>,
+}
+
+#[derive(Serialize, Deserialize)]
+struct LockfileV2 {
+    lockfile: Header,
+    #[serde(default)]
+    dependency: Vec<LockedDependency>,
+}
+
+#[derive(Serialize, Deserialize)]
+struct Header {
+    version: u64,
+}
+
+impl Lockfile {
+    /// The lockfile recording the dependencies of the root package of `graph`
+    pub fn from_resolution_graph(graph: &ResolvedGraph, version: LockfileVersion) -> Self {
+        let root_name = graph.root_package.package.name;
+        let mut dependencies = vec![];
+        for (name, package) in &graph.package_table {
+            if *name == root_name {
+                continue;
+            }
+            // the root package if it declares the dependency directly, otherwise the first
+            // dependent by name
+            let mut parents = graph
+                .graph
+                .neighbors_directed(*name, Direction::Incoming)
+                .collect::<Vec<_>>();
+            parents.sort_by_key(|parent| (*parent != root_name, parent.to_string()));
+            let parent = match parents.first() {
+                Some(parent) => *parent,
+                None => continue,
+            };
+            let parent_manifest = &graph.package_table[&parent].source_package;
+            let dep = match parent_manifest
+                .dependencies
+                .get(name)
+                .or_else(|| parent_manifest.dev_dependencies.get(name))
+            {
+                Some(dep) => dep,
+                None => continue,
+            };
+            let mut locked = LockedDependency::from_dependency(name.to_string(), dep);
+            if version >= LockfileVersion::V2 {
+                locked.parent = Some(parent.to_string());
+                locked.digest = Some(package.source_digest.to_string());
+                locked.commit = dep
+                    .git_info
+                    .as_ref()
+                    .and_then(|git_info| head_commit(&git_info.download_to));
+            }
+            dependencies.push(locked);
+        }
+        Self {
+            version,
+            dependencies,
+        }
+    }
+
+    /// Read the lockfile at `path`, or return `None` if there is none
+    pub fn read(path: &Path) -> Result<Option<Self>> {
+        let contents = match fs::read_to_string(path) {
+            Ok(contents) => contents,
+            Err(err) if err.kind() == std::io::ErrorKind::NotFound => return Ok(None),
+            Err(err) => return Err(err.into()),
+        };
+        Self::parse(&contents)
+            .with_context(|| format!("Unable to parse lockfile '{}'", path.display()))
+            .map(Some)
+    }
+
+    pub fn parse(contents: &str) -> Result<Self> {
+        let value: toml::Value = toml::from_str(contents)?;
+        let lockfile = match value.get("lockfile") {
+            // version 1 lockfiles have no header
+            None => {
+                let LockfileV1 { dependency } = value.try_into()?;
+                Self {
+                    version: LockfileVersion::V1,
+                    dependencies: dependency,
+                }
+            }
+            Some(_) => {
+                let LockfileV2 {
+                    lockfile,
+                    dependency,
+                } = value.try_into()?;
+                if lockfile.version != 2 {
+                    bail!(
+                        "Lockfile version {} is not supported by this version of Move",
+                        lockfile.version
+                    )
+                }
+                Self {
+                    version: LockfileVersion::V2,
+                    dependencies: dependency,
+                }
+            }
+        };
+        Ok(lockfile)
+    }
+
+    pub fn render(&self) -> Result<String> {
+        let dependency = self.dependencies.clone();
+        let body = match self.version {
+            LockfileVersion::V1 => toml::to_string(&LockfileV1 { dependency })?,
+            LockfileVersion::V2 => toml::to_string(&LockfileV2 {
+                lockfile: Header { version: 2 },
+                dependency,
+            })?,
+        };
+        Ok(format!("{}{}", LOCKFILE_HEADER, body))
+    }
+}
+
+impl DependencySource {
+    /// The sources of the dependencies compiled into the root package of `graph`, sorted by name.
+    /// The digests are the ones a version 2 lockfile records and verifies.
+    pub fn for_root_package(graph: &ResolvedGraph) -> Vec<Self> {
+        let root = &graph.package_table[&graph.root_package.package.name];
+        let compiled = root.transitive_dependencies(graph);
+        Lockfile::from_resolution_graph(graph, LockfileVersion::V2)
+            .dependencies
+            .into_iter()
+            .filter_map(|locked| {
+                let name = Symbol::from(locked.name.as_str());
+                compiled.contains(&name).then(|| Self {
+                    path: graph.package_table[&name].package_path.clone(),
+                    locked,
+                })
+            })
+            .collect()
+    }
+}
+
+impl LockedDependency {
+    fn from_dependency(name: String, dep: &Dependency) -> Self {
+        let mut locked = Self {
+            name,
+            parent: None,
+            local: None,
+            git: None,
+            rev: None,
+            commit: None,
+            subdir: None,
+            node: None,
+            address: None,
+            url: None,
+            sha256: None,
+            digest: None,
+        };
+        if let Some(git_info) = &dep.git_info {
+            locked.git = Some(git_info.git_url.to_string());
+            locked.rev = Some(git_info.git_rev.to_string());
+            if !git_info.subdir.as_os_str().is_empty() {
+                locked.subdir = Some(path_to_string(&git_info.subdir));
+            }
+        } else if let Some(node_info) = &dep.node_info {
+            locked.node = Some(node_info.node_url.to_string());
+            locked.address = Some(node_info.package_address.to_string());
+        } else if let Some(archive_info) = &dep.archive_info {
+            locked.url = Some(archive_info.url.to_string());
+            locked.sha256 = Some(archive_info.sha256.to_string());
+            if !archive_info.subdir.as_os_str().is_empty() {
+                locked.subdir = Some(path_to_string(&archive_info.subdir));
+            }
+        } else {
+            locked.local = Some(path_to_string(&dep.local));
+        }
+        locked
+    }
+}
+
+/// Paths are written with forward slashes so that lockfiles are the same on every platform
+fn path_to_string(path: &Path) -> String {
+    path.to_string_lossy().replace('\\', "/")
+}
+
+/// Bring the lockfile at `path` up to date with `graph`, writing it in `version` (or the latest
+/// version if `None`). If `locked` is set, the lockfile is left untouched, and it is an error if
+/// it does not match `graph` in the version it was written in.
+pub fn update_lockfile(
+    graph: &ResolvedGraph,
+    path: &Path,
+    version: Option<LockfileVersion>,
+    locked: bool,
+) -> Result<()> {
+    let existing = Lockfile::read(path)?;
+    if locked {
+        let up_to_date = match &existing {
+            Some(existing) => *existing == Lockfile::from_resolution_graph(graph, existing.version),
+            // there is nothing to lock for a package without dependencies
+            None => graph.package_table.len() <= 1,
+        };
+        if !up_to_date {
+            bail!(
+                "The lockfile '{}' needs to be updated, but --locked was passed to prevent this",
+                path.display()
+            )
+        }
+        return Ok(());
+    }
+
+    let version = version.unwrap_or(LockfileVersion::LATEST);
+    let lockfile = Lockfile::from_resolution_graph(graph, version);
+    match &existing {
+        None if lockfile.dependencies.is_empty() => return Ok(()),
+        Some(existing) if existing.version < version => eprintln!(
+            "Note: migrating lockfile '{}' from version {} to version {}",
+            path.display(),
+            existing.version,
+            version
+        ),
+        _ => (),
+    }
+    let contents = lockfile.render()?;
+    if fs::read_to_string(path).ok().as_ref() != Some(&contents) {
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        fs::write(path, contents)?;
+    }
+    Ok(())
+}
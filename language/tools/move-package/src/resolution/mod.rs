@@ -2,5 +2,9 @@
 // Copyright (c) The Move Contributors
 // SPDX-License-Identifier: Apache-2.0
 
+pub mod archive;
 mod digest;
+pub mod git;
+pub mod lockfile;
+pub mod registry;
 pub mod resolution_graph;
@@ -0,0 +1,184 @@
+// Copyright (c) The Move Contributors
+// SPDX-License-Identifier: Apache-2.0
+
+//! Registries kept in a directory instead of behind a server, for environments without network
+//! access. The directory holds an `index.json` listing the versions of its packages, and each
+//! version under `<name>/<version>/`, either as the sources of the package or as a `.tar.gz`
+//! archive named by the index. Dependencies of the form
+//! `{ version = "1.2.0", registry = "local" }` are looked up in it: archives become archive
+//! dependencies, extracted into `MOVE_HOME` and recorded in the lockfile like any other, and
+//! sources become local dependencies.
+
+use crate::source_package::{manifest_parser::parse_version_str, parsed_manifest::Version};
+use anyhow::{bail, Context, Result};
+use move_command_line_common::files::read_utf8_file;
+use once_cell::sync::Lazy;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::{
+    fs,
+    path::{Path, PathBuf},
+    sync::Mutex,
+};
+
+/// The name dependencies use to refer to the registry directory, in `registry = "local"`
+pub const LOCAL_REGISTRY_NAME: &str = "local";
+
+/// The file of a registry directory listing its packages
+pub const REGISTRY_INDEX_FILE: &str = "index.json";
+
+static REGISTRY_PATH: Lazy<Mutex<Option<PathBuf>>> = Lazy::new(|| Mutex::new(None));
+
+/// Resolve the dependencies on the local registry in the directory at `path` from now on
+pub fn set_registry_path(path: Option<PathBuf>) {
+    *REGISTRY_PATH.lock().unwrap() = path;
+}
+
+/// The directory of the local registry, if one is set
+pub fn registry_path() -> Option<PathBuf> {
+    REGISTRY_PATH.lock().unwrap().clone()
+}
+
+/// A version of a package, as listed in the index of a registry directory. The index has the format
+/// of the search responses of the registry, so that it can also be given to `move package add
+/// --index`; the fields this module does not use are kept as they are when it is rewritten.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct IndexEntry {
+    pub name: String,
+    pub version: String,
+    /// The archive of this version, relative to the registry directory. The version is held as
+    /// sources under `<name>/<version>/` if there is none.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub archive: Option<String>,
+    /// The SHA-256 digest of the archive
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub sha256: Option<String>,
+    #[serde(flatten)]
+    pub other: serde_json::Map<String, serde_json::Value>,
+}
+
+/// Where a version of a package of a registry directory is taken from
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum RegistrySource {
+    /// A `.tar.gz` archive, with its SHA-256 digest
+    Archive { path: PathBuf, sha256: String },
+    /// The directory holding the sources of the package
+    Sources(PathBuf),
+}
+
+/// The versions listed in the index of the registry directory at `dir`, none if it has no index yet
+pub fn read_index(dir: &Path) -> Result<Vec<IndexEntry>> {
+    let index_path = dir.join(REGISTRY_INDEX_FILE);
+    if !index_path.exists() {
+        return Ok(vec![]);
+    }
+    serde_json::from_str(&read_utf8_file(&index_path)?)
+        .with_context(|| format!("Error parsing the index '{}'", index_path.display()))
+}
+
+/// Replace the index of the registry directory at `dir` with `entries`. The index is written next
+/// to its final location and then renamed, so that readers never see a partly written index.
+pub fn write_index(dir: &Path, entries: &[IndexEntry]) -> Result<()> {
+    let index_path = dir.join(REGISTRY_INDEX_FILE);
+    let mut staged = tempfile::NamedTempFile::new_in(dir)
+        .with_context(|| format!("Unable to write to the registry '{}'", dir.display()))?;
+    serde_json::to_writer_pretty(&mut staged, entries)?;
+    staged
+        .persist(&index_path)
+        .with_context(|| format!("Unable to write '{}'", index_path.display()))?;
+    Ok(())
+}
+
+/// Where version `version` of the package `name` of the registry directory at `dir` is taken from.
+/// Package names are compared regardless of case, as in the registry.
+pub fn find_package(dir: &Path, name: &str, version: &Version) -> Result<RegistrySource> {
+    let entries = read_index(dir)?;
+    let entry = entries
+        .iter()
+        .find(|entry| {
+            entry.name.eq_ignore_ascii_case(name)
+                && parse_version_str(&entry.version).ok().as_ref() == Some(version)
+        })
+        .with_context(|| {
+            let listed = entries
+                .iter()
+                .filter(|entry| entry.name.eq_ignore_ascii_case(name))
+                .map(|entry| entry.version.as_str())
+                .collect::<Vec<_>>();
+            format!(
+                "The registry '{}' has no version {}.{}.{} of package '{}'{}",
+                dir.display(),
+                version.0,
+                version.1,
+                version.2,
+                name,
+                if listed.is_empty() {
+                    String::new()
+                } else {
+                    format!(", only {}", listed.join(", "))
+                }
+            )
+        })?;
+    match (&entry.archive, &entry.sha256) {
+        (Some(archive), Some(sha256)) => Ok(RegistrySource::Archive {
+            path: dir.join(archive),
+            sha256: sha256.clone(),
+        }),
+        (Some(archive), None) => bail!(
+            "The registry '{}' lists the archive '{}' of package '{}' without its SHA-256 digest",
+            dir.display(),
+            archive,
+            name
+        ),
+        (None, _) => Ok(RegistrySource::Sources(
+            dir.join(&entry.name).join(&entry.version),
+        )),
+    }
+}
+
+/// Add version `version` of the package `name` to the registry directory at `dir` from the
+/// `.tar.gz` archive at `archive`: the archive is copied to `<name>/<version>/` and then listed in
+/// the index. Fails if the registry already has that version. Returns the path of the copy.
+pub fn publish_archive(
+    dir: &Path,
+    name: &str,
+    version: &str,
+    archive: &Path,
+    other: serde_json::Map<String, serde_json::Value>,
+) -> Result<PathBuf> {
+    let mut entries = read_index(dir)?;
+    if entries
+        .iter()
+        .any(|entry| entry.name.eq_ignore_ascii_case(name) && entry.version == version)
+    {
+        bail!(
+            "The registry '{}' already has version {} of package '{}'",
+            dir.display(),
+            version,
+            name
+        )
+    }
+    let bytes =
+        fs::read(archive).with_context(|| format!("Unable to read '{}'", archive.display()))?;
+    let version_dir = dir.join(name).join(version);
+    fs::create_dir_all(&version_dir)
+        .with_context(|| format!("Unable to create '{}'", version_dir.display()))?;
+    let relative_path = format!("{}/{}/{}-{}.tar.gz", name, version, name, version);
+    let archive_path = dir.join(&relative_path);
+    let mut staged = tempfile::NamedTempFile::new_in(&version_dir)
+        .with_context(|| format!("Unable to write to '{}'", version_dir.display()))?;
+    std::io::Write::write_all(&mut staged, &bytes)?;
+    staged
+        .persist(&archive_path)
+        .with_context(|| format!("Unable to write '{}'", archive_path.display()))?;
+
+    entries.push(IndexEntry {
+        name: name.to_string(),
+        version: version.to_string(),
+        archive: Some(relative_path),
+        sha256: Some(format!("{:x}", Sha256::digest(&bytes))),
+        other,
+    });
+    write_index(dir, &entries)?;
+    Ok(archive_path)
+}
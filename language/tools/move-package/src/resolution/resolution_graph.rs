@@ -3,20 +3,33 @@
 // SPDX-License-Identifier: Apache-2.0
 
 use crate::{
-    package_hooks,
-    resolution::digest::compute_digest,
+    move_tooling_version, package_hooks,
+    resolution::{
+        archive::{download_archive_dependency, local_archive_path},
+        digest::{compute_digest, submodule_revisions},
+        git::git,
+        lockfile::Lockfile,
+    },
     source_package::{
-        layout::SourcePackageLayout,
-        manifest_parser::{parse_move_manifest_string, parse_source_manifest},
+        layout::{package_move_files, SourcePackageLayout},
+        manifest_parser::{
+            check_package_name, is_conventional_package_name, parse_move_manifest_string,
+            parse_source_manifest,
+        },
         parsed_manifest::{
-            Dependencies, Dependency, FileName, NamedAddress, PackageDigest, PackageName,
-            SourceManifest, SubstOrRename,
+            format_version, Dependencies, Dependency, FileName, GitInfo, NamedAddress,
+            PackageDigest, PackageName, SourceManifest, SubstOrRename,
         },
     },
     BuildConfig,
 };
 use anyhow::{bail, Context, Result};
-use move_command_line_common::files::{find_move_filenames, FileHash};
+use colored::Colorize;
+use move_command_line_common::{
+    env::move_home_write_error,
+    files::{read_utf8_file, FileHash, InvalidUtf8File},
+    interrupt, json_errors,
+};
 use move_core_types::account_address::AccountAddress;
 use move_symbol_pool::Symbol;
 use petgraph::{algo, graphmap::DiGraphMap, Outgoing};
@@ -24,11 +37,16 @@ use ptree::{print_tree, TreeBuilder};
 use std::{
     cell::RefCell,
     collections::{BTreeMap, BTreeSet},
-    fs,
+    fmt, fs,
+    io::{self, Write},
     path::{Path, PathBuf},
-    process::Command,
+    process::Stdio,
     rc::Rc,
+    sync::{Arc, Mutex},
+    thread,
+    time::Duration,
 };
+use tracing::{debug, info, info_span, warn};
 
 pub type ResolvedTable = ResolutionTable<AccountAddress>;
 pub type ResolvedPackage = ResolutionPackage<AccountAddress>;
@@ -38,10 +56,16 @@ pub type ResolvedGraph = ResolutionGraph<AccountAddress>;
 pub type Renaming = BTreeMap<NamedAddress, (PackageName, NamedAddress)>;
 pub type GraphIndex = PackageName;
 
+/// Appended to the directory name of a git or archive dependency while it is being downloaded
+pub const PARTIAL_DOWNLOAD_EXTENSION: &str = ".partial";
+
+/// How deeply submodules of a git dependency may be nested inside other submodules
+const MAX_SUBMODULE_DEPTH: usize = 8;
+
 type ResolutionTable<T> = BTreeMap<NamedAddress, T>;
 type ResolvingTable = ResolutionTable<ResolvingNamedAddress>;
 type ResolvingGraph = ResolutionGraph<ResolvingNamedAddress>;
-type ResolvingPackage = ResolutionPackage<ResolvingNamedAddress>;
+pub(crate) type ResolvingPackage = ResolutionPackage<ResolvingNamedAddress>;
 
 #[derive(Debug, Clone)]
 pub struct ResolvingNamedAddress {
@@ -89,6 +113,71 @@ pub struct ResolutionPackage<T> {
     pub source_digest: PackageDigest,
 }
 
+/// A package on the path of dependencies from the root package to the package being resolved
+#[derive(Debug, Clone)]
+struct ResolutionStep {
+    name: PackageName,
+    /// Whether the package is a dev-dependency of the package before it on the path
+    is_dev_dependency: bool,
+}
+
+/// A cycle in the dependencies between packages, reported with the dependency entry closing it
+#[derive(Debug, Clone)]
+pub struct DependencyCycle {
+    /// The packages of the cycle, starting and ending with the same package
+    packages: Vec<ResolutionStep>,
+    /// The manifest declaring the dependency that closes the cycle
+    manifest_path: PathBuf,
+}
+
+impl fmt::Display for DependencyCycle {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let cycle = self
+            .packages
+            .iter()
+            .map(|step| {
+                if step.is_dev_dependency {
+                    format!("{} (dev)", step.name)
+                } else {
+                    step.name.to_string()
+                }
+            })
+            .collect::<Vec<_>>()
+            .join(" -> ");
+        let closing = self.packages.last().expect("a cycle has packages");
+        let section = if closing.is_dev_dependency {
+            "dev-dependencies"
+        } else {
+            "dependencies"
+        };
+        if self.packages.iter().any(|step| step.is_dev_dependency) {
+            write!(
+                f,
+                "Found cycle between packages through dev-dependencies: {}. The cycle is closed \
+                 by the entry '{}' in the [{}] of {}. Dev-dependencies are included in dev and \
+                 test mode, where a package cannot depend on a package depending on it: move the \
+                 code needing the dev-dependency to a separate package",
+                cycle,
+                closing.name,
+                section,
+                self.manifest_path.display()
+            )
+        } else {
+            write!(
+                f,
+                "Found cycle between packages: {}. The cycle is closed by the entry '{}' in the \
+                 [{}] of {}",
+                cycle,
+                closing.name,
+                section,
+                self.manifest_path.display()
+            )
+        }
+    }
+}
+
+impl std::error::Error for DependencyCycle {}
+
 impl ResolvingGraph {
     pub fn new(
         root_package: SourceManifest,
@@ -100,6 +189,19 @@ impl ResolvingGraph {
                 build_options.architecture = info.architecture;
             }
         }
+        build_options.remap_path_prefix =
+            build_options.remap_path_prefix_for_package(&root_package, &root_package_path);
+        build_options.install_dir =
+            build_options.install_dir_for_package(root_package.package.name.as_str());
+        // The lockfile is generated too, so it goes next to the build to keep the package
+        // directory untouched
+        if let (Some(_), Some(install_dir), Some(lock_file)) = (
+            &build_options.out_dir,
+            &build_options.install_dir,
+            &build_options.lock_file,
+        ) {
+            build_options.lock_file = lock_file.file_name().map(|name| install_dir.join(name));
+        }
         let mut resolution_graph = Self {
             root_package_path: root_package_path.clone(),
             build_options,
@@ -108,8 +210,17 @@ impl ResolvingGraph {
             package_table: BTreeMap::new(),
         };
 
+        let root_step = ResolutionStep {
+            name: root_package.package.name,
+            is_dev_dependency: false,
+        };
         resolution_graph
-            .build_resolution_graph(root_package.clone(), root_package_path, true)
+            .build_resolution_graph(root_package.clone(), root_package_path, true, &[root_step])
+            // report a cycle on its own, rather than under the dependencies leading to it
+            .map_err(|err| match err.downcast_ref::<DependencyCycle>() {
+                Some(cycle) => cycle.clone().into(),
+                None => err,
+            })
             .with_context(|| {
                 format!(
                     "Unable to resolve packages for package '{}'",
@@ -189,13 +300,17 @@ impl ResolvingGraph {
         })
     }
 
+    /// Add `package`, the last package of `path`, and its dependencies to the graph
     fn build_resolution_graph(
         &mut self,
         package: SourceManifest,
         package_path: PathBuf,
         is_root_package: bool,
+        path: &[ResolutionStep],
     ) -> Result<()> {
         let package_name = package.package.name;
+        self.check_move_version(&package, is_root_package)?;
+        self.check_package_name(&package, is_root_package)?;
         let package_node_id = match self.package_table.get(&package_name) {
             None => self.get_or_add_node(package_name)?,
             // Same package and we've already resolved it: OK, return early
@@ -231,12 +346,32 @@ impl ResolvingGraph {
             BTreeMap::new()
         };
 
-        for (dep_name, dep) in package
+        for ((dep_name, dep), is_dev_dependency) in package
             .dependencies
             .clone()
             .into_iter()
-            .chain(additional_deps.into_iter())
+            .map(|dep| (dep, false))
+            .chain(additional_deps.into_iter().map(|dep| (dep, true)))
         {
+            if let Some(start) = path.iter().position(|step| step.name == dep_name) {
+                let mut packages = path[start..].to_vec();
+                packages[0].is_dev_dependency = false;
+                packages.push(ResolutionStep {
+                    name: dep_name,
+                    is_dev_dependency,
+                });
+                return Err(DependencyCycle {
+                    packages,
+                    manifest_path: package_path.join(SourcePackageLayout::Manifest.path()),
+                }
+                .into());
+            }
+            let mut dep_path = path.to_vec();
+            dep_path.push(ResolutionStep {
+                name: dep_name,
+                is_dev_dependency,
+            });
+
             let dep_node_id = self.get_or_add_node(dep_name).with_context(|| {
                 format!(
                     "Cycle between packages {} and {} found",
@@ -246,7 +381,7 @@ impl ResolvingGraph {
             self.graph.add_edge(package_node_id, dep_node_id, ());
 
             let (dep_renaming, dep_resolution_table) = self
-                .process_dependency(dep_name, dep, package_path.clone())
+                .process_dependency(dep_name, dep, package_path.clone(), &dep_path)
                 .with_context(|| {
                     format!(
                         "While resolving dependency '{}' in package '{}'",
@@ -294,6 +429,33 @@ impl ResolvingGraph {
         Ok(())
     }
 
+    /// Fail early if the package requires a different version of the Move tooling, rather than
+    /// with errors about language features this version does not understand
+    fn check_move_version(&self, package: &SourceManifest, is_root_package: bool) -> Result<()> {
+        let requirement = match &package.package.move_version {
+            Some(requirement) => requirement,
+            None => return Ok(()),
+        };
+        let current = self
+            .build_options
+            .move_tooling_version
+            .unwrap_or_else(move_tooling_version);
+        if !requirement.matches(&current) {
+            let required_by = if is_root_package {
+                "This package".to_string()
+            } else {
+                format!("Dependency '{}'", package.package.name)
+            };
+            bail!(
+                "{} requires Move tooling {}, you have {}",
+                required_by,
+                requirement,
+                format_version(&current)
+            )
+        }
+        Ok(())
+    }
+
     fn unify_addresses_in_package(
         &mut self,
         package: &SourceManifest,
@@ -387,12 +549,13 @@ impl ResolvingGraph {
         dep_name_in_pkg: PackageName,
         dep: Dependency,
         root_path: PathBuf,
+        path: &[ResolutionStep],
     ) -> Result<(Renaming, ResolvingTable)> {
-        Self::download_and_update_if_remote(dep_name_in_pkg, &dep)?;
+        Self::download_and_update_if_remote(dep_name_in_pkg, &dep, &self.build_options)?;
         let (dep_package, dep_package_dir) =
             Self::parse_package_manifest(&dep, &dep_name_in_pkg, root_path)
                 .with_context(|| format!("While processing dependency '{}'", dep_name_in_pkg))?;
-        self.build_resolution_graph(dep_package.clone(), dep_package_dir, false)
+        self.build_resolution_graph(dep_package.clone(), dep_package_dir, false, path)
             .with_context(|| {
                 format!("Unable to resolve package dependency '{}'", dep_name_in_pkg)
             })?;
@@ -472,6 +635,44 @@ impl ResolvingGraph {
         Ok((renaming, resolution_table))
     }
 
+    /// Fail early on package names that other packages cannot depend on, or that the package
+    /// registry, which ignores case, cannot tell apart from the name of another package of the
+    /// build. With `--allow-nonstandard-names`, these are only warned about.
+    fn check_package_name(&self, package: &SourceManifest, is_root_package: bool) -> Result<()> {
+        let package_name = package.package.name;
+        let result = check_package_name(package_name.as_str()).and_then(|()| {
+            let lowercase_name = package_name.as_str().to_lowercase();
+            match self.graph.nodes().find(|other| {
+                *other != package_name && other.as_str().to_lowercase() == lowercase_name
+            }) {
+                Some(other) => bail!(
+                    "The names of packages '{}' and '{}' only differ in case.",
+                    other,
+                    package_name
+                ),
+                None => Ok(()),
+            }
+        });
+        match result {
+            Err(err) if self.build_options.allow_nonstandard_names => {
+                json_errors::warning(format!("{:#}", err))
+            }
+            Err(err) => bail!(
+                "{:#} Pass --allow-nonstandard-names to build legacy packages anyway",
+                err
+            ),
+            Ok(()) if is_root_package && !is_conventional_package_name(package_name.as_str()) => {
+                json_errors::warning(format!(
+                    "the package name '{}' follows neither the snake_case nor the UpperCamelCase \
+                     convention",
+                    package_name
+                ))
+            }
+            Ok(()) => (),
+        }
+        Ok(())
+    }
+
     fn get_or_add_node(&mut self, package_name: PackageName) -> Result<GraphIndex> {
         if self.graph.contains_node(package_name) {
             // If we encounter a node that we've already added we should check for cycles
@@ -497,12 +698,13 @@ impl ResolvingGraph {
         mut root_path: PathBuf,
     ) -> Result<(SourceManifest, PathBuf)> {
         root_path.push(&dep.local);
-        match fs::read_to_string(&root_path.join(SourcePackageLayout::Manifest.path())) {
+        match read_utf8_file(&root_path.join(SourcePackageLayout::Manifest.path())) {
             Ok(contents) => {
                 let source_package: SourceManifest =
                     parse_move_manifest_string(contents).and_then(parse_source_manifest)?;
                 Ok((source_package, root_path))
             }
+            Err(err) if err.is::<InvalidUtf8File>() => Err(err),
             Err(_) => Err(anyhow::format_err!(
                 "Unable to find package manifest for '{}' at {:?}",
                 dep_name,
@@ -515,6 +717,23 @@ impl ResolvingGraph {
         manifest: &SourceManifest,
         build_options: &BuildConfig,
         root_path: &Path,
+    ) -> Result<()> {
+        Self::download_dependency_repos_on_path(
+            manifest,
+            build_options,
+            root_path,
+            &mut vec![manifest.package.name],
+        )
+    }
+
+    /// Download the dependencies of `manifest`, the manifest of the last package of `path`. A
+    /// dependency already on `path` is not followed, as that would never end: the cycle is
+    /// reported when resolving the packages.
+    fn download_dependency_repos_on_path(
+        manifest: &SourceManifest,
+        build_options: &BuildConfig,
+        root_path: &Path,
+        path: &mut Vec<PackageName>,
     ) -> Result<()> {
         // include dev dependencies if in dev mode
         let empty_deps;
@@ -526,45 +745,66 @@ impl ResolvingGraph {
         };
 
         for (dep_name, dep) in manifest.dependencies.iter().chain(additional_deps.iter()) {
-            Self::download_and_update_if_remote(*dep_name, dep)?;
+            if path.contains(dep_name) {
+                continue;
+            }
+            Self::download_and_update_if_remote(*dep_name, dep, build_options)?;
 
             let (dep_manifest, _) =
                 Self::parse_package_manifest(dep, dep_name, root_path.to_path_buf())
                     .with_context(|| format!("While processing dependency '{}'", *dep_name))?;
             // download dependencies of dependencies
-            Self::download_dependency_repos(&dep_manifest, build_options, root_path)?;
+            path.push(*dep_name);
+            Self::download_dependency_repos_on_path(&dep_manifest, build_options, root_path, path)?;
+            path.pop();
         }
         Ok(())
     }
 
-    fn download_and_update_if_remote(dep_name: PackageName, dep: &Dependency) -> Result<()> {
+    fn download_and_update_if_remote(
+        dep_name: PackageName,
+        dep: &Dependency,
+        build_options: &BuildConfig,
+    ) -> Result<()> {
         if let Some(git_info) = &dep.git_info {
-            if !git_info.download_to.exists() {
-                Command::new("git")
-                    .args([
-                        "clone",
-                        &git_info.git_url,
-                        &git_info.download_to.to_string_lossy(),
-                    ])
-                    .output()
-                    .map_err(|_| {
-                        anyhow::anyhow!("Failed to clone Git repository for package '{}'", dep_name)
-                    })?;
-                Command::new("git")
-                    .args([
-                        "-C",
-                        &git_info.download_to.to_string_lossy(),
-                        "checkout",
-                        &git_info.git_rev,
-                    ])
-                    .output()
-                    .map_err(|_| {
-                        anyhow::anyhow!(
-                            "Failed to checkout Git reference '{}' for package '{}'",
-                            &git_info.git_rev,
-                            dep_name
-                        )
-                    })?;
+            if git_info.download_to.exists() {
+                debug!(
+                    package = %dep_name,
+                    url = %git_info.git_url,
+                    path = %git_info.download_to.display(),
+                    "git dependency already downloaded"
+                );
+            } else if build_options.offline {
+                bail!(
+                    "Dependency '{}' has not been downloaded from {}, and cannot be with --offline. \
+                     Run the command once without --offline to download it",
+                    dep_name,
+                    git_info.git_url
+                )
+            } else {
+                Self::download_git_dependency(dep_name, git_info, build_options)?;
+            }
+        }
+        if let Some(archive_info) = &dep.archive_info {
+            if archive_info.download_to.exists() {
+                debug!(
+                    package = %dep_name,
+                    url = %archive_info.url,
+                    path = %archive_info.download_to.display(),
+                    "archive dependency already downloaded"
+                );
+            } else if build_options.offline
+                // the archives of a registry directory are read from disk
+                && local_archive_path(archive_info.url.as_str()).is_none()
+            {
+                bail!(
+                    "Dependency '{}' has not been downloaded from {}, and cannot be with --offline. \
+                     Run the command once without --offline to download it",
+                    dep_name,
+                    archive_info.url
+                )
+            } else {
+                download_archive_dependency(dep_name, archive_info)?;
             }
         }
         if let Some(node_info) = &dep.node_info {
@@ -572,6 +812,388 @@ impl ResolvingGraph {
         }
         Ok(())
     }
+
+    /// Clone and check out the repository in `git_info` next to its final location, and only move
+    /// it into place once complete, so that an interrupted download is never mistaken for a valid
+    /// checkout by a later build. Only the subdirectory of the package is checked out if git
+    /// supports sparse checkout, falling back to the whole repository otherwise. The commit the
+    /// lockfile records for the dependency is checked out rather than the current commit of its
+    /// revision. Submodules are initialized unless disabled in `build_options`, and files stored in
+    /// Git LFS are fetched if the repository uses it.
+    fn download_git_dependency(
+        dep_name: PackageName,
+        git_info: &GitInfo,
+        build_options: &BuildConfig,
+    ) -> Result<()> {
+        let git = git()?;
+        let _span = info_span!(
+            "fetch",
+            package = %dep_name,
+            url = %git_info.git_url,
+            rev = %git_info.git_rev
+        )
+        .entered();
+        info!("downloading git dependency");
+        let locked_commit = locked_commit(dep_name, git_info, build_options)?;
+        let partial_path = partial_download_path(&git_info.download_to);
+        if partial_path.exists() {
+            // left behind by a process that was killed before it could clean up
+            fs::remove_dir_all(&partial_path)
+                .map_err(|err| move_home_write_error("remove", &partial_path, err))?;
+        }
+        let _remove_on_interrupt = {
+            let partial_path = partial_path.clone();
+            interrupt::on_interrupt(move || {
+                let _ = fs::remove_dir_all(partial_path);
+            })
+        };
+        // created up front, rather than left to `git clone`, to report a read-only `MOVE_HOME`
+        fs::create_dir_all(&partial_path).map_err(|err| {
+            move_home_write_error("create the download directory", &partial_path, err)
+        })?;
+
+        let has_subdir = !git_info.subdir.as_os_str().is_empty();
+        if has_subdir && !git.supports_sparse_checkout() {
+            debug!(
+                version = ?git.version,
+                "git does not support sparse checkout, checking out the whole repository"
+            );
+        }
+        let sparse_checkout = has_subdir
+            && git.supports_sparse_checkout()
+            && match sparse_clone(&partial_path, git_info) {
+                Ok(()) => true,
+                Err(err) => {
+                    warn!(
+                        "sparse checkout failed, retrying with the whole repository: {:#}",
+                        err
+                    );
+                    fs::remove_dir_all(&partial_path)
+                        .and_then(|_| fs::create_dir_all(&partial_path))
+                        .map_err(|err| move_home_write_error("clean up", &partial_path, err))?;
+                    false
+                }
+            };
+        if !sparse_checkout {
+            run_git(&["clone", &git_info.git_url, &partial_path.to_string_lossy()]).map_err(
+                |_| anyhow::anyhow!("Failed to clone Git repository for package '{}'", dep_name),
+            )?;
+            run_git(&[
+                "-C",
+                &partial_path.to_string_lossy(),
+                "checkout",
+                &git_info.git_rev,
+            ])
+            .map_err(|_| checkout_error(&partial_path, git_info, dep_name))?;
+        }
+        if let Some(commit) = &locked_commit {
+            check_out_locked_commit(&partial_path, dep_name, git_info, commit, build_options)?;
+        }
+        if !build_options.no_submodules {
+            init_submodules(&partial_path, 0).map_err(|err| {
+                anyhow::anyhow!(
+                    "Failed to initialize Git submodules for package '{}': {}",
+                    dep_name,
+                    err
+                )
+            })?;
+        }
+        fetch_lfs_files(&partial_path, dep_name)?;
+        fs::rename(&partial_path, &git_info.download_to)
+            .map_err(|err| move_home_write_error("move into place", &git_info.download_to, err))?;
+        Ok(())
+    }
+}
+
+/// Clone the repository in `git_info` into `repo_path`, checking out only the subdirectory of the
+/// package. The whole repository is checked out after all if the package has local dependencies,
+/// which may live outside of its subdirectory.
+fn sparse_clone(repo_path: &Path, git_info: &GitInfo) -> Result<()> {
+    let repo = repo_path.to_string_lossy();
+    let subdir = git_info.subdir.to_string_lossy().replace('\\', "/");
+    run_git(&["clone", "--no-checkout", &git_info.git_url, &repo])?;
+    run_git(&["-C", &repo, "sparse-checkout", "init", "--cone"])?;
+    run_git(&["-C", &repo, "sparse-checkout", "set", &subdir])?;
+    run_git(&["-C", &repo, "checkout", &git_info.git_rev])?;
+
+    let has_local_dependencies = fs::read_to_string(
+        repo_path
+            .join(&git_info.subdir)
+            .join(SourcePackageLayout::Manifest.path()),
+    )
+    .map_err(anyhow::Error::from)
+    .and_then(parse_move_manifest_string)
+    .and_then(parse_source_manifest)
+    .map_or(true, |manifest| {
+        manifest
+            .dependencies
+            .values()
+            .chain(manifest.dev_dependencies.values())
+            .any(|dep| {
+                dep.git_info.is_none() && dep.node_info.is_none() && dep.archive_info.is_none()
+            })
+    });
+    if has_local_dependencies {
+        run_git(&["-C", &repo, "sparse-checkout", "disable"])?;
+    }
+    Ok(())
+}
+
+/// Recursively initialize and check out the submodules of the repository at `repo_path`, which is
+/// itself nested `depth` submodules deep.
+fn init_submodules(repo_path: &Path, depth: usize) -> Result<()> {
+    if !repo_path.join(".gitmodules").is_file() {
+        return Ok(());
+    }
+    if depth >= MAX_SUBMODULE_DEPTH {
+        bail!(
+            "submodules of '{}' are nested more than {} levels deep",
+            repo_path.display(),
+            MAX_SUBMODULE_DEPTH
+        )
+    }
+    let repo = repo_path.to_string_lossy();
+    run_git(&["-C", &repo, "submodule", "update", "--init"])?;
+    for submodule_path in submodule_paths(repo_path)? {
+        init_submodules(&repo_path.join(submodule_path), depth + 1)?;
+    }
+    Ok(())
+}
+
+/// The paths of the submodules declared in the `.gitmodules` file of the repository at `repo_path`
+fn submodule_paths(repo_path: &Path) -> Result<Vec<String>> {
+    let output = git()?
+        .command()
+        .args(["-C", &repo_path.to_string_lossy()])
+        .args([
+            "config",
+            "--file",
+            ".gitmodules",
+            "--get-regexp",
+            r"\.path$",
+        ])
+        .output()?;
+    // `git config --get-regexp` fails when nothing matches
+    Ok(String::from_utf8_lossy(&output.stdout)
+        .lines()
+        .filter_map(|line| line.split_once(' ').map(|(_, path)| path.to_string()))
+        .collect())
+}
+
+/// Fetch the files stored in Git LFS if the repository at `repo_path` uses it. A missing git-lfs
+/// installation is only a warning, since the package may not need the files stored in LFS.
+fn fetch_lfs_files(repo_path: &Path, dep_name: PackageName) -> Result<()> {
+    let uses_lfs = fs::read_to_string(repo_path.join(".gitattributes"))
+        .map_or(false, |attributes| attributes.contains("filter=lfs"));
+    if !uses_lfs {
+        return Ok(());
+    }
+    let lfs_installed = git()?
+        .command()
+        .args(["lfs", "version"])
+        .output()
+        .map_or(false, |output| output.status.success());
+    if !lfs_installed {
+        json_errors::warning(format!(
+            "package '{}' stores files in Git LFS, but git-lfs is not installed. \
+             These files will be left as LFS pointers, which may cause compilation errors.",
+            dep_name
+        ));
+        return Ok(());
+    }
+    run_git(&["-C", &repo_path.to_string_lossy(), "lfs", "pull"])
+        .map_err(|_| anyhow::anyhow!("Failed to fetch Git LFS files for package '{}'", dep_name))
+}
+
+/// Where a git or archive dependency is downloaded to before it is moved to `download_to`
+pub(crate) fn partial_download_path(download_to: &Path) -> PathBuf {
+    let mut file_name = download_to.file_name().unwrap_or_default().to_os_string();
+    file_name.push(PARTIAL_DOWNLOAD_EXTENSION);
+    download_to.with_file_name(file_name)
+}
+
+/// The commit checked out in the git repository at `repo_path`, or `None` if it is not the root of
+/// a repository
+pub(crate) fn head_commit(repo_path: &Path) -> Option<String> {
+    if !repo_path.join(".git").exists() {
+        return None;
+    }
+    let output = git()
+        .ok()?
+        .command()
+        .args(["-C", &repo_path.to_string_lossy(), "rev-parse", "HEAD"])
+        .output()
+        .ok()?;
+    output
+        .status
+        .success()
+        .then(|| String::from_utf8_lossy(&output.stdout).trim().to_string())
+}
+
+/// The commit the lockfile records for the git dependency `dep_name`, if it was locked with the
+/// repository and revision in `git_info`
+fn locked_commit(
+    dep_name: PackageName,
+    git_info: &GitInfo,
+    build_options: &BuildConfig,
+) -> Result<Option<String>> {
+    let lockfile = match &build_options.lock_file {
+        Some(path) => Lockfile::read(path)?,
+        None => None,
+    };
+    Ok(lockfile
+        .into_iter()
+        .flat_map(|lockfile| lockfile.dependencies)
+        .find(|locked| {
+            locked.name == dep_name.as_str()
+                && locked.git.as_deref() == Some(git_info.git_url.as_str())
+                && locked.rev.as_deref() == Some(git_info.git_rev.as_str())
+        })
+        .and_then(|locked| locked.commit))
+}
+
+/// Check out the locked `commit` of the git dependency `dep_name` in `repo_path`, where its
+/// revision is checked out. If the commit is no longer in the repository, which happens when the
+/// branch it was on is force pushed, the dependency is repinned to the current commit of its
+/// revision, once the user agrees or if `--repin-missing` is passed. The lockfile is updated with
+/// the new commit at the end of the build.
+fn check_out_locked_commit(
+    repo_path: &Path,
+    dep_name: PackageName,
+    git_info: &GitInfo,
+    commit: &str,
+    build_options: &BuildConfig,
+) -> Result<()> {
+    let repo = repo_path.to_string_lossy();
+    let current_commit = head_commit(repo_path).unwrap_or_default();
+    if current_commit == commit {
+        return Ok(());
+    }
+    let commit_object = format!("{}^{{commit}}", commit);
+    if run_git(&[
+        "-C",
+        &repo,
+        "rev-parse",
+        "--verify",
+        "--quiet",
+        &commit_object,
+    ])
+    .is_ok()
+    {
+        return run_git(&["-C", &repo, "checkout", commit]).map_err(|_| {
+            anyhow::anyhow!(
+                "Failed to checkout the locked commit {} of package '{}'",
+                commit,
+                dep_name
+            )
+        });
+    }
+
+    let rev = &git_info.git_rev;
+    let missing = format!(
+        "The locked commit {} of package '{}' no longer exists in {}, most likely because \
+         '{}' was force pushed.",
+        commit, dep_name, git_info.git_url, rev
+    );
+    if build_options.locked {
+        bail!(
+            "{} The lockfile cannot be updated with --locked: run the command again without \
+             --locked and with --repin-missing to lock '{}' to the current commit of '{}'",
+            missing,
+            dep_name,
+            rev
+        )
+    }
+    if !build_options.repin_missing {
+        let question = format!(
+            "{} Lock '{}' to the current commit {} of '{}' instead?",
+            missing, dep_name, current_commit, rev
+        );
+        if !atty::is(atty::Stream::Stdin) {
+            bail!(
+                "{} Pass --repin-missing to lock it without being asked",
+                question
+            )
+        }
+        eprint!("{} [y/N] ", question);
+        io::stderr().flush()?;
+        let mut answer = String::new();
+        io::stdin().read_line(&mut answer)?;
+        if !matches!(answer.trim().to_lowercase().as_str(), "y" | "yes") {
+            bail!("Cancelled")
+        }
+    }
+    eprintln!(
+        "{} {} to the current commit of '{}'",
+        "REPINNING".bold().yellow(),
+        dep_name,
+        rev
+    );
+    eprintln!("  old rev: {}", commit);
+    eprintln!("  new rev: {}", current_commit);
+    Ok(())
+}
+
+/// The error for a failed checkout of the revision in `git_info` in `repo_path`, which explains
+/// what to do if the revision is not in the repository, e.g. because the commit it names was
+/// dropped by a force push
+fn checkout_error(repo_path: &Path, git_info: &GitInfo, dep_name: PackageName) -> anyhow::Error {
+    let rev = &git_info.git_rev;
+    let commit = format!("{}^{{commit}}", rev);
+    let repo = repo_path.to_string_lossy();
+    if run_git(&["-C", &repo, "rev-parse", "--verify", "--quiet", &commit]).is_ok() {
+        return anyhow::anyhow!(
+            "Failed to checkout Git reference '{}' for package '{}'",
+            rev,
+            dep_name
+        );
+    }
+    let is_commit_hash =
+        (7..=40).contains(&rev.len()) && rev.chars().all(|c| c.is_ascii_hexdigit());
+    anyhow::anyhow!(
+        "Git revision '{}' of package '{}' does not exist in {}.{} Set the `rev` of '{}' in \
+         Move.toml to a branch, tag or commit of the repository; the lockfile is updated by the \
+         next build",
+        rev,
+        dep_name,
+        git_info.git_url,
+        if is_commit_hash {
+            " The commit may have been removed from the repository by a force push."
+        } else {
+            ""
+        },
+        dep_name
+    )
+}
+
+/// Run git with `args`, killing it if the process is interrupted
+fn run_git(args: &[&str]) -> Result<()> {
+    debug!("running git {}", args.join(" "));
+    let child = git()?
+        .command()
+        .args(args)
+        .stdout(Stdio::null())
+        .stderr(Stdio::null())
+        .spawn()?;
+    let child = Arc::new(Mutex::new(child));
+    let _kill_on_interrupt = {
+        let child = child.clone();
+        interrupt::on_interrupt(move || {
+            let _ = child.lock().unwrap().kill();
+        })
+    };
+    let status = loop {
+        if let Some(status) = child.lock().unwrap().try_wait()? {
+            break status;
+        }
+        thread::sleep(Duration::from_millis(10));
+    };
+    if !status.success() {
+        interrupt::wait_if_interrupted();
+        warn!("git {} failed: {}", args.join(" "), status);
+        bail!("git {} failed: {}", args.join(" "), status)
+    }
+    Ok(())
 }
 
 impl ResolvingPackage {
@@ -635,36 +1257,31 @@ impl ResolvingPackage {
         Ok(())
     }
 
-    fn get_source_paths_for_config(
-        package_path: &Path,
-        config: &BuildConfig,
-    ) -> Result<Vec<PathBuf>> {
-        let mut places_to_look = Vec::new();
-        let mut add_path = |layout_path: SourcePackageLayout| {
-            let path = package_path.join(layout_path.path());
-            if layout_path.is_optional() && !path.exists() {
-                return;
-            }
-            places_to_look.push(path)
-        };
-
-        add_path(SourcePackageLayout::Sources);
-        add_path(SourcePackageLayout::Scripts);
-
+    /// The directories holding the Move files of a package built with `config`, in order
+    pub(crate) fn source_layouts_for_config(config: &BuildConfig) -> Vec<SourcePackageLayout> {
+        let mut layouts = vec![SourcePackageLayout::Sources, SourcePackageLayout::Scripts];
         if config.dev_mode {
-            add_path(SourcePackageLayout::Examples);
-            add_path(SourcePackageLayout::Tests);
+            layouts.push(SourcePackageLayout::Examples);
         }
-        Ok(places_to_look)
+        // modules under `tests/` are only compiled to be tested, and never built, documented or
+        // published with the package
+        if config.test_mode {
+            layouts.push(SourcePackageLayout::Tests);
+        }
+        layouts
     }
 
     fn get_package_digest_for_config(
         package_path: &Path,
         config: &BuildConfig,
     ) -> Result<PackageDigest> {
-        let mut source_paths = Self::get_source_paths_for_config(package_path, config)?;
+        let mut source_paths: Vec<_> = Self::source_layouts_for_config(config)
+            .into_iter()
+            .map(|layout| package_path.join(layout.path()))
+            .filter(|path| path.exists())
+            .collect();
         source_paths.push(package_path.join(SourcePackageLayout::Manifest.path()));
-        compute_digest(source_paths.as_slice())
+        compute_digest(source_paths.as_slice(), &submodule_revisions(package_path)?)
     }
 }
 
@@ -698,6 +1315,18 @@ impl ResolvedGraph {
         self.package_table.get(package_ident).unwrap()
     }
 
+    /// The name of the package, followed by its Move tooling requirement if it has one
+    fn info_label(&self, package_name: &PackageName) -> String {
+        match &self.package_table[package_name]
+            .source_package
+            .package
+            .move_version
+        {
+            Some(requirement) => format!("{} (move-version {})", package_name, requirement),
+            None => package_name.to_string(),
+        }
+    }
+
     fn print_info_dfs(&self, current_node: &PackageName, tree: &mut TreeBuilder) -> Result<()> {
         let pkg = self.package_table.get(current_node).unwrap();
 
@@ -706,7 +1335,7 @@ impl ResolvedGraph {
         }
 
         for node in self.graph.neighbors_directed(*current_node, Outgoing) {
-            tree.begin_child(node.to_string());
+            tree.begin_child(self.info_label(&node));
             self.print_info_dfs(&node, tree)?;
             tree.end_child();
         }
@@ -715,7 +1344,7 @@ impl ResolvedGraph {
 
     pub fn print_info(&self) -> Result<()> {
         let root = self.root_package.package.name;
-        let mut tree = TreeBuilder::new(root.to_string());
+        let mut tree = TreeBuilder::new(self.info_label(&root));
         self.print_info_dfs(&root, &mut tree)?;
         let tree = tree.build();
         print_tree(&tree)?;
@@ -757,15 +1386,13 @@ impl ResolvedGraph {
 
 impl ResolvedPackage {
     pub fn get_sources(&self, config: &BuildConfig) -> Result<Vec<FileName>> {
-        let places_to_look =
-            ResolvingPackage::get_source_paths_for_config(&self.package_path, config)?
-                .into_iter()
-                .map(|p| p.to_string_lossy().to_string())
-                .collect::<Vec<_>>();
-        Ok(find_move_filenames(&places_to_look, false)?
-            .into_iter()
-            .map(Symbol::from)
-            .collect())
+        Ok(package_move_files(
+            &self.package_path,
+            &ResolvingPackage::source_layouts_for_config(config),
+        )?
+        .into_iter()
+        .map(|path| Symbol::from(path.to_string_lossy().to_string()))
+        .collect())
     }
 
     /// Returns the transitive dependencies of this package in dependency order
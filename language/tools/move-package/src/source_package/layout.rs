@@ -5,10 +5,15 @@
 use std::path::{Path, PathBuf};
 
 use anyhow::{bail, Result};
+use move_command_line_common::files::find_move_filenames;
 
 /// References file for documentation generation
 pub const REFERENCE_TEMPLATE_FILENAME: &str = "references.md";
 
+/// A file marking a directory above which no package root is searched for, e.g. the root of a
+/// repository holding packages
+pub const ROOT_MARKER: &str = ".move-root";
+
 #[derive(Debug, Clone, Eq, PartialEq)]
 pub enum SourcePackageLayout {
     Sources,
@@ -18,13 +23,15 @@ pub enum SourcePackageLayout {
     Examples,
     Manifest,
     DocTemplates,
+    Lockfile,
 }
 
 impl SourcePackageLayout {
     /// A Move source package is laid out on-disk as
     /// a_move_package
     /// ├── Move.toml      (required)
-    /// ├── sources        (required)
+    /// ├── Move.lock      (optional)
+    /// ├── sources        (optional, missing in packages holding only scripts or nothing)
     /// ├── examples       (optional, dev mode)
     /// ├── scripts        (optional)
     /// ├── specifications (optional)
@@ -34,21 +41,62 @@ impl SourcePackageLayout {
         Path::new(self.location_str())
     }
 
+    /// The root of the package containing `starting_path`: the closest of `starting_path` and its
+    /// ancestors holding a manifest. The search stops at a directory holding a `.move-root`
+    /// marker, and does not cross into another file system.
     pub fn try_find_root(starting_path: &Path) -> Result<PathBuf> {
         let mut current_path = starting_path.to_path_buf();
         loop {
             if current_path.join(Self::Manifest.path()).is_file() {
                 break Ok(current_path);
             }
-            if !current_path.pop() {
-                bail!(
-                    "Unable to find package manifest in '{}' or in its parents",
-                    starting_path.to_string_lossy()
-                )
+            let device = file_system_device(&current_path);
+            let at_root = current_path.join(ROOT_MARKER).exists() || !current_path.pop();
+            let crossed_file_system = matches!(
+                (device, file_system_device(&current_path)),
+                (Some(from), Some(to)) if from != to
+            );
+            if at_root || crossed_file_system {
+                bail!(Self::missing_manifest_message(starting_path))
             }
         }
     }
 
+    /// Explain that there is no package at `starting_path`, listing the packages in its
+    /// subdirectories, if any
+    fn missing_manifest_message(starting_path: &Path) -> String {
+        let mut message = format!(
+            "Unable to find package manifest ({}) in '{}' or in its parents. Create a package \
+             with `move new <name>`, or turn the directory into one with \
+             `move new <name> --path <directory>`",
+            Self::Manifest.location_str(),
+            starting_path.to_string_lossy()
+        );
+        let mut packages: Vec<_> = match std::fs::read_dir(starting_path) {
+            Ok(entries) => entries
+                .filter_map(|entry| entry.ok())
+                .map(|entry| entry.path().join(Self::Manifest.path()))
+                .filter(|manifest| manifest.is_file())
+                .filter_map(|manifest| {
+                    manifest
+                        .strip_prefix(starting_path)
+                        .ok()
+                        .map(|manifest| manifest.to_string_lossy().to_string())
+                })
+                .collect(),
+            Err(_) => vec![],
+        };
+        packages.sort();
+        if !packages.is_empty() {
+            message.push_str(&format!(
+                ", or pass the directory of one of the packages found in subdirectories with \
+                 `--path`: {}",
+                packages.join(", ")
+            ));
+        }
+        message
+    }
+
     pub fn location_str(&self) -> &'static str {
         match self {
             Self::Sources => "sources",
@@ -58,17 +106,50 @@ impl SourcePackageLayout {
             Self::Examples => "examples",
             Self::Specifications => "specifications",
             Self::DocTemplates => "doc_templates",
+            Self::Lockfile => "Move.lock",
         }
     }
 
     pub fn is_optional(&self) -> bool {
         match self {
-            Self::Sources | Self::Manifest => false,
-            Self::Tests
+            Self::Manifest => false,
+            Self::Sources
+            | Self::Tests
             | Self::Scripts
             | Self::Examples
             | Self::Specifications
-            | Self::DocTemplates => true,
+            | Self::DocTemplates
+            | Self::Lockfile => true,
         }
     }
 }
+
+/// The Move files under the directories `layouts` of the package at `package_path`, in the order of
+/// `layouts`. The directories that do not exist hold no files, so that packages without modules,
+/// such as those holding only scripts, are handled the same way by every command.
+pub fn package_move_files(
+    package_path: &Path,
+    layouts: &[SourcePackageLayout],
+) -> Result<Vec<PathBuf>> {
+    let dirs: Vec<_> = layouts
+        .iter()
+        .map(|layout| package_path.join(layout.path()))
+        .filter(|dir| dir.is_dir())
+        .collect();
+    Ok(find_move_filenames(&dirs, false)?
+        .into_iter()
+        .map(PathBuf::from)
+        .collect())
+}
+
+/// The device of the file system `path` is on, if known
+#[cfg(unix)]
+fn file_system_device(path: &Path) -> Option<u64> {
+    use std::os::unix::fs::MetadataExt;
+    std::fs::metadata(path).ok().map(|metadata| metadata.dev())
+}
+
+#[cfg(not(unix))]
+fn file_system_device(_path: &Path) -> Option<u64> {
+    None
+}
@@ -2,10 +2,22 @@
 // Copyright (c) The Move Contributors
 // SPDX-License-Identifier: Apache-2.0
 
-use crate::{package_hooks, source_package::parsed_manifest as PM, Architecture};
+use crate::{
+    package_hooks,
+    resolution::registry::{self, RegistrySource, LOCAL_REGISTRY_NAME},
+    source_package::parsed_manifest::{self as PM, format_version},
+    Architecture,
+};
 use anyhow::{bail, format_err, Context, Result};
-use move_command_line_common::env::MOVE_HOME;
-use move_core_types::account_address::{AccountAddress, AccountAddressParseError};
+use move_command_line_common::{
+    env::MOVE_HOME,
+    files::{read_utf8_file, strip_utf8_bom, PathPrefixRemap},
+    json_errors,
+};
+use move_core_types::{
+    account_address::{AccountAddress, AccountAddressParseError},
+    identifier,
+};
 use move_symbol_pool::symbol::Symbol;
 use std::{
     collections::{BTreeMap, BTreeSet},
@@ -19,33 +31,42 @@ const EMPTY_ADDR_STR: &str = "_";
 
 const PACKAGE_NAME: &str = "package";
 const BUILD_NAME: &str = "build";
+const DOCGEN_NAME: &str = "docgen";
 const ADDRESSES_NAME: &str = "addresses";
 const DEV_ADDRESSES_NAME: &str = "dev-addresses";
 const DEPENDENCY_NAME: &str = "dependencies";
 const DEV_DEPENDENCY_NAME: &str = "dev-dependencies";
+const WORKSPACE_NAME: &str = "workspace";
+// read by the CLI, which checks its version against the requirement of the project
+const TOOLCHAIN_NAME: &str = "toolchain";
 
 const KNOWN_NAMES: &[&str] = &[
     PACKAGE_NAME,
     BUILD_NAME,
+    DOCGEN_NAME,
     ADDRESSES_NAME,
     DEV_ADDRESSES_NAME,
     DEPENDENCY_NAME,
     DEV_DEPENDENCY_NAME,
+    WORKSPACE_NAME,
+    TOOLCHAIN_NAME,
 ];
 
 const REQUIRED_FIELDS: &[&str] = &[PACKAGE_NAME];
 
 pub fn parse_move_manifest_from_file(path: &Path) -> Result<PM::SourceManifest> {
     let file_contents = if path.is_file() {
-        std::fs::read_to_string(path)?
+        read_utf8_file(path)?
     } else {
-        std::fs::read_to_string(path.join(SourcePackageLayout::Manifest.path()))?
+        read_utf8_file(&path.join(SourcePackageLayout::Manifest.path()))?
     };
     parse_source_manifest(parse_move_manifest_string(file_contents)?)
 }
 
+/// Parse the contents of a `Move.toml`, which may start with a byte order mark
 pub fn parse_move_manifest_string(manifest_string: String) -> Result<TV> {
-    toml::from_str::<TV>(&manifest_string).context("Unable to parse Move package manifest")
+    toml::from_str::<TV>(strip_utf8_bom(&manifest_string))
+        .context("Unable to parse Move package manifest")
 }
 
 pub fn parse_source_manifest(tval: TV) -> Result<PM::SourceManifest> {
@@ -75,6 +96,11 @@ pub fn parse_source_manifest(tval: TV) -> Result<PM::SourceManifest> {
                 .map(parse_build_info)
                 .transpose()
                 .context("Error parsing '[build]' section of manifest")?;
+            let docgen = table
+                .remove(DOCGEN_NAME)
+                .map(parse_docgen_info)
+                .transpose()
+                .context("Error parsing '[docgen]' section of manifest")?;
             let dependencies = table
                 .remove(DEPENDENCY_NAME)
                 .map(parse_dependencies)
@@ -92,6 +118,7 @@ pub fn parse_source_manifest(tval: TV) -> Result<PM::SourceManifest> {
                 addresses,
                 dev_address_assignments,
                 build,
+                docgen,
                 dependencies,
                 dev_dependencies,
             })
@@ -106,12 +133,44 @@ pub fn parse_source_manifest(tval: TV) -> Result<PM::SourceManifest> {
     }
 }
 
+/// Parse the `[workspace]` section of the manifest `tval`, if it has one. Unlike the other sections,
+/// it may be declared by a manifest without a `[package]` section.
+pub fn parse_workspace_info(tval: &TV) -> Result<Option<PM::WorkspaceInfo>> {
+    let workspace = match tval.get(WORKSPACE_NAME) {
+        None => return Ok(None),
+        Some(TV::Table(workspace)) => workspace,
+        Some(x) => bail!(
+            "Malformed '[workspace]' section of manifest. Expected a table, but encountered a {}",
+            x.type_str()
+        ),
+    };
+    warn_if_unknown_field_names(workspace, &["members"]);
+    let members = match workspace.get("members") {
+        None => vec![],
+        Some(TV::Array(members)) => members
+            .iter()
+            .map(|member| match member.as_str() {
+                Some(member) => Ok(PathBuf::from(member)),
+                None => bail!(
+                    "Invalid workspace member {}. Expected the path to a package",
+                    member
+                ),
+            })
+            .collect::<Result<_>>()?,
+        Some(x) => bail!(
+            "Invalid workspace members {}. Expected an array of paths to packages",
+            x
+        ),
+    };
+    Ok(Some(PM::WorkspaceInfo { members }))
+}
+
 pub fn parse_package_info(tval: TV) -> Result<PM::PackageInfo> {
     match tval {
         TV::Table(mut table) => {
             check_for_required_field_names(&table, &["name", "version"])?;
             let hook_names = package_hooks::custom_package_info_fields();
-            let known_names = ["name", "version", "authors", "license"]
+            let known_names = ["name", "version", "authors", "license", "move-version"]
                 .into_iter()
                 .chain(hook_names.iter().map(|s| s.as_str()))
                 .collect::<Vec<_>>();
@@ -128,6 +187,11 @@ pub fn parse_package_info(tval: TV) -> Result<PM::PackageInfo> {
             let name = PM::PackageName::from(name);
             let version = parse_version(version)?;
             let license = table.remove("license").map(|x| Symbol::from(x.to_string()));
+            let move_version = table
+                .remove("move-version")
+                .map(parse_version_requirement)
+                .transpose()
+                .context("Invalid 'move-version' requirement")?;
             let authors = match table.remove("authors") {
                 None => Vec::new(),
                 Some(arr) => {
@@ -166,6 +230,7 @@ pub fn parse_package_info(tval: TV) -> Result<PM::PackageInfo> {
                 authors,
                 license,
                 custom_properties,
+                move_version,
             })
         }
         x => bail!(
@@ -198,13 +263,81 @@ pub fn parse_dependencies(tval: TV) -> Result<PM::Dependencies> {
 pub fn parse_build_info(tval: TV) -> Result<PM::BuildInfo> {
     match tval {
         TV::Table(mut table) => {
-            warn_if_unknown_field_names(&table, &["language_version", "arch"]);
+            warn_if_unknown_field_names(&table, &["language_version", "arch", "remap_path_prefix"]);
+            let remap_path_prefix = match table.remove("remap_path_prefix") {
+                None => vec![],
+                Some(TV::Array(remaps)) => remaps
+                    .into_iter()
+                    .map(|remap| match remap.as_str() {
+                        Some(remap) => PathPrefixRemap::parse(remap),
+                        None => bail!(
+                            "Invalid remap_path_prefix entry, expected a string of the form \
+                             <from>=<to> but found '{}'",
+                            remap
+                        ),
+                    })
+                    .collect::<Result<_>>()?,
+                Some(x) => bail!(
+                    "Invalid remap_path_prefix, expected an array of strings but found a {}",
+                    x.type_str()
+                ),
+            };
             Ok(PM::BuildInfo {
                 language_version: table
                     .remove("language_version")
                     .map(parse_version)
                     .transpose()?,
                 architecture: table.remove("arch").map(parse_architecture).transpose()?,
+                remap_path_prefix,
+            })
+        }
+        x => bail!(
+            "Malformed section in manifest {}. Expected a table, but encountered a {}",
+            x,
+            x.type_str()
+        ),
+    }
+}
+
+pub fn parse_docgen_info(tval: TV) -> Result<PM::DocgenInfo> {
+    match tval {
+        TV::Table(mut table) => {
+            warn_if_unknown_field_names(&table, &["title", "logo", "module_order"]);
+            let title = table
+                .remove("title")
+                .map(|title| match title.as_str() {
+                    Some(title) => Ok(title.to_string()),
+                    None => bail!("Invalid title, expected a string but found '{}'", title),
+                })
+                .transpose()?;
+            let logo = table
+                .remove("logo")
+                .map(|logo| match logo.as_str() {
+                    Some(logo) => Ok(PathBuf::from(logo)),
+                    None => bail!("Invalid logo path, expected a string but found '{}'", logo),
+                })
+                .transpose()?;
+            let module_order = match table.remove("module_order") {
+                None => vec![],
+                Some(TV::Array(modules)) => modules
+                    .into_iter()
+                    .map(|module| match module.as_str() {
+                        Some(module) => Ok(Symbol::from(module)),
+                        None => bail!(
+                            "Invalid module name in module_order, expected a string but found '{}'",
+                            module
+                        ),
+                    })
+                    .collect::<Result<_>>()?,
+                Some(x) => bail!(
+                    "Invalid module_order, expected an array of module names but found a {}",
+                    x.type_str()
+                ),
+            };
+            Ok(PM::DocgenInfo {
+                title,
+                logo,
+                module_order,
             })
         }
         x => bail!(
@@ -318,6 +451,10 @@ fn parse_dependency(dep_name: &str, tval: TV) -> Result<PM::Dependency> {
                 "rev",
                 "subdir",
                 "address",
+                "url",
+                "sha256",
+                "registry",
+                "name",
             ];
             let custom_key_opt = &package_hooks::custom_dependency_key();
             if let Some(key) = custom_key_opt {
@@ -330,18 +467,43 @@ fn parse_dependency(dep_name: &str, tval: TV) -> Result<PM::Dependency> {
                 .transpose()?;
             let version = table.remove("version").map(parse_version).transpose()?;
             let digest = table.remove("digest").map(parse_digest).transpose()?;
+            if let Some(registry) = table.remove("registry") {
+                let mut sources = vec!["local", "git", "url"];
+                if let Some(key) = custom_key_opt {
+                    sources.push(key.as_str())
+                }
+                if let Some(source) = sources.iter().find(|key| table.contains_key(**key)) {
+                    bail!(
+                        "'registry' and '{}' cannot both be given for dependency '{}'",
+                        source,
+                        dep_name
+                    )
+                }
+                let name = match table.remove("name") {
+                    None => dep_name.to_string(),
+                    Some(name) => name
+                        .as_str()
+                        .ok_or_else(|| format_err!("'name' not a string"))?
+                        .to_string(),
+                };
+                return parse_registry_dependency(
+                    dep_name, registry, &name, subst, version, digest,
+                );
+            }
             let mut git_info = None;
             let mut node_info = None;
+            let mut archive_info = None;
             match (
                 table.remove("local"),
                 table.remove("git"),
+                table.remove("url"),
                 if let Some(key) = custom_key_opt {
                     table.remove(key)
                 } else {
                     None
                 },
             ) {
-                (Some(local), None, None) => {
+                (Some(local), None, None, None) => {
                     let local_str = local
                         .as_str()
                         .ok_or_else(|| format_err!("Local source path not a string"))?;
@@ -353,10 +515,10 @@ fn parse_dependency(dep_name: &str, tval: TV) -> Result<PM::Dependency> {
                         local: local_path,
                         git_info,
                         node_info,
+                        archive_info,
                     })
                 }
-                (None, Some(git), None) => {
-                    let move_home = MOVE_HOME.clone();
+                (None, Some(git), None, None) => {
                     let rev_name = match table.remove("rev") {
                         None => bail!("Git revision not supplied for dependency"),
                         Some(r) => Symbol::from(
@@ -364,15 +526,10 @@ fn parse_dependency(dep_name: &str, tval: TV) -> Result<PM::Dependency> {
                                 .ok_or_else(|| format_err!("Git revision not a string"))?,
                         ),
                     };
-                    // Downloaded packages are of the form <sanitized_git_url>_<rev_name>
                     let git_url = git
                         .as_str()
                         .ok_or_else(|| anyhow::anyhow!("Git URL not a string"))?;
-                    let local_path = PathBuf::from(move_home).join(format!(
-                        "{}_{}",
-                        url_to_file_name(git_url),
-                        rev_name.replace('/', "__")
-                    ));
+                    let local_path = git_download_dir(&MOVE_HOME, git_url, &rev_name);
                     let subdir = PathBuf::from(match table.remove("subdir") {
                         None => "".to_string(),
                         Some(path) => path
@@ -394,9 +551,47 @@ fn parse_dependency(dep_name: &str, tval: TV) -> Result<PM::Dependency> {
                         local: local_path.join(subdir),
                         git_info,
                         node_info,
+                        archive_info,
                     })
                 }
-                (None, None, Some(custom_key)) => {
+                (None, None, Some(url), None) => {
+                    let url = url
+                        .as_str()
+                        .ok_or_else(|| format_err!("Archive URL not a string"))?;
+                    let sha256 = match table.remove("sha256") {
+                        None => bail!(
+                            "SHA-256 digest not supplied for archive dependency: set 'sha256' to \
+                             the digest of the archive at {}",
+                            url
+                        ),
+                        Some(sha256) => parse_sha256(sha256)?,
+                    };
+                    let local_path = archive_download_dir(&MOVE_HOME, url, &sha256);
+                    let subdir = PathBuf::from(match table.remove("subdir") {
+                        None => "".to_string(),
+                        Some(path) => path
+                            .as_str()
+                            .ok_or_else(|| format_err!("'subdir' not a string"))?
+                            .to_string(),
+                    });
+                    archive_info = Some(PM::ArchiveInfo {
+                        url: Symbol::from(url),
+                        sha256,
+                        subdir: subdir.clone(),
+                        download_to: local_path.clone(),
+                    });
+
+                    Ok(PM::Dependency {
+                        subst,
+                        version,
+                        digest,
+                        local: local_path.join(subdir),
+                        git_info,
+                        node_info,
+                        archive_info,
+                    })
+                }
+                (None, None, None, Some(custom_key)) => {
                     let package_name = Symbol::from(dep_name);
                     let address = match table.remove("address") {
                         None => bail!("Address not supplied for 'node' dependency"),
@@ -405,16 +600,11 @@ fn parse_dependency(dep_name: &str, tval: TV) -> Result<PM::Dependency> {
                                 .ok_or_else(|| format_err!("Node address not a string"))?,
                         ),
                     };
-                    // Downloaded packages are of the form <sanitized_node_url>_<address>_<package>
                     let node_url = custom_key
                         .as_str()
                         .ok_or_else(|| anyhow::anyhow!("Git URL not a string"))?;
-                    let local_path = PathBuf::from(MOVE_HOME.clone()).join(format!(
-                        "{}_{}_{}",
-                        url_to_file_name(node_url),
-                        address,
-                        package_name
-                    ));
+                    let local_path =
+                        node_download_dir(&MOVE_HOME, node_url, &address, &package_name);
                     node_info = Some(PM::CustomDepInfo {
                         node_url: Symbol::from(node_url),
                         package_address: address,
@@ -428,10 +618,11 @@ fn parse_dependency(dep_name: &str, tval: TV) -> Result<PM::Dependency> {
                         local: local_path,
                         git_info,
                         node_info,
+                        archive_info,
                     })
                 }
                 _ => {
-                    let mut keys = vec!["local", "git"];
+                    let mut keys = vec!["local", "git", "url", "registry"];
                     if let Some(k) = custom_key_opt {
                         keys.push(k.as_str())
                     }
@@ -450,6 +641,107 @@ fn parse_dependency(dep_name: &str, tval: TV) -> Result<PM::Dependency> {
     }
 }
 
+/// A dependency on version `version` of the package `name` of a registry directory, taken from the
+/// archive or the sources the registry holds it as
+fn parse_registry_dependency(
+    dep_name: &str,
+    registry: TV,
+    name: &str,
+    subst: Option<PM::Substitution>,
+    version: Option<PM::Version>,
+    digest: Option<PM::PackageDigest>,
+) -> Result<PM::Dependency> {
+    let registry = registry
+        .as_str()
+        .ok_or_else(|| format_err!("'registry' not a string"))?;
+    if registry != LOCAL_REGISTRY_NAME {
+        bail!(
+            "Unknown registry '{}' for dependency '{}': only the registry directory, named '{}', \
+             can be given",
+            registry,
+            dep_name,
+            LOCAL_REGISTRY_NAME
+        )
+    }
+    let package_version = match &version {
+        Some(version) => version,
+        None => bail!(
+            "Version not supplied for dependency '{}' on the registry '{}'",
+            dep_name,
+            registry
+        ),
+    };
+    let registry_path = registry::registry_path().ok_or_else(|| {
+        format_err!(
+            "Dependency '{}' is taken from the registry '{}', but no registry directory is set. \
+             Pass --registry-path, or set 'path' in the [registry] table of the credential file",
+            dep_name,
+            registry
+        )
+    })?;
+    let mut dependency = PM::Dependency {
+        subst,
+        version,
+        digest,
+        local: PathBuf::new(),
+        git_info: None,
+        node_info: None,
+        archive_info: None,
+    };
+    match registry::find_package(&registry_path, name, package_version)? {
+        RegistrySource::Archive { path, sha256 } => {
+            let url = format!("file://{}", path.display());
+            let sha256 = parse_sha256(TV::String(sha256))?;
+            let local_path = archive_download_dir(&MOVE_HOME, &url, &sha256);
+            dependency.local = local_path.clone();
+            dependency.archive_info = Some(PM::ArchiveInfo {
+                url: Symbol::from(url),
+                sha256,
+                subdir: PathBuf::new(),
+                download_to: local_path,
+            });
+        }
+        RegistrySource::Sources(path) => dependency.local = path,
+    }
+    Ok(dependency)
+}
+
+/// The directory under `move_home` the revision `rev` of the git repository `git_url` is downloaded
+/// to, of the form `<sanitized_git_url>_<rev>`
+pub fn git_download_dir(move_home: &str, git_url: &str, rev: &str) -> PathBuf {
+    PathBuf::from(move_home).join(format!(
+        "{}_{}",
+        url_to_file_name(git_url),
+        rev.replace('/', "__")
+    ))
+}
+
+/// The directory under `move_home` the package `package_name` published at `address` is downloaded
+/// to from `node_url`, of the form `<sanitized_node_url>_<address>_<package_name>`
+pub fn node_download_dir(
+    move_home: &str,
+    node_url: &str,
+    address: &str,
+    package_name: &str,
+) -> PathBuf {
+    PathBuf::from(move_home).join(format!(
+        "{}_{}_{}",
+        url_to_file_name(node_url),
+        address,
+        package_name
+    ))
+}
+
+/// The directory under `move_home` the archive at `url` with the SHA-256 digest `sha256` is
+/// extracted to, of the form `<sanitized_url>_<sha256 prefix>`
+pub fn archive_download_dir(move_home: &str, url: &str, sha256: &str) -> PathBuf {
+    PathBuf::from(move_home).join(format!(
+        "{}_{}",
+        url_to_file_name(url),
+        &sha256[..sha256.len().min(16)]
+    ))
+}
+
 fn url_to_file_name(url: &str) -> String {
     regex::Regex::new(r"/|:|\.|@")
         .unwrap()
@@ -492,13 +784,80 @@ fn parse_substitution(tval: TV) -> Result<PM::Substitution> {
 fn parse_version(tval: TV) -> Result<PM::Version> {
     let version_str = tval.as_str().unwrap();
     let version_parts = version_str.split('.').collect::<Vec<_>>();
-    if version_parts.len() != 3 {
-        bail!(
+    let version = if version_parts.len() == 3 {
+        parse_version_parts(&version_parts)
+    } else {
+        Err(format_err!(
             "Version is malformed. Versions must be of the form <u64>.<u64>.<u64>, but found '{}'",
             version_str
-        );
+        ))
+    };
+    match (version, corrected_version(version_str)) {
+        (Err(_), Some(corrected)) => bail!(
+            "Version is malformed. Versions must be of the form <u64>.<u64>.<u64>, but found '{}'. \
+             Did you mean '{}'?",
+            version_str,
+            corrected
+        ),
+        (version, _) => version,
     }
+}
 
+/// The full version `version_str` stands for, if it is not one, e.g. `1.0.0` for `1.0` or `v1.0`.
+/// Pre-release and build suffixes such as `-beta` are dropped, as versions cannot have them.
+fn corrected_version(version_str: &str) -> Option<String> {
+    let version_str = version_str.trim();
+    let version_str = version_str
+        .strip_prefix(|c| c == 'v' || c == 'V')
+        .unwrap_or(version_str);
+    let version_str = version_str
+        .split(&['-', '+'][..])
+        .next()
+        .unwrap_or_default();
+    let mut parts = version_str
+        .split('.')
+        .map(|part| part.parse::<u64>().ok())
+        .collect::<Option<Vec<_>>>()?;
+    if parts.len() > 3 {
+        return None;
+    }
+    parts.resize(3, 0);
+    Some(format_version(&(parts[0], parts[1], parts[2])))
+}
+
+/// Parse a requirement such as `>=1.5` or `>=1.5, <2`. Missing minor and bugfix versions are 0.
+fn parse_version_requirement(tval: TV) -> Result<PM::VersionRequirement> {
+    let requirement_str = tval
+        .as_str()
+        .ok_or_else(|| format_err!("Version requirement must be a string"))?;
+    let mut comparators = vec![];
+    for comparator in requirement_str.split(',') {
+        let comparator = comparator.trim();
+        let (op, version_str) = [
+            (">=", PM::VersionOp::GreaterEq),
+            ("<=", PM::VersionOp::LessEq),
+            (">", PM::VersionOp::Greater),
+            ("<", PM::VersionOp::Less),
+            ("=", PM::VersionOp::Eq),
+        ]
+        .into_iter()
+        .find_map(|(prefix, op)| comparator.strip_prefix(prefix).map(|rest| (op, rest)))
+        .unwrap_or((PM::VersionOp::GreaterEq, comparator));
+        let mut version_parts = version_str.trim().split('.').collect::<Vec<_>>();
+        if version_parts.len() > 3 {
+            bail!(
+                "Version requirement is malformed. Requirements must be of the form \
+                 [<op>]<u64>[.<u64>[.<u64>]], but found '{}'",
+                comparator
+            );
+        }
+        version_parts.resize(3, "0");
+        comparators.push((op, parse_version_parts(&version_parts)?));
+    }
+    Ok(PM::VersionRequirement { comparators })
+}
+
+fn parse_version_parts(version_parts: &[&str]) -> Result<PM::Version> {
     Ok((
         version_parts[0]
             .parse::<u64>()
@@ -512,6 +871,66 @@ fn parse_version(tval: TV) -> Result<PM::Version> {
     ))
 }
 
+/// Parse a version of the form `<u64>.<u64>.<u64>`
+pub fn parse_version_str(version_str: &str) -> Result<PM::Version> {
+    parse_version(TV::String(version_str.to_string()))
+}
+
+/// Parse a requirement such as `>=1.5` or `>=1.5, <2`
+pub fn parse_version_requirement_str(requirement_str: &str) -> Result<PM::VersionRequirement> {
+    parse_version_requirement(TV::String(requirement_str.to_string()))
+}
+
+/// Check that `name` can name a package: packages are named by Move identifiers, as their names
+/// are the keys of the `[dependencies]` of the packages depending on them
+pub fn check_package_name(name: &str) -> Result<()> {
+    if identifier::is_valid(name) {
+        return Ok(());
+    }
+    let suggestion = match corrected_package_name(name) {
+        Some(corrected) => format!(" Did you mean '{}'?", corrected),
+        None => String::new(),
+    };
+    bail!(
+        "Invalid package name '{}'. Package names must be Move identifiers: an ASCII letter or \
+         underscore followed by ASCII letters, digits and underscores.{}",
+        name,
+        suggestion
+    )
+}
+
+/// Whether the package name `name` follows the snake_case or the UpperCamelCase convention, e.g.
+/// `move_stdlib` or `MoveStdlib`
+pub fn is_conventional_package_name(name: &str) -> bool {
+    let snake_case = name
+        .chars()
+        .all(|c| c.is_ascii_lowercase() || c.is_ascii_digit() || c == '_');
+    let upper_camel_case = name.starts_with(|c: char| c.is_ascii_uppercase())
+        && name.chars().all(|c| c.is_ascii_alphanumeric());
+    snake_case || upper_camel_case
+}
+
+/// The identifier `name` stands for, if it only differs from one in its separators, e.g.
+/// `my_package` for `my-package` or `MyPackage` for `My Package`
+fn corrected_package_name(name: &str) -> Option<String> {
+    let words = name
+        .split(|c: char| !c.is_ascii_alphanumeric() && c != '_')
+        .filter(|word| !word.is_empty())
+        .collect::<Vec<_>>();
+    let separators_only = name
+        .chars()
+        .all(|c| c.is_ascii_alphanumeric() || matches!(c, '_' | '-' | ' ' | '.'));
+    if words.is_empty() || !separators_only {
+        return None;
+    }
+    let corrected = if words[0].starts_with(|c: char| c.is_ascii_uppercase()) {
+        words.concat()
+    } else {
+        words.join("_")
+    };
+    identifier::is_valid(&corrected).then(|| corrected)
+}
+
 fn parse_architecture(tval: TV) -> Result<Architecture> {
     Architecture::try_parse_from_str(tval.as_str().unwrap())
 }
@@ -523,6 +942,20 @@ fn parse_digest(tval: TV) -> Result<PM::PackageDigest> {
     Ok(PM::PackageDigest::from(digest_str))
 }
 
+fn parse_sha256(tval: TV) -> Result<Symbol> {
+    let sha256 = tval
+        .as_str()
+        .ok_or_else(|| format_err!("'sha256' not a string"))?
+        .to_lowercase();
+    if sha256.len() != 64 || !sha256.chars().all(|c| c.is_ascii_hexdigit()) {
+        bail!(
+            "Invalid 'sha256' digest '{}': expected 64 hexadecimal digits",
+            sha256
+        )
+    }
+    Ok(Symbol::from(sha256))
+}
+
 // check that only recognized names are provided at the top-level
 fn warn_if_unknown_field_names(table: &toml::map::Map<String, TV>, known_names: &[&str]) {
     let mut unknown_names = BTreeSet::new();
@@ -533,8 +966,8 @@ fn warn_if_unknown_field_names(table: &toml::map::Map<String, TV>, known_names:
     }
 
     if !unknown_names.is_empty() {
-        eprintln!(
-            "Warning: unknown field name{} found. Expected one of [{}], but found {}",
+        json_errors::warning(format!(
+            "unknown field name{} found. Expected one of [{}], but found {}",
             if unknown_names.len() > 1 { "s" } else { "" },
             known_names.join(", "),
             unknown_names
@@ -542,7 +975,7 @@ fn warn_if_unknown_field_names(table: &toml::map::Map<String, TV>, known_names:
                 .map(|x| format!("'{}'", x))
                 .collect::<Vec<_>>()
                 .join(", ")
-        );
+        ));
     }
 }
 
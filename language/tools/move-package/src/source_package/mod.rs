@@ -5,3 +5,4 @@
 pub mod layout;
 pub mod manifest_parser;
 pub mod parsed_manifest;
+pub mod workspace;
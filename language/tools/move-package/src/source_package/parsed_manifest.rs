@@ -3,9 +3,10 @@
 // SPDX-License-Identifier: Apache-2.0
 
 use crate::Architecture;
+use move_command_line_common::files::PathPrefixRemap;
 use move_core_types::account_address::AccountAddress;
 use move_symbol_pool::symbol::Symbol;
-use std::{collections::BTreeMap, path::PathBuf};
+use std::{collections::BTreeMap, fmt, path::PathBuf};
 
 pub type NamedAddress = Symbol;
 pub type PackageName = Symbol;
@@ -24,10 +25,18 @@ pub struct SourceManifest {
     pub addresses: Option<AddressDeclarations>,
     pub dev_address_assignments: Option<DevAddressDeclarations>,
     pub build: Option<BuildInfo>,
+    pub docgen: Option<DocgenInfo>,
     pub dependencies: Dependencies,
     pub dev_dependencies: Dependencies,
 }
 
+/// The `[workspace]` section of a manifest
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub struct WorkspaceInfo {
+    /// The root directories of the member packages, relative to the manifest
+    pub members: Vec<PathBuf>,
+}
+
 #[derive(Debug, Clone, Eq, PartialEq)]
 pub struct PackageInfo {
     pub name: PackageName,
@@ -35,6 +44,24 @@ pub struct PackageInfo {
     pub authors: Vec<Symbol>,
     pub license: Option<Symbol>,
     pub custom_properties: BTreeMap<Symbol, String>,
+    /// The versions of the Move tooling able to build the package
+    pub move_version: Option<VersionRequirement>,
+}
+
+/// A requirement on a version, such as `>=1.5` or `>=1.5, <2`. All comparators must hold for a
+/// version to match. A bare version is a minimum version.
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub struct VersionRequirement {
+    pub comparators: Vec<(VersionOp, Version)>,
+}
+
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+pub enum VersionOp {
+    Less,
+    LessEq,
+    Eq,
+    GreaterEq,
+    Greater,
 }
 
 #[derive(Debug, Clone, Eq, PartialEq)]
@@ -45,6 +72,7 @@ pub struct Dependency {
     pub digest: Option<PackageDigest>,
     pub git_info: Option<GitInfo>,
     pub node_info: Option<CustomDepInfo>,
+    pub archive_info: Option<ArchiveInfo>,
 }
 
 #[derive(Debug, Clone, Eq, PartialEq)]
@@ -60,6 +88,18 @@ pub struct GitInfo {
     pub download_to: PathBuf,
 }
 
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub struct ArchiveInfo {
+    /// The URL of the `.tar.gz` archive of the package sources
+    pub url: Symbol,
+    /// The SHA-256 digest the archive must have, in lowercase hex
+    pub sha256: Symbol,
+    /// The path under the extracted archive where the move package can be found
+    pub subdir: PathBuf,
+    /// Where the archive is extracted to.
+    pub download_to: PathBuf,
+}
+
 #[derive(Debug, Clone, Eq, PartialEq)]
 pub struct CustomDepInfo {
     /// The url of the node to download from
@@ -77,6 +117,20 @@ pub struct CustomDepInfo {
 pub struct BuildInfo {
     pub language_version: Option<Version>,
     pub architecture: Option<Architecture>,
+    /// Path prefixes to remap, applied before those of `--remap-path-prefix`. Relative `from`
+    /// prefixes are relative to the package root.
+    pub remap_path_prefix: Vec<PathPrefixRemap>,
+}
+
+/// Settings for the index page generated by `move docgen`
+#[derive(Default, Debug, Clone, Eq, PartialEq)]
+pub struct DocgenInfo {
+    /// Title of the index page. Defaults to the package name.
+    pub title: Option<String>,
+    /// Path of a logo shown at the top of the index page, relative to the package root
+    pub logo: Option<PathBuf>,
+    /// Modules listed first, in this order, in the index page's table of contents
+    pub module_order: Vec<Symbol>,
 }
 
 #[derive(Debug, Clone, Eq, PartialEq)]
@@ -84,3 +138,43 @@ pub enum SubstOrRename {
     RenameFrom(NamedAddress),
     Assign(AccountAddress),
 }
+
+impl VersionRequirement {
+    pub fn matches(&self, version: &Version) -> bool {
+        self.comparators.iter().all(|(op, bound)| match op {
+            VersionOp::Less => version < bound,
+            VersionOp::LessEq => version <= bound,
+            VersionOp::Eq => version == bound,
+            VersionOp::GreaterEq => version >= bound,
+            VersionOp::Greater => version > bound,
+        })
+    }
+}
+
+impl fmt::Display for VersionRequirement {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let comparators = self
+            .comparators
+            .iter()
+            .map(|(op, version)| format!("{}{}", op, format_version(version)))
+            .collect::<Vec<_>>();
+        write!(f, "{}", comparators.join(", "))
+    }
+}
+
+impl fmt::Display for VersionOp {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Less => write!(f, "<"),
+            Self::LessEq => write!(f, "<="),
+            Self::Eq => write!(f, "="),
+            Self::GreaterEq => write!(f, ">="),
+            Self::Greater => write!(f, ">"),
+        }
+    }
+}
+
+/// `version` as `<major>.<minor>.<bugfix>`
+pub fn format_version((major, minor, bugfix): &Version) -> String {
+    format!("{}.{}.{}", major, minor, bugfix)
+}
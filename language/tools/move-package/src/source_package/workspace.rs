@@ -0,0 +1,97 @@
+// Copyright (c) The Move Contributors
+// SPDX-License-Identifier: Apache-2.0
+
+use crate::source_package::{
+    layout::SourcePackageLayout,
+    manifest_parser::{parse_move_manifest_string, parse_workspace_info},
+};
+use anyhow::{bail, Context, Result};
+use move_command_line_common::files::read_utf8_file;
+use std::path::{Path, PathBuf};
+
+/// Packages that are built and tested together, declared by the `[workspace]` section of the
+/// manifest at the root of the workspace:
+///
+/// ```toml
+/// [workspace]
+/// members = ["core", "apps/wallet"]
+/// ```
+///
+/// The manifest at the root does not need to declare a package of its own.
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub struct Workspace {
+    /// The directory holding the manifest declaring the workspace
+    pub root: PathBuf,
+    /// The root directories of the member packages, in the order they are declared
+    pub members: Vec<PathBuf>,
+}
+
+impl Workspace {
+    /// The workspace declared by the manifest in `root`, or `None` if it declares none
+    pub fn load(root: &Path) -> Result<Option<Self>> {
+        let manifest_path = root.join(SourcePackageLayout::Manifest.path());
+        let contents = read_utf8_file(&manifest_path)?;
+        let info = match parse_workspace_info(&parse_move_manifest_string(contents)?)
+            .with_context(|| format!("Error parsing '{}'", manifest_path.display()))?
+        {
+            Some(info) => info,
+            None => return Ok(None),
+        };
+
+        let mut members = vec![];
+        for member in info.members {
+            let member_path = root.join(&member);
+            if !member_path
+                .join(SourcePackageLayout::Manifest.path())
+                .is_file()
+            {
+                bail!(
+                    "Workspace member '{}' declared in '{}' is not a package: it has no {}",
+                    member.display(),
+                    manifest_path.display(),
+                    SourcePackageLayout::Manifest.location_str()
+                )
+            }
+            members.push(member_path);
+        }
+        Ok(Some(Self {
+            root: root.to_path_buf(),
+            members,
+        }))
+    }
+
+    /// The workspace declared in `path` or in the closest of its parents that declares one
+    pub fn find(path: &Path) -> Result<Self> {
+        match Self::find_containing(path)? {
+            Some(workspace) => Ok(workspace),
+            None => bail!(
+                "Unable to find a manifest declaring a '[workspace]' in '{}' or in its parents",
+                path.display()
+            ),
+        }
+    }
+
+    /// Like `find`, but `None` if neither `path` nor its parents declare a workspace
+    pub fn find_containing(path: &Path) -> Result<Option<Self>> {
+        let path = path
+            .canonicalize()
+            .with_context(|| format!("Unable to find '{}'", path.display()))?;
+        for dir in path.ancestors() {
+            if dir.join(SourcePackageLayout::Manifest.path()).is_file() {
+                if let Some(workspace) = Self::load(dir)? {
+                    return Ok(Some(workspace));
+                }
+            }
+        }
+        Ok(None)
+    }
+
+    /// The path of `member` relative to the root of the workspace
+    pub fn member_name(&self, member: &Path) -> String {
+        match member.strip_prefix(&self.root) {
+            Ok(relative) if relative.as_os_str().is_empty() => ".".to_string(),
+            Ok(relative) => relative.to_string_lossy().replace('\\', "/"),
+            Err(_) => member.to_string_lossy().to_string(),
+        }
+    }
+}
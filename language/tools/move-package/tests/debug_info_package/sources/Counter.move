@@ -0,0 +1,11 @@
+module 0x2::Counter {
+    struct Counter<phantom CoinType> has key { value: u64 }
+
+    public fun add_to_counter<CoinType>(counter_address: address, amount_to_add: u64): u64
+    acquires Counter {
+        let counter = borrow_global_mut<Counter<CoinType>>(counter_address);
+        let updated_value = counter.value + amount_to_add;
+        counter.value = updated_value;
+        updated_value
+    }
+}
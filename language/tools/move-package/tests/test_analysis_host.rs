@@ -0,0 +1,191 @@
+// Copyright (c) The Move Contributors
+// SPDX-License-Identifier: Apache-2.0
+
+use move_command_line_common::json_errors::Level;
+use move_package::{compilation::analysis::AnalysisHost, BuildConfig};
+use std::{fs, path::Path};
+use tempfile::{tempdir, TempDir};
+
+/// Write a `Root` package depending on a `Dep` package under a new temporary directory. `B` uses
+/// `A`, and `C` only uses the dependency.
+fn write_packages() -> TempDir {
+    let dir = tempdir().unwrap();
+    let dep = dir.path().join("dep");
+    fs::create_dir_all(dep.join("sources")).unwrap();
+    fs::write(
+        dep.join("Move.toml"),
+        "[package]\nname = \"Dep\"\nversion = \"0.0.0\"\n",
+    )
+    .unwrap();
+    fs::write(
+        dep.join("sources").join("D.move"),
+        "module 0x2::D {\n    public fun one(): u64 { 1 }\n}\n",
+    )
+    .unwrap();
+
+    let root = dir.path().join("root");
+    fs::create_dir_all(root.join("sources")).unwrap();
+    fs::write(
+        root.join("Move.toml"),
+        "[package]\nname = \"Root\"\nversion = \"0.0.0\"\n\n\
+         [dependencies]\nDep = { local = \"../dep\" }\n",
+    )
+    .unwrap();
+    let sources = root.join("sources");
+    fs::write(
+        sources.join("A.move"),
+        "module 0x2::A {\n    public fun two(): u64 { 2 }\n}\n",
+    )
+    .unwrap();
+    fs::write(
+        sources.join("B.move"),
+        "module 0x2::B {\n    use 0x2::A;\n\n    public fun four(): u64 { A::two() * 2 }\n}\n",
+    )
+    .unwrap();
+    fs::write(
+        sources.join("C.move"),
+        "module 0x2::C {\n    use 0x2::D;\n\n    public fun three(): u64 { D::one() * 3 }\n}\n",
+    )
+    .unwrap();
+    dir
+}
+
+fn analysis_host(dir: &TempDir) -> AnalysisHost {
+    let graph = BuildConfig::default()
+        .resolution_graph_for_package(&dir.path().join("root"))
+        .unwrap();
+    AnalysisHost::new(graph).unwrap()
+}
+
+fn file_names(paths: &[impl AsRef<Path>]) -> Vec<String> {
+    paths
+        .iter()
+        .map(|path| {
+            let name = path.as_ref().file_name().unwrap();
+            name.to_string_lossy().to_string()
+        })
+        .collect()
+}
+
+#[test]
+fn check_file_only_checks_the_files_depending_on_the_change() {
+    let dir = write_packages();
+    let mut host = analysis_host(&dir);
+    let a = dir.path().join("root").join("sources").join("A.move");
+
+    let result = host.check_file(&a).unwrap();
+    assert!(result.diagnostics.is_empty());
+    assert_eq!(
+        file_names(&host.stats().checked_files),
+        ["A.move", "B.move"]
+    );
+    assert_eq!(host.stats().checked_modules, ["0x2::A", "0x2::B"]);
+    assert_eq!(host.stats().library_builds, 1);
+
+    // an unsaved edit with an error is reported under the path of the file on disk
+    host.set_overlay(
+        &a,
+        "module 0x2::A {\n    public fun two(): u64 { true }\n}\n".to_string(),
+    )
+    .unwrap();
+    let result = host.check_file(&a).unwrap();
+    assert!(result.has_errors());
+    let diagnostics = result.json_diagnostics();
+    assert!(diagnostics.iter().all(|diag| diag.level == Level::Error));
+    let files: Vec<_> = diagnostics
+        .iter()
+        .map(|diag| diag.file.clone().unwrap())
+        .collect();
+    assert_eq!(file_names(&files), ["A.move"]);
+    assert_eq!(
+        Path::new(&files[0]).canonicalize().unwrap(),
+        a.canonicalize().unwrap()
+    );
+    assert_eq!(diagnostics[0].line, Some(2));
+    // the dependency and `C` did not change, so they are not compiled again
+    assert_eq!(
+        file_names(&host.stats().checked_files),
+        ["A.move", "B.move"]
+    );
+    assert_eq!(host.stats().library_builds, 1);
+
+    host.remove_overlay(&a);
+    assert!(host.check_file(&a).unwrap().diagnostics.is_empty());
+    assert_eq!(host.stats().library_builds, 1);
+}
+
+#[test]
+fn check_file_compiles_the_library_again_when_it_changes() {
+    let dir = write_packages();
+    let mut host = analysis_host(&dir);
+    let sources = dir.path().join("root").join("sources");
+
+    host.check_file(&sources.join("C.move")).unwrap();
+    assert_eq!(file_names(&host.stats().checked_files), ["C.move"]);
+    assert_eq!(host.stats().checked_modules, ["0x2::C"]);
+    assert_eq!(host.stats().library_builds, 1);
+
+    // `A` is in the library of `C`
+    host.set_overlay(
+        &sources.join("A.move"),
+        "module 0x2::A {\n    public fun two(): u64 { 1 + 1 }\n}\n".to_string(),
+    )
+    .unwrap();
+    assert!(host
+        .check_file(&sources.join("C.move"))
+        .unwrap()
+        .diagnostics
+        .is_empty());
+    assert_eq!(host.stats().library_builds, 2);
+}
+
+#[test]
+fn check_file_checks_the_whole_package_if_the_library_fails_to_compile() {
+    let dir = write_packages();
+    let mut host = analysis_host(&dir);
+    let sources = dir.path().join("root").join("sources");
+    host.set_overlay(
+        &sources.join("C.move"),
+        "module 0x2::C {\n    public fun three(): u64 { false }\n}\n".to_string(),
+    )
+    .unwrap();
+
+    let result = host.check_file(&sources.join("A.move")).unwrap();
+    assert!(result.has_errors());
+    let files: Vec<_> = result
+        .json_diagnostics()
+        .into_iter()
+        .map(|diag| diag.file.unwrap())
+        .collect();
+    assert_eq!(file_names(&files), ["C.move"]);
+    assert_eq!(
+        file_names(&host.stats().checked_files),
+        ["A.move", "B.move", "C.move"]
+    );
+}
+
+#[test]
+fn check_file_rejects_files_outside_the_package_sources() {
+    let dir = write_packages();
+    let mut host = analysis_host(&dir);
+    let dep_file = dir.path().join("dep").join("sources").join("D.move");
+    let err = host.check_file(&dep_file).unwrap_err();
+    assert!(
+        err.to_string()
+            .contains("is not a source file of package 'Root'"),
+        "{}",
+        err
+    );
+}
+
+#[test]
+fn check_all_checks_every_file_of_the_package() {
+    let dir = write_packages();
+    let mut host = analysis_host(&dir);
+    assert!(host.check_all().unwrap().diagnostics.is_empty());
+    assert_eq!(
+        file_names(&host.stats().checked_files),
+        ["A.move", "B.move", "C.move"]
+    );
+    assert_eq!(host.stats().checked_modules, ["0x2::A", "0x2::B", "0x2::C"]);
+}
@@ -0,0 +1,109 @@
+// Copyright (c) The Move Contributors
+// SPDX-License-Identifier: Apache-2.0
+
+use move_binary_format::file_format::FunctionDefinitionIndex;
+use move_package::{
+    compilation::{
+        compiled_package::{CompiledPackage, OnDiskCompiledPackage},
+        package_layout::CompiledPackageLayout,
+    },
+    BuildConfig, DebugInfo,
+};
+use std::path::{Path, PathBuf};
+use tempfile::{tempdir, TempDir};
+use walkdir::WalkDir;
+
+const PACKAGE_PATH: &str = "tests/debug_info_package";
+
+fn build(debug_info: Option<DebugInfo>, release: bool) -> (TempDir, CompiledPackage) {
+    let dir = tempdir().unwrap();
+    let package = BuildConfig {
+        install_dir: Some(dir.path().to_path_buf()),
+        debug_info,
+        release,
+        ..Default::default()
+    }
+    .compile_package(Path::new(PACKAGE_PATH), &mut Vec::new())
+    .unwrap();
+    (dir, package)
+}
+
+fn package_dir(dir: &TempDir) -> PathBuf {
+    dir.path()
+        .join(CompiledPackageLayout::Root.path())
+        .join("DebugInfo")
+}
+
+fn artifacts_size(dir: &TempDir) -> u64 {
+    WalkDir::new(package_dir(dir))
+        .into_iter()
+        .map(|entry| entry.unwrap().metadata().unwrap())
+        .filter(|metadata| metadata.is_file())
+        .map(|metadata| metadata.len())
+        .sum()
+}
+
+fn parameter_names(package: &CompiledPackage) -> Vec<String> {
+    let unit = package.root_modules().next().unwrap();
+    unit.unit
+        .source_map()
+        .get_function_source_map(FunctionDefinitionIndex(0))
+        .unwrap()
+        .parameters
+        .iter()
+        .map(|(name, _)| name.clone())
+        .collect()
+}
+
+#[test]
+fn release_builds_default_to_line_only_debug_info() {
+    let config = BuildConfig::default();
+    assert_eq!(config.debug_info(), DebugInfo::Full);
+    let config = BuildConfig {
+        release: true,
+        ..Default::default()
+    };
+    assert_eq!(config.debug_info(), DebugInfo::LineOnly);
+    let config = BuildConfig {
+        release: true,
+        debug_info: Some(DebugInfo::None),
+        ..Default::default()
+    };
+    assert_eq!(config.debug_info(), DebugInfo::None);
+}
+
+#[test]
+fn debug_info_controls_source_maps_of_artifacts() {
+    let source_map_path = |dir: &TempDir| {
+        package_dir(dir)
+            .join(CompiledPackageLayout::SourceMaps.path())
+            .join("Counter.mvsm")
+    };
+
+    let (full_dir, full) = build(None, false);
+    assert!(source_map_path(&full_dir).is_file());
+    assert_eq!(
+        parameter_names(&full),
+        vec!["counter_address", "amount_to_add"]
+    );
+
+    let (line_only_dir, line_only) = build(None, true);
+    assert!(source_map_path(&line_only_dir).is_file());
+    assert_eq!(parameter_names(&line_only), vec!["Arg0", "Arg1"]);
+    let reloaded = OnDiskCompiledPackage::from_path(&package_dir(&line_only_dir))
+        .unwrap()
+        .into_compiled_package()
+        .unwrap();
+    assert_eq!(parameter_names(&reloaded), vec!["Arg0", "Arg1"]);
+
+    let (none_dir, none) = build(Some(DebugInfo::None), false);
+    assert!(!source_map_path(&none_dir).exists());
+    assert!(artifacts_size(&none_dir) < artifacts_size(&full_dir));
+    assert!(none.ensure_source_maps("Coverage").is_err());
+    // packages are loaded from disk with generated source maps in place of the missing ones
+    let reloaded = OnDiskCompiledPackage::from_path(&package_dir(&none_dir))
+        .unwrap()
+        .into_compiled_package()
+        .unwrap();
+    assert_eq!(parameter_names(&reloaded), vec!["Arg0", "Arg1"]);
+}
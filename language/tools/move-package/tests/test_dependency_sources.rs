@@ -0,0 +1,74 @@
+// Copyright (c) The Move Contributors
+// SPDX-License-Identifier: Apache-2.0
+
+use move_package::{
+    compilation::{compiled_package::OnDiskCompiledPackage, package_layout::CompiledPackageLayout},
+    resolution::lockfile::Lockfile,
+    BuildConfig,
+};
+use sha2::{Digest, Sha256};
+use std::{fs, path::Path};
+use tempfile::tempdir;
+use walkdir::WalkDir;
+
+const PACKAGE: &str = "tests/test_sources/compilation/diamond_problem_no_conflict";
+
+/// The digest of the manifest and the Move sources of the package at `path`, computed the way it
+/// is documented rather than with the package system
+fn expected_digest(path: &Path) -> String {
+    let mut file_hashes: Vec<_> = WalkDir::new(path.join("sources"))
+        .into_iter()
+        .map(|entry| entry.unwrap())
+        .filter(|entry| entry.path().extension().map_or(false, |ext| ext == "move"))
+        .map(|entry| entry.into_path())
+        .chain(std::iter::once(path.join("Move.toml")))
+        .map(|file| format!("{:X}", Sha256::digest(&fs::read(file).unwrap())))
+        .collect();
+    file_hashes.sort();
+    let mut hasher = Sha256::new();
+    for file_hash in file_hashes {
+        hasher.update(file_hash.as_bytes());
+    }
+    format!("{:X}", hasher.finalize())
+}
+
+#[test]
+fn build_info_records_dependency_sources() {
+    let dir = tempdir().unwrap();
+    let lock_file = dir.path().join("Move.lock");
+    let package = BuildConfig {
+        install_dir: Some(dir.path().to_path_buf()),
+        lock_file: Some(lock_file.clone()),
+        ..Default::default()
+    }
+    .compile_package(Path::new(PACKAGE), &mut Vec::new())
+    .unwrap();
+
+    let on_disk = OnDiskCompiledPackage::from_path(
+        &dir.path()
+            .join(CompiledPackageLayout::Root.path())
+            .join("Root"),
+    )
+    .unwrap();
+    let sources = &on_disk.package.dependency_sources;
+    assert_eq!(sources, &package.dependency_sources);
+    let names: Vec<_> = sources
+        .iter()
+        .map(|source| source.locked.name.as_str())
+        .collect();
+    assert_eq!(names, vec!["A", "B", "C"]);
+
+    let lockfile = Lockfile::read(&lock_file).unwrap().unwrap();
+    for (source, locked) in sources.iter().zip(&lockfile.dependencies) {
+        let path = Path::new(PACKAGE)
+            .join("deps_only")
+            .join(&source.locked.name);
+        assert_eq!(
+            source.path.canonicalize().unwrap(),
+            path.canonicalize().unwrap()
+        );
+        assert_eq!(source.locked.digest, Some(expected_digest(&path)));
+        // the digests are the ones the lockfile verifies
+        assert_eq!(&source.locked, locked);
+    }
+}
@@ -0,0 +1,59 @@
+// Copyright (c) The Move Contributors
+// SPDX-License-Identifier: Apache-2.0
+
+use move_package::resolution::registry::{
+    find_package, publish_archive, read_index, write_index, IndexEntry, RegistrySource,
+};
+use sha2::{Digest, Sha256};
+use std::fs;
+
+#[test]
+fn published_archives_are_found_in_the_index() {
+    let registry = tempfile::tempdir().unwrap();
+    let archive = registry.path().join("upload.tar.gz");
+    fs::write(&archive, b"not really an archive").unwrap();
+    let mut other = serde_json::Map::new();
+    other.insert("owner".to_string(), "alice".into());
+    let copy = publish_archive(registry.path(), "coin", "1.2.0", &archive, other).unwrap();
+    assert_eq!(copy, registry.path().join("coin/1.2.0/coin-1.2.0.tar.gz"));
+    let err = publish_archive(
+        registry.path(),
+        "Coin",
+        "1.2.0",
+        &archive,
+        serde_json::Map::new(),
+    )
+    .unwrap_err();
+    assert!(
+        err.to_string().contains("already has version 1.2.0"),
+        "{}",
+        err
+    );
+
+    // a version held as sources, listed by hand
+    fs::create_dir_all(registry.path().join("wallet/0.1.0")).unwrap();
+    let mut entries = read_index(registry.path()).unwrap();
+    assert_eq!(entries[0].other["owner"], "alice");
+    entries.push(IndexEntry {
+        name: "wallet".to_string(),
+        version: "0.1.0".to_string(),
+        archive: None,
+        sha256: None,
+        other: serde_json::Map::new(),
+    });
+    write_index(registry.path(), &entries).unwrap();
+
+    assert_eq!(
+        find_package(registry.path(), "COIN", &(1, 2, 0)).unwrap(),
+        RegistrySource::Archive {
+            path: copy,
+            sha256: format!("{:x}", Sha256::digest(b"not really an archive")),
+        }
+    );
+    assert_eq!(
+        find_package(registry.path(), "wallet", &(0, 1, 0)).unwrap(),
+        RegistrySource::Sources(registry.path().join("wallet/0.1.0"))
+    );
+    let err = find_package(registry.path(), "coin", &(2, 0, 0)).unwrap_err();
+    assert!(err.to_string().contains("only 1.2.0"), "{}", err);
+}
@@ -0,0 +1,133 @@
+// Copyright (c) The Move Contributors
+// SPDX-License-Identifier: Apache-2.0
+
+use move_package::{
+    resolution::lockfile::{Lockfile, LockfileVersion},
+    BuildConfig,
+};
+use std::{fs, path::Path};
+use tempfile::tempdir;
+
+const PACKAGE: &str = "tests/test_sources/resolution/diamond_problem_no_conflict";
+
+fn resolve(
+    lock_file: &Path,
+    lockfile_version: Option<LockfileVersion>,
+    locked: bool,
+) -> anyhow::Result<()> {
+    BuildConfig {
+        install_dir: Some(tempdir().unwrap().path().to_path_buf()),
+        lock_file: Some(lock_file.to_path_buf()),
+        lockfile_version,
+        locked,
+        ..Default::default()
+    }
+    .resolution_graph_for_package(Path::new(PACKAGE))
+    .map(|_| ())
+}
+
+#[test]
+fn writes_latest_version_by_default() {
+    let dir = tempdir().unwrap();
+    let lock_file = dir.path().join("Move.lock");
+    resolve(&lock_file, None, false).unwrap();
+
+    let lockfile = Lockfile::read(&lock_file).unwrap().unwrap();
+    assert_eq!(lockfile.version, LockfileVersion::V2);
+    let names: Vec<_> = lockfile
+        .dependencies
+        .iter()
+        .map(|dep| dep.name.as_str())
+        .collect();
+    assert_eq!(names, vec!["A", "B", "C"]);
+    let c = &lockfile.dependencies[2];
+    // C is depended on by both A and B
+    assert_eq!(c.parent.as_deref(), Some("A"));
+    assert_eq!(c.local.as_deref(), Some("../C"));
+    assert!(c.digest.is_some());
+
+    // writing is deterministic, and what is read back renders to the same file
+    let contents = fs::read_to_string(&lock_file).unwrap();
+    assert_eq!(lockfile.render().unwrap(), contents);
+    resolve(&lock_file, None, false).unwrap();
+    assert_eq!(fs::read_to_string(&lock_file).unwrap(), contents);
+}
+
+#[test]
+fn round_trips_version_1() {
+    let dir = tempdir().unwrap();
+    let lock_file = dir.path().join("Move.lock");
+    resolve(&lock_file, Some(LockfileVersion::V1), false).unwrap();
+
+    let contents = fs::read_to_string(&lock_file).unwrap();
+    assert!(!contents.contains("[lockfile]"));
+    let lockfile = Lockfile::parse(&contents).unwrap();
+    assert_eq!(lockfile.version, LockfileVersion::V1);
+    assert!(lockfile
+        .dependencies
+        .iter()
+        .all(|dep| dep.digest.is_none() && dep.parent.is_none()));
+    assert_eq!(lockfile.render().unwrap(), contents);
+}
+
+#[test]
+fn migrates_version_1_to_version_2() {
+    let dir = tempdir().unwrap();
+    let lock_file = dir.path().join("Move.lock");
+    resolve(&lock_file, Some(LockfileVersion::V1), false).unwrap();
+    let v1 = Lockfile::read(&lock_file).unwrap().unwrap();
+
+    resolve(&lock_file, None, false).unwrap();
+    let v2 = Lockfile::read(&lock_file).unwrap().unwrap();
+    assert_eq!(v2.version, LockfileVersion::V2);
+    // the sources recorded by version 1 carry over
+    for (old, new) in v1.dependencies.iter().zip(&v2.dependencies) {
+        assert_eq!((&old.name, &old.local), (&new.name, &new.local));
+    }
+
+    // and a version 2 lockfile can be downgraded for older readers
+    resolve(&lock_file, Some(LockfileVersion::V1), false).unwrap();
+    assert_eq!(Lockfile::read(&lock_file).unwrap().unwrap(), v1);
+}
+
+#[test]
+fn rejects_unknown_versions() {
+    let err = Lockfile::parse("[lockfile]\nversion = 3\n").unwrap_err();
+    assert!(err.to_string().contains("version 3 is not supported"));
+}
+
+#[test]
+fn locked_accepts_up_to_date_lockfiles_of_each_version() {
+    for version in [LockfileVersion::V1, LockfileVersion::V2] {
+        let dir = tempdir().unwrap();
+        let lock_file = dir.path().join("Move.lock");
+        resolve(&lock_file, Some(version), false).unwrap();
+        let contents = fs::read_to_string(&lock_file).unwrap();
+
+        // a locked build neither migrates nor rewrites the lockfile
+        resolve(&lock_file, None, true).unwrap();
+        assert_eq!(fs::read_to_string(&lock_file).unwrap(), contents);
+    }
+}
+
+#[test]
+fn locked_rejects_stale_lockfiles_of_each_version() {
+    for version in [LockfileVersion::V1, LockfileVersion::V2] {
+        let dir = tempdir().unwrap();
+        let lock_file = dir.path().join("Move.lock");
+        resolve(&lock_file, Some(version), false).unwrap();
+        let stale = fs::read_to_string(&lock_file)
+            .unwrap()
+            .replace("./deps_only/A", "./deps_only/Z");
+        fs::write(&lock_file, &stale).unwrap();
+
+        let err = resolve(&lock_file, None, true).unwrap_err();
+        assert!(err.to_string().contains("--locked"), "{}", err);
+        assert_eq!(fs::read_to_string(&lock_file).unwrap(), stale);
+    }
+
+    let dir = tempdir().unwrap();
+    let missing = dir.path().join("Move.lock");
+    assert!(resolve(&missing, None, true).is_err());
+    assert!(!missing.exists());
+}
@@ -0,0 +1,89 @@
+// Copyright (c) The Move Contributors
+// SPDX-License-Identifier: Apache-2.0
+
+use move_command_line_common::files::InvalidUtf8File;
+use move_package::{source_package::manifest_parser::parse_move_manifest_from_file, BuildConfig};
+use std::{fs, path::Path};
+use tempfile::tempdir;
+
+const BOM: &[u8] = b"\xef\xbb\xbf";
+
+fn write_package(path: &Path, manifest: &[u8]) {
+    fs::create_dir_all(path.join("sources")).unwrap();
+    fs::write(path.join("Move.toml"), manifest).unwrap();
+}
+
+#[test]
+fn manifests_starting_with_a_bom_are_parsed() {
+    let dir = tempdir().unwrap();
+    let dep = dir.path().join("dep");
+    write_package(
+        &dep,
+        &[BOM, b"[package]\nname = \"Dep\"\nversion = \"0.0.0\"\n"].concat(),
+    );
+    let root = dir.path().join("root");
+    write_package(
+        &root,
+        &[
+            BOM,
+            b"[package]\nname = \"Root\"\nversion = \"0.0.0\"\n\n",
+            b"[dependencies]\nDep = { local = \"../dep\" }\n",
+        ]
+        .concat(),
+    );
+
+    let manifest = parse_move_manifest_from_file(&root).unwrap();
+    assert_eq!(manifest.package.name.as_str(), "Root");
+    // the manifests of dependencies are read the same way
+    let graph = BuildConfig {
+        install_dir: Some(dir.path().join("build")),
+        ..Default::default()
+    }
+    .resolution_graph_for_package(&root)
+    .unwrap();
+    assert!(graph
+        .package_table
+        .keys()
+        .any(|name| name.as_str() == "Dep"));
+}
+
+#[test]
+fn manifests_with_invalid_utf8_report_the_offending_offset() {
+    let dir = tempdir().unwrap();
+    let root = dir.path().join("root");
+    // the name ends with the first byte of a two-byte sequence
+    let manifest = b"[package]\nname = \"Caf\xc3\"\nversion = \"0.0.0\"\n";
+    write_package(&root, manifest);
+
+    let err = parse_move_manifest_from_file(&root).unwrap_err();
+    let invalid = err.downcast_ref::<InvalidUtf8File>().unwrap();
+    assert_eq!(invalid.path, root.join("Move.toml"));
+    assert_eq!(invalid.offset, 21);
+    assert_eq!(
+        err.to_string(),
+        format!(
+            "'{}' is not valid UTF-8: invalid byte sequence at byte offset 21",
+            root.join("Move.toml").display()
+        )
+    );
+
+    // also when the manifest is that of a dependency
+    let dependent = dir.path().join("dependent");
+    write_package(
+        &dependent,
+        b"[package]\nname = \"Dependent\"\nversion = \"0.0.0\"\n\n\
+          [dependencies]\nRoot = { local = \"../root\" }\n",
+    );
+    let err = BuildConfig {
+        install_dir: Some(dir.path().join("build")),
+        ..Default::default()
+    }
+    .resolution_graph_for_package(&dependent)
+    .unwrap_err();
+    assert!(
+        format!("{:#}", err)
+            .contains("is not valid UTF-8: invalid byte sequence at byte offset 21"),
+        "{:#}",
+        err
+    );
+}
@@ -0,0 +1,164 @@
+// Copyright (c) The Move Contributors
+// SPDX-License-Identifier: Apache-2.0
+
+use move_command_line_common::files::PathPrefixRemap;
+use move_compiler::diagnostics::{remap_file_names, report_diagnostics_to_buffer};
+use move_package::{compilation::package_layout::CompiledPackageLayout, BuildConfig};
+use std::{fs, path::Path};
+use tempfile::{tempdir, TempDir};
+use walkdir::WalkDir;
+
+/// Write a `Dep` package and a `Root` package depending on it under a new temporary directory
+fn write_packages(root_build_section: &str) -> TempDir {
+    let dir = tempdir().unwrap();
+    let dep = dir.path().join("dep");
+    fs::create_dir_all(dep.join("sources")).unwrap();
+    fs::write(
+        dep.join("Move.toml"),
+        "[package]\nname = \"Dep\"\nversion = \"0.0.0\"\n",
+    )
+    .unwrap();
+    fs::write(
+        dep.join("sources").join("B.move"),
+        "module 0x2::B {\n    struct Thing has drop {}\n\n    \
+         public fun make(): Thing { Thing {} }\n}\n",
+    )
+    .unwrap();
+
+    let root = dir.path().join("root");
+    fs::create_dir_all(root.join("sources")).unwrap();
+    fs::write(
+        root.join("Move.toml"),
+        format!(
+            "[package]\nname = \"Root\"\nversion = \"0.0.0\"\n\n{}\
+             [dependencies]\nDep = {{ local = \"../dep\" }}\n",
+            root_build_section
+        ),
+    )
+    .unwrap();
+    fs::write(
+        root.join("sources").join("A.move"),
+        "module 0x2::A {\n    use 0x2::B;\n\n    \
+         /// Make a thing of the dependency\n    \
+         public fun make(): B::Thing { B::make() }\n}\n",
+    )
+    .unwrap();
+    dir
+}
+
+fn config(dir: &TempDir, remap: &str) -> BuildConfig {
+    BuildConfig {
+        install_dir: Some(dir.path().join("out")),
+        generate_docs: true,
+        generate_abis: true,
+        remap_path_prefix: vec![PathPrefixRemap::parse(remap).unwrap()],
+        ..Default::default()
+    }
+}
+
+/// Fail if any artifact built under `dir` mentions `prefix`
+fn assert_artifacts_do_not_mention(dir: &Path, prefix: &str) {
+    let build_dir = dir.join("out").join(CompiledPackageLayout::Root.path());
+    let mut files = 0;
+    for entry in WalkDir::new(&build_dir) {
+        let entry = entry.unwrap();
+        if !entry.file_type().is_file() {
+            continue;
+        }
+        files += 1;
+        let contents = String::from_utf8_lossy(&fs::read(entry.path()).unwrap()).to_string();
+        assert!(
+            !contents.contains(prefix),
+            "{} mentions {}:\n{}",
+            entry.path().display(),
+            prefix,
+            contents
+        );
+    }
+    assert!(files > 0, "nothing was built in {}", build_dir.display());
+}
+
+#[test]
+fn artifacts_do_not_mention_remapped_prefixes() {
+    let dir = write_packages("");
+    let prefix = dir.path().to_string_lossy().to_string();
+    let remap = format!("{}=/pkg", prefix);
+    // build the dependency on its own first, so that the docs of the root package link to its docs
+    config(&dir, &remap)
+        .compile_package(&dir.path().join("dep"), &mut Vec::new())
+        .unwrap();
+    let package = config(&dir, &remap)
+        .compile_package(&dir.path().join("root"), &mut Vec::new())
+        .unwrap();
+
+    assert_artifacts_do_not_mention(dir.path(), &prefix);
+    let build_info = fs::read_to_string(
+        dir.path()
+            .join("out")
+            .join(CompiledPackageLayout::Root.path())
+            .join("Root")
+            .join(CompiledPackageLayout::BuildInfo.path()),
+    )
+    .unwrap();
+    assert!(build_info.contains("/pkg/out"), "{}", build_info);
+
+    // the compiled units still point at the local sources
+    for unit in package.all_compiled_units_with_source() {
+        assert!(unit.source_path.starts_with(dir.path()));
+        assert!(unit.source_path.is_file());
+    }
+}
+
+#[test]
+fn diagnostics_show_remapped_file_names() {
+    let dir = write_packages("");
+    fs::write(
+        dir.path().join("root").join("sources").join("A.move"),
+        "module 0x2::A {\n    public fun f(): u64 { true }\n}\n",
+    )
+    .unwrap();
+    let prefix = dir.path().to_string_lossy().to_string();
+    let mut report = vec![];
+    config(&dir, &format!("{}=/pkg", prefix))
+        .compile_package_with_driver(&dir.path().join("root"), &mut Vec::new(), |compiler| {
+            let remaps = compiler.flags().path_prefix_remaps().to_vec();
+            let (files, units) = compiler.build()?;
+            // the files keep their local names, which are only remapped when shown
+            let mut names = files.values().map(|(name, _)| name.as_str());
+            assert!(names.all(|name| name.starts_with(&prefix)));
+            match units {
+                Ok(_) => panic!("expected a compilation error"),
+                Err(diags) => {
+                    report = report_diagnostics_to_buffer(&remap_file_names(&files, &remaps), diags)
+                }
+            }
+            anyhow::bail!("Compilation error")
+        })
+        .unwrap_err();
+
+    let report = String::from_utf8(report).unwrap();
+    assert!(report.contains("/pkg/root/sources/A.move"), "{}", report);
+    assert!(!report.contains(&prefix), "{}", report);
+}
+
+#[test]
+fn manifest_remaps_are_relative_to_the_package() {
+    let dir = write_packages("[build]\nremap_path_prefix = [\"..=/work\"]\n\n");
+    let prefix = dir.path().to_string_lossy().to_string();
+    // the mappings of the command line apply along with those of the manifest
+    config(&dir, &format!("{}/out=/out", prefix))
+        .compile_package(&dir.path().join("root"), &mut Vec::new())
+        .unwrap();
+
+    assert_artifacts_do_not_mention(dir.path(), &prefix);
+    let build_info = fs::read_to_string(
+        dir.path()
+            .join("out")
+            .join(CompiledPackageLayout::Root.path())
+            .join("Root")
+            .join(CompiledPackageLayout::BuildInfo.path()),
+    )
+    .unwrap();
+    assert!(build_info.contains("install_dir: /out"), "{}", build_info);
+    assert!(build_info.contains("/work/dep"), "{}", build_info);
+}
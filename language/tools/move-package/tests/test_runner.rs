@@ -57,6 +57,8 @@ pub fn run_test(path: &Path) -> datatest_stable::Result<()> {
                     generate_abis: false,
                     install_dir: Some(tempdir().unwrap().path().to_path_buf()),
                     force_recompilation: false,
+                    // the `move-version` fixtures are checked against this version
+                    move_tooling_version: Some((1, 5, 0)),
                     ..Default::default()
                 },
             )
@@ -78,6 +80,9 @@ pub fn run_test(path: &Path) -> datatest_stable::Result<()> {
                         Some(PackageDigest::from("ELIDED_FOR_TEST"));
                     pkg.compiled_package_info.build_flags.install_dir =
                         Some(PathBuf::from("ELIDED_FOR_TEST"));
+                    for file in pkg.compiled_package_info.source_files.iter_mut().flatten() {
+                        file.modified = 0;
+                    }
                     format!("{:#?}\n", pkg.compiled_package_info)
                 }
                 Err(error) => format!("{:#}\n", error),
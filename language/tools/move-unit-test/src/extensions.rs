@@ -6,6 +6,7 @@
 //! Such extensions are enabled by cfg features and must be compiled into the test
 //! to be usable.
 
+use move_stdlib::natives::timestamp::NativeClockContext;
 use move_vm_runtime::native_extensions::NativeContextExtensions;
 use once_cell::sync::Lazy;
 use std::{fmt::Write, sync::Mutex};
@@ -37,13 +38,15 @@ pub fn set_extension_hook(p: Box<dyn Fn(&mut NativeContextExtensions<'_>) + Send
     *EXTENSION_HOOK.lock().unwrap() = Some(p)
 }
 
-/// Create all available native context extensions.
+/// Create all available native context extensions, with `clock` for the natives of
+/// `std::timestamp`.
 #[allow(unused_mut, clippy::let_and_return)]
-pub(crate) fn new_extensions<'a>() -> NativeContextExtensions<'a> {
+pub(crate) fn new_extensions<'a>(clock: NativeClockContext) -> NativeContextExtensions<'a> {
     let mut e = NativeContextExtensions::default();
     if let Some(h) = &*EXTENSION_HOOK.lock().unwrap() {
         (*h)(&mut e)
     }
+    e.add(clock);
     #[cfg(feature = "table-extension")]
     create_table_extension(&mut e);
     e
@@ -109,7 +112,7 @@ mod tests {
     #[test]
     fn test_extension_hook() {
         set_extension_hook(Box::new(my_hook));
-        let ext = new_extensions();
+        let ext = new_extensions(Default::default());
         let _e = ext.get::<TestExtension>();
     }
 
@@ -0,0 +1,337 @@
+// Copyright (c) The Move Contributors
+// SPDX-License-Identifier: Apache-2.0
+
+use move_binary_format::errors::PartialVMResult;
+use move_core_types::{
+    gas_algebra::{InternalGas, NumArgs, NumBytes},
+    language_storage::ModuleId,
+};
+use move_vm_test_utils::gas_schedule::GasStatus;
+use move_vm_types::{
+    gas::{GasMeter, SimpleInstruction},
+    views::{TypeView, ValueView},
+};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+
+/// The cost of the calls a test made to one function
+#[derive(Debug, Clone, Default, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
+pub struct CallCost {
+    pub calls: u64,
+    /// Instructions executed by the calls, including those of the functions they called
+    pub instructions: u64,
+    /// Gas used by the calls, in internal gas units
+    pub gas: u64,
+}
+
+/// The cost of a test: of all of it, and of the calls it made itself, by function called
+#[derive(Debug, Clone, Default, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
+pub struct GasProfile {
+    pub instructions: u64,
+    /// Gas used by the test, in internal gas units
+    pub gas: u64,
+    /// The calls of the test, by function called as `<address>::<module>::<function>`
+    pub calls: BTreeMap<String, CallCost>,
+}
+
+/// A call made by the test being measured
+struct OpenCall {
+    function: String,
+    instructions: u64,
+    remaining_gas: InternalGas,
+}
+
+/// A gas meter charging `GasStatus`, which counts the instructions executed and attributes them,
+/// and the gas they use, to the calls the function executed makes
+pub(crate) struct ProfilingGasMeter<'a> {
+    gas_status: GasStatus<'a>,
+    starting_gas: InternalGas,
+    instructions: u64,
+    /// How many calls deep the execution is, the function executed being at depth 0
+    depth: usize,
+    /// The call made by the function executed that is being run
+    open_call: Option<OpenCall>,
+    /// Whether the last thing charged was a call, which is to a native function if the next is
+    /// the cost of a native function
+    after_call: bool,
+    calls: BTreeMap<String, CallCost>,
+}
+
+impl<'a> ProfilingGasMeter<'a> {
+    pub(crate) fn new(gas_status: GasStatus<'a>) -> Self {
+        Self {
+            starting_gas: gas_status.remaining_internal_gas(),
+            gas_status,
+            instructions: 0,
+            depth: 0,
+            open_call: None,
+            after_call: false,
+            calls: BTreeMap::new(),
+        }
+    }
+
+    pub(crate) fn gas_status(&self) -> &GasStatus<'a> {
+        &self.gas_status
+    }
+
+    /// What the execution cost so far. Calls that did not return, e.g. because they aborted, are
+    /// left out of the calls.
+    pub(crate) fn profile(&self) -> GasProfile {
+        GasProfile {
+            instructions: self.instructions,
+            gas: self.gas_used_since(self.starting_gas),
+            calls: self.calls.clone(),
+        }
+    }
+
+    fn gas_used_since(&self, remaining_gas: InternalGas) -> u64 {
+        remaining_gas
+            .checked_sub(self.gas_status.remaining_internal_gas())
+            .map_or(0, u64::from)
+    }
+
+    fn instruction(&mut self) {
+        self.instructions += 1;
+        self.after_call = false;
+    }
+
+    fn enter(&mut self, module_id: &ModuleId, func_name: &str) {
+        self.depth += 1;
+        if self.depth == 1 {
+            self.open_call = Some(OpenCall {
+                function: format!("{}::{}", module_id.short_str_lossless(), func_name),
+                instructions: self.instructions,
+                remaining_gas: self.gas_status.remaining_internal_gas(),
+            });
+        }
+        self.after_call = true;
+    }
+
+    fn leave(&mut self) {
+        if self.depth == 0 {
+            // the function executed returns
+            return;
+        }
+        self.depth -= 1;
+        if self.depth > 0 {
+            return;
+        }
+        if let Some(call) = self.open_call.take() {
+            let gas = self.gas_used_since(call.remaining_gas);
+            let cost = self.calls.entry(call.function).or_default();
+            cost.calls += 1;
+            cost.instructions += self.instructions - call.instructions;
+            cost.gas += gas;
+        }
+    }
+}
+
+impl<'a> GasMeter for ProfilingGasMeter<'a> {
+    fn charge_simple_instr(&mut self, instr: SimpleInstruction) -> PartialVMResult<()> {
+        self.instruction();
+        let result = self.gas_status.charge_simple_instr(instr);
+        if instr == SimpleInstruction::Ret {
+            self.leave();
+        }
+        result
+    }
+
+    fn charge_call(
+        &mut self,
+        module_id: &ModuleId,
+        func_name: &str,
+        args: impl ExactSizeIterator<Item = impl ValueView>,
+    ) -> PartialVMResult<()> {
+        self.instruction();
+        self.gas_status.charge_call(module_id, func_name, args)?;
+        self.enter(module_id, func_name);
+        Ok(())
+    }
+
+    fn charge_call_generic(
+        &mut self,
+        module_id: &ModuleId,
+        func_name: &str,
+        ty_args: impl ExactSizeIterator<Item = impl TypeView>,
+        args: impl ExactSizeIterator<Item = impl ValueView>,
+    ) -> PartialVMResult<()> {
+        self.instruction();
+        self.gas_status
+            .charge_call_generic(module_id, func_name, ty_args, args)?;
+        self.enter(module_id, func_name);
+        Ok(())
+    }
+
+    fn charge_ld_const(&mut self, size: NumBytes) -> PartialVMResult<()> {
+        self.instruction();
+        self.gas_status.charge_ld_const(size)
+    }
+
+    fn charge_copy_loc(&mut self, val: impl ValueView) -> PartialVMResult<()> {
+        self.instruction();
+        self.gas_status.charge_copy_loc(val)
+    }
+
+    fn charge_move_loc(&mut self, val: impl ValueView) -> PartialVMResult<()> {
+        self.instruction();
+        self.gas_status.charge_move_loc(val)
+    }
+
+    fn charge_store_loc(&mut self, val: impl ValueView) -> PartialVMResult<()> {
+        self.instruction();
+        self.gas_status.charge_store_loc(val)
+    }
+
+    fn charge_pack(
+        &mut self,
+        is_generic: bool,
+        args: impl ExactSizeIterator<Item = impl ValueView>,
+    ) -> PartialVMResult<()> {
+        self.instruction();
+        self.gas_status.charge_pack(is_generic, args)
+    }
+
+    fn charge_unpack(
+        &mut self,
+        is_generic: bool,
+        args: impl ExactSizeIterator<Item = impl ValueView>,
+    ) -> PartialVMResult<()> {
+        self.instruction();
+        self.gas_status.charge_unpack(is_generic, args)
+    }
+
+    fn charge_read_ref(&mut self, val: impl ValueView) -> PartialVMResult<()> {
+        self.instruction();
+        self.gas_status.charge_read_ref(val)
+    }
+
+    fn charge_write_ref(&mut self, val: impl ValueView) -> PartialVMResult<()> {
+        self.instruction();
+        self.gas_status.charge_write_ref(val)
+    }
+
+    fn charge_eq(&mut self, lhs: impl ValueView, rhs: impl ValueView) -> PartialVMResult<()> {
+        self.instruction();
+        self.gas_status.charge_eq(lhs, rhs)
+    }
+
+    fn charge_neq(&mut self, lhs: impl ValueView, rhs: impl ValueView) -> PartialVMResult<()> {
+        self.instruction();
+        self.gas_status.charge_neq(lhs, rhs)
+    }
+
+    fn charge_borrow_global(
+        &mut self,
+        is_mut: bool,
+        is_generic: bool,
+        ty: impl TypeView,
+        is_success: bool,
+    ) -> PartialVMResult<()> {
+        self.instruction();
+        self.gas_status
+            .charge_borrow_global(is_mut, is_generic, ty, is_success)
+    }
+
+    fn charge_exists(
+        &mut self,
+        is_generic: bool,
+        ty: impl TypeView,
+        exists: bool,
+    ) -> PartialVMResult<()> {
+        self.instruction();
+        self.gas_status.charge_exists(is_generic, ty, exists)
+    }
+
+    fn charge_move_from(
+        &mut self,
+        is_generic: bool,
+        ty: impl TypeView,
+        val: Option<impl ValueView>,
+    ) -> PartialVMResult<()> {
+        self.instruction();
+        self.gas_status.charge_move_from(is_generic, ty, val)
+    }
+
+    fn charge_move_to(
+        &mut self,
+        is_generic: bool,
+        ty: impl TypeView,
+        val: impl ValueView,
+        is_success: bool,
+    ) -> PartialVMResult<()> {
+        self.instruction();
+        self.gas_status
+            .charge_move_to(is_generic, ty, val, is_success)
+    }
+
+    fn charge_vec_pack<'b>(
+        &mut self,
+        ty: impl TypeView + 'b,
+        args: impl ExactSizeIterator<Item = impl ValueView>,
+    ) -> PartialVMResult<()> {
+        self.instruction();
+        self.gas_status.charge_vec_pack(ty, args)
+    }
+
+    fn charge_vec_len(&mut self, ty: impl TypeView) -> PartialVMResult<()> {
+        self.instruction();
+        self.gas_status.charge_vec_len(ty)
+    }
+
+    fn charge_vec_borrow(
+        &mut self,
+        is_mut: bool,
+        ty: impl TypeView,
+        is_success: bool,
+    ) -> PartialVMResult<()> {
+        self.instruction();
+        self.gas_status.charge_vec_borrow(is_mut, ty, is_success)
+    }
+
+    fn charge_vec_push_back(
+        &mut self,
+        ty: impl TypeView,
+        val: impl ValueView,
+    ) -> PartialVMResult<()> {
+        self.instruction();
+        self.gas_status.charge_vec_push_back(ty, val)
+    }
+
+    fn charge_vec_pop_back(
+        &mut self,
+        ty: impl TypeView,
+        val: Option<impl ValueView>,
+    ) -> PartialVMResult<()> {
+        self.instruction();
+        self.gas_status.charge_vec_pop_back(ty, val)
+    }
+
+    fn charge_vec_unpack(
+        &mut self,
+        ty: impl TypeView,
+        expect_num_elements: NumArgs,
+    ) -> PartialVMResult<()> {
+        self.instruction();
+        self.gas_status.charge_vec_unpack(ty, expect_num_elements)
+    }
+
+    fn charge_vec_swap(&mut self, ty: impl TypeView) -> PartialVMResult<()> {
+        self.instruction();
+        self.gas_status.charge_vec_swap(ty)
+    }
+
+    fn charge_load_resource(&mut self, loaded: Option<NumBytes>) -> PartialVMResult<()> {
+        self.after_call = false;
+        self.gas_status.charge_load_resource(loaded)
+    }
+
+    fn charge_native_function(&mut self, amount: InternalGas) -> PartialVMResult<()> {
+        let result = self.gas_status.charge_native_function(amount);
+        // a native function returns as soon as its cost is charged, without a `Ret`
+        if self.after_call {
+            self.after_call = false;
+            self.leave();
+        }
+        result
+    }
+}
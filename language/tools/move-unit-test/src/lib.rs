@@ -4,10 +4,14 @@
 
 pub mod cargo_runner;
 pub mod extensions;
+pub mod gas_profile;
+pub mod state_dump;
 pub mod test_reporter;
 pub mod test_runner;
 
-use crate::test_runner::TestRunner;
+use crate::{
+    state_dump::DEFAULT_STATE_DUMP_SIZE_LIMIT, test_reporter::TestResults, test_runner::TestRunner,
+};
 use clap::*;
 use move_command_line_common::files::verify_and_create_named_address_mapping;
 use move_compiler::{
@@ -18,11 +22,15 @@ use move_compiler::{
     Compiler, Flags, PASS_CFGIR,
 };
 use move_core_types::language_storage::ModuleId;
+use move_stdlib::natives::timestamp::NativeClockContext;
 use move_vm_runtime::native_functions::NativeFunctionTable;
+use move_vm_test_utils::gas_schedule::CostTable;
 use std::{
     collections::BTreeMap,
+    fs,
     io::{Result, Write},
     marker::Send,
+    path::PathBuf,
     sync::Mutex,
 };
 
@@ -72,6 +80,25 @@ pub struct UnitTestingConfig {
     #[clap(name = "global_state_on_error", short = 'g', long = "state_on_error")]
     pub report_storage_on_error: bool,
 
+    /// Dump the global storage and the events of each failing test as JSON, with the values of
+    /// resources and events decoded
+    #[clap(long = "dump-state-on-failure")]
+    pub dump_state_on_failure: bool,
+
+    /// Bound the size of each state dump, in bytes. Resources and events past the bound are left
+    /// out of the dump.
+    #[clap(
+        long = "dump-state-size-limit",
+        default_value = "1048576",
+        requires = "dump-state-on-failure"
+    )]
+    pub dump_state_size_limit: usize,
+
+    /// Write the state dumps to `<dir>/<test>/state.json` instead of the test report. The
+    /// directory is cleared first.
+    #[clap(long = "dump-state-dir", requires = "dump-state-on-failure")]
+    pub dump_state_dir: Option<PathBuf>,
+
     #[clap(
         name = "report_stacktrace_on_abort",
         short = 'r',
@@ -101,6 +128,16 @@ pub struct UnitTestingConfig {
     )]
     pub source_files: Vec<String>,
 
+    /// Time `std::timestamp` reports when each test starts, in microseconds. Tests can move it
+    /// with `std::unit_test::set_time`.
+    #[clap(long = "initial-time", default_value = "0")]
+    pub initial_time: u64,
+
+    /// Block height `std::timestamp` reports when each test starts. Tests can move it with
+    /// `std::unit_test::set_block_height`.
+    #[clap(long = "initial-height", default_value = "0")]
+    pub initial_block_height: u64,
+
     /// Use the stackless bytecode interpreter to run the tests and cross check its results with
     /// the execution result from Move VM.
     #[clap(long = "stackless")]
@@ -115,6 +152,12 @@ pub struct UnitTestingConfig {
     #[cfg(feature = "evm-backend")]
     #[clap(long = "evm")]
     pub evm: bool,
+
+    /// Meter the tests with this cost table instead of a unit one, and record the instructions
+    /// and the gas of the calls each test makes in its `gas_profile`. The execution bound is then
+    /// in gas units of this table.
+    #[clap(skip)]
+    pub gas_profile_cost_table: Option<CostTable>,
 }
 
 fn format_module_id(module_id: &ModuleId) -> String {
@@ -134,6 +177,9 @@ impl UnitTestingConfig {
             num_threads: 8,
             report_statistics: false,
             report_storage_on_error: false,
+            dump_state_on_failure: false,
+            dump_state_size_limit: DEFAULT_STATE_DUMP_SIZE_LIMIT,
+            dump_state_dir: None,
             report_stacktrace_on_abort: false,
             ignore_compile_warnings: false,
             source_files: vec![],
@@ -142,9 +188,12 @@ impl UnitTestingConfig {
             verbose: false,
             list: false,
             named_address_values: vec![],
+            initial_time: 0,
+            initial_block_height: 0,
 
             #[cfg(feature = "evm-backend")]
             evm: false,
+            gas_profile_cost_table: None,
         }
     }
 
@@ -216,6 +265,20 @@ impl UnitTestingConfig {
         native_function_table: Option<NativeFunctionTable>,
         writer: W,
     ) -> Result<(W, bool)> {
+        let (writer, test_results) =
+            self.run_and_collect_unit_tests(test_plan, native_function_table, writer)?;
+        let all_tests_passed = test_results.map_or(true, |results| results.all_tests_passed());
+        Ok((writer, all_tests_passed))
+    }
+
+    /// Like `run_and_report_unit_tests`, but also returns the results of the individual tests.
+    /// Returns `None` if the tests were only listed.
+    pub fn run_and_collect_unit_tests<W: Write + Send>(
+        &self,
+        test_plan: TestPlan,
+        native_function_table: Option<NativeFunctionTable>,
+        writer: W,
+    ) -> Result<(W, Option<TestResults>)> {
         let shared_writer = Mutex::new(writer);
 
         if self.list {
@@ -229,7 +292,7 @@ impl UnitTestingConfig {
                     )?;
                 }
             }
-            return Ok((shared_writer.into_inner().unwrap(), true));
+            return Ok((shared_writer.into_inner().unwrap(), None));
         }
 
         writeln!(shared_writer.lock().unwrap(), "Running Move unit tests")?;
@@ -240,10 +303,17 @@ impl UnitTestingConfig {
             self.check_stackless_vm,
             self.verbose,
             self.report_storage_on_error,
+            self.dump_state_on_failure
+                .then(|| self.dump_state_size_limit),
             self.report_stacktrace_on_abort,
             test_plan,
             native_function_table,
             verify_and_create_named_address_mapping(self.named_address_values.clone()).unwrap(),
+            NativeClockContext {
+                now_microseconds: self.initial_time,
+                block_height: self.initial_block_height,
+            },
+            self.gas_profile_cost_table.clone(),
             #[cfg(feature = "evm-backend")]
             self.evm,
         )
@@ -253,14 +323,32 @@ impl UnitTestingConfig {
             test_runner.filter(filter_str)
         }
 
-        let test_results = test_runner.run(&shared_writer).unwrap();
+        let mut test_results = test_runner.run(&shared_writer).unwrap();
+        if let Some(dump_dir) = &self.dump_state_dir {
+            if dump_dir.exists() {
+                fs::remove_dir_all(dump_dir)?;
+            }
+            for (test_name, state_dump) in test_results.take_state_dumps() {
+                let dump_path = dump_dir
+                    .join(test_name.replace("::", "."))
+                    .join("state.json");
+                fs::create_dir_all(dump_path.parent().unwrap())?;
+                fs::write(&dump_path, state_dump)?;
+                writeln!(
+                    shared_writer.lock().unwrap(),
+                    "Wrote the state of {} at its failure to {}",
+                    test_name,
+                    dump_path.display()
+                )?;
+            }
+        }
         if self.report_statistics {
             test_results.report_statistics(&shared_writer)?;
         }
 
-        let all_tests_passed = test_results.summarize(&shared_writer)?;
+        test_results.summarize(&shared_writer)?;
 
         let writer = shared_writer.into_inner().unwrap();
-        Ok((writer, all_tests_passed))
+        Ok((writer, Some(test_results)))
     }
 }
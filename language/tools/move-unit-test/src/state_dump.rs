@@ -0,0 +1,175 @@
+// Copyright (c) The Move Contributors
+// SPDX-License-Identifier: Apache-2.0
+
+//! Dumps of the global storage and events of failing tests, written with `--dump-state-on-failure`
+
+use anyhow::Result;
+use move_command_line_common::value_rendering::{redacted, value_rendering};
+use move_core_types::effects::{ChangeSet, Event, Op};
+use move_resource_viewer::MoveValueAnnotator;
+use move_vm_test_utils::InMemoryStorage;
+use serde::Serialize;
+use serde_json::Value;
+
+/// The default bound on the size of a state dump, in bytes
+pub const DEFAULT_STATE_DUMP_SIZE_LIMIT: usize = 1 << 20;
+
+/// Vectors with more elements are summarized by their first `MAX_VECTOR_ELEMENTS` elements
+const MAX_VECTOR_ELEMENTS: usize = 32;
+
+/// Byte strings (`vector<u8>`) longer than this are summarized by their first `MAX_BYTES_LEN`
+/// characters
+const MAX_BYTES_LEN: usize = 256;
+
+/// The global storage of a test at the point it failed, along with the events it emitted
+#[derive(Debug, Serialize)]
+pub struct StateDump {
+    pub resources: Vec<ResourceDump>,
+    pub events: Vec<EventDump>,
+    /// The number of resources and events left out to keep the dump within its size limit
+    pub omitted: usize,
+}
+
+#[derive(Debug, Serialize)]
+pub struct ResourceDump {
+    pub address: String,
+    #[serde(rename = "type")]
+    pub type_: String,
+    pub value: Value,
+    /// Whether long vectors or byte strings of the value were cut
+    pub truncated: bool,
+}
+
+#[derive(Debug, Serialize)]
+pub struct EventDump {
+    /// The event handle the event was emitted to, in hex
+    pub key: String,
+    pub sequence_number: u64,
+    #[serde(rename = "type")]
+    pub type_: String,
+    pub data: Value,
+    /// Whether long vectors or byte strings of the data were cut
+    pub truncated: bool,
+}
+
+impl StateDump {
+    /// Dump the resources written by `change_set` on top of `storage`, and the `events` emitted,
+    /// decoded with the layouts of the modules in `storage`. Entries are left out once the dump
+    /// would exceed `size_limit` bytes. Values are replaced with their types if the values of
+    /// diagnostics are redacted.
+    pub fn new(
+        change_set: &ChangeSet,
+        events: &[Event],
+        storage: &InMemoryStorage,
+        size_limit: usize,
+    ) -> Result<Self> {
+        let annotator = MoveValueAnnotator::new(storage);
+        let redact = value_rendering().redact;
+        let mut dump = StateDump {
+            resources: vec![],
+            events: vec![],
+            omitted: 0,
+        };
+        let mut size = 0;
+        let mut fits = |entry_size: usize| {
+            size += entry_size;
+            size <= size_limit
+        };
+
+        for (address, account) in change_set.accounts() {
+            for (tag, op) in account.resources() {
+                // resources deleted by the test are no longer in storage
+                let blob = match op {
+                    Op::New(blob) | Op::Modify(blob) => blob,
+                    Op::Delete => continue,
+                };
+                let mut truncated = false;
+                let value = if redact {
+                    Value::String(redacted(tag))
+                } else {
+                    summarize(
+                        serde_json::to_value(annotator.view_resource(tag, blob)?)?,
+                        &mut truncated,
+                    )
+                };
+                let resource = ResourceDump {
+                    address: format!("0x{}", address.short_str_lossless()),
+                    type_: tag.to_string(),
+                    value,
+                    truncated,
+                };
+                if dump.omitted == 0 && fits(serde_json::to_string(&resource)?.len()) {
+                    dump.resources.push(resource)
+                } else {
+                    dump.omitted += 1
+                }
+            }
+        }
+
+        for (key, sequence_number, type_, data) in events {
+            let mut truncated = false;
+            let data = if redact {
+                Value::String(redacted(type_))
+            } else {
+                summarize(
+                    serde_json::to_value(annotator.view_value(type_, data)?)?,
+                    &mut truncated,
+                )
+            };
+            let event = EventDump {
+                key: hex::encode(key),
+                sequence_number: *sequence_number,
+                type_: type_.to_string(),
+                data,
+                truncated,
+            };
+            if dump.omitted == 0 && fits(serde_json::to_string(&event)?.len()) {
+                dump.events.push(event)
+            } else {
+                dump.omitted += 1
+            }
+        }
+        Ok(dump)
+    }
+
+    pub fn to_json(&self) -> Result<String> {
+        Ok(serde_json::to_string_pretty(self)?)
+    }
+}
+
+/// Replace the tails of long vectors and byte strings in `value` with a note of how much was left
+/// out, setting `truncated` if anything was
+fn summarize(value: Value, truncated: &mut bool) -> Value {
+    match value {
+        Value::Array(elements) if elements.len() > MAX_VECTOR_ELEMENTS => {
+            *truncated = true;
+            let omitted = elements.len() - MAX_VECTOR_ELEMENTS;
+            let mut elements: Vec<_> = elements
+                .into_iter()
+                .take(MAX_VECTOR_ELEMENTS)
+                .map(|element| summarize(element, truncated))
+                .collect();
+            elements.push(Value::String(format!("... {} more elements", omitted)));
+            Value::Array(elements)
+        }
+        Value::Array(elements) => Value::Array(
+            elements
+                .into_iter()
+                .map(|element| summarize(element, truncated))
+                .collect(),
+        ),
+        Value::Object(fields) => Value::Object(
+            fields
+                .into_iter()
+                .map(|(name, value)| (name, summarize(value, truncated)))
+                .collect(),
+        ),
+        Value::String(s) if s.chars().count() > MAX_BYTES_LEN => {
+            *truncated = true;
+            let omitted = s.chars().count() - MAX_BYTES_LEN;
+            let summary: String = s.chars().take(MAX_BYTES_LEN).collect();
+            Value::String(format!("{}... {} more characters", summary, omitted))
+        }
+        value => value,
+    }
+}
@@ -2,7 +2,7 @@
 // Copyright (c) The Move Contributors
 // SPDX-License-Identifier: Apache-2.0
 
-use crate::format_module_id;
+use crate::{format_module_id, gas_profile::GasProfile};
 use codespan_reporting::files::{Files, SimpleFiles};
 use colored::{control, Colorize};
 use move_binary_format::{
@@ -57,6 +57,8 @@ pub struct TestFailure {
     pub vm_error: Option<VMError>,
     pub failure_reason: FailureReason,
     pub storage_state: Option<String>,
+    /// The global storage and events of the test at the point of failure, as JSON
+    pub state_dump: Option<String>,
 }
 
 #[derive(Debug, Clone, Ord, PartialOrd, PartialEq, Eq)]
@@ -64,6 +66,8 @@ pub struct TestRunInfo {
     pub function_ident: String,
     pub elapsed_time: Duration,
     pub instructions_executed: u64,
+    /// What the test cost, if gas is profiled
+    pub gas_profile: Option<GasProfile>,
 }
 
 #[derive(Debug, Clone)]
@@ -84,6 +88,14 @@ impl TestRunInfo {
             function_ident,
             elapsed_time,
             instructions_executed,
+            gas_profile: None,
+        }
+    }
+
+    pub fn with_gas_profile(self, gas_profile: Option<GasProfile>) -> Self {
+        Self {
+            gas_profile,
+            ..self
         }
     }
 }
@@ -149,9 +161,14 @@ impl TestFailure {
             vm_error,
             failure_reason,
             storage_state,
+            state_dump: None,
         }
     }
 
+    pub fn with_state_dump(self, state_dump: Option<String>) -> Self {
+        Self { state_dump, ..self }
+    }
+
     pub fn render_error(&self, test_plan: &TestPlan) -> String {
         let error_string = match &self.failure_reason {
             FailureReason::NoAbort(message) => message.to_string(),
@@ -212,7 +229,7 @@ impl TestFailure {
             }
         };
 
-        match &self.storage_state {
+        let error_string = match &self.storage_state {
             None => error_string,
             Some(storage_state) => {
                 format!(
@@ -225,6 +242,14 @@ impl TestFailure {
                     }
                 )
             }
+        };
+
+        match &self.state_dump {
+            None => error_string,
+            Some(state_dump) => format!(
+                "{}\n────── State dump at point of failure ──────\n{}",
+                error_string, state_dump
+            ),
         }
     }
 
@@ -407,6 +432,51 @@ impl TestResults {
         }
     }
 
+    /// The test plan the tests were run from
+    pub fn test_plan(&self) -> &TestPlan {
+        &self.test_plan
+    }
+
+    /// The tests that passed, grouped by module
+    pub fn passed_tests(&self) -> &BTreeMap<ModuleId, BTreeSet<TestRunInfo>> {
+        &self.final_statistics.passed
+    }
+
+    /// The tests that failed, grouped by module
+    pub fn failed_tests(&self) -> &BTreeMap<ModuleId, BTreeSet<TestFailure>> {
+        &self.final_statistics.failed
+    }
+
+    /// Take the state dumps out of the failed tests, so that they are left out of the test report.
+    /// Returns the fully qualified name of each test along with its dump.
+    pub fn take_state_dumps(&mut self) -> Vec<(String, String)> {
+        let mut state_dumps = vec![];
+        for (module_id, failures) in self.final_statistics.failed.iter_mut() {
+            *failures = std::mem::take(failures)
+                .into_iter()
+                .map(|mut failure| {
+                    if let Some(state_dump) = failure.state_dump.take() {
+                        state_dumps.push((
+                            format!(
+                                "{}::{}",
+                                format_module_id(module_id),
+                                failure.test_run_info.function_ident
+                            ),
+                            state_dump,
+                        ))
+                    }
+                    failure
+                })
+                .collect();
+        }
+        state_dumps
+    }
+
+    /// Returns `true` if no test failed
+    pub fn all_tests_passed(&self) -> bool {
+        self.final_statistics.failed.is_empty()
+    }
+
     pub fn report_statistics<W: Write>(&self, writer: &Mutex<W>) -> Result<()> {
         writeln!(writer.lock().unwrap(), "\nTest Statistics:\n")?;
 
@@ -498,7 +568,7 @@ impl TestResults {
     }
 
     /// Returns `true` if all tests passed, `false` if there was a test failure/timeout
-    pub fn summarize<W: Write>(self, writer: &Mutex<W>) -> Result<bool> {
+    pub fn summarize<W: Write>(&self, writer: &Mutex<W>) -> Result<bool> {
         let num_failed_tests = self
             .final_statistics
             .failed
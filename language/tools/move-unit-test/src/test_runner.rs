@@ -4,6 +4,8 @@
 
 use crate::{
     extensions, format_module_id,
+    gas_profile::ProfilingGasMeter,
+    state_dump::StateDump,
     test_reporter::{FailureReason, TestFailure, TestResults, TestRunInfo, TestStatistics},
 };
 use anyhow::Result;
@@ -11,13 +13,14 @@ use colored::*;
 
 use move_binary_format::{errors::VMResult, file_format::CompiledModule};
 use move_bytecode_utils::Modules;
+use move_command_line_common::value_rendering::render_value;
 use move_compiler::{
     shared::{Flags, NumericalAddress, PackagePaths},
     unit_test::{ExpectedFailure, ModuleTestPlan, TestCase, TestPlan},
 };
 use move_core_types::{
     account_address::AccountAddress,
-    effects::{ChangeSet, Op},
+    effects::{ChangeSet, Event, Op},
     identifier::IdentStr,
     value::serialize_values,
     vm_status::StatusCode,
@@ -32,6 +35,7 @@ use move_stackless_bytecode_interpreter::{
     shared::bridge::{adapt_move_vm_change_set, adapt_move_vm_result},
     StacklessBytecodeInterpreter,
 };
+use move_stdlib::natives::timestamp::NativeClockContext;
 use move_vm_runtime::{move_vm::MoveVM, native_functions::NativeFunctionTable};
 use move_vm_test_utils::{
     gas_schedule::{zero_cost_schedule, CostTable, Gas, GasCost, GasStatus},
@@ -54,6 +58,8 @@ use {
 /// Test state common to all tests
 pub struct SharedTestingConfig {
     save_storage_state_on_failure: bool,
+    /// Dump the state of failing tests, bounded to this many bytes
+    state_dump_size_limit: Option<usize>,
     report_stacktrace_on_abort: bool,
     execution_bound: u64,
     cost_table: CostTable,
@@ -63,6 +69,10 @@ pub struct SharedTestingConfig {
     named_address_values: BTreeMap<String, NumericalAddress>,
     check_stackless_vm: bool,
     verbose: bool,
+    /// The clock each test starts with
+    clock: NativeClockContext,
+    /// Whether to record the gas profile of each test
+    profile_gas: bool,
 
     #[cfg(feature = "evm-backend")]
     evm: bool,
@@ -118,10 +128,11 @@ fn print_resources_and_extensions(
 
         for (tag, resource_op) in account_state.resources() {
             if let Op::New(resource) | Op::Modify(resource) = resource_op {
+                let value = render_value(annotator.view_resource(tag, resource)?, tag);
                 writeln!(
                     &mut buf,
                     "\t{}",
-                    format!("=> {}", annotator.view_resource(tag, resource)?).replace('\n', "\n\t")
+                    format!("=> {}", value).replace('\n', "\n\t")
                 )?;
             }
         }
@@ -138,12 +149,15 @@ impl TestRunner {
         check_stackless_vm: bool,
         verbose: bool,
         save_storage_state_on_failure: bool,
+        state_dump_size_limit: Option<usize>,
         report_stacktrace_on_abort: bool,
         tests: TestPlan,
         // TODO: maybe we should require the clients to always pass in a list of native functions so
         // we don't have to make assumptions about their gas parameters.
         native_function_table: Option<NativeFunctionTable>,
         named_address_values: BTreeMap<String, NumericalAddress>,
+        clock: NativeClockContext,
+        gas_profile_cost_table: Option<CostTable>,
         #[cfg(feature = "evm-backend")] evm: bool,
     ) -> Result<Self> {
         let source_files = tests
@@ -162,6 +176,7 @@ impl TestRunner {
         Ok(Self {
             testing_config: SharedTestingConfig {
                 save_storage_state_on_failure,
+                state_dump_size_limit,
                 report_stacktrace_on_abort,
                 starting_storage_state,
                 execution_bound,
@@ -171,11 +186,13 @@ impl TestRunner {
                 // after executing a certain number of instructions or setting a timer.
                 //
                 // From the API standpoint, we should let the client specify the cost table.
-                cost_table: unit_cost_table(),
+                profile_gas: gas_profile_cost_table.is_some(),
+                cost_table: gas_profile_cost_table.unwrap_or_else(unit_cost_table),
                 source_files,
                 check_stackless_vm,
                 verbose,
                 named_address_values,
+                clock,
                 #[cfg(feature = "evm-backend")]
                 evm,
             },
@@ -272,12 +289,16 @@ impl SharedTestingConfig {
         VMResult<NativeContextExtensions>,
         VMResult<Vec<Vec<u8>>>,
         TestRunInfo,
+        Vec<Event>,
     ) {
         let move_vm = MoveVM::new(self.native_function_table.clone()).unwrap();
-        let extensions = extensions::new_extensions();
+        let extensions = extensions::new_extensions(self.clock.clone());
         let mut session =
             move_vm.new_session_with_extensions(&self.starting_storage_state, extensions);
-        let mut gas_meter = GasStatus::new(&self.cost_table, Gas::new(self.execution_bound));
+        let mut gas_meter = ProfilingGasMeter::new(GasStatus::new(
+            &self.cost_table,
+            Gas::new(self.execution_bound),
+        ));
         // TODO: collect VM logs if the verbose flag (i.e, `self.verbose`) is set
 
         let now = Instant::now();
@@ -305,13 +326,22 @@ impl SharedTestingConfig {
             // TODO(Gas): This doesn't look quite right...
             //            We're not computing the number of instructions executed even with a unit gas schedule.
             Gas::new(self.execution_bound)
-                .checked_sub(gas_meter.remaining_gas())
+                .checked_sub(gas_meter.gas_status().remaining_gas())
                 .unwrap()
                 .into(),
-        );
+        )
+        .with_gas_profile(self.profile_gas.then(|| gas_meter.profile()));
         match session.finish_with_extensions() {
-            Ok((cs, _, extensions)) => (Ok(cs), Ok(extensions), return_result, test_run_info),
-            Err(err) => (Err(err.clone()), Err(err), return_result, test_run_info),
+            Ok((cs, events, extensions)) => {
+                (Ok(cs), Ok(extensions), return_result, test_run_info, events)
+            }
+            Err(err) => (
+                Err(err.clone()),
+                Err(err),
+                return_result,
+                test_run_info,
+                vec![],
+            ),
         }
     }
 
@@ -404,7 +434,7 @@ impl SharedTestingConfig {
         let mut stats = TestStatistics::new();
 
         for (function_name, test_info) in &test_plan.tests {
-            let (cs_result, ext_result, exec_result, test_run_info) =
+            let (cs_result, ext_result, exec_result, test_run_info, events) =
                 self.execute_via_move_vm(test_plan, function_name, test_info);
             if self.check_stackless_vm {
                 let (stackless_vm_change_set, stackless_vm_result, _, prop_check_result) = self
@@ -454,10 +484,10 @@ impl SharedTestingConfig {
 
             let save_session_state = || {
                 if self.save_storage_state_on_failure {
-                    cs_result.ok().and_then(|changeset| {
+                    cs_result.as_ref().ok().and_then(|changeset| {
                         ext_result.ok().and_then(|extensions| {
                             print_resources_and_extensions(
-                                &changeset,
+                                changeset,
                                 extensions,
                                 &self.starting_storage_state,
                             )
@@ -468,6 +498,13 @@ impl SharedTestingConfig {
                     None
                 }
             };
+            let dump_session_state = || {
+                let size_limit = self.state_dump_size_limit?;
+                let changeset = cs_result.as_ref().ok()?;
+                StateDump::new(changeset, &events, &self.starting_storage_state, size_limit)
+                    .and_then(|state_dump| state_dump.to_json())
+                    .ok()
+            };
             match exec_result {
                 Err(err) => match (test_info.expected_failure.as_ref(), err.sub_status()) {
                     // Ran out of ticks, report a test timeout and log a test failure
@@ -479,7 +516,8 @@ impl SharedTestingConfig {
                                 test_run_info,
                                 Some(err),
                                 save_session_state(),
-                            ),
+                            )
+                            .with_state_dump(dump_session_state()),
                             test_plan,
                         )
                     }
@@ -492,7 +530,8 @@ impl SharedTestingConfig {
                                 test_run_info,
                                 Some(err),
                                 save_session_state(),
-                            ),
+                            )
+                            .with_state_dump(dump_session_state()),
                             test_plan,
                         )
                     }
@@ -517,7 +556,8 @@ impl SharedTestingConfig {
                                 test_run_info,
                                 Some(err),
                                 save_session_state(),
-                            ),
+                            )
+                            .with_state_dump(dump_session_state()),
                             test_plan,
                         )
                     }
@@ -542,7 +582,8 @@ impl SharedTestingConfig {
                                 test_run_info,
                                 Some(err),
                                 save_session_state(),
-                            ),
+                            )
+                            .with_state_dump(dump_session_state()),
                             test_plan,
                         )
                     }
@@ -557,7 +598,8 @@ impl SharedTestingConfig {
                                 test_run_info,
                                 None,
                                 save_session_state(),
-                            ),
+                            )
+                            .with_state_dump(dump_session_state()),
                             test_plan,
                         )
                     } else {